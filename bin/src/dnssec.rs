@@ -17,7 +17,11 @@ use tracing::info;
 
 use hickory_proto::rr::domain::Name;
 use hickory_proto::{
-    dnssec::{rdata::key::KeyUsage, rdata::DNSKEY, rdata::KEY, Algorithm, SigSigner, SigningKey},
+    dnssec::{
+        rdata::{key::KeyUsage, tsig::TsigAlgorithm, DNSKEY, KEY},
+        tsig::TSigner,
+        Algorithm, SigSigner, SigningKey,
+    },
     rr::domain::IntoName,
     ProtoError,
 };
@@ -54,7 +58,7 @@ impl KeyConfig {
     /// for custom keys can be found elsewhere.
     ///
     /// the currently supported set of supported_algorithms are
-    /// ["RSASHA256", "RSASHA512", "ECDSAP256SHA256", "ECDSAP384SHA384", "ED25519"]
+    /// ["RSASHA256", "RSASHA512", "ECDSAP256SHA256", "ECDSAP384SHA384", "ED25519", "ED448"]
     ///
     /// keys are listed in pairs of key_name and algorithm, the search path is the
     /// same directory has the zone $file:
@@ -150,6 +154,42 @@ pub enum KeyPurpose {
     ZoneUpdateAuth,
 }
 
+/// Shared secret for TSIG authentication of dynamic updates and zone transfers of a zone
+#[derive(Deserialize, PartialEq, Eq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TsigKeyConfig {
+    /// name of the key, must be the same as the one used by the clients
+    pub name: String,
+    /// file path to the shared secret, stored as raw bytes
+    pub key_path: PathBuf,
+    /// the MAC algorithm, e.g. hmac-sha256 (the default), hmac-sha384 or hmac-sha512
+    pub algorithm: Option<String>,
+    /// maximum difference in seconds between the time of signing and receiving, 300 by default
+    pub fudge: Option<u16>,
+}
+
+impl TsigKeyConfig {
+    /// Reads the shared secret, and constructs the signer for the key
+    pub fn try_into_signer(&self) -> Result<TSigner, String> {
+        let name = Name::parse(&self.name, Some(&Name::root()))
+            .map_err(|e| format!("error parsing TSIG key name: {e}"))?;
+        let algorithm = match &self.algorithm {
+            Some(algorithm) => TsigAlgorithm::from_name(
+                Name::from_ascii(algorithm)
+                    .map_err(|e| format!("error parsing TSIG algorithm: {e}"))?,
+            ),
+            None => TsigAlgorithm::HmacSha256,
+        };
+
+        info!("reading TSIG key: {:?}", self.key_path);
+        let key = std::fs::read(&self.key_path)
+            .map_err(|e| format!("could not read TSIG key from: {:?}: {e}", self.key_path))?;
+
+        TSigner::new(key, algorithm, name, self.fudge.unwrap_or(300))
+            .map_err(|e| format!("failed to load TSIG key {}: {e}", self.name))
+    }
+}
+
 pub fn key_from_file(path: &Path, algorithm: Algorithm) -> Result<Box<dyn SigningKey>, String> {
     use std::fs::File;
     use std::io::Read;
@@ -36,21 +36,28 @@
 #![recursion_limit = "128"]
 #![allow(clippy::redundant_clone)]
 
+#[cfg(unix)]
+use std::{collections::HashMap, time::SystemTime};
 use std::{
-    env, fmt,
+    env, fmt, fs,
     io::Error,
+    iter,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::Duration,
 };
 
 use clap::Parser;
 use socket2::{Domain, Socket, Type};
 use time::OffsetDateTime;
+#[cfg(unix)]
+use tokio::signal;
 use tokio::{
     net::{TcpListener, UdpSocket},
     runtime,
 };
-use tracing::{error, info, warn, Event, Subscriber};
+use tracing::{debug, error, info, warn, Event, Subscriber};
 use tracing_subscriber::{
     fmt::{format, FmtContext, FormatEvent, FormatFields, FormattedFields},
     layer::SubscriberExt,
@@ -58,10 +65,24 @@ use tracing_subscriber::{
     util::SubscriberInitExt,
 };
 
-use hickory_dns::Config;
 #[cfg(feature = "dns-over-tls")]
 use hickory_dns::TlsCertConfig;
-use hickory_server::{authority::Catalog, server::ServerFuture};
+use hickory_dns::{Config, ZoneConfig};
+use hickory_server::{
+    authority::{
+        AuthorityObject, Catalog, CatalogMember, CatalogZone, CatalogZoneConfig, CatalogZones,
+        ZoneType,
+    },
+    proto::rr::{LowerName, Name},
+    server::{
+        MiddlewareChain, QueryLog, Request, RequestHandler, ResponseHandler, ResponseInfo,
+        ServerFuture, ServerMetrics, View, Views,
+    },
+    store::{in_memory::InMemoryAuthority, secondary::SecondaryAuthority},
+};
+
+/// The interval at which the catalog zones are checked for changes
+const CATALOG_ZONE_INTERVAL: Duration = Duration::from_secs(5);
 
 /// Cli struct for all options managed with clap derive api.
 #[derive(Debug, Parser)]
@@ -200,16 +221,42 @@ fn run() -> Result<(), String> {
         .build()
         .map_err(|err| format!("failed to initialize Tokio runtime: {err}"))?;
 
-    let mut catalog: Catalog = Catalog::new();
+    let access_lists = config.access_lists();
+    let mut views = Views::default();
+    // the modification times of the zone files of each view, to reload the changed ones
+    #[cfg(unix)]
+    let mut modified = HashMap::new();
     // configure our server based on the config_path
-    for zone in config.zones() {
-        let zone_name = zone
-            .zone()
-            .map_err(|err| format!("failed to read zone name from {config_path:?}: {err}"))?;
-
-        match runtime.block_on(zone.load(&zone_dir)) {
-            Ok(authority) => catalog.upsert(zone_name.into(), authority),
-            Err(err) => return Err(format!("could not load zone {zone_name}: {err}")),
+    for (view, zones) in zone_sets(&config) {
+        let mut catalog: Catalog = Catalog::new();
+        catalog.set_access_lists(access_lists.clone());
+        catalog.set_refuse_any(config.refuse_any());
+        for zone in zones {
+            let zone_name = zone
+                .zone()
+                .map_err(|err| format!("failed to read zone name from {config_path:?}: {err}"))?;
+
+            #[cfg(unix)]
+            if let Some((path, time)) = zone_file_modified(zone, &zone_dir) {
+                modified.insert((view, path), time);
+            }
+
+            match runtime.block_on(zone.load(&zone_dir)) {
+                Ok(loaded) => loaded.insert_into(&zone_name, &mut catalog),
+                Err(err) => return Err(format!("could not load zone {zone_name}: {err}")),
+            }
+        }
+
+        match view.map(|index| &config.views()[index]) {
+            Some(view) => {
+                info!("serving view {} to {:?}", view.name, view.match_clients);
+                views.push(View::new(
+                    view.name.clone(),
+                    view.match_clients.clone(),
+                    catalog,
+                ));
+            }
+            None => *views.default_catalog_mut() = catalog,
         }
     }
 
@@ -242,16 +289,69 @@ fn run() -> Result<(), String> {
     let tcp_request_timeout = config.tcp_request_timeout();
 
     // now, run the server, based on the config
-    #[cfg_attr(not(feature = "dns-over-tls"), allow(unused_mut))]
-    let mut server = ServerFuture::with_access(catalog, deny_networks, allow_networks);
+    let views = ReloadableViews::new(views);
+    for (view, zones) in zone_sets(&config) {
+        let catalog_zones = zones
+            .iter()
+            .filter_map(|zone| Some((zone.zone().ok()?, zone.catalog()?.clone())))
+            .collect::<Vec<_>>();
+        if !catalog_zones.is_empty() {
+            runtime.spawn(consume_catalog_zones(views.clone(), view, catalog_zones));
+        }
+    }
+
+    let mut handler = MiddlewareChain::new(views.clone());
+    if let Some(query_log) = config.query_log() {
+        info!("writing the query log to {query_log:?}");
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(query_log)
+            .map_err(|err| format!("failed to open the query log {query_log:?}: {err}"))?;
+        let query_log = QueryLog::new(file)
+            .map_err(|err| format!("failed to start the query log writer: {err}"))?;
+        handler.push(query_log);
+    }
+
+    let metrics = config.metrics_listen_addr().map(|_| ServerMetrics::new());
+    if let Some(metrics) = &metrics {
+        handler.push(metrics.clone());
+    }
+
+    let mut server = ServerFuture::with_access(handler, deny_networks, allow_networks);
+    if let Some(metrics) = &metrics {
+        server.set_metrics(metrics.clone());
+    }
+    if let Some(response_rate_limit) = config.response_rate_limit() {
+        info!("enabling response rate limiting: {response_rate_limit:?}");
+        server.set_response_rate_limit(response_rate_limit);
+    }
 
     let _guard = runtime.enter();
 
     if !args.disable_udp && !config.disable_udp() {
+        let udp_sockets = config.udp_sockets();
+        #[cfg(not(all(unix, not(any(target_os = "solaris", target_os = "illumos")))))]
+        if udp_sockets > 1 {
+            warn!("SO_REUSEPORT is not supported, binding a single UDP socket to each address");
+        }
+
         // load all udp listeners
         for addr in &listen_addrs {
             info!("binding UDP to {addr:?}");
 
+            #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+            if udp_sockets > 1 {
+                let local_addr = server
+                    .register_reuse_port_sockets(SocketAddr::new(*addr, listen_port), udp_sockets)
+                    .map_err(|err| {
+                        format!("failed to bind to UDP socket address {addr:?}: {err}")
+                    })?;
+
+                info!("listening for UDP on {local_addr:?} with {udp_sockets} sockets");
+                continue;
+            }
+
             let udp_socket = build_udp_socket(*addr, listen_port)
                 .map_err(|err| format!("failed to bind to UDP socket address {addr:?}: {err}"))?;
 
@@ -344,6 +444,19 @@ fn run() -> Result<(), String> {
         info!("TLS related protocols (TLS, HTTPS and QUIC) are disabled")
     }
 
+    if let (Some(addr), Some(metrics)) = (config.metrics_listen_addr(), metrics) {
+        info!("binding metrics to {addr:?}");
+        let listener = build_tcp_listener(addr.ip(), addr.port())
+            .map_err(|err| format!("failed to bind to metrics address {addr:?}: {err}"))?;
+        info!(
+            "serving metrics on {:?}",
+            listener
+                .local_addr()
+                .map_err(|err| format!("failed to lookup local address: {err}"))?
+        );
+        server.register_metrics_listener(listener, metrics);
+    }
+
     // Drop privileges on Unix systems if running as root.
     #[cfg(target_family = "unix")]
     check_drop_privs(
@@ -355,6 +468,19 @@ fn run() -> Result<(), String> {
         return Err("dropping privileges is only supported on Unix systems".to_string());
     }
 
+    // the changed zone files are reloaded on SIGHUP
+    #[cfg(unix)]
+    {
+        let mut hangups = signal::unix::signal(signal::unix::SignalKind::hangup())
+            .map_err(|err| format!("failed to listen for SIGHUP: {err}"))?;
+        runtime.spawn(async move {
+            while hangups.recv().await.is_some() {
+                info!("SIGHUP received, reloading changed zones");
+                reload_zones(&views, &config, &zone_dir, &mut modified).await;
+            }
+        });
+    }
+
     // config complete, starting!
     banner();
 
@@ -385,7 +511,7 @@ fn run() -> Result<(), String> {
 #[cfg(feature = "dns-over-tls")]
 fn config_tls(
     args: &Cli,
-    server: &mut ServerFuture<Catalog>,
+    server: &mut ServerFuture<MiddlewareChain<ReloadableViews>>,
     config: &Config,
     tls_cert_config: &TlsCertConfig,
     zone_dir: &Path,
@@ -428,7 +554,7 @@ fn config_tls(
 #[cfg(feature = "dns-over-https-rustls")]
 fn config_https(
     args: &Cli,
-    server: &mut ServerFuture<Catalog>,
+    server: &mut ServerFuture<MiddlewareChain<ReloadableViews>>,
     config: &Config,
     tls_cert_config: &TlsCertConfig,
     zone_dir: &Path,
@@ -485,7 +611,7 @@ fn config_https(
 #[cfg(feature = "dns-over-quic")]
 fn config_quic(
     args: &Cli,
-    server: &mut ServerFuture<Catalog>,
+    server: &mut ServerFuture<MiddlewareChain<ReloadableViews>>,
     config: &Config,
     tls_cert_config: &TlsCertConfig,
     zone_dir: &Path,
@@ -534,6 +660,215 @@ fn config_quic(
     Ok(())
 }
 
+/// The views of the server, of which the zones can be replaced while it is running
+///
+/// Requests being processed keep the version of the views they started with.
+#[derive(Clone)]
+struct ReloadableViews(Arc<RwLock<Arc<Views>>>);
+
+impl ReloadableViews {
+    fn new(views: Views) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(views))))
+    }
+
+    fn get(&self) -> Arc<Views> {
+        self.0.read().expect("views lock poisoned").clone()
+    }
+
+    /// Replaces the views with a copy modified by `update`, the concurrent updates are serialized
+    fn update(&self, update: impl FnOnce(&mut Views)) {
+        let mut views = self.0.write().expect("views lock poisoned");
+        let mut updated = Views::clone(&views);
+        update(&mut updated);
+        *views = Arc::new(updated);
+    }
+}
+
+#[async_trait::async_trait]
+impl RequestHandler for ReloadableViews {
+    async fn handle_request<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        response_handle: R,
+    ) -> ResponseInfo {
+        self.get().handle_request(request, response_handle).await
+    }
+}
+
+/// The zones of the clients matching no view, followed by the zones of each view with its index
+fn zone_sets(config: &Config) -> impl Iterator<Item = (Option<usize>, &[ZoneConfig])> {
+    iter::once((None, config.zones())).chain(
+        config
+            .views()
+            .iter()
+            .enumerate()
+            .map(|(index, view)| (Some(index), &*view.zones)),
+    )
+}
+
+/// The catalog of the view with the index, or the default one
+fn view_catalog(views: &Views, view: Option<usize>) -> &Catalog {
+    match view {
+        Some(index) => views.views()[index].catalog(),
+        None => views.default_catalog(),
+    }
+}
+
+/// The catalog of the view with the index, or the default one
+fn view_catalog_mut(views: &mut Views, view: Option<usize>) -> &mut Catalog {
+    match view {
+        Some(index) => views.views_mut()[index].catalog_mut(),
+        None => views.default_catalog_mut(),
+    }
+}
+
+/// Provisions the member zones of the catalog zones of a view
+///
+/// The catalog zones are read from their authorities every [`CATALOG_ZONE_INTERVAL`], and applied
+/// to the catalog of the view once they changed, e.g. after a transfer or a reload of the zone.
+async fn consume_catalog_zones(
+    views: ReloadableViews,
+    view: Option<usize>,
+    configs: Vec<(Name, CatalogZoneConfig)>,
+) {
+    let mut catalog_zones = CatalogZones::new();
+    let mut interval = tokio::time::interval(CATALOG_ZONE_INTERVAL);
+    loop {
+        interval.tick().await;
+        for (origin, config) in &configs {
+            let authority = view_catalog(&views.get(), view)
+                .find(&LowerName::from(origin))
+                .and_then(|authorities| authorities.first().cloned());
+            let Some(authority) = authority else {
+                continue;
+            };
+
+            let zone = match CatalogZone::from_authority(&*authority).await {
+                Ok(zone) => zone,
+                Err(err) => {
+                    debug!("skipping the catalog zone {origin}: {err}");
+                    continue;
+                }
+            };
+            if catalog_zones.get(origin) == Some(&zone) {
+                continue;
+            }
+
+            info!("updating the members of the catalog zone {origin}");
+            views.update(|views| {
+                let catalog = view_catalog_mut(views, view);
+                let mut zone_refreshes = Vec::new();
+                catalog_zones.update(zone, catalog, |member| {
+                    let authority = provision_member(member, config)?;
+                    zone_refreshes.push((LowerName::from(member.zone()), authority.zone_refresh()));
+                    Some(vec![Arc::new(authority) as Arc<dyn AuthorityObject>])
+                });
+                for (member, zone_refresh) in zone_refreshes {
+                    catalog.set_zone_refresh(member, zone_refresh);
+                }
+            });
+        }
+    }
+}
+
+/// Provisions a member zone of a catalog zone as a secondary zone
+fn provision_member(
+    member: &CatalogMember,
+    config: &CatalogZoneConfig,
+) -> Option<SecondaryAuthority> {
+    let primaries = config.primaries(member)?;
+    let authority = InMemoryAuthority::empty(
+        member.zone().clone(),
+        ZoneType::Secondary,
+        false,
+        #[cfg(feature = "dnssec-ring")]
+        None,
+    );
+
+    Some(SecondaryAuthority::new(authority, primaries.to_vec()))
+}
+
+/// Returns the path and the modification time of the file of a zone served from a file store
+#[cfg(unix)]
+fn zone_file_modified(zone: &ZoneConfig, zone_dir: &Path) -> Option<(PathBuf, SystemTime)> {
+    let path = zone_dir.join(zone.file_store_path()?);
+    match fs::metadata(&path).and_then(|metadata| metadata.modified()) {
+        Ok(time) => Some((path, time)),
+        Err(err) => {
+            warn!("failed to read the modification time of {path:?}: {err}");
+            None
+        }
+    }
+}
+
+/// Reloads the zones of which the file changed since they were loaded
+///
+/// The views are replaced once all the changed zones are loaded, the zones failing to load keep
+/// being served from their previous version. Only the modification of the zone file itself is
+/// detected, not the ones of the files it includes. The secondaries of the reloaded zones are
+/// notified of their new version.
+#[cfg(unix)]
+async fn reload_zones(
+    views: &ReloadableViews,
+    config: &Config,
+    zone_dir: &Path,
+    modified: &mut HashMap<(Option<usize>, PathBuf), SystemTime>,
+) {
+    let zones = zone_sets(config)
+        .flat_map(|(view, zones)| zones.iter().map(move |zone| (view, zone)))
+        .collect::<Vec<_>>();
+    let mut reloaded = Vec::new();
+    for (view, zone) in zones {
+        let Some((path, time)) = zone_file_modified(zone, zone_dir) else {
+            continue;
+        };
+        let key = (view, path);
+        if modified.get(&key) == Some(&time) {
+            continue;
+        }
+
+        let zone_name = match zone.zone() {
+            Ok(zone_name) => zone_name,
+            Err(err) => {
+                error!("failed to read zone name: {err}");
+                continue;
+            }
+        };
+
+        match zone.load(zone_dir).await {
+            Ok(loaded) => {
+                info!("reloaded zone {zone_name} from {:?}", key.1);
+                reloaded.push((view, zone_name, loaded));
+                modified.insert(key, time);
+            }
+            Err(err) => {
+                error!("could not reload zone {zone_name}, keeping the previous version: {err}")
+            }
+        }
+    }
+
+    if reloaded.is_empty() {
+        return;
+    }
+
+    let zone_names = reloaded
+        .iter()
+        .map(|(view, zone_name, _)| (*view, LowerName::from(zone_name)))
+        .collect::<Vec<_>>();
+    views.update(|views| {
+        for (view, zone_name, loaded) in reloaded {
+            loaded.insert_into(&zone_name, view_catalog_mut(views, view));
+        }
+    });
+
+    let views = views.get();
+    for (view, zone_name) in zone_names {
+        view_catalog(&views, view)
+            .notify_secondaries(&zone_name)
+            .await;
+    }
+}
+
 fn banner() {
     #[cfg(feature = "ascii-art")]
     const HICKORY_DNS_LOGO: &str = include_str!("hickory-dns.ascii");
@@ -16,7 +16,7 @@ use std::{
     fmt,
     fs::File,
     io::Read,
-    net::{AddrParseError, Ipv4Addr, Ipv6Addr},
+    net::{AddrParseError, Ipv4Addr, Ipv6Addr, SocketAddr},
     path::{Path, PathBuf},
     str::FromStr,
     sync::Arc,
@@ -32,7 +32,7 @@ use rustls::pki_types::{CertificateDer, PrivateKeyDer};
 use serde::de::{self, MapAccess, SeqAccess, Visitor};
 use serde::{self, Deserialize, Deserializer};
 
-use hickory_proto::rr::Name;
+use hickory_proto::rr::{LowerName, Name};
 use hickory_proto::ProtoError;
 #[cfg(feature = "dnssec-ring")]
 use hickory_server::authority::DnssecAuthority;
@@ -51,11 +51,16 @@ use hickory_server::store::forwarder::ForwardConfig;
 use hickory_server::store::recursor::RecursiveAuthority;
 #[cfg(feature = "recursor")]
 use hickory_server::store::recursor::RecursiveConfig;
+#[cfg(feature = "rpz")]
+use hickory_server::store::rpz::{RpzAuthority, RpzConfig};
 #[cfg(feature = "sqlite")]
 use hickory_server::store::sqlite::{SqliteAuthority, SqliteConfig};
 use hickory_server::{
-    authority::{AuthorityObject, ZoneType},
+    authority::{AuthorityObject, Catalog, CatalogZoneConfig, Notifier, ZoneRefresh, ZoneType},
+    server::{AccessLists, Acl, ResponseRateLimit},
     store::file::FileAuthority,
+    store::in_memory::InMemoryAuthority,
+    store::secondary::{SecondaryAuthority, SecondaryConfig},
     ConfigError,
 };
 use tracing::{debug, info, warn};
@@ -100,6 +105,8 @@ pub struct Config {
     disable_quic: Option<bool>,
     /// Timeout associated to a request before it is closed.
     tcp_request_timeout: Option<u64>,
+    /// Number of UDP sockets bound to each address with `SO_REUSEPORT`, defaults to 1
+    udp_sockets: Option<usize>,
     /// Level at which to log, default is INFO
     log_level: Option<String>,
     /// Base configuration directory, i.e. root path for zones
@@ -118,6 +125,9 @@ pub struct Config {
     #[serde(default)]
     #[serde(deserialize_with = "deserialize_with_file")]
     zones: Vec<ZoneConfig>,
+    /// List of views, serving other zones to the clients of some networks
+    #[serde(default)]
+    views: Vec<ViewConfig>,
     /// Certificate to associate to TLS connections (currently the same is used for HTTPS and TLS)
     #[cfg(feature = "dns-over-rustls")]
     tls_cert: Option<TlsCertConfig>,
@@ -131,6 +141,24 @@ pub struct Config {
     /// Networks allowed to access the server
     #[serde(default)]
     allow_networks: Vec<IpNet>,
+    /// Rate limiting of the UDP responses, disabled by default
+    response_rate_limit: Option<ResponseRateLimit>,
+    /// Answer the ANY queries with a single HINFO record, RFC 8482, enabled by default
+    refuse_any: Option<bool>,
+    /// File to which the answered queries are appended in JSON lines, disabled by default
+    query_log: Option<PathBuf>,
+    /// Address of the HTTP endpoint serving the metrics in the Prometheus format, disabled by
+    /// default
+    metrics_listen_addr: Option<SocketAddr>,
+    /// Clients permitted to query the zones
+    #[serde(default)]
+    allow_query: Acl,
+    /// Clients permitted to query the zones served by forwarders and recursors
+    #[serde(default)]
+    allow_recursion: Acl,
+    /// Clients permitted to transfer the zones
+    #[serde(default)]
+    allow_transfer: Acl,
 }
 
 impl Config {
@@ -215,6 +243,14 @@ impl Config {
         )
     }
 
+    /// number of UDP sockets bound to each listening address, each served by its own task
+    ///
+    /// With more than one socket, the sockets share the port with `SO_REUSEPORT` and the kernel
+    /// spreads the queries across them, only supported on Unix-like platforms.
+    pub fn udp_sockets(&self) -> usize {
+        self.udp_sockets.unwrap_or(1).max(1)
+    }
+
     /// specify the log level which should be used, ["Trace", "Debug", "Info", "Warn", "Error"]
     pub fn log_level(&self) -> tracing::Level {
         if let Some(level_str) = &self.log_level {
@@ -236,6 +272,14 @@ impl Config {
         &self.zones
     }
 
+    /// the views, in the order they are matched against the clients
+    ///
+    /// Clients matching none of the views are served the zones of [`Self::zones`], which are
+    /// usually left empty when views are configured, so that these clients are refused.
+    pub fn views(&self) -> &[ViewConfig] {
+        &self.views
+    }
+
     /// the tls certificate to use for accepting tls connections
     pub fn tls_cert(&self) -> Option<&TlsCertConfig> {
         cfg_if! {
@@ -264,6 +308,49 @@ impl Config {
     pub fn allow_networks(&self) -> &[IpNet] {
         &self.allow_networks
     }
+
+    /// get the response rate limiting of the UDP responses, if enabled
+    pub fn response_rate_limit(&self) -> Option<ResponseRateLimit> {
+        self.response_rate_limit
+    }
+
+    /// whether the ANY queries are answered with the HINFO record of RFC 8482
+    pub fn refuse_any(&self) -> bool {
+        self.refuse_any.unwrap_or(true)
+    }
+
+    /// get the file of the query log, if enabled
+    pub fn query_log(&self) -> Option<&Path> {
+        self.query_log.as_deref()
+    }
+
+    /// get the address of the metrics endpoint, if enabled
+    pub fn metrics_listen_addr(&self) -> Option<SocketAddr> {
+        self.metrics_listen_addr
+    }
+
+    /// get the access control lists of the queries, recursion and zone transfers
+    pub fn access_lists(&self) -> AccessLists {
+        AccessLists {
+            allow_query: self.allow_query.clone(),
+            allow_recursion: self.allow_recursion.clone(),
+            allow_transfer: self.allow_transfer.clone(),
+        }
+    }
+}
+
+/// Configuration for a view, the zones served to the clients of a set of networks
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ViewConfig {
+    /// Name of the view, for logging
+    pub name: String,
+    /// Networks of the clients the view is served to
+    pub match_clients: Vec<IpNet>,
+    /// List of configurations for the zones of the view
+    #[serde(default)]
+    #[serde(deserialize_with = "deserialize_with_file")]
+    pub zones: Vec<ZoneConfig>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -329,7 +416,7 @@ pub struct ZoneConfig {
 
 impl ZoneConfig {
     #[warn(clippy::wildcard_enum_match_arm)] // make sure all cases are handled despite of non_exhaustive
-    pub async fn load(&self, zone_dir: &Path) -> Result<Vec<Arc<dyn AuthorityObject>>, String> {
+    pub async fn load(&self, zone_dir: &Path) -> Result<LoadedZone, String> {
         debug!("loading zone with config: {self:#?}");
 
         let zone_name = self
@@ -340,6 +427,7 @@ impl ZoneConfig {
         // load the zone and insert any configured authorities in the catalog.
 
         let mut authorities: Vec<Arc<dyn AuthorityObject>> = vec![];
+        let mut zone_refresh = None;
 
         #[cfg(feature = "blocklist")]
         let handle_blocklist_store = |config| {
@@ -370,7 +458,6 @@ impl ZoneConfig {
                     let authority: Arc<dyn AuthorityObject> = match store {
                         #[cfg(feature = "sqlite")]
                         ServerStoreConfig::Sqlite(config) => {
-                            #[cfg_attr(not(feature = "dnssec-ring"), allow(unused_mut))]
                             let mut authority = SqliteAuthority::try_from_config(
                                 zone_name.clone(),
                                 zone_type,
@@ -382,14 +469,18 @@ impl ZoneConfig {
                                 server_config.nx_proof_kind.clone(),
                             )
                             .await?;
+                            authority.set_minimal_responses(server_config.is_minimal_responses());
 
                             #[cfg(feature = "dnssec-ring")]
                             server_config.load_keys(&mut authority, &zone_name).await?;
+                            #[cfg(feature = "dnssec-ring")]
+                            for tsig_key in &server_config.tsig_keys {
+                                authority.add_tsig_signer(tsig_key.try_into_signer()?);
+                            }
                             Arc::new(authority)
                         }
 
                         ServerStoreConfig::File(config) => {
-                            #[cfg_attr(not(feature = "dnssec-ring"), allow(unused_mut))]
                             let mut authority = FileAuthority::try_from_config(
                                 zone_name.clone(),
                                 zone_type,
@@ -399,11 +490,36 @@ impl ZoneConfig {
                                 #[cfg(feature = "dnssec-ring")]
                                 server_config.nx_proof_kind.clone(),
                             )?;
+                            authority.set_minimal_responses(server_config.is_minimal_responses());
 
                             #[cfg(feature = "dnssec-ring")]
                             server_config.load_keys(&mut authority, &zone_name).await?;
                             Arc::new(authority)
                         }
+                        ServerStoreConfig::Secondary(config) => {
+                            if zone_type != ZoneType::Secondary {
+                                return Err(format!(
+                                    "secondary store configured for the primary zone {zone_name}"
+                                ));
+                            }
+                            if config.primaries.is_empty() {
+                                return Err(format!("no primaries configured for {zone_name}"));
+                            }
+
+                            let mut authority = InMemoryAuthority::empty(
+                                zone_name.clone(),
+                                zone_type,
+                                is_axfr_allowed,
+                                #[cfg(feature = "dnssec-ring")]
+                                server_config.nx_proof_kind.clone(),
+                            );
+                            authority.set_minimal_responses(server_config.is_minimal_responses());
+
+                            let authority =
+                                SecondaryAuthority::new(authority, config.primaries.clone());
+                            zone_refresh = Some(authority.zone_refresh());
+                            Arc::new(authority)
+                        }
                         _ => return empty_stores_error(),
                     };
 
@@ -417,7 +533,7 @@ impl ZoneConfig {
                 );
 
                 #[cfg_attr(
-                    not(any(feature = "blocklist", feature = "resolver")),
+                    not(any(feature = "blocklist", feature = "resolver", feature = "rpz")),
                     allow(unreachable_code, unused_variables, clippy::never_loop)
                 )]
                 for store in stores {
@@ -448,6 +564,17 @@ impl ZoneConfig {
 
                             Arc::new(recursor)
                         }
+                        #[cfg(feature = "rpz")]
+                        ExternalStoreConfig::Rpz(config) => {
+                            let rpz = RpzAuthority::try_from_config(
+                                zone_name.clone(),
+                                zone_type,
+                                config,
+                                Some(zone_dir),
+                            )?;
+
+                            Arc::new(rpz)
+                        }
                         _ => return empty_stores_error(),
                     };
 
@@ -457,7 +584,11 @@ impl ZoneConfig {
         }
 
         info!("zone successfully loaded: {}", self.zone()?);
-        Ok(authorities)
+        Ok(LoadedZone {
+            authorities,
+            zone_refresh,
+            notifier: self.notifier(),
+        })
     }
 
     // TODO this is a little ugly for the parse, b/c there is no terminal char
@@ -474,6 +605,61 @@ impl ZoneConfig {
             ZoneTypeConfig::External { .. } => ZoneType::External,
         }
     }
+
+    /// configuration of the catalog zone, if the zone is consumed as one
+    pub fn catalog(&self) -> Option<&CatalogZoneConfig> {
+        self.zone_type_config.as_server()?.catalog.as_ref()
+    }
+
+    /// the notifier of the secondaries of the zone, if any is configured with `also_notify`
+    pub fn notifier(&self) -> Option<Notifier> {
+        let also_notify = &self.zone_type_config.as_server()?.also_notify;
+        if also_notify.is_empty() {
+            return None;
+        }
+
+        Some(Notifier::new(also_notify.clone()))
+    }
+
+    /// path to the zone file of a zone served from a file store
+    ///
+    /// these zones are reloaded by the server when the file changed, the zones of the other stores
+    /// are the source of truth themselves.
+    pub fn file_store_path(&self) -> Option<&Path> {
+        match &self.zone_type_config {
+            ZoneTypeConfig::Primary(server_config) | ZoneTypeConfig::Secondary(server_config) => {
+                server_config.stores.iter().find_map(|store| match store {
+                    ServerStoreConfig::File(file_config) => Some(&*file_config.zone_file_path),
+                    _ => None,
+                })
+            }
+            ZoneTypeConfig::External { .. } => None,
+        }
+    }
+}
+
+/// A zone loaded from its configuration, to be inserted in a catalog
+pub struct LoadedZone {
+    /// the authorities of the zone, in the order of its stores
+    pub authorities: Vec<Arc<dyn AuthorityObject>>,
+    /// the handle accepting the NOTIFY of the primaries, for a zone transferred from them
+    pub zone_refresh: Option<ZoneRefresh>,
+    /// the notifier of the secondaries of the zone, see `also_notify`
+    pub notifier: Option<Notifier>,
+}
+
+impl LoadedZone {
+    /// inserts the zone in the catalog, replacing its previous version
+    pub fn insert_into(self, zone_name: &Name, catalog: &mut Catalog) {
+        let zone_name = LowerName::from(zone_name);
+        catalog.upsert(zone_name.clone(), self.authorities);
+        if let Some(zone_refresh) = self.zone_refresh {
+            catalog.set_zone_refresh(zone_name.clone(), zone_refresh);
+        }
+        if let Some(notifier) = self.notifier {
+            catalog.set_notifier(zone_name, notifier);
+        }
+    }
 }
 
 fn empty_stores_error<T>() -> Result<T, String> {
@@ -513,13 +699,25 @@ impl ZoneTypeConfig {
 pub struct ServerZoneConfig {
     /// Allow AXFR (TODO: need auth)
     pub allow_axfr: Option<bool>,
+    /// Only add the required glue to the additional section of the responses
+    pub minimal_responses: Option<bool>,
     /// Keys for use by the zone
     #[cfg(feature = "dnssec-ring")]
     #[serde(default)]
     pub keys: Vec<dnssec::KeyConfig>,
+    /// TSIG keys authorized to update and transfer the zone, zone transfers must be signed with one
+    ///   of them when set
+    #[cfg(feature = "dnssec-ring")]
+    #[serde(default)]
+    pub tsig_keys: Vec<dnssec::TsigKeyConfig>,
     /// The kind of non-existence proof provided by the nameserver
     #[cfg(feature = "dnssec-ring")]
     pub nx_proof_kind: Option<NxProofKind>,
+    /// Consume the zone as a catalog zone, its member zones are provisioned as secondary zones
+    pub catalog: Option<CatalogZoneConfig>,
+    /// Secondaries notified of the changes of the zone, after its updates and reloads
+    #[serde(default)]
+    pub also_notify: Vec<SocketAddr>,
     /// Store configurations.  Note: we specify a default handler to get a Vec containing a
     /// StoreConfig::Default, which is used for authoritative file-based zones and legacy sqlite
     /// configurations. #[serde(default)] cannot be used, because it will invoke Default for Vec,
@@ -563,7 +761,7 @@ impl ServerZoneConfig {
             ServerStoreConfig::File(file_config) => Some(&*file_config.zone_file_path),
             #[cfg(feature = "sqlite")]
             ServerStoreConfig::Sqlite(sqlite_config) => Some(&*sqlite_config.zone_file_path),
-            ServerStoreConfig::Default => None,
+            ServerStoreConfig::Secondary(_) | ServerStoreConfig::Default => None,
         })
     }
 
@@ -572,6 +770,11 @@ impl ServerZoneConfig {
         self.allow_axfr.unwrap_or(false)
     }
 
+    /// omit the optional additional records, to reduce the size of the responses
+    pub fn is_minimal_responses(&self) -> bool {
+        self.minimal_responses.unwrap_or(false)
+    }
+
     /// declare that this zone should be signed, see keys for configuration of the keys for signing
     pub fn is_dnssec_enabled(&self) -> bool {
         cfg_if! {
@@ -592,6 +795,8 @@ impl ServerZoneConfig {
 pub enum ServerStoreConfig {
     /// File based configuration
     File(FileConfig),
+    /// Zone transferred from its primaries, only for secondary zones
+    Secondary(SecondaryConfig),
     /// Sqlite based configuration file
     #[cfg(feature = "sqlite")]
     Sqlite(SqliteConfig),
@@ -605,6 +810,7 @@ pub enum ServerStoreConfig {
 #[serde(tag = "type")]
 #[serde(rename_all = "lowercase")]
 #[non_exhaustive]
+#[allow(clippy::large_enum_variant)]
 pub enum ExternalStoreConfig {
     /// Blocklist configuration
     #[cfg(feature = "blocklist")]
@@ -615,6 +821,9 @@ pub enum ExternalStoreConfig {
     /// Recursive Resolver
     #[cfg(feature = "recursor")]
     Recursor(Box<RecursiveConfig>),
+    /// Response policy zone, filtering the answers of the next stores
+    #[cfg(feature = "rpz")]
+    Rpz(RpzConfig),
     /// This is used by the configuration processing code to represent a deprecated or main-block config without an associated store.
     #[default]
     Default,
@@ -17,7 +17,7 @@
 use std::env;
 use std::fs::{read_dir, File};
 use std::io::Read;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
@@ -26,8 +26,9 @@ use toml::map::Keys;
 use toml::value::Array;
 use toml::{Table, Value};
 
-use hickory_dns::{Config, ServerZoneConfig};
+use hickory_dns::{Config, ServerStoreConfig, ServerZoneConfig};
 use hickory_server::authority::ZoneType;
+use hickory_server::server::{AccessLists, Acl, ResponseRateLimit};
 
 #[test]
 fn test_read_config() {
@@ -124,6 +125,10 @@ fn test_parse_toml() {
     let config = Config::from_toml("tcp_request_timeout = 25").unwrap();
     assert_eq!(config.tcp_request_timeout(), Duration::from_secs(25));
 
+    let config = Config::from_toml("udp_sockets = 4").unwrap();
+    assert_eq!(config.udp_sockets(), 4);
+    assert_eq!(Config::from_toml("").unwrap().udp_sockets(), 1);
+
     let config = Config::from_toml("log_level = \"Debug\"").unwrap();
     assert_eq!(config.log_level(), tracing::Level::DEBUG);
 
@@ -131,6 +136,222 @@ fn test_parse_toml() {
     assert_eq!(config.directory(), Path::new("/dev/null"));
 }
 
+#[test]
+fn test_parse_secondary_store() {
+    let config = Config::from_toml(
+        r#"
+[[zones]]
+zone = "example.com"
+zone_type = "Secondary"
+stores = { type = "secondary", primaries = ["192.0.2.1:53", "[2001:db8::1]:5353"] }
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(config.zones()[0].zone_type(), ZoneType::Secondary);
+    let ServerStoreConfig::Secondary(store) = &server_zone(&config, 0).stores[0] else {
+        panic!("expected a secondary store");
+    };
+    assert_eq!(
+        store.primaries,
+        [
+            SocketAddr::from((Ipv4Addr::new(192, 0, 2, 1), 53)),
+            SocketAddr::from((Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1), 5353)),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_catalog_zone() {
+    let config = Config::from_toml(
+        r#"
+[[zones]]
+zone = "catalog.example"
+zone_type = "Secondary"
+stores = { type = "secondary", primaries = ["192.0.2.1:53"] }
+catalog = { primaries = ["192.0.2.1:53"], groups = { signed = ["192.0.2.2:53"] } }
+"#,
+    )
+    .unwrap();
+
+    let catalog = config.zones()[0].catalog().unwrap();
+    assert_eq!(
+        catalog.primaries,
+        [SocketAddr::from((Ipv4Addr::new(192, 0, 2, 1), 53))]
+    );
+    assert_eq!(
+        catalog.groups["signed"],
+        [SocketAddr::from((Ipv4Addr::new(192, 0, 2, 2), 53))]
+    );
+
+    let config = Config::from_toml(
+        r#"
+[[zones]]
+zone = "example.com"
+zone_type = "Primary"
+file = "example.com.zone"
+"#,
+    )
+    .unwrap();
+    assert!(config.zones()[0].catalog().is_none());
+}
+
+#[test]
+fn test_parse_also_notify() {
+    let config = Config::from_toml(
+        r#"
+[[zones]]
+zone = "example.com"
+zone_type = "Primary"
+file = "example.com.zone"
+also_notify = ["192.0.2.53:53", "[2001:db8::53]:5353"]
+"#,
+    )
+    .unwrap();
+
+    let notifier = config.zones()[0].notifier().unwrap();
+    assert_eq!(
+        notifier.secondaries(),
+        [
+            SocketAddr::from((Ipv4Addr::new(192, 0, 2, 53), 53)),
+            SocketAddr::from((Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x53), 5353)),
+        ]
+    );
+
+    let config = Config::from_toml(
+        r#"
+[[zones]]
+zone = "example.com"
+zone_type = "Primary"
+file = "example.com.zone"
+"#,
+    )
+    .unwrap();
+    assert!(config.zones()[0].notifier().is_none());
+}
+
+#[test]
+fn test_parse_refuse_any() {
+    let config = Config::from_toml("listen_port = 2053").unwrap();
+    assert!(config.refuse_any());
+
+    let config = Config::from_toml("refuse_any = false").unwrap();
+    assert!(!config.refuse_any());
+}
+
+#[test]
+fn test_parse_query_log() {
+    let config = Config::from_toml("listen_port = 2053").unwrap();
+    assert_eq!(config.query_log(), None);
+
+    let config = Config::from_toml("query_log = \"/var/log/hickory/queries.jsonl\"").unwrap();
+    assert_eq!(
+        config.query_log(),
+        Some(Path::new("/var/log/hickory/queries.jsonl"))
+    );
+}
+
+#[test]
+fn test_parse_metrics_listen_addr() {
+    let config = Config::from_toml("listen_port = 2053").unwrap();
+    assert_eq!(config.metrics_listen_addr(), None);
+
+    let config = Config::from_toml("metrics_listen_addr = \"127.0.0.1:9153\"").unwrap();
+    assert_eq!(
+        config.metrics_listen_addr(),
+        Some(SocketAddr::from((Ipv4Addr::LOCALHOST, 9153)))
+    );
+}
+
+#[test]
+fn test_parse_response_rate_limit() {
+    let config = Config::from_toml("listen_port = 2053").unwrap();
+    assert_eq!(config.response_rate_limit(), None);
+
+    let config = Config::from_toml(
+        "[response_rate_limit]
+responses_per_second = 10
+slip = 0",
+    )
+    .unwrap();
+    assert_eq!(
+        config.response_rate_limit(),
+        Some(ResponseRateLimit {
+            responses_per_second: 10,
+            slip: 0,
+            ..ResponseRateLimit::default()
+        })
+    );
+}
+
+#[test]
+fn test_parse_access_lists() {
+    let config = Config::from_toml("").unwrap();
+    assert_eq!(config.access_lists(), AccessLists::default());
+
+    let config = Config::from_toml(
+        "[allow_recursion]
+allow = [\"127.0.0.0/8\", \"::1/128\"]
+
+[allow_transfer]
+allow = [\"192.0.2.0/24\"]
+deny = [\"192.0.2.1/32\"]",
+    )
+    .unwrap();
+    assert_eq!(
+        config.access_lists(),
+        AccessLists {
+            allow_query: Acl::default(),
+            allow_recursion: Acl {
+                allow: vec!["127.0.0.0/8".parse().unwrap(), "::1/128".parse().unwrap()],
+                deny: vec![],
+            },
+            allow_transfer: Acl {
+                allow: vec!["192.0.2.0/24".parse().unwrap()],
+                deny: vec!["192.0.2.1/32".parse().unwrap()],
+            },
+        }
+    );
+}
+
+#[test]
+fn test_parse_views() {
+    let config = Config::from_toml("").unwrap();
+    assert!(config.views().is_empty());
+
+    let config = Config::from_toml(
+        "[[views]]
+name = \"internal\"
+match_clients = [\"10.0.0.0/8\", \"fd00::/8\"]
+
+[[views.zones]]
+zone = \"example.com\"
+zone_type = \"Primary\"
+file = \"internal.example.com.zone\"
+
+[[views]]
+name = \"external\"
+match_clients = [\"0.0.0.0/0\"]",
+    )
+    .unwrap();
+
+    let views = config.views();
+    assert_eq!(views.len(), 2);
+    assert_eq!(views[0].name, "internal");
+    assert_eq!(
+        views[0].match_clients,
+        ["10.0.0.0/8".parse().unwrap(), "fd00::/8".parse().unwrap()]
+    );
+    assert_eq!(views[0].zones.len(), 1);
+    assert_eq!(views[0].zones[0].zone, "example.com");
+    assert_eq!(
+        views[0].zones[0].file_store_path(),
+        Some(Path::new("internal.example.com.zone"))
+    );
+    assert_eq!(views[1].name, "external");
+    assert!(views[1].zones.is_empty());
+}
+
 #[cfg(feature = "dnssec-ring")]
 #[test]
 fn test_parse_zone_keys() {
@@ -254,6 +475,8 @@ define_test_config!(all_supported_dnssec);
 define_test_config!(chained_blocklist);
 #[cfg(feature = "blocklist")]
 define_test_config!(consulting_blocklist);
+#[cfg(all(feature = "rpz", feature = "resolver"))]
+define_test_config!(chained_rpz);
 #[cfg(feature = "dns-over-https-rustls")]
 define_test_config!(dns_over_https);
 #[cfg(feature = "dns-over-tls")]
@@ -266,6 +489,7 @@ define_test_config!(example);
 define_test_config!(ipv4_and_ipv6);
 define_test_config!(ipv4_only);
 define_test_config!(ipv6_only);
+define_test_config!(example_views);
 #[cfg(feature = "resolver")]
 define_test_config!(example_forwarder);
 
@@ -493,6 +717,13 @@ fn test_reject_unknown_fields() {
                     skip = true;
                     break;
                 }
+
+                #[cfg(not(feature = "rpz"))]
+                if _store_type == "rpz" {
+                    println!("skipping due to rpz store");
+                    skip = true;
+                    break;
+                }
             }
         }
 
@@ -1,14 +1,26 @@
 #![cfg(feature = "resolver")]
 
+use std::net::{Ipv4Addr, SocketAddr};
 use std::str::FromStr;
+use std::sync::Arc;
 
+use tokio::net::UdpSocket;
 use tokio::runtime::Runtime;
 
-use hickory_proto::rr::{Name, RecordType};
+use hickory_proto::rr::{
+    rdata::{A, SOA},
+    Name, RData, Record, RecordType,
+};
 use hickory_resolver::name_server::TokioConnectionProvider;
+#[cfg(feature = "dnssec-ring")]
+use hickory_server::dnssec::NxProofKind;
 use hickory_server::{
-    authority::{Authority, LookupObject},
-    store::forwarder::ForwardAuthority,
+    authority::{Authority, Catalog, LookupObject, ZoneType},
+    store::{
+        forwarder::{ForwardAuthority, ForwardConfig},
+        in_memory::InMemoryAuthority,
+    },
+    ServerFuture,
 };
 
 #[test]
@@ -30,3 +42,105 @@ fn test_lookup() {
         "no addresses returned!"
     );
 }
+
+/// An upstream name server over UDP, answering the queries of all the names with `address`
+async fn upstream(address: Ipv4Addr) -> (SocketAddr, ServerFuture<Catalog>) {
+    let mut authority = InMemoryAuthority::empty(
+        Name::root(),
+        ZoneType::Primary,
+        false,
+        #[cfg(feature = "dnssec-ring")]
+        Some(NxProofKind::Nsec),
+    );
+    let soa = SOA::new(
+        Name::from_str("ns.invalid.").unwrap(),
+        Name::from_str("hostmaster.invalid.").unwrap(),
+        1,
+        3600,
+        600,
+        86400,
+        60,
+    );
+    authority.upsert_mut(Record::from_rdata(Name::root(), 3600, RData::SOA(soa)), 0);
+    for name in [
+        "www.corp.internal.",
+        "www.lab.corp.internal.",
+        "www.example.com.",
+    ] {
+        let record = Record::from_rdata(Name::from_str(name).unwrap(), 60, RData::A(A(address)));
+        authority.upsert_mut(record, 0);
+    }
+
+    let mut catalog = Catalog::new();
+    catalog.upsert(Name::root().into(), vec![Arc::new(authority)]);
+
+    let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = socket.local_addr().unwrap();
+    let mut server = ServerFuture::new(catalog);
+    server.register_socket(socket);
+    (addr, server)
+}
+
+fn name_servers(addr: SocketAddr) -> String {
+    format!(
+        r#"
+[[name_servers]]
+socket_addr = "{addr}"
+protocol = "udp"
+trust_negative_responses = true
+"#
+    )
+}
+
+async fn lookup_a(forwarder: &ForwardAuthority, name: &str) -> Vec<Ipv4Addr> {
+    let lookup = forwarder
+        .lookup(
+            &Name::from_str(name).unwrap().into(),
+            RecordType::A,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    lookup
+        .iter()
+        .filter_map(|record| record.data().as_a().map(|a| a.0))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_conditional_forwarding() {
+    let corp = Ipv4Addr::new(10, 0, 0, 1);
+    let lab = Ipv4Addr::new(10, 0, 1, 1);
+    let global = Ipv4Addr::new(192, 0, 2, 1);
+    let (corp_addr, _corp_server) = upstream(corp).await;
+    let (lab_addr, _lab_server) = upstream(lab).await;
+    let (global_addr, _global_server) = upstream(global).await;
+
+    let config = format!(
+        r#"
+{global}
+
+[[domains]]
+domain = "corp.internal."
+{corp}
+
+[[domains]]
+domain = "lab.corp.internal."
+{lab}
+"#,
+        global = name_servers(global_addr),
+        corp = name_servers(corp_addr).replace("[[name_servers]]", "[[domains.name_servers]]"),
+        lab = name_servers(lab_addr).replace("[[name_servers]]", "[[domains.name_servers]]"),
+    );
+    let config = toml::from_str::<ForwardConfig>(&config).unwrap();
+    assert_eq!(config.domains.len(), 2);
+
+    let forwarder = ForwardAuthority::try_from_config(Name::root(), ZoneType::External, &config)
+        .expect("failed to create forwarder");
+
+    // the longest matching domain wins, the other names are forwarded to the default upstream
+    assert_eq!(lookup_a(&forwarder, "www.corp.internal.").await, [corp]);
+    assert_eq!(lookup_a(&forwarder, "www.lab.corp.internal.").await, [lab]);
+    assert_eq!(lookup_a(&forwarder, "www.example.com.").await, [global]);
+}
@@ -5,6 +5,7 @@ mod forwarder;
 mod in_memory;
 mod named_https_tests;
 mod named_quic_tests;
+mod named_reload_tests;
 mod named_rustls_tests;
 mod named_test_rsa_dnssec;
 mod named_tests;
@@ -0,0 +1,191 @@
+#![cfg(unix)]
+
+use std::env;
+use std::fs;
+use std::net::*;
+use std::path::Path;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tokio::runtime::Runtime;
+
+use crate::server_harness::{named_test_harness_with, query_message};
+use hickory_client::client::Client;
+use hickory_proto::op::{Message, MessageType, OpCode};
+use hickory_proto::rr::{rdata::A, Name, RData, RecordType};
+use hickory_proto::runtime::TokioRuntimeProvider;
+use hickory_proto::udp::UdpClientStream;
+use hickory_proto::xfer::Protocol;
+use test_support::subscribe;
+
+const CONFIG: &str = r#"
+[[zones]]
+zone = "example.com"
+zone_type = "Primary"
+file = "example.com.zone"
+
+[[zones]]
+zone = "example.net"
+zone_type = "Primary"
+file = "example.net.zone"
+"#;
+
+fn write_zone(dir: &Path, origin: &str, serial: u32, address: &str) {
+    let zone = format!(
+        "$ORIGIN {origin}.
+$TTL 3600
+@ IN SOA ns.{origin}. hostmaster.{origin}. ( {serial} 7200 3600 1209600 300 )
+  IN NS ns
+www IN A {address}
+"
+    );
+    fs::write(dir.join(format!("{origin}.zone")), zone).unwrap();
+}
+
+fn query_address(io_loop: &mut Runtime, client: &mut Client, name: &str) -> Option<A> {
+    let name = Name::from_str(name).unwrap();
+    let response = query_message(io_loop, client, name, RecordType::A).unwrap();
+    response
+        .answers()
+        .iter()
+        .find_map(|record| match record.data() {
+            RData::A(address) => Some(*address),
+            _ => None,
+        })
+}
+
+/// Sends SIGHUP to the server, and waits for `www.example.com.` to have the expected address
+fn reload(io_loop: &mut Runtime, client: &mut Client, pid: u32, expected: &str) {
+    assert_eq!(unsafe { libc::kill(pid as libc::pid_t, libc::SIGHUP) }, 0);
+
+    let expected = Some(A::from_str(expected).unwrap());
+    let until = Instant::now() + Duration::from_secs(10);
+    while query_address(io_loop, client, "www.example.com.") != expected {
+        assert!(Instant::now() < until, "zone was not reloaded");
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn test_reload_on_sighup() {
+    subscribe();
+    let provider = TokioRuntimeProvider::new();
+
+    let dir = env::temp_dir().join(format!("hickory-dns-reload-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("reload.toml"), CONFIG).unwrap();
+    write_zone(&dir, "example.com", 1, "192.0.2.1");
+    write_zone(&dir, "example.net", 1, "192.0.2.10");
+
+    named_test_harness_with(&dir.join("reload.toml"), &dir, |socket_ports, pid| {
+        let mut io_loop = Runtime::new().unwrap();
+        let udp_port = socket_ports.get_v4(Protocol::Udp);
+        let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, udp_port.expect("no udp_port")));
+        let stream = UdpClientStream::builder(addr, provider).build();
+        let (mut client, bg) = io_loop
+            .block_on(Client::connect(stream))
+            .expect("client failed to connect");
+        hickory_proto::runtime::spawn_bg(&io_loop, bg);
+
+        assert_eq!(
+            query_address(&mut io_loop, &mut client, "www.example.com."),
+            Some(A::new(192, 0, 2, 1))
+        );
+        assert_eq!(
+            query_address(&mut io_loop, &mut client, "www.example.net."),
+            Some(A::new(192, 0, 2, 10))
+        );
+
+        // make sure that the modification times change
+        thread::sleep(Duration::from_secs(1));
+        write_zone(&dir, "example.com", 2, "192.0.2.2");
+        fs::write(dir.join("example.net.zone"), "www IN A not-an-address\n").unwrap();
+        reload(&mut io_loop, &mut client, pid, "192.0.2.2");
+
+        // the broken zone keeps being served from its previous version
+        assert_eq!(
+            query_address(&mut io_loop, &mut client, "www.example.net."),
+            Some(A::new(192, 0, 2, 10))
+        );
+
+        // until it is fixed
+        thread::sleep(Duration::from_secs(1));
+        write_zone(&dir, "example.com", 3, "192.0.2.3");
+        write_zone(&dir, "example.net", 3, "192.0.2.30");
+        reload(&mut io_loop, &mut client, pid, "192.0.2.3");
+        assert_eq!(
+            query_address(&mut io_loop, &mut client, "www.example.net."),
+            Some(A::new(192, 0, 2, 30))
+        );
+    });
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_notify_on_reload() {
+    subscribe();
+    let provider = TokioRuntimeProvider::new();
+
+    // the secondary notified of the reloads
+    let secondary = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    secondary
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let config = format!(
+        r#"
+[[zones]]
+zone = "example.com"
+zone_type = "Primary"
+file = "example.com.zone"
+also_notify = ["{}"]
+"#,
+        secondary.local_addr().unwrap()
+    );
+
+    let dir = env::temp_dir().join(format!("hickory-dns-notify-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("notify.toml"), config).unwrap();
+    write_zone(&dir, "example.com", 1, "192.0.2.1");
+
+    named_test_harness_with(&dir.join("notify.toml"), &dir, |socket_ports, pid| {
+        let mut io_loop = Runtime::new().unwrap();
+        let udp_port = socket_ports.get_v4(Protocol::Udp);
+        let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, udp_port.expect("no udp_port")));
+        let stream = UdpClientStream::builder(addr, provider).build();
+        let (mut client, bg) = io_loop
+            .block_on(Client::connect(stream))
+            .expect("client failed to connect");
+        hickory_proto::runtime::spawn_bg(&io_loop, bg);
+
+        // make sure that the modification time changes
+        thread::sleep(Duration::from_secs(1));
+        write_zone(&dir, "example.com", 2, "192.0.2.2");
+        reload(&mut io_loop, &mut client, pid, "192.0.2.2");
+
+        let mut buf = [0; 512];
+        let (len, src) = secondary.recv_from(&mut buf).expect("no NOTIFY received");
+        let notify = Message::from_vec(&buf[..len]).unwrap();
+        assert_eq!(notify.op_code(), OpCode::Notify);
+        assert_eq!(notify.message_type(), MessageType::Query);
+        assert_eq!(
+            notify.queries()[0].name(),
+            &Name::from_str("example.com.").unwrap()
+        );
+        assert_eq!(notify.queries()[0].query_type(), RecordType::SOA);
+        match notify.answers()[0].data() {
+            RData::SOA(soa) => assert_eq!(soa.serial(), 2),
+            data => panic!("unexpected answer: {data:?}"),
+        }
+
+        let mut response = Message::new();
+        response
+            .set_id(notify.id())
+            .set_message_type(MessageType::Response)
+            .set_op_code(OpCode::Notify);
+        secondary.send_to(&response.to_vec().unwrap(), src).unwrap();
+    });
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -14,7 +14,7 @@ use tokio::runtime::Runtime;
 use crate::server_harness::{named_test_harness, query_a, query_a_refused};
 use hickory_client::client::{Client, ClientHandle};
 use hickory_proto::op::ResponseCode;
-use hickory_proto::rr::{DNSClass, Name, RecordType};
+use hickory_proto::rr::{rdata::A, DNSClass, Name, RecordType};
 use hickory_proto::runtime::TokioRuntimeProvider;
 use hickory_proto::tcp::TcpClientStream;
 use hickory_proto::udp::UdpClientStream;
@@ -386,3 +386,36 @@ fn test_deny_allow_networks_toml_startup() {
         query_a_refused(&mut io_loop, &mut client);
     })
 }
+
+#[test]
+fn test_views_toml_startup() {
+    let provider = TokioRuntimeProvider::new();
+    named_test_harness("example_views.toml", |socket_ports| {
+        let mut io_loop = Runtime::new().unwrap();
+        let name = Name::from_str("www.example.com.").unwrap();
+
+        // the internal view over ipv4
+        let tcp_port = socket_ports.get_v4(Protocol::Tcp);
+        let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, tcp_port.expect("no tcp_port")));
+        let (stream, sender) = TcpClientStream::new(addr, None, None, provider.clone());
+        let client = Client::new(Box::new(stream), sender, None);
+        let (mut client, bg) = io_loop.block_on(client).expect("client failed to connect");
+        hickory_proto::runtime::spawn_bg(&io_loop, bg);
+        query_a(&mut io_loop, &mut client);
+
+        // the external view over ipv6
+        let tcp_port = socket_ports.get_v6(Protocol::Tcp);
+        let addr = SocketAddr::from((Ipv6Addr::LOCALHOST, tcp_port.expect("no tcp_port")));
+        let (stream, sender) = TcpClientStream::new(addr, None, None, provider.clone());
+        let client = Client::new(Box::new(stream), sender, None);
+        let (mut client, bg) = io_loop.block_on(client).expect("client failed to connect");
+        hickory_proto::runtime::spawn_bg(&io_loop, bg);
+        let response = io_loop
+            .block_on(client.query(name, DNSClass::IN, RecordType::A))
+            .unwrap();
+        assert_eq!(
+            response.answers()[0].data().as_a(),
+            Some(&A::new(192, 0, 2, 1))
+        );
+    })
+}
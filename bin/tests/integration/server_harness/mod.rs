@@ -6,6 +6,7 @@ use std::{
     io::{stdout, BufRead, BufReader, Write},
     net::SocketAddr,
     panic::{catch_unwind, UnwindSafe},
+    path::Path,
     process::{Command, Stdio},
     str::FromStr,
     sync::*,
@@ -85,6 +86,22 @@ where
     let server_path = env::var("TDNS_WORKSPACE_ROOT").unwrap_or_else(|_| "..".to_owned());
     println!("using server src path: {server_path}");
 
+    named_test_harness_with(
+        Path::new(&format!(
+            "{server_path}/tests/test-data/test_configs/{toml}"
+        )),
+        Path::new(&format!("{server_path}/tests/test-data/test_configs")),
+        |socket_ports, _| test(socket_ports),
+    )
+}
+
+/// Spins up a Server with the configuration file and the zone directory at the paths, the test
+/// also gets the process id of the server
+#[allow(dead_code)]
+pub fn named_test_harness_with<F, R>(config: &Path, zone_dir: &Path, test: F)
+where
+    F: FnOnce(SocketPorts, u32) -> R + UnwindSafe,
+{
     let mut command = Command::new(env!("CARGO_BIN_EXE_hickory-dns"));
     command
         .stdout(Stdio::piped())
@@ -93,12 +110,8 @@ where
             "hickory_dns=debug,hickory_client=debug,hickory_proto=debug,hickory_resolver=debug,hickory_server=debug",
         )
         .arg("-d")
-        .arg(format!(
-            "--config={server_path}/tests/test-data/test_configs/{toml}"
-        ))
-        .arg(format!(
-            "--zonedir={server_path}/tests/test-data/test_configs"
-        ))
+        .arg(format!("--config={}", config.display()))
+        .arg(format!("--zonedir={}", zone_dir.display()))
         .arg(format!("--port={}", 0));
     #[cfg(feature = "dns-over-tls")]
     command.arg(format!("--tls-port={}", 0));
@@ -110,6 +123,7 @@ where
     println!("named cli options: {command:#?}");
 
     let mut named = command.spawn().expect("failed to start named");
+    let pid = named.id();
 
     println!("server starting");
 
@@ -229,7 +243,7 @@ where
 
     println!("running test...");
 
-    let result = catch_unwind(move || test(socket_ports));
+    let result = catch_unwind(move || test(socket_ports, pid));
 
     println!("test completed");
     succeeded.store(true, atomic::Ordering::Relaxed);
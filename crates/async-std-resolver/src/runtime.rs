@@ -165,4 +165,8 @@ impl ConnectionProvider for AsyncStdConnectionProvider {
     ) -> Result<Self::FutureConn, io::Error> {
         self.connection_provider.new_connection(config, options)
     }
+
+    fn create_handle(&self) -> AsyncStdRuntimeHandle {
+        self.runtime_provider.create_handle()
+    }
 }
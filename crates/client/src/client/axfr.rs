@@ -0,0 +1,259 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Streaming full zone transfer, see [RFC 5936](https://www.rfc-editor.org/rfc/rfc5936)
+
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_util::{
+    ready,
+    stream::{Stream, StreamExt},
+};
+
+use hickory_proto::{
+    op::ResponseCode,
+    rr::{rdata::SOA, Record, RecordType},
+    xfer::DnsResponse,
+    ProtoError,
+};
+
+/// A stream of the records of an AXFR Client Request, see `ClientHandle::axfr`
+///
+/// The records are yielded as the response messages arrive, only the records of the current
+/// message are held in memory. The first record is the SOA of the zone, the SOA repeated at the
+/// end of the transfer is not yielded, [RFC 5936, section 2.2](https://www.rfc-editor.org/rfc/rfc5936#section-2.2).
+///
+/// The stream ends with an error if the transfer is not bracketed by the SOA of the zone, or if
+/// records follow the final SOA.
+#[must_use = "stream do nothing unless polled"]
+pub struct ClientAxfr<R>
+where
+    R: Stream<Item = Result<DnsResponse, ProtoError>> + Send + Unpin + 'static,
+{
+    inner: R,
+    records: VecDeque<Record>,
+    serial: Option<u32>,
+    ended: bool,
+}
+
+impl<R> ClientAxfr<R>
+where
+    R: Stream<Item = Result<DnsResponse, ProtoError>> + Send + Unpin + 'static,
+{
+    pub(crate) fn new(inner: R) -> Self {
+        Self {
+            inner,
+            records: VecDeque::new(),
+            serial: None,
+            ended: false,
+        }
+    }
+
+    /// Checks the next record of the transfer, `None` if it is the final SOA
+    fn next_record(&mut self, record: Record) -> Option<Result<Record, ProtoError>> {
+        let Some(serial) = self.serial else {
+            return match get_serial(&record) {
+                Some(serial) => {
+                    self.serial = Some(serial);
+                    Some(Ok(record))
+                }
+                None => self.fail("AXFR response does not start with a SOA".into()),
+            };
+        };
+
+        if record.record_type() != RecordType::SOA {
+            return Some(Ok(record));
+        }
+
+        self.ended = true;
+        if get_serial(&record) != Some(serial) {
+            return self.fail("AXFR response ends with a different SOA".into());
+        }
+        if !self.records.is_empty() {
+            return self.fail("invalid zone transfer, contains trailing records".into());
+        }
+
+        None
+    }
+
+    fn fail(&mut self, error: ProtoError) -> Option<Result<Record, ProtoError>> {
+        self.ended = true;
+        self.records.clear();
+        Some(Err(error))
+    }
+}
+
+impl<R> Stream for ClientAxfr<R>
+where
+    R: Stream<Item = Result<DnsResponse, ProtoError>> + Send + Unpin + 'static,
+{
+    type Item = Result<Record, ProtoError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if self.ended {
+                return Poll::Ready(None);
+            }
+
+            if let Some(record) = self.records.pop_front() {
+                return Poll::Ready(self.next_record(record));
+            }
+
+            let response = match ready!(self.inner.poll_next_unpin(cx)) {
+                Some(Ok(response)) => response,
+                Some(Err(error)) => return Poll::Ready(self.fail(error)),
+                None => {
+                    return Poll::Ready(self.fail("AXFR response is missing its final SOA".into()))
+                }
+            };
+
+            if response.response_code() != ResponseCode::NoError {
+                let error = format!("AXFR failed: {}", response.response_code());
+                return Poll::Ready(self.fail(error.into()));
+            }
+
+            let records = response.into_message().take_answers();
+            self.records.extend(records);
+        }
+    }
+}
+
+fn get_serial(record: &Record) -> Option<u32> {
+    record.data().as_soa().map(SOA::serial)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use futures_util::stream::{iter, TryStreamExt};
+    use hickory_proto::{
+        op::Message,
+        rr::{rdata::A, Name, RData},
+    };
+
+    use super::*;
+
+    fn soa_record(serial: u32) -> Record {
+        let soa = RData::SOA(SOA::new(
+            Name::from_str("example.com.").unwrap(),
+            Name::from_str("admin.example.com.").unwrap(),
+            serial,
+            60,
+            60,
+            60,
+            60,
+        ));
+        Record::from_rdata(Name::from_str("example.com.").unwrap(), 600, soa)
+    }
+
+    fn a_record(idx: u32) -> Record {
+        let name = Name::from_str(&format!("host-{idx}.example.com.")).unwrap();
+        Record::from_rdata(name, 600, RData::A(A::from(Ipv4Addr::from(idx))))
+    }
+
+    /// Encodes the messages to the wire format, and decodes them again, as received by the client
+    fn recorded_stream(
+        messages: Vec<Vec<Record>>,
+    ) -> impl Stream<Item = Result<DnsResponse, ProtoError>> + Send + Unpin + 'static {
+        let buffers = messages
+            .into_iter()
+            .map(|answers| {
+                let mut message = Message::new();
+                message.insert_answers(answers);
+                message.to_vec().unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        iter(buffers.into_iter().map(DnsResponse::from_buffer))
+    }
+
+    /// A zone with `count` A records, split in messages of 100 records
+    fn zone_transfer(count: u32) -> Vec<Vec<Record>> {
+        let mut records = vec![soa_record(7)];
+        records.extend((0..count).map(a_record));
+        records.push(soa_record(7));
+        records.chunks(100).map(<[Record]>::to_vec).collect()
+    }
+
+    #[tokio::test]
+    async fn test_axfr_stream_multi_message() {
+        let count = ClientAxfr::new(recorded_stream(zone_transfer(1000)))
+            .try_fold(0, |count, record| async move {
+                match count {
+                    0 => assert_eq!(record, soa_record(7)),
+                    _ => assert_eq!(record, a_record(count - 1)),
+                }
+                Ok(count + 1)
+            })
+            .await
+            .unwrap();
+        assert_eq!(count, 1001);
+    }
+
+    #[tokio::test]
+    async fn test_axfr_stream_empty_zone() {
+        let stream = recorded_stream(vec![vec![soa_record(7)], vec![soa_record(7)]]);
+        let records = ClientAxfr::new(stream).try_collect::<Vec<_>>().await;
+        assert_eq!(records.unwrap(), vec![soa_record(7)]);
+    }
+
+    #[tokio::test]
+    async fn test_axfr_stream_stops_at_final_soa() {
+        // the trailing message would fail the transfer if it were read
+        let mut messages = zone_transfer(10);
+        messages.push(vec![a_record(11)]);
+
+        let records = ClientAxfr::new(recorded_stream(messages))
+            .try_collect::<Vec<_>>()
+            .await;
+        assert_eq!(records.unwrap().len(), 11);
+    }
+
+    #[tokio::test]
+    async fn test_axfr_stream_invalid() {
+        async fn transfer(messages: Vec<Vec<Record>>) -> Result<Vec<Record>, ProtoError> {
+            ClientAxfr::new(recorded_stream(messages))
+                .try_collect()
+                .await
+        }
+
+        // not starting with a SOA
+        transfer(vec![vec![a_record(1), soa_record(7)]])
+            .await
+            .unwrap_err();
+
+        // truncated
+        transfer(vec![vec![soa_record(7), a_record(1)]])
+            .await
+            .unwrap_err();
+
+        // trailing records
+        transfer(vec![vec![soa_record(7), soa_record(7), a_record(1)]])
+            .await
+            .unwrap_err();
+
+        // ending with another version of the zone
+        transfer(vec![vec![soa_record(7), a_record(1)], vec![soa_record(8)]])
+            .await
+            .unwrap_err();
+
+        // error responses
+        let mut message = Message::new();
+        message.set_response_code(ResponseCode::NotAuth);
+        let stream = iter(vec![DnsResponse::from_message(message)]);
+        ClientAxfr::new(stream)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap_err();
+    }
+}
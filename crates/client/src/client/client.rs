@@ -20,9 +20,14 @@ use futures_util::{
 use rand;
 use tracing::debug;
 
+use crate::client::axfr::ClientAxfr;
+use crate::client::ixfr::ClientIxfr;
 use crate::{ClientError, ClientErrorKind};
 use hickory_proto::{
-    op::{update_message, Edns, Message, MessageFinalizer, MessageType, OpCode, Query},
+    op::{
+        update_message::{self, UpdateBuilder},
+        Edns, Message, MessageFinalizer, MessageType, OpCode, Query,
+    },
     rr::{rdata::SOA, DNSClass, Name, Record, RecordSet, RecordType},
     runtime::TokioTime,
     xfer::{
@@ -155,14 +160,72 @@ pub trait ClientHandle: 'static + Clone + DnsHandle + Send {
         name: Name,
         query_class: DNSClass,
         query_type: RecordType,
+    ) -> ClientResponse<<Self as DnsHandle>::Response> {
+        self.query_with_options(name, query_class, query_type, DnsRequestOptions::default())
+    }
+
+    /// A *classic* DNS query, with the header flags of the request set from `options`
+    ///
+    /// This sets the CD (checking disabled) bit with [`DnsRequestOptions::checking_disabled`], for
+    /// applications doing their own DNSSEC validation. The AD (authentic data) bit of the response
+    /// is returned by `authentic_data()` on the [`DnsResponse`].
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the label to lookup
+    /// * `query_class` - most likely this should always be DNSClass::IN
+    /// * `query_type` - record type to lookup
+    /// * `options` - options of the request, EDNS is always used if the client is using it
+    fn query_with_options(
+        &mut self,
+        name: Name,
+        query_class: DNSClass,
+        query_type: RecordType,
+        mut options: DnsRequestOptions,
     ) -> ClientResponse<<Self as DnsHandle>::Response> {
         let mut query = Query::query(name, query_type);
         query.set_query_class(query_class);
-        let mut options = DnsRequestOptions::default();
-        options.use_edns = self.is_using_edns();
+        options.use_edns |= self.is_using_edns();
         ClientResponse(self.lookup(query, options))
     }
 
+    /// A *classic* DNS query, with the EDNS section built with [`Edns::builder`]
+    ///
+    /// The OPT RR of the request carries the DO bit, the UDP payload size and the options of
+    /// `edns`, whether the client is using EDNS or not.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the label to lookup
+    /// * `query_class` - most likely this should always be DNSClass::IN
+    /// * `query_type` - record type to lookup
+    /// * `edns` - the EDNS section of the request
+    fn query_with_edns(
+        &mut self,
+        name: Name,
+        query_class: DNSClass,
+        query_type: RecordType,
+        edns: Edns,
+    ) -> ClientResponse<<Self as DnsHandle>::Response> {
+        let mut query = Query::query(name, query_type);
+        query.set_query_class(query_class);
+
+        let mut options = DnsRequestOptions::default();
+        options.use_edns = true;
+        options.edns_set_dnssec_ok = edns.flags().dnssec_ok;
+
+        let mut message = Message::new();
+        message
+            .set_id(rand::random())
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Query)
+            .set_recursion_desired(options.recursion_desired)
+            .add_query(query)
+            .set_edns(edns);
+
+        ClientResponse(self.send(DnsRequest::new(message, options)))
+    }
+
     /// Sends a NOTIFY message to the remote system
     ///
     /// [RFC 1996](https://tools.ietf.org/html/rfc1996), DNS NOTIFY, August 1996
@@ -565,6 +628,20 @@ pub trait ClientHandle: 'static + Clone + DnsHandle + Send {
         ClientResponse(self.send(message))
     }
 
+    /// Sends an update combining prerequisites and updates, see [`UpdateBuilder`]
+    ///
+    /// The update is applied by the server only if all the prerequisites are met, and then all
+    /// the updates are applied atomically. EDNS is used if the client is using EDNS.
+    ///
+    /// # Arguments
+    ///
+    /// * `update` - the prerequisites and updates of the zone
+    fn update(&mut self, update: UpdateBuilder) -> ClientResponse<<Self as DnsHandle>::Response> {
+        let message = update.use_edns(self.is_using_edns()).build();
+
+        ClientResponse(self.send(message))
+    }
+
     /// Download all records from a zone, or all records modified since given SOA was observed.
     /// The request will either be a AXFR Query (ask for full zone transfer) if a SOA was not
     /// provided, or a IXFR Query (incremental zone transfer) if a SOA was provided.
@@ -582,6 +659,41 @@ pub trait ClientHandle: 'static + Clone + DnsHandle + Send {
 
         ClientStreamXfr::new(self.send(message), ixfr)
     }
+
+    /// Download the changes made to a zone since the given SOA was observed, see
+    /// [RFC 1995](https://www.rfc-editor.org/rfc/rfc1995).
+    ///
+    /// The response is parsed into the differences between each version of the zone. Servers
+    /// not able or willing to send the differences respond with the full zone instead, which is
+    /// returned as `IxfrResponse::Full`.
+    ///
+    /// # Arguments
+    /// * `zone_origin` - the zone name to update, i.e. SOA name
+    /// * `last_soa` - the last SOA known, name must match `zone_origin`
+    fn ixfr(
+        &mut self,
+        zone_origin: Name,
+        last_soa: SOA,
+    ) -> ClientIxfr<<Self as DnsHandle>::Response> {
+        let last_serial = last_soa.serial();
+        let message = update_message::zone_transfer(zone_origin, Some(last_soa));
+
+        ClientIxfr::new(self.send(message), last_serial)
+    }
+
+    /// Download all records from a zone, as a stream of the records in the order they are
+    /// received, see [RFC 5936](https://www.rfc-editor.org/rfc/rfc5936).
+    ///
+    /// Unlike `zone_transfer`, the records are not tied to their response messages, which allows
+    /// processing large zones incrementally. See [`ClientAxfr`] for the validation of the transfer.
+    ///
+    /// # Arguments
+    /// * `zone_origin` - the zone name to transfer, i.e. SOA name
+    fn axfr(&mut self, zone_origin: Name) -> ClientAxfr<<Self as DnsHandle>::Response> {
+        let message = update_message::zone_transfer(zone_origin, None);
+
+        ClientAxfr::new(self.send(message))
+    }
 }
 
 /// A stream result of a Client Request
@@ -639,7 +751,7 @@ impl<R> ClientStreamXfr<R>
 where
     R: Stream<Item = Result<DnsResponse, ProtoError>> + Send + Unpin + 'static,
 {
-    fn new(inner: R, maybe_incr: bool) -> Self {
+    pub(crate) fn new(inner: R, maybe_incr: bool) -> Self {
         Self {
             state: ClientStreamXfrState::Start { inner, maybe_incr },
         }
@@ -844,10 +956,11 @@ mod tests {
 
     use super::*;
 
-    use futures_util::stream::iter;
+    use futures_util::{future, stream, stream::iter};
     use hickory_proto::{
+        op::Header,
         rr::{
-            rdata::{A, SOA},
+            rdata::{opt::EdnsOption, A, SOA},
             RData,
         },
         runtime::TokioRuntimeProvider,
@@ -885,6 +998,80 @@ mod tests {
         iter(stream)
     }
 
+    /// Answers each request with its header, as a server setting the AD bit would
+    #[derive(Clone)]
+    struct AuthenticDataHandle;
+
+    impl DnsHandle for AuthenticDataHandle {
+        type Response = stream::Once<future::Ready<Result<DnsResponse, ProtoError>>>;
+
+        fn send<R: Into<DnsRequest> + Unpin + Send + 'static>(&self, request: R) -> Self::Response {
+            let request = request.into();
+            let mut response = Message::new();
+            response
+                .set_header(Header::response_from_request(request.header()))
+                .set_authentic_data(true);
+            stream::once(future::ready(DnsResponse::from_message(response)))
+        }
+    }
+
+    /// Answers each request with the EDNS section it was emitted with
+    #[derive(Clone)]
+    struct EdnsEchoHandle;
+
+    impl DnsHandle for EdnsEchoHandle {
+        type Response = stream::Once<future::Ready<Result<DnsResponse, ProtoError>>>;
+
+        fn send<R: Into<DnsRequest> + Unpin + Send + 'static>(&self, request: R) -> Self::Response {
+            let request = Message::from_vec(&request.into().to_vec().unwrap()).unwrap();
+            let mut response = Message::new();
+            response.set_header(Header::response_from_request(request.header()));
+            if let Some(edns) = request.extensions() {
+                response.set_edns(edns.clone());
+            }
+            stream::once(future::ready(DnsResponse::from_message(response)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_with_edns() {
+        let name = Name::from_ascii("www.example.com.").unwrap();
+        let edns = Edns::builder()
+            .dnssec_ok(true)
+            .udp_payload_size(1232)
+            .option(EdnsOption::Unknown(65001, b"first".to_vec()))
+            .option(EdnsOption::Unknown(65002, b"second".to_vec()))
+            .build();
+
+        let response = EdnsEchoHandle
+            .query_with_edns(name, DNSClass::IN, RecordType::A, edns.clone())
+            .await
+            .unwrap();
+        assert!(response.recursion_desired());
+        assert_eq!(response.extensions().as_ref(), Some(&edns));
+    }
+
+    #[tokio::test]
+    async fn test_checking_disabled_and_authentic_data() {
+        let name = Name::from_ascii("www.example.com.").unwrap();
+
+        let response = AuthenticDataHandle
+            .query(name.clone(), DNSClass::IN, RecordType::A)
+            .await
+            .unwrap();
+        assert!(!response.checking_disabled());
+        assert!(response.authentic_data());
+
+        let mut options = DnsRequestOptions::default();
+        options.checking_disabled = true;
+        let response = AuthenticDataHandle
+            .query_with_options(name, DNSClass::IN, RecordType::A, options)
+            .await
+            .unwrap();
+        assert!(response.checking_disabled());
+        assert!(response.authentic_data());
+    }
+
     #[tokio::test]
     async fn test_stream_xfr_valid_axfr() {
         let stream = get_stream_testcase(vec![vec![
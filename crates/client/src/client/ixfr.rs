@@ -0,0 +1,459 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Incremental zone transfer, see [RFC 1995](https://www.rfc-editor.org/rfc/rfc1995)
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_util::{
+    ready,
+    stream::{Stream, StreamExt},
+};
+
+use crate::client::client::ClientStreamXfr;
+use crate::{ClientError, ClientErrorKind};
+use hickory_proto::{
+    op::ResponseCode,
+    rr::{rdata::SOA, Record, RecordType, SerialNumber},
+    xfer::DnsResponse,
+    ProtoError,
+};
+
+/// The outcome of an incremental zone transfer
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IxfrResponse {
+    /// The zone is already at (or newer than) the version held by the server
+    UpToDate(Record),
+    /// The server sent the entire zone instead of the changes, the first record is the SOA
+    Full(Vec<Record>),
+    /// The server sent the changes between the requested version and its current version
+    Incremental(Vec<IxfrDiff>),
+}
+
+impl IxfrResponse {
+    /// Parses the answers of all messages of an IXFR response
+    ///
+    /// [RFC 1995, Incremental Zone Transfer in DNS, August 1996](https://www.rfc-editor.org/rfc/rfc1995#section-4)
+    ///
+    /// ```text
+    /// If incremental zone transfer is available, answer section of the
+    /// response contains the new SOA, followed by the sequences of differences
+    /// between versions, each consisting of the old SOA, the deleted RRs, the
+    /// new SOA and the added RRs, and terminated by the new SOA once again.
+    ///
+    /// If incremental zone transfer is not available, the entire zone is
+    /// returned.  The first and the last RR of the response is the SOA
+    /// record of the zone.
+    /// ```
+    ///
+    /// Several versions may be condensed into a single difference by the server, so the only
+    /// requirement on the differences is that each one continues where the previous one ended.
+    ///
+    /// # Arguments
+    ///
+    /// * `answers` - the answers of all response messages, in the order they were received
+    /// * `last_serial` - the serial of the SOA sent in the IXFR request
+    #[allow(clippy::result_large_err)]
+    pub fn from_answers(answers: Vec<Record>, last_serial: u32) -> Result<Self, ClientError> {
+        let mut answers = answers.into_iter().peekable();
+
+        let soa = answers
+            .next()
+            .ok_or(ClientErrorKind::Message("empty IXFR response"))?;
+        let serial = get_serial(&soa).ok_or(ClientErrorKind::Message(
+            "IXFR response does not start with a SOA",
+        ))?;
+
+        let Some(second) = answers.next() else {
+            if SerialNumber::from(serial) <= SerialNumber::from(last_serial) {
+                return Ok(Self::UpToDate(soa));
+            }
+
+            return Err(ClientErrorKind::Message(
+                "IXFR response only contains a newer SOA, AXFR over TCP is required",
+            )
+            .into());
+        };
+
+        // not incremental, the server sent the entire zone
+        if get_serial(&second).is_none() {
+            let mut records = vec![soa, second];
+            while let Some(record) = answers.next() {
+                if record.record_type() == RecordType::SOA {
+                    if get_serial(&record) == Some(serial) && answers.peek().is_none() {
+                        return Ok(Self::Full(records));
+                    }
+                    break;
+                }
+                records.push(record);
+            }
+
+            return Err(ClientErrorKind::Message("invalid AXFR response to IXFR query").into());
+        }
+
+        // an empty AXFR, only consisting of the SOA twice
+        if get_serial(&second) == Some(serial) && answers.peek().is_none() {
+            return Ok(Self::Full(vec![soa]));
+        }
+
+        let mut diffs = Vec::<IxfrDiff>::new();
+        let mut old_soa = second;
+        loop {
+            let old_serial = get_serial(&old_soa).expect("SOA checked before");
+            let expected_serial = diffs.last().map_or(last_serial, IxfrDiff::new_serial);
+            if old_serial != expected_serial {
+                return Err(ClientErrorKind::Msg(format!(
+                    "IXFR difference starts at serial {old_serial}, expected {expected_serial}"
+                ))
+                .into());
+            }
+
+            let deleted = take_until_soa(&mut answers);
+            let new_soa = answers.next().ok_or(ClientErrorKind::Message(
+                "IXFR difference is missing its new SOA",
+            ))?;
+            let added = take_until_soa(&mut answers);
+
+            let next = answers.next().ok_or(ClientErrorKind::Message(
+                "IXFR response is missing its final SOA",
+            ))?;
+
+            diffs.push(IxfrDiff {
+                old_soa,
+                deleted,
+                new_soa,
+                added,
+            });
+
+            if answers.peek().is_none() {
+                if get_serial(&next) != Some(serial)
+                    || diffs.last().map(IxfrDiff::new_serial) != Some(serial)
+                {
+                    return Err(ClientErrorKind::Message(
+                        "IXFR response does not end with the current SOA",
+                    )
+                    .into());
+                }
+
+                return Ok(Self::Incremental(diffs));
+            }
+
+            old_soa = next;
+        }
+    }
+}
+
+/// The changes between two versions of a zone, as transferred by IXFR
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IxfrDiff {
+    old_soa: Record,
+    deleted: Vec<Record>,
+    new_soa: Record,
+    added: Vec<Record>,
+}
+
+impl IxfrDiff {
+    /// The SOA of the version these changes apply to
+    pub fn old_soa(&self) -> &Record {
+        &self.old_soa
+    }
+
+    /// The serial of the version these changes apply to
+    pub fn old_serial(&self) -> u32 {
+        get_serial(&self.old_soa).expect("SOA checked while parsing")
+    }
+
+    /// The records to delete from the zone, excluding the old SOA
+    pub fn deleted(&self) -> &[Record] {
+        &self.deleted
+    }
+
+    /// The SOA of the version resulting from these changes
+    pub fn new_soa(&self) -> &Record {
+        &self.new_soa
+    }
+
+    /// The serial of the version resulting from these changes
+    pub fn new_serial(&self) -> u32 {
+        get_serial(&self.new_soa).expect("SOA checked while parsing")
+    }
+
+    /// The records to add to the zone, excluding the new SOA
+    pub fn added(&self) -> &[Record] {
+        &self.added
+    }
+}
+
+/// A future result of an IXFR Client Request, see `ClientHandle::ixfr`
+#[must_use = "futures do nothing unless polled"]
+pub struct ClientIxfr<R>
+where
+    R: Stream<Item = Result<DnsResponse, ProtoError>> + Send + Unpin + 'static,
+{
+    inner: ClientStreamXfr<R>,
+    last_serial: u32,
+    answers: Vec<Record>,
+}
+
+impl<R> ClientIxfr<R>
+where
+    R: Stream<Item = Result<DnsResponse, ProtoError>> + Send + Unpin + 'static,
+{
+    pub(crate) fn new(inner: R, last_serial: u32) -> Self {
+        Self {
+            inner: ClientStreamXfr::new(inner, true),
+            last_serial,
+            answers: Vec::new(),
+        }
+    }
+}
+
+impl<R> Future for ClientIxfr<R>
+where
+    R: Stream<Item = Result<DnsResponse, ProtoError>> + Send + Unpin + 'static,
+{
+    type Output = Result<IxfrResponse, ClientError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        while let Some(response) = ready!(self.inner.poll_next_unpin(cx)) {
+            let response = response?;
+            if response.response_code() != ResponseCode::NoError {
+                return Poll::Ready(Err(ClientErrorKind::Msg(format!(
+                    "IXFR failed: {}",
+                    response.response_code()
+                ))
+                .into()));
+            }
+
+            // a single SOA which is not newer than ours signals that we are up to date, there is
+            //  nothing more to wait for in that case
+            let up_to_date = self.answers.is_empty()
+                && response.answers().len() == 1
+                && get_serial(&response.answers()[0]).is_some_and(|serial| {
+                    SerialNumber::from(serial) <= SerialNumber::from(self.last_serial)
+                });
+
+            self.answers.extend_from_slice(response.answers());
+            if up_to_date {
+                break;
+            }
+        }
+
+        let answers = std::mem::take(&mut self.answers);
+        Poll::Ready(IxfrResponse::from_answers(answers, self.last_serial))
+    }
+}
+
+fn get_serial(record: &Record) -> Option<u32> {
+    record.data().as_soa().map(SOA::serial)
+}
+
+fn take_until_soa(answers: &mut std::iter::Peekable<impl Iterator<Item = Record>>) -> Vec<Record> {
+    let mut records = Vec::new();
+    while let Some(record) = answers.next_if(|r| r.record_type() != RecordType::SOA) {
+        records.push(record);
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::str::FromStr;
+
+    use futures_util::stream::iter;
+    use hickory_proto::{
+        op::Message,
+        rr::{rdata::A, Name, RData},
+    };
+
+    use super::*;
+
+    fn soa_record(serial: u32) -> Record {
+        let soa = RData::SOA(SOA::new(
+            Name::from_str("JAIN.AD.JP.").unwrap(),
+            Name::from_str("mohta.jain.ad.jp.").unwrap(),
+            serial,
+            600,
+            600,
+            3600000,
+            604800,
+        ));
+        Record::from_rdata(Name::from_str("JAIN.AD.JP.").unwrap(), 86400, soa)
+    }
+
+    fn a_record(name: &str, ip: [u8; 4]) -> Record {
+        let a = RData::A(A::from(std::net::Ipv4Addr::from(ip)));
+        Record::from_rdata(Name::from_str(name).unwrap(), 86400, a)
+    }
+
+    /// The zone at serial 1, from RFC 1995 section 7
+    fn zone_v1() -> Vec<Record> {
+        vec![
+            soa_record(1),
+            a_record("NS.JAIN.AD.JP.", [133, 69, 136, 1]),
+            a_record("NEZU.JAIN.AD.JP.", [133, 69, 136, 5]),
+        ]
+    }
+
+    /// The zone at serial 3, from RFC 1995 section 7
+    fn zone_v3() -> Vec<Record> {
+        vec![
+            soa_record(3),
+            a_record("NS.JAIN.AD.JP.", [133, 69, 136, 1]),
+            a_record("JAIN-BB.JAIN.AD.JP.", [133, 69, 136, 3]),
+            a_record("JAIN-BB.JAIN.AD.JP.", [192, 41, 197, 2]),
+        ]
+    }
+
+    /// The IXFR response of RFC 1995 section 7, split across several messages
+    fn ixfr_response() -> Vec<Vec<Record>> {
+        vec![
+            vec![
+                soa_record(3),
+                soa_record(1),
+                a_record("NEZU.JAIN.AD.JP.", [133, 69, 136, 5]),
+                soa_record(2),
+            ],
+            vec![
+                a_record("JAIN-BB.JAIN.AD.JP.", [133, 69, 136, 4]),
+                a_record("JAIN-BB.JAIN.AD.JP.", [192, 41, 197, 2]),
+                soa_record(2),
+                a_record("JAIN-BB.JAIN.AD.JP.", [133, 69, 136, 4]),
+            ],
+            vec![
+                soa_record(3),
+                a_record("JAIN-BB.JAIN.AD.JP.", [133, 69, 136, 3]),
+                soa_record(3),
+            ],
+        ]
+    }
+
+    /// The condensed IXFR response of RFC 1995 section 7
+    fn condensed_ixfr_response() -> Vec<Record> {
+        vec![
+            soa_record(3),
+            soa_record(1),
+            a_record("NEZU.JAIN.AD.JP.", [133, 69, 136, 5]),
+            soa_record(3),
+            a_record("JAIN-BB.JAIN.AD.JP.", [133, 69, 136, 3]),
+            a_record("JAIN-BB.JAIN.AD.JP.", [192, 41, 197, 2]),
+            soa_record(3),
+        ]
+    }
+
+    fn get_stream_testcase(
+        records: Vec<Vec<Record>>,
+    ) -> impl Stream<Item = Result<DnsResponse, ProtoError>> + Send + Unpin + 'static {
+        let stream = records.into_iter().map(|r| {
+            Ok({
+                let mut m = Message::new();
+                m.insert_answers(r);
+                DnsResponse::from_message(m).unwrap()
+            })
+        });
+        iter(stream)
+    }
+
+    fn apply(zone: Vec<Record>, diffs: &[IxfrDiff]) -> BTreeSet<Record> {
+        let mut zone = zone.into_iter().collect::<BTreeSet<_>>();
+        for diff in diffs {
+            assert!(zone.remove(diff.old_soa()));
+            for record in diff.deleted() {
+                assert!(zone.remove(record), "{record} not in zone");
+            }
+            zone.insert(diff.new_soa().clone());
+            zone.extend(diff.added().iter().cloned());
+        }
+        zone
+    }
+
+    #[tokio::test]
+    async fn test_ixfr_apply_diffs() {
+        let stream = get_stream_testcase(ixfr_response());
+        let response = ClientIxfr::new(stream, 1).await.unwrap();
+
+        let IxfrResponse::Incremental(diffs) = response else {
+            panic!("expected incremental response: {response:?}");
+        };
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(diffs[0].old_serial(), 1);
+        assert_eq!(diffs[0].new_serial(), 2);
+        assert_eq!(diffs[0].deleted().len(), 1);
+        assert_eq!(diffs[0].added().len(), 2);
+        assert_eq!(diffs[1].old_serial(), 2);
+        assert_eq!(diffs[1].new_serial(), 3);
+
+        let zone = apply(zone_v1(), &diffs);
+        assert_eq!(zone, zone_v3().into_iter().collect());
+    }
+
+    #[test]
+    fn test_ixfr_condensed() {
+        let response = IxfrResponse::from_answers(condensed_ixfr_response(), 1).unwrap();
+
+        let IxfrResponse::Incremental(diffs) = response else {
+            panic!("expected incremental response: {response:?}");
+        };
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].old_serial(), 1);
+        assert_eq!(diffs[0].new_serial(), 3);
+
+        let zone = apply(zone_v1(), &diffs);
+        assert_eq!(zone, zone_v3().into_iter().collect());
+    }
+
+    #[tokio::test]
+    async fn test_ixfr_full_zone() {
+        let mut axfr = zone_v3();
+        axfr.push(soa_record(3));
+        let stream = get_stream_testcase(vec![axfr[..2].to_vec(), axfr[2..].to_vec()]);
+
+        let response = ClientIxfr::new(stream, 1).await.unwrap();
+        assert_eq!(response, IxfrResponse::Full(zone_v3()));
+    }
+
+    #[tokio::test]
+    async fn test_ixfr_up_to_date() {
+        // the trailing message would fail the transfer if it were read
+        let stream = get_stream_testcase(vec![
+            vec![soa_record(3)],
+            vec![soa_record(3), a_record("NS.JAIN.AD.JP.", [133, 69, 136, 1])],
+        ]);
+
+        let response = ClientIxfr::new(stream, 3).await.unwrap();
+        assert_eq!(response, IxfrResponse::UpToDate(soa_record(3)));
+    }
+
+    #[test]
+    fn test_ixfr_invalid() {
+        // only a newer SOA, the client should fall back to AXFR
+        IxfrResponse::from_answers(vec![soa_record(3)], 1).unwrap_err();
+
+        // starting at another serial than requested
+        IxfrResponse::from_answers(condensed_ixfr_response(), 2).unwrap_err();
+
+        // truncated
+        let mut answers = condensed_ixfr_response();
+        answers.pop();
+        IxfrResponse::from_answers(answers, 1).unwrap_err();
+
+        // gap between differences
+        let mut answers = ixfr_response().concat();
+        answers[6] = soa_record(5);
+        IxfrResponse::from_answers(answers, 1).unwrap_err();
+
+        IxfrResponse::from_answers(vec![], 1).unwrap_err();
+        IxfrResponse::from_answers(vec![a_record("NS.JAIN.AD.JP.", [133, 69, 136, 1])], 1)
+            .unwrap_err();
+    }
+}
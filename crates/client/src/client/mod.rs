@@ -21,11 +21,15 @@ pub(crate) mod client;
 #[cfg(feature = "dnssec-ring")]
 pub(crate) mod dnssec_client;
 
+mod axfr;
+mod ixfr;
 mod memoize_client_handle;
 mod rc_stream;
 
+pub use self::axfr::ClientAxfr;
 #[allow(deprecated)]
 pub use self::client::{Client, ClientFuture, ClientHandle, ClientStreamingResponse};
 #[cfg(feature = "dnssec-ring")]
 pub use self::dnssec_client::{AsyncSecureClientBuilder, DnssecClient};
+pub use self::ixfr::{ClientIxfr, IxfrDiff, IxfrResponse};
 pub use self::memoize_client_handle::MemoizeClientHandle;
@@ -0,0 +1,41 @@
+#![cfg(nightly)]
+#![feature(test)]
+
+extern crate test;
+
+use test::Bencher;
+
+use hickory_proto::op::{Message, Query};
+use hickory_proto::rr::{Name, RecordType};
+use hickory_proto::serialize::binary::BinEncodable;
+use hickory_proto::udp::{BufferPool, MAX_RECEIVE_BUFFER_SIZE};
+
+fn response() -> Vec<u8> {
+    let mut message = Message::new();
+    message.add_query(Query::query(
+        Name::from_ascii("www.example.com.").unwrap(),
+        RecordType::A,
+    ));
+    message.to_bytes().unwrap()
+}
+
+#[bench]
+fn udp_receive_allocated_buffer(b: &mut Bencher) {
+    let response = response();
+    b.iter(|| {
+        let mut buf = vec![0; MAX_RECEIVE_BUFFER_SIZE];
+        buf[..response.len()].copy_from_slice(&response);
+        Message::from_vec(&buf[..response.len()]).unwrap()
+    });
+}
+
+#[bench]
+fn udp_receive_pooled_buffer(b: &mut Bencher) {
+    let response = response();
+    let pool = BufferPool::new(1);
+    b.iter(|| {
+        let mut buf = pool.checkout(MAX_RECEIVE_BUFFER_SIZE);
+        buf[..response.len()].copy_from_slice(&response);
+        Message::from_vec(&buf[..response.len()]).unwrap()
+    });
+}
@@ -0,0 +1,56 @@
+#![cfg(nightly)]
+#![feature(test)]
+
+extern crate test;
+
+use test::Bencher;
+
+use hickory_proto::rr::Name;
+use hickory_proto::serialize::binary::wire::WireName;
+use hickory_proto::serialize::binary::{BinDecodable, BinDecoder};
+
+/// www.example.com. at 0, mail.example.com. at 17 pointing into it, and a pointer to it at 24
+const NAMES: &[u8] = b"\x03www\x07example\x03com\x00\x04mail\xC0\x04\xC0\x00";
+
+/// A pointer pointing to itself
+const LOOP: &[u8] = b"\x03www\xC0\x00";
+
+#[bench]
+fn name_read_uncompressed(b: &mut Bencher) {
+    b.iter(|| Name::read(&mut BinDecoder::new(NAMES)).unwrap());
+}
+
+#[bench]
+fn wire_name_read_uncompressed(b: &mut Bencher) {
+    b.iter(|| WireName::read(&mut BinDecoder::new(NAMES)).unwrap());
+}
+
+#[bench]
+fn name_read_compressed(b: &mut Bencher) {
+    b.iter(|| Name::read(&mut BinDecoder::new(NAMES).clone(17)).unwrap());
+}
+
+#[bench]
+fn wire_name_read_compressed(b: &mut Bencher) {
+    b.iter(|| WireName::read(&mut BinDecoder::new(NAMES).clone(17)).unwrap());
+}
+
+#[bench]
+fn name_read_pointer(b: &mut Bencher) {
+    b.iter(|| Name::read(&mut BinDecoder::new(NAMES).clone(24)).unwrap());
+}
+
+#[bench]
+fn wire_name_read_pointer(b: &mut Bencher) {
+    b.iter(|| WireName::read(&mut BinDecoder::new(NAMES).clone(24)).unwrap());
+}
+
+#[bench]
+fn name_read_loop(b: &mut Bencher) {
+    b.iter(|| Name::read(&mut BinDecoder::new(LOOP)).unwrap_err());
+}
+
+#[bench]
+fn wire_name_read_loop(b: &mut Bencher) {
+    b.iter(|| WireName::read(&mut BinDecoder::new(LOOP)).unwrap_err());
+}
@@ -36,7 +36,8 @@ use crate::{
 use self::rrset::Rrset;
 
 mod nsec3_validation;
-use nsec3_validation::verify_nsec3;
+#[doc(hidden)]
+pub use nsec3_validation::verify_nsec3;
 
 /// Performs DNSSEC validation of all DNS responses from the wrapped DnsHandle
 ///
@@ -54,6 +55,7 @@ where
     request_depth: usize,
     minimum_key_len: usize,
     minimum_algorithm: Algorithm, // used to prevent down grade attacks...
+    max_nsec3_iterations: u16,
 }
 
 impl<H> DnssecDnsHandle<H>
@@ -84,9 +86,19 @@ where
             request_depth: 0,
             minimum_key_len: 0,
             minimum_algorithm: Algorithm::RSASHA256,
+            max_nsec3_iterations: DEFAULT_MAX_NSEC3_ITERATIONS,
         }
     }
 
+    /// Set the maximum number of NSEC3 hash iterations accepted in a denial of existence proof.
+    ///
+    /// Responses with NSEC3 records exceeding this limit are treated as bogus. Defaults to 150,
+    /// see [RFC 9276 section 3.2](https://www.rfc-editor.org/rfc/rfc9276#section-3.2).
+    pub fn max_nsec3_iterations(mut self, max_nsec3_iterations: u16) -> Self {
+        self.max_nsec3_iterations = max_nsec3_iterations;
+        self
+    }
+
     /// An internal function used to clone the handle, but maintain some information back to the
     ///  original handle, such as the request_depth such that infinite recursion does
     ///  not occur.
@@ -97,6 +109,7 @@ where
             request_depth: self.request_depth + 1,
             minimum_key_len: self.minimum_key_len,
             minimum_algorithm: self.minimum_algorithm,
+            max_nsec3_iterations: self.max_nsec3_iterations,
         }
     }
 }
@@ -151,6 +164,7 @@ where
         request.set_authentic_data(true);
         request.set_checking_disabled(false);
         let options = *request.options();
+        let max_nsec3_iterations = self.max_nsec3_iterations;
 
         Box::pin(
             self.handle
@@ -193,7 +207,7 @@ where
                     verify_response(handle.clone(), message_response, options)
                 })
                 .and_then(move |verified_message| {
-                    future::ready(check_nsec(verified_message, &query))
+                    future::ready(check_nsec(verified_message, &query, max_nsec3_iterations))
                 }),
         )
     }
@@ -204,7 +218,11 @@ where
 ///
 /// at this point all of the message is verified.
 /// This is where NSEC and NSEC3 validation occurs
-fn check_nsec(verified_message: DnsResponse, query: &Query) -> Result<DnsResponse, ProtoError> {
+fn check_nsec(
+    verified_message: DnsResponse,
+    query: &Query,
+    max_nsec3_iterations: u16,
+) -> Result<DnsResponse, ProtoError> {
     if !verified_message.answers().is_empty() {
         return Ok(verified_message);
     }
@@ -259,6 +277,7 @@ fn check_nsec(verified_message: DnsResponse, query: &Query) -> Result<DnsRespons
             verified_message.response_code(),
             verified_message.answers(),
             &nsec3s,
+            max_nsec3_iterations,
         ),
         (false, true) => verify_nsec(query, soa_name, nsecs.as_slice()),
         (true, true) => {
@@ -1294,3 +1313,6 @@ const MAX_KEY_TAG_COLLISIONS: usize = 2;
 
 /// The maximum number of RRSIGs to attempt to validate for each RRSET.
 const MAX_RRSIGS_PER_RRSET: usize = 8;
+
+/// The default maximum number of NSEC3 hash iterations to accept, RFC 9276 section 3.2.
+const DEFAULT_MAX_NSEC3_ITERATIONS: u16 = 150;
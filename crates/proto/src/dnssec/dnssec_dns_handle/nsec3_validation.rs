@@ -83,12 +83,15 @@ use crate::{
     rr::{Name, Record, RecordType},
 };
 
-pub(super) fn verify_nsec3(
+/// Verifies the NSEC3 records proving the negative response to the query, with the `response_code`
+#[doc(hidden)]
+pub fn verify_nsec3(
     query: &Query,
     soa_name: &Name,
     response_code: ResponseCode,
     answers: &[Record],
     nsec3s: &[(&Name, &NSEC3)],
+    max_iterations: u16,
 ) -> Proof {
     debug_assert!(!nsec3s.is_empty());
 
@@ -98,12 +101,21 @@ pub(super) fn verify_nsec3(
     let nsec3s: Option<Vec<Nsec3RecordPair<'_>>> = nsec3s
         .iter()
         .map(|(record_name, nsec3_data)| {
-            split_first_label(record_name)
-                .filter(|(_, base)| base == soa_name)
-                .map(|(base32_hashed_name, _)| Nsec3RecordPair {
-                    base32_hashed_name,
-                    nsec3_data,
-                })
+            let (label, base) = split_first_label(record_name)?;
+            if &base != soa_name {
+                return None;
+            }
+
+            // Owner names are case insensitive, but the hashes are compared bytewise
+            let base32_hashed_name = label.to_ascii_lowercase();
+            let hashed_name = data_encoding::BASE32_DNSSEC
+                .decode(&base32_hashed_name)
+                .ok()?;
+            Some(Nsec3RecordPair {
+                base32_hashed_name,
+                hashed_name,
+                nsec3_data,
+            })
         })
         .collect();
 
@@ -132,6 +144,17 @@ pub(super) fn verify_nsec3(
         return proof_log_yield(Proof::Bogus, query.name(), "nsec3", "parameter mismatch");
     }
 
+    // RFC 9276 3.2 - every additional iteration multiplies the cost of validating each name,
+    // so refuse to do an unbounded amount of work on behalf of the zone
+    if iterations > max_iterations {
+        return proof_log_yield(
+            Proof::Bogus,
+            query.name(),
+            "nsec3",
+            &format!("iterations ({iterations}) exceed limit ({max_iterations})"),
+        );
+    }
+
     // Basic sanity checks are done.
 
     let query_name = query.name();
@@ -178,7 +201,8 @@ pub(super) fn verify_nsec3(
 }
 
 struct Nsec3RecordPair<'a> {
-    base32_hashed_name: &'a [u8],
+    base32_hashed_name: Vec<u8>,
+    hashed_name: Vec<u8>,
     nsec3_data: &'a NSEC3,
 }
 
@@ -211,19 +235,17 @@ struct HashedNameInfo {
 fn find_covering_record<'a>(
     nsec3s: &'a [Nsec3RecordPair<'a>],
     target_hashed_name: &[u8],
-    // Strictly speaking we don't need this parameter, we can calculate
-    // base32(target_hashed_name) inside the function.
-    // However, we already have it available at call sites, may as well use
-    // it and save on repeated base32 encodings.
-    target_base32_hashed_name: &str,
 ) -> Option<&'a Nsec3RecordPair<'a>> {
-    nsec3s
-        .iter()
-        .find(|record| {
-            record.base32_hashed_name < target_base32_hashed_name.as_bytes()
-                && target_hashed_name < record.nsec3_data.next_hashed_owner_name()
-        })
-        .or_else(|| nsec3s.iter().max_by_key(|record| record.base32_hashed_name))
+    nsec3s.iter().find(|record| {
+        let owner = record.hashed_name.as_slice();
+        let next = record.nsec3_data.next_hashed_owner_name();
+        if owner < next {
+            owner < target_hashed_name && target_hashed_name < next
+        } else {
+            // The last record in the chain wraps around to the first one
+            owner < target_hashed_name || target_hashed_name < next
+        }
+    })
 }
 
 /// There is no such `query_name` in the zone and there's no wildcard that
@@ -275,28 +297,41 @@ fn validate_nxdomain_response(
         closest_encloser_wildcard,
     } = closest_encloser_proof_info;
 
-    match (closest_encloser, next_closer, closest_encloser_wildcard) {
-        // Got all three components - we proved that there's no `query_name`
-        // in the zone
-        (Some(_), Some(_), Some(_)) => {
-            proof_log_yield(Proof::Secure, query_name, "nsec3", "direct proof")
-        }
-        // `query_name`'s parent is the `soa_name` itself, so there's no need
-        // to send `soa_name`'s NSEC3 record. Still we have to show that
-        // both `query_name` doesn't exist and there's no wildcard to service it
-        (None, Some(_), Some(_)) if &query_name.base_name() == soa_name => proof_log_yield(
-            Proof::Secure,
-            query_name,
-            "nsec3",
-            "no direct or wildcard proof, but parent name of query is SOA",
-        ),
-        _ => proof_log_yield(
-            Proof::Bogus,
+    let (next_closer_record, reason) =
+        match (closest_encloser, next_closer, closest_encloser_wildcard) {
+            // Got all three components - we proved that there's no `query_name`
+            // in the zone
+            (Some(_), Some((_, record)), Some(_)) => (record, "direct proof"),
+            // `query_name`'s parent is the `soa_name` itself, so there's no need
+            // to send `soa_name`'s NSEC3 record. Still we have to show that
+            // both `query_name` doesn't exist and there's no wildcard to service it
+            (None, Some((_, record)), Some(_)) if &query_name.base_name() == soa_name => (
+                record,
+                "no direct or wildcard proof, but parent name of query is SOA",
+            ),
+            _ => {
+                return proof_log_yield(
+                    Proof::Bogus,
+                    query_name,
+                    "nsec3",
+                    "no proof of non-existence",
+                )
+            }
+        };
+
+    // RFC 5155 9.2 - an Opt-Out NSEC3 record covering the next closer name
+    // may be hiding an insecure delegation, so it does not prove that
+    // `query_name` doesn't exist
+    if next_closer_record.nsec3_data.opt_out() {
+        return proof_log_yield(
+            Proof::Insecure,
             query_name,
             "nsec3",
-            "no proof of non-existence",
-        ),
+            "next closer covered by opt-out record",
+        );
     }
+
+    proof_log_yield(Proof::Secure, query_name, "nsec3", reason)
 }
 
 struct ClosestEncloserProofInfo<'a> {
@@ -391,12 +426,8 @@ fn closest_encloser_proof<'a>(
 
             let next_closer_hash_info =
                 closest_encloser_candidates.swap_remove(closest_encloser_index - 1);
-            let next_closer = find_covering_record(
-                nsec3s,
-                &next_closer_hash_info.hashed_name,
-                &next_closer_hash_info.base32_hashed_name,
-            )
-            .map(|record| (next_closer_hash_info, record));
+            let next_closer = find_covering_record(nsec3s, &next_closer_hash_info.hashed_name)
+                .map(|record| (next_closer_hash_info, record));
 
             let closest_encloser_wildcard_hashed_name =
                 nsec3hash(&closest_encloser_wildcard_name, salt, iterations);
@@ -407,12 +438,8 @@ fn closest_encloser_proof<'a>(
                 hashed_name: closest_encloser_wildcard_hashed_name,
                 base32_hashed_name: closest_encloser_wildcard_base32_hashed_name,
             };
-            let wildcard = find_covering_record(
-                nsec3s,
-                &wildcard_name_info.hashed_name,
-                &wildcard_name_info.base32_hashed_name,
-            )
-            .map(|record| (wildcard_name_info, record));
+            let wildcard = find_covering_record(nsec3s, &wildcard_name_info.hashed_name)
+                .map(|record| (wildcard_name_info, record));
 
             (
                 ClosestEncloserProofInfo {
@@ -447,12 +474,8 @@ fn closest_encloser_proof<'a>(
             //
             // Next closer *is* `query_name`, hence index 0
             let next_encloser_hash_info = closest_encloser_candidates.swap_remove(0);
-            let next_closer = find_covering_record(
-                nsec3s,
-                &next_encloser_hash_info.hashed_name,
-                &next_encloser_hash_info.base32_hashed_name,
-            )
-            .map(|record| (next_encloser_hash_info, record));
+            let next_closer = find_covering_record(nsec3s, &next_encloser_hash_info.hashed_name)
+                .map(|record| (next_encloser_hash_info, record));
 
             // Additionally there should be an NSEC3 record *covering*
             // `*.soa_name` wildcard.
@@ -472,12 +495,8 @@ fn closest_encloser_proof<'a>(
                 hashed_name: closest_encloser_wildcard_hashed_name,
                 base32_hashed_name: closest_encloser_wildcard_base32_hashed_name,
             };
-            let wildcard = find_covering_record(
-                nsec3s,
-                &wildcard_name_info.hashed_name,
-                &wildcard_name_info.base32_hashed_name,
-            )
-            .map(|record| (wildcard_name_info, record));
+            let wildcard = find_covering_record(nsec3s, &wildcard_name_info.hashed_name)
+                .map(|record| (wildcard_name_info, record));
 
             (
                 ClosestEncloserProofInfo {
@@ -639,19 +658,28 @@ fn validate_nodata_response(
     // *Note*: the case of an opt-out NSEC3 record having the same original owner
     // name as the hashed query name and not having the DS bit set in the type flags
     // is covered here by case 2.
-    if query_type == RecordType::DS
-        && find_covering_record(nsec3s, &hashed_query_name, &base32_hashed_query_name[..])
-            .iter()
-            .all(|x| {
-                x.nsec3_data.type_bit_maps().contains(&RecordType::DS) && x.nsec3_data.opt_out()
-            })
-    {
-        return proof_log_yield(
-            Proof::Insecure,
-            query_name,
-            "nsec3",
-            "DS query covered by opt-out proof",
-        );
+    //
+    // Otherwise, as required by RFC 5155 8.6 above, there must be a closest
+    // provable encloser proof with an Opt-Out record covering the next closer.
+    if query_type == RecordType::DS {
+        let (closest_encloser_proof_info, early_proof) =
+            closest_encloser_proof(query_name, soa_name, nsec3s);
+        let provable_encloser = closest_encloser_proof_info.closest_encloser.is_some()
+            || &query_name.base_name() == soa_name;
+        if let (None, true, Some((_, next_closer_record))) = (
+            early_proof,
+            provable_encloser,
+            closest_encloser_proof_info.next_closer,
+        ) {
+            if next_closer_record.nsec3_data.opt_out() {
+                return proof_log_yield(
+                    Proof::Insecure,
+                    query_name,
+                    "nsec3",
+                    "DS query covered by opt-out proof",
+                );
+            }
+        }
     }
 
     let (proof, reason) = match wildcard_encloser_num_labels {
@@ -680,13 +708,7 @@ fn validate_nodata_response(
             let next_closer_name = Name::from_labels(next_closer_labels)
                 .expect("next closer is `query_name` or its ancestor");
             let next_closer_hashed_name = nsec3hash(&next_closer_name, salt, iterations);
-            let next_closer_base32_hashed_name =
-                data_encoding::BASE32_DNSSEC.encode(&next_closer_hashed_name);
-            let next_closer_record = find_covering_record(
-                nsec3s,
-                &next_closer_hashed_name,
-                &next_closer_base32_hashed_name,
-            );
+            let next_closer_record = find_covering_record(nsec3s, &next_closer_hashed_name);
             match next_closer_record {
                 Some(_) => (ds_proof_override, "matching next closer record"),
                 None => (Proof::Bogus, "no matching next closer record"),
@@ -702,6 +724,16 @@ fn validate_nodata_response(
                 closest_encloser_wildcard,
             } = wildcard_based_encloser_proof(query_name, soa_name, nsec3s);
             match (closest_encloser, next_closer, closest_encloser_wildcard) {
+                // RFC 5155 8.7 - the wildcard must not have the QTYPE or CNAME either
+                (_, _, Some((_, wildcard)))
+                    if wildcard.nsec3_data.type_bit_maps().contains(&query_type)
+                        || wildcard
+                            .nsec3_data
+                            .type_bit_maps()
+                            .contains(&RecordType::CNAME) =>
+                {
+                    (Proof::Bogus, "servicing wildcard type map covers query type or CNAME")
+                }
                 (Some(_), Some(_), Some(_)) => (
                     ds_proof_override,
                     "servicing wildcard with closest encloser proof"
@@ -816,25 +848,420 @@ fn wildcard_based_encloser_proof<'a>(
     debug_assert!(closest_encloser_index >= 1);
     let closest_encloser_name_info =
         closest_encloser_candidates.swap_remove(closest_encloser_index);
-    let closest_encloser_covering_record = find_covering_record(
-        nsec3s,
-        &closest_encloser_name_info.hashed_name,
-        &closest_encloser_name_info.base32_hashed_name,
-    );
+    let closest_encloser_matching_record = nsec3s.iter().find(|record| {
+        record.base32_hashed_name == closest_encloser_name_info.base32_hashed_name.as_bytes()
+    });
 
     // Since `closest_encloser_index` is >= 1, this is always valid.
     let next_closer_index = closest_encloser_index - 1;
     let next_closer_name_info = closest_encloser_candidates.swap_remove(next_closer_index);
-    let next_closer_covering_record = find_covering_record(
-        nsec3s,
-        &next_closer_name_info.hashed_name,
-        &next_closer_name_info.base32_hashed_name,
-    );
+    let next_closer_covering_record =
+        find_covering_record(nsec3s, &next_closer_name_info.hashed_name);
 
     ClosestEncloserProofInfo {
-        closest_encloser: closest_encloser_covering_record
+        closest_encloser: closest_encloser_matching_record
             .map(|record| (closest_encloser_name_info, record)),
         next_closer: next_closer_covering_record.map(|record| (next_closer_name_info, record)),
         closest_encloser_wildcard: wildcard_encloser,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dnssec::{rdata::RRSIG, Algorithm};
+    use crate::rr::RecordData;
+
+    /// The NSEC3 chain of the signed zone from RFC 5155 Appendix A,
+    /// hashed with salt `aabbccdd` and 12 iterations.
+    const EXAMPLE_CHAIN: &[(&str, &[RecordType])] = &[
+        (
+            "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom", // example
+            &[
+                RecordType::SOA,
+                RecordType::NS,
+                RecordType::MX,
+                RecordType::DNSKEY,
+                RecordType::NSEC3PARAM,
+                RecordType::RRSIG,
+            ],
+        ),
+        (
+            "2t7b4g4vsa5smi47k61mv5bv1a22bojr", // ns1.example
+            &[RecordType::A, RecordType::RRSIG],
+        ),
+        (
+            "2vptu5timamqttgl4luu9kg21e0aor3s", // x.y.w.example
+            &[RecordType::MX, RecordType::RRSIG],
+        ),
+        (
+            "35mthgpgcu1qg68fab165klnsnk3dpvl", // a.example
+            &[RecordType::NS, RecordType::DS, RecordType::RRSIG],
+        ),
+        (
+            "b4um86eghhds6nea196smvmlo4ors995", // x.w.example
+            &[RecordType::MX, RecordType::RRSIG],
+        ),
+        (
+            "gjeqe526plbf1g8mklp59enfd789njgi", // ai.example
+            &[
+                RecordType::A,
+                RecordType::HINFO,
+                RecordType::AAAA,
+                RecordType::RRSIG,
+            ],
+        ),
+        (
+            "ji6neoaepv8b5o6k4ev33abha8ht9fgc", // y.w.example
+            &[],
+        ),
+        (
+            "k8udemvp1j2f7eg6jebps17vp3n8i58h", // w.example
+            &[],
+        ),
+        (
+            "q04jkcevqvmu85r014c7dkba38o0ji5r", // ns2.example
+            &[RecordType::A, RecordType::RRSIG],
+        ),
+        (
+            "r53bq7cc2uvmubfu5ocmm6pers9tk9en", // *.w.example
+            &[RecordType::MX, RecordType::RRSIG],
+        ),
+        (
+            "t644ebqk9bibcna874givr6joj62mlhv", // xx.example
+            &[
+                RecordType::A,
+                RecordType::HINFO,
+                RecordType::AAAA,
+                RecordType::RRSIG,
+            ],
+        ),
+    ];
+
+    /// Builds the NSEC3 records of `EXAMPLE_CHAIN` whose hashes start with one of `prefixes`.
+    fn example_nsec3s(prefixes: &[&str], opt_out: bool, iterations: u16) -> Vec<(Name, NSEC3)> {
+        let soa_name = Name::from_ascii("example.").unwrap();
+        EXAMPLE_CHAIN
+            .iter()
+            .enumerate()
+            .filter(|(_, (hash, _))| prefixes.iter().any(|prefix| hash.starts_with(prefix)))
+            .map(|(i, (hash, types))| {
+                let (next, _) = EXAMPLE_CHAIN[(i + 1) % EXAMPLE_CHAIN.len()];
+                let name = Name::from_ascii(hash).unwrap().append_domain(&soa_name);
+                let nsec3 = NSEC3::new(
+                    Nsec3HashAlgorithm::SHA1,
+                    opt_out,
+                    iterations,
+                    vec![0xaa, 0xbb, 0xcc, 0xdd],
+                    data_encoding::BASE32_DNSSEC
+                        .decode(next.as_bytes())
+                        .unwrap(),
+                    types.to_vec(),
+                );
+                (name.unwrap(), nsec3)
+            })
+            .collect()
+    }
+
+    fn verify(
+        name: &str,
+        query_type: RecordType,
+        response_code: ResponseCode,
+        answers: &[Record],
+        nsec3s: &[(Name, NSEC3)],
+    ) -> Proof {
+        let query = Query::query(Name::from_ascii(name).unwrap(), query_type);
+        let soa_name = Name::from_ascii("example.").unwrap();
+        let nsec3s = nsec3s
+            .iter()
+            .map(|(name, nsec3)| (name, nsec3))
+            .collect::<Vec<_>>();
+        verify_nsec3(&query, &soa_name, response_code, answers, &nsec3s, 150)
+    }
+
+    #[test]
+    fn test_nxdomain() {
+        // RFC 5155 B.1, closest encloser x.w.example
+        let nsec3s = example_nsec3s(&["0p9m", "b4um", "35mt"], false, 12);
+        assert_eq!(
+            verify(
+                "a.c.x.w.example.",
+                RecordType::A,
+                ResponseCode::NXDomain,
+                &[],
+                &nsec3s
+            ),
+            Proof::Secure
+        );
+    }
+
+    #[test]
+    fn test_nxdomain_wrap_around() {
+        // h(f.example) sorts after the last hash in the chain
+        let nsec3s = example_nsec3s(&["0p9m", "t644", "gjeq"], false, 12);
+        assert_eq!(
+            verify(
+                "f.example.",
+                RecordType::A,
+                ResponseCode::NXDomain,
+                &[],
+                &nsec3s
+            ),
+            Proof::Secure
+        );
+    }
+
+    #[test]
+    fn test_nxdomain_uppercase_owner() {
+        let nsec3s = example_nsec3s(&["0p9m", "b4um", "35mt"], false, 12)
+            .into_iter()
+            .map(|(name, nsec3)| {
+                (
+                    Name::from_ascii(name.to_string().to_uppercase()).unwrap(),
+                    nsec3,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            verify(
+                "a.c.x.w.example.",
+                RecordType::A,
+                ResponseCode::NXDomain,
+                &[],
+                &nsec3s
+            ),
+            Proof::Secure
+        );
+    }
+
+    #[test]
+    fn test_nxdomain_missing_next_closer() {
+        let nsec3s = example_nsec3s(&["0p9m", "b4um"], false, 12);
+        assert_eq!(
+            verify(
+                "a.c.x.w.example.",
+                RecordType::A,
+                ResponseCode::NXDomain,
+                &[],
+                &nsec3s
+            ),
+            Proof::Bogus
+        );
+    }
+
+    #[test]
+    fn test_nxdomain_missing_wildcard() {
+        let nsec3s = example_nsec3s(&["0p9m", "b4um"], false, 12);
+        assert_eq!(
+            verify(
+                "c.x.w.example.",
+                RecordType::A,
+                ResponseCode::NXDomain,
+                &[],
+                &nsec3s
+            ),
+            Proof::Bogus
+        );
+    }
+
+    #[test]
+    fn test_nxdomain_opt_out() {
+        // The next closer c.x.w.example is covered by an opt-out record
+        let nsec3s = example_nsec3s(&["0p9m", "b4um", "35mt"], true, 12);
+        assert_eq!(
+            verify(
+                "a.c.x.w.example.",
+                RecordType::A,
+                ResponseCode::NXDomain,
+                &[],
+                &nsec3s
+            ),
+            Proof::Insecure
+        );
+    }
+
+    #[test]
+    fn test_nodata() {
+        // RFC 5155 B.2
+        let nsec3s = example_nsec3s(&["2t7b"], false, 12);
+        assert_eq!(
+            verify(
+                "ns1.example.",
+                RecordType::MX,
+                ResponseCode::NoError,
+                &[],
+                &nsec3s
+            ),
+            Proof::Secure
+        );
+        assert_eq!(
+            verify(
+                "ns1.example.",
+                RecordType::A,
+                ResponseCode::NoError,
+                &[],
+                &nsec3s
+            ),
+            Proof::Bogus
+        );
+    }
+
+    #[test]
+    fn test_nodata_empty_non_terminal() {
+        // RFC 5155 B.2.1
+        let nsec3s = example_nsec3s(&["ji6n"], false, 12);
+        assert_eq!(
+            verify(
+                "y.w.example.",
+                RecordType::A,
+                ResponseCode::NoError,
+                &[],
+                &nsec3s
+            ),
+            Proof::Secure
+        );
+    }
+
+    #[test]
+    fn test_ds_opt_out() {
+        // RFC 5155 B.3, c.example is an insecure delegation without an NSEC3 record
+        let nsec3s = example_nsec3s(&["0p9m", "35mt"], true, 12);
+        assert_eq!(
+            verify(
+                "c.example.",
+                RecordType::DS,
+                ResponseCode::NoError,
+                &[],
+                &nsec3s
+            ),
+            Proof::Insecure
+        );
+
+        let nsec3s = example_nsec3s(&["0p9m", "35mt"], false, 12);
+        assert_eq!(
+            verify(
+                "c.example.",
+                RecordType::DS,
+                ResponseCode::NoError,
+                &[],
+                &nsec3s
+            ),
+            Proof::Bogus
+        );
+    }
+
+    #[test]
+    fn test_wildcard_expansion() {
+        // RFC 5155 B.4, the answer is synthesized from *.w.example
+        let rrsig = RRSIG::new(
+            RecordType::MX,
+            Algorithm::RSASHA256,
+            2,
+            3600,
+            0,
+            0,
+            0,
+            Name::from_ascii("example.").unwrap(),
+            vec![],
+        );
+        let answers = [Record::from_rdata(
+            Name::from_ascii("a.z.w.example.").unwrap(),
+            3600,
+            rrsig.into_rdata(),
+        )];
+
+        let nsec3s = example_nsec3s(&["q04j"], false, 12);
+        assert_eq!(
+            verify(
+                "a.z.w.example.",
+                RecordType::MX,
+                ResponseCode::NoError,
+                &answers,
+                &nsec3s
+            ),
+            Proof::Secure
+        );
+
+        // z.w.example is not covered by this record
+        let nsec3s = example_nsec3s(&["r53b"], false, 12);
+        assert_eq!(
+            verify(
+                "a.z.w.example.",
+                RecordType::MX,
+                ResponseCode::NoError,
+                &answers,
+                &nsec3s
+            ),
+            Proof::Bogus
+        );
+    }
+
+    #[test]
+    fn test_wildcard_nodata() {
+        // RFC 5155 B.5
+        let nsec3s = example_nsec3s(&["k8ud", "q04j", "r53b"], false, 12);
+        assert_eq!(
+            verify(
+                "a.z.w.example.",
+                RecordType::AAAA,
+                ResponseCode::NoError,
+                &[],
+                &nsec3s
+            ),
+            Proof::Secure
+        );
+
+        // the wildcard does have MX records
+        assert_eq!(
+            verify(
+                "a.z.w.example.",
+                RecordType::MX,
+                ResponseCode::NoError,
+                &[],
+                &nsec3s
+            ),
+            Proof::Bogus
+        );
+
+        // the closest encloser w.example has to be matched
+        let nsec3s = example_nsec3s(&["q04j", "r53b"], false, 12);
+        assert_eq!(
+            verify(
+                "a.z.w.example.",
+                RecordType::AAAA,
+                ResponseCode::NoError,
+                &[],
+                &nsec3s
+            ),
+            Proof::Bogus
+        );
+    }
+
+    #[test]
+    fn test_iterations_limit() {
+        let nsec3s = example_nsec3s(&["2t7b"], false, 151);
+        assert_eq!(
+            verify(
+                "ns1.example.",
+                RecordType::MX,
+                ResponseCode::NoError,
+                &[],
+                &nsec3s
+            ),
+            Proof::Bogus
+        );
+
+        let query = Query::query(Name::from_ascii("ns1.example.").unwrap(), RecordType::MX);
+        let soa_name = Name::from_ascii("example.").unwrap();
+        let nsec3s = example_nsec3s(&["2t7b"], false, 12);
+        let nsec3s = nsec3s.iter().map(|(n, r)| (n, r)).collect::<Vec<_>>();
+        assert_eq!(
+            verify_nsec3(&query, &soa_name, ResponseCode::NoError, &[], &nsec3s, 10),
+            Proof::Bogus
+        );
+        assert_eq!(
+            verify_nsec3(&query, &soa_name, ResponseCode::NoError, &[], &nsec3s, 12),
+            Proof::Secure
+        );
+    }
+}
@@ -19,14 +19,15 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::error::{ProtoError, ProtoErrorKind};
+use crate::op::ResponseCode;
 #[cfg(feature = "backtrace")]
 use crate::trace;
 
 mod algorithm;
 mod dnssec_dns_handle;
-#[doc(hidden)]
-pub use dnssec_dns_handle::verify_nsec;
 pub use dnssec_dns_handle::DnssecDnsHandle;
+#[doc(hidden)]
+pub use dnssec_dns_handle::{verify_nsec, verify_nsec3};
 mod ec_public_key;
 mod nsec3;
 pub mod proof;
@@ -35,6 +36,7 @@ pub mod rdata;
 /// ring implementations of DNSSEC traits.
 #[cfg(feature = "dnssec-ring")]
 pub mod ring;
+mod rollover;
 mod rsa_public_key;
 mod signer;
 mod supported_algorithm;
@@ -47,10 +49,11 @@ pub use self::algorithm::Algorithm;
 pub use self::nsec3::Nsec3HashAlgorithm;
 pub use self::proof::{Proof, ProofError, ProofErrorKind, ProofFlags, Proven};
 pub use self::public_key::{PublicKey, PublicKeyBuf};
+pub use self::rollover::{KeyRollover, RolloverPhase, RolloverState, RolloverStrategy};
 pub use self::signer::SigSigner;
 pub use self::supported_algorithm::SupportedAlgorithms;
 pub use self::tbs::TBS;
-pub use self::trust_anchor::TrustAnchor;
+pub use self::trust_anchor::{TrustAnchor, TrustAnchorTracker, TrustPointState, DEFAULT_HOLD_DOWN};
 pub use self::verifier::Verifier;
 
 /// DNSSEC Delegation Signer (DS) Resource Record (RR) Type Digest Algorithms
@@ -251,6 +254,14 @@ pub enum DnsSecErrorKind {
     /// Tsig key verification failed
     #[error("Tsig key wrong key error")]
     TsigWrongKey,
+
+    /// The time of the Tsig is outside of the time window allowed by the fudge
+    #[error("Tsig signature out of time window")]
+    TsigBadTime,
+
+    /// The remote rejected the Tsig of the request, with BADSIG, BADKEY or BADTIME
+    #[error("Tsig rejected by remote: {0}")]
+    TsigRemoteError(ResponseCode),
 }
 
 impl Clone for DnsSecErrorKind {
@@ -269,6 +280,8 @@ impl Clone for DnsSecErrorKind {
             Timeout => Timeout,
             TsigUnsupportedMacAlgorithm(ref alg) => TsigUnsupportedMacAlgorithm(alg.clone()),
             TsigWrongKey => TsigWrongKey,
+            TsigBadTime => TsigBadTime,
+            TsigRemoteError(code) => TsigRemoteError(*code),
         }
     }
 }
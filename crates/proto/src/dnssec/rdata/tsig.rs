@@ -17,7 +17,7 @@ use super::DNSSECRData;
 use crate::{
     dnssec::{DnsSecError, DnsSecErrorKind},
     error::{ProtoError, ProtoResult},
-    op::{Header, Message, Query},
+    op::{Header, Message, Query, ResponseCode},
     rr::{
         dns_class::DNSClass, rdata::sshfp, record_data::RData, record_type::RecordType, Name,
         Record, RecordData, RecordDataDecodable,
@@ -265,6 +265,21 @@ impl TSIG {
         &self.algorithm
     }
 
+    /// Returns the id of the message this TSIG was originally generated for
+    pub fn oid(&self) -> u16 {
+        self.oid
+    }
+
+    /// Returns the TSIG error, `NoError` unless the remote rejected the TSIG of the request
+    pub fn error(&self) -> ResponseCode {
+        self.error.into()
+    }
+
+    /// Returns the other data, the current time of the server for a `BADTIME` error
+    pub fn other(&self) -> &[u8] {
+        &self.other
+    }
+
     /// Emit TSIG RR and RDATA as used for computing MAC
     ///
     /// ```text
@@ -628,7 +643,8 @@ pub fn message_tbs<M: BinEncodable>(
         encoder.emit_u16(previous_hash.len() as u16)?;
         encoder.emit_vec(previous_hash)?;
     };
-    message.emit(&mut encoder)?;
+    // encoded on its own, so that name compression matches the transmitted message
+    encoder.emit_vec(&message.to_bytes()?)?;
     pre_tsig.emit_tsig_for_mac(&mut encoder, key_name)?;
     Ok(buf)
 }
@@ -1,8 +1,15 @@
 use std::{borrow::Cow, sync::Arc};
 
+use ed448_goldilocks_plus::{
+    self as ed448,
+    elliptic_curve::pkcs8::{
+        der::{asn1::OctetStringRef, Decode},
+        EncodePrivateKey, PrivateKeyInfo,
+    },
+};
 use ring::{
     digest,
-    rand::{self, SystemRandom},
+    rand::{self, SecureRandom, SystemRandom},
     rsa::PublicKeyComponents,
     signature::{
         self, EcdsaKeyPair, Ed25519KeyPair, KeyPair as RingKeyPair, RsaKeyPair,
@@ -33,6 +40,7 @@ pub fn signing_key_from_der(
             Ok(Box::new(EcdsaSigningKey::from_key_der(key_der, algorithm)?))
         }
         Algorithm::ED25519 => Ok(Box::new(Ed25519SigningKey::from_key_der(key_der)?)),
+        Algorithm::ED448 => Ok(Box::new(Ed448SigningKey::from_key_der(key_der)?)),
         e => Err(format!("unsupported SigningKey algorithm for ring: {e:?}").into()),
     }
 }
@@ -53,6 +61,8 @@ pub(super) fn decode_public_key<'a>(
         #[cfg(feature = "dnssec-ring")]
         Algorithm::ED25519 => Ok(Arc::new(Ed25519::from_public_bytes(public_key.into())?)),
         #[cfg(feature = "dnssec-ring")]
+        Algorithm::ED448 => Ok(Arc::new(Ed448::from_public_bytes(public_key.into())?)),
+        #[cfg(feature = "dnssec-ring")]
         Algorithm::RSASHA1
         | Algorithm::RSASHA1NSEC3SHA1
         | Algorithm::RSASHA256
@@ -304,6 +314,124 @@ impl PublicKey for Ed25519<'_> {
     }
 }
 
+/// An Ed448 signing key pair (backed by ed448-goldilocks-plus, ring does not support Ed448).
+pub struct Ed448SigningKey {
+    inner: ed448::SigningKey,
+}
+
+impl Ed448SigningKey {
+    /// Decode signing key pair from DER.
+    pub fn from_key_der(key: &PrivateKeyDer<'_>) -> DnsSecResult<Self> {
+        match key {
+            PrivateKeyDer::Pkcs8(key) => Self::from_pkcs8(key),
+            _ => Err("unsupported key format (only PKCS#8 supported)".into()),
+        }
+    }
+
+    /// Decode signing key pair from DER-encoded PKCS#8 bytes.
+    ///
+    /// The private key is the 57 octets secret key of RFC 8032, wrapped in an OCTET STRING, see
+    /// [RFC 8410, section 7](https://tools.ietf.org/html/rfc8410#section-7).
+    pub fn from_pkcs8(key: &PrivatePkcs8KeyDer<'_>) -> DnsSecResult<Self> {
+        let info = PrivateKeyInfo::try_from(key.secret_pkcs8_der())
+            .map_err(|e| format!("invalid PKCS#8 key: {e}"))?;
+        if info.algorithm.oid != ed448::ALGORITHM_OID {
+            return Err(format!("not an Ed448 key: {}", info.algorithm.oid).into());
+        }
+
+        let secret_key = OctetStringRef::from_der(info.private_key)
+            .map_err(|e| format!("invalid Ed448 private key: {e}"))?;
+        let key = Self::from_secret_key(secret_key.as_bytes())?;
+        if let Some(public_key) = info.public_key {
+            if public_key != key.inner.verifying_key().as_bytes() {
+                return Err("the Ed448 public key does not match the private key".into());
+            }
+        }
+
+        Ok(key)
+    }
+
+    /// Creates an Ed448 keypair from the 57 octets secret key of RFC 8032.
+    pub fn from_secret_key(secret_key: &[u8]) -> DnsSecResult<Self> {
+        Ok(Self {
+            inner: ed448::SigningKey::try_from(secret_key)?,
+        })
+    }
+
+    /// Generate signing key pair and return the DER-encoded PKCS#8 bytes.
+    pub fn generate_pkcs8() -> DnsSecResult<PrivatePkcs8KeyDer<'static>> {
+        let mut secret_key = ed448::SecretKey::default();
+        SystemRandom::new().fill(&mut secret_key)?;
+        let pkcs8 = ed448::SigningKey::from(&secret_key)
+            .to_pkcs8_der()
+            .map_err(|e| format!("could not encode Ed448 key: {e}"))?;
+        Ok(PrivatePkcs8KeyDer::from(pkcs8.as_bytes().to_vec()))
+    }
+}
+
+impl SigningKey for Ed448SigningKey {
+    fn sign(&self, tbs: &TBS) -> DnsSecResult<Vec<u8>> {
+        Ok(self.inner.sign_raw(tbs.as_ref()).to_bytes().to_vec())
+    }
+
+    fn to_public_key(&self) -> DnsSecResult<PublicKeyBuf> {
+        Ok(PublicKeyBuf::new(
+            self.inner.verifying_key().to_bytes().to_vec(),
+            Algorithm::ED448,
+        ))
+    }
+
+    fn algorithm(&self) -> Algorithm {
+        Algorithm::ED448
+    }
+}
+
+/// Ed448 Public key
+pub struct Ed448<'k> {
+    raw: Cow<'k, [u8]>,
+}
+
+impl<'k> Ed448<'k> {
+    /// An Ed448 public key is the 57 octets value of
+    /// [RFC 8032, section 5.2.5](https://tools.ietf.org/html/rfc8032#section-5.2.5), stored as is
+    /// in the DNSKEY, see [RFC 8080, section 3](https://tools.ietf.org/html/rfc8080#section-3).
+    pub fn from_public_bytes(public_key: Cow<'k, [u8]>) -> ProtoResult<Self> {
+        if public_key.len() != ed448::PUBLIC_KEY_LENGTH {
+            return Err(format!(
+                "expected {} byte public_key: {}",
+                ed448::PUBLIC_KEY_LENGTH,
+                public_key.len()
+            )
+            .into());
+        }
+
+        Ok(Self { raw: public_key })
+    }
+}
+
+impl PublicKey for Ed448<'_> {
+    fn public_bytes(&self) -> &[u8] {
+        self.raw.as_ref()
+    }
+
+    fn verify(&self, message: &[u8], signature: &[u8]) -> ProtoResult<()> {
+        let mut public_key = [0; ed448::PUBLIC_KEY_LENGTH];
+        public_key.copy_from_slice(self.raw.as_ref());
+        let public_key = ed448::VerifyingKey::from_bytes(&public_key)
+            .map_err(|e| format!("invalid Ed448 public key: {e}"))?;
+        let signature = ed448::Signature::try_from(signature)
+            .map_err(|e| format!("invalid Ed448 signature: {e}"))?;
+
+        public_key
+            .verify_raw(&signature, message)
+            .map_err(|e| format!("Ed448 signature verification failed: {e}").into())
+    }
+
+    fn algorithm(&self) -> Algorithm {
+        Algorithm::ED448
+    }
+}
+
 /// Rsa public key
 pub struct Rsa<'k> {
     raw: &'k [u8],
@@ -567,6 +695,213 @@ mod tests {
         hash_test(&*key, &*neg);
     }
 
+    /// The examples of [RFC 8080, section 6](https://tools.ietf.org/html/rfc8080#section-6)
+    #[test]
+    fn test_ed25519_rfc8080() {
+        use data_encoding::{BASE64, HEXLOWER};
+
+        use crate::dnssec::rdata::{DNSKEY, RRSIG};
+        use crate::dnssec::Verifier;
+        use crate::rr::rdata::MX;
+        use crate::rr::{DNSClass, Name, RData, Record, RecordType};
+
+        // private key, public key, key tag, DS digest, RRSIG signature
+        let examples = [
+            (
+                "ODIyNjAzODQ2MjgwODAxMjI2NDUxOTAyMDQxNDIyNjI=",
+                "l02Woi0iS8Aa25FQkUd9RMzZHJpBoRQwAQEX1SxZJA4=",
+                3613,
+                "3aa5ab37efce57f737fc1627013fee07bdf241bd10f3b1964ab55c78e79a304b",
+                "oL9krJun7xfBOIWcGHi7mag5/hdZrKWw15jPGrHpjQeRAvTdszaPD+QLs3fx8A4M3e23mRZ9VrbpMngwcrqNAg==",
+            ),
+            (
+                "DSSF3o0s0f+ElWzj9E/Osxw8hLpk55chkmx0LYN5WiY=",
+                "zPnZ/QwEe7S8C5SPz2OfS5RR40ATk2/rYnE9xHIEijs=",
+                35217,
+                "401781b934e392de492ec77ae2e15d70f6575a1c0bc59c5275c04ebe80c6614c",
+                "zXQ0bkYgQTEFyfLyi9QoiY6D8ZdYo4wyUhVioYZXFdT410QPRITQSqJSnzQoSm5poJ7gD7AQR0O7KuI5k2pcBg==",
+            ),
+        ];
+
+        let name = Name::from_ascii("example.com.").unwrap();
+        let mx = Record::from_rdata(
+            name.clone(),
+            3600,
+            RData::MX(MX::new(10, Name::from_ascii("mail.example.com.").unwrap())),
+        );
+
+        for (private_key, public_key, key_tag, digest, signature) in examples {
+            let dnskey = DNSKEY::new(
+                true,
+                true,
+                false,
+                Algorithm::ED25519,
+                BASE64.decode(public_key.as_bytes()).unwrap(),
+            );
+            assert_eq!(dnskey.calculate_key_tag().unwrap(), key_tag);
+            assert_eq!(
+                HEXLOWER.encode(
+                    dnskey
+                        .to_digest(&name, DigestType::SHA256)
+                        .unwrap()
+                        .as_ref()
+                ),
+                digest
+            );
+
+            // the DNSKEY and RRSIG survive a round trip through the wire format
+            let signature = BASE64.decode(signature.as_bytes()).unwrap();
+            let rrsig = RRSIG::new(
+                RecordType::MX,
+                Algorithm::ED25519,
+                2,
+                3600,
+                1440021600,
+                1438207200,
+                key_tag,
+                name.clone(),
+                signature.clone(),
+            );
+            let dnskey = round_trip(dnskey);
+            let rrsig = round_trip(rrsig);
+            assert_eq!(rrsig.algorithm(), Algorithm::ED25519);
+            assert_eq!(rrsig.sig(), signature.as_slice());
+
+            dnskey
+                .verify_rrsig(&name, DNSClass::IN, &rrsig, [&mx].into_iter())
+                .expect("RFC 8080 signature should validate");
+
+            // Ed25519 signatures are deterministic, signing again gives the same signature
+            let seed = BASE64.decode(private_key.as_bytes()).unwrap();
+            let key_pair = Ed25519KeyPair::from_seed_unchecked(&seed).unwrap();
+            let key = Ed25519SigningKey::from_ed25519(key_pair);
+            assert_eq!(
+                key.to_public_key().unwrap().public_bytes(),
+                dnskey.public_key()
+            );
+
+            let tbs = TBS::from_sig(&name, DNSClass::IN, &rrsig, [&mx].into_iter()).unwrap();
+            assert_eq!(key.sign(&tbs).unwrap(), signature);
+        }
+    }
+
+    #[test]
+    fn test_ed448() {
+        let algorithm = Algorithm::ED448;
+        let pkcs8 = Ed448SigningKey::generate_pkcs8().unwrap();
+        let key = signing_key_from_der(&PrivateKeyDer::from(pkcs8), algorithm).unwrap();
+        public_key_test(&*key);
+
+        let neg_pkcs8 = Ed448SigningKey::generate_pkcs8().unwrap();
+        let neg = signing_key_from_der(&PrivateKeyDer::from(neg_pkcs8), algorithm).unwrap();
+        hash_test(&*key, &*neg);
+    }
+
+    /// The Ed448 examples of [RFC 8032, section 7.4](https://tools.ietf.org/html/rfc8032#section-7.4)
+    #[test]
+    fn test_ed448_rfc8032() {
+        use data_encoding::HEXLOWER;
+
+        // secret key, public key, message, signature
+        let examples = [
+            (
+                "6c82a562cb808d10d632be89c8513ebf6c929f34ddfa8c9f63c9960ef6e348a3528c8a3fcc2f044e39a3fc5b94492f8f032e7549a20098f95b",
+                "5fd7449b59b461fd2ce787ec616ad46a1da1342485a70e1f8a0ea75d80e96778edf124769b46c7061bd6783df1e50f6cd1fa1abeafe8256180",
+                "",
+                "533a37f6bbe457251f023c0d88f976ae2dfb504a843e34d2074fd823d41a591f2b233f034f628281f2fd7a22ddd47d7828c59bd0a21bfd3980ff0d2028d4b18a9df63e006c5d1c2d345b925d8dc00b4104852db99ac5c7cdda8530a113a0f4dbb61149f05a7363268c71d95808ff2e652600",
+            ),
+            (
+                "c4eab05d357007c632f3dbb48489924d552b08fe0c353a0d4a1f00acda2c463afbea67c5e8d2877c5e3bc397a659949ef8021e954e0a12274e",
+                "43ba28f430cdff456ae531545f7ecd0ac834a55d9358c0372bfa0c6c6798c0866aea01eb00742802b8438ea4cb82169c235160627b4c3a9480",
+                "03",
+                "26b8f91727bd62897af15e41eb43c377efb9c610d48f2335cb0bd0087810f4352541b143c4b981b7e18f62de8ccdf633fc1bf037ab7cd779805e0dbcc0aae1cbcee1afb2e027df36bc04dcecbf154336c19f0af7e0a6472905e799f1953d2a0ff3348ab21aa4adafd1d234441cf807c03a00",
+            ),
+        ];
+
+        for (secret_key, public_bytes, message, signature) in examples {
+            let key =
+                Ed448SigningKey::from_secret_key(&HEXLOWER.decode(secret_key.as_bytes()).unwrap())
+                    .unwrap();
+            let public_key = key.to_public_key().unwrap();
+            assert_eq!(HEXLOWER.encode(public_key.public_bytes()), public_bytes);
+
+            let message = TBS::from(&HEXLOWER.decode(message.as_bytes()).unwrap()[..]);
+            let signature = HEXLOWER.decode(signature.as_bytes()).unwrap();
+            assert_eq!(key.sign(&message).unwrap(), signature);
+            public_key.verify(message.as_ref(), &signature).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_ed448_rrsig() {
+        use data_encoding::HEXLOWER;
+
+        use crate::dnssec::rdata::{DNSKEY, RRSIG};
+        use crate::dnssec::Verifier;
+        use crate::rr::rdata::MX;
+        use crate::rr::{DNSClass, Name, RData, Record, RecordType};
+
+        let secret_key = HEXLOWER
+            .decode(b"6c82a562cb808d10d632be89c8513ebf6c929f34ddfa8c9f63c9960ef6e348a3528c8a3fcc2f044e39a3fc5b94492f8f032e7549a20098f95b")
+            .unwrap();
+        let key = Ed448SigningKey::from_secret_key(&secret_key).unwrap();
+
+        // Ed448 public keys are 57 octets, RFC 8080, section 3
+        let dnskey = DNSKEY::from_key(&key.to_public_key().unwrap());
+        assert_eq!(dnskey.algorithm(), Algorithm::ED448);
+        assert_eq!(dnskey.public_key().len(), 57);
+        let dnskey = round_trip(dnskey);
+
+        let name = Name::from_ascii("example.com.").unwrap();
+        let mx = Record::from_rdata(
+            name.clone(),
+            3600,
+            RData::MX(MX::new(10, Name::from_ascii("mail.example.com.").unwrap())),
+        );
+        let rrsig = |sig| {
+            RRSIG::new(
+                RecordType::MX,
+                Algorithm::ED448,
+                2,
+                3600,
+                1440021600,
+                1438207200,
+                dnskey.calculate_key_tag().unwrap(),
+                name.clone(),
+                sig,
+            )
+        };
+
+        // Ed448 signatures are 114 octets, RFC 8080, section 4
+        let tbs = TBS::from_sig(&name, DNSClass::IN, &rrsig(vec![]), [&mx].into_iter()).unwrap();
+        let signature = key.sign(&tbs).unwrap();
+        assert_eq!(signature.len(), 114);
+
+        let rrsig = round_trip(rrsig(signature));
+        dnskey
+            .verify_rrsig(&name, DNSClass::IN, &rrsig, [&mx].into_iter())
+            .expect("Ed448 signature should validate");
+
+        let other = Record::from_rdata(
+            name.clone(),
+            3600,
+            RData::MX(MX::new(20, Name::from_ascii("mail.example.com.").unwrap())),
+        );
+        assert!(dnskey
+            .verify_rrsig(&name, DNSClass::IN, &rrsig, [&other].into_iter())
+            .is_err());
+    }
+
+    /// Encodes the record data in a record, and decodes it again
+    fn round_trip<R: crate::rr::RecordData>(rdata: R) -> R {
+        use crate::rr::{Name, Record};
+        use crate::serialize::binary::{BinDecodable, BinEncodable};
+
+        let record = Record::from_rdata(Name::root(), 3600, rdata.into_rdata());
+        let record = Record::from_bytes(&record.to_bytes().unwrap()).unwrap();
+        R::try_from_rdata(record.into_data()).unwrap()
+    }
+
     #[test]
     fn test_rsa() {
         // ring currently does not support RSA key generation support.
@@ -597,6 +932,16 @@ mod tests {
         signing_key_from_der(&PrivateKeyDer::from(pkcs8), Algorithm::ED25519).unwrap();
     }
 
+    #[test]
+    fn test_ed448_encode_decode_pkcs8() {
+        let pkcs8 = Ed448SigningKey::generate_pkcs8().unwrap();
+        signing_key_from_der(&PrivateKeyDer::from(pkcs8), Algorithm::ED448).unwrap();
+
+        // an Ed25519 key is not an Ed448 key
+        let pkcs8 = Ed25519SigningKey::generate_pkcs8().unwrap();
+        assert!(signing_key_from_der(&PrivateKeyDer::from(pkcs8), Algorithm::ED448).is_err());
+    }
+
     #[test]
     fn test_rsasha256_encode_decode_pkcs8() {
         // ring currently does not support RSA key generation support.
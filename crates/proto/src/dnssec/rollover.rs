@@ -0,0 +1,324 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Scheduling of DNSSEC key rollovers, see [RFC 6781, section 4.1](https://tools.ietf.org/html/rfc6781#section-4.1)
+
+use std::time::Duration;
+
+use time::OffsetDateTime;
+
+use super::rdata::DNSKEY;
+use crate::rr::{Name, RecordData, RecordSet, RecordType};
+
+/// The method used to replace a key, [RFC 6781, section 4.1.1](https://tools.ietf.org/html/rfc6781#section-4.1.1)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RolloverStrategy {
+    /// The incoming key is published before it is used for signing
+    ///
+    /// This keeps the size of the zone small, at the cost of a longer rollover. It is the
+    /// recommended method for zone signing keys.
+    PrePublish,
+    /// The zone is signed with both keys while the incoming key is published
+    ///
+    /// This is the faster method, at the cost of signing the zone twice during the rollover. It
+    /// is the recommended method for key signing keys.
+    DoubleSignature,
+}
+
+/// The phases of a key rollover, named as in [RFC 6781, section 4.1.1](https://tools.ietf.org/html/rfc6781#section-4.1.1)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RolloverPhase {
+    /// The incoming key is added to the DNSKEY RRset
+    NewDnskey,
+    /// The zone is signed with the incoming key only, only for [`RolloverStrategy::PrePublish`]
+    NewRrsigs,
+    /// The active key is removed from the DNSKEY RRset, this ends the rollover
+    DnskeyRemoval,
+}
+
+/// The published and signing keys of the zone, from the start of a phase of the rollover on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RolloverState {
+    phase: RolloverPhase,
+    starts_at: OffsetDateTime,
+    published: Vec<DNSKEY>,
+    signing: Vec<DNSKEY>,
+}
+
+impl RolloverState {
+    /// The phase of the rollover
+    pub fn phase(&self) -> RolloverPhase {
+        self.phase
+    }
+
+    /// The earliest time at which this state may be applied to the zone
+    pub fn starts_at(&self) -> OffsetDateTime {
+        self.starts_at
+    }
+
+    /// The keys to publish in the DNSKEY RRset of the zone
+    pub fn published(&self) -> &[DNSKEY] {
+        &self.published
+    }
+
+    /// The keys to produce the RRSIGs of the zone with
+    pub fn signing(&self) -> &[DNSKEY] {
+        &self.signing
+    }
+
+    /// Returns true if the key is in the DNSKEY RRset in this state
+    pub fn is_published(&self, key: &DNSKEY) -> bool {
+        self.published.contains(key)
+    }
+
+    /// Returns true if the zone is signed with the key in this state
+    pub fn is_signing(&self, key: &DNSKEY) -> bool {
+        self.signing.contains(key)
+    }
+
+    /// Builds the DNSKEY RRset of the zone for this state
+    ///
+    /// # Arguments
+    ///
+    /// * `zone` - the name of the zone, i.e. the owner of the DNSKEY records
+    /// * `ttl` - the TTL of the DNSKEY records
+    pub fn dnskey_rrset(&self, zone: Name, ttl: u32) -> RecordSet {
+        let mut rrset = RecordSet::with_ttl(zone, RecordType::DNSKEY, ttl);
+        for key in &self.published {
+            rrset.add_rdata(key.clone().into_rdata());
+        }
+        rrset
+    }
+}
+
+/// Computes the sequence of DNSKEY states for replacing the active key of a zone with the
+/// incoming key
+///
+/// Each phase may only start once the data published in the previous phase is in the caches of
+/// all resolvers, and the data it replaced is no longer. This depends on the TTL of the DNSKEY
+/// RRset, the maximum TTL of the signed records of the zone, and the time it takes for changes to
+/// reach all the authoritative name servers of the zone.
+///
+/// For a key signing key, the DS record in the parent zone must be replaced while both keys are
+/// published, the TTL of the DS record should be included in the propagation delay.
+#[derive(Clone, Debug)]
+pub struct KeyRollover {
+    active: DNSKEY,
+    incoming: DNSKEY,
+    strategy: RolloverStrategy,
+    dnskey_ttl: Duration,
+    max_zone_ttl: Duration,
+    propagation_delay: Duration,
+}
+
+impl KeyRollover {
+    /// Creates a rollover from the `active` to the `incoming` key
+    ///
+    /// # Arguments
+    ///
+    /// * `active` - the key currently published and used for signing
+    /// * `incoming` - the key which replaces it
+    /// * `strategy` - the method of the rollover
+    /// * `dnskey_ttl` - the TTL of the DNSKEY RRset
+    /// * `max_zone_ttl` - the maximum TTL of the RRsets signed with the keys
+    pub fn new(
+        active: DNSKEY,
+        incoming: DNSKEY,
+        strategy: RolloverStrategy,
+        dnskey_ttl: Duration,
+        max_zone_ttl: Duration,
+    ) -> Self {
+        Self {
+            active,
+            incoming,
+            strategy,
+            dnskey_ttl,
+            max_zone_ttl,
+            propagation_delay: Duration::ZERO,
+        }
+    }
+
+    /// The time it takes for a change to the zone to reach all its name servers, defaults to zero
+    pub fn with_propagation_delay(mut self, propagation_delay: Duration) -> Self {
+        self.propagation_delay = propagation_delay;
+        self
+    }
+
+    /// The method of the rollover
+    pub fn strategy(&self) -> RolloverStrategy {
+        self.strategy
+    }
+
+    /// The states of the zone during the rollover, when it starts at `start`
+    ///
+    /// The first state publishes the incoming key, the last one only contains the incoming key.
+    pub fn states(&self, start: OffsetDateTime) -> Vec<RolloverState> {
+        let both = vec![self.active.clone(), self.incoming.clone()];
+        let incoming = vec![self.incoming.clone()];
+
+        match self.strategy {
+            RolloverStrategy::PrePublish => {
+                let new_rrsigs = start + self.propagation_delay + self.dnskey_ttl;
+                vec![
+                    self.state(
+                        RolloverPhase::NewDnskey,
+                        start,
+                        both.clone(),
+                        vec![self.active.clone()],
+                    ),
+                    self.state(RolloverPhase::NewRrsigs, new_rrsigs, both, incoming.clone()),
+                    self.state(
+                        RolloverPhase::DnskeyRemoval,
+                        self.old_signatures_expire_at(start),
+                        incoming.clone(),
+                        incoming,
+                    ),
+                ]
+            }
+            RolloverStrategy::DoubleSignature => vec![
+                self.state(RolloverPhase::NewDnskey, start, both.clone(), both),
+                self.state(
+                    RolloverPhase::DnskeyRemoval,
+                    self.old_signatures_expire_at(start),
+                    incoming.clone(),
+                    incoming,
+                ),
+            ],
+        }
+    }
+
+    /// The time at which the signatures made with only the active key are no longer cached, when
+    /// the rollover starts at `start`
+    ///
+    /// From then on, the active key can be removed from the zone, and retired.
+    pub fn old_signatures_expire_at(&self, start: OffsetDateTime) -> OffsetDateTime {
+        match self.strategy {
+            RolloverStrategy::PrePublish => {
+                start
+                    + self.propagation_delay
+                    + self.dnskey_ttl
+                    + self.propagation_delay
+                    + self.max_zone_ttl
+            }
+            RolloverStrategy::DoubleSignature => {
+                start + self.propagation_delay + self.dnskey_ttl.max(self.max_zone_ttl)
+            }
+        }
+    }
+
+    fn state(
+        &self,
+        phase: RolloverPhase,
+        starts_at: OffsetDateTime,
+        published: Vec<DNSKEY>,
+        signing: Vec<DNSKEY>,
+    ) -> RolloverState {
+        RolloverState {
+            phase,
+            starts_at,
+            published,
+            signing,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dnssec::Algorithm;
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+
+    fn key(tag: u8) -> DNSKEY {
+        DNSKEY::new(true, false, false, Algorithm::ED25519, vec![tag; 32])
+    }
+
+    fn rollover(strategy: RolloverStrategy) -> KeyRollover {
+        KeyRollover::new(key(1), key(2), strategy, HOUR, 24 * HOUR)
+            .with_propagation_delay(Duration::from_secs(5 * 60))
+    }
+
+    #[test]
+    fn test_pre_publish() {
+        let (active, incoming) = (key(1), key(2));
+        let start = OffsetDateTime::UNIX_EPOCH;
+        let states = rollover(RolloverStrategy::PrePublish).states(start);
+
+        let phases = states.iter().map(RolloverState::phase).collect::<Vec<_>>();
+        assert_eq!(
+            phases,
+            vec![
+                RolloverPhase::NewDnskey,
+                RolloverPhase::NewRrsigs,
+                RolloverPhase::DnskeyRemoval
+            ]
+        );
+
+        // the incoming key is published, but not used yet
+        let new_dnskey = &states[0];
+        assert_eq!(new_dnskey.starts_at(), start);
+        assert!(new_dnskey.is_published(&active) && new_dnskey.is_published(&incoming));
+        assert!(new_dnskey.is_signing(&active) && !new_dnskey.is_signing(&incoming));
+
+        // once the new DNSKEY RRset is cached, the incoming key signs, both stay published
+        let new_rrsigs = &states[1];
+        assert_eq!(
+            new_rrsigs.starts_at(),
+            start + HOUR + Duration::from_secs(5 * 60)
+        );
+        assert!(new_rrsigs.is_published(&active) && new_rrsigs.is_published(&incoming));
+        assert!(!new_rrsigs.is_signing(&active) && new_rrsigs.is_signing(&incoming));
+
+        // once the old signatures expired from the caches, the active key is removed
+        let removal = &states[2];
+        assert_eq!(
+            removal.starts_at(),
+            start + 25 * HOUR + Duration::from_secs(10 * 60)
+        );
+        assert_eq!(removal.published().len(), 1);
+        assert!(removal.is_published(&incoming));
+        assert_eq!(removal.signing().len(), 1);
+        assert!(removal.is_signing(&incoming));
+
+        let zone = Name::from_ascii("example.com.").unwrap();
+        let rrset = new_rrsigs.dnskey_rrset(zone.clone(), 3600);
+        assert_eq!(rrset.name(), &zone);
+        assert_eq!(rrset.ttl(), 3600);
+        assert_eq!(rrset.records_without_rrsigs().count(), 2);
+        assert_eq!(
+            removal
+                .dnskey_rrset(zone, 3600)
+                .records_without_rrsigs()
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_double_signature() {
+        let (active, incoming) = (key(1), key(2));
+        let start = OffsetDateTime::UNIX_EPOCH;
+        let rollover = rollover(RolloverStrategy::DoubleSignature);
+        let states = rollover.states(start);
+
+        assert_eq!(states.len(), 2);
+        assert_eq!(states[0].phase(), RolloverPhase::NewDnskey);
+        assert_eq!(states[0].published(), &[active.clone(), incoming.clone()]);
+        assert_eq!(states[0].signing(), &[active, incoming.clone()]);
+
+        assert_eq!(states[1].phase(), RolloverPhase::DnskeyRemoval);
+        assert_eq!(
+            states[1].starts_at(),
+            start + 24 * HOUR + Duration::from_secs(5 * 60)
+        );
+        assert_eq!(
+            states[1].starts_at(),
+            rollover.old_signatures_expire_at(start)
+        );
+        assert_eq!(states[1].published(), &[incoming]);
+    }
+}
@@ -552,7 +552,7 @@ mod tests {
         Algorithm, SigningKey, Verifier, TBS,
     };
     use crate::op::{Message, Query};
-    use crate::rr::rdata::{CNAME, NS};
+    use crate::rr::rdata::{CNAME, MX, NS};
     use crate::rr::{DNSClass, Name, RData, Record, RecordType};
 
     fn assert_send_and_sync<T: Send + Sync>() {}
@@ -669,6 +669,70 @@ mod tests {
         assert!(pub_key.verify(tbs.as_ref(), &sig).is_ok());
     }
 
+    #[test]
+    fn test_sign_and_verify_compressible_rrset() {
+        let key =
+            RsaSigningKey::from_pkcs8(&PrivatePkcs8KeyDer::from(RSA_KEY), Algorithm::RSASHA256)
+                .unwrap();
+        let pub_key = key.to_public_key().unwrap();
+        let sig0key = KEY::new_sig0key(&pub_key);
+        let signer = SigSigner::sig0(sig0key, Box::new(key), Name::root());
+
+        let origin = Name::parse("example.com.", None).unwrap();
+        let rrsig = |sig| {
+            Record::from_rdata(
+                origin.clone(),
+                86400,
+                RData::DNSSEC(DNSSECRData::RRSIG(RRSIG::new(
+                    RecordType::MX,
+                    Algorithm::RSASHA256,
+                    origin.num_labels(),
+                    86400,
+                    5,
+                    0,
+                    signer.calculate_key_tag().unwrap(),
+                    origin.clone(),
+                    sig,
+                ))),
+            )
+        };
+        let rrset = ["mail.example.com.", "Backup.Example.com."].map(|exchange| {
+            Record::from_rdata(
+                origin.clone(),
+                86400,
+                RData::MX(MX::new(10, Name::parse(exchange, None).unwrap())),
+            )
+        });
+
+        // the names of the signed data are neither compressed nor in mixed case
+        let tbs = rrset_tbs(&rrsig(vec![]), &rrset);
+        let suffix = b"\x07example\x03com\x00";
+        let count = tbs.as_ref().windows(suffix.len()).filter(|w| w == suffix);
+        assert_eq!(count.count(), 5);
+        let sig = signer.sign(&tbs).unwrap();
+
+        // the signature validates the records read from a message, where the names are compressed
+        let mut message = Message::new();
+        message.add_answers(rrset).add_answer(rrsig(sig));
+        let message = Message::from_vec(&message.to_vec().unwrap()).unwrap();
+        let (rrsig, rrset) = message.answers().split_last().unwrap();
+        let RData::DNSSEC(DNSSECRData::RRSIG(sig)) = rrsig.data() else {
+            panic!("not an RRSIG: {rrsig}");
+        };
+
+        let pub_key = signer.key().to_public_key().unwrap();
+        assert!(pub_key
+            .verify(rrset_tbs(rrsig, rrset).as_ref(), sig.sig())
+            .is_ok());
+    }
+
+    fn rrset_tbs(rrsig: &Record, rrset: &[Record]) -> TBS {
+        let RData::DNSSEC(DNSSECRData::RRSIG(sig)) = rrsig.data() else {
+            panic!("not an RRSIG: {rrsig}");
+        };
+        TBS::from_sig(rrsig.name(), rrsig.dns_class(), sig, rrset.iter()).unwrap()
+    }
+
     #[test]
     #[allow(deprecated)]
     fn test_calculate_key_tag_pem() {
@@ -44,7 +44,7 @@ impl SupportedAlgorithms {
     /// Specify the entire set is supported
     pub fn all() -> Self {
         Self {
-            bit_map: 0b0111_1111,
+            bit_map: 0b1111_1111,
         }
     }
 
@@ -70,6 +70,7 @@ impl SupportedAlgorithms {
             Algorithm::ECDSAP256SHA256 => Some(4),
             Algorithm::ECDSAP384SHA384 => Some(5),
             Algorithm::ED25519 => Some(6),
+            Algorithm::ED448 => Some(7),
             Algorithm::RSAMD5 | Algorithm::DSA | Algorithm::Unknown(_) => None,
         };
 
@@ -87,6 +88,7 @@ impl SupportedAlgorithms {
             4 => Some(Algorithm::ECDSAP256SHA256),
             5 => Some(Algorithm::ECDSAP384SHA384),
             6 => Some(Algorithm::ED25519),
+            7 => Some(Algorithm::ED448),
             _ => None,
         }
     }
@@ -243,7 +245,7 @@ mod tests {
     #[test]
     fn test_iterator() {
         let supported = SupportedAlgorithms::all();
-        assert_eq!(supported.iter().count(), 7);
+        assert_eq!(supported.iter().count(), 8);
 
         // it just so happens that the iterator has a fixed order...
         let supported = SupportedAlgorithms::all();
@@ -255,6 +257,7 @@ mod tests {
         assert_eq!(iter.next(), Some(Algorithm::ECDSAP256SHA256));
         assert_eq!(iter.next(), Some(Algorithm::ECDSAP384SHA384));
         assert_eq!(iter.next(), Some(Algorithm::ED25519));
+        assert_eq!(iter.next(), Some(Algorithm::ED448));
 
         let mut supported = SupportedAlgorithms::new();
         supported.set(Algorithm::RSASHA256);
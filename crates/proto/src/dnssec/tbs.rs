@@ -12,7 +12,7 @@ use time::OffsetDateTime;
 use super::Algorithm;
 use crate::{
     error::{ProtoError, ProtoResult},
-    rr::{DNSClass, Name, Record, RecordSet, RecordType, SerialNumber},
+    rr::{rrset_canonical_sort, DNSClass, Name, Record, RecordSet, RecordType, SerialNumber},
     serialize::binary::{BinEncodable, BinEncoder, EncodeMode},
 };
 
@@ -159,7 +159,7 @@ impl TBS {
         }
 
         // put records in canonical order
-        rrset.sort();
+        rrset_canonical_sort(&mut rrset);
 
         let name = determine_name(name, num_labels)?;
 
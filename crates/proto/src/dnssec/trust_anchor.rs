@@ -15,8 +15,17 @@
  */
 
 //! Allows for the root trust_anchor to either be added to or replaced for dns_sec validation.
+//!
+//! The [`TrustAnchorTracker`] keeps the trust anchors of a zone up to date, following the key
+//! rollovers of the zone, see [RFC 5011](https://tools.ietf.org/html/rfc5011).
 
-use crate::dnssec::PublicKey;
+use std::time::Duration;
+
+use time::OffsetDateTime;
+
+use crate::dnssec::rdata::DNSKEY;
+use crate::dnssec::{Algorithm, PublicKey};
+use crate::error::{ProtoError, ProtoResult};
 
 const ROOT_ANCHOR_ORIG: &[u8] = include_bytes!("roots/19036.rsa");
 const ROOT_ANCHOR_2018: &[u8] = include_bytes!("roots/20326.rsa");
@@ -80,9 +89,365 @@ impl TrustAnchor {
     }
 }
 
+/// The default add and remove hold-down time, [RFC 5011, section 2.4.1](https://tools.ietf.org/html/rfc5011#section-2.4.1)
+pub const DEFAULT_HOLD_DOWN: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// The state of a key tracked by a [`TrustAnchorTracker`], [RFC 5011, section 4](https://tools.ietf.org/html/rfc5011#section-4)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrustPointState {
+    /// The key was seen in the DNSKEY RRset of the zone, it is trusted once it's still published
+    /// after the add hold-down time
+    AddPending {
+        /// The end of the add hold-down time
+        trusted_at: OffsetDateTime,
+    },
+    /// The key is trusted
+    Valid,
+    /// The key is trusted, but it was missing from the last DNSKEY RRset of the zone
+    Missing,
+    /// The key was revoked, it is not trusted anymore and is forgotten after the remove hold-down
+    /// time
+    Revoked {
+        /// The end of the remove hold-down time
+        removed_at: OffsetDateTime,
+    },
+}
+
+impl TrustPointState {
+    /// Returns true if the key is trusted in this state
+    pub fn is_trusted(&self) -> bool {
+        matches!(self, Self::Valid | Self::Missing)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct TrackedKey {
+    algorithm: Algorithm,
+    public_key: Vec<u8>,
+    state: TrustPointState,
+}
+
+impl TrackedKey {
+    /// Returns true if this is the key, the revoke flag is not considered
+    fn matches(&self, key: &DNSKEY) -> bool {
+        self.algorithm == key.algorithm() && self.public_key == key.public_key()
+    }
+}
+
+/// The trust anchors of a zone, automatically updated from its DNSKEY RRset
+///
+/// The key signing keys published by the zone are trusted after the add hold-down time, and the
+/// keys revoked by the zone are no longer trusted, see
+/// [RFC 5011, section 2](https://tools.ietf.org/html/rfc5011#section-2). The tracker should be
+/// updated with the DNSKEY RRset of the zone at each [`TrustAnchorTracker::query_interval`]; the
+/// [`TrustAnchor`] for the validation is built with [`TrustAnchorTracker::trust_anchor`].
+#[derive(Clone, Debug)]
+pub struct TrustAnchorTracker {
+    keys: Vec<TrackedKey>,
+    add_hold_down: Duration,
+    remove_hold_down: Duration,
+}
+
+impl Default for TrustAnchorTracker {
+    /// Tracks the root zone, starting from the compiled in root trust anchors
+    fn default() -> Self {
+        Self::new(
+            [ROOT_ANCHOR_ORIG, ROOT_ANCHOR_2018]
+                .into_iter()
+                .map(|key| DNSKEY::new(true, true, false, Algorithm::RSASHA256, key.to_vec())),
+        )
+    }
+}
+
+impl TrustAnchorTracker {
+    /// Creates a new tracker, the initial trust anchors are trusted
+    pub fn new(trust_anchors: impl IntoIterator<Item = DNSKEY>) -> Self {
+        let mut keys = Vec::<TrackedKey>::new();
+        for key in trust_anchors {
+            if !keys.iter().any(|tracked| tracked.matches(&key)) {
+                keys.push(TrackedKey {
+                    algorithm: key.algorithm(),
+                    public_key: key.public_key().to_vec(),
+                    state: TrustPointState::Valid,
+                });
+            }
+        }
+
+        Self {
+            keys,
+            add_hold_down: DEFAULT_HOLD_DOWN,
+            remove_hold_down: DEFAULT_HOLD_DOWN,
+        }
+    }
+
+    /// Sets the time a new key must be published before it is trusted, defaults to 30 days
+    pub fn with_add_hold_down(mut self, add_hold_down: Duration) -> Self {
+        self.add_hold_down = add_hold_down;
+        self
+    }
+
+    /// Sets the time a revoked key is remembered, defaults to 30 days
+    pub fn with_remove_hold_down(mut self, remove_hold_down: Duration) -> Self {
+        self.remove_hold_down = remove_hold_down;
+        self
+    }
+
+    /// The state of the key, `None` if it is not tracked
+    pub fn state(&self, key: &DNSKEY) -> Option<TrustPointState> {
+        self.keys
+            .iter()
+            .find(|tracked| tracked.matches(key))
+            .map(|tracked| tracked.state)
+    }
+
+    /// The trust anchors for the validation, the keys in the `Valid` and `Missing` states
+    pub fn trust_anchor(&self) -> TrustAnchor {
+        TrustAnchor {
+            pkeys: self
+                .keys
+                .iter()
+                .filter(|tracked| tracked.state.is_trusted())
+                .map(|tracked| tracked.public_key.clone())
+                .collect(),
+        }
+    }
+
+    /// Updates the states of the keys with the DNSKEY RRset of the zone
+    ///
+    /// The RRset must have been validated with the [`TrustAnchorTracker::trust_anchor`], and the
+    /// revoked keys with their own signature. Only the key signing keys are tracked.
+    ///
+    /// Returns true if the trusted keys changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the RRset does not contain any trusted key, the states are not changed.
+    pub fn update(&mut self, dnskeys: &[DNSKEY], now: OffsetDateTime) -> ProtoResult<bool> {
+        let is_trusted = |key: &DNSKEY| self.state(key).is_some_and(|state| state.is_trusted());
+        if !dnskeys.iter().any(is_trusted) {
+            return Err(ProtoError::from(
+                "the DNSKEY RRset does not contain any trusted key",
+            ));
+        }
+
+        let trusted = self.trust_anchor();
+        let dnskeys = dnskeys
+            .iter()
+            .filter(|key| key.zone_key() && key.secure_entry_point())
+            .collect::<Vec<_>>();
+
+        // the published keys which are not tracked yet are added, unless already revoked
+        for key in dnskeys.iter().filter(|key| !key.revoke()) {
+            if self.state(key).is_none() {
+                self.keys.push(TrackedKey {
+                    algorithm: key.algorithm(),
+                    public_key: key.public_key().to_vec(),
+                    state: TrustPointState::AddPending {
+                        trusted_at: now + self.add_hold_down,
+                    },
+                });
+            }
+        }
+
+        let remove_hold_down = self.remove_hold_down;
+        self.keys.retain_mut(|tracked| {
+            let published = dnskeys.iter().find(|key| tracked.matches(key));
+            tracked.state = match (tracked.state, published.map(|key| key.revoke())) {
+                (TrustPointState::AddPending { .. }, None | Some(true)) => return false,
+                (TrustPointState::AddPending { trusted_at }, Some(false)) if now >= trusted_at => {
+                    TrustPointState::Valid
+                }
+                (TrustPointState::Valid | TrustPointState::Missing, Some(true)) => {
+                    TrustPointState::Revoked {
+                        removed_at: now + remove_hold_down,
+                    }
+                }
+                (TrustPointState::Valid | TrustPointState::Missing, Some(false)) => {
+                    TrustPointState::Valid
+                }
+                (TrustPointState::Valid, None) => TrustPointState::Missing,
+                (TrustPointState::Revoked { removed_at }, _) if now >= removed_at => return false,
+                (state, _) => state,
+            };
+            true
+        });
+
+        let trust_anchor = self.trust_anchor();
+        Ok(trust_anchor.pkeys != trusted.pkeys)
+    }
+
+    /// The interval to query the DNSKEY RRset of the zone at, [RFC 5011, section 2.3](https://tools.ietf.org/html/rfc5011#section-2.3)
+    ///
+    /// # Arguments
+    ///
+    /// * `original_ttl` - the original TTL of the DNSKEY RRset
+    /// * `signature_expiration` - the time until the expiration of the signatures of the RRset
+    pub fn query_interval(original_ttl: u32, signature_expiration: Duration) -> Duration {
+        const HOUR: Duration = Duration::from_secs(60 * 60);
+
+        (Duration::from_secs(u64::from(original_ttl)) / 2)
+            .min(signature_expiration / 2)
+            .min(15 * 24 * HOUR)
+            .max(HOUR)
+    }
+}
+
 #[test]
 fn test_kjqmt7v() {
     let trust = TrustAnchor::default();
     assert_eq!(trust.get(0), ROOT_ANCHOR_ORIG);
     assert!(trust.contains_dnskey_bytes(ROOT_ANCHOR_ORIG));
 }
+
+#[cfg(test)]
+mod tests {
+    use std::slice;
+
+    use super::*;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn key(tag: u8, revoke: bool) -> DNSKEY {
+        DNSKEY::new(true, true, revoke, Algorithm::ED25519, vec![tag; 32])
+    }
+
+    fn is_trusted(tracker: &TrustAnchorTracker, key: &DNSKEY) -> bool {
+        tracker
+            .trust_anchor()
+            .contains_dnskey_bytes(key.public_key())
+    }
+
+    #[test]
+    fn test_root_trust_anchors() {
+        let tracker = TrustAnchorTracker::default();
+        let root = DNSKEY::new(
+            true,
+            true,
+            false,
+            Algorithm::RSASHA256,
+            ROOT_ANCHOR_2018.to_vec(),
+        );
+        assert_eq!(tracker.state(&root), Some(TrustPointState::Valid));
+        assert_eq!(tracker.trust_anchor().len(), TrustAnchor::default().len());
+    }
+
+    #[test]
+    fn test_new_key_trusted_after_hold_down() {
+        let (active, incoming) = (key(1, false), key(2, false));
+        let mut tracker = TrustAnchorTracker::new([active.clone()]);
+        let start = OffsetDateTime::UNIX_EPOCH;
+
+        let rrset = [active.clone(), incoming.clone()];
+        assert!(!tracker.update(&rrset, start).unwrap());
+        assert_eq!(
+            tracker.state(&incoming),
+            Some(TrustPointState::AddPending {
+                trusted_at: start + DEFAULT_HOLD_DOWN
+            })
+        );
+        assert!(!is_trusted(&tracker, &incoming));
+
+        // the hold-down time is not restarted by the later updates
+        assert!(!tracker.update(&rrset, start + 29 * DAY).unwrap());
+        assert!(!is_trusted(&tracker, &incoming));
+
+        assert!(tracker.update(&rrset, start + 30 * DAY).unwrap());
+        assert_eq!(tracker.state(&incoming), Some(TrustPointState::Valid));
+        assert!(is_trusted(&tracker, &incoming));
+
+        // the new key alone now validates the RRset, the removed key is still trusted
+        assert!(!tracker
+            .update(slice::from_ref(&incoming), start + 31 * DAY)
+            .unwrap());
+        assert_eq!(tracker.state(&active), Some(TrustPointState::Missing));
+        assert!(is_trusted(&tracker, &active));
+    }
+
+    #[test]
+    fn test_new_key_removed_before_hold_down() {
+        let (active, incoming) = (key(1, false), key(2, false));
+        let mut tracker = TrustAnchorTracker::new([active.clone()]).with_add_hold_down(DAY);
+        let start = OffsetDateTime::UNIX_EPOCH;
+
+        tracker
+            .update(&[active.clone(), incoming.clone()], start)
+            .unwrap();
+        tracker
+            .update(slice::from_ref(&active), start + DAY / 2)
+            .unwrap();
+        assert_eq!(tracker.state(&incoming), None);
+
+        // the hold-down time restarts when the key is published again
+        tracker
+            .update(&[active.clone(), incoming.clone()], start + DAY)
+            .unwrap();
+        assert_eq!(
+            tracker.state(&incoming),
+            Some(TrustPointState::AddPending {
+                trusted_at: start + 2 * DAY
+            })
+        );
+
+        // zone signing keys are not tracked
+        let zsk = DNSKEY::new(true, false, false, Algorithm::ED25519, vec![3; 32]);
+        tracker.update(&[active, zsk.clone()], start + DAY).unwrap();
+        assert_eq!(tracker.state(&zsk), None);
+    }
+
+    #[test]
+    fn test_revoked_key() {
+        let (active, incoming) = (key(1, false), key(2, false));
+        let mut tracker =
+            TrustAnchorTracker::new([active.clone(), incoming.clone()]).with_remove_hold_down(DAY);
+        let start = OffsetDateTime::UNIX_EPOCH;
+
+        assert!(tracker
+            .update(&[key(1, true), incoming.clone()], start)
+            .unwrap());
+        assert_eq!(
+            tracker.state(&active),
+            Some(TrustPointState::Revoked {
+                removed_at: start + DAY
+            })
+        );
+        assert!(!is_trusted(&tracker, &active));
+
+        // a revoked key is not trusted again when published without the revoke flag
+        tracker
+            .update(&[active.clone(), incoming.clone()], start + DAY / 2)
+            .unwrap();
+        assert!(!is_trusted(&tracker, &active));
+
+        tracker
+            .update(slice::from_ref(&incoming), start + DAY)
+            .unwrap();
+        assert_eq!(tracker.state(&active), None);
+    }
+
+    #[test]
+    fn test_untrusted_rrset() {
+        let active = key(1, false);
+        let mut tracker = TrustAnchorTracker::new([active.clone()]);
+
+        let rrset = [key(2, false), key(3, false)];
+        assert!(tracker.update(&rrset, OffsetDateTime::UNIX_EPOCH).is_err());
+        assert_eq!(tracker.state(&rrset[0]), None);
+        assert_eq!(tracker.state(&active), Some(TrustPointState::Valid));
+    }
+
+    #[test]
+    fn test_query_interval() {
+        const HOUR: Duration = Duration::from_secs(60 * 60);
+
+        assert_eq!(TrustAnchorTracker::query_interval(172_800, 10 * DAY), DAY);
+        assert_eq!(
+            TrustAnchorTracker::query_interval(172_800, 20 * HOUR),
+            10 * HOUR
+        );
+        assert_eq!(TrustAnchorTracker::query_interval(60, DAY), HOUR);
+        assert_eq!(
+            TrustAnchorTracker::query_interval(u32::MAX, 100 * DAY),
+            15 * DAY
+        );
+    }
+}
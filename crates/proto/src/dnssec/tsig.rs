@@ -12,6 +12,7 @@
 //!
 //! - Mac checking don't support HMAC truncation with TSIG (pedantic constant time verification)
 //! - Time checking not in TSIG implementation but in caller
+//! - Unsigned intermediate messages of a multi-message response are not accepted
 
 use std::ops::Range;
 use std::sync::Arc;
@@ -24,8 +25,9 @@ use super::rdata::tsig::{
 use super::rdata::DNSSECRData;
 use super::{DnsSecError, DnsSecErrorKind};
 use crate::error::{ProtoError, ProtoResult};
-use crate::op::{Message, MessageFinalizer, MessageVerifier};
+use crate::op::{Header, Message, MessageFinalizer, MessageVerifier, ResponseCode};
 use crate::rr::{Name, RData, Record};
+use crate::serialize::binary::{BinDecodable, BinDecoder, BinEncoder};
 use crate::xfer::DnsResponse;
 
 /// Struct to pass to a client for it to authenticate requests using TSIG.
@@ -99,6 +101,53 @@ impl TSigner {
         self.sign(&message_tbs(None, message, pre_tsig, &self.0.signer_name)?)
     }
 
+    /// Create the TSIG record of a response to a request authenticated with this key
+    ///
+    /// # Arguments
+    /// * `request_mac` - MAC of the request being responded to
+    /// * `response` - byte buffer containing the response, without the TSIG record
+    /// * `current_time` - the time of signing, in seconds since the unix epoch
+    /// * `error` - the TSIG error, a `BADTIME` response carries `current_time` as other data
+    pub fn sign_response(
+        &self,
+        request_mac: &[u8],
+        response: &[u8],
+        current_time: u64,
+        error: ResponseCode,
+    ) -> Result<Record, DnsSecError> {
+        let mut header = BinDecoder::new(response);
+        let id = Header::read(&mut header)?.id();
+
+        let other = if error == ResponseCode::BADTIME {
+            // 48-bit time, see https://tools.ietf.org/html/rfc8945#section-5.2.3
+            current_time.to_be_bytes()[2..].to_vec()
+        } else {
+            Vec::new()
+        };
+        let pre_tsig = TSIG::new(
+            self.0.algorithm.clone(),
+            current_time,
+            self.0.fudge,
+            Vec::new(),
+            id,
+            error.into(),
+            other,
+        );
+
+        let mut tbs = Vec::with_capacity(request_mac.len() + response.len() + 64);
+        let mut encoder = BinEncoder::new(&mut tbs);
+        encoder.emit_u16(request_mac.len() as u16)?;
+        encoder.emit_vec(request_mac)?;
+        encoder.emit_vec(response)?;
+        pre_tsig.emit_tsig_for_mac(&mut encoder, &self.0.signer_name)?;
+        let mac = self.sign(&tbs)?;
+
+        Ok(make_tsig_record(
+            self.0.signer_name.clone(),
+            pre_tsig.set_mac(mac),
+        ))
+    }
+
     /// Verify hmac in constant time to prevent timing attacks
     pub fn verify(&self, tbv: &[u8], tag: &[u8]) -> Result<(), DnsSecError> {
         self.0.algorithm.verify_mac(&self.0.key, tbv, tag)
@@ -115,7 +164,10 @@ impl TSigner {
     /// * `first_message` - is this the first response message
     ///
     /// # Returns
-    /// Return Ok(_) on valid signature. Inner tuple contain the following values, in order:
+    /// Return Ok(_) on valid signature. If the remote rejected the TSIG of our request, a
+    /// [`DnsSecErrorKind::TsigRemoteError`] with the TSIG error is returned instead.
+    ///
+    /// Inner tuple contain the following values, in order:
     /// * a byte buffer containing the hash of this message. Need to be passed back when
     ///   authenticating next message
     /// * a Range of time that is acceptable
@@ -140,6 +192,16 @@ impl TSigner {
             return Err(DnsSecErrorKind::TsigWrongKey.into());
         }
 
+        // https://tools.ietf.org/html/rfc8945#section-5.3.2
+        //  BADKEY and BADSIG responses are unsigned, BADTIME responses are signed as usual
+        let error = tsig.error();
+        if error != ResponseCode::NoError {
+            if error == ResponseCode::BADTIME {
+                self.verify(&tbv, tsig.mac())?;
+            }
+            return Err(DnsSecErrorKind::TsigRemoteError(error).into());
+        }
+
         // 2.  Check MAC
         //  note: that this verification does not allow for truncation of the HMAC, which technically the RFC suggests.
         //    this is to be pedantic about constant time HMAC validation (prevent timing attacks) as well as any security
@@ -169,8 +231,8 @@ impl TSigner {
         Ok((
             tsig.mac().to_vec(),
             Range {
-                start: tsig.time() - tsig.fudge() as u64,
-                end: tsig.time() + tsig.fudge() as u64,
+                start: tsig.time().saturating_sub(tsig.fudge() as u64),
+                end: tsig.time().saturating_add(tsig.fudge() as u64 + 1),
             },
             tsig.time(),
         ))
@@ -208,15 +270,19 @@ impl MessageFinalizer for TSigner {
             let (last_sig, range, rt) = self2
                 .verify_message_byte(Some(signature.as_ref()), dns_response, remote_time == 0)
                 .map_err(|err| ProtoError::from(err.to_string()))?;
-            if rt >= remote_time && range.contains(&current_time)
             // this assumes a no-latency answer
-            {
-                signature = last_sig;
-                remote_time = rt;
-                DnsResponse::from_buffer(dns_response.to_vec())
-            } else {
-                Err(ProtoError::from("tsig validation error: outdated response"))
+            if !range.contains(&current_time) {
+                return Err(ProtoError::from(
+                    DnsSecError::from(DnsSecErrorKind::TsigBadTime).to_string(),
+                ));
+            }
+            if rt < remote_time {
+                return Err(ProtoError::from("tsig validation error: outdated response"));
             }
+
+            signature = last_sig;
+            remote_time = rt;
+            DnsResponse::from_buffer(dns_response.to_vec())
         };
         Ok((vec![tsig], Some(Box::new(verifier))))
     }
@@ -226,9 +292,10 @@ impl MessageFinalizer for TSigner {
 mod tests {
     #![allow(clippy::dbg_macro, clippy::print_stdout)]
 
-    use crate::op::{Message, Query};
-    use crate::rr::Name;
-    use crate::serialize::binary::BinEncodable;
+    use crate::op::{Message, MessageType, Query};
+    use crate::rr::rdata::A;
+    use crate::rr::{Name, RecordType};
+    use crate::serialize::binary::{BinEncodable, BinEncoder};
 
     use super::*;
     fn assert_send_and_sync<T: Send + Sync>() {}
@@ -324,4 +391,236 @@ mod tests {
             .verify_message_byte(None, &question.to_bytes().unwrap(), true)
             .is_err());
     }
+
+    const TIME: u64 = 1609459200;
+    const FUDGE: u16 = 300;
+
+    fn sha256_signer() -> TSigner {
+        TSigner::new(
+            (0..32).collect(),
+            TsigAlgorithm::HmacSha256,
+            Name::from_ascii("tsig-key.").unwrap(),
+            FUDGE,
+        )
+        .unwrap()
+    }
+
+    fn question() -> Message {
+        let mut question = Message::new();
+        question.set_id(0x1234).add_query(Query::query(
+            Name::from_ascii("example.com.").unwrap(),
+            RecordType::A,
+        ));
+        question
+    }
+
+    /// Signs a response to `question`, as part of a (possibly multi-message) transfer
+    fn sign_response(
+        signer: &TSigner,
+        previous_mac: &[u8],
+        first_message: bool,
+        last_octet: u8,
+        time: u64,
+    ) -> (Vec<u8>, Vec<u8>) {
+        let mut response = question();
+        response.set_message_type(MessageType::Response);
+        response.add_answer(Record::from_rdata(
+            Name::from_ascii("example.com.").unwrap(),
+            3600,
+            RData::A(A::new(192, 0, 2, last_octet)),
+        ));
+
+        let tsig = if first_message {
+            signer
+                .sign_response(
+                    previous_mac,
+                    &response.to_bytes().unwrap(),
+                    time,
+                    ResponseCode::NoError,
+                )
+                .unwrap()
+        } else {
+            // https://tools.ietf.org/html/rfc8945#section-5.3.1, only the timers are covered
+            let mut tbs = Vec::new();
+            let mut encoder = BinEncoder::new(&mut tbs);
+            encoder.emit_u16(previous_mac.len() as u16).unwrap();
+            encoder.emit_vec(previous_mac).unwrap();
+            encoder.emit_vec(&response.to_bytes().unwrap()).unwrap();
+            encoder.emit_u16((time >> 32) as u16).unwrap();
+            encoder.emit_u32(time as u32).unwrap();
+            encoder.emit_u16(signer.fudge()).unwrap();
+
+            let pre_tsig = TSIG::new(
+                signer.algorithm().clone(),
+                time,
+                signer.fudge(),
+                signer.sign(&tbs).unwrap(),
+                response.id(),
+                0,
+                Vec::new(),
+            );
+            make_tsig_record(signer.signer_name().clone(), pre_tsig)
+        };
+
+        let Some(DNSSECRData::TSIG(tsig_data)) = tsig.data().as_dnssec() else {
+            panic!("expected TSIG record data");
+        };
+        let mac = tsig_data.mac().to_vec();
+        response.add_tsig(tsig);
+        (response.to_bytes().unwrap(), mac)
+    }
+
+    #[test]
+    fn test_sign_hmac_sha256_regression_vector() {
+        let signer = sha256_signer();
+        let mut question = question();
+
+        let (records, _) = signer.finalize_message(&question, TIME as u32).unwrap();
+        let [tsig] = &records[..] else {
+            panic!("expected a single TSIG record");
+        };
+        let Some(DNSSECRData::TSIG(tsig_data)) = tsig.data().as_dnssec() else {
+            panic!("expected TSIG record data");
+        };
+
+        // self-generated HMAC-SHA256 of the request and the TSIG variables of RFC 8945 section
+        //  4.3.3, it guards the MAC against unintended changes, it is not a published test vector
+        assert_eq!(
+            tsig_data.mac(),
+            [
+                0x71, 0x4f, 0x7b, 0xf3, 0x02, 0xa4, 0x84, 0x90, 0xf6, 0xe4, 0x61, 0x48, 0xd2, 0x50,
+                0xbc, 0x67, 0x11, 0xe1, 0xe9, 0x72, 0xf1, 0x4a, 0xe9, 0x0d, 0x58, 0x3c, 0xc4, 0xa8,
+                0x8b, 0x04, 0x43, 0xf7,
+            ]
+        );
+        assert_eq!(tsig.name(), signer.signer_name());
+        assert_eq!(tsig_data.time(), TIME);
+        assert_eq!(tsig_data.fudge(), FUDGE);
+        assert_eq!(tsig_data.oid(), 0x1234);
+
+        question.add_tsig(tsig.clone());
+        assert!(signer
+            .verify_message_byte(None, &question.to_bytes().unwrap(), true)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_verify_chained_responses() {
+        let signer = sha256_signer();
+        let question = question();
+
+        let (records, verifier) = signer.finalize_message(&question, TIME as u32).unwrap();
+        let mut verifier = verifier.unwrap();
+        let Some(DNSSECRData::TSIG(request)) = records[0].data().as_dnssec() else {
+            panic!("expected TSIG record data");
+        };
+
+        let (first, first_mac) = sign_response(&signer, request.mac(), true, 1, TIME);
+        let (second, second_mac) = sign_response(&signer, &first_mac, false, 2, TIME + 1);
+        let (third, _) = sign_response(&signer, &second_mac, false, 3, TIME + 2);
+
+        verifier(&first).expect("first message should verify");
+        verifier(&second).expect("second message should verify");
+        verifier(&third).expect("third message should verify");
+
+        // out of order messages break the chain
+        let (_, verifier) = signer.finalize_message(&question, TIME as u32).unwrap();
+        let mut verifier = verifier.unwrap();
+        verifier(&first).expect("first message should verify");
+        assert!(verifier(&third).is_err());
+    }
+
+    #[test]
+    fn test_verify_response_bad_time() {
+        let signer = sha256_signer();
+        let question = question();
+
+        let (records, verifier) = signer.finalize_message(&question, TIME as u32).unwrap();
+        let Some(DNSSECRData::TSIG(request)) = records[0].data().as_dnssec() else {
+            panic!("expected TSIG record data");
+        };
+
+        // the edges of the fudge window are still acceptable
+        let (response, _) = sign_response(&signer, request.mac(), true, 1, TIME + FUDGE as u64);
+        verifier.unwrap()(&response).expect("response at the edge of the window");
+
+        let (_, verifier) = signer.finalize_message(&question, TIME as u32).unwrap();
+        let (response, _) = sign_response(&signer, request.mac(), true, 1, TIME + 2 * FUDGE as u64);
+        let err = verifier.unwrap()(&response).unwrap_err();
+        assert!(err.to_string().contains("out of time window"), "{err}");
+
+        // a time smaller than the fudge must not underflow
+        let (_, range, _) = signer
+            .verify_message_byte(
+                Some(request.mac()),
+                &sign_response(&signer, request.mac(), true, 1, 10).0,
+                true,
+            )
+            .unwrap();
+        assert_eq!(range, 0..311);
+    }
+
+    #[test]
+    fn test_verify_response_remote_error() {
+        let signer = sha256_signer();
+
+        for error in [ResponseCode::BADKEY, ResponseCode::BADSIG] {
+            // https://tools.ietf.org/html/rfc8945#section-5.3.2, unsigned error responses
+            let mut response = question();
+            response.set_message_type(MessageType::Response);
+            response.add_tsig(make_tsig_record(
+                signer.signer_name().clone(),
+                TSIG::new(
+                    TsigAlgorithm::HmacSha256,
+                    TIME,
+                    FUDGE,
+                    Vec::new(),
+                    response.id(),
+                    error.into(),
+                    Vec::new(),
+                ),
+            ));
+
+            let err = signer
+                .verify_message_byte(Some(&[0; 32]), &response.to_bytes().unwrap(), true)
+                .unwrap_err();
+            assert!(
+                matches!(err.kind(), DnsSecErrorKind::TsigRemoteError(code) if *code == error),
+                "{err}"
+            );
+        }
+
+        // BADTIME responses are signed, and carry the time of the server
+        let mut response = question();
+        response.set_message_type(MessageType::Response);
+        let request_mac = [0; 32];
+        let tsig = signer
+            .sign_response(
+                &request_mac,
+                &response.to_bytes().unwrap(),
+                TIME + 3600,
+                ResponseCode::BADTIME,
+            )
+            .unwrap();
+        let Some(DNSSECRData::TSIG(tsig_data)) = tsig.data().as_dnssec() else {
+            panic!("expected TSIG record data");
+        };
+        assert_eq!(tsig_data.other(), &(TIME + 3600).to_be_bytes()[2..]);
+        response.add_tsig(tsig);
+        let response = response.to_bytes().unwrap();
+
+        let err = signer
+            .verify_message_byte(Some(&request_mac), &response, true)
+            .unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            DnsSecErrorKind::TsigRemoteError(ResponseCode::BADTIME)
+        ));
+
+        // a forged BADTIME response is rejected on its MAC
+        let err = signer
+            .verify_message_byte(Some(&[1; 32]), &response, true)
+            .unwrap_err();
+        assert!(matches!(err.kind(), DnsSecErrorKind::HmacInvalid), "{err}");
+    }
 }
@@ -9,9 +9,14 @@
 
 #![deny(missing_docs)]
 
-use std::cmp::Ordering;
-use std::sync::Arc;
-use std::{fmt, io, sync};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    sync::Arc,
+};
+use core::{cmp::Ordering, fmt};
+#[cfg(feature = "std")]
+use std::{io, sync};
 
 #[cfg(feature = "backtrace")]
 pub use backtrace::Backtrace as ExtBacktrace;
@@ -21,13 +26,17 @@ use once_cell::sync::Lazy;
 #[cfg(feature = "dnssec-ring")]
 use ring::error::Unspecified;
 use thiserror::Error;
+#[cfg(feature = "std")]
 use tracing::debug;
 
 #[cfg(feature = "dnssec-ring")]
 use crate::dnssec::Proof;
 use crate::op::{Header, Query, ResponseCode};
-use crate::rr::{domain::Name, rdata::SOA, resource::RecordRef, Record, RecordType};
+#[cfg(feature = "std")]
+use crate::rr::resource::RecordRef;
+use crate::rr::{domain::Name, rdata::SOA, Record, RecordType};
 use crate::serialize::binary::DecodeError;
+#[cfg(feature = "std")]
 use crate::xfer::DnsResponse;
 
 /// Boolean for checking if backtrace is enabled at runtime
@@ -56,7 +65,7 @@ macro_rules! trace {
 }
 
 /// An alias for results returned by functions of this crate
-pub(crate) type ProtoResult<T> = ::std::result::Result<T, ProtoError>;
+pub(crate) type ProtoResult<T> = core::result::Result<T, ProtoError>;
 
 /// The error kind for errors that get returned in the crate
 #[derive(Debug, EnumAsInner, Error)]
@@ -75,6 +84,7 @@ pub enum ProtoErrorKind {
     Busy,
 
     /// An error caused by a canceled future
+    #[cfg(feature = "std")]
     #[error("future was canceled: {0:?}")]
     Canceled(futures_channel::oneshot::Canceled),
 
@@ -149,6 +159,13 @@ pub enum ProtoErrorKind {
         ptr: u16,
     },
 
+    /// More compression pointers than allowed were followed for a name
+    #[error("too many compression pointers, exceeded at idx: {idx}")]
+    TooManyCompressionPointers {
+        /// index of the pointer exceeding the limit
+        idx: usize,
+    },
+
     /// The maximum buffer size was exceeded
     #[error("maximum buffer size exceeded: {0}")]
     MaxBufferSizeExceeded(usize),
@@ -241,6 +258,7 @@ pub enum ProtoErrorKind {
 
     // foreign
     /// An error got returned from IO
+    #[cfg(feature = "std")]
     #[error("io error: {0}")]
     Io(Arc<io::Error>),
 
@@ -266,20 +284,21 @@ pub enum ProtoErrorKind {
     Timeout,
 
     /// An url parsing error
+    #[cfg(feature = "std")]
     #[error("url parsing error")]
     UrlParsing(#[from] url::ParseError),
 
     /// A utf8 parsing error
     #[error("error parsing utf8 string")]
-    Utf8(#[from] std::str::Utf8Error),
+    Utf8(#[from] core::str::Utf8Error),
 
     /// A utf8 parsing error
     #[error("error parsing utf8 string")]
-    FromUtf8(#[from] std::string::FromUtf8Error),
+    FromUtf8(#[from] alloc::string::FromUtf8Error),
 
     /// An int parsing error
     #[error("error parsing int")]
-    ParseInt(#[from] std::num::ParseIntError),
+    ParseInt(#[from] core::num::ParseIntError),
 
     /// A Quinn (Quic) connection error occurred
     #[cfg(feature = "dns-over-quic")]
@@ -289,17 +308,22 @@ pub enum ProtoErrorKind {
     /// A Quinn (QUIC) connection error occurred
     #[cfg(feature = "dns-over-quic")]
     #[error("error with quic connection: {0}")]
-    QuinnConnection(#[from] quinn::ConnectionError),
+    QuinnConnection(quinn::ConnectionError),
 
     /// A Quinn (QUIC) write error occurred
     #[cfg(feature = "dns-over-quic")]
     #[error("error writing to quic connection: {0}")]
-    QuinnWriteError(#[from] quinn::WriteError),
+    QuinnWriteError(quinn::WriteError),
 
     /// A Quinn (QUIC) read error occurred
     #[cfg(feature = "dns-over-quic")]
     #[error("error writing to quic read: {0}")]
-    QuinnReadError(#[from] quinn::ReadExactError),
+    QuinnReadError(quinn::ReadExactError),
+
+    /// The remote peer closed the QUIC connection, or aborted the stream, with a DoQ error code
+    #[cfg(feature = "dns-over-quic")]
+    #[error("quic connection or stream aborted by peer: {0:?}")]
+    QuicAborted(crate::quic::DoqErrorCode),
 
     /// A Quinn (QUIC) read error occurred
     #[cfg(feature = "dns-over-quic")]
@@ -475,16 +499,29 @@ impl ProtoError {
     }
 
     /// Returns true if this is a std::io::Error
+    #[cfg(feature = "std")]
     #[inline]
     pub fn is_io(&self) -> bool {
         matches!(*self.kind, ProtoErrorKind::Io(..))
     }
 
+    /// Returns the DoQ error code if the peer aborted the QUIC connection or stream
+    #[cfg(feature = "dns-over-quic")]
+    #[inline]
+    pub fn doq_error_code(&self) -> Option<crate::quic::DoqErrorCode> {
+        match *self.kind {
+            ProtoErrorKind::QuicAborted(code) => Some(code),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "std")]
     pub(crate) fn as_dyn(&self) -> &(dyn std::error::Error + 'static) {
         self
     }
 
     /// A conversion to determine if the response is an error
+    #[cfg(feature = "std")]
     pub fn from_response(response: DnsResponse, trust_nx: bool) -> Result<DnsResponse, Self> {
         use ResponseCode::*;
         debug!("response: {}", *response);
@@ -605,6 +642,7 @@ impl ProtoError {
             _ => (),
         }
 
+        #[cfg(feature = "std")]
         match (kind, other) {
             (ProtoErrorKind::Io { .. }, ProtoErrorKind::Io { .. }) => return Ordering::Equal,
             (ProtoErrorKind::Io { .. }, _) => return Ordering::Greater,
@@ -661,6 +699,9 @@ impl From<DecodeError> for ProtoError {
             DecodeError::PointerNotPriorToLabel { idx, ptr } => {
                 ProtoErrorKind::PointerNotPriorToLabel { idx, ptr }
             }
+            DecodeError::TooManyCompressionPointers { idx } => {
+                ProtoErrorKind::TooManyCompressionPointers { idx }
+            }
             DecodeError::LabelBytesTooLong(len) => ProtoErrorKind::LabelBytesTooLong(len),
             DecodeError::UnrecognizedLabelCode(code) => ProtoErrorKind::UnrecognizedLabelCode(code),
             DecodeError::DomainNameTooLong(len) => ProtoErrorKind::DomainNameTooLong(len),
@@ -685,6 +726,7 @@ impl From<String> for ProtoError {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<io::Error> for ProtoErrorKind {
     fn from(e: io::Error) -> Self {
         match e.kind() {
@@ -694,12 +736,50 @@ impl From<io::Error> for ProtoErrorKind {
     }
 }
 
+#[cfg(feature = "dns-over-quic")]
+impl From<quinn::ConnectionError> for ProtoErrorKind {
+    fn from(e: quinn::ConnectionError) -> Self {
+        match e {
+            quinn::ConnectionError::ApplicationClosed(close) => {
+                Self::QuicAborted(close.error_code.into())
+            }
+            e => Self::QuinnConnection(e),
+        }
+    }
+}
+
+#[cfg(feature = "dns-over-quic")]
+impl From<quinn::WriteError> for ProtoErrorKind {
+    fn from(e: quinn::WriteError) -> Self {
+        match e {
+            quinn::WriteError::Stopped(code) => Self::QuicAborted(code.into()),
+            quinn::WriteError::ConnectionLost(e) => e.into(),
+            e => Self::QuinnWriteError(e),
+        }
+    }
+}
+
+#[cfg(feature = "dns-over-quic")]
+impl From<quinn::ReadExactError> for ProtoErrorKind {
+    fn from(e: quinn::ReadExactError) -> Self {
+        match e {
+            quinn::ReadExactError::ReadError(quinn::ReadError::Reset(code)) => {
+                Self::QuicAborted(code.into())
+            }
+            quinn::ReadExactError::ReadError(quinn::ReadError::ConnectionLost(e)) => e.into(),
+            e => Self::QuinnReadError(e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
 impl<T> From<sync::PoisonError<T>> for ProtoError {
     fn from(_e: sync::PoisonError<T>) -> Self {
         ProtoErrorKind::Poisoned.into()
     }
 }
 
+#[cfg(feature = "std")]
 impl From<ProtoError> for io::Error {
     fn from(e: ProtoError) -> Self {
         match e.kind() {
@@ -728,6 +808,7 @@ impl Clone for ProtoErrorKind {
         match *self {
             BadQueryCount(count) => BadQueryCount(count),
             Busy => Busy,
+            #[cfg(feature = "std")]
             Canceled(ref c) => Canceled(*c),
             CharacterDataTooLong { max, len } => CharacterDataTooLong { max, len },
             LabelOverlapsWithOther { label, other } => LabelOverlapsWithOther { label, other },
@@ -741,6 +822,7 @@ impl Clone for ProtoErrorKind {
             IncorrectRDataLengthRead { read, len } => IncorrectRDataLengthRead { read, len },
             LabelBytesTooLong(len) => LabelBytesTooLong(len),
             PointerNotPriorToLabel { idx, ptr } => PointerNotPriorToLabel { idx, ptr },
+            TooManyCompressionPointers { idx } => TooManyCompressionPointers { idx },
             MaxBufferSizeExceeded(max) => MaxBufferSizeExceeded(max),
             MaxRecordLimitExceeded { count, record_type } => {
                 MaxRecordLimitExceeded { count, record_type }
@@ -781,12 +863,14 @@ impl Clone for ProtoErrorKind {
             UnrecognizedLabelCode(value) => UnrecognizedLabelCode(value),
             UnrecognizedNsec3Flags(flags) => UnrecognizedNsec3Flags(flags),
             UnrecognizedCsyncFlags(flags) => UnrecognizedCsyncFlags(flags),
+            #[cfg(feature = "std")]
             Io(ref e) => Io(e.clone()),
             Poisoned => Poisoned,
             #[cfg(feature = "dnssec-ring")]
             Ring(ref _e) => Ring(Unspecified),
             Timeout => Timeout,
             Timer => Timer,
+            #[cfg(feature = "std")]
             UrlParsing(ref e) => UrlParsing(*e),
             Utf8(ref e) => Utf8(*e),
             FromUtf8(ref e) => FromUtf8(e.clone()),
@@ -809,6 +893,8 @@ impl Clone for ProtoErrorKind {
             QuinnTlsConfigError(ref e) => QuinnTlsConfigError(e.clone()),
             #[cfg(feature = "dns-over-quic")]
             QuinnUnknownStreamError => QuinnUnknownStreamError,
+            #[cfg(feature = "dns-over-quic")]
+            QuicAborted(code) => QuicAborted(code),
             #[cfg(feature = "rustls")]
             RustlsError(ref e) => RustlsError(e.clone()),
             #[cfg(feature = "native-certs")]
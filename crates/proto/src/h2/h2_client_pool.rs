@@ -0,0 +1,398 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+
+use crate::error::ProtoError;
+use crate::xfer::{DnsHandle, DnsRequest, DnsResponse, DnsResponseStream};
+
+use super::HttpsClientStream;
+
+type ConnectFuture = BoxFuture<'static, Result<HttpsClientStream, ProtoError>>;
+
+/// A bounded pool of HTTP/2 connections to a single DNS-over-HTTPS server
+///
+/// Concurrent queries are multiplexed as streams over the established connections. A new
+/// connection is only opened when all the existing ones are busy, and the pool has not reached
+/// its maximum size yet. Connections without any in-flight streams are closed once they were idle
+/// for longer than the idle timeout, the next time the pool is used.
+///
+/// Construct the pool with [`super::HttpsClientStreamBuilder::build_pool`].
+#[derive(Clone)]
+pub struct HttpsClientPool {
+    inner: Arc<PoolInner>,
+}
+
+impl HttpsClientPool {
+    pub(super) fn new<F>(connect: F, max_connections: usize, idle_timeout: Duration) -> Self
+    where
+        F: Fn() -> ConnectFuture + Send + Sync + 'static,
+    {
+        Self {
+            inner: Arc::new(PoolInner {
+                connect: Box::new(connect),
+                max_connections: max_connections.max(1),
+                idle_timeout,
+                state: Mutex::new(PoolState::default()),
+            }),
+        }
+    }
+
+    /// Returns the current usage of the pool
+    pub fn stats(&self) -> HttpsClientPoolStats {
+        let mut state = self.inner.lock();
+        state.evict(Instant::now(), self.inner.idle_timeout);
+
+        HttpsClientPoolStats {
+            active_connections: state
+                .connections
+                .iter()
+                .filter(|conn| matches!(conn.stream.peek(), Some(Ok(_))))
+                .count(),
+            in_flight_streams: state.connections.iter().map(|conn| conn.in_flight).sum(),
+        }
+    }
+}
+
+impl fmt::Debug for HttpsClientPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpsClientPool")
+            .field("max_connections", &self.inner.max_connections)
+            .field("idle_timeout", &self.inner.idle_timeout)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl DnsHandle for HttpsClientPool {
+    type Response = DnsResponseStream;
+
+    fn send<R: Into<DnsRequest> + Unpin + Send + 'static>(&self, request: R) -> Self::Response {
+        let mut request = request.into();
+
+        // per the RFC, a zero id allows for the HTTP packet to be cached better
+        request.set_id(0);
+
+        let bytes = match request.to_vec() {
+            Ok(bytes) => bytes,
+            Err(err) => return err.into(),
+        };
+
+        let inner = Arc::clone(&self.inner);
+        Box::pin(async move { inner.send(Bytes::from(bytes)).await }).into()
+    }
+}
+
+/// Usage of an [`HttpsClientPool`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HttpsClientPoolStats {
+    /// Number of established connections
+    pub active_connections: usize,
+    /// Number of queries waiting for a response, including the ones waiting for a connection
+    pub in_flight_streams: usize,
+}
+
+struct PoolInner {
+    connect: Box<dyn Fn() -> ConnectFuture + Send + Sync>,
+    max_connections: usize,
+    idle_timeout: Duration,
+    state: Mutex<PoolState>,
+}
+
+impl PoolInner {
+    fn lock(&self) -> std::sync::MutexGuard<'_, PoolState> {
+        self.state.lock().expect("HttpsClientPool lock poisoned")
+    }
+
+    async fn send(&self, message: Bytes) -> Result<DnsResponse, ProtoError> {
+        let (id, stream) = self.checkout();
+        let mut checkout = Checkout {
+            pool: self,
+            id,
+            failed: false,
+        };
+
+        let stream = match stream.await {
+            Ok(stream) => stream,
+            Err(e) => {
+                checkout.failed = true;
+                return Err(e);
+            }
+        };
+
+        // the errors of single streams, e.g. a reset stream or an HTTP error status, leave the
+        //  connection to the other streams, only a failed connection is evicted
+        let response = stream.send_bytes(message).await;
+        checkout.failed = response.is_err() && stream.is_connection_failed();
+        response
+    }
+
+    /// Selects the connection for the next stream, opening a new one if all are busy
+    fn checkout(&self) -> (u64, Shared<ConnectFuture>) {
+        let now = Instant::now();
+        let mut state = self.lock();
+        state.evict(now, self.idle_timeout);
+
+        let least_busy = state
+            .connections
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, conn)| conn.in_flight)
+            .map(|(idx, conn)| (idx, conn.in_flight));
+
+        let idx = match least_busy {
+            Some((idx, in_flight))
+                if in_flight == 0 || state.connections.len() >= self.max_connections =>
+            {
+                idx
+            }
+            _ => {
+                let id = state.next_id;
+                state.next_id += 1;
+                state.connections.push(PooledConnection {
+                    id,
+                    stream: (self.connect)().shared(),
+                    in_flight: 0,
+                    last_used: now,
+                });
+                state.connections.len() - 1
+            }
+        };
+
+        let conn = &mut state.connections[idx];
+        conn.in_flight += 1;
+        conn.last_used = now;
+        (conn.id, conn.stream.clone())
+    }
+
+    fn checkin(&self, id: u64, failed: bool) {
+        let mut state = self.lock();
+        let Some(idx) = state.connections.iter().position(|conn| conn.id == id) else {
+            return;
+        };
+
+        // the connection is broken, new streams will go to a fresh connection
+        if failed {
+            state.connections.swap_remove(idx);
+            return;
+        }
+
+        let conn = &mut state.connections[idx];
+        conn.in_flight -= 1;
+        conn.last_used = Instant::now();
+    }
+}
+
+/// Returns the stream to the pool, also if the request was dropped before completion
+struct Checkout<'a> {
+    pool: &'a PoolInner,
+    id: u64,
+    failed: bool,
+}
+
+impl Drop for Checkout<'_> {
+    fn drop(&mut self) {
+        self.pool.checkin(self.id, self.failed);
+    }
+}
+
+#[derive(Default)]
+struct PoolState {
+    connections: Vec<PooledConnection>,
+    next_id: u64,
+}
+
+impl PoolState {
+    /// Drops the connections which failed to connect, or were idle for too long
+    fn evict(&mut self, now: Instant, idle_timeout: Duration) {
+        self.connections.retain(|conn| {
+            if matches!(conn.stream.peek(), Some(Err(_))) {
+                return false;
+            }
+
+            conn.in_flight > 0 || now.saturating_duration_since(conn.last_used) <= idle_timeout
+        });
+    }
+}
+
+struct PooledConnection {
+    id: u64,
+    stream: Shared<ConnectFuture>,
+    in_flight: usize,
+    last_used: Instant,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures_util::future::join_all;
+    use h2::server;
+    use http::header::CONTENT_TYPE;
+    use tokio::io::duplex;
+
+    use crate::http::{response, Version, MIME_APPLICATION_DNS};
+    use crate::op::{Message, Query};
+    use crate::rr::{Name, RecordType};
+    use crate::xfer::FirstAnswer;
+
+    use super::*;
+
+    /// Connects to an in-memory h2 server, which answers the queries after a short delay
+    fn connect(connects: Arc<AtomicUsize>) -> ConnectFuture {
+        connects.fetch_add(1, Ordering::SeqCst);
+
+        async move {
+            let (client_io, server_io) = duplex(64 * 1024);
+            tokio::spawn(serve(server_io));
+
+            let (h2, connection) = h2::client::handshake(client_io)
+                .await
+                .map_err(|e| ProtoError::from(format!("h2 handshake error: {e}")))?;
+            tokio::spawn(connection);
+
+            Ok(HttpsClientStream::new(
+                SocketAddr::from(([127, 0, 0, 1], 443)),
+                Arc::from("ns.example.com"),
+                Arc::from("/dns-query"),
+                h2,
+            ))
+        }
+        .boxed()
+    }
+
+    async fn serve(io: tokio::io::DuplexStream) {
+        let mut connection = server::handshake(io)
+            .await
+            .expect("server handshake failed");
+        while let Some(Ok((request, mut respond))) = connection.accept().await {
+            tokio::spawn(async move {
+                let mut body = request.into_body();
+                let mut bytes = Vec::new();
+                while let Some(Ok(data)) = body.data().await {
+                    bytes.extend_from_slice(&data);
+                }
+
+                let mut message = Message::from_vec(&bytes).expect("bad message");
+                if message.queries()[0].name().to_ascii() == "reset.example.com." {
+                    respond.send_reset(h2::Reason::REFUSED_STREAM);
+                    return;
+                }
+                message.set_message_type(crate::op::MessageType::Response);
+                let bytes = Bytes::from(message.to_vec().expect("bad message"));
+
+                tokio::time::sleep(Duration::from_millis(20)).await;
+
+                let mut response = response::new(Version::Http2, bytes.len()).unwrap();
+                response
+                    .headers_mut()
+                    .insert(CONTENT_TYPE, MIME_APPLICATION_DNS.parse().unwrap());
+                let mut send = respond.send_response(response, false).unwrap();
+                send.send_data(bytes, true).unwrap();
+            });
+        }
+    }
+
+    fn query() -> Message {
+        query_name("www.example.com.")
+    }
+
+    fn query_name(name: &str) -> Message {
+        let mut message = Message::new();
+        message.add_query(Query::query(Name::from_str(name).unwrap(), RecordType::A));
+        message
+    }
+
+    #[tokio::test]
+    async fn test_pool_max_connections() {
+        let connects = Arc::new(AtomicUsize::new(0));
+        let pool = {
+            let connects = Arc::clone(&connects);
+            HttpsClientPool::new(
+                move || connect(connects.clone()),
+                4,
+                Duration::from_secs(60),
+            )
+        };
+
+        let lookups = (0..50).map(|_| pool.send(query()).first_answer());
+        for response in join_all(lookups).await {
+            assert_eq!(
+                response.expect("lookup failed").queries(),
+                query().queries()
+            );
+        }
+
+        assert_eq!(connects.load(Ordering::SeqCst), 4);
+        assert_eq!(
+            pool.stats(),
+            HttpsClientPoolStats {
+                active_connections: 4,
+                in_flight_streams: 0,
+            }
+        );
+
+        // an idle connection is reused
+        pool.send(query()).first_answer().await.unwrap();
+        assert_eq!(connects.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_pool_idle_timeout() {
+        let connects = Arc::new(AtomicUsize::new(0));
+        let pool = {
+            let connects = Arc::clone(&connects);
+            HttpsClientPool::new(
+                move || connect(connects.clone()),
+                2,
+                Duration::from_millis(50),
+            )
+        };
+
+        pool.send(query()).first_answer().await.unwrap();
+        assert_eq!(pool.stats().active_connections, 1);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(pool.stats(), HttpsClientPoolStats::default());
+
+        pool.send(query()).first_answer().await.unwrap();
+        assert_eq!(connects.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_pool_stream_error() {
+        let connects = Arc::new(AtomicUsize::new(0));
+        let pool = {
+            let connects = Arc::clone(&connects);
+            HttpsClientPool::new(
+                move || connect(connects.clone()),
+                1,
+                Duration::from_secs(60),
+            )
+        };
+
+        pool.send(query()).first_answer().await.unwrap();
+
+        // a reset stream leaves the connection in the pool
+        assert!(pool
+            .send(query_name("reset.example.com."))
+            .first_answer()
+            .await
+            .is_err());
+        assert_eq!(pool.stats().active_connections, 1);
+
+        pool.send(query()).first_answer().await.unwrap();
+        assert_eq!(connects.load(Ordering::SeqCst), 1);
+    }
+}
@@ -14,6 +14,7 @@ use std::pin::Pin;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
 use bytes::{Buf, Bytes, BytesMut};
 use futures_util::future::{FutureExt, TryFutureExt};
@@ -27,6 +28,7 @@ use tokio::time::{error, timeout};
 use tokio_rustls::{client::TlsStream as TokioTlsClientStream, TlsConnector};
 use tracing::{debug, warn};
 
+use super::HttpsClientPool;
 use crate::error::ProtoError;
 use crate::http::Version;
 use crate::runtime::iocompat::AsyncIoStdAsTokio;
@@ -59,6 +61,39 @@ impl Display for HttpsClientStream {
 }
 
 impl HttpsClientStream {
+    pub(super) fn new(
+        name_server: SocketAddr,
+        name_server_name: Arc<str>,
+        query_path: Arc<str>,
+        h2: SendRequest<Bytes>,
+    ) -> Self {
+        Self {
+            name_server_name,
+            query_path,
+            name_server,
+            h2,
+            is_shutdown: false,
+        }
+    }
+
+    /// Sends an already serialized DNS message on a new stream of the connection
+    pub(super) fn send_bytes(
+        &self,
+        message: Bytes,
+    ) -> impl Future<Output = Result<DnsResponse, ProtoError>> + Send + 'static {
+        Self::inner_send(
+            self.h2.clone(),
+            message,
+            Arc::clone(&self.name_server_name),
+            Arc::clone(&self.query_path),
+        )
+    }
+
+    /// Returns true if the connection failed, it can not carry any new streams then
+    pub(super) fn is_connection_failed(&self) -> bool {
+        matches!(self.h2.clone().ready().now_or_never(), Some(Err(_)))
+    }
+
     async fn inner_send(
         h2: SendRequest<Bytes>,
         message: Bytes,
@@ -241,18 +276,18 @@ impl DnsRequestSender for HttpsClientStream {
         // per the RFC, a zero id allows for the HTTP packet to be cached better
         request.set_id(0);
 
+        if let Some(block_size) = request.options().edns_padding {
+            if let Err(err) = request.pad_to_block_size(block_size) {
+                return err.into();
+            }
+        }
+
         let bytes = match request.to_vec() {
             Ok(bytes) => bytes,
             Err(err) => return err.into(),
         };
 
-        Box::pin(Self::inner_send(
-            self.h2.clone(),
-            Bytes::from(bytes),
-            Arc::clone(&self.name_server_name),
-            Arc::clone(&self.query_path),
-        ))
-        .into()
+        Box::pin(self.send_bytes(Bytes::from(bytes))).into()
     }
 
     fn shutdown(&mut self) {
@@ -340,6 +375,37 @@ impl<P: RuntimeProvider> HttpsClientStreamBuilder<P> {
             tls: Some(tls),
         })
     }
+
+    /// Creates a pool of up to `max_connections` HttpsStreams to the specified name_server
+    ///
+    /// Connections are only established when needed, and are closed after they were not used for
+    /// `idle_timeout`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - IP and Port for the remote DNS resolver
+    /// * `dns_name` - The DNS name associated with a certificate
+    /// * `http_endpoint` - The HTTP endpoint where the remote DNS resolver provides service, typically `/dns-query`
+    /// * `max_connections` - The maximum number of concurrently open connections, at least one
+    /// * `idle_timeout` - The time after which a connection without any pending queries is closed
+    pub fn build_pool(
+        self,
+        name_server: SocketAddr,
+        dns_name: String,
+        http_endpoint: String,
+        max_connections: usize,
+        idle_timeout: Duration,
+    ) -> HttpsClientPool {
+        HttpsClientPool::new(
+            move || {
+                self.clone()
+                    .build(name_server, dns_name.clone(), http_endpoint.clone())
+                    .boxed()
+            },
+            max_connections,
+            idle_timeout,
+        )
+    }
 }
 
 /// A future that resolves to an HttpsClientStream
@@ -531,13 +597,12 @@ where
                             .map(|_: Result<(), ()>| ()),
                     );
 
-                    Self::Connected(Some(HttpsClientStream {
-                        name_server_name: Arc::clone(name_server_name),
-                        name_server: *name_server,
-                        query_path: Arc::clone(query_path),
-                        h2: send_request,
-                        is_shutdown: false,
-                    }))
+                    Self::Connected(Some(HttpsClientStream::new(
+                        *name_server,
+                        Arc::clone(name_server_name),
+                        Arc::clone(query_path),
+                        send_request,
+                    )))
                 }
                 Self::Connected(conn) => {
                     return Poll::Ready(Ok(conn.take().expect("cannot poll after complete")))
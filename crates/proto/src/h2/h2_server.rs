@@ -12,13 +12,15 @@ use std::str::FromStr;
 use std::sync::Arc;
 
 use bytes::{Bytes, BytesMut};
+use data_encoding::BASE64URL_NOPAD;
 use futures_util::stream::{Stream, StreamExt};
 use h2;
 use http::header::CONTENT_LENGTH;
-use http::{Method, Request};
+use http::{Method, Request, StatusCode, Uri};
 use tracing::debug;
 
 use crate::h2::HttpsError;
+use crate::http::error::ErrorKind;
 use crate::http::Version;
 
 /// Given an HTTP request, return a future that will result in the next sequence of bytes.
@@ -55,9 +57,36 @@ where
     }
 
     match *request.method() {
-        Method::GET => Err(format!("GET unimplemented: {}", request.method()).into()),
+        Method::GET => message_from_get(request.uri()),
         Method::POST => message_from_post(request.into_body(), content_length).await,
-        _ => Err(format!("bad method: {}", request.method()).into()),
+        _ => Err(ErrorKind::Status(
+            StatusCode::METHOD_NOT_ALLOWED,
+            format!("bad method: {}", request.method()),
+        )
+        .into()),
+    }
+}
+
+/// Deserialize the message from the base64url `dns` parameter of the query of a GET message
+pub(crate) fn message_from_get(uri: &Uri) -> Result<BytesMut, HttpsError> {
+    let dns = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|param| param.strip_prefix("dns="));
+    let Some(dns) = dns else {
+        return Err(
+            ErrorKind::Status(StatusCode::BAD_REQUEST, "no dns parameter".to_owned()).into(),
+        );
+    };
+
+    match BASE64URL_NOPAD.decode(dns.as_bytes()) {
+        Ok(message) => Ok(BytesMut::from(&message[..])),
+        Err(err) => Err(ErrorKind::Status(
+            StatusCode::BAD_REQUEST,
+            format!("malformed dns parameter: {err}"),
+        )
+        .into()),
     }
 }
 
@@ -146,4 +175,33 @@ mod tests {
         let msg_from_post = Message::from_vec(bytes.as_ref()).expect("bytes failed");
         assert_eq!(message, msg_from_post);
     }
+
+    #[test]
+    fn test_from_get() {
+        let mut message = Message::new();
+        message.set_id(0xabcd);
+        let msg_bytes = message.to_vec().unwrap();
+        let request =
+            request::new_get(Version::Http2, "ns.example.com", "/dns-query", &msg_bytes).unwrap();
+        let request = request.map(|()| TestBytesStream(vec![]));
+
+        let from_get = message_from(
+            Some(Arc::from("ns.example.com")),
+            "/dns-query".into(),
+            request,
+        );
+        let bytes = block_on(from_get).expect("GET failed");
+
+        let msg_from_get = Message::from_vec(bytes.as_ref()).expect("bytes failed");
+        assert_eq!(message, msg_from_get);
+    }
+
+    #[test]
+    fn test_from_get_malformed() {
+        for query in ["/dns-query?dns=AAAA*", "/dns-query?dns=AAAAA", "/dns-query"] {
+            let uri = Uri::from_str(&format!("https://ns.example.com{query}")).unwrap();
+            let err = message_from_get(&uri).unwrap_err();
+            assert_eq!(err.status_code(), StatusCode::BAD_REQUEST, "{query}");
+        }
+    }
 }
@@ -7,11 +7,13 @@
 
 //! TLS protocol related components for DNS over HTTPS (DoH)
 
+mod h2_client_pool;
 mod h2_client_stream;
 pub mod h2_server;
 
 pub use crate::http::error::{Error as HttpsError, Result as HttpsResult};
 
+pub use self::h2_client_pool::{HttpsClientPool, HttpsClientPoolStats};
 pub use self::h2_client_stream::{
     HttpsClientConnect, HttpsClientResponse, HttpsClientStream, HttpsClientStreamBuilder,
 };
@@ -244,6 +244,12 @@ impl DnsRequestSender for H3ClientStream {
         // per the RFC, a zero id allows for the HTTP packet to be cached better
         request.set_id(0);
 
+        if let Some(block_size) = request.options().edns_padding {
+            if let Err(err) = request.pad_to_block_size(block_size) {
+                return err.into();
+            }
+        }
+
         let bytes = match request.to_vec() {
             Ok(bytes) => bytes,
             Err(err) => return err.into(),
@@ -385,7 +391,7 @@ impl H3ClientStreamBuilder {
         dns_name: String,
         query_path: String,
     ) -> Result<H3ClientStream, ProtoError> {
-        let quic_connection = connect_quic(
+        let (quic_connection, _) = connect_quic(
             name_server,
             &dns_name,
             ALPN_H3,
@@ -10,6 +10,7 @@ use std::{fmt, io};
 
 use crate::error::ProtoError;
 use http::header::ToStrError;
+use http::StatusCode;
 use thiserror::Error;
 
 #[cfg(feature = "backtrace")]
@@ -41,6 +42,10 @@ pub enum ErrorKind {
     #[error("proto error: {0}")]
     ProtoError(#[from] ProtoError),
 
+    /// The request is rejected, with the HTTP status of the response
+    #[error("{1} ({0})")]
+    Status(StatusCode, String),
+
     #[error("h2: {0}")]
     #[cfg(feature = "dns-over-https-rustls")]
     H2(#[from] h2::Error),
@@ -63,6 +68,15 @@ impl Error {
     pub fn kind(&self) -> &ErrorKind {
         &self.kind
     }
+
+    /// The HTTP status of the response to a rejected request, `400 Bad Request` unless the error is
+    ///   more specific
+    pub fn status_code(&self) -> StatusCode {
+        match &self.kind {
+            ErrorKind::Status(status, _) => *status,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
 }
 
 impl fmt::Display for Error {
@@ -9,12 +9,13 @@
 
 use std::str::FromStr;
 
+use data_encoding::BASE64URL_NOPAD;
 use http::header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE};
-use http::{header, uri, Request, Uri};
+use http::{header, uri, Method, Request, StatusCode, Uri};
 use tracing::debug;
 
 use crate::error::ProtoError;
-use crate::http::error::Result;
+use crate::http::error::{Error, ErrorKind, Result};
 use crate::http::Version;
 
 /// Create a new Request for an http dns-message request
@@ -27,38 +28,13 @@ use crate::http::Version;
 /// request (as described in Section 7), encoded with base64url
 /// [RFC4648].
 /// ```
-#[allow(clippy::field_reassign_with_default)] // https://github.com/rust-lang/rust-clippy/issues/6527
 pub fn new(
     version: Version,
     name_server_name: &str,
     query_path: &str,
     message_len: usize,
 ) -> Result<Request<()>> {
-    // TODO: this is basically the GET version, but it is more expensive than POST
-    //   perhaps add an option if people want better HTTP caching options.
-
-    // let query = BASE64URL_NOPAD.encode(&message);
-    // let url = format!("/dns-query?dns={}", query);
-    // let request = Request::get(&url)
-    //     .header(header::CONTENT_TYPE, ::MIME_DNS_BINARY)
-    //     .header(header::HOST, &self.name_server_name as &str)
-    //     .header("authority", &self.name_server_name as &str)
-    //     .header(header::USER_AGENT, USER_AGENT)
-    //     .body(());
-
-    let mut parts = uri::Parts::default();
-    parts.path_and_query = Some(
-        uri::PathAndQuery::try_from(query_path)
-            .map_err(|e| ProtoError::from(format!("invalid DoH path: {e}")))?,
-    );
-    parts.scheme = Some(uri::Scheme::HTTPS);
-    parts.authority = Some(
-        uri::Authority::from_str(name_server_name)
-            .map_err(|e| ProtoError::from(format!("invalid authority: {e}")))?,
-    );
-
-    let url =
-        Uri::from_parts(parts).map_err(|e| ProtoError::from(format!("uri parse error: {e}")))?;
+    let url = uri(name_server_name, query_path)?;
 
     // TODO: add user agent to TypedHeaders
     let request = Request::builder()
@@ -74,6 +50,49 @@ pub fn new(
     Ok(request)
 }
 
+/// Create a new GET Request for an http dns-message request, the message is encoded in the
+/// `dns` parameter of the query
+///
+/// GET requests are more expensive than POST requests, but can be cached by HTTP caches.
+pub fn new_get(
+    version: Version,
+    name_server_name: &str,
+    query_path: &str,
+    message: &[u8],
+) -> Result<Request<()>> {
+    let query_path = format!("{query_path}?dns={}", BASE64URL_NOPAD.encode(message));
+    let url = uri(name_server_name, &query_path)?;
+
+    Request::builder()
+        .method("GET")
+        .uri(url)
+        .version(version.to_http())
+        .header(ACCEPT, crate::http::MIME_APPLICATION_DNS)
+        .body(())
+        .map_err(|e| ProtoError::from(format!("http stream errored: {e}")).into())
+}
+
+#[allow(clippy::field_reassign_with_default)] // https://github.com/rust-lang/rust-clippy/issues/6527
+fn uri(name_server_name: &str, query_path: &str) -> Result<Uri> {
+    let mut parts = uri::Parts::default();
+    parts.path_and_query = Some(
+        uri::PathAndQuery::try_from(query_path)
+            .map_err(|e| ProtoError::from(format!("invalid DoH path: {e}")))?,
+    );
+    parts.scheme = Some(uri::Scheme::HTTPS);
+    parts.authority = Some(
+        uri::Authority::from_str(name_server_name)
+            .map_err(|e| ProtoError::from(format!("invalid authority: {e}")))?,
+    );
+
+    Uri::from_parts(parts).map_err(|e| ProtoError::from(format!("uri parse error: {e}")).into())
+}
+
+/// The error rejecting a request with the HTTP `status`
+fn rejected(status: StatusCode, message: impl Into<String>) -> Error {
+    ErrorKind::Status(status, message.into()).into()
+}
+
 /// Verifies the request is something we know what to deal with
 pub fn verify<T>(
     version: Version,
@@ -86,30 +105,44 @@ pub fn verify<T>(
 
     // validate path
     if uri.path() != query_path {
-        return Err(format!("bad path: {}, expected: {}", uri.path(), query_path,).into());
+        return Err(rejected(
+            StatusCode::NOT_FOUND,
+            format!("bad path: {}, expected: {}", uri.path(), query_path),
+        ));
     }
 
     // we only accept HTTPS
     if Some(&uri::Scheme::HTTPS) != uri.scheme() {
-        return Err("must be HTTPS scheme".into());
+        return Err(rejected(StatusCode::BAD_REQUEST, "must be HTTPS scheme"));
     }
 
     // the authority must match our nameserver name
     if let Some(name_server) = name_server {
         if let Some(authority) = uri.authority() {
             if authority.host() != name_server {
-                return Err("incorrect authority".into());
+                return Err(rejected(StatusCode::BAD_REQUEST, "incorrect authority"));
             }
         } else {
-            return Err("no authority in HTTPS request".into());
+            return Err(rejected(
+                StatusCode::BAD_REQUEST,
+                "no authority in HTTPS request",
+            ));
         }
     }
 
+    // the GET requests have no content, the message is in the query
     // TODO: switch to mime::APPLICATION_DNS when that stabilizes
-    match request.headers().get(CONTENT_TYPE).map(|v| v.to_str()) {
-        Some(Ok(ctype)) if ctype == crate::http::MIME_APPLICATION_DNS => {}
-        _ => return Err("unsupported content type".into()),
-    };
+    if request.method() == Method::POST {
+        match request.headers().get(CONTENT_TYPE).map(|v| v.to_str()) {
+            Some(Ok(ctype)) if ctype == crate::http::MIME_APPLICATION_DNS => {}
+            _ => {
+                return Err(rejected(
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    "unsupported content type",
+                ))
+            }
+        };
+    }
 
     // TODO: switch to mime::APPLICATION_DNS when that stabilizes
     match request.headers().get(ACCEPT).map(|v| v.to_str()) {
@@ -131,11 +164,19 @@ pub fn verify<T>(
             }
 
             if !found {
-                return Err("does not accept content type".into());
+                return Err(rejected(
+                    StatusCode::NOT_ACCEPTABLE,
+                    "does not accept content type",
+                ));
             }
         }
         Some(Err(e)) => return Err(e.into()),
-        None => return Err("Accept is unspecified".into()),
+        None => {
+            return Err(rejected(
+                StatusCode::NOT_ACCEPTABLE,
+                "Accept is unspecified",
+            ))
+        }
     };
 
     if request.version() != version.to_http() {
@@ -145,7 +186,7 @@ pub fn verify<T>(
             #[cfg(feature = "dns-over-h3")]
             Version::Http3 => "only HTTP/3 supported",
         };
-        return Err(message.into());
+        return Err(rejected(StatusCode::HTTP_VERSION_NOT_SUPPORTED, message));
     }
 
     debug!(
@@ -48,3 +48,13 @@ pub fn new(version: Version, message_len: usize) -> Result<Response<()>> {
         .body(())
         .map_err(|e| ProtoError::from(format!("invalid response: {e}")).into())
 }
+
+/// Create a new Response without content, rejecting a request with the non-successful `status`
+pub fn error(version: Version, status: StatusCode) -> Result<Response<()>> {
+    Response::builder()
+        .status(status)
+        .version(version.to_http())
+        .header(CONTENT_LENGTH, 0)
+        .body(())
+        .map_err(|e| ProtoError::from(format!("invalid response: {e}")).into())
+}
@@ -27,9 +27,21 @@
 )]
 #![recursion_limit = "2048"]
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
+#![cfg_attr(not(feature = "std"), no_std)]
 
 //! Hickory DNS Protocol library
+//!
+//! Without the default `std` feature, the crate is `no_std` and only requires `alloc`. It then
+//! only provides the messages, their records and their binary encoding, e.g. for parsing the
+//! datagrams received on a microcontroller with [`op::Message::from_vec`]. This requires Rust 1.81.
 
+#[macro_use]
+extern crate alloc;
+#[cfg(all(test, not(feature = "std")))]
+#[macro_use(dbg, eprintln, println)]
+extern crate std;
+
+#[cfg(feature = "std")]
 macro_rules! try_ready_stream {
     ($e:expr) => {{
         match $e {
@@ -56,24 +68,38 @@ pub mod op;
 #[cfg(all(feature = "dns-over-quic", feature = "tokio-runtime"))]
 pub mod quic;
 pub mod rr;
+#[cfg(feature = "std")]
 pub mod runtime;
 #[cfg(feature = "dns-over-rustls")]
 pub mod rustls;
 pub mod serialize;
+#[cfg(feature = "std")]
 pub mod tcp;
-#[cfg(any(test, feature = "testing"))]
+#[cfg(all(feature = "std", any(test, feature = "testing")))]
 pub mod tests;
+#[cfg(feature = "std")]
 pub mod udp;
+#[cfg(feature = "std")]
 pub mod xfer;
 
+#[cfg(feature = "std")]
 #[doc(hidden)]
 pub use crate::xfer::dns_handle::{DnsHandle, DnsStreamHandle};
+#[cfg(feature = "std")]
 #[doc(hidden)]
 pub use crate::xfer::dns_multiplexer::DnsMultiplexer;
+#[cfg(feature = "std")]
 #[doc(hidden)]
 pub use crate::xfer::retry_dns_handle::RetryDnsHandle;
+#[cfg(feature = "std")]
 #[doc(hidden)]
 pub use crate::xfer::BufDnsStreamHandle;
 #[cfg(feature = "backtrace")]
 pub use error::{ExtBacktrace, ENABLE_BACKTRACE};
 pub use error::{ForwardData, ForwardNSData, ProtoError, ProtoErrorKind};
+
+// `core::net` is only stable since Rust 1.77, the std build keeps the MSRV
+#[cfg(not(feature = "std"))]
+use core::net;
+#[cfg(feature = "std")]
+use std::net;
@@ -0,0 +1,458 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The presentation of messages in the output format of BIND's `dig`
+
+use alloc::string::{String, ToString};
+use core::fmt::{self, Write};
+
+use data_encoding::{HEXLOWER, HEXUPPER};
+
+use crate::op::{Edns, Message, OpCode, ResponseCode};
+use crate::rr::rdata::opt::EdnsOption;
+use crate::rr::{DNSClass, RData, Record, RecordType};
+
+/// The columns of the name, TTL, class and type of records, as in the default style of `dig`
+const COLUMNS: [usize; 4] = [24, 32, 40, 48];
+
+impl Message {
+    /// Returns the message in the presentation format of BIND's `dig`
+    ///
+    /// This allows comparing the output of tools using the message with the one of `dig`. The
+    /// output starts with the header, which `dig` prints after the `Got answer:` line.
+    ///
+    /// Rdata of unknown types is printed in the generic format of
+    /// [RFC 3597](https://tools.ietf.org/html/rfc3597#section-5), e.g. `\# 4 C0000201`.
+    ///
+    /// ```
+    /// use hickory_proto::op::{Message, MessageType, Query};
+    /// use hickory_proto::rr::{rdata::A, Name, RData, Record, RecordType};
+    ///
+    /// let name = Name::from_ascii("www.example.com.").unwrap();
+    /// let mut message = Message::new();
+    /// message
+    ///     .set_id(4660)
+    ///     .set_message_type(MessageType::Response)
+    ///     .set_recursion_desired(true)
+    ///     .add_query(Query::query(name.clone(), RecordType::A))
+    ///     .add_answer(Record::from_rdata(name, 300, RData::A(A::new(192, 0, 2, 1))));
+    ///
+    /// assert_eq!(
+    ///     message.to_dig_string(),
+    ///     ";; ->>HEADER<<- opcode: QUERY, status: NOERROR, id: 4660\n\
+    ///      ;; flags: qr rd; QUERY: 1, ANSWER: 1, AUTHORITY: 0, ADDITIONAL: 0\n\
+    ///      \n\
+    ///      ;; QUESTION SECTION:\n\
+    ///      ;www.example.com.\t\tIN\tA\n\
+    ///      \n\
+    ///      ;; ANSWER SECTION:\n\
+    ///      www.example.com.\t300\tIN\tA\t192.0.2.1\n"
+    /// );
+    /// ```
+    pub fn to_dig_string(&self) -> String {
+        let mut output = String::new();
+        self.write_dig(&mut output)
+            .expect("writing to a String does not fail");
+        output
+    }
+
+    fn write_dig(&self, f: &mut String) -> fmt::Result {
+        let sections = match self.op_code() {
+            OpCode::Update => ["ZONE", "PREREQUISITE", "UPDATE", "ADDITIONAL"],
+            _ => ["QUESTION", "ANSWER", "AUTHORITY", "ADDITIONAL"],
+        };
+        let counts = match self.op_code() {
+            OpCode::Update => ["ZONE", "PREREQ", "UPDATE", "ADDITIONAL"],
+            _ => ["QUERY", "ANSWER", "AUTHORITY", "ADDITIONAL"],
+        };
+
+        writeln!(
+            f,
+            ";; ->>HEADER<<- opcode: {}, status: {}, id: {}",
+            op_code_str(self.op_code()),
+            response_code_str(self.response_code()),
+            self.id()
+        )?;
+
+        f.push_str(";; flags:");
+        let flags = [
+            (
+                "qr",
+                self.message_type() == crate::op::MessageType::Response,
+            ),
+            ("aa", self.authoritative()),
+            ("tc", self.truncated()),
+            ("rd", self.recursion_desired()),
+            ("ra", self.recursion_available()),
+            ("ad", self.authentic_data()),
+            ("cd", self.checking_disabled()),
+        ];
+        for (flag, _) in flags.iter().filter(|(_, set)| *set) {
+            write!(f, " {flag}")?;
+        }
+
+        // the OPT record and signatures are counted in the additional section
+        let additional_count = self.additionals().len()
+            + usize::from(self.extensions().is_some())
+            + self.signature().len();
+        writeln!(
+            f,
+            "; {}: {}, {}: {}, {}: {}, {}: {}",
+            counts[0],
+            self.queries().len(),
+            counts[1],
+            self.answers().len(),
+            counts[2],
+            self.name_servers().len(),
+            counts[3],
+            additional_count,
+        )?;
+
+        if let Some(edns) = self.extensions() {
+            f.push_str("\n;; OPT PSEUDOSECTION:\n");
+            write_edns(f, edns)?;
+        }
+
+        if !self.queries().is_empty() {
+            writeln!(f, "\n;; {} SECTION:", sections[0])?;
+            for query in self.queries() {
+                let name = format!(";{}", query.name());
+                write_columns(f, &[&name, "", &class_str(query.query_class())])?;
+                writeln!(f, "{}", query.query_type())?;
+            }
+        }
+
+        for (section, records) in
+            sections[1..]
+                .iter()
+                .zip([self.answers(), self.name_servers(), self.additionals()])
+        {
+            if records.is_empty() {
+                continue;
+            }
+            writeln!(f, "\n;; {section} SECTION:")?;
+            for record in records {
+                write_record(f, record)?;
+            }
+        }
+
+        if !self.signature().is_empty() {
+            let pseudo_section = match self.signature()[0].record_type() {
+                RecordType::TSIG => "TSIG",
+                _ => "SIG0",
+            };
+            writeln!(f, "\n;; {pseudo_section} PSEUDOSECTION:")?;
+            for record in self.signature() {
+                write_record(f, record)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn write_edns(f: &mut String, edns: &Edns) -> fmt::Result {
+    write!(f, "; EDNS: version: {}, flags:", edns.version())?;
+    if edns.flags().dnssec_ok {
+        f.push_str(" do");
+    }
+    writeln!(f, "; udp: {}", edns.max_payload())?;
+
+    for (_, option) in edns.options().as_ref() {
+        match option {
+            #[cfg(feature = "dnssec-ring")]
+            EdnsOption::DAU(algorithms)
+            | EdnsOption::DHU(algorithms)
+            | EdnsOption::N3U(algorithms) => {
+                let name = match option {
+                    EdnsOption::DAU(_) => "DAU",
+                    EdnsOption::DHU(_) => "DHU",
+                    _ => "N3U",
+                };
+                write!(f, "; {name}:")?;
+                for algorithm in algorithms.iter() {
+                    write!(f, " {algorithm}")?;
+                }
+                f.push('\n');
+            }
+            EdnsOption::Subnet(subnet) => writeln!(
+                f,
+                "; CLIENT-SUBNET: {}/{}/{}",
+                subnet.addr(),
+                subnet.source_prefix(),
+                subnet.scope_prefix()
+            )?,
+            EdnsOption::Cookie(cookie) => {
+                writeln!(
+                    f,
+                    "; COOKIE: {}{}",
+                    HEXLOWER.encode(cookie.client()),
+                    HEXLOWER.encode(cookie.server().unwrap_or_default())
+                )?;
+            }
+            EdnsOption::TcpKeepalive(None) => f.push_str("; TCP-KEEPALIVE\n"),
+            EdnsOption::TcpKeepalive(Some(timeout)) => {
+                writeln!(f, "; TCP-KEEPALIVE: {}.{} secs", timeout / 10, timeout % 10)?
+            }
+            EdnsOption::Padding(len) => writeln!(f, "; PADDING: ({len} bytes)")?,
+            EdnsOption::ExtendedDnsError(ede) => {
+                write!(f, "; EDE: {}", ede.info_code())?;
+                if !ede.extra_text().is_empty() {
+                    write!(f, ": ({})", ede.extra_text())?;
+                }
+                f.push('\n');
+            }
+            EdnsOption::Unknown(code, data) => {
+                write!(f, "; OPT={code}:")?;
+                for byte in data {
+                    write!(f, " {byte:02x}")?;
+                }
+                f.push_str(" (\"");
+                for &byte in data {
+                    f.push(match byte {
+                        0x20..=0x7E => char::from(byte),
+                        _ => '.',
+                    });
+                }
+                f.push_str("\")\n");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_record(f: &mut String, record: &Record) -> fmt::Result {
+    write_columns(
+        f,
+        &[
+            &record.name().to_string(),
+            &record.ttl().to_string(),
+            &class_str(record.dns_class()),
+            &record.record_type().to_string(),
+        ],
+    )?;
+
+    match record.data() {
+        RData::TXT(txt) => {
+            for (i, data) in txt.txt_data().iter().enumerate() {
+                if i > 0 {
+                    f.push(' ');
+                }
+                write_character_string(f, data)?;
+            }
+        }
+        RData::HINFO(hinfo) => {
+            write_character_string(f, hinfo.cpu())?;
+            f.push(' ');
+            write_character_string(f, hinfo.os())?;
+        }
+        RData::NULL(null) => write_generic(f, null.anything())?,
+        #[allow(deprecated)]
+        RData::ZERO | RData::Update0(_) => write_generic(f, &[])?,
+        rdata => write!(f, "{rdata}")?,
+    }
+    f.push('\n');
+
+    Ok(())
+}
+
+/// Writes the fields separated by tabs, so that each of them ends before its column
+fn write_columns(f: &mut String, fields: &[&str]) -> fmt::Result {
+    let mut position = 0;
+    for (field, column) in fields.iter().zip(COLUMNS) {
+        f.push_str(field);
+        position += field.len();
+        loop {
+            f.push('\t');
+            position = (position / 8 + 1) * 8;
+            if position >= column {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes a quoted character string, escaping the quotes, backslashes and unprintable bytes
+fn write_character_string(f: &mut String, data: &[u8]) -> fmt::Result {
+    f.push('"');
+    for &byte in data {
+        match byte {
+            b'"' | b'\\' => write!(f, "\\{}", char::from(byte))?,
+            0x20..=0x7E => f.push(char::from(byte)),
+            _ => write!(f, "\\{byte:03}")?,
+        }
+    }
+    f.push('"');
+    Ok(())
+}
+
+/// Writes rdata in the generic format of RFC 3597
+fn write_generic(f: &mut String, data: &[u8]) -> fmt::Result {
+    write!(f, "\\# {}", data.len())?;
+    if !data.is_empty() {
+        f.push(' ');
+        f.push_str(&HEXUPPER.encode(data));
+    }
+    Ok(())
+}
+
+fn class_str(class: DNSClass) -> String {
+    match class {
+        DNSClass::Unknown(code) => format!("CLASS{code}"),
+        _ => class.to_string(),
+    }
+}
+
+fn op_code_str(op_code: OpCode) -> String {
+    match op_code {
+        OpCode::Unknown(code) => format!("RESERVED{code}"),
+        _ => op_code.to_string(),
+    }
+}
+
+fn response_code_str(response_code: ResponseCode) -> String {
+    let name = match response_code {
+        ResponseCode::NoError => "NOERROR",
+        ResponseCode::FormErr => "FORMERR",
+        ResponseCode::ServFail => "SERVFAIL",
+        ResponseCode::NXDomain => "NXDOMAIN",
+        ResponseCode::NotImp => "NOTIMP",
+        ResponseCode::Refused => "REFUSED",
+        ResponseCode::YXDomain => "YXDOMAIN",
+        ResponseCode::YXRRSet => "YXRRSET",
+        ResponseCode::NXRRSet => "NXRRSET",
+        ResponseCode::NotAuth => "NOTAUTH",
+        ResponseCode::NotZone => "NOTZONE",
+        ResponseCode::BADVERS | ResponseCode::BADSIG => "BADVERS",
+        ResponseCode::BADKEY => "BADKEY",
+        ResponseCode::BADTIME => "BADTIME",
+        ResponseCode::BADMODE => "BADMODE",
+        ResponseCode::BADNAME => "BADNAME",
+        ResponseCode::BADALG => "BADALG",
+        ResponseCode::BADTRUNC => "BADTRUNC",
+        ResponseCode::BADCOOKIE => "BADCOOKIE",
+        ResponseCode::Unknown(code) => return format!("RESERVED{code}"),
+    };
+    name.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::op::{MessageType, Query};
+    use crate::rr::rdata::opt::Cookie;
+    use crate::rr::rdata::{A, CNAME, NULL, SOA, TXT};
+    use crate::rr::Name;
+
+    #[test]
+    fn test_multi_record_response() {
+        let zone = Name::from_ascii("example.com.").unwrap();
+        let alias = Name::from_ascii("alias.example.com.").unwrap();
+        let www = Name::from_ascii("www.example.com.").unwrap();
+
+        let mut edns = Edns::new();
+        edns.set_dnssec_ok(true).set_max_payload(1232);
+        edns.options_mut().insert(EdnsOption::Cookie(Cookie::new(
+            [1, 2, 3, 4, 5, 6, 7, 8],
+            Some(vec![0xab; 16]),
+        )));
+
+        let mut message = Message::new();
+        message
+            .set_id(48879)
+            .set_message_type(MessageType::Response)
+            .set_authoritative(true)
+            .set_recursion_desired(true)
+            .add_query(Query::query(alias.clone(), RecordType::A))
+            .add_answer(Record::from_rdata(
+                alias,
+                300,
+                RData::CNAME(CNAME(www.clone())),
+            ))
+            .add_answer(Record::from_rdata(
+                www.clone(),
+                300,
+                RData::A(A::new(192, 0, 2, 1)),
+            ))
+            .add_answer(Record::from_rdata(
+                www.clone(),
+                300,
+                RData::TXT(TXT::new(vec![
+                    "v=1 \"quoted\"".to_string(),
+                    "tab\there".to_string(),
+                ])),
+            ))
+            .add_answer(Record::from_rdata(
+                www,
+                300,
+                RData::Unknown {
+                    code: RecordType::Unknown(65280),
+                    rdata: NULL::with(vec![0xc0, 0x00, 0x02, 0x01]),
+                },
+            ))
+            .add_name_server(Record::from_rdata(
+                zone.clone(),
+                3600,
+                RData::SOA(SOA::new(
+                    Name::from_ascii("ns.example.com.").unwrap(),
+                    Name::from_ascii("hostmaster.example.com.").unwrap(),
+                    2024010101,
+                    7200,
+                    3600,
+                    1209600,
+                    300,
+                )),
+            ))
+            .set_edns(edns);
+
+        assert_eq!(
+            message.to_dig_string(),
+            "\
+;; ->>HEADER<<- opcode: QUERY, status: NOERROR, id: 48879
+;; flags: qr aa rd; QUERY: 1, ANSWER: 4, AUTHORITY: 1, ADDITIONAL: 1
+
+;; OPT PSEUDOSECTION:
+; EDNS: version: 0, flags: do; udp: 1232
+; COOKIE: 0102030405060708abababababababababababababababab
+
+;; QUESTION SECTION:
+;alias.example.com.\t\tIN\tA
+
+;; ANSWER SECTION:
+alias.example.com.\t300\tIN\tCNAME\twww.example.com.
+www.example.com.\t300\tIN\tA\t192.0.2.1
+www.example.com.\t300\tIN\tTXT\t\"v=1 \\\"quoted\\\"\" \"tab\\009here\"
+www.example.com.\t300\tIN\tTYPE65280\t\\# 4 C0000201
+
+;; AUTHORITY SECTION:
+example.com.\t\t3600\tIN\tSOA\tns.example.com. hostmaster.example.com. 2024010101 7200 3600 1209600 300
+"
+        );
+    }
+
+    #[test]
+    fn test_update_and_error_response() {
+        let mut message = Message::error_msg(7, OpCode::Update, ResponseCode::NotAuth);
+        message.add_query(Query::query(
+            Name::from_ascii("example.com.").unwrap(),
+            RecordType::SOA,
+        ));
+
+        assert_eq!(
+            message.to_dig_string(),
+            "\
+;; ->>HEADER<<- opcode: UPDATE, status: NOTAUTH, id: 7
+;; flags: qr; ZONE: 1, PREREQ: 0, UPDATE: 0, ADDITIONAL: 0
+
+;; ZONE SECTION:
+;example.com.\t\t\tIN\tSOA
+"
+        );
+    }
+}
@@ -7,7 +7,8 @@
 
 //! Extended DNS options
 
-use std::fmt;
+use alloc::{string::String, vec::Vec};
+use core::fmt;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -23,7 +24,7 @@ use crate::{
         },
         DNSClass, Name, RData, Record, RecordType,
     },
-    serialize::binary::{BinEncodable, BinEncoder},
+    serialize::binary::{BinDecoder, BinEncodable, BinEncoder},
 };
 
 /// Edns implements the higher level concepts for working with extended dns as it is used to create or be
@@ -61,6 +62,11 @@ impl Edns {
         Self::default()
     }
 
+    /// Creates a builder of the EDNS section of a request, see [`EdnsBuilder`]
+    pub fn builder() -> EdnsBuilder {
+        EdnsBuilder::new()
+    }
+
     /// The high order bytes for the response code in the DNS Message
     pub fn rcode_high(&self) -> u8 {
         self.rcode_high
@@ -129,6 +135,7 @@ impl Edns {
 
         #[cfg(feature = "dnssec-ring")]
         algorithms.set(Algorithm::ED25519);
+        algorithms.set(Algorithm::ED448);
         algorithms.set(Algorithm::ECDSAP256SHA256);
         algorithms.set(Algorithm::ECDSAP384SHA384);
         algorithms.set(Algorithm::RSASHA256);
@@ -161,6 +168,61 @@ impl Edns {
     }
 }
 
+/// A builder of the EDNS section of a request, emitted as the OPT RR of the message
+///
+/// The EDNS version is 0, the only one defined by RFC 6891, and the payload size defaults to 512.
+///
+/// ```
+/// use hickory_proto::op::{Edns, Message};
+/// use hickory_proto::rr::rdata::opt::EdnsOption;
+///
+/// let edns = Edns::builder()
+///     .dnssec_ok(true)
+///     .udp_payload_size(1232)
+///     .option(EdnsOption::Unknown(65001, b"hickory".to_vec()))
+///     .build();
+///
+/// let mut message = Message::new();
+/// message.set_edns(edns);
+/// assert!(message.edns().unwrap().flags().dnssec_ok);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct EdnsBuilder {
+    edns: Edns,
+}
+
+impl EdnsBuilder {
+    /// Creates the builder of an EDNS section without option, and the DO bit clear
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the DO bit, requesting the DNSSEC records, RFC 3225
+    pub fn dnssec_ok(mut self, dnssec_ok: bool) -> Self {
+        self.edns.set_dnssec_ok(dnssec_ok);
+        self
+    }
+
+    /// Sets the largest UDP payload the requestor can reassemble, at least 512
+    pub fn udp_payload_size(mut self, udp_payload_size: u16) -> Self {
+        self.edns.set_max_payload(udp_payload_size);
+        self
+    }
+
+    /// Adds an option to the OPT RR, after the ones already added
+    pub fn option(mut self, option: EdnsOption) -> Self {
+        self.edns.options_mut().insert(option);
+        self
+    }
+
+    /// Builds the EDNS section, set it on the request with [`Message::set_edns`]
+    ///
+    /// [`Message::set_edns`]: crate::op::Message::set_edns
+    pub fn build(self) -> Edns {
+        self.edns
+    }
+}
+
 // FIXME: this should be a TryFrom
 impl<'a> From<&'a Record> for Edns {
     fn from(value: &'a Record) -> Self {
@@ -290,11 +352,157 @@ impl From<EdnsFlags> for u16 {
     }
 }
 
-#[cfg(all(test, feature = "dnssec-ring"))]
+/// [RFC 8914, Extended DNS Errors](https://tools.ietf.org/html/rfc8914)
+///
+/// ```text
+///                                              1   1   1   1   1   1
+///      0   1   2   3   4   5   6   7   8   9   0   1   2   3   4   5
+///    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+/// 0: |                            OPTION-CODE                        |
+///    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+/// 2: |                           OPTION-LENGTH                       |
+///    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+/// 4: | INFO-CODE                                                     |
+///    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+/// 6: / EXTRA-TEXT ...                                                /
+///    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///
+/// o  INFO-CODE, 16 bits, which is the principal contribution of this
+///    document.  This 16-bit value, encoded in network (MSB) byte order,
+///    provides the additional context for the RESPONSE-CODE of the DNS
+///    message.
+/// o  EXTRA-TEXT, a variable-length, UTF-8-encoded [RFC5198] text field
+///    that may hold additional textual information.  This information is
+///    intended for human consumption (not automated parsing).  EDE text
+///    may be null terminated but MUST NOT be assumed to be; the length
+///    MUST be derived from the OPTION-LENGTH field.
+/// ```
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialOrd, PartialEq, Eq, Clone, Hash)]
+pub struct ExtendedDnsError {
+    info_code: u16,
+    extra_text: String,
+}
+
+impl ExtendedDnsError {
+    /// Construct a new Extended DNS Error with the info code and extra text, which may be empty
+    ///
+    /// Returns an error if the extra text does not fit in the option, it is at most 65533 bytes.
+    pub fn new(info_code: u16, extra_text: impl Into<String>) -> ProtoResult<Self> {
+        let extra_text = extra_text.into();
+        if extra_text.len() > MAX_EXTRA_TEXT_LEN {
+            return Err(
+                ProtoErrorKind::Message("EXTRA-TEXT of extended dns error too long").into(),
+            );
+        }
+
+        Ok(Self {
+            info_code,
+            extra_text,
+        })
+    }
+
+    /// Returns the INFO-CODE, see the [IANA registry](https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#extended-dns-error-codes)
+    pub fn info_code(&self) -> u16 {
+        self.info_code
+    }
+
+    /// Returns the EXTRA-TEXT, this is intended for human consumption only
+    pub fn extra_text(&self) -> &str {
+        &self.extra_text
+    }
+
+    /// Returns the length in bytes of the EdnsOption
+    pub fn len(&self) -> u16 {
+        // INFO-CODE: 2 octets
+        // EXTRA-TEXT: the remainder of the option, an oversized one fails to emit
+        u16::try_from(self.extra_text.len())
+            .ok()
+            .and_then(|len| len.checked_add(2))
+            .unwrap_or(u16::MAX)
+    }
+
+    /// Returns `true` if the length in bytes of the ExtendedDnsError is 0
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+/// The EXTRA-TEXT is the remainder of the option after the INFO-CODE
+const MAX_EXTRA_TEXT_LEN: usize = u16::MAX as usize - 2;
+
+impl BinEncodable for ExtendedDnsError {
+    fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
+        if self.extra_text.len() > MAX_EXTRA_TEXT_LEN {
+            return Err(
+                ProtoErrorKind::Message("EXTRA-TEXT of extended dns error too long").into(),
+            );
+        }
+
+        encoder.emit_u16(self.info_code)?;
+        encoder.emit_vec(self.extra_text.as_bytes())
+    }
+}
+
+impl<'a> TryFrom<&'a ExtendedDnsError> for Vec<u8> {
+    type Error = ProtoError;
+
+    fn try_from(value: &'a ExtendedDnsError) -> Result<Self, Self::Error> {
+        let mut bytes = Self::with_capacity(value.len() as usize);
+        let mut encoder = BinEncoder::new(&mut bytes);
+        value.emit(&mut encoder)?;
+        Ok(bytes)
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for ExtendedDnsError {
+    type Error = ProtoError;
+
+    fn try_from(value: &'a [u8]) -> Result<Self, Self::Error> {
+        let mut decoder = BinDecoder::new(value);
+        let info_code = decoder.read_u16()?.unverified(/*any info code is valid*/);
+
+        // the text is intended for humans, don't reject the whole message for bad UTF-8
+        let text = decoder
+            .read_slice(decoder.len())?
+            .unverified(/*only informational*/);
+        let mut extra_text = String::from_utf8_lossy(text).into_owned();
+        // the replacement characters of bad UTF-8 may grow the text beyond the option
+        if extra_text.len() > MAX_EXTRA_TEXT_LEN {
+            let mut end = MAX_EXTRA_TEXT_LEN;
+            while !extra_text.is_char_boundary(end) {
+                end -= 1;
+            }
+            extra_text.truncate(end);
+        }
+
+        Ok(Self {
+            info_code,
+            extra_text,
+        })
+    }
+}
+
+impl fmt::Display for ExtendedDnsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "extended dns error {}", self.info_code)?;
+        if !self.extra_text.is_empty() {
+            write!(f, ": {}", self.extra_text)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
+    use crate::op::{Message, Query};
+    use crate::serialize::binary::BinDecodable;
 
     #[test]
+    #[cfg(feature = "dnssec-ring")]
     fn test_encode_decode() {
         let mut edns = Edns::new();
 
@@ -323,4 +531,111 @@ mod tests {
         edns.options_mut().remove(EdnsCode::DAU);
         assert!(edns.option(EdnsCode::DAU).is_none());
     }
+
+    #[test]
+    fn test_builder() {
+        let edns = Edns::builder()
+            .dnssec_ok(true)
+            .udp_payload_size(1232)
+            .option(EdnsOption::Unknown(65001, b"first".to_vec()))
+            .option(EdnsOption::Unknown(65002, b"second".to_vec()))
+            .option(EdnsOption::Padding(4))
+            .build();
+
+        let mut message = Message::new();
+        message.add_query(Query::query(Name::root(), RecordType::NS));
+        message.set_edns(edns);
+        let bytes = message.to_vec().unwrap();
+
+        // the OPT RR is the only additional record: root name, type, class and TTL, then RDATA
+        let opt = &bytes[bytes.len() - 11 - 27..];
+        assert_eq!(opt[0], 0);
+        assert_eq!(opt[1..3], u16::from(RecordType::OPT).to_be_bytes());
+        assert_eq!(opt[3..5], 1232u16.to_be_bytes());
+        // extended RCODE, version 0 and the DO bit
+        assert_eq!(opt[5..9], [0, 0, 0x80, 0]);
+        assert_eq!(opt[9..11], 27u16.to_be_bytes());
+        assert_eq!(
+            opt[11..],
+            *b"\xfd\xe9\x00\x05first\xfd\xea\x00\x06second\x00\x0c\x00\x04\0\0\0\0"
+        );
+
+        let decoded = Message::from_vec(&bytes).unwrap();
+        let edns = decoded.extensions().as_ref().unwrap();
+        assert!(edns.flags().dnssec_ok);
+        assert_eq!(edns.version(), 0);
+        assert_eq!(edns.max_payload(), 1232);
+        assert_eq!(
+            edns.options().get_all(EdnsCode::Unknown(65001)),
+            [&EdnsOption::Unknown(65001, b"first".to_vec())]
+        );
+        assert_eq!(
+            edns.options().get(EdnsCode::Unknown(65002)),
+            Some(&EdnsOption::Unknown(65002, b"second".to_vec()))
+        );
+
+        // the payload size is at least 512, the DO bit is clear by default
+        let edns = Edns::builder().udp_payload_size(100).build();
+        assert_eq!(edns.max_payload(), 512);
+        assert!(!edns.flags().dnssec_ok);
+        assert!(edns.options().as_ref().is_empty());
+    }
+
+    #[test]
+    fn test_read_extended_dns_error() {
+        // DNSSEC Bogus, as sent by a validating resolver
+        let bytes = b"\x00\x06signature expired";
+        let ede = ExtendedDnsError::try_from(&bytes[..]).unwrap();
+        assert_eq!(ede.info_code(), 6);
+        assert_eq!(ede.extra_text(), "signature expired");
+        assert_eq!(Vec::<u8>::try_from(&ede).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_read_extended_dns_error_empty_text() {
+        let ede = ExtendedDnsError::try_from(&[0x00, 0x0f][..]).unwrap();
+        assert_eq!(ede, ExtendedDnsError::new(15, "").unwrap());
+        assert_eq!(ede.len(), 2);
+    }
+
+    #[test]
+    fn test_read_extended_dns_error_truncated() {
+        assert!(ExtendedDnsError::try_from(&[0x00][..]).is_err());
+    }
+
+    #[test]
+    fn test_extended_dns_error_too_long() {
+        let ede = ExtendedDnsError::new(0, "x".repeat(65533)).unwrap();
+        assert_eq!(ede.len(), u16::MAX);
+        assert!(ExtendedDnsError::new(0, "x".repeat(65534)).is_err());
+
+        // the replacement characters of bad UTF-8 are truncated to the size of the option
+        let mut bytes = vec![0x00, 0x00];
+        bytes.extend_from_slice(&[0xff; 65533]);
+        let ede = ExtendedDnsError::try_from(&bytes[..]).unwrap();
+        assert!(ede.extra_text().len() <= 65533);
+        assert!(Vec::<u8>::try_from(&ede).is_ok());
+    }
+
+    #[test]
+    fn test_extended_dns_error_in_opt() {
+        let mut edns = Edns::new();
+        edns.options_mut().insert(EdnsOption::ExtendedDnsError(
+            ExtendedDnsError::new(18, "prohibited").unwrap(),
+        ));
+
+        let mut bytes = Vec::new();
+        let mut encoder = BinEncoder::new(&mut bytes);
+        edns.emit(&mut encoder).unwrap();
+
+        let mut decoder = BinDecoder::new(&bytes);
+        let record = Record::<RData>::read(&mut decoder).unwrap();
+        let decoded = Edns::from(&record);
+        assert_eq!(
+            decoded.option(EdnsCode::ExtendedDnsError),
+            Some(&EdnsOption::ExtendedDnsError(
+                ExtendedDnsError::new(18, "prohibited").unwrap()
+            ))
+        );
+    }
 }
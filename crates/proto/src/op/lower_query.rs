@@ -5,7 +5,7 @@
 // https://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::fmt::{self, Display};
+use core::fmt::{self, Display};
 
 use crate::error::*;
 use crate::op::Query;
@@ -7,18 +7,25 @@
 
 //! Basic protocol message for DNS
 
-use std::{fmt, iter, mem, ops::Deref};
+use alloc::vec::Vec;
+use core::{fmt, iter, mem, ops::Deref};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use tracing::{debug, warn};
+#[cfg(feature = "std")]
+use tracing::debug;
+use tracing::warn;
 
+#[cfg(feature = "std")]
+use crate::xfer::DnsResponse;
 use crate::{
     error::*,
     op::{Edns, Header, MessageType, OpCode, Query, ResponseCode},
-    rr::{Record, RecordType},
+    rr::{
+        rdata::opt::{EdnsCode, EdnsOption},
+        Record, RecordType,
+    },
     serialize::binary::{BinDecodable, BinDecoder, BinEncodable, BinEncoder, EncodeMode},
-    xfer::DnsResponse,
 };
 
 /// The basic request and response data structure, used for all DNS protocols.
@@ -603,6 +610,34 @@ impl Message {
         &mut self.edns
     }
 
+    /// Pads the message with the EDNS Padding option, so that its encoded length is a multiple
+    /// of `block_size`
+    ///
+    /// Any existing padding is replaced, and messages without an EDNS section are left untouched.
+    /// [RFC 8467](https://tools.ietf.org/html/rfc8467#section-4.1) recommends a block size of 128
+    /// octets for queries. Padding must only be used over encrypted transports, see
+    /// [RFC 7830](https://tools.ietf.org/html/rfc7830#section-6).
+    pub fn pad_to_block_size(&mut self, block_size: u16) -> ProtoResult<()> {
+        let Some(edns) = self.edns.as_mut() else {
+            return Ok(());
+        };
+        edns.options_mut().remove(EdnsCode::Padding);
+        if block_size == 0 {
+            return Ok(());
+        }
+
+        // the option code and the option length of the padding take 4 octets
+        let len = self.to_vec()?.len() + 4;
+        let block_size = usize::from(block_size);
+        let padding = (block_size - len % block_size) % block_size;
+
+        if let Some(edns) = self.edns.as_mut() {
+            edns.options_mut()
+                .insert(EdnsOption::Padding(padding as u16));
+        }
+        Ok(())
+    }
+
     /// # Return value
     ///
     /// the max payload value as it's defined in the EDNS section.
@@ -782,6 +817,7 @@ impl Message {
     ///
     /// Subsequent to calling this, the Message should not change.
     #[allow(clippy::match_single_binding)]
+    #[cfg(feature = "std")]
     pub fn finalize(
         &mut self,
         finalizer: &dyn MessageFinalizer,
@@ -895,12 +931,14 @@ impl Deref for Message {
 }
 
 /// Alias for a function verifying if a message is properly signed
+#[cfg(feature = "std")]
 pub type MessageVerifier = Box<dyn FnMut(&[u8]) -> ProtoResult<DnsResponse> + Send>;
 
 /// A trait for performing final amendments to a Message before it is sent.
 ///
 /// An example of this is a SIG0 signer, which needs the final form of the message,
 ///  but then needs to attach additional data to the body of the message.
+#[cfg(feature = "std")]
 pub trait MessageFinalizer: Send + Sync + 'static {
     /// The message taken in should be processed and then return [`Record`]s which should be
     ///  appended to the additional section of the message.
@@ -979,17 +1017,38 @@ where
     let place = encoder.place::<Header>()?;
 
     let query_count = queries.emit(encoder)?;
-    // TODO: need to do something on max records
-    //  return offset of last emitted record.
-    let answer_count = count_was_truncated(answers.emit(encoder))?;
-    let nameserver_count = count_was_truncated(name_servers.emit(encoder))?;
-    let mut additional_count = count_was_truncated(additionals.emit(encoder))?;
 
-    if let Some(mut edns) = edns.cloned() {
+    let opt = edns.map(|edns| {
+        let mut edns = edns.clone();
         // need to commit the error code
         edns.set_rcode_high(header.response_code().high());
+        Record::from(&edns)
+    });
+
+    // the OPT record must also be included in truncated responses, RFC 6891, section 7,
+    //  so its space is reserved while emitting the records
+    let max_size = encoder.max_size();
+    if let Some(opt) = &opt {
+        let mut buffer = Vec::new();
+        opt.emit(&mut BinEncoder::new(&mut buffer))?;
+        let reserved = u16::try_from(buffer.len()).unwrap_or(u16::MAX);
+        encoder.set_max_size(max_size.saturating_sub(reserved));
+    }
 
-        let count = count_was_truncated(encoder.emit_all(iter::once(&Record::from(&edns))))?;
+    // TODO: need to do something on max records
+    //  return offset of last emitted record.
+    let counts = (|| {
+        Ok::<_, ProtoError>((
+            count_was_truncated(answers.emit(encoder))?,
+            count_was_truncated(name_servers.emit(encoder))?,
+            count_was_truncated(additionals.emit(encoder))?,
+        ))
+    })();
+    encoder.set_max_size(max_size);
+    let (answer_count, nameserver_count, mut additional_count) = counts?;
+
+    if let Some(opt) = &opt {
+        let count = count_was_truncated(encoder.emit_all(iter::once(opt)))?;
         additional_count.0 += count.0;
         additional_count.1 |= count.1;
     } else if header.response_code().high() > 0 {
@@ -1126,6 +1185,8 @@ impl fmt::Display for Message {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rr::rdata::{A, CNAME, NS, SRV, SVCB};
+    use crate::rr::{Name, RData};
 
     #[test]
     fn test_emit_and_read_header() {
@@ -1198,6 +1259,43 @@ mod tests {
         assert_eq!(got, message);
     }
 
+    #[test]
+    fn test_pad_to_block_size() {
+        for name in [
+            "a.",
+            "www.example.com.",
+            "a.very.long.name.with.many.labels.example.net.",
+        ] {
+            let mut message = Message::new();
+            message.add_query(Query::query(Name::from_ascii(name).unwrap(), RecordType::A));
+            message.set_edns(Edns::new());
+
+            message.pad_to_block_size(128).unwrap();
+            let bytes = message.to_vec().unwrap();
+            assert_eq!(bytes.len() % 128, 0, "{name}");
+
+            // padding again replaces the existing option
+            message.pad_to_block_size(128).unwrap();
+            assert_eq!(message.to_vec().unwrap().len(), bytes.len());
+
+            let read = Message::from_vec(&bytes).unwrap();
+            let padding = read
+                .extensions()
+                .as_ref()
+                .unwrap()
+                .option(EdnsCode::Padding);
+            assert!(matches!(padding, Some(EdnsOption::Padding(_))));
+        }
+
+        // messages without EDNS are not padded
+        let mut message = Message::new();
+        message.add_query(Query::new());
+        let len = message.to_vec().unwrap().len();
+        message.pad_to_block_size(128).unwrap();
+        assert!(message.extensions().is_none());
+        assert_eq!(message.to_vec().unwrap().len(), len);
+    }
+
     #[test]
     fn test_header_counts_correction_after_emit_read() {
         let mut message = Message::new();
@@ -1308,4 +1406,123 @@ mod tests {
 
         Message::from_vec(CRASHING_MESSAGE).expect("failed to parse message");
     }
+
+    #[test]
+    fn test_read_fixture() {
+        // a response for www.example.com. A, with a CNAME to web.example.com. and compressed names
+        let bytes = include_bytes!("../../tests/test-data/www.example.com-cname.bin");
+        let message = Message::from_vec(bytes).unwrap();
+
+        assert_eq!(message.id(), 0x1234);
+        assert_eq!(message.message_type(), MessageType::Response);
+        assert!(message.recursion_desired());
+        assert!(message.recursion_available());
+        assert_eq!(message.queries()[0].name(), &name("www.example.com."));
+
+        assert_eq!(
+            message.answers(),
+            [
+                record(
+                    "www.example.com.",
+                    RData::CNAME(CNAME(name("web.example.com."))),
+                ),
+                record("web.example.com.", RData::A(A::new(192, 0, 2, 1))),
+            ]
+        );
+        assert!(message.name_servers().is_empty());
+        assert_eq!(message.extensions().as_ref().unwrap().max_payload(), 1232);
+    }
+
+    fn record(name: &str, rdata: RData) -> Record {
+        Record::from_rdata(Name::from_ascii(name).unwrap(), 300, rdata)
+    }
+
+    fn name(name: &str) -> Name {
+        Name::from_ascii(name).unwrap()
+    }
+
+    #[test]
+    fn test_compression() {
+        let mut message = Message::new();
+        message
+            .set_message_type(MessageType::Response)
+            .add_query(Query::query(name("www.example.com."), RecordType::A))
+            .add_answer(record(
+                "www.example.com.",
+                RData::CNAME(CNAME(name("web.example.com."))),
+            ))
+            .add_answer(record("web.example.com.", RData::A(A::new(192, 0, 2, 1))))
+            .add_name_server(record(
+                "example.com.",
+                RData::NS(NS(name("ns1.example.com."))),
+            ))
+            .add_additional(record("ns1.example.com.", RData::A(A::new(192, 0, 2, 53))));
+
+        let compressed = message.to_vec().unwrap();
+        let mut uncompressed = Vec::new();
+        let mut encoder = BinEncoder::new(&mut uncompressed);
+        encoder.set_canonical_names(true);
+        message.emit(&mut encoder).unwrap();
+
+        // each name repeating an earlier one is a pointer, saving 15 bytes, and the others share the
+        //   example.com. suffix, saving 11 bytes
+        assert_eq!(uncompressed.len() - compressed.len(), 15 * 3 + 11 * 3);
+
+        for bytes in [compressed, uncompressed] {
+            let read = Message::from_vec(&bytes).unwrap();
+            assert_eq!(read.queries(), message.queries());
+            assert_eq!(read.answers(), message.answers());
+            assert_eq!(read.name_servers(), message.name_servers());
+            assert_eq!(read.additionals(), message.additionals());
+        }
+    }
+
+    #[test]
+    fn test_no_compression_into_rdata() {
+        // the SRV target is not compressed, and later names do not point into it
+        let mut message = Message::new();
+        message
+            .add_query(Query::query(
+                name("_sip._tcp.example.com."),
+                RecordType::SRV,
+            ))
+            .add_answer(record(
+                "_sip._tcp.example.com.",
+                RData::SRV(SRV::new(0, 0, 5060, name("srv.example.com."))),
+            ))
+            .add_additional(record("srv.example.com.", RData::A(A::new(192, 0, 2, 1))));
+
+        let bytes = message.to_vec().unwrap();
+        let target = b"\x03srv\x07example\x03com\x00";
+        assert_eq!(
+            bytes.windows(target.len()).filter(|w| w == target).count(),
+            1
+        );
+        // the owner name of the additional record points to example.com. in the query name
+        assert!(bytes.windows(6).any(|w| w == b"\x03srv\xC0\x16"));
+        assert_eq!(
+            Message::from_vec(&bytes).unwrap().additionals(),
+            message.additionals()
+        );
+
+        // the SVCB target is not compressed
+        let mut message = Message::new();
+        message
+            .add_query(Query::query(name("_dns.resolver.arpa."), RecordType::SVCB))
+            .add_answer(record(
+                "_dns.resolver.arpa.",
+                RData::SVCB(SVCB::new(1, name("resolver.arpa."), vec![])),
+            ));
+
+        let bytes = message.to_vec().unwrap();
+        let target = b"\x08resolver\x04arpa\x00";
+        assert_eq!(
+            bytes.windows(target.len()).filter(|w| w == target).count(),
+            2
+        );
+        assert_eq!(
+            Message::from_vec(&bytes).unwrap().answers(),
+            message.answers()
+        );
+    }
 }
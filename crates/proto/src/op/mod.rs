@@ -17,6 +17,7 @@
 //! Operations to send with a `Client` or server, e.g. `Query`, `Message`, or `UpdateMessage` can
 //! be used together to either query or update resource records sets.
 
+mod dig;
 mod edns;
 pub mod header;
 mod lower_query;
@@ -26,10 +27,12 @@ pub mod query;
 pub mod response_code;
 pub mod update_message;
 
-pub use self::edns::{Edns, EdnsFlags};
+pub use self::edns::{Edns, EdnsBuilder, EdnsFlags, ExtendedDnsError};
 pub use self::header::Header;
 pub use self::header::MessageType;
-pub use self::message::{Message, MessageFinalizer, MessageParts, MessageVerifier};
+pub use self::message::{Message, MessageParts};
+#[cfg(feature = "std")]
+pub use self::message::{MessageFinalizer, MessageVerifier};
 pub use self::op_code::OpCode;
 pub use self::query::Query;
 pub use self::response_code::ResponseCode;
@@ -16,8 +16,10 @@
 
 //! Query struct for looking up resource records
 
-use std::fmt;
-use std::fmt::{Display, Formatter};
+#[cfg(test)]
+use alloc::vec::Vec;
+use core::fmt;
+use core::fmt::{Display, Formatter};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -321,7 +323,7 @@ fn test_read_and_emit() {
 #[test]
 fn test_mdns_unicast_response_bit_handling() {
     const QCLASS_OFFSET: usize = 1 /* empty name */ +
-        std::mem::size_of::<u16>() /* query_type */;
+        core::mem::size_of::<u16>() /* query_type */;
 
     let mut query = Query::new();
     query.set_mdns_unicast_response(true);
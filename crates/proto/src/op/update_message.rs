@@ -7,11 +7,16 @@
 
 //! Update related operations for Messages
 
-use std::fmt::Debug;
+use core::fmt::Debug;
 
+#[cfg(feature = "std")]
 use crate::{
-    op::{Edns, Message, MessageType, OpCode, Query},
-    rr::{rdata::SOA, DNSClass, Name, RData, Record, RecordSet, RecordType},
+    op::{Edns, MessageType, OpCode},
+    rr::{rdata::SOA, DNSClass, Name, RData, RecordSet, RecordType},
+};
+use crate::{
+    op::{Message, Query},
+    rr::Record,
 };
 
 /// To reduce errors in using the Message struct as an Update, this will do the call throughs
@@ -159,6 +164,7 @@ impl UpdateMessage for Message {
 /// * `zone_origin` - the zone name to update, i.e. SOA name
 ///
 /// The update must go to a zone authority (i.e. the server used in the ClientConnection)
+#[cfg(feature = "std")]
 pub fn create(rrset: RecordSet, zone_origin: Name, use_edns: bool) -> Message {
     // TODO: assert non-empty rrset?
     assert!(zone_origin.zone_of(rrset.name()));
@@ -229,6 +235,7 @@ pub fn create(rrset: RecordSet, zone_origin: Name, use_edns: bool) -> Message {
 ///
 /// The update must go to a zone authority (i.e. the server used in the ClientConnection). If
 /// the rrset does not exist and must_exist is false, then the RRSet will be created.
+#[cfg(feature = "std")]
 pub fn append(rrset: RecordSet, zone_origin: Name, must_exist: bool, use_edns: bool) -> Message {
     assert!(zone_origin.zone_of(rrset.name()));
 
@@ -308,6 +315,7 @@ pub fn append(rrset: RecordSet, zone_origin: Name, must_exist: bool, use_edns: b
 /// * `zone_origin` - the zone name to update, i.e. SOA name
 ///
 /// The update must go to a zone authority (i.e. the server used in the ClientConnection).
+#[cfg(feature = "std")]
 pub fn compare_and_swap(
     current: RecordSet,
     new: RecordSet,
@@ -396,6 +404,7 @@ pub fn compare_and_swap(
 ///
 /// The update must go to a zone authority (i.e. the server used in the ClientConnection). If
 /// the rrset does not exist and must_exist is false, then the RRSet will be deleted.
+#[cfg(feature = "std")]
 pub fn delete_by_rdata(mut rrset: RecordSet, zone_origin: Name, use_edns: bool) -> Message {
     assert!(zone_origin.zone_of(rrset.name()));
 
@@ -466,6 +475,7 @@ pub fn delete_by_rdata(mut rrset: RecordSet, zone_origin: Name, use_edns: bool)
 ///
 /// The update must go to a zone authority (i.e. the server used in the ClientConnection). If
 /// the rrset does not exist and must_exist is false, then the RRSet will be deleted.
+#[cfg(feature = "std")]
 pub fn delete_rrset(mut record: Record, zone_origin: Name, use_edns: bool) -> Message {
     assert!(zone_origin.zone_of(record.name()));
 
@@ -528,6 +538,7 @@ pub fn delete_rrset(mut record: Record, zone_origin: Name, use_edns: bool) -> Me
 /// The update must go to a zone authority (i.e. the server used in the ClientConnection). This
 /// operation attempts to delete all resource record sets the specified name regardless of
 /// the record type.
+#[cfg(feature = "std")]
 pub fn delete_all(
     name_of_records: Name,
     zone_origin: Name,
@@ -573,6 +584,172 @@ pub fn delete_all(
     message
 }
 
+/// A builder of UPDATE messages, combining any prerequisites and updates of a zone
+///
+/// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
+///
+/// The prerequisites are placed in the prerequisite (answer) section, the updates in the update
+/// (authority) section, with the classes and TTLs of section 2.4 and 2.5 of the RFC. All the
+/// names must be in the zone, the methods panic otherwise, like the other functions of this
+/// module.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use hickory_proto::op::update_message::UpdateBuilder;
+/// use hickory_proto::rr::{rdata::A, DNSClass, Name, RData, Record, RecordType};
+///
+/// let zone = Name::from_str("example.com.").unwrap();
+/// let www = Name::from_str("www.example.com.").unwrap();
+/// let message = UpdateBuilder::new(zone, DNSClass::IN)
+///     .require_not_exists(www.clone(), RecordType::A)
+///     .add_record(Record::from_rdata(www, 300, RData::A(A::new(192, 0, 2, 1))))
+///     .build();
+/// ```
+#[derive(Clone, Debug)]
+#[cfg(feature = "std")]
+pub struct UpdateBuilder {
+    zone_origin: Name,
+    dns_class: DNSClass,
+    prerequisites: Vec<Record>,
+    updates: Vec<Record>,
+    use_edns: bool,
+    edns: Option<Edns>,
+}
+
+#[cfg(feature = "std")]
+impl UpdateBuilder {
+    /// Creates an update of the zone `zone_origin`, i.e. the name of its SOA, of class `dns_class`
+    pub fn new(zone_origin: Name, dns_class: DNSClass) -> Self {
+        Self {
+            zone_origin,
+            dns_class,
+            prerequisites: Vec::new(),
+            updates: Vec::new(),
+            use_edns: false,
+            edns: None,
+        }
+    }
+
+    /// Adds an EDNS OPT record to the message
+    pub fn use_edns(mut self, use_edns: bool) -> Self {
+        self.use_edns = use_edns;
+        self
+    }
+
+    /// Sets the EDNS OPT record of the message, built with [`Edns::builder`]
+    pub fn edns(mut self, edns: Edns) -> Self {
+        self.edns = Some(edns);
+        self
+    }
+
+    /// Requires an RRset of `name` and `record_type` to exist, whatever its records
+    ///
+    /// 2.4.1 - RRset Exists (Value Independent), the prerequisite is of class ANY
+    pub fn require_exists(mut self, name: Name, record_type: RecordType) -> Self {
+        self.assert_in_zone(&name);
+        self.prerequisites
+            .push(Self::empty_record(name, record_type, DNSClass::ANY));
+        self
+    }
+
+    /// Requires no RRset of `name` and `record_type` to exist
+    ///
+    /// 2.4.3 - RRset Does Not Exist, the prerequisite is of class NONE
+    pub fn require_not_exists(mut self, name: Name, record_type: RecordType) -> Self {
+        self.assert_in_zone(&name);
+        self.prerequisites
+            .push(Self::empty_record(name, record_type, DNSClass::NONE));
+        self
+    }
+
+    /// Adds `record` to its RRset
+    ///
+    /// 2.5.1 - Add To An RRset, the record is of the class of the zone
+    pub fn add_record(mut self, mut record: Record) -> Self {
+        self.assert_in_zone(record.name());
+        record.set_dns_class(self.dns_class);
+        self.updates.push(record);
+        self
+    }
+
+    /// Deletes `record`, matched by its name, type and data, from its RRset
+    ///
+    /// 2.5.4 - Delete An RR From An RRset, the record is of class NONE with a TTL of 0
+    pub fn delete_record(mut self, mut record: Record) -> Self {
+        self.assert_in_zone(record.name());
+        record.set_dns_class(DNSClass::NONE).set_ttl(0);
+        self.updates.push(record);
+        self
+    }
+
+    /// Deletes the RRset of `name` and `record_type`
+    ///
+    /// 2.5.2 - Delete An RRset, the record is of class ANY without data
+    pub fn delete_rrset(mut self, name: Name, record_type: RecordType) -> Self {
+        self.assert_in_zone(&name);
+        self.updates
+            .push(Self::empty_record(name, record_type, DNSClass::ANY));
+        self
+    }
+
+    /// Deletes all the RRsets of `name`
+    ///
+    /// 2.5.3 - Delete All RRsets From A Name, the record is of type and class ANY without data
+    pub fn delete_all(mut self, name: Name) -> Self {
+        self.assert_in_zone(&name);
+        self.updates
+            .push(Self::empty_record(name, RecordType::ANY, DNSClass::ANY));
+        self
+    }
+
+    /// Builds the UPDATE message, with a random id
+    pub fn build(self) -> Message {
+        // for updates, the query section is used for the zone
+        let mut zone = Query::new();
+        zone.set_name(self.zone_origin)
+            .set_query_class(self.dns_class)
+            .set_query_type(RecordType::SOA);
+
+        let mut message = Message::new();
+        message
+            .set_id(rand::random())
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Update)
+            .set_recursion_desired(false);
+        message.add_zone(zone);
+        message.add_pre_requisites(self.prerequisites);
+        message.add_updates(self.updates);
+
+        if let Some(edns) = self.edns {
+            message.set_edns(edns);
+        } else if self.use_edns {
+            message
+                .extensions_mut()
+                .get_or_insert_with(Edns::new)
+                .set_max_payload(MAX_PAYLOAD_LEN)
+                .set_version(0);
+        }
+
+        message
+    }
+
+    fn assert_in_zone(&self, name: &Name) {
+        assert!(
+            self.zone_origin.zone_of(name),
+            "{name} is not in the zone {}",
+            self.zone_origin
+        );
+    }
+
+    /// A record with RDLENGTH 0 and a TTL of 0
+    fn empty_record(name: Name, record_type: RecordType, dns_class: DNSClass) -> Record {
+        let mut record = Record::update0(name, 0, record_type).into_record_of_rdata();
+        record.set_dns_class(dns_class);
+        record
+    }
+}
+
 // not an update per-se, but it fits nicely with other functions here
 /// Download all records from a zone, or all records modified since given SOA was observed.
 /// The request will either be a AXFR Query (ask for full zone transfer) if a SOA was not
@@ -581,6 +758,7 @@ pub fn delete_all(
 /// # Arguments
 /// * `zone_origin` - the zone name to update, i.e. SOA name
 /// * `last_soa` - the last SOA known, if any. If provided, name must match `zone_origin`
+#[cfg(feature = "std")]
 pub fn zone_transfer(zone_origin: Name, last_soa: Option<SOA>) -> Message {
     if let Some(soa) = &last_soa {
         assert_eq!(&zone_origin, soa.mname());
@@ -625,3 +803,120 @@ pub fn zone_transfer(zone_origin: Name, last_soa: Option<SOA>) -> Message {
 // https://dnsflagday.net/2020/
 /// Maximum payload length for EDNS update messages
 pub const MAX_PAYLOAD_LEN: u16 = 1232;
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::rr::rdata::{opt::EdnsOption, A};
+
+    fn name(name: &str) -> Name {
+        Name::from_str(name).unwrap()
+    }
+
+    fn a(owner: &str, ip: Ipv4Addr) -> Record {
+        Record::from_rdata(name(owner), 300, RData::A(A::from(ip)))
+    }
+
+    #[test]
+    fn test_update_builder_wire_layout() {
+        let mut message = UpdateBuilder::new(name("example.com."), DNSClass::IN)
+            .require_exists(name("www.example.com."), RecordType::A)
+            .require_not_exists(name("ftp.example.com."), RecordType::A)
+            .delete_record(a("www.example.com.", Ipv4Addr::new(192, 0, 2, 1)))
+            .add_record(a("www.example.com.", Ipv4Addr::new(192, 0, 2, 2)))
+            .delete_rrset(name("mail.example.com."), RecordType::TXT)
+            .delete_all(name("old.example.com."))
+            .build();
+        message.set_id(0x1234);
+        let bytes = message.to_vec().unwrap();
+
+        #[rustfmt::skip]
+        let expected: &[u8] = &[
+            // id, QR=0 and opcode UPDATE, ZOCOUNT 1, PRCOUNT 2, UPCOUNT 4, ADCOUNT 0
+            0x12, 0x34, 0x28, 0x00, 0, 1, 0, 2, 0, 4, 0, 0,
+            // zone section: example.com. SOA IN
+            7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 3, b'c', b'o', b'm', 0, 0, 6, 0, 1,
+            // prerequisite: www.example.com. A ANY, TTL 0 and RDLENGTH 0
+            3, b'w', b'w', b'w', 0xC0, 12, 0, 1, 0, 255, 0, 0, 0, 0, 0, 0,
+            // prerequisite: ftp.example.com. A NONE, TTL 0 and RDLENGTH 0
+            3, b'f', b't', b'p', 0xC0, 12, 0, 1, 0, 254, 0, 0, 0, 0, 0, 0,
+        ];
+        assert_eq!(&bytes[..expected.len()], expected);
+
+        let message = Message::from_vec(&bytes).unwrap();
+        assert_eq!(message.op_code(), OpCode::Update);
+        assert_eq!(message.zones().len(), 1);
+        assert_eq!(message.zones()[0].query_type(), RecordType::SOA);
+        assert!(message.additionals().is_empty());
+
+        let sections = |records: &[Record]| {
+            records
+                .iter()
+                .map(|r| {
+                    (
+                        r.name().to_string(),
+                        r.record_type(),
+                        r.dns_class(),
+                        r.ttl(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let owned = |name: &str, rtype, class, ttl| (name.to_owned(), rtype, class, ttl);
+        assert_eq!(
+            sections(message.prerequisites()),
+            [
+                owned("www.example.com.", RecordType::A, DNSClass::ANY, 0),
+                owned("ftp.example.com.", RecordType::A, DNSClass::NONE, 0),
+            ]
+        );
+        assert_eq!(
+            sections(message.updates()),
+            [
+                owned("www.example.com.", RecordType::A, DNSClass::NONE, 0),
+                owned("www.example.com.", RecordType::A, DNSClass::IN, 300),
+                owned("mail.example.com.", RecordType::TXT, DNSClass::ANY, 0),
+                owned("old.example.com.", RecordType::ANY, DNSClass::ANY, 0),
+            ]
+        );
+
+        // the deleted and added records carry their data, the RRset deletions none
+        let updates = message.updates();
+        assert_eq!(updates[0].data(), &RData::A(A::new(192, 0, 2, 1)));
+        assert_eq!(updates[1].data(), &RData::A(A::new(192, 0, 2, 2)));
+        assert!(matches!(updates[2].data(), RData::Update0(_)));
+        assert!(matches!(updates[3].data(), RData::Update0(_)));
+    }
+
+    #[test]
+    fn test_update_builder_edns() {
+        let message = UpdateBuilder::new(name("example.com."), DNSClass::IN).build();
+        assert!(message.extensions().is_none());
+
+        let message = UpdateBuilder::new(name("example.com."), DNSClass::IN)
+            .use_edns(true)
+            .build();
+        let edns = message.extensions().as_ref().unwrap();
+        assert_eq!(edns.max_payload(), MAX_PAYLOAD_LEN);
+
+        let edns = Edns::builder()
+            .udp_payload_size(1232)
+            .option(EdnsOption::Unknown(65001, vec![1]))
+            .build();
+        let message = UpdateBuilder::new(name("example.com."), DNSClass::IN)
+            .use_edns(true)
+            .edns(edns.clone())
+            .build();
+        assert_eq!(message.extensions().as_ref(), Some(&edns));
+    }
+
+    #[test]
+    #[should_panic(expected = "www.example.org. is not in the zone example.com.")]
+    fn test_update_builder_outside_zone() {
+        UpdateBuilder::new(name("example.com."), DNSClass::IN)
+            .add_record(a("www.example.org.", Ipv4Addr::new(192, 0, 2, 1)));
+    }
+}
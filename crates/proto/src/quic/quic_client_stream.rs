@@ -15,14 +15,20 @@ use std::{
     task::{Context, Poll},
 };
 
-use futures_util::{future::FutureExt, stream::Stream};
+use futures_util::{
+    future::{FutureExt, Shared},
+    stream::{FuturesUnordered, Stream},
+};
 use quinn::{
     crypto::rustls::QuicClientConfig, ClientConfig, Connection, Endpoint, TransportConfig, VarInt,
+    ZeroRttAccepted,
 };
 use tokio::time::timeout;
+use tracing::debug;
 
 use crate::{
     error::ProtoError,
+    op::OpCode,
     quic::quic_stream::{DoqErrorCode, QuicStream},
     rustls::client_config,
     udp::UdpSocket,
@@ -36,6 +42,8 @@ use super::{quic_config, quic_stream};
 #[derive(Clone)]
 pub struct QuicClientStream {
     quic_connection: Connection,
+    /// Resolves once the handshake completes, if the connection was opened with 0-RTT
+    zero_rtt_accepted: Option<Shared<ZeroRttAccepted>>,
     name_server_name: Arc<str>,
     name_server: SocketAddr,
     is_shutdown: bool,
@@ -57,7 +65,63 @@ impl QuicClientStream {
         QuicClientStreamBuilder::default()
     }
 
+    /// Sends all `requests` concurrently over this connection, each on its own QUIC stream
+    ///
+    /// The resulting stream yields every request together with its response in the order that the
+    ///  responses arrive, which may differ from the order of `requests`. As DoQ requires the DNS
+    ///  Message ID to be zero, responses are correlated to their request by the QUIC stream they
+    ///  were received on rather than by the Message ID.
+    pub fn send_concurrent(
+        &self,
+        requests: impl IntoIterator<Item = DnsRequest>,
+    ) -> impl Stream<Item = (DnsRequest, Result<DnsResponse, ProtoError>)> + Send + 'static {
+        if self.is_shutdown {
+            panic!("can not send messages after stream is shutdown")
+        }
+
+        requests
+            .into_iter()
+            .map(|request| {
+                let connection = self.quic_connection.clone();
+                let zero_rtt_accepted = self.zero_rtt_accepted.clone();
+                async move {
+                    let response =
+                        Self::inner_send(connection, zero_rtt_accepted, request.clone()).await;
+                    (request, response)
+                }
+            })
+            .collect::<FuturesUnordered<_>>()
+    }
+
     async fn inner_send(
+        connection: Connection,
+        zero_rtt_accepted: Option<Shared<ZeroRttAccepted>>,
+        message: DnsRequest,
+    ) -> Result<DnsResponse, ProtoError> {
+        let zero_rtt_accepted = match zero_rtt_accepted {
+            Some(accepted) if accepted.clone().now_or_never().is_none() => accepted,
+            // the handshake is complete, nothing will be sent as early data
+            _ => return Self::send_on_new_stream(connection, message).await,
+        };
+
+        // 0-RTT data may be replayed by an attacker, only idempotent queries are allowed to be
+        //  sent before the handshake is complete.
+        if message.op_code() != OpCode::Query {
+            zero_rtt_accepted.await;
+            return Self::send_on_new_stream(connection, message).await;
+        }
+
+        match Self::send_on_new_stream(connection.clone(), message.clone()).await {
+            // the server did not accept the early data, so it never saw the query
+            Err(_) if !zero_rtt_accepted.await => {
+                debug!("0-RTT data rejected by the server, resending query");
+                Self::send_on_new_stream(connection, message).await
+            }
+            result => result,
+        }
+    }
+
+    async fn send_on_new_stream(
         connection: Connection,
         message: DnsRequest,
     ) -> Result<DnsResponse, ProtoError> {
@@ -67,7 +131,12 @@ impl QuicClientStream {
         //  QUIC stream for each query. The server then uses the same stream to provide all the response messages for that query.
         let mut stream = QuicStream::new(send_stream, recv_stream);
 
-        stream.send(message.into_parts().0).await?;
+        let (mut message, options) = message.into_parts();
+        if let Some(block_size) = options.edns_padding {
+            message.pad_to_block_size(block_size)?;
+        }
+
+        stream.send(message).await?;
 
         // The client MUST send the DNS query over the selected stream,
         // and MUST indicate through the STREAM FIN mechanism that no further data will be sent on that stream.
@@ -122,7 +191,12 @@ impl DnsRequestSender for QuicClientStream {
             panic!("can not send messages after stream is shutdown")
         }
 
-        Box::pin(Self::inner_send(self.quic_connection.clone(), request)).into()
+        Box::pin(Self::inner_send(
+            self.quic_connection.clone(),
+            self.zero_rtt_accepted.clone(),
+            request,
+        ))
+        .into()
     }
 
     fn shutdown(&mut self) {
@@ -154,6 +228,7 @@ pub struct QuicClientStreamBuilder {
     crypto_config: Option<rustls::ClientConfig>,
     transport_config: Arc<TransportConfig>,
     bind_addr: Option<SocketAddr>,
+    early_data: bool,
 }
 
 impl QuicClientStreamBuilder {
@@ -169,6 +244,20 @@ impl QuicClientStreamBuilder {
         self
     }
 
+    /// Sends queries as QUIC 0-RTT early data when reconnecting to a previously used server
+    ///
+    /// This saves a round trip for the first queries on a resumed connection. Early data is not
+    ///  protected against replay, so only idempotent requests, i.e. those with `OpCode::Query`, are
+    ///  sent before the handshake is complete, all other requests wait for the handshake. If the
+    ///  server rejects the early data, the affected queries are resent once the handshake is done.
+    ///
+    /// Resumption requires the session of the previous connection, the builder should be cloned
+    ///  from the one used for that connection, or share its `crypto_config`.
+    pub fn enable_early_data(&mut self, enable: bool) -> &mut Self {
+        self.early_data = enable;
+        self
+    }
+
     /// Creates a new QuicStream to the specified name_server
     ///
     /// # Arguments
@@ -230,13 +319,17 @@ impl QuicClientStreamBuilder {
         dns_name: String,
     ) -> Result<QuicClientStream, ProtoError> {
         // ensure the ALPN protocol is set correctly
-        let crypto_config = if let Some(crypto_config) = self.crypto_config {
+        let mut crypto_config = if let Some(crypto_config) = self.crypto_config {
             crypto_config
         } else {
             client_config().map_err(|err| ProtoError::from(err.to_string()))?
         };
 
-        let quic_connection = connect_quic(
+        if self.early_data {
+            crypto_config.enable_early_data = true;
+        }
+
+        let (quic_connection, zero_rtt_accepted) = connect_quic(
             name_server,
             &dns_name,
             quic_stream::DOQ_ALPN,
@@ -248,6 +341,7 @@ impl QuicClientStreamBuilder {
 
         Ok(QuicClientStream {
             quic_connection,
+            zero_rtt_accepted: zero_rtt_accepted.map(FutureExt::shared),
             name_server_name: Arc::from(dns_name),
             name_server,
             is_shutdown: false,
@@ -255,6 +349,7 @@ impl QuicClientStreamBuilder {
     }
 }
 
+/// Connects to a QUIC server, returns the acceptance of the early data as well if 0-RTT was used
 pub(crate) async fn connect_quic(
     addr: SocketAddr,
     server_name: &str,
@@ -262,7 +357,7 @@ pub(crate) async fn connect_quic(
     mut crypto_config: rustls::ClientConfig,
     transport_config: Arc<TransportConfig>,
     mut endpoint: Endpoint,
-) -> Result<Connection, ProtoError> {
+) -> Result<(Connection, Option<ZeroRttAccepted>), ProtoError> {
     if crypto_config.alpn_protocols.is_empty() {
         crypto_config.alpn_protocols = vec![protocol.to_vec()];
     }
@@ -278,11 +373,11 @@ pub(crate) async fn connect_quic(
 
     Ok(if early_data_enabled {
         match connecting.into_0rtt() {
-            Ok((new_connection, _)) => new_connection,
-            Err(connecting) => connect_with_timeout(connecting).await?,
+            Ok((new_connection, zero_rtt_accepted)) => (new_connection, Some(zero_rtt_accepted)),
+            Err(connecting) => (connect_with_timeout(connecting).await?, None),
         }
     } else {
-        connect_with_timeout(connecting).await?
+        (connect_with_timeout(connecting).await?, None)
     })
 }
 
@@ -307,6 +402,7 @@ impl Default for QuicClientStreamBuilder {
             crypto_config: None,
             transport_config: Arc::new(transport_config),
             bind_addr: None,
+            early_data: false,
         }
     }
 }
@@ -17,7 +17,7 @@ use crate::{error::ProtoError, udp::UdpSocket};
 
 use super::{
     quic_config,
-    quic_stream::{self, QuicStream},
+    quic_stream::{self, DoqErrorCode, QuicStream},
 };
 
 /// A DNS-over-QUIC Server, see QuicClientStream for the client counterpart
@@ -110,4 +110,9 @@ impl QuicStreams {
             Err(e) => Some(Err(e.into())),
         }
     }
+
+    /// Close the connection with the error code, e.g. `DOQ_PROTOCOL_ERROR` after a malformed query
+    pub fn close(&self, code: DoqErrorCode) {
+        self.connection.close(code.into(), b"");
+    }
 }
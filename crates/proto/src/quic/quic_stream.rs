@@ -6,12 +6,13 @@
 // copied, modified, or distributed except according to those terms.
 
 use bytes::{Bytes, BytesMut};
-use quinn::{RecvStream, SendStream, VarInt};
+use quinn::{ReadExactError, ReadToEndError, RecvStream, SendStream, StoppedError, VarInt};
 use tracing::debug;
 
 use crate::{
     error::{ProtoError, ProtoErrorKind},
-    op::Message,
+    op::{Header, Message},
+    serialize::binary::{BinDecodable, BinDecoder},
     xfer::DnsResponse,
 };
 
@@ -23,6 +24,9 @@ use crate::{
 /// ```
 pub(crate) const DOQ_ALPN: &[u8] = b"doq";
 
+/// The maximum size of a DNS message, as limited by the 2-octet length field
+const MAX_MESSAGE_SIZE: usize = u16::MAX as usize;
+
 /// [DoQ Error Codes](https://www.ietf.org/archive/id/draft-ietf-dprive-dnsoquic-10.html#name-doq-error-codes), draft-ietf-dprive-dnsoquic, Feb. 28, 2022
 /// ```text
 ///  5.3. DoQ Error Codes
@@ -47,7 +51,7 @@ pub(crate) const DOQ_ALPN: &[u8] = b"doq";
 /// DOQ_ERROR_RESERVED (0xd098ea5e):
 ///     Alternative error code used for tests.
 /// ```
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum DoqErrorCode {
     /// No error. This is used when the connection or stream needs to be closed, but there is no error to signal.
     NoError,
@@ -140,8 +144,7 @@ impl QuicStream {
         // field defined for DNS over TCP [RFC1035]. The practical result of this is that the content of each QUIC stream is exactly
         // the same as the content of a TCP connection that would manage exactly one query.All DNS messages (queries and responses)
         // sent over DoQ connections MUST be encoded as a 2-octet length field followed by the message content as specified in [RFC1035].
-        let bytes_len = u16::try_from(bytes.len())
-            .map_err(|_e| ProtoErrorKind::MaxBufferSizeExceeded(bytes.len()))?;
+        let bytes_len = check_message_len(bytes.len())?;
         let len = bytes_len.to_be_bytes().to_vec();
         let len = Bytes::from(len);
 
@@ -156,6 +159,20 @@ impl QuicStream {
         Ok(())
     }
 
+    /// Waits until the peer received all the data of the finished stream, or stopped the stream
+    ///
+    /// Data not yet received is discarded when the connection is closed, so closing the connection
+    /// should wait for the responses sent.
+    pub async fn stopped(&mut self) -> Result<(), ProtoError> {
+        match self.send_stream.stopped().await {
+            Ok(_) => Ok(()),
+            Err(StoppedError::ConnectionLost(e)) => Err(ProtoErrorKind::from(e).into()),
+            Err(StoppedError::ZeroRttRejected) => {
+                Err(ProtoErrorKind::QuinnUnknownStreamError.into())
+            }
+        }
+    }
+
     /// Receive a single packet
     pub async fn receive(&mut self) -> Result<DnsResponse, ProtoError> {
         let bytes = self.receive_bytes().await?;
@@ -172,13 +189,59 @@ impl QuicStream {
         DnsResponse::from_buffer(bytes.to_vec())
     }
 
+    /// Receive the query of a stream opened by the client, on the server side
+    ///
+    /// The stream must carry a single query with a Message ID of 0, and be finished by the client
+    /// after it. Otherwise the stream is reset with `DOQ_PROTOCOL_ERROR`, which should be treated
+    /// as a connection error, see [`QuicStreams::close`](super::QuicStreams::close).
+    pub async fn receive_query(&mut self) -> Result<BytesMut, ProtoError> {
+        let bytes = self.receive_bytes().await?;
+
+        let header = Header::read(&mut BinDecoder::new(&bytes));
+        let error = match header {
+            Ok(header) if header.id() == 0 => None,
+            Ok(header) => Some(ProtoErrorKind::QuicMessageIdNot0(header.id()).into()),
+            Err(e) => Some(e),
+        };
+        if let Some(error) = error {
+            self.reset(DoqErrorCode::ProtocolError)
+                .map_err(|_| debug!("stream already closed"))
+                .ok();
+            return Err(error);
+        }
+
+        // RFC: The client MUST send the DNS query over the selected stream, and MUST indicate through the STREAM FIN mechanism
+        //  that no further data will be sent on that stream.
+        match self.receive_stream.read_to_end(0).await {
+            Ok(_) => Ok(bytes),
+            Err(ReadToEndError::TooLong) => {
+                self.reset(DoqErrorCode::ProtocolError)
+                    .map_err(|_| debug!("stream already closed"))
+                    .ok();
+                Err(ProtoErrorKind::Message("DoQ stream with data after the query").into())
+            }
+            Err(ReadToEndError::Read(e)) => Err(ReadExactError::ReadError(e).into()),
+        }
+    }
+
     // TODO: we should change the protocol handlers to work with Messages since some require things like 0 for the Message ID.
     /// Receive a single packet as raw bytes
     pub async fn receive_bytes(&mut self) -> Result<BytesMut, ProtoError> {
         // following above, the data should be first the length, followed by the message(s)
         let mut len = [0u8; 2];
-        self.receive_stream.read_exact(&mut len).await?;
+        self.receive_stream
+            .read_exact(&mut len)
+            .await
+            .map_err(map_read_error)?;
         let len = u16::from_be_bytes(len) as usize;
+        check_message_len(len)?;
+
+        if len == 0 {
+            self.reset(DoqErrorCode::ProtocolError)
+                .map_err(|_| debug!("stream already closed"))
+                .ok();
+            return Err(ProtoErrorKind::Message("DoQ message with a length of 0").into());
+        }
 
         // RFC: DoQ Queries and Responses are sent on QUIC streams, which in theory can carry up to 2^62 bytes.
         //  However, DNS messages are restricted in practice to a maximum size of 65535 bytes. This maximum size
@@ -192,7 +255,7 @@ impl QuicStream {
             self.reset(DoqErrorCode::ProtocolError)
                 .map_err(|_| debug!("stream already closed"))
                 .ok();
-            return Err(e.into());
+            return Err(map_read_error(e));
         }
 
         debug!("received packet len: {} bytes: {:x?}", len, bytes);
@@ -213,3 +276,39 @@ impl QuicStream {
             .map_err(|_| ProtoError::from(ProtoErrorKind::QuinnUnknownStreamError))
     }
 }
+
+/// Verifies that a message of `len` bytes can be framed with the 2-octet length field of DoQ
+fn check_message_len(len: usize) -> Result<u16, ProtoError> {
+    if len > MAX_MESSAGE_SIZE {
+        return Err(ProtoErrorKind::MaxBufferSizeExceeded(len).into());
+    }
+
+    Ok(len as u16)
+}
+
+/// Maps the peer finishing the stream before the full message was read to a descriptive error
+fn map_read_error(error: ReadExactError) -> ProtoError {
+    match error {
+        ReadExactError::FinishedEarly(_) => {
+            ProtoErrorKind::Message("DoQ stream closed before full response").into()
+        }
+        error => error.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_message_len() {
+        assert_eq!(check_message_len(0).unwrap(), 0);
+        assert_eq!(check_message_len(65535).unwrap(), u16::MAX);
+
+        let error = check_message_len(65536).expect_err("65536 should exceed the maximum");
+        assert!(matches!(
+            error.kind(),
+            ProtoErrorKind::MaxBufferSizeExceeded(65536)
+        ));
+    }
+}
@@ -7,9 +7,10 @@
 
 #![allow(clippy::print_stdout)] // this is a test module
 
-use std::{env, net::SocketAddr, path::Path, str::FromStr, sync::Arc};
+use std::{env, net::SocketAddr, path::Path, str::FromStr, sync::Arc, time::Duration};
 
 use futures_util::StreamExt;
+use quinn::{crypto::rustls::QuicServerConfig, Connection, Endpoint, ServerConfig};
 use rustls::{
     pki_types::{
         pem::{self, PemObject},
@@ -19,13 +20,19 @@ use rustls::{
 };
 
 use crate::{
-    op::{Message, Query},
+    error::{ProtoError, ProtoErrorKind},
+    op::{Message, MessageType, Query},
     quic::QuicClientStreamBuilder,
-    rr::{Name, RecordType},
-    xfer::DnsRequestSender,
+    rr::{rdata::A, Name, RData, Record, RecordType},
+    xfer::{DnsRequest, DnsRequestSender},
 };
 
-use super::quic_server::QuicServer;
+use super::{
+    quic_client_stream::connect_quic,
+    quic_config,
+    quic_server::QuicServer,
+    quic_stream::{DoqErrorCode, QuicStream, DOQ_ALPN},
+};
 
 async fn server_responder(mut server: QuicServer) {
     while let Some((mut conn, addr)) = server
@@ -121,6 +128,534 @@ async fn test_quic_stream() {
     server_join.abort();
 }
 
+#[tokio::test]
+async fn test_quic_stream_send_length_prefix() {
+    let (client, server) = connection_pair().await;
+
+    let (send_stream, recv_stream) = client.open_bi().await.expect("failed to open stream");
+    let mut stream = QuicStream::new(send_stream, recv_stream);
+
+    let mut message = Message::default();
+    message.set_id(1234);
+    message.add_query(Query::query(
+        Name::from_str("www.example.test.").unwrap(),
+        RecordType::AAAA,
+    ));
+
+    stream.send(message.clone()).await.expect("failed to send");
+    stream.finish().await.expect("failed to finish");
+
+    let (_, mut server_recv) = server.accept_bi().await.expect("failed to accept stream");
+    let received = server_recv
+        .read_to_end(u16::MAX as usize + 2)
+        .await
+        .expect("failed to read");
+
+    // the message id is always zeroed on DoQ
+    message.set_id(0);
+    let expected = message.to_vec().unwrap();
+
+    assert_eq!(received.len(), expected.len() + 2);
+    assert_eq!(received[..2], (expected.len() as u16).to_be_bytes());
+    assert_eq!(received[2..], expected[..]);
+}
+
+#[tokio::test]
+async fn test_quic_stream_receive() {
+    let (client, server) = connection_pair().await;
+
+    let (send_stream, recv_stream) = client.open_bi().await.expect("failed to open stream");
+    let mut stream = QuicStream::new(send_stream, recv_stream);
+
+    // the server only sees the stream once something has been sent on it
+    let mut query = Message::default();
+    query.add_query(Query::query(
+        Name::from_str("www.example.test.").unwrap(),
+        RecordType::A,
+    ));
+    stream.send(query.clone()).await.expect("failed to send");
+    stream.finish().await.expect("failed to finish");
+
+    let mut response = query;
+    response.set_id(0);
+    response.set_message_type(MessageType::Response);
+    response.add_answer(Record::from_rdata(
+        Name::from_str("www.example.test.").unwrap(),
+        86400,
+        RData::A(A::new(93, 184, 215, 14)),
+    ));
+    let bytes = response.to_vec().unwrap();
+
+    let (mut server_send, _) = server.accept_bi().await.expect("failed to accept stream");
+    server_send
+        .write_all(&(bytes.len() as u16).to_be_bytes())
+        .await
+        .unwrap();
+    server_send.write_all(&bytes).await.unwrap();
+    server_send.finish().unwrap();
+
+    let received = stream.receive().await.expect("failed to receive");
+    assert_eq!(*received, Message::from_vec(&bytes).unwrap());
+    assert_eq!(received.answers(), response.answers());
+}
+
+#[tokio::test]
+async fn test_quic_connection_closed_with_error_code() {
+    let (client, server) = connection_pair().await;
+
+    let (send_stream, recv_stream) = client.open_bi().await.expect("failed to open stream");
+    let mut stream = QuicStream::new(send_stream, recv_stream);
+    stream.send(test_query()).await.expect("failed to send");
+    stream.finish().await.expect("failed to finish");
+
+    server.accept_bi().await.expect("failed to accept stream");
+    server.close(DoqErrorCode::ExcessiveLoad.into(), b"excessive load");
+
+    let error = stream.receive().await.expect_err("receive should fail");
+    assert!(matches!(
+        error.kind(),
+        ProtoErrorKind::QuicAborted(DoqErrorCode::ExcessiveLoad)
+    ));
+    assert_eq!(error.doq_error_code(), Some(DoqErrorCode::ExcessiveLoad));
+}
+
+#[tokio::test]
+async fn test_quic_stream_reset_with_error_code() {
+    let (client, server) = connection_pair().await;
+
+    let (send_stream, recv_stream) = client.open_bi().await.expect("failed to open stream");
+    let mut stream = QuicStream::new(send_stream, recv_stream);
+    stream.send(test_query()).await.expect("failed to send");
+    stream.finish().await.expect("failed to finish");
+
+    let (send_stream, recv_stream) = server.accept_bi().await.expect("failed to accept stream");
+    QuicStream::new(send_stream, recv_stream)
+        .reset(DoqErrorCode::ExcessiveLoad)
+        .expect("failed to reset");
+
+    let error = stream.receive().await.expect_err("receive should fail");
+    assert_eq!(error.doq_error_code(), Some(DoqErrorCode::ExcessiveLoad));
+}
+
+#[tokio::test]
+async fn test_quic_stream_finished_after_length() {
+    let (client, server) = connection_pair().await;
+
+    let (send_stream, recv_stream) = client.open_bi().await.expect("failed to open stream");
+    let mut stream = QuicStream::new(send_stream, recv_stream);
+    stream.send(test_query()).await.expect("failed to send");
+    stream.finish().await.expect("failed to finish");
+
+    // announce a message, but finish the stream before sending it
+    let (mut server_send, _) = server.accept_bi().await.expect("failed to accept stream");
+    server_send.write_all(&512u16.to_be_bytes()).await.unwrap();
+    server_send.finish().unwrap();
+
+    let error = tokio::time::timeout(Duration::from_secs(5), stream.receive())
+        .await
+        .expect("receive timed out")
+        .expect_err("receive should fail");
+    assert!(matches!(
+        error.kind(),
+        ProtoErrorKind::Message("DoQ stream closed before full response")
+    ));
+}
+
+#[tokio::test]
+async fn test_quic_stream_zero_length() {
+    let (client, server) = connection_pair().await;
+
+    let (send_stream, recv_stream) = client.open_bi().await.expect("failed to open stream");
+    let mut stream = QuicStream::new(send_stream, recv_stream);
+    stream.send(test_query()).await.expect("failed to send");
+    stream.finish().await.expect("failed to finish");
+
+    let (mut server_send, _) = server.accept_bi().await.expect("failed to accept stream");
+    server_send.write_all(&0u16.to_be_bytes()).await.unwrap();
+
+    let error = tokio::time::timeout(Duration::from_secs(5), stream.receive())
+        .await
+        .expect("receive timed out")
+        .expect_err("receive should fail");
+    assert!(matches!(
+        error.kind(),
+        ProtoErrorKind::Message("DoQ message with a length of 0")
+    ));
+}
+
+#[tokio::test]
+async fn test_quic_receive_query() {
+    let (client, server) = connection_pair().await;
+
+    let (send_stream, recv_stream) = client.open_bi().await.expect("failed to open stream");
+    let mut stream = QuicStream::new(send_stream, recv_stream);
+    stream.send(test_query()).await.expect("failed to send");
+    stream.finish().await.expect("failed to finish");
+
+    let (send_stream, recv_stream) = server.accept_bi().await.expect("failed to accept stream");
+    let query = QuicStream::new(send_stream, recv_stream)
+        .receive_query()
+        .await
+        .expect("failed to receive query");
+    assert_eq!(
+        Message::from_vec(&query).unwrap().queries(),
+        test_query().queries()
+    );
+}
+
+#[tokio::test]
+async fn test_quic_receive_query_message_id_not_0() {
+    let (client, server) = connection_pair().await;
+
+    // the message id is only zeroed by `send`
+    let (send_stream, recv_stream) = client.open_bi().await.expect("failed to open stream");
+    let mut stream = QuicStream::new(send_stream, recv_stream);
+    let mut query = test_query();
+    query.set_id(1234);
+    stream
+        .send_bytes(query.to_vec().unwrap().into())
+        .await
+        .expect("failed to send");
+    stream.finish().await.expect("failed to finish");
+
+    let (send_stream, recv_stream) = server.accept_bi().await.expect("failed to accept stream");
+    let error = QuicStream::new(send_stream, recv_stream)
+        .receive_query()
+        .await
+        .expect_err("receive should fail");
+    assert!(matches!(
+        error.kind(),
+        ProtoErrorKind::QuicMessageIdNot0(1234)
+    ));
+
+    let error = stream.receive().await.expect_err("receive should fail");
+    assert_eq!(error.doq_error_code(), Some(DoqErrorCode::ProtocolError));
+}
+
+#[tokio::test]
+async fn test_quic_receive_query_single_message() {
+    let (client, server) = connection_pair().await;
+
+    // two queries on the same stream
+    let (send_stream, recv_stream) = client.open_bi().await.expect("failed to open stream");
+    let mut stream = QuicStream::new(send_stream, recv_stream);
+    stream.send(test_query()).await.expect("failed to send");
+    stream.send(test_query()).await.expect("failed to send");
+    stream.finish().await.expect("failed to finish");
+
+    let (send_stream, recv_stream) = server.accept_bi().await.expect("failed to accept stream");
+    let error = QuicStream::new(send_stream, recv_stream)
+        .receive_query()
+        .await
+        .expect_err("receive should fail");
+    assert!(matches!(
+        error.kind(),
+        ProtoErrorKind::Message("DoQ stream with data after the query")
+    ));
+
+    let error = stream.receive().await.expect_err("receive should fail");
+    assert_eq!(error.doq_error_code(), Some(DoqErrorCode::ProtocolError));
+}
+
+#[tokio::test]
+async fn test_quic_streams_close_with_protocol_error() {
+    let (cert_chain, key) = server_cert();
+    let mut server = QuicServer::new(SocketAddr::from(([127, 0, 0, 1], 0)), cert_chain, key)
+        .await
+        .expect("failed to initialize QuicServer");
+    let server_addr = server.local_addr().expect("no address");
+
+    let server_join = tokio::spawn(async move {
+        let (mut conn, _) = server
+            .next()
+            .await
+            .expect("failed to get next quic session")
+            .expect("server closed");
+        let mut stream = conn
+            .next()
+            .await
+            .expect("connection closed")
+            .expect("new client stream failed");
+        stream
+            .receive_query()
+            .await
+            .expect_err("receive should fail");
+        conn.close(DoqErrorCode::ProtocolError);
+
+        // keep the endpoint open until the client is done
+        conn.next().await;
+    });
+
+    let client_endpoint = Endpoint::client(SocketAddr::from(([127, 0, 0, 1], 0)))
+        .expect("failed to bind client endpoint");
+    let (client, _) = connect_quic(
+        server_addr,
+        "ns.example.com",
+        DOQ_ALPN,
+        client_tls_config(),
+        Arc::new(quic_config::transport()),
+        client_endpoint,
+    )
+    .await
+    .expect("failed to connect");
+
+    let (send_stream, recv_stream) = client.open_bi().await.expect("failed to open stream");
+    let mut stream = QuicStream::new(send_stream, recv_stream);
+    stream
+        .send_bytes(vec![0xff; 4].into())
+        .await
+        .expect("failed to send");
+    stream.finish().await.expect("failed to finish");
+
+    // the connection is closed with the error code, not only the stream
+    let error = client.closed().await;
+    assert_eq!(
+        ProtoError::from(error).doq_error_code(),
+        Some(DoqErrorCode::ProtocolError)
+    );
+
+    server_join.abort();
+}
+
+fn test_query() -> Message {
+    let mut message = Message::default();
+    message.add_query(Query::query(
+        Name::from_str("www.example.test.").unwrap(),
+        RecordType::A,
+    ));
+    message
+}
+
+#[tokio::test]
+async fn test_quic_send_concurrent() {
+    let (cert_chain, key) = server_cert();
+    let mut server = QuicServer::new(SocketAddr::from(([127, 0, 0, 1], 0)), cert_chain, key)
+        .await
+        .expect("failed to initialize QuicServer");
+    let server_addr = server.local_addr().expect("no address");
+
+    // answer the queries in the reverse order of their arrival, each one after the client received
+    // the previous answer
+    let (received_tx, mut received_rx) = tokio::sync::mpsc::unbounded_channel::<()>();
+    let server_join = tokio::spawn(async move {
+        let (mut conn, _) = server
+            .next()
+            .await
+            .expect("failed to get next quic session")
+            .expect("server closed");
+
+        let mut streams = Vec::new();
+        for _ in 0..3 {
+            let mut stream = conn
+                .next()
+                .await
+                .expect("connection closed")
+                .expect("new client stream failed");
+            let message = stream.receive().await.expect("failed to receive");
+            streams.push((stream, message));
+        }
+
+        for (mut stream, message) in streams.into_iter().rev() {
+            stream
+                .send(message.into_message())
+                .await
+                .expect("failed to send response");
+            stream.finish().await.expect("failed to finish");
+            received_rx.recv().await.expect("client is gone");
+        }
+
+        // keep the connection open until the client is done
+        conn.next().await;
+    });
+
+    let mut builder = QuicClientStreamBuilder::default();
+    builder.crypto_config(client_tls_config());
+    let client_stream = builder
+        .build(server_addr, "ns.example.com".to_string())
+        .await
+        .expect("failed to connect");
+
+    let names = ["a.example.test.", "b.example.test.", "c.example.test."];
+    let requests = names.iter().map(|name| {
+        let mut message = Message::default();
+        message.add_query(Query::query(Name::from_str(name).unwrap(), RecordType::A));
+        DnsRequest::from(message)
+    });
+
+    let mut responses = client_stream.send_concurrent(requests);
+    for name in names.iter().rev() {
+        let (request, response) = responses.next().await.expect("missing response");
+        let response = response.expect("failed to read response");
+        assert_eq!(request.queries()[0].name(), &Name::from_str(name).unwrap());
+        assert_eq!(response.queries(), request.queries());
+        received_tx.send(()).expect("server is gone");
+    }
+    assert!(responses.next().await.is_none());
+
+    server_join.abort();
+}
+
+#[tokio::test]
+async fn test_quic_early_data() {
+    let (cert_chain, key) = server_cert();
+
+    let mut tls_config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_protocol_versions(&[&rustls::version::TLS13])
+    .unwrap()
+    .with_no_client_auth()
+    .with_single_cert(cert_chain, key)
+    .unwrap();
+    tls_config.alpn_protocols = vec![DOQ_ALPN.to_vec()];
+    tls_config.max_early_data_size = u32::MAX;
+
+    let mut server_config =
+        ServerConfig::with_crypto(Arc::new(QuicServerConfig::try_from(tls_config).unwrap()));
+    server_config.transport = Arc::new(quic_config::transport());
+
+    let server_endpoint = Endpoint::server(server_config, SocketAddr::from(([127, 0, 0, 1], 0)))
+        .expect("failed to bind server endpoint");
+    let server_addr = server_endpoint.local_addr().unwrap();
+
+    // reports for every query if it was received before the handshake was complete
+    let (early_tx, mut early_rx) = futures_channel::mpsc::unbounded();
+    let server_join = tokio::spawn(async move {
+        let mut connections = Vec::new();
+        while let Some(incoming) = server_endpoint.accept().await {
+            let (connection, _) = incoming
+                .accept()
+                .expect("failed to accept connection")
+                .into_0rtt()
+                .unwrap_or_else(|_| unreachable!("0.5-RTT always succeeds on the server"));
+
+            let (send_stream, recv_stream) = connection
+                .accept_bi()
+                .await
+                .expect("failed to accept stream");
+            early_tx.unbounded_send(recv_stream.is_0rtt()).unwrap();
+
+            let mut stream = QuicStream::new(send_stream, recv_stream);
+            let message = stream.receive().await.expect("failed to receive");
+            stream
+                .send(message.into_message())
+                .await
+                .expect("failed to send response");
+            stream.finish().await.expect("failed to finish");
+
+            connections.push(connection);
+        }
+    });
+
+    let mut builder = QuicClientStreamBuilder::default();
+    builder
+        .crypto_config(client_tls_config())
+        .enable_early_data(true);
+
+    // the first connection has no session to resume, it receives the resumption ticket
+    let mut client_stream = builder
+        .clone()
+        .build(server_addr, "ns.example.com".to_string())
+        .await
+        .expect("failed to connect");
+    client_stream
+        .send_message(test_query().into())
+        .next()
+        .await
+        .expect("no response received")
+        .expect("failed to read response");
+    assert!(!early_rx.next().await.unwrap());
+    client_stream.shutdown();
+
+    let mut client_stream = builder
+        .build(server_addr, "ns.example.com".to_string())
+        .await
+        .expect("failed to connect");
+    let response = client_stream
+        .send_message(test_query().into())
+        .next()
+        .await
+        .expect("no response received")
+        .expect("failed to read response");
+    assert_eq!(response.queries(), test_query().queries());
+    assert!(early_rx.next().await.unwrap());
+
+    server_join.abort();
+}
+
+/// Establishes a DoQ connection over the loopback interface, returns the (client, server) sides
+async fn connection_pair() -> (Connection, Connection) {
+    let (cert_chain, key) = server_cert();
+
+    let mut tls_config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_protocol_versions(&[&rustls::version::TLS13])
+    .unwrap()
+    .with_no_client_auth()
+    .with_single_cert(cert_chain, key)
+    .unwrap();
+    tls_config.alpn_protocols = vec![DOQ_ALPN.to_vec()];
+
+    let mut server_config =
+        ServerConfig::with_crypto(Arc::new(QuicServerConfig::try_from(tls_config).unwrap()));
+    server_config.transport = Arc::new(quic_config::transport());
+
+    let server_endpoint = Endpoint::server(server_config, SocketAddr::from(([127, 0, 0, 1], 0)))
+        .expect("failed to bind server endpoint");
+    let server_addr = server_endpoint.local_addr().unwrap();
+
+    let client_endpoint = Endpoint::client(SocketAddr::from(([127, 0, 0, 1], 0)))
+        .expect("failed to bind client endpoint");
+
+    let accept = async {
+        server_endpoint
+            .accept()
+            .await
+            .expect("server endpoint closed")
+            .await
+            .expect("failed to accept connection")
+    };
+    let connect = connect_quic(
+        server_addr,
+        "ns.example.com",
+        DOQ_ALPN,
+        client_tls_config(),
+        Arc::new(quic_config::transport()),
+        client_endpoint,
+    );
+
+    let (server, client) = tokio::join!(accept, connect);
+    (client.expect("failed to connect").0, server)
+}
+
+/// Loads the certificate chain and key of the test server, `ns.example.com`
+fn server_cert() -> (Vec<CertificateDer<'static>>, PrivateKeyDer<'static>) {
+    let server_path = env::var("TDNS_WORKSPACE_ROOT").unwrap_or_else(|_| "../..".to_owned());
+
+    let cert_chain = read_certs(format!("{server_path}/tests/test-data/cert.pem")).unwrap();
+    let key =
+        PrivateKeyDer::from_pem_file(format!("{server_path}/tests/test-data/cert.key")).unwrap();
+
+    (cert_chain, key)
+}
+
+/// A client TLS configuration which trusts the test CA
+fn client_tls_config() -> ClientConfig {
+    let server_path = env::var("TDNS_WORKSPACE_ROOT").unwrap_or_else(|_| "../..".to_owned());
+    let ca = read_certs(format!("{server_path}/tests/test-data/ca.pem")).unwrap();
+
+    let mut roots = rustls::RootCertStore::empty();
+    let (_, ignored) = roots.add_parsable_certificates(ca);
+    assert_eq!(ignored, 0);
+
+    ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth()
+}
+
 fn read_certs(cert_path: impl AsRef<Path>) -> Result<Vec<CertificateDer<'static>>, pem::Error> {
     CertificateDer::pem_file_iter(cert_path)?.collect::<Result<Vec<_>, _>>()
 }
@@ -7,17 +7,21 @@
 
 //! domain name, aka labels, implementation
 
-use std::char;
-use std::cmp::{Ordering, PartialEq};
-use std::fmt::{self, Write};
-use std::hash::{Hash, Hasher};
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
-use std::str::FromStr;
+use alloc::{string::String, vec::Vec};
+use core::char;
+use core::cmp::{Ordering, PartialEq};
+use core::fmt::{self, Write};
+use core::hash::{Hash, Hasher};
+use core::str::FromStr;
 
 use crate::error::*;
+use crate::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use crate::rr::domain::label::{CaseInsensitive, CaseSensitive, IntoLabel, Label, LabelCmp};
+#[cfg(feature = "std")]
 use crate::rr::domain::usage::LOCALHOST as LOCALHOST_usage;
+use crate::serialize::binary::wire::WireName;
 use crate::serialize::binary::*;
+#[cfg(feature = "std")]
 use ipnet::{IpNet, Ipv4Net, Ipv6Net};
 #[cfg(feature = "serde")]
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
@@ -57,6 +61,7 @@ impl Name {
     }
 
     /// Randomize the case of ASCII alpha characters in a name
+    #[cfg(feature = "std")]
     pub fn randomize_label_case(&mut self) {
         use rand::distributions::{Distribution, Standard};
         let mut rand = rand::thread_rng();
@@ -669,25 +674,27 @@ impl Name {
         let last_index = encoder.offset();
         // now search for other labels already stored matching from the beginning label, strip then to the end
         //   if it's not found, then store this as a new label
-        for label_idx in &labels_written {
-            match encoder.get_label_pointer(*label_idx, last_index) {
-                // if writing canonical and already found, continue
-                Some(_) if canonical => continue,
-                Some(loc) if !canonical => {
-                    // reset back to the beginning of this label, and then write the pointer...
-                    encoder.set_offset(*label_idx);
-                    encoder.trim();
-
-                    // write out the pointer marker
-                    //  or'd with the location which shouldn't be larger than this 2^14 or 16k
-                    encoder.emit_u16(0xC000u16 | (loc & 0x3FFFu16))?;
-
-                    // we found a pointer don't write more, break
-                    return Ok(());
-                }
-                _ => {
-                    // no existing label exists, store this new one.
-                    encoder.store_label_pointer(*label_idx, last_index);
+        // canonical names are neither compressed nor stored, so that no pointer ever references the
+        //   RDATA of a type which disallows compression, see RFC 3597 section 4
+        if !canonical {
+            for label_idx in &labels_written {
+                match encoder.get_label_pointer(*label_idx, last_index) {
+                    Some(loc) => {
+                        // reset back to the beginning of this label, and then write the pointer...
+                        encoder.set_offset(*label_idx);
+                        encoder.trim();
+
+                        // write out the pointer marker
+                        //  or'd with the location which shouldn't be larger than this 2^14 or 16k
+                        encoder.emit_u16(0xC000u16 | (loc & 0x3FFFu16))?;
+
+                        // we found a pointer don't write more, break
+                        return Ok(());
+                    }
+                    None => {
+                        // no existing label exists, store this new one.
+                        encoder.store_label_pointer(*label_idx, last_index);
+                    }
                 }
             }
         }
@@ -756,6 +763,16 @@ impl Name {
         self.label_ends.len().cmp(&other.label_ends.len())
     }
 
+    /// Canonical DNS name order, [RFC 4034, section 6.1](https://tools.ietf.org/html/rfc4034#section-6.1)
+    ///
+    /// The labels are compared from the most significant, rightmost, one, as lowercased octet
+    /// strings in which the absence of an octet sorts before a zero octet. Unlike [`Ord`], this
+    /// does not consider whether the names are fully qualified, names in canonical form always
+    /// are.
+    pub fn cmp_canonical(&self, other: &Self) -> Ordering {
+        self.cmp_labels::<CaseInsensitive>(other)
+    }
+
     /// Case sensitive comparison
     pub fn cmp_case(&self, other: &Self) -> Ordering {
         self.cmp_with_f::<CaseSensitive>(other)
@@ -849,6 +866,7 @@ impl Name {
     }
 
     /// Converts a *.arpa Name in a PTR record back into an IpNet if possible.
+    #[cfg(feature = "std")]
     pub fn parse_arpa_name(&self) -> Result<IpNet, ProtoError> {
         if !self.is_fqdn() {
             return Err("PQDN cannot be valid arpa name".into());
@@ -857,19 +875,19 @@ impl Name {
         let first = iter
             .next()
             .ok_or_else(|| ProtoError::from("not an arpa address"))?;
-        if !"arpa".eq_ignore_ascii_case(std::str::from_utf8(first)?) {
+        if !"arpa".eq_ignore_ascii_case(core::str::from_utf8(first)?) {
             return Err("not an arpa address".into());
         }
         let second = iter
             .next()
             .ok_or_else(|| ProtoError::from("invalid arpa address"))?;
         let mut prefix_len: u8 = 0;
-        match &std::str::from_utf8(second)?.to_ascii_lowercase()[..] {
+        match &core::str::from_utf8(second)?.to_ascii_lowercase()[..] {
             "in-addr" => {
                 let mut octets: [u8; 4] = [0; 4];
                 for octet in octets.iter_mut() {
                     match iter.next() {
-                        Some(label) => *octet = std::str::from_utf8(label)?.parse()?,
+                        Some(label) => *octet = core::str::from_utf8(label)?.parse()?,
                         None => break,
                     }
                     prefix_len += 8;
@@ -888,7 +906,7 @@ impl Name {
                         Some(label) => {
                             if label.len() == 1 {
                                 prefix_len += 4;
-                                let hex = u8::from_str_radix(std::str::from_utf8(label)?, 16)?;
+                                let hex = u8::from_str_radix(core::str::from_utf8(label)?, 16)?;
                                 address |= u128::from(hex) << (128 - prefix_len);
                             } else {
                                 return Err("invalid label length for ip6.arpa".into());
@@ -943,6 +961,7 @@ impl Name {
     /// let name = Name::from_str("my.localhost.").unwrap();
     /// assert!(name.is_localhost());
     /// ```
+    #[cfg(feature = "std")]
     pub fn is_localhost(&self) -> bool {
         LOCALHOST_usage.zone_of(self)
     }
@@ -1005,8 +1024,8 @@ impl Name {
     }
 }
 
-impl std::fmt::Debug for Name {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Debug for Name {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.write_str("Name(\"")?;
         self.write_labels::<_, LabelEncUtf8>(f)?;
         f.write_str("\")")
@@ -1222,143 +1241,24 @@ impl BinEncodable for Name {
 }
 
 impl<'r> BinDecodable<'r> for Name {
-    /// parses the chain of labels
+    /// parses the chain of labels with [`WireName::read`], which borrows them from the message
+    ///  where possible, they are copied once into the name.
     ///  this has a max of 255 octets, with each label being less than 63.
-    ///  all names will be stored lowercase internally.
-    /// This will consume the portions of the `Vec` which it is reading...
     fn read(decoder: &mut BinDecoder<'r>) -> ProtoResult<Self> {
-        let mut name = Self::default();
-        read_inner(decoder, &mut name, None)?;
-        Ok(name)
+        Ok(Self::from(&WireName::read(decoder)?))
     }
 }
 
-fn read_inner(
-    decoder: &mut BinDecoder<'_>,
-    name: &mut Name,
-    max_idx: Option<usize>,
-) -> Result<(), DecodeError> {
-    let mut state: LabelParseState = LabelParseState::LabelLengthOrPointer;
-    let name_start = decoder.index();
-
-    // assume all chars are utf-8. We're doing byte-by-byte operations, no endianness issues...
-    // reserved: (1000 0000 aka 0800) && (0100 0000 aka 0400)
-    // pointer: (slice == 1100 0000 aka C0) & C0 == true, then 03FF & slice = offset
-    // label: 03FF & slice = length; slice.next(length) = label
-    // root: 0000
-    loop {
-        // this protects against overlapping labels
-        if let Some(max_idx) = max_idx {
-            if decoder.index() >= max_idx {
-                return Err(DecodeError::LabelOverlapsWithOther {
-                    label: name_start,
-                    other: max_idx,
-                });
-            }
+impl From<&WireName<'_>> for Name {
+    fn from(name: &WireName<'_>) -> Self {
+        // the length of the name and of its labels was verified by WireName::read
+        let mut this = Self::root();
+        for label in name.labels() {
+            this.label_data.extend_from_slice(label);
+            this.label_ends.push(this.label_data.len() as u8);
         }
-
-        state = match state {
-            LabelParseState::LabelLengthOrPointer => {
-                // determine what the next label is
-                match decoder
-                    .peek()
-                    .map(Restrict::unverified /*verified in this usage*/)
-                {
-                    Some(0) => {
-                        // RFC 1035 Section 3.1 - Name space definitions
-                        //
-                        // Domain names in messages are expressed in terms of a sequence of labels.
-                        // Each label is represented as a one octet length field followed by that
-                        // number of octets.  **Since every domain name ends with the null label of
-                        // the root, a domain name is terminated by a length byte of zero.**  The
-                        // high order two bits of every length octet must be zero, and the
-                        // remaining six bits of the length field limit the label to 63 octets or
-                        // less.
-                        name.set_fqdn(true);
-                        LabelParseState::Root
-                    }
-                    None => {
-                        // Valid names on the wire should end in a 0-octet, signifying the end of
-                        // the name. If the last byte wasn't 00, the name is invalid.
-                        return Err(DecodeError::InsufficientBytes);
-                    }
-                    Some(byte) if byte & 0b1100_0000 == 0b1100_0000 => LabelParseState::Pointer,
-                    Some(byte) if byte & 0b1100_0000 == 0b0000_0000 => LabelParseState::Label,
-                    Some(byte) => return Err(DecodeError::UnrecognizedLabelCode(byte)),
-                }
-            }
-            // labels must have a maximum length of 63
-            LabelParseState::Label => {
-                let label = decoder
-                    .read_character_data()?
-                    .verify_unwrap(|l| l.len() <= 63)
-                    .map_err(|l| DecodeError::LabelBytesTooLong(l.len()))?;
-
-                name.extend_name(label)
-                    .map_err(|_| DecodeError::DomainNameTooLong(label.len()))?;
-
-                // reset to collect more data
-                LabelParseState::LabelLengthOrPointer
-            }
-            //         4.1.4. Message compression
-            //
-            // In order to reduce the size of messages, the domain system utilizes a
-            // compression scheme which eliminates the repetition of domain names in a
-            // message.  In this scheme, an entire domain name or a list of labels at
-            // the end of a domain name is replaced with a pointer to a prior occurrence
-            // of the same name.
-            //
-            // The pointer takes the form of a two octet sequence:
-            //
-            //     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
-            //     | 1  1|                OFFSET                   |
-            //     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
-            //
-            // The first two bits are ones.  This allows a pointer to be distinguished
-            // from a label, since the label must begin with two zero bits because
-            // labels are restricted to 63 octets or less.  (The 10 and 01 combinations
-            // are reserved for future use.)  The OFFSET field specifies an offset from
-            // the start of the message (i.e., the first octet of the ID field in the
-            // domain header).  A zero offset specifies the first byte of the ID field,
-            // etc.
-            LabelParseState::Pointer => {
-                let pointer_location = decoder.index();
-                let location = decoder
-                    .read_u16()?
-                    .map(|u| {
-                        // get rid of the two high order bits, they are markers for length or pointers
-                        u & 0x3FFF
-                    })
-                    .verify_unwrap(|ptr| {
-                        // all labels must appear "prior" to this Name
-                        (*ptr as usize) < name_start
-                    })
-                    .map_err(|e| DecodeError::PointerNotPriorToLabel {
-                        idx: pointer_location,
-                        ptr: e,
-                    })?;
-
-                let mut pointer = decoder.clone(location);
-                read_inner(&mut pointer, name, Some(name_start))?;
-
-                // Pointers always finish the name, break like Root.
-                break;
-            }
-            LabelParseState::Root => {
-                // need to pop() the 0 off the stack...
-                decoder.pop()?;
-                break;
-            }
-        }
-    }
-
-    // TODO: should we consider checking this while the name is parsed?
-    let len = name.len();
-    if len >= 255 {
-        return Err(DecodeError::DomainNameTooLong(len));
+        this
     }
-
-    Ok(())
 }
 
 impl fmt::Display for Name {
@@ -1414,14 +1314,6 @@ impl Ord for Name {
     }
 }
 
-/// This is the list of states for the label parsing state machine
-enum LabelParseState {
-    LabelLengthOrPointer, // basically the start of the FSM
-    Label,                // storing length of the label, must be < 63
-    Pointer,              // location of pointer in slice,
-    Root,                 // root is the end of the labels list for an FQDN
-}
-
 impl FromStr for Name {
     type Err = ProtoError;
 
@@ -1511,10 +1403,11 @@ impl<'de> Deserialize<'de> for Name {
 mod tests {
     #![allow(clippy::dbg_macro, clippy::print_stdout)]
 
-    use std::cmp::Ordering;
+    use alloc::string::ToString;
+    use core::cmp::Ordering;
+    use core::iter;
+    use core::str::FromStr;
     use std::collections::hash_map::DefaultHasher;
-    use std::iter;
-    use std::str::FromStr;
 
     use super::*;
 
@@ -1664,6 +1557,58 @@ mod tests {
         assert!(Name::read(&mut d).is_err());
     }
 
+    #[test]
+    fn test_pointer_limit() {
+        // a pointer to itself
+        let bytes = [0x01, b'a', 0xC0, 0x02];
+        let mut d = BinDecoder::new(&bytes);
+        d.read_slice(2).unwrap();
+        assert!(matches!(
+            Name::read(&mut d).unwrap_err().kind(),
+            ProtoErrorKind::PointerNotPriorToLabel { idx: 2, ptr: 2 }
+        ));
+
+        // a chain of pointers to the root, one longer than allowed
+        let mut bytes = vec![0];
+        for i in 0..=MAX_COMPRESSION_POINTERS {
+            let previous = if i == 0 { 0 } else { 2 * i - 1 };
+            bytes.extend_from_slice(&(0xC000 | previous as u16).to_be_bytes());
+        }
+        let mut d = BinDecoder::new(&bytes).clone(bytes.len() as u16 - 2);
+        assert!(matches!(
+            Name::read(&mut d).unwrap_err().kind(),
+            ProtoErrorKind::TooManyCompressionPointers { idx: 1 }
+        ));
+
+        // the chain without the last pointer
+        let mut d = BinDecoder::new(&bytes).clone(bytes.len() as u16 - 4);
+        assert!(Name::read(&mut d).unwrap().is_root());
+    }
+
+    #[test]
+    fn test_deeply_compressed_message() {
+        use crate::op::Message;
+        use crate::rr::{rdata::A, RData, Record};
+
+        // each name is compressed to a label and a pointer to the previous one
+        let mut message = Message::new();
+        let mut name = Name::root();
+        for labels in 1..=100 {
+            name = Name::from_labels(vec!["a"; labels]).unwrap();
+            message.add_answer(Record::from_rdata(
+                name.clone(),
+                300,
+                RData::A(A::new(192, 0, 2, 1)),
+            ));
+        }
+
+        let bytes = message.to_vec().unwrap();
+        let message = Message::from_vec(&bytes).unwrap();
+        let last = message.answers().last().unwrap().name();
+        assert_eq!(last, &name);
+        assert_eq!(last.num_labels(), 100);
+    }
+
     #[test]
     fn test_bin_overlap_enforced() {
         let mut bytes: Vec<u8> = Vec::with_capacity(512);
@@ -1747,6 +1692,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cmp_canonical() {
+        // the example of RFC 4034, section 6.1
+        let canonical = vec![
+            Name::from_ascii("example").unwrap(),
+            Name::from_ascii("a.example").unwrap(),
+            Name::from_ascii("yljkjljk.a.example").unwrap(),
+            Name::from_ascii("Z.a.example").unwrap(),
+            Name::from_ascii("zABC.a.EXAMPLE").unwrap(),
+            Name::from_ascii("z.example").unwrap(),
+            Name::from_labels(vec![&[1u8] as &[u8], b"z", b"example"]).unwrap(),
+            Name::from_ascii("*.z.example").unwrap(),
+            Name::from_labels(vec![&[200u8] as &[u8], b"z", b"example"]).unwrap(),
+        ];
+
+        let mut names = canonical.clone();
+        names.reverse();
+        names.swap(2, 6);
+        names.sort_by(Name::cmp_canonical);
+        assert_eq!(names, canonical);
+
+        assert_eq!(
+            Name::from_ascii("Z.A.Example.")
+                .unwrap()
+                .cmp_canonical(&Name::from_ascii("z.a.example").unwrap()),
+            Ordering::Equal
+        );
+    }
+
     #[test]
     fn test_partial_cmp() {
         let comparisons: Vec<(Name, Name)> = vec![
@@ -1956,6 +1930,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn test_parse_arpa_name() {
         assert!(Name::from_ascii("168.192.in-addr.arpa")
             .unwrap()
@@ -2089,6 +2064,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn test_label_randomization() {
         let mut name = Name::root();
         name.randomize_label_case();
@@ -2187,6 +2163,7 @@ mod tests {
         assert!(max_zscore < 0.33);
     }
 
+    #[cfg(feature = "std")]
     fn std_deviation(mean: f64, data: &[f64]) -> f64 {
         match (mean, data.len()) {
             (data_mean, count) if count > 0 => {
@@ -2241,7 +2218,7 @@ mod tests {
 
     #[test]
     fn test_name_partialord_constraints() {
-        use std::cmp::Ordering::*;
+        use core::cmp::Ordering::*;
 
         let example_fqdn = Name::from_utf8("example.com.").unwrap();
         let foo_example_fqdn = Name::from_utf8("foo.example.com.").unwrap();
@@ -2307,7 +2284,7 @@ mod tests {
 
     #[test]
     fn test_name_ord_constraints() {
-        use std::cmp;
+        use core::cmp;
 
         let example_fqdn = Name::from_utf8("example.com.").unwrap();
         let foo_example_fqdn = Name::from_utf8("foo.example.com.").unwrap();
@@ -20,7 +20,7 @@ mod rr_set;
 pub mod serial_number;
 pub mod type_bit_map;
 
-use std::fmt::{Debug, Display};
+use core::fmt::{Debug, Display};
 
 use crate::{
     error::ProtoResult,
@@ -31,7 +31,7 @@ pub use self::dns_class::DNSClass;
 pub use self::domain::{IntoName, Name};
 pub use self::record_data::RData;
 pub use self::record_type::RecordType;
-pub use self::resource::Record;
+pub use self::resource::{rrset_canonical_sort, Record};
 #[allow(deprecated)]
 pub use self::rr_set::IntoRecordSet;
 pub use self::rr_set::RecordSet;
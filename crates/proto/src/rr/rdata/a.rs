@@ -31,8 +31,10 @@
 //! "10.2.0.52" or "192.0.5.6").
 //! ```
 
-pub use std::net::Ipv4Addr;
-use std::{fmt, net::AddrParseError, ops::Deref, str};
+pub use crate::net::Ipv4Addr;
+use core::{fmt, ops::Deref, str};
+
+use crate::net::AddrParseError;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -104,6 +106,18 @@ impl<'r> BinDecodable<'r> for A {
     }
 }
 
+impl BinEncodable for Ipv4Addr {
+    fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
+        A(*self).emit(encoder)
+    }
+}
+
+impl<'r> BinDecodable<'r> for Ipv4Addr {
+    fn read(decoder: &mut BinDecoder<'r>) -> ProtoResult<Self> {
+        A::read(decoder).map(Self::from)
+    }
+}
+
 /// Read the RData from the given Decoder
 #[deprecated(note = "use the BinDecodable::read method instead")]
 pub fn read(decoder: &mut BinDecoder<'_>) -> ProtoResult<A> {
@@ -151,6 +165,8 @@ impl str::FromStr for A {
 
 #[cfg(test)]
 mod mytests {
+    use alloc::vec::Vec;
+
     use super::*;
     use crate::serialize::binary::bin_tests::{test_emit_data_set, test_read_data_set};
 
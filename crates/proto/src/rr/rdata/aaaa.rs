@@ -23,8 +23,10 @@
 //!   resource record in network byte order (high-order byte first).
 //! ```
 
-pub use std::net::Ipv6Addr;
-use std::{fmt, net::AddrParseError, ops::Deref, str};
+pub use crate::net::Ipv6Addr;
+use core::{fmt, ops::Deref, str};
+
+use crate::net::AddrParseError;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -105,6 +107,18 @@ impl<'r> BinDecodable<'r> for AAAA {
     }
 }
 
+impl BinEncodable for Ipv6Addr {
+    fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
+        AAAA(*self).emit(encoder)
+    }
+}
+
+impl<'r> BinDecodable<'r> for Ipv6Addr {
+    fn read(decoder: &mut BinDecoder<'r>) -> ProtoResult<Self> {
+        AAAA::read(decoder).map(Self::from)
+    }
+}
+
 /// Read the RData from the given Decoder
 #[allow(clippy::many_single_char_names)]
 #[deprecated(note = "use the BinDecodable::read method instead")]
@@ -153,7 +167,8 @@ impl str::FromStr for AAAA {
 
 #[cfg(test)]
 mod tests {
-    use std::str::FromStr;
+    use alloc::vec::Vec;
+    use core::str::FromStr;
 
     use super::*;
     use crate::serialize::binary::bin_tests::{test_emit_data_set, test_read_data_set};
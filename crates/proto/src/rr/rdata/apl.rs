@@ -0,0 +1,395 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! APL records for lists of address prefixes
+
+use alloc::vec::Vec;
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use data_encoding::HEXLOWER;
+
+use crate::{
+    error::{ProtoError, ProtoResult},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    rr::{RData, RecordData, RecordDataDecodable, RecordType},
+    serialize::binary::{BinDecoder, BinEncodable, BinEncoder, Restrict},
+};
+
+/// The IANA address family number of IPv4
+pub const FAMILY_IPV4: u16 = 1;
+
+/// The IANA address family number of IPv6
+pub const FAMILY_IPV6: u16 = 2;
+
+/// [RFC 3123](https://tools.ietf.org/html/rfc3123#section-4)
+///
+/// ```text
+/// 4. APL RDATA format
+///
+///    The RDATA section consists of zero or more items (<apitem>) of the
+///    form
+///
+///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       |                          ADDRESSFAMILY                |
+///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       |             PREFIX    | N|         AFDLENGTH  |
+///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       /                            AFDPART                    /
+///       |                                                       |
+///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///
+///       ADDRESSFAMILY     16 bit unsigned value as assigned by IANA
+///                         (see IANA Considerations)
+///       PREFIX            8 bit unsigned binary coded prefix length.
+///                         Upper and lower bounds and interpretation of
+///                         this value are address family specific.
+///       N                 negation flag, indicates the presence of the
+///                         "!" character in the textual format.  It has
+///                         the value "1" if the "!" was given, "0" else.
+///       AFDLENGTH         length in octets of the following address
+///                         family dependent part (7 bit unsigned).
+///       AFDPART           address family dependent part.  See below.
+/// ```
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct APL {
+    items: Vec<AplItem>,
+}
+
+impl APL {
+    /// Creates a new APL record data.
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - the address prefixes of the list, in order.
+    pub fn new(items: Vec<AplItem>) -> Self {
+        Self { items }
+    }
+
+    /// The address prefixes of the list, in order
+    pub fn items(&self) -> &[AplItem] {
+        &self.items
+    }
+}
+
+/// An address prefix of an [`APL`]
+///
+/// The prefixes of the IPv4 and IPv6 address families are validated, the address family dependent
+/// part of the other families is kept as is.
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct AplItem {
+    negation: bool,
+    family: u16,
+    prefix: u8,
+    afd_part: Vec<u8>,
+}
+
+impl AplItem {
+    /// Creates a new address prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `negation` - true if the prefix is excluded from the list, `!` in the textual format.
+    /// * `address` - the address of the prefix, which determines its address family.
+    /// * `prefix` - the prefix length, at most 32 for IPv4 and 128 for IPv6.
+    pub fn new(negation: bool, address: IpAddr, prefix: u8) -> ProtoResult<Self> {
+        let (family, mut afd_part) = match address {
+            IpAddr::V4(address) => (FAMILY_IPV4, address.octets().to_vec()),
+            IpAddr::V6(address) => (FAMILY_IPV6, address.octets().to_vec()),
+        };
+
+        while afd_part.last() == Some(&0) {
+            afd_part.pop();
+        }
+        Self::from_afd_part(negation, family, prefix, afd_part)
+    }
+
+    /// Creates a new address prefix from its address family dependent part, as in the wire format.
+    ///
+    /// # Arguments
+    ///
+    /// * `negation` - true if the prefix is excluded from the list, `!` in the textual format.
+    /// * `family` - the IANA address family number.
+    /// * `prefix` - the prefix length, at most 32 for IPv4 and 128 for IPv6.
+    /// * `afd_part` - the address family dependent part, at most 127 octets. For IPv4 and IPv6,
+    ///   this is the address without its trailing zero octets.
+    pub fn from_afd_part(
+        negation: bool,
+        family: u16,
+        prefix: u8,
+        afd_part: Vec<u8>,
+    ) -> ProtoResult<Self> {
+        let (address_length, max_prefix) = match family {
+            FAMILY_IPV4 => (4, 32),
+            FAMILY_IPV6 => (16, 128),
+            _ => (0x7f, u8::MAX),
+        };
+        let is_ip = matches!(family, FAMILY_IPV4 | FAMILY_IPV6);
+        if afd_part.len() > address_length || (is_ip && afd_part.last() == Some(&0)) {
+            return Err(ProtoError::from(format!(
+                "invalid APL address of {} octets for family {family}",
+                afd_part.len()
+            )));
+        }
+        if prefix > max_prefix {
+            return Err(ProtoError::from(format!(
+                "invalid APL prefix length for family {family}: {prefix}"
+            )));
+        }
+
+        Ok(Self {
+            negation,
+            family,
+            prefix,
+            afd_part,
+        })
+    }
+
+    /// True if the prefix is excluded from the list
+    pub fn negation(&self) -> bool {
+        self.negation
+    }
+
+    /// The address of the prefix, `None` for the address families other than IPv4 and IPv6
+    pub fn address(&self) -> Option<IpAddr> {
+        let mut octets = [0; 16];
+        match self.family {
+            FAMILY_IPV4 => {
+                octets[..self.afd_part.len()].copy_from_slice(&self.afd_part);
+                Some(IpAddr::from(Ipv4Addr::new(
+                    octets[0], octets[1], octets[2], octets[3],
+                )))
+            }
+            FAMILY_IPV6 => {
+                octets[..self.afd_part.len()].copy_from_slice(&self.afd_part);
+                Some(IpAddr::from(Ipv6Addr::from(octets)))
+            }
+            _ => None,
+        }
+    }
+
+    /// The prefix length
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    /// The IANA address family number, e.g. [`FAMILY_IPV4`] and [`FAMILY_IPV6`]
+    pub fn family(&self) -> u16 {
+        self.family
+    }
+
+    /// The address family dependent part, the address without its trailing zero octets
+    ///
+    /// ```text
+    ///    Trailing zero octets do not bear any information (e.g., there is no
+    ///    semantic difference between 10.0.0.0/16 and 10/16) in an address
+    ///    prefix, so the shortest possible AFDLENGTH can be used to encode it.
+    ///    However, for DNSSEC [RFC 2535] a single wire encoding must be used by
+    ///    all.  Therefore the sender MUST NOT include trailing zero octets in
+    ///    the AFDPART regardless of the value of PREFIX.
+    /// ```
+    pub fn afd_part(&self) -> &[u8] {
+        &self.afd_part
+    }
+
+    /// The length in octets of the address family dependent part
+    pub fn afd_length(&self) -> u8 {
+        self.afd_part.len() as u8
+    }
+}
+
+impl BinEncodable for APL {
+    fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
+        for item in &self.items {
+            encoder.emit_u16(item.family)?;
+            encoder.emit_u8(item.prefix)?;
+            encoder.emit_u8(u8::from(item.negation) << 7 | item.afd_length())?;
+            encoder.emit_vec(&item.afd_part)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'r> RecordDataDecodable<'r> for APL {
+    fn read_data(decoder: &mut BinDecoder<'r>, length: Restrict<u16>) -> ProtoResult<Self> {
+        let data_len = decoder.len();
+        let mut items = Vec::new();
+
+        // no unsafe usage of rdata length after this point
+        let rdata_length =
+            length.map(|u| u as usize).unverified(/*used as a higher bound, safely*/);
+        while data_len - decoder.len() < rdata_length {
+            let family = decoder.read_u16()?.unverified(/*checked in AplItem::from_afd_part*/);
+            let prefix = decoder.read_u8()?.unverified(/*checked in AplItem::from_afd_part*/);
+            let flags = decoder.read_u8()?.unverified(/*any negation and length is valid here*/);
+            let negation = flags & 0x80 != 0;
+            let afd_length = (flags & 0x7f) as usize;
+
+            let afd_part = decoder
+                .read_vec(afd_length)?
+                .unverified(/*checked in AplItem::from_afd_part*/);
+            items.push(AplItem::from_afd_part(negation, family, prefix, afd_part)?);
+        }
+
+        Ok(Self::new(items))
+    }
+}
+
+impl RecordData for APL {
+    fn try_from_rdata(data: RData) -> Result<Self, RData> {
+        match data {
+            RData::APL(data) => Ok(data),
+            _ => Err(data),
+        }
+    }
+
+    fn try_borrow(data: &RData) -> Option<&Self> {
+        match data {
+            RData::APL(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    fn record_type(&self) -> RecordType {
+        RecordType::APL
+    }
+
+    fn into_rdata(self) -> RData {
+        RData::APL(self)
+    }
+}
+
+/// [RFC 3123](https://tools.ietf.org/html/rfc3123#section-5)
+///
+/// ```text
+/// 5. Zone File Syntax
+///
+///    The textual representation of an APL RR in a DNS zone file is as
+///    follows:
+///
+///    <owner>   IN   <TTL>   APL   {[!]afi:address/prefix}*
+///
+///    The data consists of zero or more strings of the address family
+///    indicator <afi>, immediately followed by a colon ":", an address,
+///    immediately followed by the "/" character, immediately followed by a
+///    decimal numeric value for the prefix length.  Any such string may be
+///    preceded by a "!" character.  The strings are separated by
+///    whitespace.  The <afi> is the decimal numeric value of that
+///    particular address family.
+/// ```
+impl fmt::Display for APL {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        for (i, item) in self.items.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{item}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The address of the families other than IPv4 and IPv6 is written as the hexadecimal of its
+/// address family dependent part
+impl fmt::Display for AplItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        if self.negation {
+            f.write_str("!")?;
+        }
+        write!(f, "{}:", self.family)?;
+        match self.address() {
+            Some(address) => write!(f, "{address}")?,
+            None => f.write_str(&HEXLOWER.encode(&self.afd_part))?,
+        }
+        write!(f, "/{}", self.prefix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn test_encode_decode_apl() {
+        let rdata = APL::new(vec![
+            AplItem::new(false, "192.168.32.0".parse().unwrap(), 21).unwrap(),
+            AplItem::new(true, "192.168.38.0".parse().unwrap(), 28).unwrap(),
+            AplItem::new(false, "0.0.0.0".parse().unwrap(), 0).unwrap(),
+            AplItem::new(true, "2001:db8::".parse().unwrap(), 32).unwrap(),
+            AplItem::new(false, "ff00::".parse().unwrap(), 8).unwrap(),
+            // the trailing zero octets of the unknown families are kept
+            AplItem::from_afd_part(true, 3, 8, vec![10, 0]).unwrap(),
+        ]);
+
+        let mut bytes = Vec::new();
+        let mut encoder = BinEncoder::new(&mut bytes);
+        rdata.emit(&mut encoder).expect("failed to emit APL");
+        let bytes = encoder.into_bytes();
+        assert_eq!(
+            bytes,
+            &[
+                0, 1, 21, 0x03, 192, 168, 32, //
+                0, 1, 28, 0x83, 192, 168, 38, //
+                0, 1, 0, 0x00, //
+                0, 2, 32, 0x84, 0x20, 0x01, 0x0d, 0xb8, //
+                0, 2, 8, 0x01, 0xff, //
+                0, 3, 8, 0x82, 10, 0,
+            ]
+        );
+
+        let mut decoder = BinDecoder::new(bytes);
+        let read_rdata = APL::read_data(&mut decoder, Restrict::new(bytes.len() as u16))
+            .expect("failed to read APL");
+        assert_eq!(read_rdata, rdata);
+        assert_eq!(read_rdata.items()[1].afd_length(), 3);
+        assert_eq!(read_rdata.items()[3].family(), FAMILY_IPV6);
+        assert_eq!(read_rdata.items()[5].address(), None);
+        assert_eq!(read_rdata.items()[5].afd_part(), &[10, 0]);
+        assert_eq!(
+            read_rdata.to_string(),
+            "1:192.168.32.0/21 !1:192.168.38.0/28 1:0.0.0.0/0 !2:2001:db8::/32 2:ff00::/8 !3:0a00/8"
+        );
+
+        let mut decoder = BinDecoder::new(&[]);
+        let empty = APL::read_data(&mut decoder, Restrict::new(0)).unwrap();
+        assert!(empty.items().is_empty());
+    }
+
+    #[test]
+    fn test_decode_invalid_apl() {
+        for invalid in [
+            // trailing zero octet
+            &[0, 1, 24, 0x03, 192, 168, 0][..],
+            // address longer than the family
+            &[0, 1, 32, 0x05, 192, 168, 1, 1, 1],
+            // prefix longer than the family
+            &[0, 1, 33, 0x01, 10],
+            &[0, 2, 129, 0x01, 0xff],
+            // truncated
+            &[0, 1, 24, 0x03, 192, 168],
+        ] {
+            let mut decoder = BinDecoder::new(invalid);
+            assert!(
+                APL::read_data(&mut decoder, Restrict::new(invalid.len() as u16)).is_err(),
+                "{invalid:?}"
+            );
+        }
+
+        assert!(AplItem::new(false, Ipv4Addr::UNSPECIFIED.into(), 33).is_err());
+        assert!(AplItem::new(false, Ipv6Addr::UNSPECIFIED.into(), 129).is_err());
+        assert!(AplItem::from_afd_part(false, FAMILY_IPV4, 8, vec![10, 0]).is_err());
+        assert!(AplItem::from_afd_part(false, 3, 8, vec![0; 128]).is_err());
+    }
+}
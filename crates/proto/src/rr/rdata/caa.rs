@@ -21,10 +21,12 @@
 //! ```
 #![allow(clippy::use_self)]
 
-use std::{fmt, str};
+use alloc::{string::String, vec::Vec};
+use core::{fmt, str};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
 use url::Url;
 
 use crate::{
@@ -94,6 +96,7 @@ impl CAA {
     ///
     /// * `issuer_critical` - indicates that the corresponding property tag MUST be understood if the semantics of the CAA record are to be correctly interpreted by an issuer
     /// * `url` - Url where issuer errors should be reported
+    #[cfg(feature = "std")]
     pub fn new_iodef(issuer_critical: bool, url: Url) -> Self {
         Self {
             issuer_critical,
@@ -133,6 +136,45 @@ impl CAA {
         self.tag = tag;
     }
 
+    /// true if the property tag is `issue`
+    pub fn is_issue(&self) -> bool {
+        self.tag.is_issue()
+    }
+
+    /// true if the property tag is `issuewild`
+    pub fn is_issuewild(&self) -> bool {
+        self.tag.is_issuewild()
+    }
+
+    /// true if the property tag is `iodef`
+    pub fn is_iodef(&self) -> bool {
+        self.tag.is_iodef()
+    }
+
+    /// true if the Issuer Critical Flag is set on a property tag not known to Hickory DNS
+    ///
+    /// [RFC 8659 section 4.1](https://www.rfc-editor.org/rfc/rfc8659#section-4.1) requires that
+    /// an issuer MUST NOT issue certificates if it encounters such a record.
+    pub fn is_unknown_critical(&self) -> bool {
+        self.issuer_critical && self.tag.is_unknown()
+    }
+
+    /// The issuer domain name and parameters of an `issue` or `issuewild` property
+    ///
+    /// Returns `None` for any other property. An issuer domain name of `None` means that no
+    /// issuance is allowed, which is also how a malformed value is reported, see
+    /// [RFC 8659 section 4.2](https://www.rfc-editor.org/rfc/rfc8659#section-4.2).
+    pub fn issuer(&self) -> Option<(Option<&Name>, &[KeyValue])> {
+        if !self.is_issue() && !self.is_issuewild() {
+            return None;
+        }
+
+        match &self.value {
+            Value::Issuer(name, key_values) => Some((name.as_ref(), key_values)),
+            _ => Some((None, &[])),
+        }
+    }
+
     /// a potentially associated value with the property tag, see struct documentation
     pub fn value(&self) -> &Value {
         &self.value
@@ -227,13 +269,15 @@ impl From<String> for Property {
 /// `Iodef` => `Url`,
 /// `Unknown` => `Unknown`.
 ///
-/// `Unknown` is also used for invalid values of known Tag types that cannot be parsed.
+/// `Unknown` is also used for invalid values of known Tag types that cannot be parsed, and for
+/// the `Iodef` values without the `std` feature.
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum Value {
     /// Issuer authorized to issue certs for this zone, and any associated parameters
     Issuer(Option<Name>, Vec<KeyValue>),
     /// Url to which to send CA errors
+    #[cfg(feature = "std")]
     Url(Url),
     /// Uninterpreted data, either for a tag that is not known to Hickory DNS, or an invalid value
     Unknown(Vec<u8>),
@@ -246,6 +290,7 @@ impl Value {
     }
 
     /// true if this is a `Url`
+    #[cfg(feature = "std")]
     pub fn is_url(&self) -> bool {
         matches!(*self, Value::Url(..))
     }
@@ -270,6 +315,7 @@ fn read_value(
                 Err(_) => Value::Unknown(slice.to_vec()),
             })
         }
+        #[cfg(feature = "std")]
         Property::Iodef => {
             let url = decoder.read_slice(value_len)?.unverified(/*read_iodef verified as safe*/);
             Ok(match read_iodef(url) {
@@ -277,6 +323,10 @@ fn read_value(
                 Err(_) => Value::Unknown(url.to_vec()),
             })
         }
+        #[cfg(not(feature = "std"))]
+        Property::Iodef => Ok(Value::Unknown(
+            decoder.read_vec(value_len)?.unverified(/*unknown will fail in usage*/),
+        )),
         Property::Unknown(_) => Ok(Value::Unknown(
             decoder.read_vec(value_len)?.unverified(/*unknown will fail in usage*/),
         )),
@@ -307,6 +357,7 @@ fn emit_value(encoder: &mut BinEncoder<'_>, value: &Value) -> ProtoResult<()> {
 
             Ok(())
         }
+        #[cfg(feature = "std")]
         Value::Url(url) => {
             let url = url.as_str();
             let bytes = url.as_bytes();
@@ -422,9 +473,9 @@ pub fn read_issuer(bytes: &[u8]) -> ProtoResult<(Option<Name>, Vec<KeyValue>)> {
     let name: Option<Name> = {
         let take_name = byte_iter.by_ref().take_while(|ch| char::from(**ch) != ';');
         let name_str = take_name.cloned().collect::<Vec<u8>>();
+        let name_str = str::from_utf8(&name_str)?.trim();
 
         if !name_str.is_empty() {
-            let name_str = str::from_utf8(&name_str)?;
             Some(Name::from_ascii(name_str)?)
         } else {
             None
@@ -573,6 +624,7 @@ pub fn read_issuer(bytes: &[u8]) -> ProtoResult<(Option<Name>, Vec<KeyValue>)> {
 ///    report.example.com         CAA 0 iodef "mailto:security@example.com"
 ///    report.example.com         CAA 0 iodef "https://iodef.example.com/"
 /// ```
+#[cfg(feature = "std")]
 pub fn read_iodef(url: &[u8]) -> ProtoResult<Url> {
     let url = str::from_utf8(url)?;
     let url = Url::parse(url)?;
@@ -817,6 +869,7 @@ impl fmt::Display for Value {
                     write!(f, "; {value}")?;
                 }
             }
+            #[cfg(feature = "std")]
             Value::Url(url) => write!(f, "{url}")?,
             Value::Unknown(v) => match str::from_utf8(v) {
                 Ok(text) => write!(f, "{text}")?,
@@ -856,7 +909,8 @@ impl fmt::Display for CAA {
 mod tests {
     #![allow(clippy::dbg_macro, clippy::print_stdout)]
 
-    use std::str;
+    use alloc::string::ToString;
+    use core::str;
 
     use super::*;
 
@@ -967,10 +1021,20 @@ mod tests {
             )
         );
         assert_eq!(read_issuer(b";").unwrap(), (None, vec![]));
+        assert_eq!(read_issuer(b"").unwrap(), (None, vec![]));
+        assert_eq!(read_issuer(b" ; ").unwrap(), (None, vec![]));
+        assert_eq!(
+            read_issuer(b" ca.example.net ;account=123").unwrap(),
+            (
+                Some(Name::parse("ca.example.net", None).unwrap()),
+                vec![KeyValue::new("account", "123")],
+            )
+        );
         read_issuer(b"example.com; param=\xff").unwrap_err();
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn test_read_iodef() {
         assert_eq!(
             read_iodef(b"mailto:security@example.com").unwrap(),
@@ -1037,6 +1101,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn test_encode_decode_iodef() {
         test_encode_decode(CAA::new_iodef(
             true,
@@ -1170,6 +1235,18 @@ mod tests {
             .to_string(),
             "0 issue \"ca.example.net; policy=ev\""
         );
+        let unknown = CAA {
+            issuer_critical: true,
+            reserved_flags: 0,
+            tag: Property::from("tbs".to_string()),
+            value: Value::Unknown("Unknown".as_bytes().to_vec()),
+        };
+        assert_eq!(unknown.to_string(), "128 tbs \"Unknown\"");
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_iodef_to_string() {
         assert_eq!(
             CAA::new_iodef(false, Url::parse("mailto:security@example.com").unwrap()).to_string(),
             "0 iodef \"mailto:security@example.com\""
@@ -1178,13 +1255,6 @@ mod tests {
             CAA::new_iodef(false, Url::parse("https://iodef.example.com/").unwrap()).to_string(),
             "0 iodef \"https://iodef.example.com/\""
         );
-        let unknown = CAA {
-            issuer_critical: true,
-            reserved_flags: 0,
-            tag: Property::from("tbs".to_string()),
-            value: Value::Unknown("Unknown".as_bytes().to_vec()),
-        };
-        assert_eq!(unknown.to_string(), "128 tbs \"Unknown\"");
     }
 
     #[test]
@@ -6,7 +6,11 @@
 // copied, modified, or distributed except according to those terms.
 
 //! CERT record type for storing certificates in DNS
-use std::fmt;
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
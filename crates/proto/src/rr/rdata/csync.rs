@@ -7,7 +7,8 @@
 
 //! CSYNC record for synchronizing data from a child zone to the parent
 
-use std::fmt;
+use alloc::vec::Vec;
+use core::fmt;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
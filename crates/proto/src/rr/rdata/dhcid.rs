@@ -0,0 +1,298 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DHCID records for the association of DHCP clients with their names
+
+use alloc::vec::Vec;
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{ProtoError, ProtoResult},
+    rr::{RData, RecordData, RecordDataDecodable, RecordType},
+    serialize::binary::{BinDecoder, BinEncodable, BinEncoder, Restrict, RestrictedMath},
+};
+
+/// [RFC 4701](https://tools.ietf.org/html/rfc4701#section-3.1)
+///
+/// ```text
+/// 3.1.  DHCID RDATA Format
+///
+///    The RDATA section of a DHCID RR in transmission contains RDLENGTH
+///    octets of binary data.  The format of this data and its
+///    interpretation by DHCP servers and clients are described below.
+///
+/// 3.3.  The DHCID RR Identifier Type Codes
+///
+///    The DHCID RR Identifier Type Code specifies what data from the DHCP
+///    client's request was used as input into the hash function.
+///
+/// 3.4.  The DHCID RR Digest Type Code
+///
+///    The DHCID RR Digest Type Code is an identifier for the digest
+///    algorithm used.  The digest is calculated over an identifier and the
+///    canonical FQDN as described in the next section.
+///
+/// 3.5.  Computation of the RDATA
+///
+///    The DHCID RDATA is formed by concatenating the 2-octet identifier
+///    type code with variable-length data.
+///
+///    The RDATA for all type codes other than 0xffff, which is reserved for
+///    future expansion, is formed by concatenating the 2-octet identifier
+///    type code, the 1-octet digest type code, and the digest value (32
+///    octets for SHA-256).
+/// ```
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct DHCID {
+    identifier_type: IdentifierType,
+    digest_type: DigestType,
+    digest: Vec<u8>,
+}
+
+impl DHCID {
+    /// Creates a new DHCID record data.
+    ///
+    /// # Arguments
+    ///
+    /// * `identifier_type` - the identifier of the DHCP client that the digest is computed over.
+    /// * `digest_type` - the digest algorithm.
+    /// * `digest` - the digest of the identifier and the name of the client.
+    pub fn new(identifier_type: IdentifierType, digest_type: DigestType, digest: Vec<u8>) -> Self {
+        Self {
+            identifier_type,
+            digest_type,
+            digest,
+        }
+    }
+
+    /// The identifier of the DHCP client that the digest is computed over.
+    pub fn identifier_type(&self) -> IdentifierType {
+        self.identifier_type
+    }
+
+    /// The digest algorithm.
+    pub fn digest_type(&self) -> DigestType {
+        self.digest_type
+    }
+
+    /// The digest of the identifier and the name of the client.
+    pub fn digest(&self) -> &[u8] {
+        &self.digest
+    }
+}
+
+/// ```text
+/// 3.3.  The DHCID RR Identifier Type Codes
+///
+///    The following values for the Identifier Type Code are defined:
+///
+///    +------------------+------------------------------------------------+
+///    |  Identifier Type | Identifier                                     |
+///    |       Code       |                                                |
+///    +------------------+------------------------------------------------+
+///    |      0x0000      | The 1-octet 'htype' followed by 'hlen' octets  |
+///    |                  | of 'chaddr' from a DHCPv4 client's DHCPREQUEST |
+///    |                  | [7].                                           |
+///    |      0x0001      | The data octets (i.e., the Type and            |
+///    |                  | Client-Identifier fields) from a DHCPv4        |
+///    |                  | client's Client Identifier option [10].        |
+///    |      0x0002      | The client's DUID (i.e., the data octets of a  |
+///    |                  | DHCPv6 client's Client Identifier option [11]  |
+///    |                  | or the DUID field from a DHCPv4 client's       |
+///    |                  | Client Identifier option [6]).                 |
+///    |  0x0003 - 0xfffe | Undefined; available to be assigned by IANA.   |
+///    |      0xffff      | Undefined; RESERVED.                           |
+///    +------------------+------------------------------------------------+
+/// ```
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum IdentifierType {
+    /// The hardware type and address of a DHCPv4 client
+    HardwareAddress,
+
+    /// The Client Identifier option of a DHCPv4 client
+    ClientIdentifier,
+
+    /// The DUID of a DHCPv6 or a DHCPv4 client
+    Duid,
+
+    /// Reserved value
+    Reserved,
+
+    /// Unassigned value
+    Unassigned(u16),
+}
+
+impl From<u16> for IdentifierType {
+    fn from(identifier_type: u16) -> Self {
+        match identifier_type {
+            0x0000 => Self::HardwareAddress,
+            0x0001 => Self::ClientIdentifier,
+            0x0002 => Self::Duid,
+            0xffff => Self::Reserved,
+            _ => Self::Unassigned(identifier_type),
+        }
+    }
+}
+
+impl From<IdentifierType> for u16 {
+    fn from(identifier_type: IdentifierType) -> Self {
+        match identifier_type {
+            IdentifierType::HardwareAddress => 0x0000,
+            IdentifierType::ClientIdentifier => 0x0001,
+            IdentifierType::Duid => 0x0002,
+            IdentifierType::Reserved => 0xffff,
+            IdentifierType::Unassigned(identifier_type) => identifier_type,
+        }
+    }
+}
+
+/// ```text
+/// 3.4.  The DHCID RR Digest Type Code
+///
+///    The Digest Type Code 1 is assigned to SHA-256 [12] as specified in
+///    [13].  The Digest Type Code 0 is reserved.
+/// ```
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum DigestType {
+    /// Reserved value
+    Reserved,
+
+    /// SHA-256
+    SHA256,
+
+    /// Unassigned value
+    Unassigned(u8),
+}
+
+impl From<u8> for DigestType {
+    fn from(digest_type: u8) -> Self {
+        match digest_type {
+            0 => Self::Reserved,
+            1 => Self::SHA256,
+            _ => Self::Unassigned(digest_type),
+        }
+    }
+}
+
+impl From<DigestType> for u8 {
+    fn from(digest_type: DigestType) -> Self {
+        match digest_type {
+            DigestType::Reserved => 0,
+            DigestType::SHA256 => 1,
+            DigestType::Unassigned(digest_type) => digest_type,
+        }
+    }
+}
+
+impl BinEncodable for DHCID {
+    fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
+        encoder.emit_u16(self.identifier_type().into())?;
+        encoder.emit_u8(self.digest_type().into())?;
+        encoder.emit_vec(self.digest())
+    }
+}
+
+impl<'r> RecordDataDecodable<'r> for DHCID {
+    fn read_data(decoder: &mut BinDecoder<'r>, length: Restrict<u16>) -> ProtoResult<Self> {
+        let digest_len = length
+            .map(|l| l as usize)
+            .checked_sub(3)
+            .map_err(|_| ProtoError::from("invalid rdata length in DHCID"))?
+            .unverified();
+        let identifier_type = decoder.read_u16()?.unverified().into();
+        let digest_type = decoder.read_u8()?.unverified().into();
+        let digest = decoder.read_vec(digest_len)?.unverified();
+        Ok(Self::new(identifier_type, digest_type, digest))
+    }
+}
+
+impl RecordData for DHCID {
+    fn try_from_rdata(data: RData) -> Result<Self, RData> {
+        match data {
+            RData::DHCID(data) => Ok(data),
+            _ => Err(data),
+        }
+    }
+
+    fn try_borrow(data: &RData) -> Option<&Self> {
+        match data {
+            RData::DHCID(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    fn record_type(&self) -> RecordType {
+        RecordType::DHCID
+    }
+
+    fn into_rdata(self) -> RData {
+        RData::DHCID(self)
+    }
+}
+
+/// [RFC 4701](https://tools.ietf.org/html/rfc4701#section-3.2)
+///
+/// ```text
+/// 3.2.  DHCID Presentation Format
+///
+///    In DNS master files, the RDATA is represented as a single block in
+///    base-64 encoding identical to that used for representing binary data
+///    in [3], Section 3.  The data may be divided up into any number of
+///    white-space-separated substrings, each of which is a valid base-64
+///    encoding, which are then concatenated.
+/// ```
+impl fmt::Display for DHCID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let mut rdata = Vec::with_capacity(3 + self.digest.len());
+        rdata.extend_from_slice(&u16::from(self.identifier_type).to_be_bytes());
+        rdata.push(self.digest_type.into());
+        rdata.extend_from_slice(&self.digest);
+
+        f.write_str(&data_encoding::BASE64.encode(&rdata))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode_dhcid() {
+        let rdata = DHCID::new(IdentifierType::Duid, DigestType::SHA256, vec![0xab; 32]);
+
+        let mut bytes = Vec::new();
+        let mut encoder = BinEncoder::new(&mut bytes);
+        rdata.emit(&mut encoder).expect("failed to emit DHCID");
+        let bytes = encoder.into_bytes();
+        assert_eq!(&bytes[..3], &[0, 2, 1]);
+        assert_eq!(bytes.len(), 35);
+
+        let mut decoder = BinDecoder::new(bytes);
+        let read_rdata = DHCID::read_data(&mut decoder, Restrict::new(bytes.len() as u16))
+            .expect("failed to read DHCID");
+        assert_eq!(read_rdata, rdata);
+
+        let unknown = [0x12, 0x34, 0x07];
+        let mut decoder = BinDecoder::new(&unknown);
+        let read_rdata = DHCID::read_data(&mut decoder, Restrict::new(3)).unwrap();
+        assert_eq!(
+            read_rdata.identifier_type(),
+            IdentifierType::Unassigned(0x1234)
+        );
+        assert_eq!(read_rdata.digest_type(), DigestType::Unassigned(7));
+        assert!(read_rdata.digest().is_empty());
+
+        let mut decoder = BinDecoder::new(&unknown[..2]);
+        assert!(DHCID::read_data(&mut decoder, Restrict::new(2)).is_err());
+    }
+}
@@ -7,7 +7,8 @@
 
 //! HINFO record for storing host information
 
-use std::fmt;
+use alloc::{boxed::Box, string::String};
+use core::fmt;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -181,6 +182,8 @@ impl fmt::Display for HINFO {
 mod tests {
     #![allow(clippy::dbg_macro, clippy::print_stdout)]
 
+    use alloc::{string::ToString, vec::Vec};
+
     use super::*;
 
     #[test]
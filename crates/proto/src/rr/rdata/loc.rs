@@ -0,0 +1,492 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! LOC records for the geographical location of hosts, networks and subnets
+
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{ProtoError, ProtoResult},
+    rr::{RData, RecordData, RecordDataDecodable, RecordType},
+    serialize::binary::{BinDecoder, BinEncodable, BinEncoder, Restrict},
+};
+
+/// The encoded latitude of the equator
+pub const EQUATOR: u32 = 1 << 31;
+
+/// The encoded longitude of the prime meridian
+pub const PRIME_MERIDIAN: u32 = EQUATOR;
+
+/// The encoded altitude of the WGS 84 reference spheroid, 100,000m above the base of the altitude
+pub const REFERENCE_SPHEROID: u32 = 10_000_000;
+
+/// The number of thousandths of a second of arc in one degree
+pub(crate) const DEGREE: u32 = 60 * 60 * 1000;
+
+/// [RFC 1876](https://tools.ietf.org/html/rfc1876#section-2)
+///
+/// ```text
+/// 2. RDATA Format
+///
+///        MSB                                           LSB
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       0|        VERSION        |         SIZE          |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       2|       HORIZ PRE       |       VERT PRE        |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       4|                   LATITUDE                    |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       6|                   LATITUDE                    |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       8|                   LONGITUDE                   |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///      10|                   LONGITUDE                   |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///      12|                   ALTITUDE                    |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///      14|                   ALTITUDE                    |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///
+/// where:
+///
+/// VERSION      Version number of the representation.  This must be zero.
+///              Implementations are required to check this field and make
+///              no assumptions about the format of unrecognized versions.
+///
+/// SIZE         The diameter of a sphere enclosing the described entity, in
+///              centimeters, expressed as a pair of four-bit unsigned
+///              integers, each ranging from zero to nine, with the most
+///              significant four bits representing the base and the second
+///              number representing the power of ten by which to multiply
+///              the base.
+///
+/// HORIZ PRE    The horizontal precision of the data, in centimeters,
+///              expressed using the same representation as SIZE.
+///
+/// VERT PRE     The vertical precision of the data, in centimeters,
+///              expressed using the sane representation as for SIZE.
+///
+/// LATITUDE     The latitude of the center of the sphere described by the
+///              SIZE field, expressed as a 32-bit integer, most significant
+///              octet first (network standard byte order), in thousandths
+///              of a second of arc.  2^31 represents the equator; numbers
+///              above that are north latitude.
+///
+/// LONGITUDE    The longitude of the center of the sphere described by the
+///              SIZE field, expressed as a 32-bit integer, most significant
+///              octet first (network standard byte order), in thousandths
+///              of a second of arc, rounded away from the prime meridian.
+///              2^31 represents the prime meridian; numbers above that are
+///              east longitude.
+///
+/// ALTITUDE     The altitude of the center of the sphere described by the
+///              SIZE field, expressed as a 32-bit integer, most significant
+///              octet first (network standard byte order), in centimeters,
+///              from a base of 100,000m below the [WGS 84] reference
+///              spheroid used by GPS.
+/// ```
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct LOC {
+    size: Precision,
+    horizontal_precision: Precision,
+    vertical_precision: Precision,
+    latitude: u32,
+    longitude: u32,
+    altitude: u32,
+}
+
+impl LOC {
+    /// Creates a new LOC record data.
+    ///
+    /// # Arguments
+    ///
+    /// * `latitude` - the latitude in thousandths of a second of arc, where [`EQUATOR`] is the
+    ///   equator and values above it are north.
+    /// * `longitude` - the longitude in thousandths of a second of arc, where [`PRIME_MERIDIAN`] is
+    ///   the prime meridian and values above it are east.
+    /// * `altitude` - the altitude in centimeters from a base of 100,000m below the WGS 84
+    ///   reference spheroid, see [`REFERENCE_SPHEROID`].
+    /// * `size` - the diameter of a sphere enclosing the described entity.
+    /// * `horizontal_precision` - the horizontal precision of the location.
+    /// * `vertical_precision` - the vertical precision of the location.
+    pub fn new(
+        latitude: u32,
+        longitude: u32,
+        altitude: u32,
+        size: Precision,
+        horizontal_precision: Precision,
+        vertical_precision: Precision,
+    ) -> Self {
+        Self {
+            size,
+            horizontal_precision,
+            vertical_precision,
+            latitude,
+            longitude,
+            altitude,
+        }
+    }
+
+    /// The latitude in thousandths of a second of arc, where [`EQUATOR`] is the equator
+    pub fn latitude(&self) -> u32 {
+        self.latitude
+    }
+
+    /// The longitude in thousandths of a second of arc, where [`PRIME_MERIDIAN`] is the prime
+    /// meridian
+    pub fn longitude(&self) -> u32 {
+        self.longitude
+    }
+
+    /// The altitude in centimeters from a base of 100,000m below the WGS 84 reference spheroid
+    pub fn altitude(&self) -> u32 {
+        self.altitude
+    }
+
+    /// The diameter of a sphere enclosing the described entity
+    pub fn size(&self) -> Precision {
+        self.size
+    }
+
+    /// The horizontal precision of the location
+    pub fn horizontal_precision(&self) -> Precision {
+        self.horizontal_precision
+    }
+
+    /// The vertical precision of the location
+    pub fn vertical_precision(&self) -> Precision {
+        self.vertical_precision
+    }
+}
+
+/// A size or precision in centimeters, as a base and a power of ten
+///
+/// ```text
+/// SIZE         The diameter of a sphere enclosing the described entity, in
+///              centimeters, expressed as a pair of four-bit unsigned
+///              integers, each ranging from zero to nine, with the most
+///              significant four bits representing the base and the second
+///              number representing the power of ten by which to multiply
+///              the base.  This allows sizes from 0e0 (<1cm) to 9e9
+///              (90,000km) to be expressed.
+/// ```
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Precision(u8);
+
+impl Precision {
+    /// The default size of 1m
+    pub const DEFAULT_SIZE: Self = Self(0x12);
+
+    /// The default horizontal precision of 10,000m
+    pub const DEFAULT_HORIZONTAL: Self = Self(0x16);
+
+    /// The default vertical precision of 10m
+    pub const DEFAULT_VERTICAL: Self = Self(0x13);
+
+    /// The largest value that can be expressed, 9e9 centimeters
+    pub const MAX_CENTIMETERS: u64 = 9_000_000_000;
+
+    /// Creates the precision from a base and a power of ten, both ranging from zero to nine
+    pub fn new(base: u8, exponent: u8) -> Option<Self> {
+        (base <= 9 && exponent <= 9).then_some(Self(base << 4 | exponent))
+    }
+
+    /// Converts a value in centimeters, the part that can not be expressed is truncated
+    pub fn from_centimeters(centimeters: u64) -> Self {
+        let centimeters = centimeters.min(Self::MAX_CENTIMETERS);
+        let mut exponent = 0;
+        while exponent < 9 && centimeters >= 10_u64.pow(exponent + 1) {
+            exponent += 1;
+        }
+
+        Self(((centimeters / 10_u64.pow(exponent)) as u8) << 4 | exponent as u8)
+    }
+
+    /// The value in centimeters
+    pub fn centimeters(self) -> u64 {
+        u64::from(self.base()) * 10_u64.pow(u32::from(self.exponent()))
+    }
+
+    /// The base, from zero to nine
+    pub fn base(self) -> u8 {
+        self.0 >> 4
+    }
+
+    /// The power of ten by which the base is multiplied, from zero to nine
+    pub fn exponent(self) -> u8 {
+        self.0 & 0x0f
+    }
+}
+
+impl TryFrom<u8> for Precision {
+    type Error = ProtoError;
+
+    fn try_from(value: u8) -> ProtoResult<Self> {
+        Self::new(value >> 4, value & 0x0f)
+            .ok_or_else(|| ProtoError::from(format!("invalid LOC size or precision: {value:#04x}")))
+    }
+}
+
+impl From<Precision> for u8 {
+    fn from(precision: Precision) -> Self {
+        precision.0
+    }
+}
+
+/// Formats the value in meters, without the fraction if it is a whole number of meters
+impl fmt::Display for Precision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let centimeters = self.centimeters();
+        match centimeters % 100 {
+            0 => write!(f, "{}m", centimeters / 100),
+            fraction => write!(f, "{}.{fraction:02}m", centimeters / 100),
+        }
+    }
+}
+
+impl BinEncodable for LOC {
+    fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
+        encoder.emit_u8(0)?;
+        encoder.emit_u8(self.size.into())?;
+        encoder.emit_u8(self.horizontal_precision.into())?;
+        encoder.emit_u8(self.vertical_precision.into())?;
+        encoder.emit_u32(self.latitude)?;
+        encoder.emit_u32(self.longitude)?;
+        encoder.emit_u32(self.altitude)
+    }
+}
+
+impl<'r> RecordDataDecodable<'r> for LOC {
+    fn read_data(decoder: &mut BinDecoder<'r>, length: Restrict<u16>) -> ProtoResult<Self> {
+        let version = decoder.read_u8()?.unverified(/*checked below*/);
+        if version != 0 {
+            return Err(ProtoError::from(format!(
+                "unsupported LOC version: {version}"
+            )));
+        }
+
+        length
+            .verify_unwrap(|length| *length == 16)
+            .map_err(|length| ProtoError::from(format!("invalid rdata length in LOC: {length}")))?;
+
+        let size = Precision::try_from(decoder.read_u8()?.unverified())?;
+        let horizontal_precision = Precision::try_from(decoder.read_u8()?.unverified())?;
+        let vertical_precision = Precision::try_from(decoder.read_u8()?.unverified())?;
+        let latitude = decoder.read_u32()?.unverified(/*any latitude is valid on the wire*/);
+        let longitude = decoder.read_u32()?.unverified(/*any longitude is valid on the wire*/);
+        let altitude = decoder.read_u32()?.unverified(/*any altitude is valid*/);
+
+        Ok(Self::new(
+            latitude,
+            longitude,
+            altitude,
+            size,
+            horizontal_precision,
+            vertical_precision,
+        ))
+    }
+}
+
+impl RecordData for LOC {
+    fn try_from_rdata(data: RData) -> Result<Self, RData> {
+        match data {
+            RData::LOC(data) => Ok(data),
+            _ => Err(data),
+        }
+    }
+
+    fn try_borrow(data: &RData) -> Option<&Self> {
+        match data {
+            RData::LOC(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    fn record_type(&self) -> RecordType {
+        RecordType::LOC
+    }
+
+    fn into_rdata(self) -> RData {
+        RData::LOC(self)
+    }
+}
+
+/// Writes an angle from its reference in the `d m s` notation followed by the hemisphere
+fn write_angle(
+    f: &mut fmt::Formatter<'_>,
+    angle: u32,
+    reference: u32,
+    positive: char,
+    negative: char,
+) -> Result<(), fmt::Error> {
+    let (angle, hemisphere) = match angle.checked_sub(reference) {
+        Some(angle) => (angle, positive),
+        None => (reference - angle, negative),
+    };
+
+    write!(
+        f,
+        "{degrees} {minutes} {seconds}.{thousandths:03} {hemisphere}",
+        degrees = angle / DEGREE,
+        minutes = angle / 60_000 % 60,
+        seconds = angle / 1000 % 60,
+        thousandths = angle % 1000,
+    )
+}
+
+/// [RFC 1876](https://tools.ietf.org/html/rfc1876#section-3)
+///
+/// ```text
+/// 3. Master File Format
+///
+///    The LOC record is expressed in a master file in the following format:
+///
+///    <owner> <TTL> <class> LOC ( d1 [m1 [s1]] {"N"|"S"} d2 [m2 [s2]]
+///                                {"E"|"W"} alt["m"] [siz["m"] [hp["m"]
+///                                [vp["m"]]]] )
+///
+///    (The parentheses are used for multi-line data as specified in [RFC
+///    1035] section 5.1.)
+///
+///    where:
+///
+///        d1:     [0 .. 90]            (degrees latitude)
+///        d2:     [0 .. 180]           (degrees longitude)
+///        m1, m2: [0 .. 59]            (minutes latitude/longitude)
+///        s1, s2: [0 .. 59.999]        (seconds latitude/longitude)
+///        alt:    [-100000.00 .. 42849672.95] BY .01 (altitude in meters)
+///        siz, hp, vp: [0 .. 90000000.00] (size/precision in meters)
+/// ```
+impl fmt::Display for LOC {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write_angle(f, self.latitude, EQUATOR, 'N', 'S')?;
+        f.write_str(" ")?;
+        write_angle(f, self.longitude, PRIME_MERIDIAN, 'E', 'W')?;
+
+        let (altitude, sign) = match self.altitude.checked_sub(REFERENCE_SPHEROID) {
+            Some(altitude) => (altitude, ""),
+            None => (REFERENCE_SPHEROID - self.altitude, "-"),
+        };
+
+        write!(
+            f,
+            " {sign}{meters}.{centimeters:02}m {size} {horizontal} {vertical}",
+            meters = altitude / 100,
+            centimeters = altitude % 100,
+            size = self.size,
+            horizontal = self.horizontal_precision,
+            vertical = self.vertical_precision,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec::Vec};
+
+    use super::*;
+
+    #[test]
+    fn test_precision() {
+        assert_eq!(Precision::DEFAULT_SIZE.centimeters(), 100);
+        assert_eq!(Precision::DEFAULT_HORIZONTAL.centimeters(), 1_000_000);
+        assert_eq!(Precision::DEFAULT_VERTICAL.centimeters(), 1000);
+
+        assert_eq!(
+            Precision::from_centimeters(0),
+            Precision::new(0, 0).unwrap()
+        );
+        assert_eq!(
+            Precision::from_centimeters(3000),
+            Precision::new(3, 3).unwrap()
+        );
+        assert_eq!(
+            Precision::from_centimeters(3999),
+            Precision::new(3, 3).unwrap()
+        );
+        assert_eq!(
+            Precision::from_centimeters(u64::MAX),
+            Precision::new(9, 9).unwrap()
+        );
+        assert_eq!(Precision::new(9, 9).unwrap().centimeters(), 9_000_000_000);
+
+        assert!(Precision::new(10, 0).is_none());
+        assert!(Precision::try_from(0x1a).is_err());
+        assert_eq!(u8::from(Precision::try_from(0x35).unwrap()), 0x35);
+
+        assert_eq!(Precision::new(3, 3).unwrap().to_string(), "30m");
+        assert_eq!(Precision::new(5, 0).unwrap().to_string(), "0.05m");
+    }
+
+    #[test]
+    fn test_encode_decode_loc() {
+        // RFC 1876, section 4, cambridge-net.kei.com.
+        let rdata = LOC::new(
+            EQUATOR + 152_514_000,
+            PRIME_MERIDIAN - 255_978_000,
+            REFERENCE_SPHEROID - 2400,
+            Precision::new(3, 3).unwrap(),
+            Precision::DEFAULT_HORIZONTAL,
+            Precision::DEFAULT_VERTICAL,
+        );
+
+        let mut bytes = Vec::new();
+        let mut encoder = BinEncoder::new(&mut bytes);
+        rdata.emit(&mut encoder).expect("failed to emit LOC");
+        let bytes = encoder.into_bytes();
+        assert_eq!(
+            bytes,
+            &[
+                0x00, 0x33, 0x16, 0x13, 0x89, 0x17, 0x2d, 0xd0, 0x70, 0xbe, 0x15, 0xf0, 0x00, 0x98,
+                0x8d, 0x20
+            ]
+        );
+
+        let mut decoder = BinDecoder::new(bytes);
+        let read_rdata = LOC::read_data(&mut decoder, Restrict::new(bytes.len() as u16))
+            .expect("failed to read LOC");
+        assert_eq!(read_rdata, rdata);
+        assert_eq!(
+            read_rdata.to_string(),
+            "42 21 54.000 N 71 6 18.000 W -24.00m 30m 10000m 10m"
+        );
+
+        // the other versions are kept as unknown RDATA, which is emitted unchanged
+        let mut version = bytes.to_vec();
+        version[0] = 1;
+        let mut decoder = BinDecoder::new(&version);
+        assert!(LOC::read_data(&mut decoder, Restrict::new(16)).is_err());
+        let mut decoder = BinDecoder::new(&version);
+        let unknown = RData::read(&mut decoder, RecordType::LOC, Restrict::new(16))
+            .expect("failed to read LOC version 1");
+        assert!(matches!(
+            unknown,
+            RData::Unknown {
+                code: RecordType::LOC,
+                ..
+            }
+        ));
+        let mut emitted = Vec::new();
+        unknown
+            .emit(&mut BinEncoder::new(&mut emitted))
+            .expect("failed to emit LOC version 1");
+        assert_eq!(emitted, version);
+
+        let mut precision = bytes.to_vec();
+        precision[1] = 0xa0;
+        let mut decoder = BinDecoder::new(&precision);
+        assert!(LOC::read_data(&mut decoder, Restrict::new(16)).is_err());
+
+        let mut decoder = BinDecoder::new(&bytes[..12]);
+        assert!(LOC::read_data(&mut decoder, Restrict::new(12)).is_err());
+    }
+}
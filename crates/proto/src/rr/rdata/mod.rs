@@ -12,11 +12,14 @@
 // each of these module's has the parser for that rdata embedded, to keep the file sizes down...
 pub mod a;
 pub mod aaaa;
+pub mod apl;
 pub mod caa;
 pub mod cert;
 pub mod csync;
+pub mod dhcid;
 pub mod hinfo;
 pub mod https;
+pub mod loc;
 pub mod mx;
 pub mod name;
 pub mod naptr;
@@ -29,17 +32,21 @@ pub mod sshfp;
 pub mod svcb;
 pub mod tlsa;
 pub mod txt;
+pub mod zonemd;
 
 pub use self::a::A;
 pub use self::aaaa::AAAA;
+pub use self::apl::APL;
 pub use self::caa::CAA;
 pub use self::cert::CERT;
 pub use self::csync::CSYNC;
+pub use self::dhcid::DHCID;
 pub use self::hinfo::HINFO;
 pub use self::https::HTTPS;
+pub use self::loc::LOC;
 pub use self::mx::MX;
 pub use self::name::{ANAME, CNAME, NS, PTR};
-pub use self::naptr::NAPTR;
+pub use self::naptr::{NaptrFlag, NaptrServices, NAPTR};
 pub use self::null::NULL;
 pub use self::openpgpkey::OPENPGPKEY;
 pub use self::opt::OPT;
@@ -49,3 +56,4 @@ pub use self::sshfp::SSHFP;
 pub use self::svcb::SVCB;
 pub use self::tlsa::TLSA;
 pub use self::txt::TXT;
+pub use self::zonemd::ZONEMD;
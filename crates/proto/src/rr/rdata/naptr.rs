@@ -7,7 +7,8 @@
 
 //! Dynamic Delegation Discovery System
 
-use std::fmt;
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::fmt;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -196,6 +197,272 @@ impl NAPTR {
     pub fn replacement(&self) -> &Name {
         &self.replacement
     }
+
+    /// The flags of the FLAGS field, in upper case
+    pub fn parsed_flags(&self) -> impl Iterator<Item = NaptrFlag> + '_ {
+        self.flags.iter().map(|flag| NaptrFlag::from(*flag))
+    }
+
+    /// Whether one of the flags ends the rewriting of the DDDS algorithm, see [`NaptrFlag::is_terminal`]
+    pub fn is_terminal(&self) -> bool {
+        self.parsed_flags().any(NaptrFlag::is_terminal)
+    }
+
+    /// The SERVICES field split into its parts, `None` if the field is empty or not UTF-8
+    pub fn parsed_services(&self) -> Option<NaptrServices<'_>> {
+        let services = core::str::from_utf8(&self.services).ok()?;
+        let mut parts = services.split('+');
+        let protocol = parts.next().filter(|protocol| !protocol.is_empty())?;
+
+        Some(NaptrServices {
+            protocol,
+            services: parts.collect(),
+        })
+    }
+
+    /// Applies the substitution expression of the REGEXP field to `input`, the string held by the
+    /// client, e.g. the E.164 number `+441632960083` of ENUM
+    ///
+    /// The first match of the POSIX Extended Regular Expression is replaced with the replacement,
+    /// in which `\1` to `\9` are the capture groups of the match, see
+    /// [RFC 3402, section 3.2](https://tools.ietf.org/html/rfc3402#section-3.2). The delimiter is
+    /// escaped in the expression as `\` followed by the delimiter.
+    ///
+    /// Returns `None` if the REGEXP field is empty, the REPLACEMENT field is used instead, or if
+    /// the expression does not match `input`. Returns an error if the expression is malformed.
+    #[cfg(feature = "naptr-regexp")]
+    pub fn apply_regexp(&self, input: &str) -> ProtoResult<Option<String>> {
+        if self.regexp.is_empty() {
+            return Ok(None);
+        }
+
+        let expression = core::str::from_utf8(&self.regexp)
+            .map_err(|_| ProtoError::from("NAPTR regexp is not UTF-8"))?;
+        SubstitutionExpression::parse(expression)?.apply(input)
+    }
+}
+
+/// A flag of the FLAGS field of a NAPTR record
+///
+/// The flags of the URI resolution application are defined in
+/// [RFC 3404, section 4.3](https://tools.ietf.org/html/rfc3404#section-4.3), the other flags are
+/// up to the application.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum NaptrFlag {
+    /// The next lookup is for SRV records
+    S,
+    /// The next lookup is for A, AAAA or A6 records
+    A,
+    /// The output of the rule is a URI, e.g. those of ENUM
+    U,
+    /// The rest of the algorithm is specific to the protocol
+    P,
+    /// Another flag, in upper case
+    Other(u8),
+}
+
+impl NaptrFlag {
+    /// Whether the flag ends the rewriting, the flags `S`, `A` and `U` are terminal
+    pub fn is_terminal(self) -> bool {
+        matches!(self, Self::S | Self::A | Self::U)
+    }
+}
+
+impl From<u8> for NaptrFlag {
+    fn from(flag: u8) -> Self {
+        // the case of the flags is not significant
+        match flag.to_ascii_uppercase() {
+            b'S' => Self::S,
+            b'A' => Self::A,
+            b'U' => Self::U,
+            b'P' => Self::P,
+            flag => Self::Other(flag),
+        }
+    }
+}
+
+/// The Service Parameters of the SERVICES field of a NAPTR record
+///
+/// The field starts with the protocol or the application of the rule, followed by the services it
+/// provides, separated by `+`, e.g. `E2U+sip` for ENUM or `http+N2L+N2C` for the URI resolution
+/// application.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NaptrServices<'a> {
+    protocol: &'a str,
+    services: Vec<&'a str>,
+}
+
+impl<'a> NaptrServices<'a> {
+    /// The protocol or the application of the rule, e.g. `E2U` or `http`
+    pub fn protocol(&self) -> &'a str {
+        self.protocol
+    }
+
+    /// The services, e.g. `sip` or `voice:tel` for ENUM, and `N2L` or `N2C` for the URI
+    /// resolution application
+    pub fn services(&self) -> &[&'a str] {
+        &self.services
+    }
+}
+
+/// A substitution expression of the REGEXP field
+///
+/// ```text
+/// subst-expr   = delim-char  ere  delim-char  repl  delim-char  *flags
+/// delim-char   = "/" / "!" / <Any octet not in 'POS-DIGIT' or 'flags'>
+/// ere          = <POSIX Extended Regular Expression>
+/// repl         = *(string / backref)
+/// string       = *(anychar / escapeddelim)
+/// anychar      = <any character other than delim-char>
+/// escapeddelim = "\" delim-char
+/// backref      = "\" POS-DIGIT
+/// flags        = "i"
+/// POS-DIGIT    = %x31-39                 ; 0 is not an allowed backref
+/// ```
+#[cfg(feature = "naptr-regexp")]
+struct SubstitutionExpression<'a> {
+    delimiter: char,
+    ere: &'a str,
+    replacement: &'a str,
+    case_insensitive: bool,
+}
+
+#[cfg(feature = "naptr-regexp")]
+impl<'a> SubstitutionExpression<'a> {
+    fn parse(expression: &'a str) -> ProtoResult<Self> {
+        let mut chars = expression.chars();
+        let delimiter = chars
+            .next()
+            .filter(|c| !matches!(c, '1'..='9' | 'i' | '\\'))
+            .ok_or_else(|| ProtoError::from("NAPTR regexp has an invalid delimiter"))?;
+
+        // the positions of the unescaped delimiters
+        let mut delimiters = Vec::with_capacity(2);
+        let mut escaped = false;
+        for (idx, c) in expression.char_indices().skip(1) {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                c if c == delimiter => delimiters.push(idx),
+                _ => {}
+            }
+        }
+
+        let [ere_end, replacement_end] = delimiters[..] else {
+            return Err(ProtoError::from(
+                "NAPTR regexp does not have three delimiters",
+            ));
+        };
+        let case_insensitive = match &expression[replacement_end + delimiter.len_utf8()..] {
+            "" => false,
+            "i" => true,
+            _ => return Err(ProtoError::from("NAPTR regexp has invalid flags")),
+        };
+
+        Ok(Self {
+            delimiter,
+            ere: &expression[delimiter.len_utf8()..ere_end],
+            replacement: &expression[ere_end + delimiter.len_utf8()..replacement_end],
+            case_insensitive,
+        })
+    }
+
+    fn apply(&self, input: &str) -> ProtoResult<Option<String>> {
+        let regex = regex::RegexBuilder::new(&self.translate_ere())
+            .case_insensitive(self.case_insensitive)
+            .build()
+            .map_err(|e| ProtoError::from(format!("NAPTR regexp is invalid: {e}")))?;
+        let Some(captures) = regex.captures(input) else {
+            return Ok(None);
+        };
+
+        let mut replacement = String::with_capacity(self.replacement.len());
+        let mut chars = self.replacement.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                replacement.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some(digit @ '1'..='9') => {
+                    let group = digit as usize - '0' as usize;
+                    replacement.push_str(captures.get(group).map_or("", |m| m.as_str()));
+                }
+                Some(c) if c == self.delimiter || c == '\\' => replacement.push(c),
+                Some(c) => {
+                    replacement.push('\\');
+                    replacement.push(c);
+                }
+                None => replacement.push('\\'),
+            }
+        }
+
+        let matched = captures.get(0).expect("the match is the group 0");
+        Ok(Some(format!(
+            "{}{replacement}{}",
+            &input[..matched.start()],
+            &input[matched.end()..]
+        )))
+    }
+
+    /// Translates the POSIX ERE into the syntax of the `regex` crate
+    ///
+    /// The escaped delimiters are unescaped, and the backslashes and the characters with a special
+    /// meaning within the classes of the `regex` crate are escaped within the bracket
+    /// expressions, in which they are literals in POSIX.
+    fn translate_ere(&self) -> String {
+        let mut translated = String::with_capacity(self.ere.len());
+        let mut chars = self.ere.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some(c) if c == self.delimiter => {
+                        translated.push_str(&regex::escape(c.encode_utf8(&mut [0; 4])))
+                    }
+                    Some(c) => {
+                        translated.push('\\');
+                        translated.push(c);
+                    }
+                    None => translated.push('\\'),
+                },
+                '[' => {
+                    translated.push('[');
+                    if chars.next_if_eq(&'^').is_some() {
+                        translated.push('^');
+                    }
+                    // a leading `]` is a literal
+                    if chars.next_if_eq(&']').is_some() {
+                        translated.push_str("\\]");
+                    }
+
+                    while let Some(c) = chars.next() {
+                        match c {
+                            ']' => break,
+                            // character classes, e.g. `[:digit:]`
+                            '[' if chars.next_if_eq(&':').is_some() => {
+                                translated.push_str("[:");
+                                for c in chars.by_ref() {
+                                    translated.push(c);
+                                    if c == ']' {
+                                        break;
+                                    }
+                                }
+                                continue;
+                            }
+                            '\\' | '[' | '&' | '~' => translated.push('\\'),
+                            _ => {}
+                        }
+                        translated.push(c);
+                    }
+                    translated.push(']');
+                }
+                c => translated.push(c),
+            }
+        }
+
+        translated
+    }
 }
 
 /// verifies that the flags are valid
@@ -301,7 +568,7 @@ mod tests {
     use super::*;
     #[test]
     fn test() {
-        use std::str::FromStr;
+        use core::str::FromStr;
 
         let rdata = NAPTR::new(
             8,
@@ -326,7 +593,7 @@ mod tests {
 
     #[test]
     fn test_bad_data() {
-        use std::str::FromStr;
+        use core::str::FromStr;
 
         let rdata = NAPTR::new(
             8,
@@ -351,4 +618,94 @@ mod tests {
             "should have failed decoding with bad flag data"
         );
     }
+
+    fn naptr(flags: &str, services: &str, regexp: &str) -> NAPTR {
+        NAPTR::new(
+            100,
+            10,
+            flags.as_bytes().into(),
+            services.as_bytes().into(),
+            regexp.as_bytes().into(),
+            Name::root(),
+        )
+    }
+
+    #[test]
+    fn test_parsed_flags_and_services() {
+        let rdata = naptr("u", "E2U+sip", "");
+        assert_eq!(rdata.parsed_flags().collect::<Vec<_>>(), [NaptrFlag::U]);
+        assert!(rdata.is_terminal());
+
+        let services = rdata.parsed_services().unwrap();
+        assert_eq!(services.protocol(), "E2U");
+        assert_eq!(services.services(), ["sip"]);
+
+        let rdata = naptr("Px", "http+N2L+N2C", "");
+        assert_eq!(
+            rdata.parsed_flags().collect::<Vec<_>>(),
+            [NaptrFlag::P, NaptrFlag::Other(b'X')]
+        );
+        assert!(!rdata.is_terminal());
+        let services = rdata.parsed_services().unwrap();
+        assert_eq!(services.protocol(), "http");
+        assert_eq!(services.services(), ["N2L", "N2C"]);
+
+        assert!(naptr("", "", "").parsed_services().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "naptr-regexp")]
+    fn test_apply_enum_regexp() {
+        // the ENUM rule of RFC 6116, for the phone number +44 1632 960083
+        let rdata = naptr("u", "E2U+sip", "!^\\+44([0-9]*)$!sip:0\\1@example.com!");
+        assert_eq!(
+            rdata.apply_regexp("+441632960083").unwrap().as_deref(),
+            Some("sip:01632960083@example.com")
+        );
+        assert_eq!(rdata.apply_regexp("+331632960083").unwrap(), None);
+
+        let rdata = naptr("u", "E2U+sip", "!^.*$!sip:info@example.com!");
+        assert_eq!(
+            rdata.apply_regexp("+441632960083").unwrap().as_deref(),
+            Some("sip:info@example.com")
+        );
+
+        // the REPLACEMENT field is used instead of an empty REGEXP field
+        assert_eq!(naptr("s", "SIP+D2U", "").apply_regexp("x").unwrap(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "naptr-regexp")]
+    fn test_regexp_escaping() {
+        let apply = |regexp, input| naptr("u", "E2U+sip", regexp).apply_regexp(input);
+
+        // escaped delimiters, in the expression and in the replacement
+        assert_eq!(
+            apply("!^(.*)\\!(.*)$!\\2\\!\\1!", "a!b")
+                .unwrap()
+                .as_deref(),
+            Some("b!a")
+        );
+        // the delimiter is a special character of the expression
+        assert_eq!(apply("|a\\|b|c|", "xa|by").unwrap().as_deref(), Some("xcy"));
+        // backslashes are literals in bracket expressions, as are leading `]`
+        assert_eq!(apply("/[\\]/+/", "a\\b").unwrap().as_deref(), Some("a+b"));
+        assert_eq!(apply("/[]a]/-/", "b]").unwrap().as_deref(), Some("b-"));
+        assert_eq!(
+            apply("/^[[:digit:]]+$/n/", "123").unwrap().as_deref(),
+            Some("n")
+        );
+        // case insensitive matching, and captures which did not match
+        assert_eq!(
+            apply("!^SIP(x)?:(.*)$!\\1\\2!i", "sip:alice")
+                .unwrap()
+                .as_deref(),
+            Some("alice")
+        );
+
+        assert!(apply("!a!b", "a").is_err());
+        assert!(apply("!a!b!x", "a").is_err());
+        assert!(apply("1a1b1", "a").is_err());
+        assert!(apply("!(!b!", "a").is_err());
+    }
 }
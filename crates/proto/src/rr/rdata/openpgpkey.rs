@@ -6,7 +6,8 @@
 // copied, modified, or distributed except according to those terms.
 
 //! OPENPGPKEY records for OpenPGP public keys
-use std::fmt;
+use alloc::vec::Vec;
+use core::fmt;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -105,4 +106,28 @@ impl fmt::Display for OPENPGPKEY {
     }
 }
 
-// TODO test
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn test_encode_decode() {
+        // the start of an OpenPGP public key packet, the bytes are not interpreted
+        let rdata = OPENPGPKEY::new(vec![0x99, 0x01, 0x0d, 0x04, 0x5a, 0x6b, 0x7c, 0x8d]);
+
+        let mut bytes = Vec::new();
+        let mut encoder = BinEncoder::new(&mut bytes);
+        rdata.emit(&mut encoder).expect("failed to emit openpgpkey");
+        let bytes = encoder.into_bytes();
+        assert_eq!(bytes, rdata.public_key());
+
+        let mut decoder = BinDecoder::new(bytes);
+        let read_rdata = OPENPGPKEY::read_data(&mut decoder, Restrict::new(bytes.len() as u16))
+            .expect("failed to read openpgpkey");
+        assert_eq!(read_rdata, rdata);
+        assert_eq!(read_rdata.public_key(), rdata.public_key());
+        assert_eq!(read_rdata.to_string(), "mQENBFprfI0=");
+    }
+}
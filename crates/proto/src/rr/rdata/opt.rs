@@ -8,9 +8,10 @@
 //! option record for passing protocol options between the client and server
 #![allow(clippy::use_self)]
 
-use std::fmt;
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
-use std::str::FromStr;
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use core::str::FromStr;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -19,6 +20,8 @@ use tracing::warn;
 
 use crate::{
     error::{ProtoError, ProtoErrorKind, ProtoResult},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    op::ExtendedDnsError,
     rr::{RData, RecordData, RecordDataDecodable, RecordType},
     serialize::binary::{BinDecodable, BinDecoder, BinEncodable, BinEncoder, Restrict},
 };
@@ -407,6 +410,9 @@ pub enum EdnsCode {
     /// [RFC 7901, CHAIN Query Requests in DNS, Optional](https://tools.ietf.org/html/rfc7901)
     Chain,
 
+    /// [RFC 8914, Extended DNS Errors](https://tools.ietf.org/html/rfc8914)
+    ExtendedDnsError,
+
     /// Unknown, used to deal with unknown or unsupported codes
     Unknown(u16),
 }
@@ -429,6 +435,7 @@ impl From<u16> for EdnsCode {
             11 => Self::Keepalive,
             12 => Self::Padding,
             13 => Self::Chain,
+            15 => Self::ExtendedDnsError,
             _ => Self::Unknown(value),
         }
     }
@@ -451,6 +458,7 @@ impl From<EdnsCode> for u16 {
             EdnsCode::Keepalive => 11,
             EdnsCode::Padding => 12,
             EdnsCode::Chain => 13,
+            EdnsCode::ExtendedDnsError => 15,
             EdnsCode::Unknown(value) => value,
         }
     }
@@ -480,6 +488,24 @@ pub enum EdnsOption {
     /// [RFC 7871, Client Subnet, Optional](https://tools.ietf.org/html/rfc7871)
     Subnet(ClientSubnet),
 
+    /// [RFC 7873, DNS Cookies](https://tools.ietf.org/html/rfc7873)
+    Cookie(Cookie),
+
+    /// [RFC 7828, edns-tcp-keepalive](https://tools.ietf.org/html/rfc7828)
+    ///
+    /// The idle timeout of the TCP connection in units of 100 milliseconds, which is sent by
+    /// servers. It is `None` in queries, which signal the support of the option.
+    TcpKeepalive(Option<u16>),
+
+    /// [RFC 7830, The EDNS(0) Padding Option](https://tools.ietf.org/html/rfc7830)
+    ///
+    /// The number of padding octets, which are emitted as zeros. The content of received padding
+    /// is ignored.
+    Padding(u16),
+
+    /// [RFC 8914, Extended DNS Errors](https://tools.ietf.org/html/rfc8914)
+    ExtendedDnsError(ExtendedDnsError),
+
     /// Unknown, used to deal with unknown or unsupported codes
     Unknown(u16, Vec<u8>),
 }
@@ -493,6 +519,10 @@ impl EdnsOption {
             | EdnsOption::DHU(algorithms)
             | EdnsOption::N3U(algorithms) => algorithms.len(),
             EdnsOption::Subnet(subnet) => subnet.len(),
+            EdnsOption::Cookie(cookie) => cookie.len(),
+            EdnsOption::TcpKeepalive(timeout) => timeout.map_or(0, |_| 2),
+            EdnsOption::Padding(len) => *len,
+            EdnsOption::ExtendedDnsError(ede) => ede.len(),
             EdnsOption::Unknown(_, data) => data.len() as u16, // TODO: should we verify?
         }
     }
@@ -505,6 +535,10 @@ impl EdnsOption {
             | EdnsOption::DHU(algorithms)
             | EdnsOption::N3U(algorithms) => algorithms.is_empty(),
             EdnsOption::Subnet(subnet) => subnet.is_empty(),
+            EdnsOption::Cookie(cookie) => cookie.is_empty(),
+            EdnsOption::TcpKeepalive(timeout) => timeout.is_none(),
+            EdnsOption::Padding(len) => *len == 0,
+            EdnsOption::ExtendedDnsError(ede) => ede.is_empty(),
             EdnsOption::Unknown(_, data) => data.is_empty(),
         }
     }
@@ -518,6 +552,13 @@ impl BinEncodable for EdnsOption {
             | EdnsOption::DHU(algorithms)
             | EdnsOption::N3U(algorithms) => algorithms.emit(encoder),
             EdnsOption::Subnet(subnet) => subnet.emit(encoder),
+            EdnsOption::Cookie(cookie) => cookie.emit(encoder),
+            EdnsOption::TcpKeepalive(timeout) => match timeout {
+                Some(timeout) => encoder.emit_u16(*timeout),
+                None => Ok(()),
+            },
+            EdnsOption::Padding(len) => encoder.emit_vec(&vec![0; usize::from(*len)]),
+            EdnsOption::ExtendedDnsError(ede) => ede.emit(encoder),
             EdnsOption::Unknown(_, data) => encoder.emit_vec(data), // gah, clone needed or make a crazy api.
         }
     }
@@ -537,6 +578,14 @@ impl<'a> TryFrom<(EdnsCode, &'a [u8])> for EdnsOption {
             #[cfg(feature = "dnssec-ring")]
             EdnsCode::N3U => Self::N3U(value.1.into()),
             EdnsCode::Subnet => Self::Subnet(value.1.try_into()?),
+            EdnsCode::Cookie => Self::Cookie(value.1.try_into()?),
+            EdnsCode::Keepalive => Self::TcpKeepalive(match value.1 {
+                [] => None,
+                [high, low] => Some(u16::from_be_bytes([*high, *low])),
+                _ => return Err("invalid length of edns-tcp-keepalive option".into()),
+            }),
+            EdnsCode::Padding => Self::Padding(value.1.len() as u16),
+            EdnsCode::ExtendedDnsError => Self::ExtendedDnsError(value.1.try_into()?),
             _ => Self::Unknown(value.0.into(), value.1.to_vec()),
         })
     }
@@ -552,6 +601,12 @@ impl<'a> TryFrom<&'a EdnsOption> for Vec<u8> {
             | EdnsOption::DHU(algorithms)
             | EdnsOption::N3U(algorithms) => algorithms.into(),
             EdnsOption::Subnet(subnet) => subnet.try_into()?,
+            EdnsOption::Cookie(cookie) => cookie.try_into()?,
+            EdnsOption::TcpKeepalive(timeout) => timeout
+                .map(|timeout| timeout.to_be_bytes().to_vec())
+                .unwrap_or_default(),
+            EdnsOption::Padding(len) => vec![0; usize::from(*len)],
+            EdnsOption::ExtendedDnsError(ede) => ede.try_into()?,
             EdnsOption::Unknown(_, data) => data.clone(), // gah, clone needed or make a crazy api.
         })
     }
@@ -567,6 +622,10 @@ impl<'a> From<&'a EdnsOption> for EdnsCode {
             #[cfg(feature = "dnssec-ring")]
             EdnsOption::N3U(..) => Self::N3U,
             EdnsOption::Subnet(..) => Self::Subnet,
+            EdnsOption::Cookie(..) => Self::Cookie,
+            EdnsOption::TcpKeepalive(..) => Self::Keepalive,
+            EdnsOption::Padding(..) => Self::Padding,
+            EdnsOption::ExtendedDnsError(..) => Self::ExtendedDnsError,
             EdnsOption::Unknown(code, _) => (*code).into(),
         }
     }
@@ -784,6 +843,7 @@ impl<'a> TryFrom<&'a [u8]> for ClientSubnet {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<ipnet::IpNet> for ClientSubnet {
     fn from(net: ipnet::IpNet) -> Self {
         Self {
@@ -794,6 +854,7 @@ impl From<ipnet::IpNet> for ClientSubnet {
     }
 }
 
+#[cfg(feature = "std")]
 impl FromStr for ClientSubnet {
     type Err = ipnet::AddrParseError;
 
@@ -802,6 +863,100 @@ impl FromStr for ClientSubnet {
     }
 }
 
+/// [RFC 7873, DNS Cookies](https://tools.ietf.org/html/rfc7873#section-4)
+///
+/// ```text
+/// 4.  DNS Cookies
+///
+///    The COOKIE option is
+///    either 12 (for a Client Cookie only) or 16 to 40 octets long:
+///
+///    Client Cookie
+///       The fixed-size 8-byte Client Cookie.
+///
+///    Server Cookie
+///       The variable-size Server Cookie, 8 to 32 bytes long.
+///
+///    If the COOKIE option is too short to contain a Client Cookie, is
+///    longer than the maximum valid COOKIE option (40 bytes), or is 9 to 15
+///    bytes in length, a FORMERR is generated.
+/// ```
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialOrd, PartialEq, Eq, Clone, Hash)]
+pub struct Cookie {
+    client: [u8; 8],
+    server: Option<Vec<u8>>,
+}
+
+impl Cookie {
+    /// Construct a new cookie option for `client` and, if it is known, for the `server` cookie,
+    /// which must be 8 to 32 bytes long
+    pub fn new(client: [u8; 8], server: Option<Vec<u8>>) -> Self {
+        Self { client, server }
+    }
+
+    /// Returns the length in bytes of the EdnsOption
+    pub fn len(&self) -> u16 {
+        8 + self.server.as_ref().map_or(0, |server| server.len() as u16)
+    }
+
+    /// Returns `true` if the length in bytes of the Cookie is 0
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// returns the client cookie
+    pub fn client(&self) -> &[u8; 8] {
+        &self.client
+    }
+
+    /// returns the server cookie, which is absent in the first query to a server
+    pub fn server(&self) -> Option<&[u8]> {
+        self.server.as_deref()
+    }
+}
+
+impl BinEncodable for Cookie {
+    fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
+        encoder.emit_vec(&self.client)?;
+        match &self.server {
+            Some(server) if (8..=32).contains(&server.len()) => encoder.emit_vec(server),
+            Some(_) => Err(ProtoErrorKind::Message("Invalid server cookie length").into()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Cookie> for Vec<u8> {
+    type Error = ProtoError;
+
+    fn try_from(value: &'a Cookie) -> Result<Self, Self::Error> {
+        let mut bytes = Self::with_capacity(value.len() as usize);
+        let mut encoder = BinEncoder::new(&mut bytes);
+        value.emit(&mut encoder)?;
+        Ok(bytes)
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for Cookie {
+    type Error = ProtoError;
+
+    fn try_from(value: &'a [u8]) -> Result<Self, Self::Error> {
+        if value.len() != 8 && !(16..=40).contains(&value.len()) {
+            return Err(ProtoErrorKind::Message("Invalid cookie length").into());
+        }
+
+        let (client, server) = value.split_at(8);
+        let mut cookie = Self::new([0; 8], None);
+        cookie.client.copy_from_slice(client);
+        if !server.is_empty() {
+            cookie.server = Some(server.to_vec());
+        }
+        Ok(cookie)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #![allow(clippy::dbg_macro, clippy::print_stdout)]
@@ -828,6 +983,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn test_read_empty_option_at_end_of_opt() {
         let bytes: Vec<u8> = vec![
             0x00, 0x0a, 0x00, 0x08, 0x0b, 0x64, 0xb4, 0xdc, 0xd7, 0xb0, 0xcc, 0x8f, 0x00, 0x08,
@@ -850,9 +1006,12 @@ mod tests {
             ),
             (
                 EdnsCode::Cookie,
-                EdnsOption::Unknown(10, vec![0x0b, 0x64, 0xb4, 0xdc, 0xd7, 0xb0, 0xcc, 0x8f]),
+                EdnsOption::Cookie(Cookie::new(
+                    [0x0b, 0x64, 0xb4, 0xdc, 0xd7, 0xb0, 0xcc, 0x8f],
+                    None,
+                )),
             ),
-            (EdnsCode::Keepalive, EdnsOption::Unknown(11, vec![])),
+            (EdnsCode::Keepalive, EdnsOption::TcpKeepalive(None)),
         ];
         let options = OPT::new(options);
         assert_eq!(opt, options);
@@ -876,18 +1035,12 @@ mod tests {
         let opt = read_rdata.unwrap();
         let options = vec![
             (
-                EdnsCode::Unknown(15u16),
-                EdnsOption::Unknown(15u16, vec![0x00, 0x06]),
+                EdnsCode::ExtendedDnsError,
+                EdnsOption::ExtendedDnsError(ExtendedDnsError::new(6, "").unwrap()),
             ),
             (
-                EdnsCode::Unknown(15u16),
-                EdnsOption::Unknown(
-                    15u16,
-                    vec![
-                        0x00, 0x09, 0x55, 0x6E, 0x6B, 0x6E, 0x6F, 0x77, 0x6E, 0x20, 0x65, 0x72,
-                        0x72, 0x6F, 0x72,
-                    ],
-                ),
+                EdnsCode::ExtendedDnsError,
+                EdnsOption::ExtendedDnsError(ExtendedDnsError::new(9, "Unknown error").unwrap()),
             ),
         ];
         let options = OPT::new(options);
@@ -895,6 +1048,83 @@ mod tests {
     }
 
     #[test]
+    fn test_tcp_keepalive() {
+        for (timeout, expected_bytes) in [
+            (None, vec![0x00, 0x0b, 0x00, 0x00]),
+            (Some(1200), vec![0x00, 0x0b, 0x00, 0x02, 0x04, 0xb0]),
+        ] {
+            let mut rdata = OPT::default();
+            rdata.insert(EdnsOption::TcpKeepalive(timeout));
+
+            let mut bytes = Vec::new();
+            let mut encoder = BinEncoder::new(&mut bytes);
+            rdata.emit(&mut encoder).unwrap();
+            let bytes = encoder.into_bytes();
+            assert_eq!(bytes, &expected_bytes);
+
+            let mut decoder = BinDecoder::new(bytes);
+            let read_rdata = OPT::read_data(&mut decoder, Restrict::new(bytes.len() as u16));
+            assert_eq!(read_rdata.unwrap(), rdata);
+        }
+
+        let bytes = [0x00, 0x0b, 0x00, 0x01, 0x04];
+        let mut decoder = BinDecoder::new(&bytes);
+        assert!(OPT::read_data(&mut decoder, Restrict::new(bytes.len() as u16)).is_err());
+    }
+
+    #[test]
+    fn test_cookie() {
+        let client = [1, 2, 3, 4, 5, 6, 7, 8];
+        for (server, len) in [(None, 8), (Some(vec![0xaa; 16]), 24)] {
+            let mut rdata = OPT::default();
+            rdata.insert(EdnsOption::Cookie(Cookie::new(client, server)));
+
+            let mut bytes = Vec::new();
+            let mut encoder = BinEncoder::new(&mut bytes);
+            rdata.emit(&mut encoder).unwrap();
+            let bytes = encoder.into_bytes();
+            assert_eq!(&bytes[..4], &[0x00, 0x0a, 0x00, len]);
+            assert_eq!(&bytes[4..12], &client);
+
+            let mut decoder = BinDecoder::new(bytes);
+            let read_rdata = OPT::read_data(&mut decoder, Restrict::new(bytes.len() as u16));
+            assert_eq!(read_rdata.unwrap(), rdata);
+        }
+
+        // too short, or a server cookie of less than 8 bytes
+        for len in [7, 12, 41] {
+            assert!(Cookie::try_from(&vec![0; len][..]).is_err());
+        }
+        let mut bytes = Vec::new();
+        let mut encoder = BinEncoder::new(&mut bytes);
+        assert!(Cookie::new(client, Some(vec![0; 4]))
+            .emit(&mut encoder)
+            .is_err());
+    }
+
+    #[test]
+    fn test_padding() {
+        let mut rdata = OPT::default();
+        rdata.insert(EdnsOption::Padding(3));
+
+        let mut bytes = Vec::new();
+        let mut encoder = BinEncoder::new(&mut bytes);
+        rdata.emit(&mut encoder).unwrap();
+        let bytes = encoder.into_bytes();
+        assert_eq!(bytes, &[0x00, 0x0c, 0x00, 0x03, 0x00, 0x00, 0x00]);
+
+        // the content of the padding is not checked by receivers
+        let bytes = [0x00, 0x0c, 0x00, 0x02, 0xde, 0xad];
+        let mut decoder = BinDecoder::new(&bytes);
+        let read_rdata = OPT::read_data(&mut decoder, Restrict::new(bytes.len() as u16)).unwrap();
+        assert_eq!(
+            read_rdata.get(EdnsCode::Padding),
+            Some(&EdnsOption::Padding(2))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
     fn test_write_client_subnet() {
         let expected_bytes: Vec<u8> = vec![0x00, 0x01, 0x18, 0x00, 0xac, 0x01, 0x01];
         let ecs: ClientSubnet = "172.1.1.1/24".parse().unwrap();
@@ -904,6 +1134,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn test_read_client_subnet() {
         let bytes: Vec<u8> = vec![0x00, 0x01, 0x18, 0x00, 0xac, 0x01, 0x01];
         let ecs = ClientSubnet::try_from(bytes.as_slice()).unwrap();
@@ -8,14 +8,20 @@
 //! SSHFP records for SSH public key fingerprints
 #![allow(clippy::use_self)]
 
-use std::fmt;
+use alloc::vec::Vec;
+use core::fmt;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+use data_encoding::HEXLOWER;
+#[cfg(feature = "std")]
 use data_encoding::{Encoding, Specification};
+#[cfg(feature = "std")]
 use once_cell::sync::Lazy;
 
+#[cfg(feature = "dnssec-ring")]
+use crate::dnssec::{ring::Digest, DigestType};
 use crate::{
     error::{ProtoError, ProtoResult},
     rr::{RData, RecordData, RecordDataDecodable, RecordType},
@@ -23,6 +29,7 @@ use crate::{
 };
 
 /// HEX formatting specific to TLSA and SSHFP encodings
+#[cfg(feature = "std")]
 pub static HEX: Lazy<Encoding> = Lazy::new(|| {
     let mut spec = Specification::new();
     spec.symbols.push_str("0123456789abcdef");
@@ -100,6 +107,27 @@ impl SSHFP {
     pub fn fingerprint(&self) -> &[u8] {
         &self.fingerprint
     }
+
+    /// Checks if this is the fingerprint of the SSH `public_key`
+    ///
+    /// The key is in the SSH wire format, e.g. the base64 decoded key of a `.pub` file. Returns
+    /// `false` if the key is not of the algorithm of this record, or if the algorithm or the
+    /// fingerprint type is not supported.
+    #[cfg(feature = "dnssec-ring")]
+    pub fn matches(&self, public_key: &[u8]) -> bool {
+        let digest_type = match self.fingerprint_type {
+            FingerprintType::SHA1 => DigestType::SHA1,
+            FingerprintType::SHA256 => DigestType::SHA256,
+            FingerprintType::Reserved | FingerprintType::Unassigned(_) => return false,
+        };
+
+        match Algorithm::from_public_key(public_key) {
+            Some(algorithm) if algorithm == self.algorithm => {
+                Digest::new(public_key, digest_type).as_ref() == self.fingerprint
+            }
+            _ => false,
+        }
+    }
 }
 
 /// ```text
@@ -146,6 +174,27 @@ pub enum Algorithm {
     Unassigned(u8),
 }
 
+impl Algorithm {
+    /// The algorithm of an SSH public key in the wire format, `None` if it is not known
+    ///
+    /// The key starts with the name of its algorithm, e.g. `ssh-ed25519`, see
+    /// [RFC 4253, section 6.6](https://tools.ietf.org/html/rfc4253#section-6.6).
+    pub fn from_public_key(public_key: &[u8]) -> Option<Self> {
+        let len = public_key.get(..4)?;
+        let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+        let name = public_key.get(4..4usize.checked_add(len)?)?;
+
+        match name {
+            b"ssh-rsa" => Some(Self::RSA),
+            b"ssh-dss" => Some(Self::DSA),
+            b"ssh-ed25519" => Some(Self::Ed25519),
+            b"ssh-ed448" => Some(Self::Ed448),
+            _ if name.starts_with(b"ecdsa-sha2-") => Some(Self::ECDSA),
+            _ => None,
+        }
+    }
+}
+
 impl From<u8> for Algorithm {
     fn from(alg: u8) -> Self {
         match alg {
@@ -299,7 +348,7 @@ impl fmt::Display for SSHFP {
             "{algorithm} {ty} {fingerprint}",
             algorithm = u8::from(self.algorithm),
             ty = u8::from(self.fingerprint_type),
-            fingerprint = HEX.encode(&self.fingerprint),
+            fingerprint = HEXLOWER.encode(&self.fingerprint),
         )
     }
 }
@@ -319,14 +368,14 @@ mod tests {
         assert_eq!(Algorithm::Unassigned(17), 17.into());
         assert_eq!(Algorithm::Unassigned(42), 42.into());
 
-        assert_eq!(0u8, Algorithm::Reserved.into());
-        assert_eq!(1u8, Algorithm::RSA.into());
-        assert_eq!(2u8, Algorithm::DSA.into());
-        assert_eq!(3u8, Algorithm::ECDSA.into());
-        assert_eq!(4u8, Algorithm::Ed25519.into());
-        assert_eq!(6u8, Algorithm::Ed448.into());
-        assert_eq!(17u8, Algorithm::Unassigned(17).into());
-        assert_eq!(42u8, Algorithm::Unassigned(42).into());
+        assert_eq!(0, u8::from(Algorithm::Reserved));
+        assert_eq!(1, u8::from(Algorithm::RSA));
+        assert_eq!(2, u8::from(Algorithm::DSA));
+        assert_eq!(3, u8::from(Algorithm::ECDSA));
+        assert_eq!(4, u8::from(Algorithm::Ed25519));
+        assert_eq!(6, u8::from(Algorithm::Ed448));
+        assert_eq!(17, u8::from(Algorithm::Unassigned(17)));
+        assert_eq!(42, u8::from(Algorithm::Unassigned(42)));
     }
 
     #[test]
@@ -337,11 +386,11 @@ mod tests {
         assert_eq!(FingerprintType::Unassigned(12), 12.into());
         assert_eq!(FingerprintType::Unassigned(89), 89.into());
 
-        assert_eq!(0u8, FingerprintType::Reserved.into());
-        assert_eq!(1u8, FingerprintType::SHA1.into());
-        assert_eq!(2u8, FingerprintType::SHA256.into());
-        assert_eq!(12u8, FingerprintType::Unassigned(12).into());
-        assert_eq!(89u8, FingerprintType::Unassigned(89).into());
+        assert_eq!(0, u8::from(FingerprintType::Reserved));
+        assert_eq!(1, u8::from(FingerprintType::SHA1));
+        assert_eq!(2, u8::from(FingerprintType::SHA256));
+        assert_eq!(12, u8::from(FingerprintType::Unassigned(12)));
+        assert_eq!(89, u8::from(FingerprintType::Unassigned(89)));
     }
 
     fn test_encode_decode(rdata: SSHFP, result: &[u8]) {
@@ -8,7 +8,8 @@
 //! SVCB records, see [RFC 9460 SVCB and HTTPS Resource Records, Nov 2023](https://datatracker.ietf.org/doc/html/rfc9460)
 #![allow(clippy::use_self)]
 
-use std::{
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::{
     cmp::{Ord, Ordering, PartialOrd},
     convert::TryFrom,
     fmt,
@@ -21,10 +22,8 @@ use enum_as_inner::EnumAsInner;
 
 use crate::{
     error::{ProtoError, ProtoErrorKind, ProtoResult},
-    rr::{
-        rdata::{A, AAAA},
-        Name, RData, RecordData, RecordDataDecodable, RecordType,
-    },
+    net::{Ipv4Addr, Ipv6Addr},
+    rr::{Name, RData, RecordData, RecordDataDecodable, RecordType},
     serialize::binary::{
         BinDecodable, BinDecoder, BinEncodable, BinEncoder, Restrict, RestrictedMath,
     },
@@ -79,6 +78,11 @@ pub struct SVCB {
 }
 
 impl SVCB {
+    /// Returns a builder to construct a validated SVCB record, see [`SvcbBuilder`]
+    pub fn builder() -> SvcbBuilder {
+        SvcbBuilder::default()
+    }
+
     /// Create a new SVCB record from parts
     ///
     /// It is up to the caller to validate the data going into the record
@@ -156,6 +160,228 @@ impl SVCB {
     pub fn svc_params(&self) -> &[(SvcParamKey, SvcParamValue)] {
         &self.svc_params
     }
+
+    /// Returns the protocol identifiers of the `alpn` SvcParam, in the order they appear in the record
+    ///
+    /// The iterator is empty if there is no `alpn` SvcParam, which is also the case when `alpn` is
+    ///  only listed in the `mandatory` SvcParam. This does not include the default ALPN of the
+    ///  protocol mapping, which the client must add unless `no-default-alpn` is set, see [`Alpn`].
+    pub fn alpns(&self) -> impl Iterator<Item = &[u8]> {
+        self.svc_params
+            .iter()
+            .filter_map(|(_, value)| value.as_alpn())
+            .flat_map(|alpn| alpn.0.iter().map(|id| id.as_bytes()))
+    }
+
+    /// Returns the addresses of the `ipv4hint` SvcParam, empty if there are none
+    ///
+    /// Clients may use these to connect without waiting for an A lookup of the TargetName.
+    pub fn ipv4_hint(&self) -> &[Ipv4Addr] {
+        self.svc_params
+            .iter()
+            .find_map(|(_, value)| value.as_ipv4_hint())
+            .map_or(&[], |hint| &hint.0)
+    }
+
+    /// Returns the addresses of the `ipv6hint` SvcParam, empty if there are none
+    ///
+    /// Clients may use these to connect without waiting for an AAAA lookup of the TargetName.
+    pub fn ipv6_hint(&self) -> &[Ipv6Addr] {
+        self.svc_params
+            .iter()
+            .find_map(|(_, value)| value.as_ipv6_hint())
+            .map_or(&[], |hint| &hint.0)
+    }
+}
+
+/// A builder for SVCB (and HTTPS) records
+///
+/// SvcParams may be set in any order, they are emitted in increasing numeric order of their keys
+///  as required by the wire format. Setting a parameter twice replaces the earlier value. Without
+///  further configuration, the record is in ServiceMode with a priority of 1 and a TargetName of
+///  `.`, i.e. the owner name of the record.
+///
+/// ```
+/// use std::net::Ipv4Addr;
+///
+/// use hickory_proto::rr::{rdata::{HTTPS, SVCB}, Name};
+///
+/// let https = HTTPS(
+///     SVCB::builder()
+///         .priority(1)
+///         .target_name(Name::from_ascii("svc.example.net.").unwrap())
+///         .alpn(&[b"h2", b"h3"])
+///         .port(8443)
+///         .ipv4_hint(&[Ipv4Addr::new(192, 0, 2, 1)])
+///         .build()
+///         .unwrap(),
+/// );
+///
+/// assert_eq!(https.alpns().collect::<Vec<_>>(), [b"h2", b"h3"]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct SvcbBuilder {
+    svc_priority: u16,
+    target_name: Name,
+    alpn: Option<Vec<Vec<u8>>>,
+    svc_params: BTreeMap<SvcParamKey, SvcParamValue>,
+}
+
+impl SvcbBuilder {
+    /// Sets the SvcPriority, 0 selects AliasMode which does not allow any SvcParams
+    pub fn priority(mut self, svc_priority: u16) -> Self {
+        self.svc_priority = svc_priority;
+        self
+    }
+
+    /// Sets the TargetName
+    pub fn target_name(mut self, target_name: Name) -> Self {
+        self.target_name = target_name;
+        self
+    }
+
+    /// Sets the `mandatory` SvcParam, all listed keys must be set on the builder as well
+    pub fn mandatory(mut self, keys: &[SvcParamKey]) -> Self {
+        let mut keys = keys.to_vec();
+        keys.sort();
+        keys.dedup();
+
+        self.svc_params.insert(
+            SvcParamKey::Mandatory,
+            SvcParamValue::Mandatory(Mandatory(keys)),
+        );
+        self
+    }
+
+    /// Sets the `alpn` SvcParam to the protocol identifiers, e.g. `b"h2"`
+    pub fn alpn(mut self, alpn_ids: &[&[u8]]) -> Self {
+        self.alpn = Some(alpn_ids.iter().map(|id| id.to_vec()).collect());
+        self
+    }
+
+    /// Sets the `no-default-alpn` SvcParam, which requires `alpn` to be set as well
+    pub fn no_default_alpn(mut self) -> Self {
+        self.svc_params
+            .insert(SvcParamKey::NoDefaultAlpn, SvcParamValue::NoDefaultAlpn);
+        self
+    }
+
+    /// Sets the `port` SvcParam
+    pub fn port(mut self, port: u16) -> Self {
+        self.svc_params
+            .insert(SvcParamKey::Port, SvcParamValue::Port(port));
+        self
+    }
+
+    /// Sets the `ipv4hint` SvcParam
+    pub fn ipv4_hint(mut self, addrs: &[Ipv4Addr]) -> Self {
+        self.svc_params.insert(
+            SvcParamKey::Ipv4Hint,
+            SvcParamValue::Ipv4Hint(IpHint(addrs.to_vec())),
+        );
+        self
+    }
+
+    /// Sets the `ipv6hint` SvcParam
+    pub fn ipv6_hint(mut self, addrs: &[Ipv6Addr]) -> Self {
+        self.svc_params.insert(
+            SvcParamKey::Ipv6Hint,
+            SvcParamValue::Ipv6Hint(IpHint(addrs.to_vec())),
+        );
+        self
+    }
+
+    /// Sets the `ech` SvcParam to the ECHConfigList, including its length prefix
+    pub fn ech_config_list(mut self, ech_config_list: Vec<u8>) -> Self {
+        self.svc_params.insert(
+            SvcParamKey::EchConfigList,
+            SvcParamValue::EchConfigList(EchConfigList(ech_config_list)),
+        );
+        self
+    }
+
+    /// Validates the SvcParams and constructs the record
+    pub fn build(self) -> ProtoResult<SVCB> {
+        let Self {
+            svc_priority,
+            target_name,
+            alpn,
+            mut svc_params,
+        } = self;
+
+        if let Some(alpn) = alpn {
+            if alpn.is_empty() || alpn.iter().any(|id| id.is_empty()) {
+                return Err(ProtoError::from(
+                    "Alpn expects at least one non-empty value",
+                ));
+            }
+
+            let alpn = alpn
+                .into_iter()
+                .map(String::from_utf8)
+                .collect::<Result<Vec<_>, _>>()?;
+            svc_params.insert(SvcParamKey::Alpn, SvcParamValue::Alpn(Alpn(alpn)));
+        }
+
+        if svc_priority == 0 && !svc_params.is_empty() {
+            return Err(ProtoError::from(
+                "SVCB in AliasMode must not have SvcParams",
+            ));
+        }
+
+        if svc_params.contains_key(&SvcParamKey::NoDefaultAlpn)
+            && !svc_params.contains_key(&SvcParamKey::Alpn)
+        {
+            return Err(ProtoError::from("no-default-alpn requires alpn to be set"));
+        }
+
+        for (key, value) in svc_params.iter() {
+            match value {
+                SvcParamValue::Mandatory(Mandatory(keys)) => {
+                    if keys.is_empty() {
+                        return Err(ProtoError::from("Mandatory expects at least one value"));
+                    }
+
+                    if let Some(key) = keys
+                        .iter()
+                        .find(|k| **k == SvcParamKey::Mandatory || !svc_params.contains_key(k))
+                    {
+                        return Err(ProtoError::from(format!(
+                            "mandatory key {key} is not a SvcParam of the record"
+                        )));
+                    }
+                }
+                SvcParamValue::Ipv4Hint(IpHint(addrs)) if addrs.is_empty() => {
+                    return Err(ProtoError::from(format!(
+                        "{key} expects at least one address"
+                    )));
+                }
+                SvcParamValue::Ipv6Hint(IpHint(addrs)) if addrs.is_empty() => {
+                    return Err(ProtoError::from(format!(
+                        "{key} expects at least one address"
+                    )));
+                }
+                _ => (),
+            }
+        }
+
+        Ok(SVCB::new(
+            svc_priority,
+            target_name,
+            svc_params.into_iter().collect(),
+        ))
+    }
+}
+
+impl Default for SvcbBuilder {
+    fn default() -> Self {
+        Self {
+            svc_priority: 1,
+            target_name: Name::root(),
+            alpn: None,
+            svc_params: BTreeMap::new(),
+        }
+    }
 }
 
 ///  [RFC 9460 SVCB and HTTPS Resource Records, Nov 2023](https://datatracker.ietf.org/doc/html/rfc9460#section-14.3.2)
@@ -295,7 +521,7 @@ impl fmt::Display for SvcParamKey {
     }
 }
 
-impl std::str::FromStr for SvcParamKey {
+impl core::str::FromStr for SvcParamKey {
     type Err = ProtoError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -410,7 +636,7 @@ pub enum SvcParamValue {
     ///   or other geo-aware features and thereby degrade client performance.
     ///
     /// see `IpHint`
-    Ipv4Hint(IpHint<A>),
+    Ipv4Hint(IpHint<Ipv4Addr>),
     /// [draft-ietf-tls-svcb-ech-01 Bootstrapping TLS Encrypted ClientHello with DNS Service Bindings, Sep 2024](https://datatracker.ietf.org/doc/html/draft-ietf-tls-svcb-ech-01)
     ///
     /// ```text
@@ -423,7 +649,7 @@ pub enum SvcParamValue {
     /// ```
     EchConfigList(EchConfigList),
     /// See `IpHint`
-    Ipv6Hint(IpHint<AAAA>),
+    Ipv6Hint(IpHint<Ipv6Addr>),
     /// Unparsed network data. Refer to documents on the associated key value
     ///
     /// This will be left as is when read off the wire, and encoded in bas64
@@ -468,9 +694,15 @@ impl SvcParamValue {
                 let port = decoder.read_u16()?.unverified(/*all values are legal ports*/);
                 Self::Port(port)
             }
-            SvcParamKey::Ipv4Hint => Self::Ipv4Hint(IpHint::<A>::read(&mut decoder)?),
+            SvcParamKey::Ipv4Hint => {
+                check_ip_hint_len(len, 4)?;
+                Self::Ipv4Hint(IpHint::<Ipv4Addr>::read(&mut decoder)?)
+            }
             SvcParamKey::EchConfigList => Self::EchConfigList(EchConfigList::read(&mut decoder)?),
-            SvcParamKey::Ipv6Hint => Self::Ipv6Hint(IpHint::<AAAA>::read(&mut decoder)?),
+            SvcParamKey::Ipv6Hint => {
+                check_ip_hint_len(len, 16)?;
+                Self::Ipv6Hint(IpHint::<Ipv6Addr>::read(&mut decoder)?)
+            }
             SvcParamKey::Key(_) | SvcParamKey::Key65535 | SvcParamKey::Unknown(_) => {
                 Self::Unknown(Unknown::read(&mut decoder)?)
             }
@@ -480,6 +712,17 @@ impl SvcParamValue {
     }
 }
 
+/// An IP hint must hold at least one address, and only complete addresses
+fn check_ip_hint_len(len: usize, addr_len: usize) -> ProtoResult<()> {
+    if len == 0 || len % addr_len != 0 {
+        return Err(ProtoError::from(format!(
+            "length of IpHint ({len}) is not a non-zero multiple of the address length ({addr_len})"
+        )));
+    }
+
+    Ok(())
+}
+
 impl BinEncodable for SvcParamValue {
     // a 2 octet field containing the length of the SvcParamValue as an
     //      integer between 0 and 65535 in network byte order (but constrained
@@ -1174,7 +1417,10 @@ impl fmt::Display for SVCB {
 
 #[cfg(test)]
 mod tests {
+    use alloc::string::ToString;
+
     use super::*;
+    use crate::rr::rdata::HTTPS;
 
     #[test]
     fn read_svcb_key() {
@@ -1269,6 +1515,171 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_alpns() {
+        // 1 . alpn=h2,h3
+        const RDATA: &[u8] = &[0, 1, 0, 0, 1, 0, 6, 2, b'h', b'2', 2, b'h', b'3'];
+
+        let mut decoder = BinDecoder::new(RDATA);
+        let https = HTTPS::read_data(&mut decoder, Restrict::new(RDATA.len() as u16)).unwrap();
+        assert_eq!(https.alpns().collect::<Vec<_>>(), [b"h2", b"h3"]);
+    }
+
+    #[test]
+    fn test_alpns_mandatory() {
+        // 1 . mandatory=alpn alpn=h3
+        const RDATA: &[u8] = &[0, 1, 0, 0, 0, 0, 2, 0, 1, 0, 1, 0, 3, 2, b'h', b'3'];
+
+        let mut decoder = BinDecoder::new(RDATA);
+        let svcb = SVCB::read_data(&mut decoder, Restrict::new(RDATA.len() as u16)).unwrap();
+        assert_eq!(svcb.alpns().collect::<Vec<_>>(), [b"h3"]);
+
+        // only listed as mandatory, the alpn SvcParam itself is missing
+        let svcb = SVCB::new(
+            1,
+            Name::root(),
+            vec![(
+                SvcParamKey::Mandatory,
+                SvcParamValue::Mandatory(Mandatory(vec![SvcParamKey::Alpn])),
+            )],
+        );
+        assert_eq!(svcb.alpns().count(), 0);
+    }
+
+    #[test]
+    fn test_alpns_empty() {
+        let svcb = SVCB::new(
+            1,
+            Name::root(),
+            vec![(SvcParamKey::Port, SvcParamValue::Port(443))],
+        );
+        assert_eq!(svcb.alpns().count(), 0);
+
+        // 1 . alpn="", an empty value is malformed
+        const RDATA: &[u8] = &[0, 1, 0, 0, 1, 0, 0];
+
+        let mut decoder = BinDecoder::new(RDATA);
+        assert!(SVCB::read_data(&mut decoder, Restrict::new(RDATA.len() as u16)).is_err());
+    }
+
+    #[test]
+    fn test_ip_hints() {
+        // 1 . ipv4hint=192.0.2.1,192.0.2.2 ipv6hint=2001:db8::1
+        const RDATA: &[u8] = &[
+            0, 1, 0, 0, 4, 0, 8, 192, 0, 2, 1, 192, 0, 2, 2, 0, 6, 0, 16, 0x20, 0x01, 0x0d, 0xb8,
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1,
+        ];
+
+        let mut decoder = BinDecoder::new(RDATA);
+        let svcb = SVCB::read_data(&mut decoder, Restrict::new(RDATA.len() as u16)).unwrap();
+        assert_eq!(
+            svcb.ipv4_hint(),
+            [Ipv4Addr::new(192, 0, 2, 1), Ipv4Addr::new(192, 0, 2, 2)]
+        );
+        assert_eq!(
+            svcb.ipv6_hint(),
+            [Ipv6Addr::new(0x2001, 0x0db8, 0, 0, 0, 0, 0, 1)]
+        );
+
+        let svcb = SVCB::new(1, Name::root(), vec![]);
+        assert!(svcb.ipv4_hint().is_empty());
+        assert!(svcb.ipv6_hint().is_empty());
+    }
+
+    #[test]
+    fn test_ip_hints_truncated() {
+        // 1 . ipv4hint=192.0.2.1 with a trailing partial address
+        const IPV4_RDATA: &[u8] = &[0, 1, 0, 0, 4, 0, 6, 192, 0, 2, 1, 192, 0];
+        let mut decoder = BinDecoder::new(IPV4_RDATA);
+        assert!(SVCB::read_data(&mut decoder, Restrict::new(IPV4_RDATA.len() as u16)).is_err());
+
+        // 1 . ipv6hint with only 8 bytes of an address
+        const IPV6_RDATA: &[u8] = &[0, 1, 0, 0, 6, 0, 8, 0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0];
+        let mut decoder = BinDecoder::new(IPV6_RDATA);
+        assert!(SVCB::read_data(&mut decoder, Restrict::new(IPV6_RDATA.len() as u16)).is_err());
+
+        // 1 . ipv4hint="", an empty list is invalid
+        const EMPTY_RDATA: &[u8] = &[0, 1, 0, 0, 4, 0, 0];
+        let mut decoder = BinDecoder::new(EMPTY_RDATA);
+        assert!(SVCB::read_data(&mut decoder, Restrict::new(EMPTY_RDATA.len() as u16)).is_err());
+    }
+
+    #[test]
+    fn test_builder_round_trip() {
+        let https = HTTPS(
+            SVCB::builder()
+                .priority(1)
+                .target_name(Name::from_utf8("svc.example.net.").unwrap())
+                .ipv6_hint(&[Ipv6Addr::new(0x2001, 0x0db8, 0, 0, 0, 0, 0, 1)])
+                .port(8443)
+                .ipv4_hint(&[Ipv4Addr::new(192, 0, 2, 1)])
+                .alpn(&[b"h2", b"h3"])
+                .mandatory(&[SvcParamKey::Port, SvcParamKey::Alpn])
+                .build()
+                .unwrap(),
+        );
+
+        let keys = https
+            .svc_params()
+            .iter()
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            [
+                SvcParamKey::Mandatory,
+                SvcParamKey::Alpn,
+                SvcParamKey::Port,
+                SvcParamKey::Ipv4Hint,
+                SvcParamKey::Ipv6Hint,
+            ]
+        );
+
+        let mut bytes = Vec::new();
+        let mut encoder = BinEncoder::new(&mut bytes);
+        https.emit(&mut encoder).expect("failed to emit HTTPS");
+
+        let mut decoder = BinDecoder::new(&bytes);
+        let decoded = HTTPS::read_data(&mut decoder, Restrict::new(bytes.len() as u16))
+            .expect("failed to read back");
+        assert_eq!(https, decoded);
+        assert_eq!(decoded.alpns().collect::<Vec<_>>(), [b"h2", b"h3"]);
+        assert_eq!(
+            decoded.svc_params()[0].1,
+            SvcParamValue::Mandatory(Mandatory(vec![SvcParamKey::Alpn, SvcParamKey::Port]))
+        );
+    }
+
+    #[test]
+    fn test_builder_invalid() {
+        assert!(SVCB::builder().priority(0).port(443).build().is_err());
+        assert!(SVCB::builder().alpn(&[]).build().is_err());
+        assert!(SVCB::builder()
+            .alpn(&[b"h2"])
+            .no_default_alpn()
+            .build()
+            .is_ok());
+        assert!(SVCB::builder().alpn(&[&[0xff]]).build().is_err());
+        assert!(SVCB::builder().no_default_alpn().build().is_err());
+        assert!(SVCB::builder().ipv4_hint(&[]).build().is_err());
+        assert!(SVCB::builder()
+            .mandatory(&[SvcParamKey::Port])
+            .build()
+            .is_err());
+        assert!(SVCB::builder()
+            .mandatory(&[SvcParamKey::Mandatory])
+            .build()
+            .is_err());
+
+        let alias = SVCB::builder()
+            .priority(0)
+            .target_name(Name::from_utf8("svc.example.net.").unwrap())
+            .build()
+            .unwrap();
+        assert_eq!(alias.svc_priority(), 0);
+        assert!(alias.svc_params().is_empty());
+    }
+
     #[test]
     fn test_no_panic() {
         const BUF: &[u8] = &[
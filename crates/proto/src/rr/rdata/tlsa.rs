@@ -8,12 +8,13 @@
 //! TLSA records for storing TLS certificate validation information
 #![allow(clippy::use_self)]
 
-use std::fmt;
+use alloc::vec::Vec;
+use core::fmt;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-use super::sshfp;
+use data_encoding::HEXLOWER;
 
 use crate::{
     error::{ProtoError, ProtoResult},
@@ -336,10 +337,137 @@ impl TLSA {
         self.matching
     }
 
+    /// The matching type field of RFC 6698, the same as [`Self::matching`]
+    pub fn matching_type(&self) -> Matching {
+        self.matching
+    }
+
     /// Binary data for validating the cert, see other members to understand format
     pub fn cert_data(&self) -> &[u8] {
         &self.cert_data
     }
+
+    /// The certificate association data field of RFC 6698, the same as [`Self::cert_data`]
+    pub fn cert_association(&self) -> &[u8] {
+        &self.cert_data
+    }
+
+    /// Returns true if the DER encoded certificate `cert_der` matches the certificate association
+    ///
+    /// [RFC 6698, DNS-Based Authentication for TLS](https://tools.ietf.org/html/rfc6698#section-2.1.2)
+    ///
+    /// The full certificate or its SubjectPublicKeyInfo is selected, and compared to the
+    /// association data either exactly or by its SHA-256 or SHA-512 digest. This only matches the
+    /// certificate, the certificate usage is left to the caller: the matching certificate must be
+    /// the end entity certificate for DANE-EE and PKIX-EE records, and a certificate of the chain
+    /// for DANE-TA and PKIX-TA records.
+    ///
+    /// Certificates which can't be parsed, and the unassigned and private selectors and matching
+    /// types never match.
+    #[cfg(any(feature = "dnssec-ring", feature = "dns-over-rustls"))]
+    pub fn verify_tlsa(&self, cert_der: &[u8]) -> bool {
+        use ring::digest::{digest, SHA256, SHA512};
+
+        let selected = match self.selector {
+            Selector::Full => cert_der,
+            Selector::Spki => match subject_public_key_info(cert_der) {
+                Some(spki) => spki,
+                None => return false,
+            },
+            Selector::Unassigned(_) | Selector::Private => return false,
+        };
+
+        match self.matching {
+            Matching::Raw => selected == self.cert_data,
+            Matching::Sha256 => digest(&SHA256, selected).as_ref() == self.cert_data,
+            Matching::Sha512 => digest(&SHA512, selected).as_ref() == self.cert_data,
+            Matching::Unassigned(_) | Matching::Private => false,
+        }
+    }
+}
+
+/// Returns the DER encoded SubjectPublicKeyInfo of a DER encoded X.509 certificate
+///
+/// [RFC 5280, section 4.1](https://tools.ietf.org/html/rfc5280#section-4.1)
+///
+/// ```text
+/// Certificate  ::=  SEQUENCE  {
+///      tbsCertificate       TBSCertificate,
+///      signatureAlgorithm   AlgorithmIdentifier,
+///      signatureValue       BIT STRING  }
+///
+/// TBSCertificate  ::=  SEQUENCE  {
+///      version         [0]  EXPLICIT Version DEFAULT v1,
+///      serialNumber         CertificateSerialNumber,
+///      signature            AlgorithmIdentifier,
+///      issuer               Name,
+///      validity             Validity,
+///      subject              Name,
+///      subjectPublicKeyInfo SubjectPublicKeyInfo,
+///      ... }
+/// ```
+#[cfg(any(feature = "dnssec-ring", feature = "dns-over-rustls"))]
+fn subject_public_key_info(cert_der: &[u8]) -> Option<&[u8]> {
+    const SEQUENCE: u8 = 0x30;
+    const VERSION: u8 = 0xa0;
+
+    let (certificate, _) = der_element(cert_der, SEQUENCE)?;
+    let (tbs_certificate, _) = der_element(certificate.content, SEQUENCE)?;
+    let mut tbs_certificate = tbs_certificate.content;
+
+    if tbs_certificate.first() == Some(&VERSION) {
+        tbs_certificate = der_element(tbs_certificate, VERSION)?.1;
+    }
+
+    // serialNumber, signature, issuer, validity and subject
+    for _ in 0..5 {
+        tbs_certificate = der_element(tbs_certificate, *tbs_certificate.first()?)?.1;
+    }
+
+    der_element(tbs_certificate, SEQUENCE).map(|(spki, _)| spki.encoded)
+}
+
+/// A DER element, see [`der_element`]
+#[cfg(any(feature = "dnssec-ring", feature = "dns-over-rustls"))]
+struct DerElement<'a> {
+    /// The whole element, including the tag and the length
+    encoded: &'a [u8],
+    content: &'a [u8],
+}
+
+/// Splits the DER element with the `tag` from the front of `input`, returning it and the rest
+#[cfg(any(feature = "dnssec-ring", feature = "dns-over-rustls"))]
+fn der_element(input: &[u8], tag: u8) -> Option<(DerElement<'_>, &[u8])> {
+    let (&first, rest) = input.split_first()?;
+    if first != tag {
+        return None;
+    }
+
+    let (&length, mut rest) = rest.split_first()?;
+    let length = match length {
+        0..=0x7f => usize::from(length),
+        // the long form, the number of length octets follows, up to 4 are supported
+        0x81..=0x84 => {
+            let octets = usize::from(length & 0x7f);
+            if rest.len() < octets {
+                return None;
+            }
+            let (length, content) = rest.split_at(octets);
+            rest = content;
+            length
+                .iter()
+                .fold(0_usize, |length, octet| (length << 8) | usize::from(*octet))
+        }
+        _ => return None,
+    };
+
+    if rest.len() < length {
+        return None;
+    }
+
+    let (content, rest) = rest.split_at(length);
+    let encoded = &input[..input.len() - rest.len()];
+    Some((DerElement { encoded, content }, rest))
 }
 
 impl BinEncodable for TLSA {
@@ -468,7 +596,7 @@ impl fmt::Display for TLSA {
             usage = u8::from(self.cert_usage),
             selector = u8::from(self.selector),
             matching = u8::from(self.matching),
-            cert = sshfp::HEX.encode(&self.cert_data),
+            cert = HEXLOWER.encode(&self.cert_data),
         )
     }
 }
@@ -478,6 +606,8 @@ mod tests {
     #![allow(clippy::dbg_macro, clippy::print_stdout)]
 
     use super::*;
+    #[cfg(any(feature = "dnssec-ring", feature = "dns-over-rustls"))]
+    use crate::rr::rdata::sshfp;
 
     #[test]
     fn read_cert_usage() {
@@ -544,6 +674,93 @@ mod tests {
         assert_eq!(rdata, read_rdata);
     }
 
+    #[test]
+    fn test_accessors() {
+        let tlsa = TLSA::new(
+            CertUsage::DomainIssued,
+            Selector::Spki,
+            Matching::Sha256,
+            vec![1, 2, 3, 4],
+        );
+        assert_eq!(tlsa.cert_usage(), CertUsage::DomainIssued);
+        assert_eq!(tlsa.selector(), Selector::Spki);
+        assert_eq!(tlsa.matching_type(), Matching::Sha256);
+        assert_eq!(tlsa.cert_association(), &[1, 2, 3, 4]);
+    }
+
+    #[cfg(any(feature = "dnssec-ring", feature = "dns-over-rustls"))]
+    const CERT: &[u8] = include_bytes!("../../../tests/test-data/tlsa-cert.der");
+
+    #[cfg(any(feature = "dnssec-ring", feature = "dns-over-rustls"))]
+    fn dane_ee(selector: Selector, matching: Matching, cert_data: &str) -> TLSA {
+        let cert_data = sshfp::HEX.decode(cert_data.as_bytes()).unwrap();
+        TLSA::new(CertUsage::DomainIssued, selector, matching, cert_data)
+    }
+
+    #[test]
+    #[cfg(any(feature = "dnssec-ring", feature = "dns-over-rustls"))]
+    fn test_verify_dane_ee_spki_sha256() {
+        // openssl x509 -pubkey -noout | openssl pkey -pubin -outform der | openssl dgst -sha256
+        let tlsa = dane_ee(
+            Selector::Spki,
+            Matching::Sha256,
+            "1759fa5230b3c00b4b5d3c1e38eeae75546cc96391a5fa8a396be5373bc1ed77",
+        );
+        assert!(tlsa.verify_tlsa(CERT));
+
+        let other = dane_ee(
+            Selector::Spki,
+            Matching::Sha256,
+            "0000fa5230b3c00b4b5d3c1e38eeae75546cc96391a5fa8a396be5373bc1ed77",
+        );
+        assert!(!other.verify_tlsa(CERT));
+
+        // the digest of the full certificate is not the digest of the public key
+        let full = dane_ee(
+            Selector::Full,
+            Matching::Sha256,
+            "1759fa5230b3c00b4b5d3c1e38eeae75546cc96391a5fa8a396be5373bc1ed77",
+        );
+        assert!(!full.verify_tlsa(CERT));
+
+        assert!(!tlsa.verify_tlsa(&CERT[..CERT.len() / 2]));
+        assert!(!tlsa.verify_tlsa(&[]));
+    }
+
+    #[test]
+    #[cfg(any(feature = "dnssec-ring", feature = "dns-over-rustls"))]
+    fn test_verify_selectors_and_matching_types() {
+        assert!(dane_ee(
+            Selector::Full,
+            Matching::Sha256,
+            "aba67d7efd2d23be3dbdf1c086fbcd3ddff57c8d54c9358f0eb844dc5aeabde7",
+        )
+        .verify_tlsa(CERT));
+        assert!(dane_ee(
+            Selector::Spki,
+            Matching::Sha512,
+            "0d9772743b03a85fd7a9978da3949aeec1b6e2803a3ce6602c1b483df228273b\
+             416c5f3665cd2d90627bf4bbd8b3b078c07d275c499be87729c6e45abc58bb97",
+        )
+        .verify_tlsa(CERT));
+
+        let raw = TLSA::new(
+            CertUsage::DomainIssued,
+            Selector::Full,
+            Matching::Raw,
+            CERT.to_vec(),
+        );
+        assert!(raw.verify_tlsa(CERT));
+
+        let private = TLSA::new(
+            CertUsage::DomainIssued,
+            Selector::Full,
+            Matching::Private,
+            CERT.to_vec(),
+        );
+        assert!(!private.verify_tlsa(CERT));
+    }
+
     #[test]
     fn test_encode_decode_tlsa() {
         test_encode_decode(TLSA::new(
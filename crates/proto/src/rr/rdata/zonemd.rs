@@ -0,0 +1,350 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! ZONEMD records for message digests of zone data
+#![allow(clippy::use_self)]
+
+use alloc::vec::Vec;
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use data_encoding::HEXLOWER;
+
+use crate::{
+    error::{ProtoError, ProtoResult},
+    rr::{RData, RecordData, RecordDataDecodable, RecordType},
+    serialize::binary::{BinDecoder, BinEncodable, BinEncoder, Restrict, RestrictedMath},
+};
+
+/// [RFC 8976](https://www.rfc-editor.org/rfc/rfc8976#section-2.2)
+///
+/// ```text
+/// 2.2.  ZONEMD RDATA Wire Format
+///
+///    The ZONEMD RDATA wire format is encoded as follows:
+///
+///                         1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+///     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |                             Serial                            |
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |    Scheme     |Hash Algorithm |                               |
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+                               |
+///    |                             Digest                            |
+///    /                                                               /
+///    /                                                               /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///
+/// 2.2.4.  The Digest Field
+///
+///    The Digest field MUST NOT be shorter than 12 octets.  Digests for the
+///    SHA384 and SHA512 hash algorithms specified herein are never
+///    truncated.
+/// ```
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct ZONEMD {
+    serial: u32,
+    scheme: Scheme,
+    hash_algorithm: HashAlgorithm,
+    digest: Vec<u8>,
+}
+
+impl ZONEMD {
+    /// The minimum length of the digest, see RFC 8976 section 2.2.4
+    pub const MIN_DIGEST_LEN: usize = 12;
+
+    /// Creates a new ZONEMD record data.
+    ///
+    /// # Arguments
+    ///
+    /// * `serial` - the SOA serial of the zone the digest was computed for.
+    /// * `scheme` - the method used to collate the zone data.
+    /// * `hash_algorithm` - the hash algorithm used for the digest.
+    /// * `digest` - the digest of the zone data.
+    pub fn new(
+        serial: u32,
+        scheme: Scheme,
+        hash_algorithm: HashAlgorithm,
+        digest: Vec<u8>,
+    ) -> Self {
+        Self {
+            serial,
+            scheme,
+            hash_algorithm,
+            digest,
+        }
+    }
+
+    /// The SOA serial of the zone the digest was computed for.
+    pub fn serial(&self) -> u32 {
+        self.serial
+    }
+
+    /// The method used to collate the zone data.
+    pub fn scheme(&self) -> Scheme {
+        self.scheme
+    }
+
+    /// The hash algorithm used for the digest.
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash_algorithm
+    }
+
+    /// The digest of the zone data.
+    pub fn digest(&self) -> &[u8] {
+        &self.digest
+    }
+}
+
+/// ```text
+/// 2.2.2.  The Scheme Field
+///
+///    The Scheme field is an 8-bit unsigned integer that identifies the
+///    methods by which data is collated and presented as input to the
+///    hashing function.
+///
+///    Herein, SIMPLE, with Scheme value 1, is the only standardized Scheme
+///    defined for ZONEMD records and it MUST be implemented.
+/// ```
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Scheme {
+    /// Reserved value
+    Reserved,
+
+    /// SIMPLE collation of the zone data
+    Simple,
+
+    /// Unassigned value
+    Unassigned(u8),
+}
+
+impl From<u8> for Scheme {
+    fn from(scheme: u8) -> Self {
+        match scheme {
+            0 => Self::Reserved,
+            1 => Self::Simple,
+            _ => Self::Unassigned(scheme),
+        }
+    }
+}
+
+impl From<Scheme> for u8 {
+    fn from(scheme: Scheme) -> Self {
+        match scheme {
+            Scheme::Reserved => 0,
+            Scheme::Simple => 1,
+            Scheme::Unassigned(scheme) => scheme,
+        }
+    }
+}
+
+/// ```text
+/// 2.2.3.  The Hash Algorithm Field
+///
+///    The Hash Algorithm field is an 8-bit unsigned integer that identifies
+///    the cryptographic hash algorithm used to construct the digest.
+///
+///    Herein, SHA384 [RFC6234], with Hash Algorithm value 1, is the only
+///    standardized Hash Algorithm defined for ZONEMD records that MUST be
+///    implemented.  When SHA384 is used, the size of the Digest field is 48
+///    octets.
+///
+///    SHA512 [RFC6234], with Hash Algorithm value 2, is also defined for
+///    ZONEMD records and SHOULD be implemented.  When SHA512 is used, the
+///    size of the Digest field is 64 octets.
+/// ```
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum HashAlgorithm {
+    /// Reserved value
+    Reserved,
+
+    /// SHA-384
+    SHA384,
+
+    /// SHA-512
+    SHA512,
+
+    /// Unassigned value
+    Unassigned(u8),
+}
+
+impl From<u8> for HashAlgorithm {
+    fn from(alg: u8) -> Self {
+        match alg {
+            0 => Self::Reserved,
+            1 => Self::SHA384,
+            2 => Self::SHA512,
+            _ => Self::Unassigned(alg),
+        }
+    }
+}
+
+impl From<HashAlgorithm> for u8 {
+    fn from(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Reserved => 0,
+            HashAlgorithm::SHA384 => 1,
+            HashAlgorithm::SHA512 => 2,
+            HashAlgorithm::Unassigned(alg) => alg,
+        }
+    }
+}
+
+impl BinEncodable for ZONEMD {
+    fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
+        encoder.emit_u32(self.serial)?;
+        encoder.emit_u8(self.scheme.into())?;
+        encoder.emit_u8(self.hash_algorithm.into())?;
+        encoder.emit_vec(&self.digest)
+    }
+}
+
+impl<'r> RecordDataDecodable<'r> for ZONEMD {
+    fn read_data(decoder: &mut BinDecoder<'r>, length: Restrict<u16>) -> ProtoResult<Self> {
+        let serial = decoder.read_u32()?.unverified();
+        let scheme = decoder.read_u8()?.unverified().into();
+        let hash_algorithm = decoder.read_u8()?.unverified().into();
+        let digest_len = length
+            .map(|l| l as usize)
+            .checked_sub(6)
+            .map_err(|_| ProtoError::from("invalid rdata length in ZONEMD"))?
+            .verify_unwrap(|l| *l >= Self::MIN_DIGEST_LEN)
+            .map_err(|_| ProtoError::from("ZONEMD digest is shorter than 12 octets"))?;
+        let digest = decoder.read_vec(digest_len)?.unverified();
+        Ok(ZONEMD::new(serial, scheme, hash_algorithm, digest))
+    }
+}
+
+impl RecordData for ZONEMD {
+    fn try_from_rdata(data: RData) -> Result<Self, RData> {
+        match data {
+            RData::ZONEMD(data) => Ok(data),
+            _ => Err(data),
+        }
+    }
+
+    fn try_borrow(data: &RData) -> Option<&Self> {
+        match data {
+            RData::ZONEMD(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    fn record_type(&self) -> RecordType {
+        RecordType::ZONEMD
+    }
+
+    fn into_rdata(self) -> RData {
+        RData::ZONEMD(self)
+    }
+}
+
+/// [RFC 8976](https://www.rfc-editor.org/rfc/rfc8976#section-2.3)
+///
+/// ```text
+/// 2.3.  ZONEMD Presentation Format
+///
+///    The presentation format of the RDATA portion is as follows:
+///
+///    The Serial field MUST be represented as an unsigned decimal integer.
+///
+///    The Scheme field MUST be represented as an unsigned decimal integer.
+///
+///    The Hash Algorithm field MUST be represented as an unsigned decimal
+///    integer.
+///
+///    The Digest MUST be represented as a sequence of case-insensitive
+///    hexadecimal digits.  Whitespace is allowed within the hexadecimal
+///    text.
+/// ```
+impl fmt::Display for ZONEMD {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "{serial} {scheme} {hash_algorithm} {digest}",
+            serial = self.serial,
+            scheme = u8::from(self.scheme),
+            hash_algorithm = u8::from(self.hash_algorithm),
+            digest = HEXLOWER.encode(&self.digest),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    fn test_encode_decode(rdata: ZONEMD, result: &[u8]) {
+        let mut bytes = Vec::new();
+        let mut encoder = BinEncoder::new(&mut bytes);
+        rdata.emit(&mut encoder).expect("failed to emit ZONEMD");
+        let bytes = encoder.into_bytes();
+        assert_eq!(bytes, &result);
+
+        let mut decoder = BinDecoder::new(result);
+        let read_rdata = ZONEMD::read_data(&mut decoder, Restrict::new(result.len() as u16))
+            .expect("failed to read ZONEMD");
+        assert_eq!(read_rdata, rdata)
+    }
+
+    #[test]
+    fn test_encode_decode_zonemd() {
+        test_encode_decode(
+            ZONEMD::new(
+                2018031900,
+                Scheme::Simple,
+                HashAlgorithm::SHA384,
+                (0..48).collect(),
+            ),
+            &[
+                0x78, 0x48, 0xb9, 0x1c, 1, 1, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+                16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36,
+                37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47,
+            ],
+        );
+        test_encode_decode(
+            ZONEMD::new(
+                1,
+                Scheme::Unassigned(241),
+                HashAlgorithm::Unassigned(242),
+                vec![0xff; 12],
+            ),
+            &[
+                0, 0, 0, 1, 241, 242, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_short_digest() {
+        let bytes = [0, 0, 0, 1, 1, 1, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+        let mut decoder = BinDecoder::new(&bytes);
+        assert!(ZONEMD::read_data(&mut decoder, Restrict::new(bytes.len() as u16)).is_err());
+
+        let bytes = [0, 0, 0, 1, 1];
+        let mut decoder = BinDecoder::new(&bytes);
+        assert!(ZONEMD::read_data(&mut decoder, Restrict::new(bytes.len() as u16)).is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        let rdata = ZONEMD::new(
+            2018031900,
+            Scheme::Simple,
+            HashAlgorithm::SHA384,
+            vec![0xab; 12],
+        );
+        assert_eq!(rdata.to_string(), "2018031900 1 1 abababababababababababab");
+    }
+}
@@ -8,13 +8,10 @@
 //! record data enum variants
 #![allow(deprecated, clippy::use_self)] // allows us to deprecate RData types
 
+use alloc::vec::Vec;
 #[cfg(test)]
-use std::convert::From;
-use std::{
-    cmp::Ordering,
-    fmt,
-    net::{IpAddr, Ipv4Addr, Ipv6Addr},
-};
+use core::convert::From;
+use core::{cmp::Ordering, fmt};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -24,10 +21,11 @@ use tracing::{trace, warn};
 
 use crate::{
     error::{ProtoError, ProtoErrorKind, ProtoResult},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
     rr::{
         rdata::{
-            A, AAAA, ANAME, CAA, CERT, CNAME, CSYNC, HINFO, HTTPS, MX, NAPTR, NS, NULL, OPENPGPKEY,
-            OPT, PTR, SOA, SRV, SSHFP, SVCB, TLSA, TXT,
+            A, AAAA, ANAME, APL, CAA, CERT, CNAME, CSYNC, DHCID, HINFO, HTTPS, LOC, MX, NAPTR, NS,
+            NULL, OPENPGPKEY, OPT, PTR, SOA, SRV, SSHFP, SVCB, TLSA, TXT, ZONEMD,
         },
         record_type::RecordType,
         RecordData, RecordDataDecodable,
@@ -113,6 +111,11 @@ pub enum RData {
     /// ```
     ANAME(ANAME),
 
+    /// A list of address prefixes, see [RFC 3123](https://tools.ietf.org/html/rfc3123)
+    ///
+    /// The prefixes of the address families other than IPv4 and IPv6 are kept as is.
+    APL(APL),
+
     /// ```text
     /// -- RFC 6844          Certification Authority Authorization     January 2013
     ///
@@ -212,6 +215,21 @@ pub enum RData {
     /// ```
     CSYNC(CSYNC),
 
+    /// ```text
+    /// -- RFC 4701 -- A DNS Resource Record (RR) for Encoding DHCP Information
+    ///
+    /// 3.5.  Computation of the RDATA
+    ///
+    ///    The DHCID RDATA is formed by concatenating the 2-octet identifier
+    ///    type code with variable-length data.
+    ///
+    ///    The RDATA for all type codes other than 0xffff, which is reserved for
+    ///    future expansion, is formed by concatenating the 2-octet identifier
+    ///    type code, the 1-octet digest type code, and the digest value (32
+    ///    octets for SHA-256).
+    /// ```
+    DHCID(DHCID),
+
     /// ```text
     /// 3.3.2. HINFO RDATA format
     ///
@@ -261,6 +279,12 @@ pub enum RData {
     /// ```
     HTTPS(HTTPS),
 
+    /// The geographical location of a host, network or subnet, see
+    /// [RFC 1876](https://tools.ietf.org/html/rfc1876)
+    ///
+    /// Only version 0 of the RDATA is defined, the other versions are read as [`RData::Unknown`].
+    LOC(LOC),
+
     /// ```text
     /// 3.3.9. MX RDATA format
     ///
@@ -696,6 +720,19 @@ pub enum RData {
     /// ```
     TXT(TXT),
 
+    /// [RFC 8976](https://www.rfc-editor.org/rfc/rfc8976#section-2)
+    ///
+    /// ```text
+    /// 2.  The ZONEMD Resource Record
+    ///
+    ///    This section describes the ZONEMD Resource Record, including its
+    ///    fields, wire format, and presentation format.  The Type value for the
+    ///    ZONEMD RR is 63.  The ZONEMD RR is class independent.  The RDATA of
+    ///    the resource record consists of four fields: Serial, Scheme, Hash
+    ///    Algorithm, and Digest.
+    /// ```
+    ZONEMD(ZONEMD),
+
     /// A DNSSEC- or SIG(0)- specific record. See `DNSSECRData` for details.
     ///
     /// These types are in `DNSSECRData` to make them easy to disable when
@@ -737,12 +774,15 @@ impl RData {
             Self::A(..) => RecordType::A,
             Self::AAAA(..) => RecordType::AAAA,
             Self::ANAME(..) => RecordType::ANAME,
+            Self::APL(..) => RecordType::APL,
             Self::CAA(..) => RecordType::CAA,
             Self::CERT(..) => RecordType::CERT,
             Self::CNAME(..) => RecordType::CNAME,
             Self::CSYNC(..) => RecordType::CSYNC,
+            Self::DHCID(..) => RecordType::DHCID,
             Self::HINFO(..) => RecordType::HINFO,
             Self::HTTPS(..) => RecordType::HTTPS,
+            Self::LOC(..) => RecordType::LOC,
             Self::MX(..) => RecordType::MX,
             Self::NAPTR(..) => RecordType::NAPTR,
             Self::NS(..) => RecordType::NS,
@@ -756,6 +796,7 @@ impl RData {
             Self::SVCB(..) => RecordType::SVCB,
             Self::TLSA(..) => RecordType::TLSA,
             Self::TXT(..) => RecordType::TXT,
+            Self::ZONEMD(..) => RecordType::ZONEMD,
             #[cfg(feature = "dnssec-ring")]
             Self::DNSSEC(rdata) => DNSSECRData::to_record_type(rdata),
             Self::Unknown { code, .. } => *code,
@@ -794,6 +835,10 @@ impl RData {
                 trace!("reading ANAME");
                 ANAME::read(decoder).map(Self::ANAME)
             }
+            RecordType::APL => {
+                trace!("reading APL");
+                APL::read_data(decoder, length).map(Self::APL)
+            }
             rt @ RecordType::ANY | rt @ RecordType::AXFR | rt @ RecordType::IXFR => {
                 return Err(ProtoErrorKind::UnknownRecordTypeValue(rt.into()).into());
             }
@@ -813,6 +858,10 @@ impl RData {
                 trace!("reading CSYNC");
                 CSYNC::read_data(decoder, length).map(Self::CSYNC)
             }
+            RecordType::DHCID => {
+                trace!("reading DHCID");
+                DHCID::read_data(decoder, length).map(Self::DHCID)
+            }
             RecordType::HINFO => {
                 trace!("reading HINFO");
                 HINFO::read_data(decoder, length).map(Self::HINFO)
@@ -828,6 +877,19 @@ impl RData {
                 #[allow(deprecated)]
                 Ok(Self::ZERO)
             }
+            RecordType::LOC => {
+                trace!("reading LOC");
+                // no assumptions are made about the format of the other versions, RFC 1876
+                match decoder.peek().map(Restrict::unverified) {
+                    Some(version) if version != 0 => {
+                        NULL::read_data(decoder, length).map(|rdata| Self::Unknown {
+                            code: record_type,
+                            rdata,
+                        })
+                    }
+                    _ => LOC::read_data(decoder, length).map(Self::LOC),
+                }
+            }
             RecordType::MX => {
                 trace!("reading MX");
                 MX::read_data(decoder, length).map(Self::MX)
@@ -880,6 +942,10 @@ impl RData {
                 trace!("reading TXT");
                 TXT::read_data(decoder, length).map(Self::TXT)
             }
+            RecordType::ZONEMD => {
+                trace!("reading ZONEMD");
+                ZONEMD::read_data(decoder, length).map(Self::ZONEMD)
+            }
             #[cfg(feature = "dnssec-ring")]
             r if r.is_dnssec() => DNSSECRData::read(decoder, record_type, length).map(Self::DNSSEC),
             record_type => {
@@ -976,34 +1042,41 @@ impl BinEncodable for RData {
     ///   ...
     /// ```
     fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
+        // the names are only compressed in the RDATA of the well-known types
+        if !encoder.is_canonical_names() && !self.record_type().is_compressible() {
+            return encoder.with_canonical_names(|encoder| self.emit(encoder));
+        }
+
         match self {
             Self::A(address) => address.emit(encoder),
             Self::AAAA(address) => address.emit(encoder),
-            Self::ANAME(name) => encoder.with_canonical_names(|encoder| name.emit(encoder)),
-            Self::CAA(caa) => encoder.with_canonical_names(|encoder| caa.emit(encoder)),
+            Self::ANAME(name) => name.emit(encoder),
+            Self::APL(apl) => apl.emit(encoder),
+            Self::CAA(caa) => caa.emit(encoder),
             Self::CERT(cert) => cert.emit(encoder),
             Self::CNAME(cname) => cname.emit(encoder),
             Self::NS(ns) => ns.emit(encoder),
             Self::PTR(ptr) => ptr.emit(encoder),
             Self::CSYNC(csync) => csync.emit(encoder),
+            Self::DHCID(dhcid) => dhcid.emit(encoder),
             Self::HINFO(hinfo) => hinfo.emit(encoder),
             Self::HTTPS(https) => https.emit(encoder),
+            Self::LOC(loc) => loc.emit(encoder),
             Self::ZERO => Ok(()),
             Self::MX(mx) => mx.emit(encoder),
-            Self::NAPTR(naptr) => encoder.with_canonical_names(|encoder| naptr.emit(encoder)),
+            Self::NAPTR(naptr) => naptr.emit(encoder),
             Self::NULL(null) => null.emit(encoder),
-            Self::OPENPGPKEY(openpgpkey) => {
-                encoder.with_canonical_names(|encoder| openpgpkey.emit(encoder))
-            }
+            Self::OPENPGPKEY(openpgpkey) => openpgpkey.emit(encoder),
             Self::OPT(opt) => opt.emit(encoder),
             Self::SOA(soa) => soa.emit(encoder),
-            Self::SRV(srv) => encoder.with_canonical_names(|encoder| srv.emit(encoder)),
-            Self::SSHFP(sshfp) => encoder.with_canonical_names(|encoder| sshfp.emit(encoder)),
+            Self::SRV(srv) => srv.emit(encoder),
+            Self::SSHFP(sshfp) => sshfp.emit(encoder),
             Self::SVCB(svcb) => svcb.emit(encoder),
-            Self::TLSA(tlsa) => encoder.with_canonical_names(|encoder| tlsa.emit(encoder)),
+            Self::TLSA(tlsa) => tlsa.emit(encoder),
             Self::TXT(txt) => txt.emit(encoder),
+            Self::ZONEMD(zonemd) => zonemd.emit(encoder),
             #[cfg(feature = "dnssec-ring")]
-            Self::DNSSEC(rdata) => encoder.with_canonical_names(|encoder| rdata.emit(encoder)),
+            Self::DNSSEC(rdata) => rdata.emit(encoder),
             Self::Unknown { rdata, .. } => rdata.emit(encoder),
             Self::Update0(_) => Ok(()),
         }
@@ -1042,6 +1115,7 @@ impl fmt::Display for RData {
             Self::A(address) => w(f, address),
             Self::AAAA(address) => w(f, address),
             Self::ANAME(name) => w(f, name),
+            Self::APL(apl) => w(f, apl),
             Self::CAA(caa) => w(f, caa),
             Self::CERT(cert) => w(f, cert),
             // to_lowercase for rfc4034 and rfc6840
@@ -1049,8 +1123,10 @@ impl fmt::Display for RData {
             Self::NS(ns) => w(f, ns),
             Self::PTR(ptr) => w(f, ptr),
             Self::CSYNC(csync) => w(f, csync),
+            Self::DHCID(dhcid) => w(f, dhcid),
             Self::HINFO(hinfo) => w(f, hinfo),
             Self::HTTPS(https) => w(f, https),
+            Self::LOC(loc) => w(f, loc),
             Self::ZERO => Ok(()),
             // to_lowercase for rfc4034 and rfc6840
             Self::MX(mx) => w(f, mx),
@@ -1067,14 +1143,48 @@ impl fmt::Display for RData {
             Self::SVCB(svcb) => w(f, svcb),
             Self::TLSA(tlsa) => w(f, tlsa),
             Self::TXT(txt) => w(f, txt),
+            Self::ZONEMD(zonemd) => w(f, zonemd),
             #[cfg(feature = "dnssec-ring")]
             Self::DNSSEC(rdata) => w(f, rdata),
-            Self::Unknown { rdata, .. } => w(f, rdata),
+            // the generic format of RFC 3597, e.g. `\# 4 C0000201`
+            Self::Unknown { rdata, .. } => match rdata.anything() {
+                [] => f.write_str("\\# 0"),
+                data => write!(
+                    f,
+                    "\\# {} {}",
+                    data.len(),
+                    data_encoding::HEXUPPER.encode(data)
+                ),
+            },
             Self::Update0(_) => w(f, "UPDATE"),
         }
     }
 }
 
+impl RData {
+    /// Canonical RR ordering within an RRset, [RFC 4034, section 6.3](https://tools.ietf.org/html/rfc4034#section-6.3)
+    ///
+    /// The RDATA are compared as the octets of their canonical form, RFC 4034, section 6.2, in
+    /// which the names are not compressed and lowercased for the types listed there. Unlike the
+    /// [`Ord`] implementation, names in the RDATA differing only in case compare equal.
+    pub fn cmp_canonical(&self, other: &Self) -> Ordering {
+        self.to_canonical_bytes().cmp(&other.to_canonical_bytes())
+    }
+
+    /// Encodes the RDATA in canonical form, [RFC 4034, section 6.2](https://tools.ietf.org/html/rfc4034#section-6.2)
+    pub(crate) fn to_canonical_bytes(&self) -> Vec<u8> {
+        let mut buf: Vec<u8> = Vec::new();
+        {
+            let mut encoder: BinEncoder<'_> = BinEncoder::new(&mut buf);
+            encoder.set_canonical_names(true);
+            self.emit(&mut encoder).unwrap_or_else(|_| {
+                warn!("could not encode RDATA: {:?}", self);
+            });
+        }
+        buf
+    }
+}
+
 impl PartialOrd<Self> for RData {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
@@ -1132,11 +1242,12 @@ impl From<Ipv6Addr> for RData {
 mod tests {
     #![allow(clippy::dbg_macro, clippy::print_stdout)]
 
-    use std::str::FromStr;
+    use alloc::string::ToString;
+    use core::str::FromStr;
 
     use super::*;
     use crate::rr::domain::Name;
-    use crate::rr::rdata::{MX, SOA, SRV, TXT};
+    use crate::rr::rdata::{HTTPS, MX, SOA, SRV, SVCB, TXT};
     use crate::serialize::binary::bin_tests::test_emit_data_set;
     #[allow(clippy::useless_attribute)]
     #[allow(unused)]
@@ -1311,12 +1422,15 @@ mod tests {
             RData::A(..) => RecordType::A,
             RData::AAAA(..) => RecordType::AAAA,
             RData::ANAME(..) => RecordType::ANAME,
+            RData::APL(..) => RecordType::APL,
             RData::CAA(..) => RecordType::CAA,
             RData::CERT(..) => RecordType::CERT,
             RData::CNAME(..) => RecordType::CNAME,
             RData::CSYNC(..) => RecordType::CSYNC,
+            RData::DHCID(..) => RecordType::DHCID,
             RData::HINFO(..) => RecordType::HINFO,
             RData::HTTPS(..) => RecordType::HTTPS,
+            RData::LOC(..) => RecordType::LOC,
             RData::MX(..) => RecordType::MX,
             RData::NAPTR(..) => RecordType::NAPTR,
             RData::NS(..) => RecordType::NS,
@@ -1330,6 +1444,7 @@ mod tests {
             RData::SVCB(..) => RecordType::SVCB,
             RData::TLSA(..) => RecordType::TLSA,
             RData::TXT(..) => RecordType::TXT,
+            RData::ZONEMD(..) => RecordType::ZONEMD,
             #[cfg(feature = "dnssec-ring")]
             RData::DNSSEC(rdata) => rdata.to_record_type(),
             RData::Unknown { code, .. } => *code,
@@ -1342,4 +1457,24 @@ mod tests {
     fn test_write_to() {
         test_emit_data_set(get_data(), |e, d| d.emit(e));
     }
+
+    #[test]
+    fn test_compression_by_type() {
+        let name = Name::from_str("svc.example.com.").unwrap();
+        let emit_after_name = |rdata: RData| {
+            let mut bytes = Vec::new();
+            let mut encoder = BinEncoder::new(&mut bytes);
+            name.emit(&mut encoder).unwrap();
+            rdata.emit(&mut encoder).unwrap();
+            bytes.split_off(17)
+        };
+
+        // the exchange of an MX points to the name written before
+        let mx = emit_after_name(RData::MX(MX::new(10, name.clone())));
+        assert_eq!(mx, [0, 10, 0xC0, 0]);
+
+        // the target of an HTTPS record, a type defined after RFC 3597, is never compressed
+        let https = emit_after_name(RData::HTTPS(HTTPS(SVCB::new(1, name.clone(), vec![]))));
+        assert_eq!(https[2..], *b"\x03svc\x07example\x03com\x00");
+    }
 }
@@ -8,9 +8,10 @@
 //! record type definitions
 #![allow(clippy::use_self)]
 
-use std::cmp::Ordering;
-use std::fmt::{self, Display, Formatter};
-use std::str::FromStr;
+use alloc::string::ToString;
+use core::cmp::Ordering;
+use core::fmt::{self, Display, Formatter};
+use core::str::FromStr;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -39,7 +40,8 @@ pub enum RecordType {
     //  AFSDB,      //	18	RFC 1183	AFS database record
     /// [RFC 1035](https://tools.ietf.org/html/rfc1035) All cached records, aka ANY
     ANY,
-    //  APL,        //	42	RFC 3123	Address Prefix List
+    /// [RFC 3123](https://tools.ietf.org/html/rfc3123) Address Prefix List
+    APL,
     /// [RFC 1035](https://tools.ietf.org/html/rfc1035) Authoritative Zone Transfer
     AXFR,
     /// [RFC 6844](https://tools.ietf.org/html/rfc6844) Certification Authority Authorization
@@ -52,11 +54,12 @@ pub enum RecordType {
     CERT,
     /// [RFC 1035](https://tools.ietf.org/html/rfc1035) Canonical name record
     CNAME,
-    //  DHCID,      // 49 RFC 4701 DHCP identifier
     //  DLV,        //	32769	RFC 4431	DNSSEC Lookaside Validation record
     //  DNAME,      // 39 RFC 2672 Delegation Name
     /// [RFC 7477](https://tools.ietf.org/html/rfc4034) Child-to-parent synchronization record
     CSYNC,
+    /// [RFC 4701](https://tools.ietf.org/html/rfc4701) DHCP identifier
+    DHCID,
     /// [RFC 4034](https://tools.ietf.org/html/rfc4034) DNS Key record: RSASHA256 and RSASHA512, RFC5702
     DNSKEY,
     /// [RFC 4034](https://tools.ietf.org/html/rfc4034) Delegation signer: RSASHA256 and RSASHA512, RFC5702
@@ -72,7 +75,8 @@ pub enum RecordType {
     //  KX,         // 36 RFC 2230 Key eXchanger record
     /// [RFC 2535](https://tools.ietf.org/html/rfc2535) and [RFC 2930](https://tools.ietf.org/html/rfc2930) Key record
     KEY,
-    //  LOC,        // 29 RFC 1876 Location record
+    /// [RFC 1876](https://tools.ietf.org/html/rfc1876) Location record
+    LOC,
     /// [RFC 1035](https://tools.ietf.org/html/rfc1035) Mail exchange record
     MX,
     /// [RFC 3403](https://tools.ietf.org/html/rfc3403) Naming Authority Pointer
@@ -114,6 +118,8 @@ pub enum RecordType {
     TSIG,
     /// [RFC 1035](https://tools.ietf.org/html/rfc1035) Text record
     TXT,
+    /// [RFC 8976](https://www.rfc-editor.org/rfc/rfc8976) Message digest for DNS zones
+    ZONEMD,
     /// Unknown Record type, or unsupported
     Unknown(u16),
 
@@ -183,6 +189,27 @@ impl RecordType {
         )
     }
 
+    /// Returns true if the names in the RDATA of this type may be compressed
+    ///
+    /// Only the well-known types defined in RFC 1035 are compressed, the names embedded in the
+    /// RDATA of all other types, including every type defined since, are written uncompressed, see
+    /// [RFC 3597 section 4](https://tools.ietf.org/html/rfc3597#section-4).
+    #[inline]
+    pub fn is_compressible(self) -> bool {
+        matches!(
+            self,
+            Self::A
+                | Self::CNAME
+                | Self::HINFO
+                | Self::MX
+                | Self::NS
+                | Self::NULL
+                | Self::PTR
+                | Self::SOA
+                | Self::TXT
+        )
+    }
+
     /// Returns true if this is a Zero (unspecified) RecordType
     #[inline]
     pub fn is_zero(self) -> bool {
@@ -201,6 +228,10 @@ impl FromStr for RecordType {
     ///
     /// let var: RecordType = RecordType::from_str("A").unwrap();
     /// assert_eq!(RecordType::A, var);
+    ///
+    /// // the generic format of RFC 3597
+    /// let var: RecordType = RecordType::from_str("TYPE65534").unwrap();
+    /// assert_eq!(RecordType::Unknown(65534), var);
     /// ```
     fn from_str(str: &str) -> ProtoResult<Self> {
         // TODO missing stuff?
@@ -209,6 +240,7 @@ impl FromStr for RecordType {
             "A" => Ok(Self::A),
             "AAAA" => Ok(Self::AAAA),
             "ANAME" => Ok(Self::ANAME),
+            "APL" => Ok(Self::APL),
             "AXFR" => Ok(Self::AXFR),
             "CAA" => Ok(Self::CAA),
             "CDNSKEY" => Ok(Self::CDNSKEY),
@@ -216,11 +248,13 @@ impl FromStr for RecordType {
             "CDS" => Ok(Self::CDS),
             "CNAME" => Ok(Self::CNAME),
             "CSYNC" => Ok(Self::CSYNC),
+            "DHCID" => Ok(Self::DHCID),
             "DNSKEY" => Ok(Self::DNSKEY),
             "DS" => Ok(Self::DS),
             "HINFO" => Ok(Self::HINFO),
             "HTTPS" => Ok(Self::HTTPS),
             "KEY" => Ok(Self::KEY),
+            "LOC" => Ok(Self::LOC),
             "MX" => Ok(Self::MX),
             "NAPTR" => Ok(Self::NAPTR),
             "NSEC" => Ok(Self::NSEC),
@@ -238,9 +272,13 @@ impl FromStr for RecordType {
             "SVCB" => Ok(Self::SVCB),
             "TLSA" => Ok(Self::TLSA),
             "TXT" => Ok(Self::TXT),
+            "ZONEMD" => Ok(Self::ZONEMD),
             "TSIG" => Ok(Self::TSIG),
             "ANY" | "*" => Ok(Self::ANY),
-            _ => Err(ProtoErrorKind::UnknownRecordTypeStr(str.to_string()).into()),
+            _ => match str.strip_prefix("TYPE").map(u16::from_str) {
+                Some(Ok(code)) => Ok(Self::from(code)),
+                _ => Err(ProtoErrorKind::UnknownRecordTypeStr(str.to_string()).into()),
+            },
         }
     }
 }
@@ -260,6 +298,7 @@ impl From<u16> for RecordType {
             28 => Self::AAAA,
             // TODO: wrong value here, see https://github.com/hickory-dns/hickory-dns/issues/723
             65305 => Self::ANAME,
+            42 => Self::APL,
             255 => Self::ANY,
             251 => Self::IXFR,
             252 => Self::AXFR,
@@ -269,11 +308,13 @@ impl From<u16> for RecordType {
             37 => Self::CERT,
             5 => Self::CNAME,
             62 => Self::CSYNC,
+            49 => Self::DHCID,
             48 => Self::DNSKEY,
             43 => Self::DS,
             13 => Self::HINFO,
             65 => Self::HTTPS,
             25 => Self::KEY,
+            29 => Self::LOC,
             15 => Self::MX,
             35 => Self::NAPTR,
             2 => Self::NS,
@@ -293,6 +334,7 @@ impl From<u16> for RecordType {
             52 => Self::TLSA,
             250 => Self::TSIG,
             16 => Self::TXT,
+            63 => Self::ZONEMD,
             0 => Self::ZERO,
             // all unknown record types
             _ => Self::Unknown(value),
@@ -336,6 +378,7 @@ impl From<RecordType> for &'static str {
             RecordType::A => "A",
             RecordType::AAAA => "AAAA",
             RecordType::ANAME => "ANAME",
+            RecordType::APL => "APL",
             RecordType::ANY => "ANY",
             RecordType::AXFR => "AXFR",
             RecordType::CAA => "CAA",
@@ -344,12 +387,14 @@ impl From<RecordType> for &'static str {
             RecordType::CDS => "CDS",
             RecordType::CNAME => "CNAME",
             RecordType::CSYNC => "CSYNC",
+            RecordType::DHCID => "DHCID",
             RecordType::DNSKEY => "DNSKEY",
             RecordType::DS => "DS",
             RecordType::HINFO => "HINFO",
             RecordType::HTTPS => "HTTPS",
             RecordType::KEY => "KEY",
             RecordType::IXFR => "IXFR",
+            RecordType::LOC => "LOC",
             RecordType::MX => "MX",
             RecordType::NAPTR => "NAPTR",
             RecordType::NS => "NS",
@@ -369,6 +414,7 @@ impl From<RecordType> for &'static str {
             RecordType::TLSA => "TLSA",
             RecordType::TSIG => "TSIG",
             RecordType::TXT => "TXT",
+            RecordType::ZONEMD => "ZONEMD",
             RecordType::ZERO => "ZERO",
             RecordType::Unknown(_) => "Unknown",
         }
@@ -390,6 +436,7 @@ impl From<RecordType> for u16 {
             RecordType::AAAA => 28,
             // TODO: wrong value here, see https://github.com/hickory-dns/hickory-dns/issues/723
             RecordType::ANAME => 65305,
+            RecordType::APL => 42,
             RecordType::ANY => 255,
             RecordType::AXFR => 252,
             RecordType::CAA => 257,
@@ -398,12 +445,14 @@ impl From<RecordType> for u16 {
             RecordType::CDS => 59,
             RecordType::CNAME => 5,
             RecordType::CSYNC => 62,
+            RecordType::DHCID => 49,
             RecordType::DNSKEY => 48,
             RecordType::DS => 43,
             RecordType::HINFO => 13,
             RecordType::HTTPS => 65,
             RecordType::KEY => 25,
             RecordType::IXFR => 251,
+            RecordType::LOC => 29,
             RecordType::MX => 15,
             RecordType::NAPTR => 35,
             RecordType::NS => 2,
@@ -423,6 +472,7 @@ impl From<RecordType> for u16 {
             RecordType::TLSA => 52,
             RecordType::TSIG => 250,
             RecordType::TXT => 16,
+            RecordType::ZONEMD => 63,
             RecordType::ZERO => 0,
             RecordType::Unknown(code) => code,
         }
@@ -444,8 +494,12 @@ impl Ord for RecordType {
 }
 
 impl Display for RecordType {
+    /// Unknown types are displayed in the generic format of RFC 3597, e.g. `TYPE65534`
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
-        f.write_str(Into::<&str>::into(*self))
+        match self {
+            Self::Unknown(code) => write!(f, "TYPE{code}"),
+            _ => f.write_str(Into::<&str>::into(*self)),
+        }
     }
 }
 
@@ -510,11 +564,14 @@ mod tests {
             "A",
             "AAAA",
             "ANAME",
+            "APL",
             "CAA",
             "CERT",
             "CNAME",
             "CSYNC",
+            "DHCID",
             "HINFO",
+            "LOC",
             "NULL",
             "MX",
             "NAPTR",
@@ -526,6 +583,7 @@ mod tests {
             "SSHFP",
             "TLSA",
             "TXT",
+            "ZONEMD",
             "ANY",
             "AXFR",
         ];
@@ -555,6 +613,43 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_generic_record_type() {
+        let rtype: RecordType = "TYPE65534".parse().unwrap();
+        assert_eq!(rtype, RecordType::Unknown(65534));
+        assert_eq!(rtype.to_string(), "TYPE65534");
+
+        // known types may be in the generic format as well
+        assert_eq!("TYPE1".parse::<RecordType>().unwrap(), RecordType::A);
+
+        assert!("TYPE".parse::<RecordType>().is_err());
+        assert!("TYPE65536".parse::<RecordType>().is_err());
+        assert!("TYPE-1".parse::<RecordType>().is_err());
+    }
+
+    #[test]
+    fn test_is_compressible() {
+        for rtype in [
+            RecordType::CNAME,
+            RecordType::MX,
+            RecordType::NS,
+            RecordType::SOA,
+        ] {
+            assert!(rtype.is_compressible(), "{rtype}");
+        }
+
+        for rtype in [
+            RecordType::HTTPS,
+            RecordType::NAPTR,
+            RecordType::RRSIG,
+            RecordType::SRV,
+            RecordType::SVCB,
+            RecordType::Unknown(65534),
+        ] {
+            assert!(!rtype.is_compressible(), "{rtype}");
+        }
+    }
+
     #[test]
     fn check_record_type_parse_wont_panic_with_symbols() {
         let dns_class = "a-b-c".to_ascii_uppercase().parse::<RecordType>();
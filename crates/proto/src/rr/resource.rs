@@ -7,7 +7,8 @@
 
 //! resource record implementation
 
-use std::{cmp::Ordering, convert::TryFrom, fmt};
+use alloc::{borrow::ToOwned, vec::Vec};
+use core::{borrow::Borrow, cmp::Ordering, convert::TryFrom, fmt};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -595,6 +596,38 @@ impl<R: RecordData> PartialEq for Record<R> {
     }
 }
 
+/// Sorts the records in canonical order, [RFC 4034, section 6](https://tools.ietf.org/html/rfc4034#section-6)
+///
+/// The records are ordered by owner name, see [`Name::cmp_canonical`], record type, class, and
+/// then by their RDATA in canonical form, see [`RData::cmp_canonical`]. The TTLs are not
+/// considered. Duplicate records, which only differ in TTL or in the case of names, are removed,
+/// as required by RFC 4034, section 6.3.
+///
+/// This is the order of the records of an RRset in the signed data of its RRSIG, and of the
+/// records of a zone for its ZONEMD digest.
+pub fn rrset_canonical_sort<R: Borrow<Record>>(records: &mut Vec<R>) {
+    fn cmp_owner(left: &Record, right: &Record) -> Ordering {
+        left.name()
+            .cmp_canonical(right.name())
+            .then_with(|| left.record_type().cmp(&right.record_type()))
+            .then_with(|| left.dns_class().cmp(&right.dns_class()))
+    }
+
+    let mut keyed = records
+        .drain(..)
+        .map(|record| (record.borrow().data().to_canonical_bytes(), record))
+        .collect::<Vec<_>>();
+
+    keyed.sort_by(|(left_data, left), (right_data, right)| {
+        cmp_owner(left.borrow(), right.borrow()).then_with(|| left_data.cmp(right_data))
+    });
+    keyed.dedup_by(|(right_data, right), (left_data, left)| {
+        left_data == right_data && cmp_owner((*left).borrow(), (*right).borrow()) == Ordering::Equal
+    });
+
+    records.extend(keyed.into_iter().map(|(_, record)| record));
+}
+
 /// returns the value of the compare if the items are greater or lesser, but continues on equal
 macro_rules! compare_or_equal {
     ($x:ident, $y:ident, $z:ident) => {
@@ -820,12 +853,13 @@ impl<'a, R: RecordData> TryFrom<&'a Record> for RecordRef<'a, R> {
 mod tests {
     #![allow(clippy::dbg_macro, clippy::print_stdout)]
 
-    use std::cmp::Ordering;
-    use std::str::FromStr;
+    use alloc::string::ToString;
+    use core::cmp::Ordering;
+    use core::str::FromStr;
 
     use super::*;
     use crate::rr::dns_class::DNSClass;
-    use crate::rr::rdata::{A, AAAA};
+    use crate::rr::rdata::{A, AAAA, MX};
     use crate::rr::record_data::RData;
     use crate::rr::Name;
     #[allow(clippy::useless_attribute)]
@@ -888,11 +922,88 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_rrset_canonical_sort() {
+        let name = Name::from_str("example.").unwrap();
+        let mx = |preference: u16, exchange: &str, ttl: u32| {
+            let exchange = Name::from_ascii(exchange).unwrap();
+            Record::from_rdata(name.clone(), ttl, RData::MX(MX::new(preference, exchange)))
+        };
+
+        let mut records = vec![
+            mx(10, "b.example.", 300),
+            mx(20, "a.example.", 300),
+            // duplicates, only the TTL and the case of the exchange differ
+            mx(10, "A.Example.", 300),
+            mx(10, "a.example.", 60),
+            // the RDATA are compared as octets, the length of the label sorts first
+            mx(10, "aa.example.", 300),
+            mx(10, "a.example.", 300),
+        ];
+        rrset_canonical_sort(&mut records);
+
+        let exchanges = records
+            .iter()
+            .map(|record| {
+                let mx = record.data().as_mx().unwrap();
+                (mx.preference(), mx.exchange().to_lowercase().to_string())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            exchanges,
+            vec![
+                (10, "a.example.".to_string()),
+                (10, "b.example.".to_string()),
+                (10, "aa.example.".to_string()),
+                (20, "a.example.".to_string()),
+            ]
+        );
+
+        // the owner names are sorted first, then the types
+        let mut records = vec![
+            Record::from_rdata(
+                Name::from_str("z.example.").unwrap(),
+                300,
+                RData::A(A::new(192, 0, 2, 1)),
+            ),
+            Record::from_rdata(
+                Name::from_ascii("Z.a.example.").unwrap(),
+                300,
+                RData::AAAA(AAAA::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+            ),
+            Record::from_rdata(
+                Name::from_str("yljkjljk.a.example.").unwrap(),
+                300,
+                RData::A(A::new(192, 0, 2, 1)),
+            ),
+            Record::from_rdata(
+                Name::from_str("z.a.example.").unwrap(),
+                300,
+                RData::A(A::new(192, 0, 2, 1)),
+            ),
+        ];
+        rrset_canonical_sort(&mut records);
+
+        let owners = records
+            .iter()
+            .map(|record| (record.name().to_string(), record.record_type()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            owners,
+            vec![
+                ("yljkjljk.a.example.".to_string(), RecordType::A),
+                ("z.a.example.".to_string(), RecordType::A),
+                ("Z.a.example.".to_string(), RecordType::AAAA),
+                ("z.example.".to_string(), RecordType::A),
+            ]
+        );
+    }
+
     #[cfg(feature = "mdns")]
     #[test]
     fn test_mdns_cache_flush_bit_handling() {
         const RR_CLASS_OFFSET: usize = 1 /* empty name */ +
-            std::mem::size_of::<u16>() /* rr_type */;
+            core::mem::size_of::<u16>() /* rr_type */;
 
         let mut record = Record::<RData>::stub();
         record.set_mdns_cache_flush(true);
@@ -5,7 +5,8 @@
 // https://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::{iter::Chain, slice::Iter, vec};
+use alloc::vec::{self, Vec};
+use core::{iter::Chain, slice::Iter};
 
 use tracing::{info, warn};
 
@@ -605,8 +606,8 @@ impl<'r> Iterator for RrsetRecords<'r> {
 
 #[cfg(test)]
 mod test {
-    use std::net::Ipv4Addr;
-    use std::str::FromStr;
+    use crate::net::Ipv4Addr;
+    use core::str::FromStr;
 
     use crate::rr::rdata::{CNAME, NS, SOA};
     use crate::rr::*;
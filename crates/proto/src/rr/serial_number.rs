@@ -1,6 +1,6 @@
 //! Number type to support Serial Number Arithmetics
 
-use std::{cmp::Ordering, ops::Add};
+use core::{cmp::Ordering, ops::Add};
 
 /// Wrapper type to support Serial Number Arithmetics as defined
 /// in RFC 1982. The signaure fields (expireation, inception) defined in RFC 4034, section 3.1.5
@@ -15,6 +15,12 @@ impl SerialNumber {
     }
 }
 
+impl From<u32> for SerialNumber {
+    fn from(serial: u32) -> Self {
+        Self(serial)
+    }
+}
+
 /// Serial Number Addition, see RFC 1982, section 3.1
 ///
 /// The result is a wrapping add.
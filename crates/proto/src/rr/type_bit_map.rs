@@ -7,10 +7,11 @@
 
 //! type bit map helper definitions
 
+use alloc::{collections::BTreeMap, vec::Vec};
+
 use crate::error::*;
 use crate::rr::RecordType;
 use crate::serialize::binary::*;
-use std::collections::BTreeMap;
 
 enum BitMapReadState {
     Window,
@@ -0,0 +1,317 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Authentication of the servers with TLSA records, DANE [RFC 7671](https://tools.ietf.org/html/rfc7671)
+
+use std::sync::Arc;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{CertificateError, DigitallySignedStruct, Error, RootCertStore, SignatureScheme};
+use tracing::debug;
+
+use crate::rr::rdata::tlsa::{CertUsage, Matching, Selector, TLSA};
+
+/// A verifier of the server certificates with the expected TLSA records
+///
+/// The DANE-EE (3) records match the certificate of the server, neither the name nor the validity
+/// of the certificate are checked. The DANE-TA (2) records match a trust anchor of the chain
+/// presented by the server, or the certificate of a `2 0 0` record itself, then the chain is
+/// validated as usual down to the certificate of the server. The PKIX-TA (0) and PKIX-EE (1)
+/// records are ignored.
+///
+/// If none of the records match, the certificate is verified by the fallback verifier, if any,
+/// for example the WebPKI verifier of the trust anchors of [`super::client_config`].
+#[derive(Debug)]
+pub struct DaneVerifier {
+    tlsa_records: Vec<TLSA>,
+    fallback: Option<Arc<dyn ServerCertVerifier>>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl DaneVerifier {
+    /// Creates a verifier accepting the certificates matching one of the `tlsa_records`
+    pub fn new(tlsa_records: Vec<TLSA>) -> Self {
+        Self {
+            tlsa_records,
+            fallback: None,
+            provider: Arc::new(rustls::crypto::ring::default_provider()),
+        }
+    }
+
+    /// Verify the certificates matching none of the records with `fallback`
+    pub fn with_fallback(mut self, fallback: Arc<dyn ServerCertVerifier>) -> Self {
+        self.fallback = Some(fallback);
+        self
+    }
+
+    /// The expected TLSA records
+    pub fn tlsa_records(&self) -> &[TLSA] {
+        &self.tlsa_records
+    }
+
+    /// Validates the chain of `end_entity` with `trust_anchor` as its only root
+    fn verify_chain(
+        &self,
+        trust_anchor: CertificateDer<'_>,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        let mut roots = RootCertStore::empty();
+        roots.add(trust_anchor.into_owned())?;
+
+        WebPkiServerVerifier::builder_with_provider(Arc::new(roots), self.provider.clone())
+            .build()
+            .map_err(|e| Error::General(e.to_string()))?
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+    }
+}
+
+impl ServerCertVerifier for DaneVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        for tlsa in &self.tlsa_records {
+            match tlsa.cert_usage() {
+                CertUsage::DomainIssued if tlsa.verify_tlsa(end_entity) => {
+                    debug!("server certificate matched DANE-EE record: {tlsa}");
+                    return Ok(ServerCertVerified::assertion());
+                }
+                CertUsage::TrustAnchor => {
+                    let mut trust_anchors = intermediates
+                        .iter()
+                        .filter(|cert| tlsa.verify_tlsa(cert))
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    if let (Selector::Full, Matching::Raw) = (tlsa.selector(), tlsa.matching()) {
+                        trust_anchors.push(CertificateDer::from(tlsa.cert_data()));
+                    }
+
+                    for trust_anchor in trust_anchors {
+                        match self.verify_chain(
+                            trust_anchor,
+                            end_entity,
+                            intermediates,
+                            server_name,
+                            ocsp_response,
+                            now,
+                        ) {
+                            Ok(verified) => {
+                                debug!("server certificate matched DANE-TA record: {tlsa}");
+                                return Ok(verified);
+                            }
+                            Err(e) => debug!("DANE-TA chain validation failed: {e}"),
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        match &self.fallback {
+            Some(fallback) => fallback.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                ocsp_response,
+                now,
+            ),
+            None => Err(Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            )),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use rustls::pki_types::pem::PemObject;
+
+    use super::*;
+
+    const SELF_SIGNED: &[u8] = include_bytes!("../../tests/test-data/tlsa-cert.der");
+
+    fn server_name() -> ServerName<'static> {
+        ServerName::try_from("ns.example.com").unwrap()
+    }
+
+    fn sha256(cert: &[u8]) -> Vec<u8> {
+        ring::digest::digest(&ring::digest::SHA256, cert)
+            .as_ref()
+            .to_vec()
+    }
+
+    /// The certificate of ns.example.com and the CA which issued it
+    fn ca_chain() -> (CertificateDer<'static>, CertificateDer<'static>) {
+        let server_path = env::var("TDNS_WORKSPACE_ROOT").unwrap_or_else(|_| "../..".to_owned());
+        let cert = CertificateDer::from_pem_file(format!("{server_path}/tests/test-data/cert.pem"))
+            .unwrap();
+        let ca =
+            CertificateDer::from_pem_file(format!("{server_path}/tests/test-data/ca.pem")).unwrap();
+        (cert, ca)
+    }
+
+    #[test]
+    fn test_dane_ee() {
+        let cert = CertificateDer::from(SELF_SIGNED);
+        let verify = |tlsa| {
+            DaneVerifier::new(vec![tlsa]).verify_server_cert(
+                &cert,
+                &[],
+                &server_name(),
+                &[],
+                UnixTime::now(),
+            )
+        };
+
+        // the self-signed certificate is accepted, even though it isn't issued for the name
+        let tlsa = TLSA::new(
+            CertUsage::DomainIssued,
+            Selector::Full,
+            Matching::Sha256,
+            sha256(SELF_SIGNED),
+        );
+        assert!(verify(tlsa).is_ok());
+
+        let tlsa = TLSA::new(
+            CertUsage::DomainIssued,
+            Selector::Full,
+            Matching::Sha256,
+            vec![0; 32],
+        );
+        assert!(verify(tlsa).is_err());
+
+        // the certificate must be the certificate of the server with DANE-EE
+        let tlsa = TLSA::new(
+            CertUsage::DomainIssued,
+            Selector::Full,
+            Matching::Sha256,
+            sha256(&ca_chain().1),
+        );
+        let (cert, ca) = ca_chain();
+        assert!(DaneVerifier::new(vec![tlsa])
+            .verify_server_cert(&cert, &[ca], &server_name(), &[], UnixTime::now())
+            .is_err());
+    }
+
+    #[test]
+    fn test_dane_ta() {
+        let (cert, ca) = ca_chain();
+        let tlsa = TLSA::new(
+            CertUsage::TrustAnchor,
+            Selector::Full,
+            Matching::Sha256,
+            sha256(&ca),
+        );
+        let verifier = DaneVerifier::new(vec![tlsa]);
+
+        assert!(verifier
+            .verify_server_cert(
+                &cert,
+                std::slice::from_ref(&ca),
+                &server_name(),
+                &[],
+                UnixTime::now()
+            )
+            .is_ok());
+
+        // the chain is validated, including the name of the server
+        let other_name = ServerName::try_from("other.example.com").unwrap();
+        assert!(verifier
+            .verify_server_cert(
+                &cert,
+                std::slice::from_ref(&ca),
+                &other_name,
+                &[],
+                UnixTime::now()
+            )
+            .is_err());
+
+        // the trust anchor must be presented, unless the record holds the certificate
+        assert!(verifier
+            .verify_server_cert(&cert, &[], &server_name(), &[], UnixTime::now())
+            .is_err());
+        let tlsa = TLSA::new(
+            CertUsage::TrustAnchor,
+            Selector::Full,
+            Matching::Raw,
+            ca.to_vec(),
+        );
+        assert!(DaneVerifier::new(vec![tlsa])
+            .verify_server_cert(&cert, &[], &server_name(), &[], UnixTime::now())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_fallback() {
+        let (cert, ca) = ca_chain();
+        let mut roots = RootCertStore::empty();
+        roots.add(ca).unwrap();
+        let webpki = WebPkiServerVerifier::builder_with_provider(
+            Arc::new(roots),
+            Arc::new(rustls::crypto::ring::default_provider()),
+        )
+        .build()
+        .unwrap();
+
+        let verifier = DaneVerifier::new(vec![]);
+        assert!(verifier
+            .verify_server_cert(&cert, &[], &server_name(), &[], UnixTime::now())
+            .is_err());
+
+        let verifier = verifier.with_fallback(webpki);
+        assert!(verifier
+            .verify_server_cert(&cert, &[], &server_name(), &[], UnixTime::now())
+            .is_ok());
+    }
+}
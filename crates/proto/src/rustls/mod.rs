@@ -10,12 +10,16 @@
 use std::sync::Arc;
 
 use rustls::{ClientConfig, RootCertStore};
+
+use crate::rr::rdata::TLSA;
 #[cfg(feature = "native-certs")]
 use tracing::warn;
 
+pub mod dane;
 pub mod tls_client_stream;
 pub mod tls_stream;
 
+pub use self::dane::DaneVerifier;
 pub use self::tls_client_stream::{
     tls_client_connect, tls_client_connect_with_bind_addr, TlsClientStream,
 };
@@ -55,3 +59,16 @@ pub fn client_config() -> Result<ClientConfig, Box<dyn std::error::Error>> {
             .with_no_client_auth(),
     )
 }
+
+/// Make a new [`ClientConfig`] authenticating the servers with the expected TLSA records only
+///
+/// See [`DaneVerifier`] for the DANE-TA and DANE-EE records accepted, use
+/// [`DaneVerifier::with_fallback`] for a fallback to the usual validation of the certificates.
+pub fn dane_client_config(tlsa_records: Vec<TLSA>) -> ClientConfig {
+    ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(DaneVerifier::new(tlsa_records)))
+        .with_no_client_auth()
+}
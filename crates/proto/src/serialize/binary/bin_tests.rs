@@ -17,7 +17,7 @@
 
 use super::*;
 use crate::error::*;
-use std::fmt::Debug;
+use core::fmt::Debug;
 
 fn get_character_data() -> Vec<(&'static str, Vec<u8>)> {
     vec![
@@ -14,6 +14,8 @@
  * limitations under the License.
  */
 
+use alloc::{borrow::ToOwned, vec::Vec};
+
 use crate::serialize::binary::Restrict;
 use thiserror::Error;
 
@@ -31,6 +33,11 @@ pub struct BinDecoder<'a> {
 
 pub(crate) type DecodeResult<T> = Result<T, DecodeError>;
 
+/// The maximum number of compression pointers followed for a name
+///
+/// Names have at most 127 labels, this allows for each of them to be behind a pointer.
+pub(crate) const MAX_COMPRESSION_POINTERS: usize = 127;
+
 /// An error that can occur deep in a decoder
 /// This type is kept very small so that function that use it inline often
 #[derive(Clone, Copy, Debug, Error)]
@@ -55,6 +62,13 @@ pub enum DecodeError {
         ptr: u16,
     },
 
+    /// More compression pointers than allowed were followed for a name
+    #[error("too many compression pointers, exceeded at idx: {idx}")]
+    TooManyCompressionPointers {
+        /// index of the pointer exceeding the limit
+        idx: usize,
+    },
+
     /// Label bytes exceeded the limit of 63
     #[error("label bytes exceed 63: {0}")]
     LabelBytesTooLong(usize),
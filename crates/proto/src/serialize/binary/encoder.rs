@@ -5,7 +5,8 @@
 // https://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::marker::PhantomData;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
 
 use crate::{
     error::{ProtoErrorKind, ProtoResult},
@@ -16,6 +17,8 @@ use super::BinEncodable;
 
 // this is private to make sure there is no accidental access to the inner buffer.
 mod private {
+    use alloc::vec::Vec;
+
     use crate::error::{ProtoErrorKind, ProtoResult};
 
     /// A wrapper for a buffer that guarantees writes never exceed a defined set of bytes
@@ -37,6 +40,11 @@ mod private {
             self.max_size = max as usize;
         }
 
+        /// Returns the maximum size to enforce
+        pub(super) fn max_size(&self) -> usize {
+            self.max_size
+        }
+
         pub(super) fn write(&mut self, offset: usize, data: &[u8]) -> ProtoResult<()> {
             debug_assert!(offset <= self.buffer.len());
             if offset + data.len() > self.max_size {
@@ -149,6 +157,11 @@ impl<'a> BinEncoder<'a> {
         self.buffer.set_max_size(max);
     }
 
+    /// Returns the maximum size of the buffer
+    pub fn max_size(&self) -> u16 {
+        self.buffer.max_size() as u16
+    }
+
     /// Returns a reference to the internal buffer
     pub fn into_bytes(self) -> &'a Vec<u8> {
         self.buffer.into_bytes()
@@ -491,7 +504,7 @@ pub enum EncodeMode {
 
 #[cfg(test)]
 mod tests {
-    use std::str::FromStr;
+    use core::str::FromStr;
 
     use super::*;
     use crate::{
@@ -612,7 +625,7 @@ mod tests {
     }
 
     #[test]
-    fn test_target_compression() {
+    fn test_no_target_compression() {
         let mut msg = Message::new();
         msg.add_query(Query::query(
             Name::from_str("www.google.com.").unwrap(),
@@ -638,7 +651,7 @@ mod tests {
                 Name::from_str("www.compressme.com.").unwrap(),
             )),
         ))
-        // name here should use compressed label from target in previous records
+        // name here must not point to the uncompressed target in previous records, RFC 3597
         .add_answer(Record::from_rdata(
             Name::from_str("www.compressme.com.").unwrap(),
             0,
@@ -646,8 +659,8 @@ mod tests {
         ));
 
         let bytes = msg.to_vec().unwrap();
-        // label is compressed pointing to target, would be 145 otherwise
-        assert_eq!(bytes.len(), 130);
+        // label is not compressed pointing to target, would be 130 otherwise
+        assert_eq!(bytes.len(), 145);
         // check re-serializing
         assert!(Message::from_vec(&bytes).is_ok());
     }
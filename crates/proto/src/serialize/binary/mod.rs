@@ -10,7 +10,9 @@
 mod decoder;
 mod encoder;
 mod restrict;
+pub mod wire;
 
+pub(crate) use self::decoder::MAX_COMPRESSION_POINTERS;
 pub use self::decoder::{BinDecoder, DecodeError};
 pub use self::encoder::BinEncoder;
 pub use self::encoder::EncodeMode;
@@ -19,6 +21,8 @@ pub use self::restrict::{Restrict, RestrictedMath, Verified};
 #[cfg(test)]
 pub(crate) mod bin_tests;
 
+use alloc::vec::Vec;
+
 use crate::error::*;
 
 /// A type which can be encoded into a DNS binary format
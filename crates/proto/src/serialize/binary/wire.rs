@@ -0,0 +1,331 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Decompression of names without copying their labels
+//!
+//! This is the decoding of names on the wire, [`Name`](crate::rr::Name) is read from a
+//! [`WireName`], with a single copy of the labels.
+//!
+//! ```
+//! use hickory_proto::serialize::binary::{wire::WireName, BinDecoder};
+//!
+//! let bytes = b"\x07example\x03com\x00";
+//! let name = WireName::read(&mut BinDecoder::new(bytes)).unwrap();
+//! assert!(name.is_borrowed());
+//! assert_eq!(name.to_string(), "example.com.");
+//! ```
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::fmt;
+
+use crate::serialize::binary::{BinDecoder, DecodeError, Restrict, MAX_COMPRESSION_POINTERS};
+
+/// The maximum length of a name on the wire, including the root label
+const MAX_NAME_LEN: usize = 255;
+
+/// The maximum length of a label
+const MAX_LABEL_LEN: usize = 63;
+
+/// A decompressed name, in its uncompressed wire format
+///
+/// The name borrows the labels from the message when they are contiguous in it, i.e. when the
+/// name is not compressed, or when it is only a pointer to labels which are not compressed
+/// themselves. Otherwise the labels are assembled into an owned buffer.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct WireName<'a>(Cow<'a, [u8]>);
+
+impl<'a> WireName<'a> {
+    /// Reads the possibly compressed name at the current position of `decoder`
+    ///
+    /// Compression pointers must point before the start of the labels containing them, which
+    /// rules out loops, and at most 127 pointers are followed.
+    pub fn read(decoder: &mut BinDecoder<'a>) -> Result<Self, DecodeError> {
+        let mut labels_start = decoder.index();
+        let (mut labels, mut pointer) = read_labels(decoder, None, 0)?;
+
+        // labels split by pointers are assembled, pointers are followed without recursion
+        let mut assembled = Vec::new();
+        let mut pointers = 0;
+        while let Some(location) = pointer {
+            if pointers >= MAX_COMPRESSION_POINTERS {
+                return Err(DecodeError::TooManyCompressionPointers {
+                    idx: labels_start + labels.len(),
+                });
+            }
+            pointers += 1;
+
+            assembled.extend_from_slice(labels);
+            (labels, pointer) = read_labels(
+                &mut decoder.clone(location),
+                Some(labels_start),
+                assembled.len(),
+            )?;
+            labels_start = usize::from(location);
+        }
+
+        if assembled.is_empty() {
+            return Ok(Self(Cow::Borrowed(labels)));
+        }
+        assembled.extend_from_slice(labels);
+        Ok(Self(Cow::Owned(assembled)))
+    }
+
+    /// The name in the wire format, a sequence of labels each preceded by its length, ending with
+    /// the root label
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns `true` if the labels are borrowed from the message
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self.0, Cow::Borrowed(_))
+    }
+
+    /// Copies the labels if they are borrowed from the message
+    pub fn into_owned(self) -> WireName<'static> {
+        WireName(Cow::Owned(self.0.into_owned()))
+    }
+
+    /// Returns an iterator over the labels of the name, without the root label
+    pub fn labels(&self) -> impl Iterator<Item = &[u8]> {
+        let mut remaining = &self.0[..];
+        core::iter::from_fn(move || {
+            let (&length, rest) = remaining.split_first()?;
+            if length == 0 {
+                return None;
+            }
+            let (label, rest) = rest.split_at(usize::from(length));
+            remaining = rest;
+            Some(label)
+        })
+    }
+
+    /// Returns `true` if this is the root name
+    pub fn is_root(&self) -> bool {
+        self.0[..] == [0]
+    }
+}
+
+impl fmt::Display for WireName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_root() {
+            return f.write_str(".");
+        }
+
+        for label in self.labels() {
+            for &byte in label {
+                match byte {
+                    b'.' | b'\\' => write!(f, "\\{}", char::from(byte))?,
+                    0x21..=0x7E => write!(f, "{}", char::from(byte))?,
+                    _ => write!(f, "\\{byte:03}")?,
+                }
+            }
+            f.write_str(".")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the labels at the current position of `decoder`, up to the root label or a pointer
+///
+/// Returns the labels, including the root label, and the location of the pointer. `max_idx` is
+/// the start of the labels which pointed to these labels, which they must not reach, and
+/// `name_len` is the length of the labels read so far for the name.
+fn read_labels<'a>(
+    decoder: &mut BinDecoder<'a>,
+    max_idx: Option<usize>,
+    name_len: usize,
+) -> Result<(&'a [u8], Option<u16>), DecodeError> {
+    let labels_start = decoder.index();
+
+    loop {
+        // this protects against overlapping labels
+        if let Some(max_idx) = max_idx {
+            if decoder.index() >= max_idx {
+                return Err(DecodeError::LabelOverlapsWithOther {
+                    label: labels_start,
+                    other: max_idx,
+                });
+            }
+        }
+
+        match decoder.peek().map(Restrict::unverified /*verified below*/) {
+            None => return Err(DecodeError::InsufficientBytes),
+            Some(0) => {
+                decoder.pop()?;
+                return Ok((decoder.slice_from(labels_start)?, None));
+            }
+            Some(byte) if byte & 0b1100_0000 == 0b1100_0000 => {
+                let labels = decoder.slice_from(labels_start)?;
+                let pointer_location = decoder.index();
+                let location = decoder
+                    .read_u16()?
+                    .map(|u| u & 0x3FFF)
+                    .verify_unwrap(|ptr| usize::from(*ptr) < labels_start)
+                    .map_err(|ptr| DecodeError::PointerNotPriorToLabel {
+                        idx: pointer_location,
+                        ptr,
+                    })?;
+
+                return Ok((labels, Some(location)));
+            }
+            Some(byte) if byte & 0b1100_0000 == 0 => {
+                decoder
+                    .read_character_data()?
+                    .verify_unwrap(|l| l.len() <= MAX_LABEL_LEN)
+                    .map_err(|l| DecodeError::LabelBytesTooLong(l.len()))?;
+
+                // the root label must still fit
+                let len = name_len + decoder.index() - labels_start;
+                if len + 1 > MAX_NAME_LEN {
+                    return Err(DecodeError::DomainNameTooLong(len));
+                }
+            }
+            Some(byte) => return Err(DecodeError::UnrecognizedLabelCode(byte)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    /// Reads the name at `index` of `bytes`
+    fn read_name(bytes: &[u8], index: u16) -> Result<WireName<'_>, DecodeError> {
+        WireName::read(&mut BinDecoder::new(bytes).clone(index))
+    }
+
+    #[test]
+    fn test_uncompressed_name_is_borrowed() {
+        let bytes = b"\x03www\x07example\x03com\x00";
+        let name = read_name(bytes, 0).unwrap();
+        assert!(name.is_borrowed());
+        assert_eq!(name.as_bytes(), bytes);
+        assert_eq!(name.to_string(), "www.example.com.");
+
+        let owned = name.clone().into_owned();
+        assert!(!owned.is_borrowed());
+        assert_eq!(owned, name);
+    }
+
+    #[test]
+    fn test_compressed_names() {
+        // example.com. at 0, www.example.com. at 13, and pointers to each of them
+        let mut bytes = b"\x07example\x03com\x00".to_vec();
+        bytes.extend_from_slice(b"\x03www\xC0\x00");
+        bytes.extend_from_slice(&[0xC0, 0]);
+        bytes.extend_from_slice(&[0xC0, 13]);
+        bytes.extend_from_slice(&[0xC0, 21]);
+
+        // the labels before the pointer are assembled with the ones pointed to
+        let name = read_name(&bytes, 13).unwrap();
+        assert!(!name.is_borrowed());
+        assert_eq!(name.to_string(), "www.example.com.");
+
+        // a name which is only a pointer to uncompressed labels borrows them
+        let mut decoder = BinDecoder::new(&bytes).clone(19);
+        let name = WireName::read(&mut decoder).unwrap();
+        assert_eq!(decoder.index(), 21);
+        assert!(name.is_borrowed());
+        assert_eq!(name.as_bytes(), &bytes[..13]);
+
+        // also through a chain of pointers, unless labels are split by one of them
+        let name = read_name(&bytes, 23).unwrap();
+        assert!(!name.is_borrowed());
+        assert_eq!(name.to_string(), "www.example.com.");
+        assert_eq!(name, read_name(&bytes, 13).unwrap());
+    }
+
+    #[test]
+    fn test_pointer_loops() {
+        // a pointer to itself
+        let bytes = [0x01, b'a', 0xC0, 2];
+        assert!(matches!(
+            read_name(&bytes, 2),
+            Err(DecodeError::PointerNotPriorToLabel { idx: 2, ptr: 2 })
+        ));
+
+        // two pointers pointing to each other
+        let bytes = [0xC0, 2, 0xC0, 0];
+        assert!(matches!(
+            read_name(&bytes, 0),
+            Err(DecodeError::PointerNotPriorToLabel { idx: 0, ptr: 2 })
+        ));
+        assert!(matches!(
+            read_name(&bytes, 2),
+            Err(DecodeError::PointerNotPriorToLabel { idx: 0, ptr: 2 })
+        ));
+
+        // a label pointing to a pointer back to the label
+        let bytes = [0xC0, 2, 0x01, b'a', 0xC0, 2];
+        assert!(matches!(
+            read_name(&bytes, 2),
+            Err(DecodeError::PointerNotPriorToLabel { idx: 4, ptr: 2 })
+        ));
+
+        // labels pointed to running into the labels of the pointer
+        let bytes = [0x01, b'a', 0x01, b'b', 0xC0, 0];
+        assert!(matches!(
+            read_name(&bytes, 2),
+            Err(DecodeError::LabelOverlapsWithOther { label: 0, other: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_long_names() {
+        // long chains of pointers are rejected
+        let mut bytes = vec![0];
+        let mut last = 0_u16;
+        for _ in 0..MAX_COMPRESSION_POINTERS {
+            let next = bytes.len() as u16;
+            bytes.extend_from_slice(&(0xC000 | last).to_be_bytes());
+            last = next;
+        }
+        let name = read_name(&bytes, last).unwrap();
+        assert!(name.is_root());
+        assert!(name.is_borrowed());
+
+        let pointer = bytes.len();
+        bytes.extend_from_slice(&(0xC000 | last).to_be_bytes());
+        assert!(matches!(
+            read_name(&bytes, pointer as u16),
+            Err(DecodeError::TooManyCompressionPointers { idx: 1 })
+        ));
+
+        // names assembled from pointers must not exceed 255 bytes
+        let mut bytes = vec![0];
+        let mut start = 0_u16;
+        for _ in 0..4 {
+            let next = bytes.len() as u16;
+            bytes.push(63);
+            bytes.extend_from_slice(&[b'a'; 63]);
+            bytes.extend_from_slice(&(0xC000 | start).to_be_bytes());
+            start = next;
+        }
+        assert!(matches!(
+            read_name(&bytes, start),
+            Err(DecodeError::DomainNameTooLong(_))
+        ));
+    }
+
+    #[test]
+    fn test_escaped_labels() {
+        let mut decoder = BinDecoder::new(b"\x03a.b\x02\x00\\\x00");
+        let name = WireName::read(&mut decoder).unwrap();
+        assert_eq!(name.to_string(), "a\\.b.\\000\\\\.");
+        assert_eq!(name.labels().count(), 2);
+
+        assert_eq!(
+            WireName::read(&mut BinDecoder::new(&[0]))
+                .unwrap()
+                .to_string(),
+            "."
+        );
+    }
+}
@@ -62,19 +62,27 @@ impl RDataParser for RData {
         tokens: I,
         origin: Option<&Name>,
     ) -> ParseResult<Self> {
+        let mut tokens = tokens.peekable();
+        if tokens.peek() == Some(&generic::GENERIC_MARKER) {
+            return generic::parse(record_type, tokens);
+        }
+
         let rdata = match record_type {
             RecordType::A => Self::A(a::parse(tokens)?),
             RecordType::AAAA => Self::AAAA(aaaa::parse(tokens)?),
             RecordType::ANAME => Self::ANAME(ANAME(name::parse(tokens, origin)?)),
             RecordType::ANY => return Err(ParseError::from("parsing ANY doesn't make sense")),
+            RecordType::APL => Self::APL(apl::parse(tokens)?),
             RecordType::AXFR => return Err(ParseError::from("parsing AXFR doesn't make sense")),
             RecordType::CAA => caa::parse(tokens).map(Self::CAA)?,
             RecordType::CERT => Self::CERT(cert::parse(tokens)?),
             RecordType::CNAME => Self::CNAME(CNAME(name::parse(tokens, origin)?)),
             RecordType::CSYNC => csync::parse(tokens).map(Self::CSYNC)?,
+            RecordType::DHCID => Self::DHCID(dhcid::parse(tokens)?),
             RecordType::HINFO => Self::HINFO(hinfo::parse(tokens)?),
             RecordType::HTTPS => svcb::parse(tokens).map(HTTPS).map(Self::HTTPS)?,
             RecordType::IXFR => return Err(ParseError::from("parsing IXFR doesn't make sense")),
+            RecordType::LOC => Self::LOC(loc::parse(tokens)?),
             RecordType::MX => Self::MX(mx::parse(tokens, origin)?),
             RecordType::NAPTR => Self::NAPTR(naptr::parse(tokens, origin)?),
             RecordType::NULL => Self::NULL(null::parse(tokens)?),
@@ -88,6 +96,7 @@ impl RDataParser for RData {
             RecordType::SVCB => svcb::parse(tokens).map(Self::SVCB)?,
             RecordType::TLSA => Self::TLSA(tlsa::parse(tokens)?),
             RecordType::TXT => Self::TXT(txt::parse(tokens)?),
+            RecordType::ZONEMD => Self::ZONEMD(zonemd::parse(tokens)?),
             RecordType::SIG => return Err(ParseError::from("parsing SIG doesn't make sense")),
             RecordType::DNSKEY => {
                 return Err(ParseError::from("DNSKEY should be dynamically generated"))
@@ -118,8 +127,8 @@ impl RDataParser for RData {
             RecordType::TSIG => return Err(ParseError::from("TSIG is only used during AXFR")),
             #[allow(deprecated)]
             RecordType::ZERO => Self::ZERO,
+            // the rdata of unknown types can only be in the generic format
             r @ RecordType::Unknown(..) => {
-                // TODO: add a way to associate generic record types to the zone
                 return Err(ParseError::from(ParseErrorKind::UnsupportedRecordType(r)));
             }
         };
@@ -0,0 +1,120 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! APL records for lists of address prefixes
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::rr::rdata::apl::{AplItem, FAMILY_IPV4, FAMILY_IPV6};
+use crate::rr::rdata::sshfp::HEX;
+use crate::rr::rdata::APL;
+use crate::serialize::txt::errors::{ParseErrorKind, ParseResult};
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 3123](https://tools.ietf.org/html/rfc3123#section-5)
+///
+/// ```text
+/// 5. Zone File Syntax
+///
+///    <owner>   IN   <TTL>   APL   {[!]afi:address/prefix}*
+///
+///    The data consists of zero or more strings of the address family
+///    indicator <afi>, immediately followed by a colon ":", an address,
+///    immediately followed by the "/" character, immediately followed by a
+///    decimal numeric value for the prefix length.  Any such string may be
+///    preceded by a "!" character.  The strings are separated by
+///    whitespace.  The <afi> is the decimal numeric value of that
+///    particular address family.
+/// ```
+pub(crate) fn parse<'i, I: Iterator<Item = &'i str>>(tokens: I) -> ParseResult<APL> {
+    tokens
+        .map(parse_item)
+        .collect::<ParseResult<_>>()
+        .map(APL::new)
+}
+
+/// Parses a single `[!]afi:address/prefix` item
+fn parse_item(token: &str) -> ParseResult<AplItem> {
+    let (negation, item) = match token.strip_prefix('!') {
+        Some(item) => (true, item),
+        None => (false, token),
+    };
+
+    let (family, item) = item
+        .split_once(':')
+        .ok_or_else(|| ParseErrorKind::Msg(format!("APL item is missing the family: {token}")))?;
+    let (address, prefix) = item
+        .rsplit_once('/')
+        .ok_or_else(|| ParseErrorKind::Msg(format!("APL item is missing the prefix: {token}")))?;
+
+    let prefix = prefix.parse::<u8>()?;
+    let address = match family.parse::<u16>()? {
+        FAMILY_IPV4 => IpAddr::from(address.parse::<Ipv4Addr>()?),
+        FAMILY_IPV6 => IpAddr::from(address.parse::<Ipv6Addr>()?),
+        // the address of the other families is the hexadecimal of its address family dependent part
+        family => {
+            let afd_part = HEX.decode(address.as_bytes())?;
+            return Ok(AplItem::from_afd_part(negation, family, prefix, afd_part)?);
+        }
+    };
+
+    Ok(AplItem::new(negation, address, prefix)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parsing() {
+        // RFC 3123, section 6
+        let apl = parse("1:192.168.32.0/21 !1:192.168.38.0/28".split(' ')).unwrap();
+        assert_eq!(apl.items().len(), 2);
+        assert!(!apl.items()[0].negation());
+        assert_eq!(apl.items()[0].prefix(), 21);
+        assert_eq!(apl.items()[0].afd_part(), &[192, 168, 32]);
+        assert!(apl.items()[1].negation());
+        assert_eq!(apl.to_string(), "1:192.168.32.0/21 !1:192.168.38.0/28");
+
+        let apl =
+            parse("1:224.0.0.0/4 2:FF00:0:0:0:0:0:0:0/8 !2:2001:db8::/32 !1:0.0.0.0/0".split(' '))
+                .unwrap();
+        assert_eq!(apl.items()[1].family(), FAMILY_IPV6);
+        assert_eq!(apl.items()[1].afd_part(), &[0xff]);
+        assert_eq!(apl.items()[3].afd_length(), 0);
+        assert_eq!(
+            apl.to_string(),
+            "1:224.0.0.0/4 2:ff00::/8 !2:2001:db8::/32 !1:0.0.0.0/0"
+        );
+
+        // the unknown families round-trip
+        let apl = parse("!3:0A00/8 4:/0".split(' ')).unwrap();
+        assert_eq!(apl.items()[0].family(), 3);
+        assert_eq!(apl.items()[0].afd_part(), &[10, 0]);
+        assert_eq!(apl.items()[1].afd_length(), 0);
+        assert_eq!(apl.to_string(), "!3:0a00/8 4:/0");
+        assert_eq!(parse(apl.to_string().split(' ')).unwrap(), apl);
+
+        let apl = parse(std::iter::empty()).unwrap();
+        assert!(apl.items().is_empty());
+        assert_eq!(apl.to_string(), "");
+
+        for invalid in [
+            "192.168.32.0/21",
+            "1:192.168.32.0",
+            "1:192.168.32.0/33",
+            "2:2001:db8::/129",
+            "1:2001:db8::/32",
+            "2:192.168.32.0/21",
+            "!!1:192.168.32.0/21",
+            "1:192.168.32.0/-1",
+        ] {
+            assert!(parse(std::iter::once(invalid)).is_err(), "{invalid}");
+        }
+    }
+}
@@ -121,6 +121,43 @@ mod tests {
             parsed_rdata => panic!("Parsed RData is not CAA: {:?}", parsed_rdata),
         }
     }
+    #[test]
+    fn test_parsing_issuer_fields() {
+        let caa = parse(vec!["0", "issue", "ca.example.net; account=123"].into_iter()).unwrap();
+        assert!(caa.is_issue());
+        assert!(!caa.is_issuewild());
+        assert!(!caa.is_iodef());
+        assert!(!caa.issuer_critical());
+
+        let (name, key_values) = caa.issuer().expect("issue should have an issuer");
+        assert_eq!(name, Some(&Name::parse("ca.example.net", None).unwrap()));
+        assert_eq!(key_values, &[KeyValue::new("account", "123")]);
+
+        // no issuance allowed
+        let caa = parse(vec!["0", "issuewild", ";"].into_iter()).unwrap();
+        assert!(caa.is_issuewild());
+        assert_eq!(caa.issuer(), Some((None, &[][..])));
+
+        let caa = parse(vec!["0", "iodef", "mailto:security@example.com"].into_iter()).unwrap();
+        assert!(caa.is_iodef());
+        assert!(caa.issuer().is_none());
+        assert!(!caa.is_unknown_critical());
+    }
+
+    #[test]
+    fn test_parsing_unknown_critical() {
+        let caa = parse(vec!["128", "tbs", "Unknown"].into_iter()).unwrap();
+        assert!(caa.issuer_critical());
+        assert!(caa.is_unknown_critical());
+        assert!(caa.issuer().is_none());
+
+        let caa = parse(vec!["0", "tbs", "Unknown"].into_iter()).unwrap();
+        assert!(!caa.is_unknown_critical());
+
+        let caa = parse(vec!["128", "issue", "ca.example.net"].into_iter()).unwrap();
+        assert!(!caa.is_unknown_critical());
+    }
+
     #[test]
     fn test_parsing() {
         //nocerts       CAA 0 issue \";\"
@@ -0,0 +1,71 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DHCID records for the association of DHCP clients with their names
+
+use crate::rr::rdata::DHCID;
+use crate::serialize::txt::errors::{ParseErrorKind, ParseResult};
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 4701](https://tools.ietf.org/html/rfc4701#section-3.2)
+///
+/// ```text
+/// 3.2.  DHCID Presentation Format
+///
+///    In DNS master files, the RDATA is represented as a single block in
+///    base-64 encoding identical to that used for representing binary data
+///    in [3], Section 3.  The data may be divided up into any number of
+///    white-space-separated substrings, each of which is a valid base-64
+///    encoding, which are then concatenated.
+/// ```
+pub(crate) fn parse<'i, I: Iterator<Item = &'i str>>(tokens: I) -> ParseResult<DHCID> {
+    let encoded = tokens.collect::<String>();
+    if encoded.is_empty() {
+        return Err(ParseErrorKind::Message("DHCID rdata field is missing").into());
+    }
+
+    let rdata = data_encoding::BASE64.decode(encoded.as_bytes())?;
+    if rdata.len() < 3 {
+        return Err(ParseErrorKind::Message("DHCID rdata is too short").into());
+    }
+
+    let identifier_type = u16::from_be_bytes([rdata[0], rdata[1]]).into();
+    let digest_type = rdata[2].into();
+    Ok(DHCID::new(
+        identifier_type,
+        digest_type,
+        rdata[3..].to_vec(),
+    ))
+}
+
+#[test]
+fn test_parsing() {
+    use crate::rr::rdata::dhcid::{DigestType, IdentifierType};
+
+    assert!(parse(::std::iter::empty()).is_err());
+    assert!(parse(vec!["äöüäööüä"].into_iter()).is_err());
+    assert!(parse(vec!["AAI="].into_iter()).is_err());
+
+    // RFC 4701, section 3.6.1, the DUID of a DHCPv6 client of client.example.com.
+    let dhcid =
+        parse(vec!["AAIBY2/AuCccgoJbsaxcQc9TUapptP69l", "OjxfNuVAA2kjEA="].into_iter()).unwrap();
+    assert_eq!(dhcid.identifier_type(), IdentifierType::Duid);
+    assert_eq!(dhcid.digest_type(), DigestType::SHA256);
+    assert_eq!(dhcid.digest().len(), 32);
+    assert_eq!(
+        dhcid.to_string(),
+        "AAIBY2/AuCccgoJbsaxcQc9TUapptP69lOjxfNuVAA2kjEA="
+    );
+
+    let unknown = parse(vec!["EjQH"].into_iter()).unwrap();
+    assert_eq!(
+        unknown.identifier_type(),
+        IdentifierType::Unassigned(0x1234)
+    );
+    assert_eq!(unknown.digest_type(), DigestType::Unassigned(7));
+}
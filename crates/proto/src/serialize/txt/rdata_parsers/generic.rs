@@ -0,0 +1,132 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! generic format of the rdata of any record type
+
+use crate::rr::rdata::{sshfp, NULL};
+use crate::rr::{RData, RecordType};
+use crate::serialize::binary::{BinDecoder, Restrict};
+use crate::serialize::txt::errors::{ParseError, ParseErrorKind, ParseResult};
+
+/// The token starting rdata in the generic format
+pub(crate) const GENERIC_MARKER: &str = "\\#";
+
+/// [RFC 3597](https://tools.ietf.org/html/rfc3597#section-5), Unknown DNS RR Types, September 2003
+///
+/// ```text
+/// 5.  Text Representation
+///
+///    The RDATA section of an RR of unknown type is represented as a
+///    sequence of white space separated words as follows:
+///
+///       The special token \# (a backslash immediately followed by a hash
+///       sign), which identifies the RDATA as having the generic encoding
+///       defined herein rather than a traditional type-specific encoding.
+///
+///       An unsigned decimal integer specifying the RDATA length in octets.
+///
+///       Zero or more words of hexadecimal data encoding the actual RDATA
+///       field, each containing an even number of hexadecimal digits.
+///
+///    ...
+///
+///    Even though an RR of known type represented in the \# format is
+///    effectively treated as an unknown type for the purpose of parsing the
+///    RDATA text representation, all further processing by the server MUST
+///    treat it as a known type and take into account any applicable type-
+///    specific rules regarding compression, canonicalization, etc.
+/// ```
+///
+/// The rdata of known types is decoded from the wire format, so that it is the same as if it
+/// had been in the format of the type.
+pub(crate) fn parse<'i, I: Iterator<Item = &'i str>>(
+    record_type: RecordType,
+    mut tokens: I,
+) -> ParseResult<RData> {
+    if tokens.next() != Some(GENERIC_MARKER) {
+        return Err(ParseErrorKind::Message("generic rdata must start with \\#").into());
+    }
+
+    let length = tokens
+        .next()
+        .ok_or_else(|| ParseErrorKind::MissingToken("generic rdata length".to_string()))?
+        .parse::<u16>()?;
+    let mut data = Vec::with_capacity(length as usize);
+    for word in tokens {
+        data.extend(sshfp::HEX.decode(word.as_bytes())?);
+    }
+
+    if data.len() != length as usize {
+        return Err(ParseError::from(format!(
+            "generic rdata length {length} does not match the {} bytes of data",
+            data.len()
+        )));
+    }
+
+    if let RecordType::Unknown(_) = record_type {
+        let rdata = match data.is_empty() {
+            true => NULL::new(),
+            false => NULL::with(data),
+        };
+        return Ok(RData::Unknown {
+            code: record_type,
+            rdata,
+        });
+    }
+
+    let mut decoder = BinDecoder::new(&data);
+    Ok(RData::read(
+        &mut decoder,
+        record_type,
+        Restrict::new(length),
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rr::rdata::A;
+
+    #[test]
+    fn test_parsing() {
+        assert_eq!(
+            parse(
+                RecordType::Unknown(65534),
+                ["\\#", "4", "DEADBEEF"].into_iter()
+            )
+            .unwrap(),
+            RData::Unknown {
+                code: RecordType::Unknown(65534),
+                rdata: NULL::with(vec![0xde, 0xad, 0xbe, 0xef]),
+            }
+        );
+
+        // the data may be split into words, and the rdata of known types is decoded
+        assert_eq!(
+            parse(RecordType::A, ["\\#", "4", "c000", "0201"].into_iter()).unwrap(),
+            RData::A(A::new(192, 0, 2, 1))
+        );
+
+        assert_eq!(
+            parse(RecordType::Unknown(65534), ["\\#", "0"].into_iter()).unwrap(),
+            RData::Unknown {
+                code: RecordType::Unknown(65534),
+                rdata: NULL::new(),
+            }
+        );
+
+        assert!(parse(RecordType::Unknown(65534), ["\\#"].into_iter()).is_err());
+        assert!(parse(RecordType::Unknown(65534), ["\\#", "4", "DEAD"].into_iter()).is_err());
+        assert!(parse(
+            RecordType::Unknown(65534),
+            ["\\#", "2", "DEADBEEF"].into_iter()
+        )
+        .is_err());
+        assert!(parse(RecordType::Unknown(65534), ["\\#", "1", "D"].into_iter()).is_err());
+        assert!(parse(RecordType::A, ["\\#", "3", "C00002"].into_iter()).is_err());
+    }
+}
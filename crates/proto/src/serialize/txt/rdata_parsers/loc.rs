@@ -0,0 +1,227 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! LOC records for the geographical location of hosts, networks and subnets
+
+use std::iter::Peekable;
+
+use crate::rr::rdata::loc::{Precision, DEGREE, EQUATOR, PRIME_MERIDIAN, REFERENCE_SPHEROID};
+use crate::rr::rdata::LOC;
+use crate::serialize::txt::errors::{ParseError, ParseErrorKind, ParseResult};
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 1876](https://tools.ietf.org/html/rfc1876#section-3)
+///
+/// ```text
+/// 3. Master File Format
+///
+///    The LOC record is expressed in a master file in the following format:
+///
+///    <owner> <TTL> <class> LOC ( d1 [m1 [s1]] {"N"|"S"} d2 [m2 [s2]]
+///                                {"E"|"W"} alt["m"] [siz["m"] [hp["m"]
+///                                [vp["m"]]]] )
+///
+///    If omitted, minutes and seconds default to zero, size defaults to 1m,
+///    horizontal precision defaults to 10000m, and vertical precision
+///    defaults to 10m.  These defaults are chosen to represent typical
+///    ZIP/postal code area sizes, since it is often easy to find
+///    approximate geographical location by ZIP/postal code.
+/// ```
+pub(crate) fn parse<'i, I: Iterator<Item = &'i str>>(tokens: I) -> ParseResult<LOC> {
+    let mut tokens = tokens.peekable();
+
+    let latitude = parse_angle(&mut tokens, 90, EQUATOR, ("N", "S"))?;
+    let longitude = parse_angle(&mut tokens, 180, PRIME_MERIDIAN, ("E", "W"))?;
+
+    let altitude = tokens
+        .next()
+        .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken("altitude".to_string())))?;
+    let altitude = parse_meters(altitude)
+        .and_then(|altitude| u32::try_from(altitude + i64::from(REFERENCE_SPHEROID)).ok())
+        .ok_or_else(|| ParseErrorKind::Msg(format!("invalid LOC altitude: {altitude}")))?;
+
+    let mut precision = |default: Precision| -> ParseResult<Precision> {
+        let Some(token) = tokens.next() else {
+            return Ok(default);
+        };
+
+        match parse_meters(token) {
+            Some(centimeters @ 0..) if centimeters as u64 <= Precision::MAX_CENTIMETERS => {
+                Ok(Precision::from_centimeters(centimeters as u64))
+            }
+            _ => Err(ParseErrorKind::Msg(format!("invalid LOC size or precision: {token}")).into()),
+        }
+    };
+
+    let size = precision(Precision::DEFAULT_SIZE)?;
+    let horizontal_precision = precision(Precision::DEFAULT_HORIZONTAL)?;
+    let vertical_precision = precision(Precision::DEFAULT_VERTICAL)?;
+
+    if let Some(token) = tokens.next() {
+        return Err(ParseErrorKind::Msg(format!("unexpected LOC token: {token}")).into());
+    }
+
+    Ok(LOC::new(
+        latitude,
+        longitude,
+        altitude,
+        size,
+        horizontal_precision,
+        vertical_precision,
+    ))
+}
+
+/// Parses `d [m [s]] {positive|negative}` into thousandths of a second of arc from the reference
+fn parse_angle<'i, I: Iterator<Item = &'i str>>(
+    tokens: &mut Peekable<I>,
+    max_degrees: u32,
+    reference: u32,
+    (positive, negative): (&str, &str),
+) -> ParseResult<u32> {
+    let is_hemisphere =
+        |token: &&str| token.eq_ignore_ascii_case(positive) || token.eq_ignore_ascii_case(negative);
+
+    let degrees = tokens
+        .next()
+        .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken("degrees".to_string())))?
+        .parse::<u32>()?;
+    let minutes = match tokens.next_if(|token| !is_hemisphere(token)) {
+        Some(minutes) => minutes.parse::<u32>()?,
+        None => 0,
+    };
+    let thousandths = match tokens.next_if(|token| !is_hemisphere(token)) {
+        Some(seconds) => parse_decimal(seconds, 3)
+            .and_then(|seconds| u32::try_from(seconds).ok())
+            .ok_or_else(|| ParseErrorKind::Msg(format!("invalid LOC seconds: {seconds}")))?,
+        None => 0,
+    };
+    let hemisphere = tokens
+        .next_if(is_hemisphere)
+        .ok_or_else(|| ParseErrorKind::Msg(format!("expected LOC {positive} or {negative}")))?;
+
+    if minutes >= 60 || thousandths >= 60_000 {
+        return Err(ParseErrorKind::Message("LOC minutes or seconds out of range").into());
+    }
+
+    let angle = degrees
+        .checked_mul(DEGREE)
+        .and_then(|angle| angle.checked_add(minutes * 60_000 + thousandths))
+        .filter(|angle| *angle <= max_degrees * DEGREE)
+        .ok_or_else(|| ParseErrorKind::Msg(format!("LOC angle beyond {max_degrees} degrees")))?;
+
+    if hemisphere.eq_ignore_ascii_case(positive) {
+        Ok(reference + angle)
+    } else {
+        Ok(reference - angle)
+    }
+}
+
+/// Parses a distance in meters with an optional `m` suffix into centimeters
+fn parse_meters(token: &str) -> Option<i64> {
+    parse_decimal(token.strip_suffix(['m', 'M']).unwrap_or(token), 2)
+}
+
+/// Parses a decimal number with at most `scale` fractional digits, multiplied by `10^scale`
+fn parse_decimal(token: &str, scale: u32) -> Option<i64> {
+    let (negative, token) = match token.strip_prefix('-') {
+        Some(token) => (true, token),
+        None => (false, token),
+    };
+    let (integer, fraction) = token.split_once('.').unwrap_or((token, ""));
+
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !digits(integer) || (token.contains('.') && !digits(fraction)) {
+        return None;
+    }
+    if fraction.len() > scale as usize || integer.len() > 12 {
+        return None;
+    }
+
+    let value = integer.parse::<i64>().ok()? * 10_i64.pow(scale)
+        + fraction.parse::<i64>().unwrap_or(0) * 10_i64.pow(scale - fraction.len() as u32);
+    Some(if negative { -value } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parsing() {
+        // RFC 1876, section 4
+        let loc = parse("42 21 54 N 71 06 18 W -24m 30m".split(' ')).unwrap();
+        assert_eq!(loc.latitude(), EQUATOR + 152_514_000);
+        assert_eq!(loc.longitude(), PRIME_MERIDIAN - 255_978_000);
+        assert_eq!(loc.altitude(), REFERENCE_SPHEROID - 2400);
+        assert_eq!(loc.size(), Precision::new(3, 3).unwrap());
+        assert_eq!(loc.horizontal_precision(), Precision::DEFAULT_HORIZONTAL);
+        assert_eq!(loc.vertical_precision(), Precision::DEFAULT_VERTICAL);
+        assert_eq!(
+            loc.to_string(),
+            "42 21 54.000 N 71 6 18.000 W -24.00m 30m 10000m 10m"
+        );
+
+        let loc = parse("42 21 43.952 N 71 5 6.344 W -24m 1m 200m".split(' ')).unwrap();
+        assert_eq!(loc.latitude(), EQUATOR + 152_503_952);
+        assert_eq!(loc.horizontal_precision(), Precision::new(2, 4).unwrap());
+        assert_eq!(
+            loc.to_string(),
+            "42 21 43.952 N 71 5 6.344 W -24.00m 1m 200m 10m"
+        );
+
+        let loc = parse("52 14 05 N 00 08 50 E 10m".split(' ')).unwrap();
+        assert_eq!(loc.longitude(), PRIME_MERIDIAN + 530_000);
+        assert_eq!(loc.size(), Precision::DEFAULT_SIZE);
+
+        let loc = parse("32 7 19 S 116 2 25 E 10m".split(' ')).unwrap();
+        assert_eq!(loc.latitude(), EQUATOR - 115_639_000);
+
+        let loc = parse("90 S 180 W 42849672.95m 90000000m 0.05m 0m".split(' ')).unwrap();
+        assert_eq!(loc.latitude(), EQUATOR - 90 * DEGREE);
+        assert_eq!(loc.longitude(), PRIME_MERIDIAN - 180 * DEGREE);
+        assert_eq!(loc.altitude(), u32::MAX);
+        assert_eq!(
+            loc.to_string(),
+            "90 0 0.000 S 180 0 0.000 W 42849672.95m 90000000m 0.05m 0m"
+        );
+
+        for invalid in [
+            "",
+            "42 21 54 N",
+            "42 21 54 E 71 06 18 W -24m",
+            "91 N 71 W -24m",
+            "42 60 N 71 W -24m",
+            "42 21 60 N 71 W -24m",
+            "42 21 54.1234 N 71 W -24m",
+            "42 N 181 W -24m",
+            "42 N 71 W -100000.01m",
+            "42 N 71 W 42849672.96m",
+            "42 N 71 W 1.234m",
+            "42 N 71 W 0m -1m",
+            "42 N 71 W 0m 90000000.01m",
+            "42 N 71 W 0m 1m 1m 1m 1m",
+        ] {
+            assert!(parse(invalid.split_whitespace()).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_parse_decimal() {
+        assert_eq!(parse_decimal("1", 2), Some(100));
+        assert_eq!(parse_decimal("1.5", 2), Some(150));
+        assert_eq!(parse_decimal("-0.05", 2), Some(-5));
+        assert_eq!(parse_decimal("59.999", 3), Some(59_999));
+        assert_eq!(parse_meters("30m"), Some(3000));
+
+        assert_eq!(parse_decimal("", 2), None);
+        assert_eq!(parse_decimal("1.", 2), None);
+        assert_eq!(parse_decimal(".5", 2), None);
+        assert_eq!(parse_decimal("+1", 2), None);
+        assert_eq!(parse_decimal("1.234", 2), None);
+    }
+}
@@ -21,14 +21,18 @@
 // each of these module's has the parser for that rdata embedded, to keep the file sizes down...
 pub(crate) mod a;
 pub(crate) mod aaaa;
+pub(crate) mod apl;
 pub(crate) mod caa;
 pub(crate) mod cert;
 pub(crate) mod csync;
+pub(crate) mod dhcid;
 #[cfg(feature = "dnssec-ring")]
 pub(crate) mod dnskey;
 #[cfg(feature = "dnssec-ring")]
 pub(crate) mod ds;
+pub(crate) mod generic;
 pub(crate) mod hinfo;
+pub(crate) mod loc;
 pub(crate) mod mx;
 pub(crate) mod name;
 pub(crate) mod naptr;
@@ -40,3 +44,4 @@ pub(crate) mod sshfp;
 pub(crate) mod svcb;
 pub(crate) mod tlsa;
 pub(crate) mod txt;
+pub(crate) mod zonemd;
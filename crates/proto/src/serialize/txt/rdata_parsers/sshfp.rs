@@ -158,3 +158,46 @@ fn test_parsing() {
         ],
     );
 }
+
+#[cfg(feature = "dnssec-ring")]
+#[test]
+fn test_matches() {
+    use crate::rr::rdata::sshfp::Algorithm;
+
+    // an ssh-ed25519 key of the bytes 0..32
+    let mut public_key = b"\0\0\0\x0bssh-ed25519\0\0\0\x20".to_vec();
+    public_key.extend(0..32);
+    assert_eq!(
+        Algorithm::from_public_key(&public_key),
+        Some(Algorithm::Ed25519)
+    );
+
+    let sha256 = parse(
+        vec![
+            "4",
+            "2",
+            "66402c9468c58941dd19ffd650bf2b42f9226f83d3bd06ad515d0e5104a77020",
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+    assert!(sha256.matches(&public_key));
+
+    let sha1 =
+        parse(vec!["4", "1", "568be87a0fbb623a91793addce529ff4c254abd3"].into_iter()).unwrap();
+    assert!(sha1.matches(&public_key));
+
+    // a different key, algorithm, or an unknown fingerprint type do not match
+    let mut other_key = public_key.clone();
+    *other_key.last_mut().unwrap() ^= 1;
+    assert!(!sha256.matches(&other_key));
+
+    let rsa =
+        parse(vec!["1", "1", "568be87a0fbb623a91793addce529ff4c254abd3"].into_iter()).unwrap();
+    assert!(!rsa.matches(&public_key));
+
+    let unknown =
+        parse(vec!["4", "42", "568be87a0fbb623a91793addce529ff4c254abd3"].into_iter()).unwrap();
+    assert!(!unknown.matches(&public_key));
+    assert!(!sha256.matches(b"not a key"));
+}
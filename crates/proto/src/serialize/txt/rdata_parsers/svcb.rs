@@ -7,13 +7,11 @@
 
 //! SVCB records in presentation format
 
+use std::net::{Ipv4Addr, Ipv6Addr};
 use std::str::FromStr;
 
 use crate::{
-    rr::{
-        rdata::{svcb::*, A, AAAA},
-        Name,
-    },
+    rr::{rdata::svcb::*, Name},
     serialize::txt::{
         errors::{ParseError, ParseErrorKind, ParseResult},
         Lexer, Token,
@@ -249,7 +247,7 @@ fn parse_ipv4_hint(value: Option<&str>) -> Result<SvcParamValue, ParseError> {
         ParseError::from(ParseErrorKind::Message("expected at least one ipv4 hint"))
     })?;
 
-    let hints = parse_list::<A>(value)?;
+    let hints = parse_list::<Ipv4Addr>(value)?;
     Ok(SvcParamValue::Ipv4Hint(IpHint(hints)))
 }
 
@@ -266,7 +264,7 @@ fn parse_ipv6_hint(value: Option<&str>) -> Result<SvcParamValue, ParseError> {
         ParseError::from(ParseErrorKind::Message("expected at least one ipv6 hint"))
     })?;
 
-    let hints = parse_list::<AAAA>(value)?;
+    let hints = parse_list::<Ipv6Addr>(value)?;
     Ok(SvcParamValue::Ipv6Hint(IpHint(hints)))
 }
 
@@ -401,7 +399,10 @@ mod tests {
         assert_eq!(SvcParamKey::Ipv4Hint, param.0);
         assert_eq!(
             param.1.as_ipv4_hint().expect("ipv4hint").0,
-            &[A::new(162, 159, 137, 85), A::new(162, 159, 138, 85)]
+            &[
+                Ipv4Addr::new(162, 159, 137, 85),
+                Ipv4Addr::new(162, 159, 138, 85)
+            ]
         );
 
         // echconfig
@@ -418,8 +419,8 @@ mod tests {
         assert_eq!(
             param.1.as_ipv6_hint().expect("ipv6hint").0,
             &[
-                AAAA::new(0x2606, 0x4700, 0x7, 0, 0, 0, 0xa29f, 0x8955),
-                AAAA::new(0x2606, 0x4700, 0x7, 0, 0, 0, 0xa29f, 0x8a5)
+                Ipv6Addr::new(0x2606, 0x4700, 0x7, 0, 0, 0, 0xa29f, 0x8955),
+                Ipv6Addr::new(0x2606, 0x4700, 0x7, 0, 0, 0, 0xa29f, 0x8a5)
             ]
         );
     }
@@ -529,8 +530,8 @@ mod tests {
                 params: vec![(
                     SvcParamKey::Ipv6Hint,
                     SvcParamValue::Ipv6Hint(IpHint(vec![
-                        AAAA::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1),
-                        AAAA::new(0x2001, 0xdb8, 0, 0, 0, 0, 0x53, 1),
+                        Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1),
+                        Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0x53, 1),
                     ])),
                 )],
             },
@@ -542,7 +543,7 @@ mod tests {
                 priority: 1,
                 params: vec![(
                     SvcParamKey::Ipv6Hint,
-                    SvcParamValue::Ipv6Hint(IpHint(vec![AAAA::new(
+                    SvcParamValue::Ipv6Hint(IpHint(vec![Ipv6Addr::new(
                         0x2001, 0xdb8, 0x122, 0x344, 0, 0, 0xc000, 0x221,
                     )])),
                 )],
@@ -567,7 +568,7 @@ mod tests {
                     ),
                     (
                         SvcParamKey::Ipv4Hint,
-                        SvcParamValue::Ipv4Hint(IpHint(vec![A::new(192, 0, 2, 1)])),
+                        SvcParamValue::Ipv4Hint(IpHint(vec![Ipv4Addr::new(192, 0, 2, 1)])),
                     ),
                 ],
             },
@@ -0,0 +1,99 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! ZONEMD records for message digests of zone data
+
+use crate::rr::rdata::{sshfp, ZONEMD};
+use crate::serialize::txt::errors::{ParseError, ParseErrorKind, ParseResult};
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 8976](https://www.rfc-editor.org/rfc/rfc8976#section-2.3)
+///
+/// ```text
+/// 2.3.  ZONEMD Presentation Format
+///
+///    The Digest MUST be represented as a sequence of case-insensitive
+///    hexadecimal digits.  Whitespace is allowed within the hexadecimal
+///    text.
+///
+/// 2.4.  ZONEMD Example
+///
+///    example.com. 86400 IN ZONEMD 2018031500 1 1 (
+///        FEBE3D4CE2EC2FFA4BA99D46CD69D6D29711E55217057BEE
+///        7EB1A7B641A47BA7FED2DD5B97AE499FAFA4F22C6BD647DE )
+/// ```
+pub(crate) fn parse<'i, I: Iterator<Item = &'i str>>(mut tokens: I) -> ParseResult<ZONEMD> {
+    fn missing_field<E: From<ParseErrorKind>>(field: &str) -> E {
+        ParseErrorKind::Msg(format!("ZONEMD {field} field missing")).into()
+    }
+
+    let serial = tokens
+        .next()
+        .ok_or_else(|| missing_field::<ParseError>("serial"))?
+        .parse::<u32>()?;
+    let (scheme, hash_algorithm) = {
+        let mut parse_u8 = |field: &str| {
+            tokens
+                .next()
+                .ok_or_else(|| missing_field(field))
+                .and_then(|t| t.parse::<u8>().map_err(ParseError::from))
+        };
+        (
+            parse_u8("scheme")?.into(),
+            parse_u8("hash algorithm")?.into(),
+        )
+    };
+
+    // whitespace is allowed within the digest, so it may span several tokens
+    let digest = tokens.collect::<String>();
+    if digest.is_empty() {
+        return Err(missing_field("digest"));
+    }
+    let digest = sshfp::HEX.decode(digest.as_bytes())?;
+    if digest.len() < ZONEMD::MIN_DIGEST_LEN {
+        return Err(ParseErrorKind::Message("ZONEMD digest is shorter than 12 octets").into());
+    }
+
+    Ok(ZONEMD::new(serial, scheme, hash_algorithm, digest))
+}
+
+#[test]
+fn test_parsing() {
+    use crate::rr::rdata::zonemd::{HashAlgorithm, Scheme};
+
+    assert!(parse(::std::iter::empty()).is_err());
+    assert!(parse(vec!["2018031500", "1"].into_iter()).is_err());
+    assert!(parse(vec!["2018031500", "1", "1"].into_iter()).is_err());
+    assert!(parse(vec!["2018031500", "1", "1", "abcd"].into_iter()).is_err());
+    assert!(parse(vec!["-1", "1", "1", "000102030405060708090a0b"].into_iter()).is_err());
+
+    assert_eq!(
+        parse(
+            vec![
+                "2018031500",
+                "1",
+                "1",
+                "FEBE3D4CE2EC2FFA4BA99D46CD69D6D29711E55217057BEE",
+                "7EB1A7B641A47BA7FED2DD5B97AE499FAFA4F22C6BD647DE",
+            ]
+            .into_iter()
+        )
+        .unwrap(),
+        ZONEMD::new(
+            2018031500,
+            Scheme::Simple,
+            HashAlgorithm::SHA384,
+            sshfp::HEX
+                .decode(
+                    b"febe3d4ce2ec2ffa4ba99d46cd69d6d29711e55217057bee\
+                      7eb1a7b641a47ba7fed2dd5b97ae499fafa4f22c6bd647de"
+                )
+                .unwrap(),
+        )
+    );
+}
@@ -243,6 +243,7 @@ impl TryFrom<LexToken> for Token {
             | LexToken::Include
             | LexToken::Origin
             | LexToken::Ttl
+            | LexToken::Generate
             | LexToken::List(_) => return Err(ParseErrorKind::UnexpectedToken(token).into()),
             LexToken::Blank => Self::Blank,
             LexToken::CharData(data) => Self::CharData(data),
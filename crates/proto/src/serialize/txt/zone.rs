@@ -122,6 +122,19 @@ use crate::{
 /// ;               Semicolon is used to start a comment; the remainder of
 ///                 the line is ignored.
 /// ```
+///
+/// The `$GENERATE` directive of BIND is supported as well, it produces a record for each value of
+/// a range:
+///
+/// ```text
+/// $GENERATE <start>-<stop>[/<step>] <owner> [<TTL>] [<class>] <type> <RDATA>
+/// ```
+///
+/// Each `$` of the owner and the RDATA is replaced by the value, `${offset[,width[,base]]}` adds
+/// the offset to the value and formats it with at least `width` digits in the base, one of `d`
+/// (decimal, the default), `o` (octal), `x` or `X` (hexadecimal) and `n` or `N` (reversed
+/// nibbles, as in `ip6.arpa.` names). `\$` is a literal `$`. A directive produces at most
+/// 65536 records.
 pub struct Parser<'a> {
     lexers: Vec<(Lexer<'a>, Option<PathBuf>)>,
     origin: Option<Name>,
@@ -157,6 +170,8 @@ impl<'a> Parser<'a> {
         let mut ttl: Option<u32> = None;
         let mut state = State::StartLine;
         let mut stack = self.lexers.len();
+        // the origins of the files including the current one, restored at the end of it
+        let mut origins = Vec::new();
 
         'outer: while let Some((lexer, path)) = self.lexers.last_mut() {
             while let Some(t) = lexer.next_token()? {
@@ -167,7 +182,8 @@ impl<'a> Parser<'a> {
 
                         match t {
                             // if Dollar, then $INCLUDE or $ORIGIN
-                            Token::Include => State::Include(None),
+                            Token::Include => State::Include(vec![]),
+                            Token::Generate => State::Generate(vec![]),
                             Token::Origin => State::Origin,
                             Token::Ttl => State::Ttl,
 
@@ -206,9 +222,13 @@ impl<'a> Parser<'a> {
                             _ => return Err(ParseErrorKind::UnexpectedToken(t).into()),
                         }
                     }
-                    State::Include(include_path) => match (t, include_path) {
-                        (Token::CharData(data), None) => State::Include(Some(data)),
-                        (Token::EOL, Some(include_path)) => {
+                    State::Include(mut parts) => match t {
+                        // the file name and the optional origin
+                        Token::CharData(data) if parts.len() < 2 => {
+                            parts.push(data);
+                            State::Include(parts)
+                        }
+                        Token::EOL if !parts.is_empty() => {
                             // RFC1035 (section 5) does not specify how filename for $INCLUDE
                             // should be resolved into file path. The underlying code implements the
                             // following:
@@ -226,7 +246,12 @@ impl<'a> Parser<'a> {
                                 .into());
                             }
 
-                            let include = Path::new(&include_path);
+                            let include_origin = match parts.get(1) {
+                                Some(name) => Some(Name::parse(name, origin.as_ref())?),
+                                None => origin.clone(),
+                            };
+
+                            let include = Path::new(&parts[0]);
                             let include = match (include.is_absolute(), path) {
                                 (true, _) => include.to_path_buf(),
                                 (false, Some(path)) => path
@@ -244,19 +269,27 @@ impl<'a> Parser<'a> {
                             let input = fs::read_to_string(&include)?;
                             let lexer = Lexer::new(input);
                             self.lexers.push((lexer, Some(include)));
+                            origins.push(mem::replace(&mut origin, include_origin));
                             stack += 1;
                             state = State::StartLine;
                             continue 'outer;
                         }
-                        (Token::CharData(_), Some(_)) => {
-                            return Err(ParseErrorKind::Message(
-                                "Domain name for $INCLUDE is not supported",
-                            )
-                            .into());
+                        t => return Err(ParseErrorKind::UnexpectedToken(t).into()),
+                    },
+                    State::Generate(mut parts) => match t {
+                        Token::CharData(data) => {
+                            parts.push(data);
+                            State::Generate(parts)
+                        }
+                        Token::At => {
+                            parts.push("@".to_string());
+                            State::Generate(parts)
                         }
-                        (t, _) => {
-                            return Err(ParseErrorKind::UnexpectedToken(t).into());
+                        Token::EOL => {
+                            Self::generate(parts, &origin, ttl, class, &mut records)?;
+                            State::StartLine
                         }
+                        _ => return Err(ParseErrorKind::UnexpectedToken(t).into()),
                     },
                     State::TtlClassType => {
                         match t {
@@ -324,8 +357,8 @@ impl<'a> Parser<'a> {
             }
 
             // Extra flush at the end for the case of missing endline
-            if let State::Record(record_parts) = mem::replace(&mut state, State::StartLine) {
-                Self::flush_record(
+            match mem::replace(&mut state, State::StartLine) {
+                State::Record(record_parts) => Self::flush_record(
                     record_parts,
                     &origin,
                     &current_name,
@@ -333,11 +366,17 @@ impl<'a> Parser<'a> {
                     &mut ttl,
                     class,
                     &mut records,
-                )?;
+                )?,
+                State::Generate(parts) => Self::generate(parts, &origin, ttl, class, &mut records)?,
+                _ => {}
             }
 
             stack -= 1;
             self.lexers.pop();
+            // an included file never changes the origin of the including one
+            if let Some(parent_origin) = origins.pop() {
+                origin = parent_origin;
+            }
         }
 
         //
@@ -425,6 +464,95 @@ impl<'a> Parser<'a> {
         Ok(())
     }
 
+    /// Adds the records of a `$GENERATE` directive
+    fn generate(
+        parts: Vec<String>,
+        origin: &Option<Name>,
+        ttl: Option<u32>,
+        class: DNSClass,
+        records: &mut BTreeMap<RrKey, RecordSet>,
+    ) -> ParseResult<()> {
+        let mut parts = parts.into_iter();
+        let mut next = |field: &str| {
+            parts.next().ok_or_else(|| {
+                ParseError::from(ParseErrorKind::MissingToken(format!("$GENERATE {field}")))
+            })
+        };
+
+        let range = next("range")?;
+        let owner = next("owner")?;
+        let (mut ttl, mut class) = (ttl, class);
+        let rtype = loop {
+            let mut data = next("type")?;
+            if let Ok(parsed) = Self::parse_time(&data) {
+                ttl = Some(parsed);
+                continue;
+            }
+            data.make_ascii_uppercase();
+            match DNSClass::from_str(&data) {
+                Ok(parsed) => class = parsed,
+                Err(_) => break RecordType::from_str(&data)?,
+            }
+        };
+        let rdata = next("rdata")?;
+        if parts.next().is_some() {
+            return Err(ParseErrorKind::Message("too many fields for $GENERATE").into());
+        }
+
+        let (range, step) = match range.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>()?),
+            None => (range.as_str(), 1),
+        };
+        let (start, stop) = range
+            .split_once('-')
+            .ok_or_else(|| ParseError::from(format!("invalid $GENERATE range: {range}")))?;
+        let (start, stop) = (start.parse::<u32>()?, stop.parse::<u32>()?);
+        if start > stop || step == 0 {
+            return Err(ParseError::from(format!(
+                "invalid $GENERATE range: {range}"
+            )));
+        }
+        if (stop - start) / step >= MAX_GENERATE_RECORDS {
+            return Err(ParseError::from(format!(
+                "$GENERATE range of more than {MAX_GENERATE_RECORDS} records: {range}"
+            )));
+        }
+
+        for value in (start..=stop).step_by(step as usize) {
+            let mut record_ttl = ttl;
+            let name = match owner.as_str() {
+                "@" => origin.clone(),
+                owner => Some(Name::parse(
+                    &generate_substitute(owner, value)?,
+                    origin.as_ref(),
+                )?),
+            };
+
+            let mut record_parts = Vec::new();
+            let mut lexer = Lexer::new(generate_substitute(&rdata, value)?);
+            while let Some(token) = lexer.next_token()? {
+                match token {
+                    Token::CharData(data) => record_parts.push(data),
+                    Token::List(list) => record_parts.extend(list),
+                    Token::Blank | Token::EOL => {}
+                    t => return Err(ParseErrorKind::UnexpectedToken(t).into()),
+                }
+            }
+
+            Self::flush_record(
+                record_parts,
+                origin,
+                &name,
+                Some(rtype),
+                &mut record_ttl,
+                class,
+                records,
+            )?;
+        }
+
+        Ok(())
+    }
+
     /// parses the string following the rules from:
     ///  <https://tools.ietf.org/html/rfc2308> (NXCaching RFC) and
     ///  <https://www.zytrax.com/books/dns/apa/time.html>
@@ -518,16 +646,114 @@ enum State {
     TtlClassType, // [<TTL>] [<class>] <type>,
     Ttl,          // $TTL <time>
     Record(Vec<String>),
-    Include(Option<String>), // $INCLUDE <filename>
+    Include(Vec<String>),  // $INCLUDE <filename> [<domain-name>]
+    Generate(Vec<String>), // $GENERATE <range> <owner> [<TTL>] [<class>] <type> <RDATA>
     Origin,
 }
 
+/// Replaces the iterator of a `$GENERATE` template, `$` or `${offset[,width[,base]]}`, by `value`
+fn generate_substitute(template: &str, value: u32) -> ParseResult<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match (ch, chars.peek()) {
+            ('\\', Some('$')) => {
+                output.push('$');
+                chars.next();
+            }
+            ('$', Some('{')) => {
+                chars.next();
+                let mut modifier = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(ch) => modifier.push(ch),
+                        None => {
+                            return Err(ParseError::from(format!(
+                                "unterminated $GENERATE modifier: {template}"
+                            )))
+                        }
+                    }
+                }
+                output.push_str(&generate_modifier(&modifier, value)?);
+            }
+            ('$', _) => output.push_str(&value.to_string()),
+            (ch, _) => output.push(ch),
+        }
+    }
+
+    Ok(output)
+}
+
+/// Formats `value` following a `$GENERATE` modifier, `offset[,width[,base]]`
+fn generate_modifier(modifier: &str, value: u32) -> ParseResult<String> {
+    let mut fields = modifier.split(',');
+    let offset = match fields.next() {
+        Some("") | None => 0,
+        Some(offset) => offset.parse::<i64>()?,
+    };
+    let width = match fields.next() {
+        Some(width) => width.parse::<usize>()?,
+        None => 0,
+    };
+    let base = fields.next().unwrap_or("d");
+    // the value is part of a name, which is at most 255 octets long
+    if fields.next().is_some() || width > 255 {
+        return Err(ParseError::from(format!(
+            "invalid $GENERATE modifier: {modifier}"
+        )));
+    }
+
+    let value = i64::from(value)
+        .checked_add(offset)
+        .ok_or_else(|| ParseError::from(format!("overflow of $GENERATE modifier: {modifier}")))?;
+    let value = u64::try_from(value).map_err(|_| {
+        ParseError::from(format!("negative value of $GENERATE modifier: {modifier}"))
+    })?;
+    Ok(match base {
+        "d" => format!("{value:0width$}"),
+        "o" => format!("{value:0width$o}"),
+        "x" => format!("{value:0width$x}"),
+        "X" => format!("{value:0width$X}"),
+        "n" | "N" => {
+            // the nibbles from the least significant one, separated by dots
+            let mut nibbles = String::new();
+            let (mut value, mut width) = (value, width);
+            loop {
+                let nibble = char::from_digit((value & 0xf) as u32, 16).unwrap_or_default();
+                nibbles.push(match base {
+                    "N" => nibble.to_ascii_uppercase(),
+                    _ => nibble,
+                });
+                value >>= 4;
+                width = width.saturating_sub(1);
+                if value == 0 && width == 0 {
+                    break;
+                }
+                nibbles.push('.');
+                width = width.saturating_sub(1);
+            }
+            nibbles
+        }
+        _ => {
+            return Err(ParseError::from(format!(
+                "invalid base of $GENERATE modifier: {modifier}"
+            )))
+        }
+    })
+}
+
 /// Max traversal depth for $INCLUDE files
 const MAX_INCLUDE_LEVEL: usize = 256;
 
+/// Max number of records of a $GENERATE directive
+const MAX_GENERATE_RECORDS: u32 = 65536;
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rr::rdata::{A, NULL};
+    use crate::serialize::binary::{BinDecodable, BinEncodable};
 
     #[test]
     #[allow(clippy::uninlined_format_args)]
@@ -550,4 +776,172 @@ mod tests {
             result
         );
     }
+
+    #[test]
+    fn test_generic_rdata() {
+        let zone_data = r#"$ORIGIN example.com.
+unknown 60 IN TYPE65534 \# 4 DEADBEEF
+empty 60 IN TYPE65535 \# 0
+a 60 IN A \# 4 C0000201
+"#;
+
+        let (_, records) = Parser::new(zone_data, None, None).parse().unwrap();
+        let record = |name: &str, record_type| {
+            let key = RrKey::new(LowerName::from_str(name).unwrap(), record_type);
+            records[&key]
+                .records_without_rrsigs()
+                .next()
+                .unwrap()
+                .clone()
+        };
+
+        let unknown = record("unknown.example.com.", RecordType::Unknown(65534));
+        assert_eq!(
+            unknown.data(),
+            &RData::Unknown {
+                code: RecordType::Unknown(65534),
+                rdata: NULL::with(vec![0xde, 0xad, 0xbe, 0xef]),
+            }
+        );
+        assert_eq!(
+            unknown.to_string(),
+            "unknown.example.com. 60 IN TYPE65534 \\# 4 DEADBEEF"
+        );
+        let empty = record("empty.example.com.", RecordType::Unknown(65535));
+        assert_eq!(
+            empty.to_string(),
+            "empty.example.com. 60 IN TYPE65535 \\# 0"
+        );
+        let a = record("a.example.com.", RecordType::A);
+        assert_eq!(a.data(), &RData::A(A::new(192, 0, 2, 1)));
+
+        // round trip through the wire format, empty rdata is read as an update instead
+        let bytes = unknown.to_bytes().unwrap();
+        assert_eq!(Record::from_bytes(&bytes).unwrap(), unknown);
+
+        // and back through the parser
+        for record in [unknown, empty] {
+            let zone_data = format!("$ORIGIN example.com.\n{record}\n");
+            let (_, records) = Parser::new(zone_data, None, None).parse().unwrap();
+            let key = RrKey::new(record.name().into(), record.record_type());
+            assert_eq!(records[&key].records_without_rrsigs().next(), Some(&record));
+        }
+    }
+
+    #[test]
+    fn test_generate() {
+        let zone_data = r#"$ORIGIN example.com.
+$TTL 3600
+$GENERATE 1-10 host$ A 10.0.0.$
+$GENERATE 0-8/4 ${10,3}.rev 300 IN PTR host${0,2,x}.example.com.
+$GENERATE 10-11 ${0,4,n}.nibble TXT value\$$
+$GENERATE 1-2 @ MX "$0 mail$"
+"#;
+
+        let (_, records) = Parser::new(zone_data, None, None).parse().unwrap();
+        let record = |name: &str, record_type| {
+            let key = RrKey::new(LowerName::from_str(name).unwrap(), record_type);
+            records[&key]
+                .records_without_rrsigs()
+                .next()
+                .unwrap()
+                .clone()
+        };
+
+        let hosts = records
+            .values()
+            .filter(|set| set.record_type() == RecordType::A)
+            .collect::<Vec<_>>();
+        assert_eq!(hosts.len(), 10);
+        for i in 1..=10 {
+            let host = record(&format!("host{i}.example.com."), RecordType::A);
+            assert_eq!(host.data(), &RData::A(A::new(10, 0, 0, i)));
+            assert_eq!(host.ttl(), 3600);
+        }
+
+        for (owner, target) in [("010", "host00"), ("014", "host04"), ("018", "host08")] {
+            let ptr = record(&format!("{owner}.rev.example.com."), RecordType::PTR);
+            assert_eq!(
+                ptr.to_string(),
+                format!("{owner}.rev.example.com. 300 IN PTR {target}.example.com.")
+            );
+        }
+        assert_eq!(records.len(), 10 + 3 + 2 + 1);
+
+        let txt = record("a.0.0.nibble.example.com.", RecordType::TXT);
+        assert_eq!(
+            txt.to_string(),
+            "a.0.0.nibble.example.com. 3600 IN TXT value$10"
+        );
+        record("b.0.0.nibble.example.com.", RecordType::TXT);
+
+        // the rdata may have multiple fields
+        let key = RrKey::new(LowerName::from_str("example.com.").unwrap(), RecordType::MX);
+        let mx = records[&key]
+            .records_without_rrsigs()
+            .map(|record| record.data().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(mx, ["10 mail1.example.com.", "20 mail2.example.com."]);
+
+        for directive in [
+            "$GENERATE 10-1 host$ A 10.0.0.$",
+            "$GENERATE 1-2/0 host$ A 10.0.0.$",
+            "$GENERATE 1-2 host${0,1,z} A 10.0.0.$",
+            "$GENERATE 1-2 host${-2} A 10.0.0.$",
+            "$GENERATE 1-2 host${9223372036854775807} A 10.0.0.$",
+            "$GENERATE 1-2 host${0,256} A 10.0.0.$",
+            "$GENERATE 0-65536 host$ A 10.0.0.1",
+            "$GENERATE 0-4294967295 host$ A 10.0.0.1",
+            "$GENERATE 1-2 host${0 A 10.0.0.$",
+            "$GENERATE 1-2 host$ A",
+            "$GENERATE 1-2 host$ A 10.0.0.$ extra",
+        ] {
+            let zone_data = format!("$ORIGIN example.com.\n$TTL 3600\n{directive}\n");
+            assert!(
+                Parser::new(zone_data, None, None).parse().is_err(),
+                "{directive}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_include_origin() {
+        let zone_data = r#"$ORIGIN example.com.
+$TTL 3600
+$INCLUDE include-origin.zone sub.example.com.
+after A 192.0.2.3
+"#;
+
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/test-data/example.zone");
+        let (origin, records) = Parser::new(zone_data, Some(path), None).parse().unwrap();
+        assert_eq!(origin, Name::from_str("example.com.").unwrap());
+
+        let names = records
+            .keys()
+            .map(|key| key.name().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "after.example.com.",
+                "www.sub.example.com.",
+                "other.changed.example.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_include_depth() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/test-data/example.zone");
+        let result = Parser::new(
+            "$ORIGIN example.com.\n$INCLUDE include-loop.zone\n",
+            Some(path),
+            None,
+        )
+        .parse();
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Max depth level for nested $INCLUDE is reached"));
+    }
 }
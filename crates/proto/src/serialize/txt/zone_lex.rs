@@ -143,6 +143,11 @@ impl<'a> Lexer<'a> {
                             self.txt.next();
                             Self::push_to_str(&mut char_data, ch)?;
                         }
+                        // a $ without a directive is part of the data, e.g. the iterator of $GENERATE
+                        Some(_) | None if char_data.as_deref() == Some("") => {
+                            Self::push_to_str(&mut char_data, '$')?;
+                            self.state = State::CharData { is_list: false };
+                        }
                         // finishes the Dollar...
                         Some(_) | None => {
                             self.state = State::RestOfLine;
@@ -157,12 +162,8 @@ impl<'a> Lexer<'a> {
                                 "INCLUDE" => Token::Include,
                                 "ORIGIN" => Token::Origin,
                                 "TTL" => Token::Ttl,
-                                _ => {
-                                    return Err(LexerErrorKind::UnrecognizedDollar(
-                                        char_data.take().unwrap_or_else(|| "".into()),
-                                    )
-                                    .into())
-                                }
+                                "GENERATE" => Token::Generate,
+                                _ => return Err(LexerErrorKind::UnrecognizedDollar(dollar).into()),
                             }));
                         }
                     }
@@ -388,6 +389,8 @@ pub enum Token {
     Origin,
     /// $TTL
     Ttl,
+    /// $GENERATE
+    Generate,
     /// \n or \r\n
     EOL,
 }
@@ -575,6 +578,19 @@ mod lex_test {
             Token::Origin
         );
         assert_eq!(next_token(&mut Lexer::new("$TTL")).unwrap(), Token::Ttl);
+        assert_eq!(
+            next_token(&mut Lexer::new("$GENERATE")).unwrap(),
+            Token::Generate
+        );
+        assert_eq!(
+            next_token(&mut Lexer::new("${0,3,d}")).unwrap(),
+            Token::CharData("${0,3,d}".to_string())
+        );
+        assert_eq!(
+            next_token(&mut Lexer::new("$ A")).unwrap(),
+            Token::CharData("$".to_string())
+        );
+        assert!(Lexer::new("$FOO").next_token().is_err());
         assert_eq!(next_token(&mut Lexer::new("\n")), Some(Token::EOL));
         assert_eq!(next_token(&mut Lexer::new("\r\n")), Some(Token::EOL));
     }
@@ -15,8 +15,10 @@
  */
 
 //! TCP protocol related components for DNS
+mod socks5;
 mod tcp_client_stream;
 mod tcp_stream;
 
+pub use self::socks5::Socks5Provider;
 pub use self::tcp_client_stream::TcpClientStream;
 pub use self::tcp_stream::{DnsTcpStream, TcpStream};
@@ -0,0 +1,256 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! TCP connections through a SOCKS5 proxy, [RFC 1928](https://tools.ietf.org/html/rfc1928)
+
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_io::{AsyncRead, AsyncWrite};
+use futures_util::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::debug;
+
+use crate::runtime::{RuntimeProvider, Time};
+use crate::xfer::CONNECT_TIMEOUT;
+
+const VERSION: u8 = 5;
+const NO_AUTHENTICATION: u8 = 0;
+const USERNAME_PASSWORD: u8 = 2;
+const NO_ACCEPTABLE_METHODS: u8 = 0xFF;
+const USERNAME_PASSWORD_VERSION: u8 = 1;
+const CONNECT: u8 = 1;
+const ATYP_IPV4: u8 = 1;
+const ATYP_DOMAIN_NAME: u8 = 3;
+const ATYP_IPV6: u8 = 4;
+
+/// A [`RuntimeProvider`] making the TCP connections of `P` through a SOCKS5 proxy
+///
+/// All the protocols over TCP are tunneled, i.e. TCP, DNS over TLS and DNS over HTTPS, the proxy
+/// connects to the name servers on behalf of the client. The username/password authentication of
+/// [RFC 1929](https://tools.ietf.org/html/rfc1929) is offered to the proxy when credentials are
+/// configured.
+///
+/// UDP is not supported, as with many proxies, binding UDP sockets fails. The name servers should
+/// be configured over TCP, or the queries retried over TCP after UDP errors, e.g. with the
+/// `try_tcp_on_error` option of the resolver. QUIC connections are not supported either.
+#[derive(Clone)]
+pub struct Socks5Provider<P> {
+    inner: P,
+    proxy: SocketAddr,
+    credentials: Option<Arc<(String, String)>>,
+}
+
+impl<P: RuntimeProvider> Socks5Provider<P> {
+    /// Makes the TCP connections of `inner` through the SOCKS5 proxy at `proxy`
+    pub fn new(inner: P, proxy: SocketAddr) -> Self {
+        Self {
+            inner,
+            proxy,
+            credentials: None,
+        }
+    }
+
+    /// Authenticates to the proxy with `username` and `password`, each at most 255 bytes long
+    pub fn with_credentials(
+        mut self,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        self.credentials = Some(Arc::new((username.into(), password.into())));
+        self
+    }
+
+    /// The address of the proxy
+    pub fn proxy(&self) -> SocketAddr {
+        self.proxy
+    }
+}
+
+impl<P: RuntimeProvider> RuntimeProvider for Socks5Provider<P> {
+    type Handle = P::Handle;
+    type Timer = P::Timer;
+    type Udp = P::Udp;
+    type Tcp = P::Tcp;
+
+    fn create_handle(&self) -> Self::Handle {
+        self.inner.create_handle()
+    }
+
+    fn connect_tcp(
+        &self,
+        server_addr: SocketAddr,
+        bind_addr: Option<SocketAddr>,
+        timeout: Option<Duration>,
+    ) -> Pin<Box<dyn Send + Future<Output = io::Result<Self::Tcp>>>> {
+        let proxy = self.proxy;
+        let credentials = self.credentials.clone();
+        let connect = self.inner.connect_tcp(proxy, bind_addr, timeout);
+
+        Box::pin(async move {
+            let stream = connect.await?;
+            debug!("connecting to {server_addr} through the SOCKS5 proxy {proxy}");
+
+            let handshake = handshake(stream, server_addr, credentials);
+            match P::Timer::timeout(timeout.unwrap_or(CONNECT_TIMEOUT), handshake).await {
+                Ok(result) => result,
+                Err(_) => Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("SOCKS5 handshake with {proxy} timed out"),
+                )),
+            }
+        })
+    }
+
+    fn bind_udp(
+        &self,
+        _local_addr: SocketAddr,
+        _server_addr: SocketAddr,
+    ) -> Pin<Box<dyn Send + Future<Output = io::Result<Self::Udp>>>> {
+        Box::pin(async {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "UDP is not supported through the SOCKS5 proxy",
+            ))
+        })
+    }
+}
+
+/// Negotiates the authentication with the proxy, and requests the connection to `server_addr`
+async fn handshake<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    server_addr: SocketAddr,
+    credentials: Option<Arc<(String, String)>>,
+) -> io::Result<S> {
+    // the methods offered, the credentials are only sent if the proxy selects them
+    let greeting: &[u8] = match credentials {
+        Some(_) => &[VERSION, 2, NO_AUTHENTICATION, USERNAME_PASSWORD],
+        None => &[VERSION, 1, NO_AUTHENTICATION],
+    };
+    stream.write_all(greeting).await?;
+    stream.flush().await?;
+
+    let mut selected = [0; 2];
+    stream.read_exact(&mut selected).await?;
+    if selected[0] != VERSION {
+        return Err(proxy_error("unexpected version in the method selection"));
+    }
+
+    match (selected[1], credentials) {
+        (NO_AUTHENTICATION, _) => (),
+        (USERNAME_PASSWORD, Some(credentials)) => {
+            let (username, password) = &*credentials;
+            let (Ok(username_len), Ok(password_len)) =
+                (u8::try_from(username.len()), u8::try_from(password.len()))
+            else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "SOCKS5 username or password longer than 255 bytes",
+                ));
+            };
+
+            let mut request = Vec::with_capacity(3 + username.len() + password.len());
+            request.extend([USERNAME_PASSWORD_VERSION, username_len]);
+            request.extend(username.as_bytes());
+            request.push(password_len);
+            request.extend(password.as_bytes());
+            stream.write_all(&request).await?;
+            stream.flush().await?;
+
+            let mut status = [0; 2];
+            stream.read_exact(&mut status).await?;
+            if status[1] != 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "SOCKS5 proxy rejected the credentials",
+                ));
+            }
+        }
+        (NO_ACCEPTABLE_METHODS, _) => {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "SOCKS5 proxy accepted none of the authentication methods",
+            ))
+        }
+        (method, _) => {
+            return Err(proxy_error(&format!(
+                "unexpected authentication method selected: {method}"
+            )))
+        }
+    }
+
+    let mut request = vec![VERSION, CONNECT, 0];
+    match server_addr {
+        SocketAddr::V4(addr) => {
+            request.push(ATYP_IPV4);
+            request.extend(addr.ip().octets());
+        }
+        SocketAddr::V6(addr) => {
+            request.push(ATYP_IPV6);
+            request.extend(addr.ip().octets());
+        }
+    }
+    request.extend(server_addr.port().to_be_bytes());
+    stream.write_all(&request).await?;
+    stream.flush().await?;
+
+    let mut reply = [0; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != VERSION {
+        return Err(proxy_error("unexpected version in the reply"));
+    }
+    if reply[1] != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!(
+                "SOCKS5 proxy failed to connect to {server_addr}: {}",
+                reply_message(reply[1])
+            ),
+        ));
+    }
+
+    // the address bound by the proxy is not needed, but must be consumed
+    let bound_len = match reply[3] {
+        ATYP_IPV4 => 4,
+        ATYP_IPV6 => 16,
+        ATYP_DOMAIN_NAME => {
+            let mut len = [0; 1];
+            stream.read_exact(&mut len).await?;
+            usize::from(len[0])
+        }
+        atyp => return Err(proxy_error(&format!("unexpected address type: {atyp}"))),
+    };
+    let mut bound = vec![0; bound_len + 2];
+    stream.read_exact(&mut bound).await?;
+
+    Ok(stream)
+}
+
+fn proxy_error(message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid SOCKS5 response: {message}"),
+    )
+}
+
+/// The meaning of the reply field, RFC 1928 section 6
+fn reply_message(reply: u8) -> &'static str {
+    match reply {
+        1 => "general SOCKS server failure",
+        2 => "connection not allowed by ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unassigned error",
+    }
+}
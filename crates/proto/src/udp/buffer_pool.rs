@@ -0,0 +1,202 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A bounded pool of receive buffers, to avoid an allocation for each received message
+
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, PoisonError};
+
+use crate::udp::MAX_RECEIVE_BUFFER_SIZE;
+
+/// The pool of receive buffers shared by the UDP client and server streams
+pub(crate) static RECEIVE_BUFFER_POOL: BufferPool = BufferPool::new(64);
+
+/// A bounded pool of reusable receive buffers
+///
+/// The buffers are allocated with the size of their first checkout, and return to the pool when
+/// they are dropped, unless the pool already holds `max_buffers`. They are zeroed when checked out,
+/// the data of a previous use is never visible.
+pub struct BufferPool {
+    buffers: Mutex<Vec<Vec<u8>>>,
+    max_buffers: usize,
+}
+
+impl BufferPool {
+    /// Creates an empty pool, which keeps up to `max_buffers` buffers for reuse
+    pub const fn new(max_buffers: usize) -> Self {
+        Self {
+            buffers: Mutex::new(Vec::new()),
+            max_buffers,
+        }
+    }
+
+    /// Checks out a zeroed buffer of `size` bytes, bounded by [`MAX_RECEIVE_BUFFER_SIZE`]
+    ///
+    /// A new buffer is allocated if none is available in the pool.
+    pub fn checkout(&self, size: usize) -> PooledBuffer<'_> {
+        let buffer = self
+            .buffers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop();
+        let size = size.min(MAX_RECEIVE_BUFFER_SIZE);
+        let mut buffer = buffer.unwrap_or_else(|| Vec::with_capacity(size));
+        buffer.resize(size, 0);
+
+        PooledBuffer { pool: self, buffer }
+    }
+
+    /// The number of buffers available for reuse
+    pub fn available(&self) -> usize {
+        self.buffers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+}
+
+/// A buffer checked out from a [`BufferPool`], it returns to the pool when dropped
+pub struct PooledBuffer<'a> {
+    pool: &'a BufferPool,
+    buffer: Vec<u8>,
+}
+
+impl PooledBuffer<'_> {
+    /// Takes the first `len` bytes out of the pool, e.g. to keep a received message without a copy
+    ///
+    /// The buffer does not return to the pool.
+    pub fn detach(mut self, len: usize) -> Vec<u8> {
+        let mut buffer = mem::take(&mut self.buffer);
+        buffer.truncate(len);
+        buffer
+    }
+}
+
+impl Deref for PooledBuffer<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.buffer
+    }
+}
+
+impl DerefMut for PooledBuffer<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.buffer
+    }
+}
+
+impl Drop for PooledBuffer<'_> {
+    fn drop(&mut self) {
+        let mut buffer = mem::take(&mut self.buffer);
+        if buffer.capacity() == 0 {
+            // detached
+            return;
+        }
+        buffer.clear();
+
+        let mut buffers = self
+            .pool
+            .buffers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if buffers.len() < self.pool.max_buffers {
+            buffers.push(buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::*;
+    use crate::op::{Message, Query};
+    use crate::rr::{Name, RecordType};
+    use crate::serialize::binary::BinEncodable;
+
+    #[test]
+    fn test_reuse_is_zeroed() {
+        let pool = BufferPool::new(1);
+        let mut buffer = pool.checkout(512);
+        assert_eq!(buffer.len(), 512);
+        buffer.fill(0xff);
+        let ptr = buffer.as_ptr();
+        drop(buffer);
+
+        // the same allocation is reused, without the previous data
+        let buffer = pool.checkout(256);
+        assert_eq!(buffer.as_ptr(), ptr);
+        assert_eq!(buffer.len(), 256);
+        assert!(buffer.iter().all(|b| *b == 0));
+        drop(buffer);
+
+        let buffer = pool.checkout(MAX_RECEIVE_BUFFER_SIZE + 1);
+        assert_eq!(buffer.len(), MAX_RECEIVE_BUFFER_SIZE);
+        assert!(buffer.iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn test_detach() {
+        let pool = BufferPool::new(1);
+        let mut buffer = pool.checkout(512);
+        buffer[..3].copy_from_slice(b"abc");
+        let ptr = buffer.as_ptr();
+
+        // the bytes are taken without a copy, and the buffer does not return to the pool
+        let bytes = buffer.detach(3);
+        assert_eq!(bytes, b"abc");
+        assert_eq!(bytes.as_ptr(), ptr);
+        assert_eq!(pool.available(), 0);
+    }
+
+    #[test]
+    fn test_bounded() {
+        let pool = BufferPool::new(2);
+        let buffers = (0..3).map(|_| pool.checkout(512)).collect::<Vec<_>>();
+        assert_eq!(pool.available(), 0);
+
+        drop(buffers);
+        assert_eq!(pool.available(), 2);
+    }
+
+    #[test]
+    fn test_concurrent_reuse() {
+        let pool = Arc::new(BufferPool::new(4));
+        let threads = (0..8u16)
+            .map(|thread| {
+                let pool = pool.clone();
+                thread::spawn(move || {
+                    for i in 0..200u16 {
+                        let id = thread * 1000 + i;
+                        let name = Name::from_ascii(format!("host-{id}.example.com.")).unwrap();
+                        let mut message = Message::new();
+                        message
+                            .set_id(id)
+                            .add_query(Query::query(name.clone(), RecordType::A));
+                        let bytes = message.to_bytes().unwrap();
+
+                        // receive the message in a pooled buffer, and parse the bytes received
+                        let mut buffer = pool.checkout(MAX_RECEIVE_BUFFER_SIZE);
+                        buffer[..bytes.len()].copy_from_slice(&bytes);
+                        let parsed = Message::from_vec(&buffer[..bytes.len()]).unwrap();
+                        assert_eq!(parsed.id(), id);
+                        assert_eq!(parsed.queries()[0].name(), &name);
+                        assert!(buffer[bytes.len()..].iter().all(|b| *b == 0));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert!(pool.available() <= 4);
+    }
+}
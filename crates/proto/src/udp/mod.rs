@@ -16,9 +16,11 @@
 
 //! UDP protocol related components for DNS
 
+mod buffer_pool;
 mod udp_client_stream;
 mod udp_stream;
 
+pub use self::buffer_pool::{BufferPool, PooledBuffer};
 pub use self::udp_client_stream::{UdpClientConnect, UdpClientStream, UdpClientStreamBuilder};
 pub use self::udp_stream::{DnsUdpSocket, UdpSocket, UdpStream};
 
@@ -19,6 +19,7 @@ use tracing::{debug, trace, warn};
 use crate::error::ProtoError;
 use crate::op::{Message, MessageFinalizer, MessageVerifier};
 use crate::runtime::{RuntimeProvider, Time};
+use crate::udp::buffer_pool::RECEIVE_BUFFER_POOL;
 use crate::udp::udp_stream::NextRandomUdpSocket;
 use crate::udp::{DnsUdpSocket, MAX_RECEIVE_BUFFER_SIZE};
 use crate::xfer::{DnsRequest, DnsRequestSender, DnsResponse, DnsResponseStream, SerialMessage};
@@ -285,17 +286,14 @@ async fn send_serial_message_inner<S: DnsUdpSocket + Send>(
         )));
     }
 
-    // Create the receive buffer.
-    trace!("creating UDP receive buffer with size {recv_buf_size}");
-    let mut recv_buf = vec![0; recv_buf_size];
+    // Check out the receive buffer, it is kept by the response, or returns to the pool on errors.
+    trace!("checking out UDP receive buffer with size {recv_buf_size}");
+    let mut recv_buf = RECEIVE_BUFFER_POOL.checkout(recv_buf_size);
 
     // TODO: limit the max number of attempted messages? this relies on a timeout to die...
     loop {
         let (len, src) = socket.recv_from(&mut recv_buf).await?;
-
-        // Copy the slice of read bytes.
         let response_bytes = &recv_buf[0..len];
-        let response_buffer = Vec::from(response_bytes);
 
         // compare expected src to received packet
         let request_target = msg.addr();
@@ -311,7 +309,7 @@ async fn send_serial_message_inner<S: DnsUdpSocket + Send>(
             continue;
         }
 
-        let response = match DnsResponse::from_buffer(response_buffer) {
+        let response = match Message::from_vec(response_bytes) {
             Ok(response) => response,
             Err(e) => {
                 // on errors deserializing, continue
@@ -373,7 +371,7 @@ async fn send_serial_message_inner<S: DnsUdpSocket + Send>(
         if let Some(mut verifier) = verifier {
             return verifier(response_bytes);
         } else {
-            return Ok(response);
+            return Ok(DnsResponse::from_parts(response, recv_buf.detach(len)));
         }
     }
 }
@@ -382,10 +380,18 @@ async fn send_serial_message_inner<S: DnsUdpSocket + Send>(
 #[cfg(feature = "tokio-runtime")]
 mod tests {
     #![allow(clippy::dbg_macro, clippy::print_stdout)]
-    use crate::{runtime::TokioRuntimeProvider, tests::udp_client_stream_test};
-    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use super::UdpClientStream;
+    use crate::{
+        op::{Message, Query},
+        rr::{Name, RecordType},
+        runtime::TokioRuntimeProvider,
+        tests::udp_client_stream_test,
+        xfer::{DnsRequest, DnsRequestOptions, DnsRequestSender, FirstAnswer},
+    };
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+    use std::str::FromStr;
     use test_support::subscribe;
-    use tokio::runtime::Runtime;
+    use tokio::{net::UdpSocket, runtime::Runtime};
 
     #[test]
     fn test_udp_client_stream_ipv4() {
@@ -406,4 +412,57 @@ mod tests {
             provider,
         )
     }
+
+    #[test]
+    fn test_udp_client_stream_response_buffer() {
+        subscribe();
+        let io_loop = Runtime::new().expect("failed to create tokio runtime");
+        io_loop.block_on(async {
+            let server = UdpSocket::bind(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0))
+                .await
+                .unwrap();
+            let server_addr = server.local_addr().unwrap();
+
+            // the owner name of the answer is not compressed, unlike when encoding the message
+            let name = b"\x04dead\x04beef\x00";
+            let wire = move |id: [u8; 2]| {
+                let mut wire = id.to_vec();
+                wire.extend_from_slice(&[0x81, 0x80, 0, 1, 0, 1, 0, 0, 0, 0]);
+                wire.extend_from_slice(name);
+                wire.extend_from_slice(&[0, 10, 0, 1]);
+                wire.extend_from_slice(name);
+                wire.extend_from_slice(&[0, 10, 0, 1, 0, 0, 0, 0, 0, 8]);
+                wire.extend_from_slice(b"DEADBEEF");
+                wire
+            };
+
+            tokio::spawn(async move {
+                let mut buffer = [0_u8; 512];
+                let (_, addr) = server.recv_from(&mut buffer).await.unwrap();
+                server
+                    .send_to(&wire([buffer[0], buffer[1]]), addr)
+                    .await
+                    .unwrap();
+            });
+
+            let mut stream = UdpClientStream::builder(server_addr, TokioRuntimeProvider::new())
+                .build()
+                .await
+                .unwrap();
+            let mut query = Message::new();
+            query.add_query(Query::query(
+                Name::from_str("dead.beef.").unwrap(),
+                RecordType::NULL,
+            ));
+            let response = stream
+                .send_message(DnsRequest::new(query, DnsRequestOptions::default()))
+                .first_answer()
+                .await
+                .unwrap();
+
+            let id = response.id().to_be_bytes();
+            assert_eq!(response.as_buffer(), wire(id).as_slice());
+            assert_ne!(response.to_vec().unwrap(), wire(id));
+        });
+    }
 }
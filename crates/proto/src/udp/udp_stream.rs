@@ -21,7 +21,7 @@ use rand::distributions::{uniform::Uniform, Distribution};
 use tracing::{debug, trace, warn};
 
 use crate::runtime::{RuntimeProvider, Time};
-use crate::udp::MAX_RECEIVE_BUFFER_SIZE;
+use crate::udp::{buffer_pool::RECEIVE_BUFFER_POOL, MAX_RECEIVE_BUFFER_SIZE};
 use crate::xfer::{BufDnsStreamHandle, SerialMessage, StreamReceiver};
 
 /// Trait for DnsUdpSocket
@@ -216,11 +216,12 @@ impl<P: RuntimeProvider> Stream for UdpStream<P> {
         // For QoS, this will only accept one message and output that
         // receive all inbound messages
 
-        // TODO: this should match edns settings
-        let mut buf = [0u8; MAX_RECEIVE_BUFFER_SIZE];
+        // the EDNS payload size of a query is only known once it is parsed, receive up to the
+        // recommended maximum
+        let mut buf = RECEIVE_BUFFER_POOL.checkout(MAX_RECEIVE_BUFFER_SIZE);
         let (len, src) = ready!(socket.poll_recv_from(cx, &mut buf))?;
 
-        let serial_message = SerialMessage::new(buf.iter().take(len).cloned().collect(), src);
+        let serial_message = SerialMessage::new(buf[..len].to_vec(), src);
         Poll::Ready(Some(Ok(serial_message)))
     }
 }
@@ -291,7 +291,7 @@ where
     },
     Connected {
         exchange: DnsExchange,
-        background: Option<DnsExchangeBackground<S, TE>>,
+        background: Option<Box<DnsExchangeBackground<S, TE>>>,
     },
     FailAll {
         error: ProtoError,
@@ -333,7 +333,7 @@ where
 
                             next = Self::Connected {
                                 exchange,
-                                background: Some(background),
+                                background: Some(Box::new(background)),
                             };
                         }
                         Poll::Pending => return Poll::Pending,
@@ -355,7 +355,7 @@ where
                     let exchange = exchange.clone();
                     let background = background.take().expect("cannot poll after complete");
 
-                    return Poll::Ready(Ok((exchange, background)));
+                    return Poll::Ready(Ok((exchange, *background)));
                 }
                 Self::FailAll {
                     error,
@@ -77,7 +77,8 @@ fn build_message(query: Query, options: DnsRequestOptions) -> Message {
         .set_id(id)
         .set_message_type(MessageType::Query)
         .set_op_code(OpCode::Query)
-        .set_recursion_desired(options.recursion_desired);
+        .set_recursion_desired(options.recursion_desired)
+        .set_checking_disabled(options.checking_disabled);
 
     // Extended dns
     if options.use_edns {
@@ -91,3 +92,28 @@ fn build_message(query: Query, options: DnsRequestOptions) -> Message {
 
     message
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::rr::{Name, RecordType};
+
+    use super::*;
+
+    #[test]
+    fn test_checking_disabled() {
+        let query = Query::query(Name::from_ascii("www.example.com.").unwrap(), RecordType::A);
+
+        let message = build_message(query.clone(), DnsRequestOptions::default());
+        let message = Message::from_vec(&message.to_vec().unwrap()).unwrap();
+        assert!(!message.checking_disabled());
+
+        let options = DnsRequestOptions {
+            checking_disabled: true,
+            ..DnsRequestOptions::default()
+        };
+        let message = build_message(query, options);
+        let message = Message::from_vec(&message.to_vec().unwrap()).unwrap();
+        assert!(message.checking_disabled());
+        assert!(message.recursion_desired());
+    }
+}
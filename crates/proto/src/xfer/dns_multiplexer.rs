@@ -34,6 +34,7 @@ use tracing::debug;
 use crate::{
     error::{ProtoError, ProtoErrorKind},
     op::{MessageFinalizer, MessageVerifier},
+    rr::rdata::opt::{EdnsCode, EdnsOption},
     runtime::Time,
     xfer::{
         ignore_send, BufDnsStreamHandle, DnsClientStream, DnsRequest, DnsRequestSender,
@@ -278,9 +279,22 @@ where
             Err(e) => return e.into(),
         };
 
-        let (mut request, _) = request.into_parts();
+        let (mut request, options) = request.into_parts();
         request.set_id(query_id);
 
+        // signal the support of idle timeouts of the stream, RFC 7828
+        if let Some(edns) = request.extensions_mut() {
+            if edns.option(EdnsCode::Keepalive).is_none() {
+                edns.options_mut().insert(EdnsOption::TcpKeepalive(None));
+            }
+        }
+
+        if let Some(block_size) = options.edns_padding {
+            if let Err(e) = request.pad_to_block_size(block_size) {
+                return e.into();
+            }
+        }
+
         let now = match SystemTime::now().duration_since(UNIX_EPOCH) {
             Ok(now) => now.as_secs(),
             Err(_) => return ProtoError::from("Current time is before the Unix epoch.").into(),
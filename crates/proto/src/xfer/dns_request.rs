@@ -12,7 +12,7 @@ use std::ops::{Deref, DerefMut};
 use crate::op::Message;
 
 /// A set of options for expressing options to how requests should be treated
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[non_exhaustive]
 pub struct DnsRequestOptions {
     /// When true, the underlying DNS protocols will not return on the first response received.
@@ -31,6 +31,13 @@ pub struct DnsRequestOptions {
     pub max_request_depth: usize,
     /// set recursion desired (or not) for any requests
     pub recursion_desired: bool,
+    /// Sets the CD (checking disabled) bit, the server then returns the records without validating
+    /// them, for clients doing their own DNSSEC validation
+    pub checking_disabled: bool,
+    /// Pads the EDNS section of the request to a multiple of this block size, RFC 7830
+    ///
+    /// This is only applied by encrypted transports, and must not be set for plain TCP.
+    pub edns_padding: Option<u16>,
 }
 
 impl Default for DnsRequestOptions {
@@ -42,6 +49,8 @@ impl Default for DnsRequestOptions {
             use_edns: false,
             edns_set_dnssec_ok: false,
             recursion_desired: true,
+            checking_disabled: false,
+            edns_padding: None,
         }
     }
 }
@@ -66,6 +75,11 @@ impl DnsRequest {
         &self.options
     }
 
+    /// Get a mutable reference to the request options.
+    pub fn options_mut(&mut self) -> &mut DnsRequestOptions {
+        &mut self.options
+    }
+
     /// Unwraps the raw message
     pub fn into_parts(self) -> (Message, DnsRequestOptions) {
         (self.message, self.options)
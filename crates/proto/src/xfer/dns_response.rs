@@ -11,6 +11,7 @@ use std::{
     convert::TryFrom,
     future::Future,
     io,
+    net::SocketAddr,
     ops::{Deref, DerefMut},
     pin::Pin,
     task::{Context, Poll},
@@ -24,8 +25,13 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     error::{ProtoError, ProtoErrorKind, ProtoResult},
-    op::{Message, ResponseCode},
-    rr::{rdata::SOA, resource::RecordRef, RecordType},
+    op::{ExtendedDnsError, Message, ResponseCode},
+    rr::{
+        rdata::{opt::EdnsOption, SOA},
+        resource::RecordRef,
+        RecordType,
+    },
+    xfer::Protocol,
 };
 
 /// A stream returning DNS responses
@@ -125,7 +131,6 @@ type TimeoutFuture = Pin<
     Box<dyn Future<Output = Result<Result<DnsResponse, ProtoError>, io::Error>> + Send + 'static>,
 >;
 
-// TODO: this needs to have the IP addr of the remote system...
 // TODO: see https://github.com/hickory-dns/hickory-dns/issues/383 for removing vec of messages and instead returning a Stream
 /// A DNS response object
 ///
@@ -135,6 +140,8 @@ type TimeoutFuture = Pin<
 pub struct DnsResponse {
     message: Message,
     buffer: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    server: Option<(SocketAddr, Protocol)>,
 }
 
 // TODO: when `impl Trait` lands in stable, remove this, and expose FlatMap over answers, et al.
@@ -144,15 +151,44 @@ impl DnsResponse {
         Ok(Self {
             buffer: message.to_vec()?,
             message,
+            server: None,
         })
     }
 
+    /// Constructs a new DnsResponse from a message already decoded from `buffer`
+    pub(crate) fn from_parts(message: Message, buffer: Vec<u8>) -> Self {
+        Self {
+            message,
+            buffer,
+            server: None,
+        }
+    }
+
     /// Constructs a new DnsResponse by parsing a message from a buffer.
     ///
-    /// Returns an error if the response message cannot be decoded.
+    /// The buffer is retained as it is, see [`Self::as_buffer`]. Returns an error if the response
+    /// message cannot be decoded.
     pub fn from_buffer(buffer: Vec<u8>) -> Result<Self, ProtoError> {
         let message = Message::from_vec(&buffer)?;
-        Ok(Self { message, buffer })
+        Ok(Self {
+            message,
+            buffer,
+            server: None,
+        })
+    }
+
+    /// The address of the name server which sent the response, and the protocol it was received on
+    ///
+    /// This is `None` unless it is set by the handle which received the response, see
+    /// [`Self::set_server`].
+    pub fn server(&self) -> Option<(SocketAddr, Protocol)> {
+        self.server
+    }
+
+    /// Sets the name server which sent the response, see [`Self::server`]
+    pub fn set_server(&mut self, addr: SocketAddr, protocol: Protocol) -> &mut Self {
+        self.server = Some((addr, protocol));
+        self
     }
 
     /// Retrieves the SOA from the response. This will only exist if it was an authoritative response.
@@ -226,6 +262,20 @@ impl DnsResponse {
             .map(|(ttl, soa)| (ttl).min(soa.minimum()))
     }
 
+    /// Returns the Extended DNS Errors, [RFC 8914](https://tools.ietf.org/html/rfc8914), included in the response
+    ///
+    /// These provide additional context for the response code, for example why a resolver returned
+    /// `SERVFAIL`. A response may contain more than one.
+    pub fn extended_dns_errors(&self) -> impl Iterator<Item = &ExtendedDnsError> + '_ {
+        self.extensions()
+            .iter()
+            .flat_map(|edns| edns.options().as_ref())
+            .filter_map(|(_, option)| match option {
+                EdnsOption::ExtendedDnsError(ede) => Some(ede),
+                _ => None,
+            })
+    }
+
     /// Does the response contain any records matching the query name and type?
     pub fn contains_answer(&self) -> bool {
         for q in self.queries() {
@@ -300,6 +350,11 @@ impl DnsResponse {
     }
 
     /// Borrow the inner buffer from the response
+    ///
+    /// For responses received from a name server, over UDP, TCP, TLS, HTTPS or QUIC, these are
+    /// the exact bytes of the message as received on the wire, also if encoding the message again
+    /// would produce different bytes, e.g. because of another name compression. The buffer is not
+    /// updated when the message is modified through `DerefMut`.
     pub fn as_buffer(&self) -> &[u8] {
         &self.buffer
     }
@@ -314,7 +369,7 @@ impl DnsResponse {
         self.message
     }
 
-    /// Take the inner Message and buffer from the response
+    /// Take the inner Message and buffer from the response, see [`Self::as_buffer`]
     pub fn into_parts(self) -> (Message, Vec<u8>) {
         (self.message, self.buffer)
     }
@@ -651,7 +706,7 @@ impl NegativeType {
 
 #[cfg(test)]
 mod tests {
-    use crate::op::{Message, Query, ResponseCode};
+    use crate::op::{Edns, Message, MessageType, Query, ResponseCode};
     use crate::rr::rdata::{A, CNAME, NS, SOA};
     use crate::rr::RData;
     use crate::rr::{Name, Record, RecordType};
@@ -726,6 +781,22 @@ mod tests {
         Query::query(another_example(), RecordType::A)
     }
 
+    #[test]
+    fn test_authentic_data() {
+        let mut message = Message::new();
+        message
+            .set_message_type(MessageType::Response)
+            .set_authentic_data(true)
+            .set_checking_disabled(true);
+
+        let response = DnsResponse::from_buffer(message.to_vec().unwrap()).unwrap();
+        assert!(response.authentic_data());
+        assert!(response.checking_disabled());
+
+        let response = DnsResponse::from_message(Message::new()).unwrap();
+        assert!(!response.authentic_data());
+    }
+
     #[test]
     fn test_contains_answer() {
         let mut message = Message::default();
@@ -908,4 +979,64 @@ mod tests {
 
         assert!(response.contains_answer());
     }
+
+    #[test]
+    fn extended_dns_errors() {
+        let mut message = Message::default();
+        message.set_response_code(ResponseCode::ServFail);
+        message.add_query(Query::query(an_example(), RecordType::A));
+        let options = message
+            .extensions_mut()
+            .get_or_insert_with(Edns::new)
+            .options_mut();
+        options.insert(EdnsOption::ExtendedDnsError(
+            ExtendedDnsError::new(6, "no valid signature").unwrap(),
+        ));
+        options.insert(EdnsOption::ExtendedDnsError(
+            ExtendedDnsError::new(22, "").unwrap(),
+        ));
+
+        let buffer = DnsResponse::from_message(message).unwrap().into_buffer();
+        let response = DnsResponse::from_buffer(buffer).unwrap();
+
+        let errors = response.extended_dns_errors().collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                &ExtendedDnsError::new(6, "no valid signature").unwrap(),
+                &ExtendedDnsError::new(22, "").unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn retains_wire_bytes() {
+        // a response for AN.EXAMPLE. A, with the name of the answer not compressed
+        let mut wire = vec![0x12, 0x34, 0x81, 0x80, 0, 1, 0, 1, 0, 0, 0, 0];
+        let name = b"\x02AN\x07EXAMPLE\x00";
+        wire.extend_from_slice(name);
+        wire.extend_from_slice(&[0, 1, 0, 1]);
+        wire.extend_from_slice(name);
+        wire.extend_from_slice(&[0, 1, 0, 1, 0, 0, 0x0e, 0x10, 0, 4, 192, 0, 2, 1]);
+
+        let response = DnsResponse::from_buffer(wire.clone()).unwrap();
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(response.answers()[0].name(), &an_example());
+        assert_eq!(response.as_buffer(), wire.as_slice());
+
+        // encoding the message again compresses the name
+        assert_ne!(response.to_vec().unwrap(), wire);
+
+        let (message, buffer) = response.into_parts();
+        assert_eq!(message.id(), 0x1234);
+        assert_eq!(buffer, wire);
+    }
+
+    #[test]
+    fn no_extended_dns_errors() {
+        let mut message = Message::default();
+        message.add_query(Query::query(an_example(), RecordType::A));
+        let response = DnsResponse::from_message(message).unwrap();
+        assert_eq!(response.extended_dns_errors().count(), 0);
+    }
 }
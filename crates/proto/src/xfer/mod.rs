@@ -169,6 +169,14 @@ impl DnsHandle for BufDnsRequestStreamHandle {
             request.queries()
         );
 
+        // a DNS message carries a single question in practice, RFC 9619
+        let query_count = request.queries().len();
+        if query_count > 1 {
+            return DnsResponseReceiver::Err(Some(
+                ProtoErrorKind::BadQueryCount(query_count).into(),
+            ));
+        }
+
         let (request, oneshot) = OneshotDnsRequest::oneshot(request);
         let mut sender = self.sender.clone();
         let try_send = sender.try_send(request).map_err(|_| {
@@ -388,3 +396,35 @@ impl Default for Protocol {
 
 #[allow(unused)] // May be unused depending on features
 pub(crate) const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+
+    use crate::op::{Message, Query};
+    use crate::rr::{Name, RecordType};
+
+    use super::*;
+
+    #[test]
+    fn test_reject_multiple_queries() {
+        let (sender, mut receiver) = mpsc::channel(CHANNEL_BUFFER_SIZE);
+        let handle = BufDnsRequestStreamHandle { sender };
+
+        let mut message = Message::new();
+        message
+            .add_query(Query::query(Name::root(), RecordType::A))
+            .add_query(Query::query(Name::root(), RecordType::AAAA));
+        let error = block_on(handle.send(message).next()).unwrap().unwrap_err();
+        assert!(matches!(error.kind(), ProtoErrorKind::BadQueryCount(2)));
+        assert!(receiver.try_next().is_err());
+
+        // a single query, or none, e.g. for a NOTIFY, is sent
+        let mut message = Message::new();
+        message.add_query(Query::query(Name::root(), RecordType::A));
+        drop(handle.send(message));
+        drop(handle.send(Message::new()));
+        assert!(receiver.try_next().unwrap().is_some());
+        assert!(receiver.try_next().unwrap().is_some());
+    }
+}
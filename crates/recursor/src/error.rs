@@ -22,7 +22,7 @@ use crate::proto::{
 };
 #[cfg(feature = "backtrace")]
 use crate::proto::{trace, ExtBacktrace};
-use crate::resolver::ResolveError;
+use crate::resolver::{lookup::LookupMetadata, ResolveError};
 
 /// The error kind for errors that get returned in the crate
 #[derive(Debug, EnumAsInner, Error)]
@@ -75,6 +75,7 @@ pub enum ErrorKind {
 pub struct Error {
     /// Kind of error that occurred
     pub kind: Box<ErrorKind>,
+    pub(crate) metadata: LookupMetadata,
     /// Backtrace to the source of the error
     #[cfg(feature = "backtrace")]
     pub backtrack: Option<ExtBacktrace>,
@@ -91,6 +92,17 @@ impl Error {
         *self.kind
     }
 
+    /// Returns where the error was obtained from, e.g. a negative answer from the cache
+    pub fn metadata(&self) -> LookupMetadata {
+        self.metadata
+    }
+
+    /// Sets where the error was obtained from, see [`Self::metadata`]
+    pub(crate) fn with_metadata(mut self, metadata: LookupMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
     /// Returns true if the domain does not exist
     pub fn is_nx_domain(&self) -> bool {
         match &*self.kind {
@@ -170,6 +182,7 @@ where
 
         Self {
             kind: Box::new(kind),
+            metadata: LookupMetadata::default(),
             #[cfg(feature = "backtrace")]
             backtrack: trace!(),
         }
@@ -129,7 +129,7 @@ fn maybe_strip_dnssec_records(query_has_dnssec_ok: bool, lookup: Lookup, query:
         .cloned()
         .collect();
 
-    Lookup::new_with_deadline(query, records, lookup.valid_until())
+    Lookup::new_with_deadline(query, records, lookup.valid_until()).with_metadata(lookup.metadata())
 }
 
 /// Bailiwick/sub zone checking.
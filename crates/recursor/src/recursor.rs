@@ -17,7 +17,11 @@ use ipnet::IpNet;
 use crate::{
     proto::op::Query,
     recursor_dns_handle::RecursorDnsHandle,
-    resolver::{config::NameServerConfigGroup, dns_lru::TtlConfig, lookup::Lookup},
+    resolver::{
+        config::NameServerConfigGroup,
+        dns_lru::TtlConfig,
+        lookup::{Lookup, LookupMetadata},
+    },
     DnssecPolicy, Error,
 };
 #[cfg(feature = "dnssec-ring")]
@@ -49,6 +53,7 @@ pub struct RecursorBuilder {
     deny_servers: Vec<IpNet>,
     avoid_local_udp_ports: HashSet<u16>,
     ttl_config: TtlConfig,
+    qname_minimization: bool,
 }
 
 impl RecursorBuilder {
@@ -111,6 +116,15 @@ impl RecursorBuilder {
         self
     }
 
+    /// Enables QNAME minimisation, [RFC 9156](https://datatracker.ietf.org/doc/html/rfc9156)
+    ///
+    /// The name servers of each zone are only sent the queried name up to one label below that
+    /// zone, until the name servers for the full name are found. Disabled by default.
+    pub fn qname_minimization(mut self, enabled: bool) -> Self {
+        self.qname_minimization = enabled;
+        self
+    }
+
     /// Construct a new recursor using the list of NameServerConfigs for the root node list
     ///
     /// # Panics
@@ -155,6 +169,7 @@ impl Recursor {
             deny_servers,
             avoid_local_udp_ports,
             ttl_config,
+            qname_minimization,
         } = builder;
 
         let handle = RecursorDnsHandle::new(
@@ -168,6 +183,7 @@ impl Recursor {
             deny_servers,
             Arc::new(avoid_local_udp_ports),
             ttl_config,
+            qname_minimization,
         );
 
         let mode = match dnssec_policy {
@@ -368,6 +384,33 @@ impl Recursor {
             return Err(Error::from("query's domain name must be fully qualified"));
         }
 
+        // the answers from the cache are marked by the lookups, the others took the whole lookup
+        let started = Instant::now();
+        let result = self
+            .inner_resolve(query, request_time, query_has_dnssec_ok)
+            .await;
+        let upstream_duration = started.elapsed();
+
+        match result {
+            Ok(lookup) if lookup.metadata().from_cache => Ok(lookup),
+            Ok(lookup) => {
+                let metadata = lookup.metadata().with_upstream_duration(upstream_duration);
+                Ok(lookup.with_metadata(metadata))
+            }
+            Err(e) if e.metadata().from_cache => Err(e),
+            Err(e) => {
+                let metadata = LookupMetadata::default().with_upstream_duration(upstream_duration);
+                Err(e.with_metadata(metadata))
+            }
+        }
+    }
+
+    async fn inner_resolve(
+        &self,
+        query: Query,
+        request_time: Instant,
+        query_has_dnssec_ok: bool,
+    ) -> Result<Lookup, Error> {
         match &self.mode {
             RecursorMode::NonValidating { handle } => {
                 handle
@@ -397,7 +440,7 @@ impl Recursor {
                     if none_indeterminate {
                         return Ok(super::maybe_strip_dnssec_records(
                             query_has_dnssec_ok,
-                            lookup,
+                            lookup.with_metadata(LookupMetadata::cached()),
                             query,
                         ));
                     }
@@ -423,6 +466,7 @@ impl Recursor {
 
                     Err(Error {
                         kind: Box::new(ErrorKind::Proto(proto_err)),
+                        metadata: LookupMetadata::default(),
                         #[cfg(feature = "backtrace")]
                         backtrack: None,
                     })
@@ -451,6 +495,7 @@ impl Recursor {
                             true,
                             Some(authorities),
                         ))),
+                        metadata: LookupMetadata::default(),
                         #[cfg(feature = "backtrace")]
                         backtrack: None,
                     })
@@ -490,6 +535,7 @@ impl Default for RecursorBuilder {
             deny_servers: vec![],
             avoid_local_udp_ports: HashSet::new(),
             ttl_config: TtlConfig::default(),
+            qname_minimization: false,
         }
     }
 }
@@ -633,4 +679,102 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn qname_minimization() -> Result<(), Error> {
+        use std::str::FromStr;
+
+        use parking_lot::Mutex;
+        use tokio::net::UdpSocket;
+
+        use crate::proto::{
+            op::{Message, MessageType, ResponseCode},
+            rr::{
+                rdata::{A, SOA},
+                Name, RData, Record, RecordType,
+            },
+            serialize::binary::BinDecodable,
+        };
+
+        // a single upstream, authoritative for the whole tree, recording the queries it receives
+        let socket = UdpSocket::bind("127.0.0.1:0").await?;
+        let port = socket.local_addr()?.port();
+        let queries = Arc::new(Mutex::new(Vec::new()));
+        let received = Arc::clone(&queries);
+        tokio::spawn(async move {
+            let mut buf = [0u8; 4096];
+            loop {
+                let (len, src) = socket.recv_from(&mut buf).await.unwrap();
+                let request = Message::from_bytes(&buf[..len]).unwrap();
+                let query = request.queries()[0].clone();
+                received.lock().push(query.clone());
+
+                let mut response =
+                    Message::error_msg(request.id(), request.op_code(), ResponseCode::NoError);
+                response
+                    .set_message_type(MessageType::Response)
+                    .set_authoritative(true)
+                    .add_query(query.clone());
+                if query.name().num_labels() == 4 {
+                    response.add_answer(Record::from_rdata(
+                        query.name().clone(),
+                        300,
+                        RData::A(A::new(192, 0, 2, 1)),
+                    ));
+                } else {
+                    // no data, the intermediate names are empty non-terminals
+                    response.add_name_server(Record::from_rdata(
+                        Name::root(),
+                        300,
+                        RData::SOA(SOA::new(
+                            Name::from_str("ns.example.").unwrap(),
+                            Name::from_str("hostmaster.example.").unwrap(),
+                            1,
+                            3600,
+                            600,
+                            86400,
+                            300,
+                        )),
+                    ));
+                }
+
+                socket
+                    .send_to(&response.to_vec().unwrap(), src)
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let roots =
+            NameServerConfigGroup::from_ips_clear(&[Ipv4Addr::LOCALHOST.into()], port, true);
+        let recursor = Recursor::builder().qname_minimization(true).build(roots)?;
+
+        let name = Name::from_str("a.b.example.com.")?;
+        let lookup = recursor
+            .resolve(Query::query(name, RecordType::A), Instant::now(), false)
+            .await?;
+        assert_eq!(
+            lookup.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::A(A::new(192, 0, 2, 1))]
+        );
+
+        // one more label is sent in each query, the full name only in the last one
+        let queries = queries
+            .lock()
+            .iter()
+            .map(|query| (query.name().to_string(), query.query_type()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            queries,
+            [
+                ("com.", RecordType::A),
+                ("example.com.", RecordType::A),
+                ("b.example.com.", RecordType::A),
+                ("a.b.example.com.", RecordType::A),
+            ]
+            .map(|(name, query_type)| (name.to_string(), query_type))
+        );
+
+        Ok(())
+    }
 }
@@ -27,7 +27,7 @@ use crate::{
     resolver::{
         config::{NameServerConfigGroup, ResolverOpts},
         dns_lru::{DnsLru, TtlConfig},
-        lookup::Lookup,
+        lookup::{Lookup, LookupMetadata},
         name_server::{GenericNameServerPool, TokioConnectionProvider},
         Name,
     },
@@ -50,6 +50,7 @@ pub(crate) struct RecursorDnsHandle {
     allow_server_v4: PrefixSet<Ipv4Net>,
     allow_server_v6: PrefixSet<Ipv6Net>,
     avoid_local_udp_ports: Arc<HashSet<u16>>,
+    qname_minimization: bool,
 }
 
 impl RecursorDnsHandle {
@@ -65,6 +66,7 @@ impl RecursorDnsHandle {
         deny_server: Vec<IpNet>,
         avoid_local_udp_ports: Arc<HashSet<u16>>,
         ttl_config: TtlConfig,
+        qname_minimization: bool,
     ) -> Self {
         // configure the hickory-resolver
         let roots: NameServerConfigGroup = roots.into();
@@ -121,6 +123,7 @@ impl RecursorDnsHandle {
             allow_server_v4,
             allow_server_v6,
             avoid_local_udp_ports,
+            qname_minimization,
         }
     }
 
@@ -133,9 +136,11 @@ impl RecursorDnsHandle {
         cname_limit: Arc<AtomicU8>,
     ) -> Result<Lookup, Error> {
         if let Some(lookup) = self.record_cache.get(&query, request_time) {
+            let lookup =
+                lookup.map_err(|e| Error::from(e).with_metadata(LookupMetadata::cached()))?;
             let response = self
                 .resolve_cnames(
-                    lookup?,
+                    lookup.with_metadata(LookupMetadata::cached()),
                     query.clone(),
                     request_time,
                     query_has_dnssec_ok,
@@ -201,10 +206,15 @@ impl RecursorDnsHandle {
             _ => query.name().base_name(),
         };
 
-        let (mut depth, mut ns) = match self
-            .ns_pool_for_zone(zone.clone(), request_time, depth)
-            .await
-        {
+        let ns_pool = if self.qname_minimization {
+            self.ns_pool_minimized(zone.clone(), request_time, depth)
+                .await
+        } else {
+            self.ns_pool_for_zone(zone.clone(), request_time, depth)
+                .await
+        };
+
+        let (mut depth, mut ns) = match ns_pool {
             Ok((depth, ns)) => (depth, ns),
             Err(e) => return Err(Error::from(format!("no nameserver found for {zone}: {e}"))),
         };
@@ -564,6 +574,71 @@ impl RecursorDnsHandle {
         Ok((depth, ns))
     }
 
+    /// Find the name servers for the `zone` with QNAME minimisation, [RFC 9156, section 3](https://datatracker.ietf.org/doc/html/rfc9156#section-3)
+    ///
+    /// Starting from the closest enclosing zone with known name servers, a query with one more
+    /// label of the `zone` is sent to these name servers, see [`minimized_names`]. A referral in
+    /// the response makes the queried name the new enclosing zone. The queries are sent with the A
+    /// record type, as recommended in RFC 9156, section 2.1, the referrals are the same as for NS.
+    ///
+    /// Some name servers wrongly answer NXDOMAIN for empty non-terminals, so on NXDOMAIN the
+    /// minimisation stops, and the full name is sent to the name servers found so far.
+    async fn ns_pool_minimized(
+        &self,
+        zone: Name,
+        request_time: Instant,
+        mut depth: u8,
+    ) -> Result<(u8, RecursorPool<TokioRuntimeProvider>), Error> {
+        let (mut ancestor, mut ns) = self.closest_ns_pool(&zone);
+
+        for child in minimized_names(&ancestor, &zone) {
+            let query = Query::query(child.clone(), RecordType::A);
+            trace!("ns_pool_minimized: querying {child} at {ancestor}");
+
+            let error = match self
+                .lookup(query.clone(), ns.clone(), request_time, false)
+                .await
+            {
+                Ok(_) => continue,
+                Err(e) => e,
+            };
+
+            match error.kind() {
+                ErrorKind::ForwardNS(referral_ns) => {
+                    debug!("ns for {child} referred by {ancestor}");
+                    (depth, ns) = self
+                        .ns_pool_for_referral(query, referral_ns.clone(), request_time, depth)
+                        .await?;
+                    ancestor = child;
+                }
+                _ if error.is_nx_domain() => {
+                    debug!("{child} does not exist at {ancestor}, sending the full name");
+                    break;
+                }
+                // no data, the name is not a zone cut
+                ErrorKind::Forward(_) => continue,
+                _ => return Err(error),
+            }
+        }
+
+        Ok((depth, ns))
+    }
+
+    /// Returns the cached name servers for the closest enclosing zone of `name`
+    fn closest_ns_pool(&self, name: &Name) -> (Name, RecursorPool<TokioRuntimeProvider>) {
+        let mut cache = self.name_server_cache.lock();
+        let mut zone = name.clone();
+        while !zone.is_root() {
+            if let Some(ns) = cache.get_mut(&zone) {
+                debug!("returning cached pool for {zone}");
+                return (zone, ns.clone());
+            }
+            zone = zone.base_name();
+        }
+
+        (zone, self.roots.clone())
+    }
+
     /// Build an NS Pool based on an NS-record referral.
     ///
     /// Normally, when we build an NS Pool with ns_pool_for_zone, we search recursively, starting at
@@ -814,6 +889,7 @@ fn test_nameserver_filter() {
         deny_server,
         Arc::new(HashSet::new()),
         TtlConfig::default(),
+        false,
     );
 
     for addr in [
@@ -831,6 +907,82 @@ fn test_nameserver_filter() {
     }
 }
 
+#[cfg(test)]
+#[test]
+fn test_minimized_names() {
+    use std::str::FromStr;
+
+    let query_name = Name::from_str("a.b.example.com.").unwrap();
+    let zone = query_name.base_name();
+
+    // the intermediate queries only contain the suffix below the enclosing zone
+    let names = minimized_names(&Name::root(), &zone);
+    assert_eq!(
+        names,
+        ["com.", "example.com.", "b.example.com."]
+            .into_iter()
+            .map(|name| Name::from_str(name).unwrap())
+            .collect::<Vec<_>>()
+    );
+    assert!(names.iter().all(|name| *name != query_name));
+
+    let names = minimized_names(&Name::from_str("example.com.").unwrap(), &zone);
+    assert_eq!(names, vec![Name::from_str("b.example.com.").unwrap()]);
+
+    assert!(minimized_names(&zone, &zone).is_empty());
+    assert!(minimized_names(&Name::from_str("example.net.").unwrap(), &zone).is_empty());
+
+    // long names are covered in a limited number of queries
+    let long_name = Name::from_str(&"x.".repeat(30)).unwrap();
+    let names = minimized_names(&Name::root(), &long_name);
+    assert_eq!(names.len(), MAX_MINIMISE_COUNT);
+    for (i, name) in names.iter().take(MINIMISE_ONE_LAB).enumerate() {
+        assert_eq!(usize::from(name.num_labels()), i + 1);
+    }
+    for pair in names.windows(2) {
+        assert!(pair[0].zone_of(&pair[1]));
+        assert!(pair[0].num_labels() < pair[1].num_labels());
+    }
+    assert_eq!(names.last(), Some(&long_name));
+}
+
+/// Returns the names to query for, from `ancestor` towards `target`, with QNAME minimisation
+///
+/// Each name has one more label than the previous one, but long names are covered in at most
+/// [`MAX_MINIMISE_COUNT`] queries, as described in [RFC 9156, section 2.3](https://datatracker.ietf.org/doc/html/rfc9156#section-2.3).
+/// The last name is the `target`, unless it is not below the `ancestor`.
+fn minimized_names(ancestor: &Name, target: &Name) -> Vec<Name> {
+    if !ancestor.zone_of(target) {
+        return vec![];
+    }
+
+    let end = usize::from(target.num_labels());
+    let mut labels = usize::from(ancestor.num_labels());
+    let mut names = Vec::new();
+
+    while labels < end {
+        let iterations = names.len();
+        let step = if iterations < MINIMISE_ONE_LAB {
+            1
+        } else {
+            // spread the remaining labels over the remaining queries
+            let remaining = MAX_MINIMISE_COUNT - iterations;
+            (end - labels + remaining - 1) / remaining
+        };
+
+        labels = (labels + step).min(end);
+        names.push(target.trim_to(labels));
+    }
+
+    names
+}
+
+/// Maximum number of queries to send for QNAME minimisation, per RFC 9156, section 2.3
+const MAX_MINIMISE_COUNT: usize = 10;
+
+/// Number of queries adding a single label, before labels are added in bulk, per RFC 9156
+const MINIMISE_ONE_LAB: usize = 4;
+
 /// Maximum number of cname records to look up in a CNAME chain, regardless of the recursion
 /// depth limit
 const MAX_CNAME_LOOKUPS: u8 = 64;
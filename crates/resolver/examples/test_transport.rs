@@ -0,0 +1,80 @@
+//! Drives a resolver with canned answers, without any network access
+//!
+//! This is how code using the resolver can be tested, see `hickory_resolver::test_transport`.
+
+use hickory_resolver::{
+    config::ResolverOpts,
+    proto::{
+        op::{Message, Query},
+        rr::{
+            rdata::{A, CNAME},
+            Name, RData, Record, RecordType,
+        },
+    },
+    test_transport::TestTransport,
+    Resolver,
+};
+
+fn main() {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            tokio_main().await;
+        });
+}
+
+async fn tokio_main() {
+    let www = Name::from_ascii("www.example.com.").unwrap();
+    let alias = Name::from_ascii("alias.example.com.").unwrap();
+    let broken = Name::from_ascii("broken.example.com.").unwrap();
+    let large = Name::from_ascii("large.example.com.").unwrap();
+
+    let transport = TestTransport::new();
+    transport.answer_records(
+        Query::query(www.clone(), RecordType::A),
+        vec![Record::from_rdata(
+            www.clone(),
+            300,
+            RData::A(A::new(192, 0, 2, 1)),
+        )],
+    );
+    transport.answer_records(
+        Query::query(alias.clone(), RecordType::A),
+        vec![
+            Record::from_rdata(alias.clone(), 300, RData::CNAME(CNAME(www.clone()))),
+            Record::from_rdata(www.clone(), 300, RData::A(A::new(192, 0, 2, 1))),
+        ],
+    );
+    transport.servfail(Query::query(broken.clone(), RecordType::A));
+
+    // over UDP the response is truncated, the resolver then retries the query over TCP
+    let mut response = Message::new();
+    response.insert_answers(
+        (1..=20)
+            .map(|i| Record::from_rdata(large.clone(), 300, RData::A(A::new(192, 0, 2, i))))
+            .collect(),
+    );
+    transport.truncate(Query::query(large.clone(), RecordType::A), response);
+
+    let resolver = Resolver::new(
+        TestTransport::config(),
+        ResolverOpts::default(),
+        transport.clone(),
+    );
+
+    for name in [www, alias, broken, large] {
+        match resolver.ipv4_lookup(name.clone()).await {
+            Ok(lookup) => {
+                let addresses = lookup.iter().map(ToString::to_string).collect::<Vec<_>>();
+                println!("{name}: {}", addresses.join(", "));
+            }
+            Err(error) => println!("{name}: {error}"),
+        }
+    }
+
+    for (protocol, query) in transport.queries() {
+        println!("sent over {protocol}: {query}");
+    }
+}
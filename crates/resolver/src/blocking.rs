@@ -0,0 +1,235 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A blocking Resolver, for consumers without an async runtime
+//!
+//! ```rust,no_run
+//! use hickory_resolver::blocking::Resolver;
+//! use hickory_resolver::config::{ResolverConfig, ResolverOpts};
+//!
+//! let resolver = Resolver::new(ResolverConfig::default(), ResolverOpts::default()).unwrap();
+//! let response = resolver.lookup_ip("www.example.com.").unwrap();
+//! for ip in response.iter() {
+//!     println!("{ip}");
+//! }
+//! ```
+
+use std::fmt;
+use std::io;
+use std::net::IpAddr;
+
+use tokio::runtime::{self, Runtime};
+
+use crate::config::{ResolverConfig, ResolverOpts};
+use crate::error::ResolveError;
+use crate::lookup::{Lookup, ReverseLookup};
+use crate::lookup_ip::LookupIp;
+use crate::proto::rr::RecordType;
+use crate::{Hosts, IntoName, TokioResolver};
+
+/// A Resolver which blocks the calling thread until each lookup completes
+///
+/// The Resolver owns a current-thread Tokio runtime, which only runs while a lookup is performed
+/// on the calling thread: the connections to the name servers and the refreshes of the cache make
+/// progress during the lookups, not in between. Creating one Resolver per thread is fine, the
+/// runtimes are independent. A Resolver can also be shared between threads, the concurrent
+/// lookups run one at a time.
+///
+/// The blocking calls panic when called from within an async runtime, use [`TokioResolver`]
+/// there instead.
+pub struct Resolver {
+    runtime: Runtime,
+    resolver: TokioResolver,
+}
+
+impl Resolver {
+    /// Constructs a new blocking Resolver with the provided configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - configuration, name_servers, etc. for the Resolver
+    /// * `options` - basic lookup options for the resolver
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the runtime of the Resolver can't be created.
+    pub fn new(config: ResolverConfig, options: ResolverOpts) -> io::Result<Self> {
+        Ok(Self {
+            runtime: runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?,
+            resolver: TokioResolver::tokio(config, options),
+        })
+    }
+
+    /// Constructs a new blocking Resolver with the system configuration.
+    ///
+    /// This will use `/etc/resolv.conf` on Unix OSes and the registry on Windows.
+    #[cfg(any(unix, target_os = "windows"))]
+    #[cfg(feature = "system-config")]
+    pub fn from_system_conf() -> Result<Self, ResolveError> {
+        let (config, options) = crate::system_conf::read_system_conf()?;
+        Ok(Self::new(config, options)?)
+    }
+
+    /// Generic lookup for any RecordType, see [`TokioResolver::lookup`]
+    pub fn lookup(
+        &self,
+        name: impl IntoName,
+        record_type: RecordType,
+    ) -> Result<Lookup, ResolveError> {
+        self.runtime
+            .block_on(self.resolver.lookup(name, record_type))
+    }
+
+    /// Performs a dual-stack DNS lookup for the IP for the given hostname, see
+    /// [`TokioResolver::lookup_ip`]
+    pub fn lookup_ip(&self, host: impl IntoName) -> Result<LookupIp, ResolveError> {
+        self.runtime.block_on(self.resolver.lookup_ip(host))
+    }
+
+    /// Performs a lookup of the names of the IP, see [`TokioResolver::reverse_lookup`]
+    pub fn reverse_lookup(&self, query: IpAddr) -> Result<ReverseLookup, ResolveError> {
+        self.runtime.block_on(self.resolver.reverse_lookup(query))
+    }
+
+    /// Customizes the static hosts used in this resolver.
+    pub fn set_hosts(&mut self, hosts: Option<Hosts>) {
+        self.resolver.set_hosts(hosts);
+    }
+
+    /// Flushes/Removes all entries from the cache
+    pub fn clear_cache(&self) {
+        self.resolver.clear_cache();
+    }
+
+    /// The async Resolver performing the lookups
+    pub fn as_async(&self) -> &TokioResolver {
+        &self.resolver
+    }
+}
+
+impl fmt::Debug for Resolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Resolver")
+            .field("resolver", &self.resolver)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::config::{NameServerConfig, ResolveHosts};
+    use crate::proto::op::{Message, MessageType};
+    use crate::proto::rr::rdata::{A, PTR};
+    use crate::proto::rr::{Name, RData, Record};
+    use crate::proto::xfer::Protocol;
+
+    /// Answers the A and PTR queries over UDP, until no query is received for a while
+    fn name_server() -> SocketAddr {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        let addr = socket.local_addr().unwrap();
+
+        thread::spawn(move || {
+            let mut buf = [0; 512];
+            while let Ok((len, src)) = socket.recv_from(&mut buf) {
+                let mut message = Message::from_vec(&buf[..len]).unwrap();
+                let query = message.queries()[0].clone();
+                let rdata = match query.query_type() {
+                    RecordType::A => Some(RData::A(A::new(192, 0, 2, 1))),
+                    RecordType::PTR => Some(RData::PTR(PTR(
+                        Name::from_ascii("host.example.com.").unwrap()
+                    ))),
+                    _ => None,
+                };
+
+                message.set_message_type(MessageType::Response);
+                if let Some(rdata) = rdata {
+                    message.add_answer(Record::from_rdata(query.name().clone(), 300, rdata));
+                }
+                socket.send_to(&message.to_vec().unwrap(), src).unwrap();
+            }
+        });
+
+        addr
+    }
+
+    fn resolver(name_server: SocketAddr) -> Resolver {
+        let mut config = ResolverConfig::new();
+        config.add_name_server(NameServerConfig::new(name_server, Protocol::Udp));
+        let options = ResolverOpts {
+            use_hosts_file: ResolveHosts::Never,
+            ..ResolverOpts::default()
+        };
+        Resolver::new(config, options).unwrap()
+    }
+
+    #[test]
+    fn test_blocking_lookups() {
+        let resolver = resolver(name_server());
+        let ip = IpAddr::from(Ipv4Addr::new(192, 0, 2, 1));
+
+        let response = resolver.lookup_ip("host.example.com.").unwrap();
+        assert_eq!(response.iter().collect::<Vec<_>>(), vec![ip]);
+
+        let response = resolver.lookup("host.example.com.", RecordType::A).unwrap();
+        assert_eq!(response.records().len(), 1);
+
+        let response = resolver.reverse_lookup(ip).unwrap();
+        let names = response.iter().map(|ptr| ptr.0.clone()).collect::<Vec<_>>();
+        assert_eq!(names, vec![Name::from_ascii("host.example.com.").unwrap()]);
+    }
+
+    #[test]
+    fn test_resolver_per_thread() {
+        let name_server = name_server();
+        let threads = (0..4)
+            .map(|_| {
+                thread::spawn(move || {
+                    let resolver = resolver(name_server);
+                    for _ in 0..4 {
+                        resolver.clear_cache();
+                        let response = resolver.lookup_ip("host.example.com.").unwrap();
+                        assert_eq!(response.iter().count(), 1);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_shared_resolver() {
+        let resolver = Arc::new(resolver(name_server()));
+        let threads = (0..4)
+            .map(|_| {
+                let resolver = resolver.clone();
+                thread::spawn(move || {
+                    resolver
+                        .lookup_ip("host.example.com.")
+                        .map(|r| r.iter().count())
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for thread in threads {
+            assert_eq!(thread.join().unwrap().unwrap(), 1);
+        }
+    }
+}
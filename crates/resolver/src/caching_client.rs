@@ -7,15 +7,28 @@
 
 //! Caching related functionality for the Resolver.
 
-use std::{borrow::Cow, future::Future, pin::Pin, sync::Arc, time::Instant};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    time::Instant,
+};
 
-use futures_util::future::TryFutureExt;
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+use ipnet::IpNet;
 use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use tracing::debug;
 
 use crate::{
-    dns_lru::{self, DnsLru, TtlConfig},
+    config::Dns64Prefix,
+    dns64::Dns64,
+    dns_lru::{self, CachedAnswer, DnsLru, TtlConfig},
     error::ResolveError,
-    lookup::Lookup,
+    lookup::{Lookup, LookupMetadata},
     proto::{
         op::{Query, ResponseCode},
         rr::{
@@ -23,7 +36,10 @@ use crate::{
                 ResolverUsage, DEFAULT, INVALID, IN_ADDR_ARPA_127, IP6_ARPA_1, LOCAL,
                 LOCALHOST as LOCALHOST_usage, ONION,
             },
-            rdata::{A, AAAA, CNAME, PTR, SOA},
+            rdata::{
+                opt::{EdnsCode, EdnsOption},
+                A, AAAA, CNAME, PTR, SOA,
+            },
             resource::RecordRef,
             DNSClass, Name, RData, Record, RecordType,
         },
@@ -31,6 +47,10 @@ use crate::{
         {ForwardNSData, ProtoError, ProtoErrorKind},
     },
 };
+#[cfg(feature = "dnssec-ring")]
+use crate::{
+    config::DnssecMode, lookup::DnssecStatus, nsec_cache::NsecCache, proto::dnssec::Proof,
+};
 
 static LOCALHOST: Lazy<RData> =
     Lazy::new(|| RData::PTR(PTR(Name::from_ascii("localhost.").unwrap())));
@@ -57,6 +77,64 @@ impl DepthTracker {
     const MAX_QUERY_DEPTH: u8 = 8; // arbitrarily chosen number...
 }
 
+/// Spawns a future in the background, used to refresh the cached answers
+pub(crate) type Spawner = Arc<dyn Fn(Pin<Box<dyn Future<Output = ()> + Send>>) + Send + Sync>;
+
+#[derive(Clone)]
+struct BackgroundRefresh {
+    spawn: Spawner,
+    /// Queries which are currently refreshed
+    in_flight: Arc<Mutex<HashSet<Query>>>,
+}
+
+impl fmt::Debug for BackgroundRefresh {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BackgroundRefresh")
+            .field("in_flight", &self.in_flight)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The upstream queries in flight, shared by the concurrent lookups of the same query
+type InFlightQueries =
+    Arc<Mutex<HashMap<(Query, DnsRequestOptions), Shared<BoxFuture<'static, QueryResult>>>>>;
+
+type QueryResult = Result<DnsResponse, ProtoError>;
+
+/// Removes the query in flight when the lookup waiting for it completes or is dropped
+///
+/// A cancelled lookup also removes the query still awaited by other lookups, the later identical
+/// queries are then sent again rather than leaking the entry.
+struct InFlightGuard<'a> {
+    queries: &'a InFlightQueries,
+    key: (Query, DnsRequestOptions),
+    response: Shared<BoxFuture<'static, QueryResult>>,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        let mut queries = self.queries.lock();
+        if queries
+            .get(&self.key)
+            .is_some_and(|other| other.ptr_eq(&self.response))
+        {
+            queries.remove(&self.key);
+        }
+    }
+}
+
+/// Removes the refreshed query once the refresh completes or is dropped without running
+struct RefreshGuard {
+    queries: Arc<Mutex<HashSet<Query>>>,
+    query: Query,
+}
+
+impl Drop for RefreshGuard {
+    fn drop(&mut self) {
+        self.queries.lock().remove(&self.query);
+    }
+}
+
 // TODO: need to consider this storage type as it compares to Authority in server...
 //       should it just be an variation on Authority?
 #[derive(Clone, Debug)]
@@ -68,6 +146,19 @@ where
     lru: DnsLru,
     client: C,
     preserve_intermediates: bool,
+    client_subnet: Option<IpNet>,
+    refresh: Option<BackgroundRefresh>,
+    prefetch_threshold: Option<f64>,
+    /// Set when the concurrent identical queries are coalesced
+    in_flight: Option<InFlightQueries>,
+    /// Set for the background refreshes, which always query the name servers
+    is_refresh: bool,
+    dns64: Option<Dns64>,
+    #[cfg(feature = "dnssec-ring")]
+    dnssec_mode: DnssecMode,
+    /// Set when the negative answers are synthesized from the cached NSEC records
+    #[cfg(feature = "dnssec-ring")]
+    nsec_cache: Option<Arc<NsecCache>>,
 }
 
 impl<C> CachingClient<C>
@@ -80,33 +171,198 @@ where
             DnsLru::new(max_size, TtlConfig::default()),
             client,
             preserve_intermediates,
+            None,
         )
     }
 
-    pub(crate) fn with_cache(lru: DnsLru, client: C, preserve_intermediates: bool) -> Self {
+    pub(crate) fn with_cache(
+        lru: DnsLru,
+        client: C,
+        preserve_intermediates: bool,
+        client_subnet: Option<IpNet>,
+    ) -> Self {
         Self {
             lru,
             client,
             preserve_intermediates,
+            client_subnet: client_subnet.map(|subnet| subnet.trunc()),
+            refresh: None,
+            prefetch_threshold: None,
+            in_flight: None,
+            is_refresh: false,
+            dns64: None,
+            #[cfg(feature = "dnssec-ring")]
+            dnssec_mode: DnssecMode::default(),
+            #[cfg(feature = "dnssec-ring")]
+            nsec_cache: None,
         }
     }
 
+    /// Synthesize AAAA records from A records with the NAT64 `prefix`, see [`Dns64`]
+    pub(crate) fn with_dns64(mut self, prefix: Option<Dns64Prefix>) -> Self {
+        self.dns64 = prefix.map(Dns64::new);
+        self
+    }
+
+    /// Fail the lookups of bogus records with [`DnssecMode::ValidateAndFail`]
+    #[cfg(feature = "dnssec-ring")]
+    pub(crate) fn with_dnssec_mode(mut self, dnssec_mode: DnssecMode) -> Self {
+        self.dnssec_mode = dnssec_mode;
+        self
+    }
+
+    /// Synthesize the negative answers from the validated NSEC and NSEC3 records, see [`NsecCache`]
+    ///
+    /// Up to `max_records` records are cached.
+    #[cfg(feature = "dnssec-ring")]
+    pub(crate) fn with_aggressive_nsec(mut self, max_records: usize) -> Self {
+        self.nsec_cache = Some(Arc::new(NsecCache::new(max_records)));
+        self
+    }
+
+    /// Refresh cached answers in the background with `spawn`
+    ///
+    /// This is required for serving stale answers from the cache when the lookups fail, the cache
+    /// must also retain the expired entries, see [`DnsLru::with_serve_stale`], and for prefetching.
+    pub(crate) fn with_background_refresh(mut self, spawn: Spawner) -> Self {
+        self.refresh = Some(BackgroundRefresh {
+            spawn,
+            in_flight: Arc::default(),
+        });
+        self
+    }
+
+    /// Prefetch the cached answers which have less than `threshold` of their TTL remaining
+    ///
+    /// The cached answer is returned, while the query is sent again in the background. This
+    /// requires [`Self::with_background_refresh`].
+    pub(crate) fn with_prefetch_threshold(mut self, threshold: Option<f64>) -> Self {
+        self.prefetch_threshold = threshold;
+        self
+    }
+
+    /// Share a single upstream query between the concurrent lookups of the same query
+    pub(crate) fn with_query_coalescing(mut self, enabled: bool) -> Self {
+        self.in_flight = enabled.then(Arc::default);
+        self
+    }
+
     /// Perform a lookup against this caching client, looking first in the cache for a result
     pub fn lookup(
         &mut self,
         query: Query,
         options: DnsRequestOptions,
     ) -> Pin<Box<dyn Future<Output = Result<Lookup, ResolveError>> + Send>> {
-        Box::pin(
-            Self::inner_lookup(
-                query,
-                options,
-                self.clone(),
-                vec![],
-                DepthTracker::default(),
-            )
-            .map_err(ResolveError::from),
+        let client = self.clone();
+        Box::pin(async move {
+            let started = Instant::now();
+            let cached = client.lookup_from_cache(&query, options);
+            let from_cache = cached.is_some();
+            let result = async {
+                let result = match cached {
+                    Some(cached) => cached,
+                    None => {
+                        Self::inner_lookup(
+                            query.clone(),
+                            options,
+                            client.clone(),
+                            vec![],
+                            DepthTracker::default(),
+                        )
+                        .await
+                    }
+                };
+
+                let result = match client.dns64 {
+                    Some(dns64) if query.query_type() == RecordType::AAAA => {
+                        client
+                            .dns64_lookup(dns64, query.clone(), options, result)
+                            .await
+                    }
+                    _ => result,
+                };
+
+                #[cfg(feature = "dnssec-ring")]
+                if let (DnssecMode::ValidateAndFail, Ok(lookup)) = (client.dnssec_mode, &result) {
+                    if lookup.dnssec_status() == DnssecStatus::Bogus {
+                        debug!("failing the lookup of bogus records for {query}");
+                        return Err(ProtoError::from(ProtoErrorKind::Nsec {
+                            query: Box::new(query),
+                            proof: Proof::Bogus,
+                        }));
+                    }
+                }
+
+                result
+            }
+            .await;
+
+            // the upstream duration covers the whole lookup, including the CNAME chain
+            let upstream_duration = started.elapsed();
+            match result {
+                Ok(lookup) if from_cache || lookup.metadata().from_cache => Ok(lookup),
+                Ok(lookup) => {
+                    let metadata = lookup.metadata().with_upstream_duration(upstream_duration);
+                    Ok(lookup.with_metadata(metadata))
+                }
+                Err(e) if from_cache => {
+                    Err(ResolveError::from(e).with_metadata(LookupMetadata::cached()))
+                }
+                Err(e) => Err(ResolveError::from(e).with_metadata(
+                    LookupMetadata::default().with_upstream_duration(upstream_duration),
+                )),
+            }
+        })
+    }
+
+    /// Synthesizes the answer to the AAAA `query` if there are no AAAA records, RFC 6147, section 5.1
+    ///
+    /// Nothing is synthesized for NXDOMAIN responses, or when there are AAAA records in the answer.
+    async fn dns64_lookup(
+        &self,
+        dns64: Dns64,
+        query: Query,
+        options: DnsRequestOptions,
+        result: Result<Lookup, ProtoError>,
+    ) -> Result<Lookup, ProtoError> {
+        let negative_ttl = match &result {
+            Ok(lookup)
+                if lookup
+                    .record_iter()
+                    .any(|r| r.record_type() == RecordType::AAAA) =>
+            {
+                return result
+            }
+            Ok(_) => None,
+            Err(e) => match e.kind() {
+                ProtoErrorKind::NoRecordsFound {
+                    response_code,
+                    negative_ttl,
+                    ..
+                } if *response_code != ResponseCode::NXDomain => *negative_ttl,
+                _ => return result,
+            },
+        };
+
+        let a_lookup = Self::inner_lookup(
+            Query::query(query.name().clone(), RecordType::A),
+            options,
+            self.clone(),
+            vec![],
+            DepthTracker::default(),
         )
+        .await;
+
+        match a_lookup {
+            Ok(a_lookup) => match dns64.synthesize(query, &a_lookup, negative_ttl) {
+                Some(lookup) => {
+                    debug!("synthesized AAAA records for {}", lookup.query());
+                    Ok(lookup)
+                }
+                None => result,
+            },
+            Err(_) => result,
+        }
     }
 
     async fn inner_lookup(
@@ -176,16 +432,18 @@ where
         let is_dnssec = client.client.is_verifying_dnssec();
 
         // first transition any polling that is needed (mutable refs...)
-        if let Some(cached_lookup) = client.lookup_from_cache(&query) {
+        if let Some(cached_lookup) = client.lookup_from_cache(&query, options) {
             return cached_lookup;
         };
 
-        let response_message = client
-            .client
-            .lookup(query.clone(), options)
-            .first_answer()
-            .await
-            .map_err(ProtoError::into);
+        #[cfg(feature = "dnssec-ring")]
+        if let Some(nsec_cache) = client.nsec_cache.as_ref().filter(|_| !client.is_refresh) {
+            if let Some(error) = nsec_cache.synthesize(&query, Instant::now()) {
+                return client.cache(query, None, Err(error));
+            }
+        }
+
+        let response_message = client.send(query.clone(), options).await;
 
         // TODO: technically this might be duplicating work, as name_server already performs this evaluation.
         //  we may want to create a new type, if evaluated... but this is most generic to support any impl in LookupState...
@@ -195,11 +453,27 @@ where
             response_message
         };
 
+        // without a response to inspect, assume the answer is only valid for our client subnet
+        let mut scope = client.client_subnet;
+        let mut metadata = LookupMetadata::default();
+
         // TODO: take all records and cache them?
         //  if it's DNSSEC they must be signed, otherwise?
         let records: Result<Records, ProtoError> = match response_message {
             // this is the only cacheable form
             Err(e) => {
+                #[cfg(feature = "dnssec-ring")]
+                if let (
+                    Some(nsec_cache),
+                    ProtoErrorKind::NoRecordsFound {
+                        authorities: Some(authorities),
+                        ..
+                    },
+                ) = (client.nsec_cache.as_ref().filter(|_| is_dnssec), e.kind())
+                {
+                    nsec_cache.insert(authorities, Instant::now());
+                }
+
                 match e.kind() {
                     ProtoErrorKind::NoRecordsFound {
                         query,
@@ -221,10 +495,18 @@ where
                             *trusted,
                         ))
                     }
-                    _ => return Err(e),
+                    _ => {
+                        if let Some(lookup) = client.serve_stale(&query, options) {
+                            return Ok(lookup);
+                        }
+                        return Err(e);
+                    }
                 }
             }
             Ok(response_message) => {
+                scope = client.response_scope(&response_message);
+                metadata = LookupMetadata::from_response(&response_message);
+
                 // allow the handle_noerror function to deal with any error codes
                 let records = Self::handle_noerror(
                     &mut client,
@@ -247,16 +529,140 @@ where
                 min_ttl: ttl,
             }) => match future.await {
                 Ok(lookup) => client.cname(lookup, query, ttl),
-                Err(e) => client.cache(query, Err(e)),
+                Err(e) => client.cache(query, scope, Err(e)),
             },
-            Ok(Records::Exists(rdata)) => client.cache(query, Ok(rdata)),
-            Err(e) => client.cache(query, Err(e)),
+            Ok(Records::Exists(rdata)) => client
+                .cache(query, scope, Ok(rdata))
+                .map(|lookup| lookup.with_metadata(metadata)),
+            Err(e) => client.cache(query, scope, Err(e)),
         }
     }
 
+    /// Sends the query to the name servers, or waits for the identical query in flight
+    ///
+    /// The query in flight is removed once completed, so that a failure is not shared with the
+    /// later lookups, or once a lookup waiting for it is cancelled.
+    async fn send(&self, query: Query, options: DnsRequestOptions) -> QueryResult {
+        let Some(in_flight) = &self.in_flight else {
+            return self.client.lookup(query, options).first_answer().await;
+        };
+
+        let key = (query, options);
+        let response = in_flight
+            .lock()
+            .entry(key.clone())
+            .or_insert_with(|| {
+                let response = self.client.lookup(key.0.clone(), options).first_answer();
+                response.boxed().shared()
+            })
+            .clone();
+
+        let guard = InFlightGuard {
+            queries: in_flight,
+            key,
+            response,
+        };
+        guard.response.clone().await
+    }
+
     /// Check if this query is already cached
-    fn lookup_from_cache(&self, query: &Query) -> Option<Result<Lookup, ProtoError>> {
-        self.lru.get(query, Instant::now())
+    ///
+    /// Answers close to their expiration are prefetched, if a prefetch threshold is configured.
+    fn lookup_from_cache(
+        &self,
+        query: &Query,
+        options: DnsRequestOptions,
+    ) -> Option<Result<Lookup, ProtoError>> {
+        if self.is_refresh {
+            return None;
+        }
+
+        let now = Instant::now();
+        let entry = self.lru.get_scoped_entry(query, self.client_subnet, now)?;
+        if let Some(threshold) = self.prefetch_threshold {
+            let positive = matches!(entry.answer(), CachedAnswer::Records { .. });
+            if positive && entry.ttl_fraction(now) < threshold {
+                debug!("prefetching {query}");
+                self.spawn_refresh(query, options);
+            }
+        }
+
+        Some(
+            entry
+                .lookup()
+                .map(|lookup| lookup.with_metadata(LookupMetadata::cached())),
+        )
+    }
+
+    /// Returns the stale answer to the query after a failed lookup, and refreshes it in the background
+    ///
+    /// See [RFC 8767](https://tools.ietf.org/html/rfc8767). The refresh itself does not serve
+    /// stale answers.
+    fn serve_stale(&self, query: &Query, options: DnsRequestOptions) -> Option<Lookup> {
+        if self.refresh.is_none() || self.is_refresh {
+            return None;
+        }
+
+        let lookup = self
+            .lru
+            .get_stale(query, self.client_subnet, Instant::now())?;
+        debug!("serving stale answer for {query}");
+        self.spawn_refresh(query, options);
+
+        Some(lookup.with_metadata(LookupMetadata::cached()))
+    }
+
+    /// Sends the query again in the background, updating the cache
+    ///
+    /// Only one refresh per query is in flight.
+    fn spawn_refresh(&self, query: &Query, options: DnsRequestOptions) {
+        let Some(refresh) = &self.refresh else {
+            return;
+        };
+        if !refresh.in_flight.lock().insert(query.clone()) {
+            return;
+        }
+
+        let mut client = self.clone();
+        client.is_refresh = true;
+        let guard = RefreshGuard {
+            queries: Arc::clone(&refresh.in_flight),
+            query: query.clone(),
+        };
+
+        (refresh.spawn)(Box::pin(async move {
+            let query = &guard.query;
+            if let Err(e) = Self::inner_lookup(
+                query.clone(),
+                options,
+                client,
+                vec![],
+                DepthTracker::default(),
+            )
+            .await
+            {
+                debug!("refresh of {query} failed: {e}");
+            }
+        }));
+    }
+
+    /// The subnet the answer in the response should be cached for, `None` if it is valid for all clients
+    ///
+    /// See [RFC 7871, section 7.3.1](https://tools.ietf.org/html/rfc7871#section-7.3.1), a
+    /// response without an ECS option is treated as having a scope prefix length of 0.
+    fn response_scope(&self, response: &DnsResponse) -> Option<IpNet> {
+        let subnet = self.client_subnet?;
+        let edns = response.extensions().as_ref()?;
+
+        match edns.option(EdnsCode::Subnet) {
+            // a scope longer than the source prefix length is limited to the subnet that was sent
+            Some(EdnsOption::Subnet(ecs)) if ecs.scope_prefix() > 0 => {
+                IpNet::new(subnet.addr(), ecs.scope_prefix().min(subnet.prefix_len()))
+                    .map(|scope| scope.trunc())
+                    .ok()
+            }
+            _ => None,
+        }
     }
 
     /// See https://tools.ietf.org/html/rfc2308
@@ -386,6 +792,15 @@ where
             // TODO: this needs to be enhanced for SRV
             let mut found_name = false;
 
+            // the glue of all the SRV targets is kept, not only that of the search name
+            let srv_targets = answers
+                .iter()
+                .filter_map(|r| match r.data() {
+                    RData::SRV(srv) => Some(srv.target().clone()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+
             // After following all the CNAMES to the last one, try and lookup the final name
             let records = answers
                 .into_iter()
@@ -414,7 +829,7 @@ where
                         //    and it's an IP
                         if query.query_type().is_srv()
                             && r.record_type().is_ip_addr()
-                            && search_name.as_ref() == r.name()
+                            && (search_name.as_ref() == r.name() || srv_targets.contains(r.name()))
                         {
                             found_name = true;
                             Some((r, ttl))
@@ -477,25 +892,33 @@ where
 
     #[allow(clippy::unnecessary_wraps)]
     fn cname(&self, lookup: Lookup, query: Query, cname_ttl: u32) -> Result<Lookup, ProtoError> {
-        // this duplicates the cache entry under the original query
-        Ok(self.lru.duplicate(query, lookup, cname_ttl, Instant::now()))
+        // this duplicates the cache entry under the original query, the scope of the final answer
+        //  isn't known here, so limit it to our client subnet
+        Ok(self
+            .lru
+            .duplicate(query, self.client_subnet, lookup, cname_ttl, Instant::now()))
     }
 
     fn cache(
         &self,
         query: Query,
+        scope: Option<IpNet>,
         records: Result<Vec<(Record, u32)>, ProtoError>,
     ) -> Result<Lookup, ProtoError> {
         // this will put this object into an inconsistent state, but no one should call poll again...
         match records {
-            Ok(rdata) => Ok(self.lru.insert(query, rdata, Instant::now())),
-            Err(err) => Err(self.lru.negative(query, err, Instant::now())),
+            Ok(rdata) => Ok(self.lru.insert_scoped(query, scope, rdata, Instant::now())),
+            Err(err) => Err(self.lru.negative(query, scope, err, Instant::now())),
         }
     }
 
     /// Flushes/Removes all entries from the cache
     pub fn clear_cache(&self) {
         self.lru.clear();
+        #[cfg(feature = "dnssec-ring")]
+        if let Some(nsec_cache) = &self.nsec_cache {
+            nsec_cache.clear();
+        }
     }
 }
 
@@ -516,10 +939,15 @@ mod tests {
     use std::str::FromStr;
     use std::time::*;
 
-    use crate::proto::op::{Message, Query};
-    use crate::proto::rr::rdata::{NS, SRV};
+    use crate::proto::op::{Edns, Message, Query};
+    use crate::proto::rr::rdata::{opt::ClientSubnet, AAAA, NS, SRV};
     use crate::proto::rr::{Name, Record};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::proto::xfer::{DnsRequest, Protocol};
     use futures_executor::block_on;
+    use futures_util::future;
+    use futures_util::stream::{self, Stream};
 
     use super::*;
     use crate::lookup_ip::tests::*;
@@ -528,7 +956,7 @@ mod tests {
     fn test_empty_cache() {
         let cache = DnsLru::new(1, dns_lru::TtlConfig::default());
         let client = mock(vec![empty()]);
-        let client = CachingClient::with_cache(cache, client, false);
+        let client = CachingClient::with_cache(cache, client, false, None);
 
         if let ProtoErrorKind::NoRecordsFound {
             query,
@@ -569,7 +997,7 @@ mod tests {
         );
 
         let client = mock(vec![empty()]);
-        let client = CachingClient::with_cache(cache, client, false);
+        let client = CachingClient::with_cache(cache, client, false, None);
 
         let ips = block_on(CachingClient::inner_lookup(
             Query::new(),
@@ -591,7 +1019,7 @@ mod tests {
         let cache = DnsLru::new(1, dns_lru::TtlConfig::default());
         // first should come from client...
         let client = mock(vec![v4_message()]);
-        let client = CachingClient::with_cache(cache.clone(), client, false);
+        let client = CachingClient::with_cache(cache.clone(), client, false, None);
 
         let ips = block_on(CachingClient::inner_lookup(
             Query::query(Name::root(), RecordType::A),
@@ -609,7 +1037,7 @@ mod tests {
 
         // next should come from cache...
         let client = mock(vec![empty()]);
-        let client = CachingClient::with_cache(cache, client, false);
+        let client = CachingClient::with_cache(cache, client, false, None);
 
         let ips = block_on(CachingClient::inner_lookup(
             Query::query(Name::root(), RecordType::A),
@@ -626,6 +1054,957 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_lookup_metadata() {
+        let cache = DnsLru::new(1, dns_lru::TtlConfig::default());
+        let query = Query::query(Name::root(), RecordType::A);
+        let server = SocketAddr::from(([192, 0, 2, 53], 53));
+        let mut response = v4_message().unwrap();
+        response.set_server(server, Protocol::Udp);
+
+        // a fresh answer reports the name server which sent it
+        let client = mock(vec![Ok(response)]);
+        let client = CachingClient::with_cache(cache.clone(), client, false, None);
+        let lookup = block_on(CachingClient::inner_lookup(
+            query.clone(),
+            DnsRequestOptions::default(),
+            client,
+            vec![],
+            DepthTracker::default(),
+        ))
+        .unwrap();
+        assert_eq!(
+            lookup.metadata(),
+            LookupMetadata {
+                server: Some(server),
+                protocol: Some(Protocol::Udp),
+                from_cache: false,
+                upstream_duration: Duration::ZERO,
+            }
+        );
+
+        // the cached answer has no name server
+        let client = mock(vec![empty()]);
+        let client = CachingClient::with_cache(cache, client, false, None);
+        let lookup = block_on(CachingClient::inner_lookup(
+            query,
+            DnsRequestOptions::default(),
+            client,
+            vec![],
+            DepthTracker::default(),
+        ))
+        .unwrap();
+        assert!(lookup.metadata().from_cache);
+        assert_eq!(lookup.metadata().server, None);
+
+        // the answer at the end of a CNAME chain reports the name server which sent it
+        let cache = DnsLru::new(2, dns_lru::TtlConfig::default());
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let mut cname = cname_message().unwrap();
+        cname.set_server(SocketAddr::from(([192, 0, 2, 1], 53)), Protocol::Udp);
+        let mut actual = Message::new();
+        actual.add_query(Query::query(
+            Name::from_str("actual.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        actual.add_answer(Record::from_rdata(
+            Name::from_str("actual.example.com.").unwrap(),
+            86400,
+            RData::A(A::new(127, 0, 0, 1)),
+        ));
+        let mut actual = DnsResponse::from_message(actual).unwrap();
+        actual.set_server(server, Protocol::Tcp);
+
+        let client = mock(vec![Ok(actual), Ok(cname)]);
+        let client = CachingClient::with_cache(cache.clone(), client, false, None);
+        let lookup = block_on(CachingClient::inner_lookup(
+            query.clone(),
+            DnsRequestOptions::default(),
+            client,
+            vec![],
+            DepthTracker::default(),
+        ))
+        .unwrap();
+        assert_eq!(
+            lookup.metadata(),
+            LookupMetadata {
+                server: Some(server),
+                protocol: Some(Protocol::Tcp),
+                from_cache: false,
+                upstream_duration: Duration::ZERO,
+            }
+        );
+
+        // the chain is then answered from the cache
+        let client = mock(vec![empty()]);
+        let client = CachingClient::with_cache(cache, client, false, None);
+        let lookup = block_on(CachingClient::inner_lookup(
+            query,
+            DnsRequestOptions::default(),
+            client,
+            vec![],
+            DepthTracker::default(),
+        ))
+        .unwrap();
+        assert!(lookup.metadata().from_cache);
+        assert_eq!(lookup.metadata().server, None);
+    }
+
+    type RefreshQueue = Arc<Mutex<Vec<Pin<Box<dyn Future<Output = ()> + Send>>>>>;
+
+    /// Queues the refreshes, to run them when the test decides to
+    fn queue_spawner() -> (Spawner, RefreshQueue) {
+        let queue = Arc::new(Mutex::new(Vec::new()));
+        let spawner = {
+            let queue = Arc::clone(&queue);
+            Arc::new(move |refresh| queue.lock().push(refresh)) as Spawner
+        };
+        (spawner, queue)
+    }
+
+    /// A handle counting the queries sent, which answers after a delay, so that the concurrent
+    /// queries are all in flight
+    #[derive(Clone)]
+    struct CountingDnsHandle {
+        sent: Arc<AtomicUsize>,
+        response: fn() -> Result<DnsResponse, ProtoError>,
+    }
+
+    impl DnsHandle for CountingDnsHandle {
+        type Response = Pin<Box<dyn Stream<Item = Result<DnsResponse, ProtoError>> + Send>>;
+
+        fn send<R: Into<DnsRequest>>(&self, _: R) -> Self::Response {
+            self.sent.fetch_add(1, Ordering::SeqCst);
+            let response = self.response;
+            Box::pin(stream::once(
+                tokio::time::sleep(Duration::from_millis(10)).map(move |_| response()),
+            ))
+        }
+    }
+
+    /// Performs 100 concurrent lookups of the same query, returns the number of queries sent
+    async fn concurrent_lookups(
+        coalesce: bool,
+        response: fn() -> Result<DnsResponse, ProtoError>,
+    ) -> (Vec<Result<Lookup, ProtoError>>, Arc<AtomicUsize>) {
+        let sent = Arc::new(AtomicUsize::new(0));
+        let handle = CountingDnsHandle {
+            sent: sent.clone(),
+            response,
+        };
+        let client = CachingClient::new(0, handle, false).with_query_coalescing(coalesce);
+
+        let lookup = || {
+            CachingClient::inner_lookup(
+                Query::query(Name::root(), RecordType::A),
+                DnsRequestOptions::default(),
+                client.clone(),
+                vec![],
+                DepthTracker::default(),
+            )
+        };
+        let mut results = future::join_all((0..100).map(|_| lookup())).await;
+
+        // the query is sent again after the completion of the shared one
+        results.push(lookup().await);
+        (results, sent)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_coalesced_queries() {
+        let (results, sent) = concurrent_lookups(true, v4_message).await;
+        assert_eq!(sent.load(Ordering::SeqCst), 2);
+        for result in results {
+            assert_eq!(
+                result.unwrap().iter().cloned().collect::<Vec<_>>(),
+                vec![RData::A(A::new(127, 0, 0, 1))]
+            );
+        }
+
+        let (results, sent) = concurrent_lookups(false, v4_message).await;
+        assert_eq!(sent.load(Ordering::SeqCst), 101);
+        assert!(results.iter().all(Result::is_ok));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_coalesced_query_cancelled() {
+        let handle = CountingDnsHandle {
+            sent: Arc::default(),
+            response: v4_message,
+        };
+        let client = CachingClient::new(0, handle, false).with_query_coalescing(true);
+        let in_flight = client.in_flight.clone().unwrap();
+
+        let lookup = tokio::spawn(async move {
+            let query = Query::query(Name::root(), RecordType::A);
+            client.send(query, DnsRequestOptions::default()).await
+        });
+        tokio::task::yield_now().await;
+        assert_eq!(in_flight.lock().len(), 1);
+
+        // the query in flight is removed once the lookup is cancelled
+        lookup.abort();
+        assert!(lookup.await.unwrap_err().is_cancelled());
+        assert!(in_flight.lock().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_coalesced_failure() {
+        // the failure is delivered to all the lookups, and not to the later ones
+        let (results, sent) = concurrent_lookups(true, error).await;
+        assert_eq!(sent.load(Ordering::SeqCst), 2);
+        assert_eq!(results.len(), 101);
+        assert!(results.iter().all(Result::is_err));
+    }
+
+    #[test]
+    fn test_serve_stale() {
+        let cache = DnsLru::new(1, dns_lru::TtlConfig::default())
+            .with_serve_stale(Some(Duration::from_secs(60)));
+        let query = Query::query(Name::root(), RecordType::A);
+
+        // the entry expired 4 seconds ago
+        cache.insert(
+            query.clone(),
+            vec![(
+                Record::from_rdata(Name::root(), 1, RData::A(A::new(127, 0, 0, 2))),
+                1,
+            )],
+            Instant::now() - Duration::from_secs(5),
+        );
+
+        let (spawner, queue) = queue_spawner();
+        let client = CachingClient::with_cache(
+            cache,
+            mock(vec![v4_message(), error(), error()]),
+            false,
+            None,
+        )
+        .with_background_refresh(spawner);
+
+        // the upstream fails, the stale answer is returned and refreshed
+        for _ in 0..2 {
+            let lookup = block_on(CachingClient::inner_lookup(
+                query.clone(),
+                DnsRequestOptions::default(),
+                client.clone(),
+                vec![],
+                DepthTracker::default(),
+            ))
+            .expect("stale answer should be served");
+
+            assert_eq!(
+                lookup.iter().cloned().collect::<Vec<_>>(),
+                vec![RData::A(A::new(127, 0, 0, 2))]
+            );
+            assert_eq!(
+                lookup.record_iter().next().unwrap().ttl(),
+                dns_lru::STALE_ANSWER_TTL
+            );
+        }
+
+        // only one refresh is started at a time
+        let refreshes = std::mem::take(&mut *queue.lock());
+        assert_eq!(refreshes.len(), 1);
+        for refresh in refreshes {
+            block_on(refresh);
+        }
+
+        // the refreshed answer is cached
+        let lookup = block_on(CachingClient::inner_lookup(
+            query,
+            DnsRequestOptions::default(),
+            client,
+            vec![],
+            DepthTracker::default(),
+        ))
+        .unwrap();
+        assert_eq!(
+            lookup.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::A(A::new(127, 0, 0, 1))]
+        );
+        assert!(queue.lock().is_empty());
+    }
+
+    #[test]
+    fn test_serve_stale_refresh_dropped() {
+        let cache = DnsLru::new(1, dns_lru::TtlConfig::default())
+            .with_serve_stale(Some(Duration::from_secs(60)));
+        let query = Query::query(Name::root(), RecordType::A);
+
+        cache.insert(
+            query.clone(),
+            vec![(
+                Record::from_rdata(Name::root(), 1, RData::A(A::new(127, 0, 0, 2))),
+                1,
+            )],
+            Instant::now() - Duration::from_secs(5),
+        );
+
+        let (spawner, queue) = queue_spawner();
+        let client = CachingClient::with_cache(cache, mock(vec![error(), error()]), false, None)
+            .with_background_refresh(spawner);
+
+        // the refreshes are dropped without running, e.g. by a runtime shutting down
+        for _ in 0..2 {
+            block_on(CachingClient::inner_lookup(
+                query.clone(),
+                DnsRequestOptions::default(),
+                client.clone(),
+                vec![],
+                DepthTracker::default(),
+            ))
+            .expect("stale answer should be served");
+
+            assert_eq!(std::mem::take(&mut *queue.lock()).len(), 1);
+        }
+        assert!(client.refresh.unwrap().in_flight.lock().is_empty());
+    }
+
+    #[test]
+    fn test_serve_stale_expired() {
+        let cache = DnsLru::new(1, dns_lru::TtlConfig::default())
+            .with_serve_stale(Some(Duration::from_secs(2)));
+        let query = Query::query(Name::root(), RecordType::A);
+
+        // the entry expired 4 seconds ago, past the stale window
+        cache.insert(
+            query.clone(),
+            vec![(
+                Record::from_rdata(Name::root(), 1, RData::A(A::new(127, 0, 0, 2))),
+                1,
+            )],
+            Instant::now() - Duration::from_secs(5),
+        );
+
+        let (spawner, queue) = queue_spawner();
+        let client = CachingClient::with_cache(cache, mock(vec![error()]), false, None)
+            .with_background_refresh(spawner);
+
+        assert!(block_on(CachingClient::inner_lookup(
+            query,
+            DnsRequestOptions::default(),
+            client,
+            vec![],
+            DepthTracker::default(),
+        ))
+        .is_err());
+        assert!(queue.lock().is_empty());
+    }
+
+    #[test]
+    fn test_prefetch() {
+        let cache = DnsLru::new(1, dns_lru::TtlConfig::default());
+        let query = Query::query(Name::root(), RecordType::A);
+
+        // 5 of the 100 seconds of TTL are remaining
+        cache.insert(
+            query.clone(),
+            vec![(
+                Record::from_rdata(Name::root(), 100, RData::A(A::new(127, 0, 0, 2))),
+                100,
+            )],
+            Instant::now() - Duration::from_secs(95),
+        );
+
+        let (spawner, queue) = queue_spawner();
+        let client = CachingClient::with_cache(cache, mock(vec![v4_message()]), false, None)
+            .with_background_refresh(spawner)
+            .with_prefetch_threshold(Some(0.1));
+
+        // all the concurrent hits, from threads starting together, are answered from the cache
+        let barrier = std::sync::Barrier::new(20);
+        std::thread::scope(|scope| {
+            let lookups = (0..20)
+                .map(|_| {
+                    scope.spawn(|| {
+                        barrier.wait();
+                        block_on(CachingClient::inner_lookup(
+                            query.clone(),
+                            DnsRequestOptions::default(),
+                            client.clone(),
+                            vec![],
+                            DepthTracker::default(),
+                        ))
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for lookup in lookups {
+                assert_eq!(
+                    lookup
+                        .join()
+                        .unwrap()
+                        .unwrap()
+                        .iter()
+                        .cloned()
+                        .collect::<Vec<_>>(),
+                    vec![RData::A(A::new(127, 0, 0, 2))]
+                );
+            }
+        });
+
+        // exactly one prefetch was started
+        let prefetches = std::mem::take(&mut *queue.lock());
+        assert_eq!(prefetches.len(), 1);
+        for prefetch in prefetches {
+            block_on(prefetch);
+        }
+
+        // the prefetched answer is cached, and far from its expiration
+        let lookup = block_on(CachingClient::inner_lookup(
+            query,
+            DnsRequestOptions::default(),
+            client,
+            vec![],
+            DepthTracker::default(),
+        ))
+        .unwrap();
+        assert_eq!(
+            lookup.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::A(A::new(127, 0, 0, 1))]
+        );
+        assert!(queue.lock().is_empty());
+    }
+
+    fn ecs_message(scope_prefix: u8) -> Result<DnsResponse, ProtoError> {
+        let mut message = v4_message()?.into_message();
+        message
+            .extensions_mut()
+            .get_or_insert_with(Edns::new)
+            .options_mut()
+            .insert(EdnsOption::Subnet(ClientSubnet::new(
+                Ipv4Addr::new(192, 0, 2, 0).into(),
+                24,
+                scope_prefix,
+            )));
+        Ok(DnsResponse::from_message(message).unwrap())
+    }
+
+    fn subnet_lookup(
+        cache: &DnsLru,
+        subnet: &str,
+        response: Result<DnsResponse, ProtoError>,
+    ) -> Result<Lookup, ProtoError> {
+        let client = CachingClient::with_cache(
+            cache.clone(),
+            mock(vec![response]),
+            false,
+            Some(subnet.parse().unwrap()),
+        );
+
+        block_on(CachingClient::inner_lookup(
+            Query::query(Name::root(), RecordType::A),
+            DnsRequestOptions::default(),
+            client,
+            vec![],
+            DepthTracker::default(),
+        ))
+    }
+
+    #[test]
+    fn test_client_subnet_scoped_cache() {
+        let cache = DnsLru::new(2, dns_lru::TtlConfig::default());
+
+        let ips = subnet_lookup(&cache, "192.0.2.1/24", ecs_message(24)).unwrap();
+        assert_eq!(
+            ips.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::A(A::new(127, 0, 0, 1))]
+        );
+
+        // another subnet must not be answered from the cache
+        assert!(subnet_lookup(&cache, "198.51.100.1/24", empty()).is_err());
+
+        // the original subnet is still answered from the cache
+        let ips = subnet_lookup(&cache, "192.0.2.77/24", empty()).unwrap();
+        assert_eq!(
+            ips.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::A(A::new(127, 0, 0, 1))]
+        );
+    }
+
+    #[test]
+    fn test_client_subnet_response_scope() {
+        let cache = DnsLru::new(4, dns_lru::TtlConfig::default());
+
+        // the answer is valid for the whole 192.0.0.0/16
+        subnet_lookup(&cache, "192.0.2.1/24", ecs_message(16)).unwrap();
+        let ips = subnet_lookup(&cache, "192.0.100.1/24", empty()).unwrap();
+        assert_eq!(
+            ips.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::A(A::new(127, 0, 0, 1))]
+        );
+        assert!(subnet_lookup(&cache, "192.1.2.1/24", empty()).is_err());
+
+        // a scope longer than the subnet sent is limited to that subnet
+        let cache = DnsLru::new(4, dns_lru::TtlConfig::default());
+        subnet_lookup(&cache, "192.0.2.1/24", ecs_message(32)).unwrap();
+        assert!(subnet_lookup(&cache, "192.0.2.200/24", empty()).is_ok());
+        assert!(subnet_lookup(&cache, "192.0.3.1/24", empty()).is_err());
+    }
+
+    #[test]
+    fn test_client_subnet_global_scope() {
+        let cache = DnsLru::new(1, dns_lru::TtlConfig::default());
+
+        subnet_lookup(&cache, "192.0.2.1/24", ecs_message(0)).unwrap();
+
+        // a scope prefix of 0 means the answer is valid for all clients
+        let ips = subnet_lookup(&cache, "198.51.100.1/24", empty()).unwrap();
+        assert_eq!(
+            ips.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::A(A::new(127, 0, 0, 1))]
+        );
+        let ips = block_on(CachingClient::inner_lookup(
+            Query::query(Name::root(), RecordType::A),
+            DnsRequestOptions::default(),
+            CachingClient::with_cache(cache, mock(vec![empty()]), false, None),
+            vec![],
+            DepthTracker::default(),
+        ))
+        .unwrap();
+        assert_eq!(
+            ips.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::A(A::new(127, 0, 0, 1))]
+        );
+    }
+
+    /// The answer to an A query of `name`, with the records marked with `proof` as by the validation
+    #[cfg(feature = "dnssec-ring")]
+    fn proven_message(name: &str, proof: Proof) -> Result<DnsResponse, ProtoError> {
+        let name = Name::from_str(name).unwrap();
+        let mut record = Record::from_rdata(name.clone(), 86400, RData::A(A::new(192, 0, 2, 1)));
+        record.set_proof(proof);
+
+        let mut message = Message::new();
+        message.add_query(Query::query(name, RecordType::A));
+        message.add_answer(record);
+        Ok(DnsResponse::from_message(message).unwrap())
+    }
+
+    #[cfg(feature = "dnssec-ring")]
+    fn dnssec_lookup(
+        dnssec_mode: DnssecMode,
+        name: &str,
+        proof: Proof,
+    ) -> Result<Lookup, ResolveError> {
+        let mut client = CachingClient::new(0, mock(vec![proven_message(name, proof)]), false)
+            .with_dnssec_mode(dnssec_mode);
+        block_on(client.lookup(
+            Query::query(Name::from_str(name).unwrap(), RecordType::A),
+            DnsRequestOptions::default(),
+        ))
+    }
+
+    /// A validating handle counting the queries sent, the names of `example.com.` between `a` and
+    /// `c` do not exist
+    #[cfg(feature = "dnssec-ring")]
+    #[derive(Clone)]
+    struct ValidatingDnsHandle {
+        sent: Arc<AtomicUsize>,
+    }
+
+    #[cfg(feature = "dnssec-ring")]
+    impl DnsHandle for ValidatingDnsHandle {
+        type Response = Pin<Box<dyn Stream<Item = Result<DnsResponse, ProtoError>> + Send>>;
+
+        fn is_verifying_dnssec(&self) -> bool {
+            true
+        }
+
+        fn send<R: Into<DnsRequest>>(&self, request: R) -> Self::Response {
+            use crate::proto::dnssec::rdata::{DNSSECRData, NSEC};
+
+            self.sent.fetch_add(1, Ordering::SeqCst);
+            let zone = Name::from_ascii("example.com.").unwrap();
+            let secure = |name: &str, rdata| {
+                let mut record = Record::from_rdata(Name::from_ascii(name).unwrap(), 3600, rdata);
+                record.set_proof(Proof::Secure);
+                record
+            };
+            let nsec = |owner, next: &str, types: &[RecordType]| {
+                let nsec = NSEC::new(Name::from_ascii(next).unwrap(), types.to_vec());
+                secure(owner, RData::DNSSEC(DNSSECRData::NSEC(nsec)))
+            };
+            let soa = SOA::new(zone.clone(), zone.clone(), 1, 3600, 600, 86400, 300);
+
+            let mut message = Message::new();
+            message
+                .add_query(request.into().queries()[0].clone())
+                .set_response_code(ResponseCode::NXDomain)
+                .add_name_server(secure("example.com.", RData::SOA(soa)))
+                .add_name_server(nsec("example.com.", "a.example.com.", &[RecordType::SOA]))
+                .add_name_server(nsec("a.example.com.", "c.example.com.", &[RecordType::A]));
+            Box::pin(stream::once(future::ok(
+                DnsResponse::from_message(message).unwrap(),
+            )))
+        }
+    }
+
+    /// An upstream for the `example.com.` zone signed with `key`, whose RRSIG of the A record of
+    /// `www.example.com.` does not cover the address served
+    #[cfg(feature = "dnssec-ring")]
+    #[derive(Clone)]
+    struct BadSignatureDnsHandle {
+        key: Arc<crate::proto::dnssec::ring::Ed25519SigningKey>,
+    }
+
+    #[cfg(feature = "dnssec-ring")]
+    impl DnsHandle for BadSignatureDnsHandle {
+        type Response = Pin<Box<dyn Stream<Item = Result<DnsResponse, ProtoError>> + Send>>;
+
+        fn send<R: Into<DnsRequest>>(&self, request: R) -> Self::Response {
+            use crate::proto::dnssec::rdata::{DNSSECRData, DNSKEY, RRSIG};
+            use crate::proto::dnssec::{Algorithm, SigningKey, TBS};
+            use crate::proto::rr::DNSClass;
+
+            let query = request.into().queries()[0].clone();
+            let zone = Name::from_ascii("example.com.").unwrap();
+            let dnskey = DNSKEY::from_key(&self.key.to_public_key().unwrap());
+
+            let mut message = Message::new();
+            message.add_query(query.clone());
+            match query.query_type() {
+                RecordType::DNSKEY => {
+                    message.add_answer(Record::from_rdata(
+                        zone,
+                        3600,
+                        RData::DNSSEC(DNSSECRData::DNSKEY(dnskey)),
+                    ));
+                }
+                _ => {
+                    let name = query.name().clone();
+                    let signed =
+                        Record::from_rdata(name.clone(), 3600, RData::A(A::new(192, 0, 2, 1)));
+                    let now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs() as u32;
+                    let rrsig = |sig| {
+                        RRSIG::new(
+                            RecordType::A,
+                            Algorithm::ED25519,
+                            name.num_labels(),
+                            3600,
+                            now + 3600,
+                            now - 3600,
+                            dnskey.calculate_key_tag().unwrap(),
+                            zone.clone(),
+                            sig,
+                        )
+                    };
+                    let tbs =
+                        TBS::from_sig(&name, DNSClass::IN, &rrsig(vec![]), [&signed].into_iter())
+                            .unwrap();
+                    let sig = self.key.sign(&tbs).unwrap();
+
+                    message
+                        .add_answer(Record::from_rdata(
+                            name.clone(),
+                            3600,
+                            RData::A(A::new(192, 0, 2, 2)),
+                        ))
+                        .add_answer(Record::from_rdata(
+                            name.clone(),
+                            3600,
+                            RData::DNSSEC(DNSSECRData::RRSIG(rrsig(sig))),
+                        ));
+                }
+            }
+
+            Box::pin(stream::once(future::ok(
+                DnsResponse::from_message(message).unwrap(),
+            )))
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "dnssec-ring")]
+    fn test_bad_signature_extended_dns_error() {
+        use crate::proto::dnssec::ring::Ed25519SigningKey;
+        use crate::proto::dnssec::{DnssecDnsHandle, SigningKey, TrustAnchor};
+
+        let key =
+            Ed25519SigningKey::from_pkcs8(&Ed25519SigningKey::generate_pkcs8().unwrap()).unwrap();
+        let mut trust_anchor = TrustAnchor::new();
+        trust_anchor.insert_trust_anchor(&key.to_public_key().unwrap());
+
+        let handle = DnssecDnsHandle::with_trust_anchor(
+            BadSignatureDnsHandle { key: Arc::new(key) },
+            Arc::new(trust_anchor),
+        );
+        let mut client =
+            CachingClient::new(0, handle, false).with_dnssec_mode(DnssecMode::ValidateAndFail);
+
+        let error = block_on(client.lookup(
+            Query::query(Name::from_ascii("www.example.com.").unwrap(), RecordType::A),
+            DnsRequestOptions::default(),
+        ))
+        .unwrap_err();
+        assert!(matches!(
+            error.proto().map(ProtoError::kind),
+            Some(ProtoErrorKind::Nsec {
+                proof: Proof::Bogus,
+                ..
+            })
+        ));
+        assert_eq!(
+            error.extended_dns_error().map(|ede| ede.info_code()),
+            Some(6)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "dnssec-ring")]
+    fn test_aggressive_nsec() {
+        let sent = Arc::new(AtomicUsize::new(0));
+        let handle = ValidatingDnsHandle { sent: sent.clone() };
+        let client = CachingClient::new(0, handle, false).with_aggressive_nsec(16);
+        let lookup = |name| {
+            let query = Query::query(Name::from_ascii(name).unwrap(), RecordType::A);
+            let error = block_on(CachingClient::inner_lookup(
+                query,
+                DnsRequestOptions::default(),
+                client.clone(),
+                vec![],
+                DepthTracker::default(),
+            ))
+            .unwrap_err();
+            match error.kind() {
+                ProtoErrorKind::NoRecordsFound { response_code, .. } => *response_code,
+                _ => panic!("unexpected error: {error}"),
+            }
+        };
+
+        assert_eq!(lookup("aa.example.com."), ResponseCode::NXDomain);
+        assert_eq!(sent.load(Ordering::SeqCst), 1);
+
+        // the cached NSEC record spanning `a` to `c` proves that `b` does not exist
+        assert_eq!(lookup("b.example.com."), ResponseCode::NXDomain);
+        assert_eq!(sent.load(Ordering::SeqCst), 1);
+
+        assert_eq!(lookup("c.example.com."), ResponseCode::NXDomain);
+        assert_eq!(sent.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "dnssec-ring")]
+    fn test_dnssec_validate_report() {
+        let status = |name, proof| {
+            dnssec_lookup(DnssecMode::ValidateReport, name, proof)
+                .expect("lookup failed")
+                .dnssec_status()
+        };
+
+        assert_eq!(
+            status("signed.example.com.", Proof::Secure),
+            DnssecStatus::AuthenticData
+        );
+        assert_eq!(
+            status("unsigned.example.com.", Proof::Insecure),
+            DnssecStatus::Insecure
+        );
+
+        // the records failing the validation are still returned
+        let lookup = dnssec_lookup(
+            DnssecMode::ValidateReport,
+            "bogus.example.com.",
+            Proof::Bogus,
+        )
+        .expect("lookup failed");
+        assert_eq!(lookup.dnssec_status(), DnssecStatus::Bogus);
+        assert_eq!(
+            lookup.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::A(A::new(192, 0, 2, 1))]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "dnssec-ring")]
+    fn test_dnssec_validate_and_fail() {
+        let lookup = dnssec_lookup(
+            DnssecMode::ValidateAndFail,
+            "signed.example.com.",
+            Proof::Secure,
+        )
+        .expect("lookup failed");
+        assert_eq!(lookup.dnssec_status(), DnssecStatus::AuthenticData);
+
+        let lookup = dnssec_lookup(
+            DnssecMode::ValidateAndFail,
+            "unsigned.example.com.",
+            Proof::Insecure,
+        )
+        .expect("lookup failed");
+        assert_eq!(lookup.dnssec_status(), DnssecStatus::Insecure);
+
+        let error = dnssec_lookup(
+            DnssecMode::ValidateAndFail,
+            "bogus.example.com.",
+            Proof::Bogus,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.extended_dns_error().map(|ede| ede.info_code()),
+            Some(6)
+        );
+    }
+
+    #[test]
+    fn test_timeout_extended_dns_error() {
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let timeout = ProtoError::from(ProtoErrorKind::Timeout);
+        let mut client = CachingClient::new(0, mock(vec![Err(timeout)]), false);
+
+        let error = block_on(client.lookup(query, DnsRequestOptions::default())).unwrap_err();
+        assert_eq!(
+            error.extended_dns_error().map(|ede| ede.info_code()),
+            Some(22)
+        );
+    }
+
+    #[test]
+    fn test_no_extended_dns_error() {
+        let mut client = CachingClient::new(0, mock(vec![empty()]), false);
+
+        let error = block_on(client.lookup(
+            Query::query(Name::root(), RecordType::A),
+            DnsRequestOptions::default(),
+        ))
+        .unwrap_err();
+        assert!(error.extended_dns_error().is_none());
+    }
+
+    /// A negative response, with an SOA in the authority section if `soa_ttls` is set
+    fn negative_message(
+        response_code: ResponseCode,
+        soa_ttls: Option<(u32, u32)>,
+    ) -> Result<DnsResponse, ProtoError> {
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let mut message = Message::new();
+        message.add_query(query);
+        message.set_response_code(response_code);
+        if let Some((ttl, minimum)) = soa_ttls {
+            let soa = SOA::new(
+                Name::from_str("ns.example.com.").unwrap(),
+                Name::from_str("hostmaster.example.com.").unwrap(),
+                1,
+                3600,
+                600,
+                86400,
+                minimum,
+            );
+            message.add_name_server(Record::from_rdata(
+                Name::from_str("example.com.").unwrap(),
+                ttl,
+                RData::SOA(soa),
+            ));
+        }
+        Ok(DnsResponse::from_message(message).unwrap())
+    }
+
+    /// Looks up the A records of www.example.com., returning the negative TTL of the error and
+    /// whether the error was cached
+    fn negative_lookup(message: Result<DnsResponse, ProtoError>) -> (Option<u32>, bool) {
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let cache = DnsLru::new(1, dns_lru::TtlConfig::default());
+        let mut client = CachingClient::with_cache(cache, mock(vec![message]), false, None);
+
+        let error = block_on(client.lookup(query.clone(), DnsRequestOptions::default()))
+            .expect_err("lookup should have failed");
+        let negative_ttl = match error.proto().map(ProtoError::kind) {
+            Some(ProtoErrorKind::NoRecordsFound { negative_ttl, .. }) => *negative_ttl,
+            _ => panic!("wrong error received: {error}"),
+        };
+
+        let cached = client.lru.get(&query, Instant::now());
+        assert!(matches!(cached, None | Some(Err(_))));
+        (negative_ttl, cached.is_some())
+    }
+
+    #[test]
+    fn test_negative_ttl_from_soa() {
+        // the negative TTL is the smaller of the SOA TTL and the SOA MINIMUM, RFC 2308, section 5
+        for response_code in [ResponseCode::NXDomain, ResponseCode::NoError] {
+            assert_eq!(
+                negative_lookup(negative_message(response_code, Some((3600, 300)))),
+                (Some(300), true)
+            );
+            assert_eq!(
+                negative_lookup(negative_message(response_code, Some((60, 300)))),
+                (Some(60), true)
+            );
+        }
+    }
+
+    #[test]
+    fn test_negative_without_soa() {
+        // without an SOA there is no negative TTL, the response is not cached
+        for response_code in [ResponseCode::NXDomain, ResponseCode::NoError] {
+            assert_eq!(
+                negative_lookup(negative_message(response_code, None)),
+                (None, false)
+            );
+        }
+    }
+
+    #[test]
+    fn test_negative_answer_metadata() {
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let cache = DnsLru::new(1, dns_lru::TtlConfig::default());
+        let message = negative_message(ResponseCode::NXDomain, Some((3600, 300)));
+        let mut client = CachingClient::with_cache(cache, mock(vec![message]), false, None);
+
+        // the first error is answered by the name server, then from the cache
+        let error =
+            block_on(client.lookup(query.clone(), DnsRequestOptions::default())).unwrap_err();
+        assert!(!error.metadata().from_cache);
+
+        let error = block_on(client.lookup(query, DnsRequestOptions::default())).unwrap_err();
+        assert!(error.is_nx_domain());
+        assert_eq!(error.metadata(), LookupMetadata::cached());
+    }
+
+    fn dns64_lookup(
+        messages: Vec<Result<DnsResponse, ProtoError>>,
+    ) -> Result<Lookup, ResolveError> {
+        let mut client = CachingClient::new(0, mock(messages), false).with_dns64(Some(
+            Dns64Prefix::new("2001:db8:64::/96".parse().unwrap()).unwrap(),
+        ));
+        block_on(client.lookup(
+            Query::query(Name::root(), RecordType::AAAA),
+            DnsRequestOptions::default(),
+        ))
+    }
+
+    #[test]
+    fn test_dns64_synthesis() {
+        // the responses are popped from the end, first the AAAA query, then the A query
+        let lookup = dns64_lookup(vec![v4_message(), empty()]).expect("lookup failed");
+        assert_eq!(lookup.query().query_type(), RecordType::AAAA);
+        assert_eq!(
+            lookup.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::AAAA(AAAA("2001:db8:64::127.0.0.1".parse().unwrap()))]
+        );
+    }
+
+    #[test]
+    fn test_dns64_no_synthesis() {
+        // existing AAAA records are returned as they are
+        let lookup = dns64_lookup(vec![v4_message(), v6_message()]).expect("lookup failed");
+        assert_eq!(
+            lookup.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::AAAA(Ipv6Addr::LOCALHOST.into())]
+        );
+
+        // the name does not exist
+        let mut message = Message::new();
+        message.set_response_code(ResponseCode::NXDomain);
+        let nx_domain = Ok(DnsResponse::from_message(message).unwrap());
+        let error = dns64_lookup(vec![v4_message(), nx_domain]).unwrap_err();
+        assert!(error.is_nx_domain());
+    }
+
     #[allow(clippy::unnecessary_wraps)]
     pub(crate) fn cname_message() -> Result<DnsResponse, ProtoError> {
         let mut message = Message::new();
@@ -681,7 +2060,7 @@ mod tests {
 
         // the cname should succeed, we shouldn't query again after that, which would cause an error...
         let client = mock(vec![error(), cname_message()]);
-        let client = CachingClient::with_cache(cache, client, false);
+        let client = CachingClient::with_cache(cache, client, false, None);
 
         let ips = block_on(CachingClient::inner_lookup(
             Query::query(Name::from_str("www.example.com.").unwrap(), query_type),
@@ -716,7 +2095,7 @@ mod tests {
 
         // the cname should succeed, we shouldn't query again after that, which would cause an error...
         let client = mock(vec![error(), srv_message()]);
-        let client = CachingClient::with_cache(cache, client, false);
+        let client = CachingClient::with_cache(cache, client, false, None);
 
         let ips = block_on(CachingClient::inner_lookup(
             Query::query(
@@ -768,7 +2147,7 @@ mod tests {
             error(),
             Ok(DnsResponse::from_message(message).unwrap()),
         ]);
-        let client = CachingClient::with_cache(cache, client, false);
+        let client = CachingClient::with_cache(cache, client, false, None);
 
         let ips = block_on(CachingClient::inner_lookup(
             Query::query(
@@ -873,7 +2252,7 @@ mod tests {
             error(),
             Ok(DnsResponse::from_message(message).unwrap()),
         ]);
-        let client = CachingClient::with_cache(cache, client, false);
+        let client = CachingClient::with_cache(cache, client, false, None);
 
         let ips = block_on(CachingClient::inner_lookup(
             Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::NS),
@@ -897,7 +2276,7 @@ mod tests {
     fn cname_ttl_test(first: u32, second: u32) {
         let lru = DnsLru::new(1, dns_lru::TtlConfig::default());
         // expecting no queries to be performed
-        let mut client = CachingClient::with_cache(lru, mock(vec![error()]), false);
+        let mut client = CachingClient::with_cache(lru, mock(vec![error()]), false, None);
 
         let mut message = Message::new();
         message.insert_answers(vec![Record::from_rdata(
@@ -947,7 +2326,7 @@ mod tests {
     fn test_early_return_localhost() {
         let cache = DnsLru::new(0, dns_lru::TtlConfig::default());
         let client = mock(vec![empty()]);
-        let mut client = CachingClient::with_cache(cache, client, false);
+        let mut client = CachingClient::with_cache(cache, client, false, None);
 
         {
             let query = Query::query(Name::from_ascii("localhost.").unwrap(), RecordType::A);
@@ -1022,7 +2401,7 @@ mod tests {
     fn test_early_return_invalid() {
         let cache = DnsLru::new(0, dns_lru::TtlConfig::default());
         let client = mock(vec![empty()]);
-        let mut client = CachingClient::with_cache(cache, client, false);
+        let mut client = CachingClient::with_cache(cache, client, false, None);
 
         assert!(block_on(client.lookup(
             Query::query(
@@ -1053,7 +2432,7 @@ mod tests {
             error(),
             Ok(DnsResponse::from_message(message).unwrap()),
         ]);
-        let mut client = CachingClient::with_cache(cache, client, false);
+        let mut client = CachingClient::with_cache(cache, client, false, None);
 
         assert!(block_on(client.lookup(
             Query::query(
@@ -15,13 +15,16 @@ use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
 use std::time::Duration;
 
+use ipnet::{IpNet, Ipv6Net};
 #[cfg(feature = "dns-over-rustls")]
 use rustls::ClientConfig;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+use crate::dns_lru::CacheStore;
 use crate::proto::rr::Name;
 use crate::proto::xfer::Protocol;
+use crate::proto::ProtoError;
 
 /// Configuration for the upstream nameservers to use for resolution
 #[derive(Clone, Debug)]
@@ -361,6 +364,10 @@ pub struct NameServerConfig {
     /// inserted if none was specified.
     pub tls_config: Option<Arc<rustls::ClientConfig>>,
     /// The client address (IP and port) to use for connecting to the server.
+    ///
+    /// The sockets of all the protocols are bound to this address before connecting, e.g. to
+    /// choose the source address and thus the outgoing interface on multi-homed hosts. With a port
+    /// of 0, a random port is selected for UDP.
     pub bind_addr: Option<SocketAddr>,
 }
 
@@ -809,6 +816,19 @@ impl Default for ServerOrderingStrategy {
     }
 }
 
+/// The order of the addresses returned by repeated `lookup_ip` of the same name, when
+/// [`ResolverOpts::rotate`] is enabled.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RotationStrategy {
+    /// The addresses are rotated by one position on each lookup, cycling through all of them.
+    /// This is the default.
+    #[default]
+    RoundRobin,
+    /// The addresses are shuffled on each lookup.
+    Shuffle,
+}
+
 /// Whether the system hosts file should be respected by the resolver.
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -824,6 +844,21 @@ pub enum ResolveHosts {
     Auto,
 }
 
+/// How the resolver handles the outcome of the DNSSEC validation of the lookups
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DnssecMode {
+    /// The responses are not validated, unless [`ResolverOpts::validate`] is set. This is the
+    /// default.
+    #[default]
+    Off,
+    /// The responses are validated, lookups of bogus records fail
+    ValidateAndFail,
+    /// The responses are validated and all the records are returned, the outcome is reported by
+    /// `Lookup::dnssec_status`
+    ValidateReport,
+}
+
 /// Configuration for the Resolver
 #[derive(Debug, Clone)]
 #[cfg_attr(
@@ -837,26 +872,73 @@ pub struct ResolverOpts {
     /// Sets the number of dots that must appear (unless it's a final dot representing the root)
     ///  before a query is assumed to include the TLD. The default is one, which means that `www`
     ///  would never be assumed to be a TLD, and would always be appended to either the search
+    ///  domains or the local domain first. Names with at least `ndots` dots are tried as they are
+    ///  first, and then with the search domains. Fully qualified names, with a trailing dot, are
+    ///  never searched.
     pub ndots: usize,
+    /// Never look up a single label name as it is, as a top-level domain, only with the search
+    ///  domains or the local domain. Defaults to `false`
+    pub no_tld_query: bool,
     /// Specify the timeout for a request. Defaults to 5 seconds
     pub timeout: Duration,
     /// Number of retries after lookup failure before giving up. Defaults to 2
     pub attempts: usize,
-    /// Rotate through the resource records in the response (if there is more than one for a given name)
+    /// Rotate through the addresses returned by `lookup_ip` (if there is more than one for a given name)
+    ///
+    /// The order of the addresses varies across repeated lookups of the same name, with the
+    /// `rotation_strategy`. Defaults to `false`, which keeps the order of the name server.
     pub rotate: bool,
+    /// The order of the addresses when `rotate` is enabled
+    pub rotation_strategy: RotationStrategy,
     /// Validate the names in the response, not implemented don't really see the point unless you need to support
     ///  badly configured DNS
     pub check_names: bool,
     /// Enable edns, for larger records
     pub edns0: bool,
     /// Use DNSSEC to validate the request
+    ///
+    /// The records are only marked with their proofs, see [`DnssecMode`] for acting on the outcome
+    /// of the validation.
     pub validate: bool,
+    /// How the outcome of the DNSSEC validation is handled, validation is enabled by every mode but
+    /// [`DnssecMode::Off`]. Defaults to [`DnssecMode::Off`]
+    pub dnssec_mode: DnssecMode,
+    /// Synthesize the negative answers proven by the cached NSEC and NSEC3 records, without
+    /// querying the name servers, [RFC 8198](https://tools.ietf.org/html/rfc8198)
+    ///
+    /// This only applies when the responses are validated, see [`ResolverOpts::validate`].
+    /// Defaults to `true`
+    pub aggressive_nsec: bool,
     /// The ip_strategy for the Resolver to use when lookup Ipv4 or Ipv6 addresses
     pub ip_strategy: LookupIpStrategy,
+    /// Send the A and AAAA queries of [`LookupIpStrategy::Ipv4AndIpv6`] one after the other,
+    /// instead of concurrently. Defaults to `false`
+    pub single_request: bool,
+    /// The scope id of the link-local IPv6 addresses of the `lookup_ip` results, e.g. the index of
+    /// the network interface they are reachable from, see [`crate::lookup_ip::LookupIp::socket_addrs`].
+    /// Defaults to `None`
+    pub ipv6_scope_id: Option<u32>,
     /// Cache size is in number of records (some records can be large)
     pub cache_size: usize,
+    /// The maximum approximate size of the cached records in bytes, in addition to `cache_size`
+    ///
+    /// The entries are sized by the serialized size of their records, see
+    /// [`LruStore::with_max_bytes`](crate::dns_lru::LruStore::with_max_bytes). Defaults to `None`,
+    /// only the number of entries is limited.
+    pub cache_max_bytes: Option<usize>,
+    /// Custom storage for the cache, shared by the resolvers created with these options
+    ///
+    /// When set, `cache_size` and `cache_max_bytes` are ignored, the TTL limits still apply. Defaults to `None`, in which
+    /// case an in-memory [`LruStore`](crate::dns_lru::LruStore) of `cache_size` entries is used.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub cache_store: Option<Arc<dyn CacheStore>>,
     /// Check /etc/hosts file before dns requery (only works for unix like OS)
     pub use_hosts_file: ResolveHosts,
+    /// Interval at which the hosts file is checked for changes, which are then used without
+    /// recreating the resolver
+    ///
+    /// Defaults to `None`, the hosts file is only read when the resolver is created.
+    pub hosts_file_reload_interval: Option<Duration>,
     /// Optional minimum TTL for positive responses.
     ///
     /// If this is set, any positive responses with a TTL lower than this value will have a TTL of
@@ -890,6 +972,40 @@ pub struct ResolverOpts {
     pub preserve_intermediates: bool,
     /// Try queries over TCP if they fail over UDP.
     pub try_tcp_on_error: bool,
+    /// Number of retries of the queries failing on all the name servers with a connection error
+    /// or a timeout. Defaults to 0
+    ///
+    /// The retries are delayed by an exponential backoff from `connection_retry_backoff`, with a
+    /// random jitter. Error responses of the name servers, e.g. SERVFAIL, are never retried. The
+    /// lookup, including its retries, is cut off after `timeout`.
+    pub connection_retries: usize,
+    /// Delay before the first connection retry, doubled for each following retry up to 10
+    /// seconds. Defaults to 100 milliseconds
+    pub connection_retry_backoff: Duration,
+    /// Serve expired answers from the cache for up to this duration past their TTL, when the name
+    /// servers can not be reached, [RFC 8767](https://tools.ietf.org/html/rfc8767)
+    ///
+    /// The stale answers are returned with a TTL of 30 seconds, and a refresh of the answer is
+    /// started in the background. Defaults to `None`, expired answers are never served.
+    pub serve_stale_ttl: Option<Duration>,
+    /// Prefetch cached answers which have less than this fraction of their TTL remaining
+    ///
+    /// When such an answer is returned from the cache, the query is sent again in the background
+    /// to refresh it, e.g. `0.1` refreshes the answers during the last 10% of their TTL. Only one
+    /// prefetch per query is in flight. Defaults to `None`, answers are never prefetched.
+    pub prefetch_threshold: Option<f64>,
+    /// Share a single upstream query between the concurrent lookups of the same query
+    ///
+    /// The lookups of the same name, type and class, which are not answered from the cache, wait
+    /// for the query in flight instead of sending their own, and all receive its response or its
+    /// error. Defaults to `true`
+    pub coalesce_queries: bool,
+    /// Fall back to DNS-over-HTTPS over HTTP/2, if the HTTP/3 connection to a name server fails.
+    ///
+    /// This only applies to name servers configured with [`Protocol::H3`]. The HTTP/3 connection
+    /// is first attempted for up to `timeout`, if the QUIC handshake does not succeed in time, an
+    /// HTTP/2 connection to the same address and port is made instead. Defaults to `false`.
+    pub prefer_h3_fallback_h2: bool,
     /// The server ordering strategy that the resolver should use.
     pub server_ordering_strategy: ServerOrderingStrategy,
     /// Request upstream recursive resolvers to not perform any recursion.
@@ -913,6 +1029,51 @@ pub struct ResolverOpts {
     /// prevent those prompts from being displayed. If os_port_selection is true, avoid_local_udp_ports
     /// will be ignored.
     pub os_port_selection: bool,
+    /// EDNS Client Subnet to send with outgoing queries, [RFC 7871](https://tools.ietf.org/html/rfc7871)
+    ///
+    /// When set, an ECS option carrying this network is added to each query sent to the name
+    /// servers. Cached answers are scoped to this subnet, unless the response indicates a scope
+    /// prefix of 0, in which case the answer is valid for all clients. Defaults to `None`.
+    pub edns_client_subnet: Option<IpNet>,
+    /// Block size to pad queries to with the EDNS Padding option, [RFC 7830](https://tools.ietf.org/html/rfc7830)
+    ///
+    /// Padding is only applied to queries sent over encrypted transports, where it hides the
+    /// length of the query from observers. [RFC 8467](https://tools.ietf.org/html/rfc8467)
+    /// recommends a block size of 128. Defaults to `None`.
+    pub edns_padding: Option<usize>,
+    /// Send DNS Cookies with each query, [RFC 7873](https://tools.ietf.org/html/rfc7873)
+    ///
+    /// A random client cookie is generated for each name server, and the server cookie from its
+    /// responses is sent with the following queries. Responses which do not echo the client
+    /// cookie are rejected. Defaults to `false`.
+    pub edns_cookies: bool,
+    /// Randomize the case of the letters of query names, known as 0x20 encoding
+    ///
+    /// Name servers echo the question of a query in their responses, a forged response must then
+    /// also guess the case of each letter of the name. Responses whose question does not match the
+    /// case of the query are rejected, see `case_randomization_tolerant`. Defaults to `false`.
+    pub case_randomization: bool,
+    /// Accept responses whose question only differs in case from the randomized query name
+    ///
+    /// Some name servers normalize the case of the names of their responses, making them
+    /// unusable with `case_randomization`. When set, such responses are accepted, at the cost of
+    /// the spoofing resistance brought by the randomization. Defaults to `false`.
+    pub case_randomization_tolerant: bool,
+    /// Upgrade to the encrypted resolvers designated by the name servers, [RFC 9462](https://tools.ietf.org/html/rfc9462)
+    ///
+    /// When connecting to a name server over an unencrypted protocol, the SVCB records of
+    /// `_dns.resolver.arpa` are queried from it. The following queries are then sent to the first
+    /// designated resolver with an enabled encrypted protocol, if its certificate is valid for the
+    /// IP address of the name server. Defaults to `false`.
+    pub discover_designated_resolvers: bool,
+    /// NAT64 prefix for DNS64, synthesis of AAAA records from A records, [RFC 6147](https://tools.ietf.org/html/rfc6147)
+    ///
+    /// When an AAAA lookup has no answer, but the name has A records, AAAA records are synthesized
+    /// by embedding the IPv4 addresses in this prefix, as described in
+    /// [RFC 6052](https://tools.ietf.org/html/rfc6052). For the well-known prefix `64:ff9b::/96`,
+    /// see [`DNS64_WELL_KNOWN_PREFIX`], non-global IPv4 addresses are not synthesized. Defaults to
+    /// `None`, no records are synthesized.
+    pub dns64_prefix: Option<Dns64Prefix>,
 }
 
 impl Default for ResolverOpts {
@@ -922,15 +1083,24 @@ impl Default for ResolverOpts {
     fn default() -> Self {
         Self {
             ndots: 1,
+            no_tld_query: false,
             timeout: Duration::from_secs(5),
             attempts: 2,
             rotate: false,
+            rotation_strategy: RotationStrategy::default(),
             check_names: true,
             edns0: false,
             validate: false,
+            dnssec_mode: DnssecMode::default(),
+            aggressive_nsec: true,
             ip_strategy: LookupIpStrategy::default(),
+            single_request: false,
+            ipv6_scope_id: None,
             cache_size: 32,
+            cache_max_bytes: None,
+            cache_store: None,
             use_hosts_file: ResolveHosts::default(),
+            hosts_file_reload_interval: None,
             positive_min_ttl: None,
             negative_min_ttl: None,
             positive_max_ttl: None,
@@ -941,14 +1111,71 @@ impl Default for ResolverOpts {
             preserve_intermediates: true,
 
             try_tcp_on_error: false,
+            connection_retries: 0,
+            connection_retry_backoff: Duration::from_millis(100),
+            serve_stale_ttl: None,
+            prefetch_threshold: None,
+            coalesce_queries: true,
+            prefer_h3_fallback_h2: false,
             server_ordering_strategy: ServerOrderingStrategy::default(),
             recursion_desired: true,
             authentic_data: false,
             shuffle_dns_servers: false,
             avoid_local_udp_ports: Arc::new(HashSet::new()),
             os_port_selection: false,
+            edns_client_subnet: None,
+            edns_padding: None,
+            edns_cookies: false,
+            case_randomization: false,
+            case_randomization_tolerant: false,
+            discover_designated_resolvers: false,
+            dns64_prefix: None,
+        }
+    }
+}
+
+/// The address of the well-known DNS64 prefix `64:ff9b::/96`, [RFC 6052, section 2.1](https://tools.ietf.org/html/rfc6052#section-2.1)
+pub const DNS64_WELL_KNOWN_PREFIX: Ipv6Addr = Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0);
+
+/// A NAT64 prefix for DNS64, see [`ResolverOpts::dns64_prefix`]
+///
+/// The prefix length is one of those supported by [RFC 6052, section 2.2](https://tools.ietf.org/html/rfc6052#section-2.2),
+/// 32, 40, 48, 56, 64 or 96, other prefixes are rejected when the prefix is created or deserialized.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "Ipv6Net", into = "Ipv6Net")
+)]
+pub struct Dns64Prefix(Ipv6Net);
+
+impl Dns64Prefix {
+    /// Returns an error if the prefix length is not one of those supported by RFC 6052
+    pub fn new(prefix: Ipv6Net) -> Result<Self, ProtoError> {
+        match prefix.prefix_len() {
+            32 | 40 | 48 | 56 | 64 | 96 => Ok(Self(prefix.trunc())),
+            len => Err(format!("unsupported DNS64 prefix length {len}: {prefix}").into()),
         }
     }
+
+    /// The prefix, with the host bits cleared
+    pub fn prefix(&self) -> Ipv6Net {
+        self.0
+    }
+}
+
+impl TryFrom<Ipv6Net> for Dns64Prefix {
+    type Error = ProtoError;
+
+    fn try_from(prefix: Ipv6Net) -> Result<Self, Self::Error> {
+        Self::new(prefix)
+    }
+}
+
+impl From<Dns64Prefix> for Ipv6Net {
+    fn from(prefix: Dns64Prefix) -> Self {
+        prefix.0
+    }
 }
 
 /// IP addresses for Google Public DNS
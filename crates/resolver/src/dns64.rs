@@ -0,0 +1,234 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DNS64, synthesis of AAAA records from A records, [RFC 6147](https://tools.ietf.org/html/rfc6147)
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use ipnet::Ipv6Net;
+
+use crate::config::{Dns64Prefix, DNS64_WELL_KNOWN_PREFIX};
+use crate::lookup::Lookup;
+#[cfg(feature = "dnssec-ring")]
+use crate::proto::dnssec::Proof;
+use crate::proto::op::Query;
+use crate::proto::rr::rdata::AAAA;
+use crate::proto::rr::{RData, Record, RecordType};
+
+/// Synthesizes AAAA records for the addresses of A records, by embedding them in a NAT64 prefix
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Dns64 {
+    prefix: Ipv6Net,
+}
+
+impl Dns64 {
+    pub(crate) fn new(prefix: Dns64Prefix) -> Self {
+        Self {
+            prefix: prefix.prefix(),
+        }
+    }
+
+    /// Builds the answer to the AAAA `query` from the answer to the A query for the same name
+    ///
+    /// The CNAME records of the A lookup are kept. The TTLs of the synthesized records are the
+    /// minimum of the A record TTL and `negative_ttl`, the negative caching TTL of the AAAA
+    /// response, per RFC 6147, section 5.1.7. The synthesized records are not signed, with DNSSEC
+    /// they are marked `Proof::Insecure`, also when the A records were validated.
+    ///
+    /// Returns `None` if no A record could be synthesized.
+    pub(crate) fn synthesize(
+        &self,
+        query: Query,
+        a_lookup: &Lookup,
+        negative_ttl: Option<u32>,
+    ) -> Option<Lookup> {
+        let mut synthesized = false;
+        let records = a_lookup
+            .record_iter()
+            .filter_map(|record| match record.data() {
+                RData::A(a) if record.record_type() == RecordType::A => {
+                    let ip = self.embed(a.0)?;
+                    let ttl = negative_ttl.map_or(record.ttl(), |ttl| ttl.min(record.ttl()));
+
+                    let mut aaaa =
+                        Record::from_rdata(record.name().clone(), ttl, RData::AAAA(AAAA(ip)));
+                    aaaa.set_dns_class(record.dns_class());
+                    #[cfg(feature = "dnssec-ring")]
+                    aaaa.set_proof(Proof::Insecure);
+                    synthesized = true;
+                    Some(aaaa)
+                }
+                RData::CNAME(_) => Some(record.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if !synthesized {
+            return None;
+        }
+
+        let valid_until = match negative_ttl {
+            Some(ttl) => a_lookup
+                .valid_until()
+                .min(Instant::now() + Duration::from_secs(u64::from(ttl))),
+            None => a_lookup.valid_until(),
+        };
+        Some(
+            Lookup::new_with_deadline(query, Arc::from(records), valid_until)
+                .with_metadata(a_lookup.metadata()),
+        )
+    }
+
+    /// Embeds the IPv4 address in the prefix, [RFC 6052, section 2.2](https://tools.ietf.org/html/rfc6052#section-2.2)
+    ///
+    /// Non-global addresses are not embedded in the well-known prefix, see RFC 6052, section 3.1.
+    fn embed(&self, ip: Ipv4Addr) -> Option<Ipv6Addr> {
+        if self.prefix.addr() == DNS64_WELL_KNOWN_PREFIX && !is_global(ip) {
+            return None;
+        }
+
+        let mut octets = self.prefix.addr().octets();
+        // bits 64 to 71 are reserved, and must be zero
+        let positions = (usize::from(self.prefix.prefix_len()) / 8..16).filter(|&pos| pos != 8);
+        for (pos, octet) in positions.zip(ip.octets()) {
+            octets[pos] = octet;
+        }
+
+        Some(Ipv6Addr::from(octets))
+    }
+}
+
+/// Approximates the unstable `Ipv4Addr::is_global`
+fn is_global(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    let shared = a == 100 && (b & 0xc0) == 64;
+    let protocol_assignments = a == 192 && b == 0 && ip.octets()[2] == 0;
+    let benchmarking = a == 198 && (b & 0xfe) == 18;
+    let reserved = a >= 240;
+
+    !(ip.is_unspecified()
+        || ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_documentation()
+        || ip.is_broadcast()
+        || a == 0
+        || shared
+        || protocol_assignments
+        || benchmarking
+        || reserved)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::proto::rr::rdata::{A, CNAME};
+    use crate::proto::rr::Name;
+
+    use super::*;
+
+    fn dns64(prefix: &str) -> Dns64 {
+        Dns64::new(Dns64Prefix::new(prefix.parse().unwrap()).unwrap())
+    }
+
+    #[test]
+    fn test_embed() {
+        // the examples of RFC 6052, section 2.4
+        let ip = Ipv4Addr::new(192, 0, 2, 33);
+        for (prefix, expected) in [
+            ("2001:db8::/32", "2001:db8:c000:221::"),
+            ("2001:db8:100::/40", "2001:db8:1c0:2:21::"),
+            ("2001:db8:122::/48", "2001:db8:122:c000:2:2100::"),
+            ("2001:db8:122:300::/56", "2001:db8:122:3c0:0:221::"),
+            ("2001:db8:122:344::/64", "2001:db8:122:344:c0:2:2100:0"),
+            ("2001:db8:122:344::/96", "2001:db8:122:344::192.0.2.33"),
+        ] {
+            assert_eq!(
+                dns64(prefix).embed(ip),
+                Some(expected.parse().unwrap()),
+                "{prefix}"
+            );
+        }
+
+        // only global addresses are embedded in the well-known prefix
+        let wkp = dns64("64:ff9b::/96");
+        assert_eq!(
+            wkp.embed(Ipv4Addr::new(93, 184, 216, 34)),
+            Some("64:ff9b::93.184.216.34".parse().unwrap())
+        );
+        assert_eq!(wkp.embed(ip), None);
+        assert_eq!(wkp.embed(Ipv4Addr::new(10, 0, 0, 1)), None);
+
+        assert!(Dns64Prefix::new("2001:db8::/80".parse().unwrap()).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_prefix_deserialize() {
+        use crate::config::ResolverOpts;
+
+        let options = toml::from_str::<ResolverOpts>(r#"dns64_prefix = "64:ff9b::1/96""#).unwrap();
+        assert_eq!(
+            options.dns64_prefix.map(|prefix| prefix.prefix()),
+            Some("64:ff9b::/96".parse().unwrap())
+        );
+
+        let error =
+            toml::from_str::<ResolverOpts>(r#"dns64_prefix = "2001:db8::/80""#).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("unsupported DNS64 prefix length 80"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn test_synthesize() {
+        let name = Name::from_str("www.example.com.").unwrap();
+        let target = Name::from_str("target.example.com.").unwrap();
+        let a_lookup = Lookup::new_with_max_ttl(
+            Query::query(name.clone(), RecordType::A),
+            Arc::from([
+                Record::from_rdata(name.clone(), 300, RData::CNAME(CNAME(target.clone()))),
+                Record::from_rdata(target.clone(), 600, RData::A(A::new(93, 184, 216, 34))),
+            ]),
+        );
+
+        let query = Query::query(name.clone(), RecordType::AAAA);
+        let lookup = dns64("64:ff9b::/96")
+            .synthesize(query.clone(), &a_lookup, Some(60))
+            .expect("AAAA should be synthesized");
+
+        assert_eq!(lookup.query(), &query);
+        let records = lookup.records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].record_type(), RecordType::CNAME);
+
+        let aaaa = &records[1];
+        assert_eq!(aaaa.name(), &target);
+        assert_eq!(
+            aaaa.data(),
+            &RData::AAAA(AAAA("64:ff9b::93.184.216.34".parse().unwrap()))
+        );
+        assert_eq!(aaaa.ttl(), 60);
+        #[cfg(feature = "dnssec-ring")]
+        assert_eq!(aaaa.proof(), Proof::Insecure);
+
+        // nothing to synthesize for non-global addresses
+        let a_lookup = Lookup::from_rdata(
+            Query::query(name, RecordType::A),
+            RData::A(A::new(10, 0, 0, 1)),
+        );
+        assert!(dns64("64:ff9b::/96")
+            .synthesize(query, &a_lookup, None)
+            .is_none());
+    }
+}
@@ -8,22 +8,28 @@
 //! An LRU cache designed for work with DNS lookups
 
 use std::collections::HashMap;
+use std::fmt;
+use std::mem;
 use std::ops::RangeInclusive;
-use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
 
+use ipnet::IpNet;
 use moka::{sync::Cache, Expiry};
 #[cfg(feature = "serde")]
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize};
 
 use crate::config;
 use crate::lookup::Lookup;
 #[cfg(feature = "dnssec-ring")]
 use crate::proto::dnssec::rdata::RRSIG;
-use crate::proto::op::Query;
+use crate::proto::op::{Query, ResponseCode};
+use crate::proto::rr::rdata::SOA;
 #[cfg(feature = "dnssec-ring")]
 use crate::proto::rr::RecordData;
 use crate::proto::rr::{Record, RecordType};
+use crate::proto::serialize::binary::BinEncodable;
 use crate::proto::{ProtoError, ProtoErrorKind};
 
 /// Maximum TTL. This is set to one day (in seconds).
@@ -33,59 +39,362 @@ use crate::proto::{ProtoError, ProtoErrorKind};
 /// upper bound on received TTLs.
 pub(crate) const MAX_TTL: u32 = 86400_u32;
 
+/// TTL of stale answers, as recommended in [RFC 8767, section 4](https://tools.ietf.org/html/rfc8767#section-4)
+pub(crate) const STALE_ANSWER_TTL: u32 = 30;
+
+/// The key for entries in the cache
+///
+/// Answers to queries sent with an EDNS Client Subnet may only be valid for the scope returned by
+/// the name server, these are stored with the `subnet` set to that scope, so that clients outside
+/// of it don't share entries.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct CacheKey {
+    query: Query,
+    subnet: Option<IpNet>,
+}
+
+impl CacheKey {
+    /// The query the entry answers
+    pub fn query(&self) -> &Query {
+        &self.query
+    }
+
+    /// The client subnet the entry is valid for, `None` if it is valid for all clients
+    pub fn subnet(&self) -> Option<IpNet> {
+        self.subnet
+    }
+}
+
+/// An entry in the cache, the answer to a query and the time until which it is valid
+///
+/// Entries only hold serializable data, with wall-clock times, so that stores may persist them or
+/// share them between processes.
 #[derive(Debug, Clone)]
-struct LruValue {
-    // In the Err case, this represents an NXDomain
-    lookup: Result<Lookup, ProtoError>,
-    valid_until: Instant,
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct CacheEntry {
+    answer: CachedAnswer,
+    inserted_at: SystemTime,
+    valid_until: SystemTime,
+    stale_until: SystemTime,
 }
 
-impl LruValue {
+impl CacheEntry {
+    /// Creates an entry with the answer to a query, either the records or a negative response
+    pub fn new(answer: CachedAnswer, valid_until: SystemTime) -> Self {
+        Self {
+            answer,
+            inserted_at: SystemTime::now(),
+            valid_until,
+            stale_until: valid_until,
+        }
+    }
+
+    /// The answer as it was inserted, the TTLs of the records are not updated
+    pub fn answer(&self) -> &CachedAnswer {
+        &self.answer
+    }
+
+    /// The answer as the result of a lookup, a negative response is returned as the error
+    pub fn lookup(&self) -> Result<Lookup, ProtoError> {
+        self.answer.to_lookup(instant(self.valid_until))
+    }
+
+    /// The approximate size of the entry in bytes, the fixed size of the entry and the serialized
+    /// size of its records
+    ///
+    /// This serializes the records, it is only computed by the stores limiting their size, see
+    /// [`LruStore::with_max_bytes`]. Negative responses are only counted with the fixed size.
+    pub fn size(&self) -> usize {
+        let records = match &self.answer {
+            CachedAnswer::Records { records, .. } => records
+                .iter()
+                .map(|record| record.to_bytes().map_or(0, |bytes| bytes.len()))
+                .sum(),
+            CachedAnswer::Negative(_) => 0,
+        };
+
+        mem::size_of::<Self>() + records
+    }
+
+    /// The time until which the entry is valid
+    pub fn valid_until(&self) -> SystemTime {
+        self.valid_until
+    }
+
+    /// The time until which the entry may be served stale, the store can drop the entry afterwards
+    ///
+    /// This is the same as `valid_until`, unless serving stale answers is enabled.
+    pub fn stale_until(&self) -> SystemTime {
+        self.stale_until
+    }
+
     /// Returns true if this set of ips is still valid
-    fn is_current(&self, now: Instant) -> bool {
-        now <= self.valid_until
+    pub fn is_current(&self, now: Instant) -> bool {
+        system_time(now) <= self.valid_until
     }
 
     /// Returns the ttl as a Duration of time remaining.
-    fn ttl(&self, now: Instant) -> Duration {
-        self.valid_until.saturating_duration_since(now)
+    pub fn ttl(&self, now: Instant) -> Duration {
+        self.valid_until
+            .duration_since(system_time(now))
+            .unwrap_or_default()
+    }
+
+    /// Returns the fraction of the original ttl which remains, between 0 and 1
+    pub(crate) fn ttl_fraction(&self, now: Instant) -> f64 {
+        let original = self
+            .valid_until
+            .duration_since(self.inserted_at)
+            .unwrap_or_default();
+        if original.is_zero() {
+            return 0.0;
+        }
+
+        self.ttl(now).as_secs_f64() / original.as_secs_f64()
+    }
+
+    fn is_stale(&self, now: Instant) -> bool {
+        system_time(now) > self.stale_until
+    }
+
+    /// The time remaining until the entry may no longer be served stale
+    fn stale_ttl(&self, now: Instant) -> Duration {
+        self.stale_until
+            .duration_since(system_time(now))
+            .unwrap_or_default()
     }
 
     fn with_updated_ttl(&self, now: Instant) -> Self {
-        let lookup = match &self.lookup {
-            Ok(lookup) => {
-                let records = lookup
-                    .records()
+        let ttl = u32::try_from(self.ttl(now).as_secs()).unwrap_or(MAX_TTL);
+        let answer = match &self.answer {
+            CachedAnswer::Records { query, records } => CachedAnswer::Records {
+                query: query.clone(),
+                records: records
                     .iter()
                     .map(|record| {
                         let mut record = record.clone();
-                        record.set_ttl(self.ttl(now).as_secs() as u32);
+                        record.set_ttl(ttl);
                         record
                     })
-                    .collect::<Vec<Record>>();
-                Ok(Lookup::new_with_deadline(
-                    lookup.query().clone(),
-                    Arc::from(records),
-                    self.valid_until,
-                ))
-            }
-            Err(e) => Err(e.clone()),
+                    .collect(),
+            },
+            CachedAnswer::Negative(negative) => CachedAnswer::Negative(NegativeAnswer {
+                negative_ttl: Some(ttl),
+                ..negative.clone()
+            }),
         };
         Self {
-            lookup,
+            answer,
+            inserted_at: self.inserted_at,
             valid_until: self.valid_until,
+            stale_until: self.stale_until,
         }
     }
 }
 
+/// The answer to a query held by a [`CacheEntry`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[non_exhaustive]
+pub enum CachedAnswer {
+    /// The records answering the query
+    Records {
+        /// The query answered by the records, this is the last query of a CNAME chain
+        query: Query,
+        /// The records, including the CNAMEs followed
+        records: Arc<[Record]>,
+    },
+    /// The query name does not exist, or has no records of the queried type
+    Negative(NegativeAnswer),
+}
+
+impl CachedAnswer {
+    /// The records of the lookup
+    pub fn from_lookup(lookup: &Lookup) -> Self {
+        Self::Records {
+            query: lookup.query().clone(),
+            records: Arc::from(lookup.records()),
+        }
+    }
+
+    /// The negative response of the error, `None` for other errors
+    pub fn from_error(error: &ProtoError) -> Option<Self> {
+        let ProtoErrorKind::NoRecordsFound {
+            query,
+            soa,
+            negative_ttl,
+            response_code,
+            trusted,
+            authorities,
+            ..
+        } = error.kind()
+        else {
+            return None;
+        };
+
+        Some(Self::Negative(NegativeAnswer {
+            query: query.as_ref().clone(),
+            soa: soa.clone(),
+            negative_ttl: *negative_ttl,
+            response_code: *response_code,
+            trusted: *trusted,
+            authorities: authorities.clone(),
+        }))
+    }
+
+    fn to_lookup(&self, valid_until: Instant) -> Result<Lookup, ProtoError> {
+        match self {
+            Self::Records { query, records } => Ok(Lookup::new_with_deadline(
+                query.clone(),
+                records.clone(),
+                valid_until,
+            )),
+            Self::Negative(negative) => Err(ProtoErrorKind::NoRecordsFound {
+                query: Box::new(negative.query.clone()),
+                soa: negative.soa.clone(),
+                ns: None,
+                negative_ttl: negative.negative_ttl,
+                response_code: negative.response_code,
+                trusted: negative.trusted,
+                authorities: negative.authorities.clone(),
+            }
+            .into()),
+        }
+    }
+}
+
+/// A cached negative response, see [`ProtoErrorKind::NoRecordsFound`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct NegativeAnswer {
+    /// The query for which no records were found
+    pub query: Query,
+    /// The SOA of the zone, which determines the negative TTL
+    pub soa: Option<Box<Record<SOA>>>,
+    /// The negative TTL, the remaining TTL in the entries returned by the cache
+    pub negative_ttl: Option<u32>,
+    /// `NXDomain` if the name does not exist, `NoError` if it has no records of the type
+    pub response_code: ResponseCode,
+    /// If the name server is trusted to send `NXDomain`
+    pub trusted: bool,
+    /// The authority records of the response, for DNSSEC validation
+    pub authorities: Option<Arc<[Record]>>,
+}
+
+/// The wall-clock time of the `instant`
+///
+/// The conversions between the clocks use the same reference point for the whole process, so that
+/// they are consistent with each other.
+fn system_time(instant: Instant) -> SystemTime {
+    let (base, base_time) = clocks();
+    match instant.checked_duration_since(base) {
+        Some(elapsed) => base_time + elapsed,
+        None => base_time - base.duration_since(instant),
+    }
+}
+
+/// The `Instant` of the wall-clock `time`, see [`system_time`]
+fn instant(time: SystemTime) -> Instant {
+    let (base, base_time) = clocks();
+    match time.duration_since(base_time) {
+        Ok(elapsed) => base.checked_add(elapsed).unwrap_or(base),
+        Err(e) => base.checked_sub(e.duration()).unwrap_or(base),
+    }
+}
+
+fn clocks() -> (Instant, SystemTime) {
+    static CLOCKS: OnceLock<(Instant, SystemTime)> = OnceLock::new();
+    *CLOCKS.get_or_init(|| (Instant::now(), SystemTime::now()))
+}
+
+/// Storage for the entries of a [`DnsLru`]
+///
+/// The stores only hold the entries, the `DnsLru` clamps the TTLs of inserted entries, caches negative
+/// responses, and decrements the TTLs of the records it returns. Stores may keep entries after they
+/// expired, these are ignored and removed by the `DnsLru`.
+pub trait CacheStore: fmt::Debug + Send + Sync + 'static {
+    /// Returns the entry for the key, if any
+    fn get(&self, key: &CacheKey) -> Option<CacheEntry>;
+
+    /// Stores the entry for the key, replacing any previous entry
+    fn insert(&self, key: CacheKey, entry: CacheEntry);
+
+    /// Removes the entry for the key, if any
+    fn remove(&self, key: &CacheKey);
+
+    /// Removes all entries
+    fn clear(&self);
+}
+
+/// The default, in-memory [`CacheStore`]
+///
+/// It currently uses a "TinyLFU" policy, implemented in the `moka` library.
+#[derive(Clone, Debug)]
+pub struct LruStore {
+    cache: Cache<CacheKey, CacheEntry>,
+}
+
+impl LruStore {
+    /// Construct a new store holding up to `capacity` entries
+    pub fn new(capacity: usize) -> Self {
+        let cache = Cache::builder()
+            .max_capacity(capacity.try_into().unwrap_or(u64::MAX))
+            .expire_after(CacheEntryExpiry)
+            .build();
+        Self { cache }
+    }
+
+    /// Construct a new store holding up to `capacity` entries, of up to `max_bytes` in total
+    ///
+    /// The entries are weighed by their approximate size, see [`CacheEntry::size`]. Each entry
+    /// weighs at least its share of `max_bytes`, so that no more than `capacity` entries are kept.
+    pub fn with_max_bytes(capacity: usize, max_bytes: usize) -> Self {
+        let max_capacity = match capacity {
+            0 => 0,
+            _ => max_bytes,
+        };
+        let min_weight = (max_bytes + capacity.max(1) - 1) / capacity.max(1);
+
+        let cache = Cache::builder()
+            .max_capacity(max_capacity.try_into().unwrap_or(u64::MAX))
+            .weigher(move |_key, entry: &CacheEntry| {
+                entry.size().max(min_weight).try_into().unwrap_or(u32::MAX)
+            })
+            .expire_after(CacheEntryExpiry)
+            .build();
+        Self { cache }
+    }
+}
+
+impl CacheStore for LruStore {
+    fn get(&self, key: &CacheKey) -> Option<CacheEntry> {
+        self.cache.get(key)
+    }
+
+    fn insert(&self, key: CacheKey, entry: CacheEntry) {
+        self.cache.insert(key, entry);
+    }
+
+    fn remove(&self, key: &CacheKey) {
+        self.cache.invalidate(key);
+    }
+
+    fn clear(&self) {
+        self.cache.invalidate_all();
+    }
+}
+
 /// A cache specifically for storing DNS records.
 ///
-/// This is named `DnsLru` for historical reasons. It currently uses a "TinyLFU" policy, implemented
-/// in the `moka` library.
+/// This is named `DnsLru` for historical reasons. The entries are kept in a [`CacheStore`], by
+/// default an [`LruStore`].
 #[derive(Clone, Debug)]
 pub struct DnsLru {
-    cache: Cache<Query, LruValue>,
+    store: Arc<dyn CacheStore>,
     ttl_config: Arc<TtlConfig>,
+    serve_stale: Option<Duration>,
+    scope_prefixes: Arc<ScopePrefixes>,
 }
 
 /// The time-to-live (TTL) configuration used by the cache.
@@ -253,18 +562,52 @@ impl DnsLru {
     /// * `capacity` - size in number of cached queries
     /// * `ttl_config` - minimum and maximum TTLs for cached records
     pub fn new(capacity: usize, ttl_config: TtlConfig) -> Self {
-        let cache = Cache::builder()
-            .max_capacity(capacity.try_into().unwrap_or(u64::MAX))
-            .expire_after(LruValueExpiry)
-            .build();
+        Self::with_store(Arc::new(LruStore::new(capacity)), ttl_config)
+    }
+
+    /// Construct a new cache, keeping the entries in the `store`
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - storage for the cached queries, this may be shared with other caches
+    /// * `ttl_config` - minimum and maximum TTLs for cached records
+    ///
+    /// Entries scoped to a client subnet are only found by the caches which inserted an entry
+    /// with a scope of the same prefix length, lookups don't probe the others.
+    pub fn with_store(store: Arc<dyn CacheStore>, ttl_config: TtlConfig) -> Self {
         Self {
-            cache,
+            store,
             ttl_config: Arc::new(ttl_config),
+            serve_stale: None,
+            scope_prefixes: Arc::default(),
+        }
+    }
+
+    /// Keep the entries for `serve_stale` after they expired, see [`Self::get_stale`]
+    pub(crate) fn with_serve_stale(mut self, serve_stale: Option<Duration>) -> Self {
+        self.serve_stale = serve_stale;
+        self
+    }
+
+    fn entry(&self, answer: CachedAnswer, now: Instant, valid_until: Instant) -> CacheEntry {
+        let valid_until = system_time(valid_until);
+        CacheEntry {
+            answer,
+            inserted_at: system_time(now),
+            valid_until,
+            stale_until: valid_until + self.serve_stale.unwrap_or_default(),
         }
     }
 
     pub(crate) fn clear(&self) {
-        self.cache.invalidate_all();
+        self.store.clear();
+    }
+
+    fn insert_entry(&self, key: CacheKey, entry: CacheEntry) {
+        if let Some(subnet) = key.subnet {
+            self.scope_prefixes.insert(subnet);
+        }
+        self.store.insert(key, entry);
     }
 
     pub(crate) fn insert(
@@ -272,6 +615,17 @@ impl DnsLru {
         query: Query,
         records_and_ttl: Vec<(Record, u32)>,
         now: Instant,
+    ) -> Lookup {
+        self.insert_scoped(query, None, records_and_ttl, now)
+    }
+
+    /// Inserts the records for the query, only valid for lookups from the `subnet` if specified
+    pub(crate) fn insert_scoped(
+        &self,
+        query: Query,
+        subnet: Option<IpNet>,
+        records_and_ttl: Vec<(Record, u32)>,
+        now: Instant,
     ) -> Lookup {
         let len = records_and_ttl.len();
         let (positive_min_ttl, positive_max_ttl) = self
@@ -280,7 +634,7 @@ impl DnsLru {
             .into_inner();
 
         // collapse the values, we're going to take the Minimum TTL as the correct one
-        let (records, ttl): (Vec<Record>, Duration) = records_and_ttl.into_iter().fold(
+        let (mut records, ttl): (Vec<Record>, Duration) = records_and_ttl.into_iter().fold(
             (Vec::with_capacity(len), positive_max_ttl),
             |(mut records, mut min_ttl), (record, ttl)| {
                 records.push(record);
@@ -295,14 +649,16 @@ impl DnsLru {
         let ttl = positive_min_ttl.max(ttl);
         let valid_until = now + ttl;
 
+        // the records returned now carry the same TTL as those returned from the cache later
+        for record in &mut records {
+            record.set_ttl(u32::try_from(ttl.as_secs()).unwrap_or(MAX_TTL));
+        }
+
         // insert into the LRU
         let lookup = Lookup::new_with_deadline(query.clone(), Arc::from(records), valid_until);
-        self.cache.insert(
-            query,
-            LruValue {
-                lookup: Ok(lookup.clone()),
-                valid_until,
-            },
+        self.insert_entry(
+            CacheKey { query, subnet },
+            self.entry(CachedAnswer::from_lookup(&lookup), now, valid_until),
         );
 
         lookup
@@ -395,16 +751,22 @@ impl DnsLru {
     }
 
     /// Generally for inserting a set of records that have already been cached, but with a different Query.
-    pub(crate) fn duplicate(&self, query: Query, lookup: Lookup, ttl: u32, now: Instant) -> Lookup {
+    ///
+    /// The lookup is returned as is, keeping the metadata of the response it was built from.
+    pub(crate) fn duplicate(
+        &self,
+        query: Query,
+        subnet: Option<IpNet>,
+        lookup: Lookup,
+        ttl: u32,
+        now: Instant,
+    ) -> Lookup {
         let ttl = Duration::from_secs(u64::from(ttl));
         let valid_until = now + ttl;
 
-        self.cache.insert(
-            query,
-            LruValue {
-                lookup: Ok(lookup.clone()),
-                valid_until,
-            },
+        self.insert_entry(
+            CacheKey { query, subnet },
+            self.entry(CachedAnswer::from_lookup(&lookup), now, valid_until),
         );
 
         lookup
@@ -420,15 +782,25 @@ impl DnsLru {
         }
     }
 
-    pub(crate) fn negative(&self, query: Query, mut error: ProtoError, now: Instant) -> ProtoError {
+    /// Caches the negative response for the query, only valid for lookups from the `subnet` if specified
+    pub(crate) fn negative(
+        &self,
+        query: Query,
+        subnet: Option<IpNet>,
+        mut error: ProtoError,
+        now: Instant,
+    ) -> ProtoError {
         let ProtoError { kind, .. } = &error;
 
         // TODO: if we are getting a negative response, should we instead fallback to cache?
         //   this would cache indefinitely, probably not correct
-        if let ProtoErrorKind::NoRecordsFound {
-            negative_ttl: Some(ttl),
-            ..
-        } = kind.as_ref()
+        if let (
+            ProtoErrorKind::NoRecordsFound {
+                negative_ttl: Some(ttl),
+                ..
+            },
+            Some(answer),
+        ) = (kind.as_ref(), CachedAnswer::from_error(&error))
         {
             let (negative_min_ttl, negative_max_ttl) = self
                 .ttl_config
@@ -441,17 +813,10 @@ impl DnsLru {
                 .clamp(negative_min_ttl, negative_max_ttl);
             let valid_until = now + ttl_duration;
 
-            {
-                let error = error.clone();
-
-                self.cache.insert(
-                    query,
-                    LruValue {
-                        lookup: Err(error),
-                        valid_until,
-                    },
-                );
-            }
+            self.insert_entry(
+                CacheKey { query, subnet },
+                self.entry(answer, now, valid_until),
+            );
 
             Self::nx_error_with_ttl(&mut error, ttl_duration);
         }
@@ -461,15 +826,141 @@ impl DnsLru {
 
     /// Based on the query, see if there are any records available
     pub fn get(&self, query: &Query, now: Instant) -> Option<Result<Lookup, ProtoError>> {
-        let value = self.cache.get(query)?;
+        self.get_scoped(query, None, now)
+    }
+
+    /// Based on the query and the client subnet, see if there are any records available
+    ///
+    /// Entries scoped to the `subnet` take precedence over those valid for all clients.
+    pub(crate) fn get_scoped(
+        &self,
+        query: &Query,
+        subnet: Option<IpNet>,
+        now: Instant,
+    ) -> Option<Result<Lookup, ProtoError>> {
+        self.get_scoped_entry(query, subnet, now)
+            .map(|entry| entry.lookup())
+    }
+
+    /// Like [`Self::get_scoped`], but returns the whole entry, with the TTLs updated
+    pub(crate) fn get_scoped_entry(
+        &self,
+        query: &Query,
+        subnet: Option<IpNet>,
+        now: Instant,
+    ) -> Option<CacheEntry> {
+        let mut key = CacheKey {
+            query: query.clone(),
+            subnet: None,
+        };
+        self.scopes(subnet).find_map(|scope| {
+            key.subnet = scope;
+            self.get_entry(&key, now)
+        })
+    }
+
+    /// Returns the records for the query which expired, but may still be served stale
+    ///
+    /// See [RFC 8767](https://tools.ietf.org/html/rfc8767), this is only used when the name servers
+    /// could not be reached. The TTLs of the returned records are set to 30 seconds.
+    pub(crate) fn get_stale(
+        &self,
+        query: &Query,
+        subnet: Option<IpNet>,
+        now: Instant,
+    ) -> Option<Lookup> {
+        self.serve_stale?;
+
+        let mut key = CacheKey {
+            query: query.clone(),
+            subnet: None,
+        };
+        let value = self
+            .scopes(subnet)
+            .filter_map(|scope| {
+                key.subnet = scope;
+                self.store.get(&key)
+            })
+            .find(|value| !value.is_current(now) && !value.is_stale(now))?;
+
+        let CachedAnswer::Records { records, .. } = &value.answer else {
+            return None;
+        };
+
+        let records = records
+            .iter()
+            .map(|record| {
+                let mut record = record.clone();
+                record.set_ttl(STALE_ANSWER_TTL);
+                record
+            })
+            .collect::<Vec<Record>>();
+        Some(Lookup::new_with_deadline(
+            query.clone(),
+            Arc::from(records),
+            now + Duration::from_secs(u64::from(STALE_ANSWER_TTL)),
+        ))
+    }
+
+    fn get_entry(&self, key: &CacheKey, now: Instant) -> Option<CacheEntry> {
+        let value = self.store.get(key)?;
         if !value.is_current(now) {
+            if value.is_stale(now) {
+                self.store.remove(key);
+            }
             return None;
         }
-        let mut result = value.with_updated_ttl(now).lookup;
-        if let Err(err) = &mut result {
-            Self::nx_error_with_ttl(err, value.ttl(now));
+        Some(value.with_updated_ttl(now))
+    }
+
+    /// The scopes of the entries that may answer a lookup from the `subnet`, the most specific first
+    ///
+    /// Entries are cached for the scope prefix length of the response, which may be shorter than the
+    /// prefix length of the subnet sent, see
+    /// [RFC 7871, section 7.3.1](https://tools.ietf.org/html/rfc7871#section-7.3.1). Only the prefix
+    /// lengths of the scopes inserted are probed.
+    fn scopes(&self, subnet: Option<IpNet>) -> impl Iterator<Item = Option<IpNet>> + '_ {
+        let scoped = subnet.into_iter().flat_map(move |subnet| {
+            (1..=subnet.prefix_len())
+                .rev()
+                .filter_map(move |len| IpNet::new(subnet.addr(), len).ok())
+                .filter(|scope| self.scope_prefixes.contains(scope))
+                .map(|scope| Some(scope.trunc()))
+        });
+
+        scoped.chain([None])
+    }
+}
+
+/// The prefix lengths of the scopes of the entries inserted, by address family
+///
+/// Bit `n - 1` is set once an entry scoped to a prefix length of `n` was inserted, the IPv6 lengths
+/// span two words.
+#[derive(Debug, Default)]
+struct ScopePrefixes {
+    v4: AtomicU64,
+    v6: [AtomicU64; 2],
+}
+
+impl ScopePrefixes {
+    fn insert(&self, scope: IpNet) {
+        if let Some((word, bit)) = self.bit(&scope) {
+            word.fetch_or(bit, Ordering::Relaxed);
         }
-        Some(result)
+    }
+
+    fn contains(&self, scope: &IpNet) -> bool {
+        self.bit(scope)
+            .is_some_and(|(word, bit)| word.load(Ordering::Relaxed) & bit != 0)
+    }
+
+    fn bit(&self, scope: &IpNet) -> Option<(&AtomicU64, u64)> {
+        let index = usize::from(scope.prefix_len().checked_sub(1)?);
+        let word = match scope {
+            IpNet::V4(_) => &self.v4,
+            IpNet::V6(_) => &self.v6[index / 64],
+        };
+        Some((word, 1 << (index % 64)))
     }
 }
 
@@ -489,26 +980,26 @@ where
 #[cfg(feature = "serde")]
 mod ttl_config_deserialize;
 
-struct LruValueExpiry;
+struct CacheEntryExpiry;
 
-impl Expiry<Query, LruValue> for LruValueExpiry {
+impl Expiry<CacheKey, CacheEntry> for CacheEntryExpiry {
     fn expire_after_create(
         &self,
-        _key: &Query,
-        value: &LruValue,
+        _key: &CacheKey,
+        value: &CacheEntry,
         created_at: Instant,
     ) -> Option<Duration> {
-        Some(value.ttl(created_at))
+        Some(value.stale_ttl(created_at))
     }
 
     fn expire_after_update(
         &self,
-        _key: &Query,
-        value: &LruValue,
+        _key: &CacheKey,
+        value: &CacheEntry,
         updated_at: Instant,
         _duration_until_expiry: Option<Duration>,
     ) -> Option<Duration> {
-        Some(value.ttl(updated_at))
+        Some(value.stale_ttl(updated_at))
     }
 }
 
@@ -533,10 +1024,18 @@ mod tests {
         let future = now + Duration::from_secs(5);
         let past_the_future = now + Duration::from_secs(6);
 
-        let value = LruValue {
-            lookup: Err(ProtoErrorKind::Message("test error").into()),
-            valid_until: future,
-        };
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let error = ProtoError::nx_error(
+            Box::new(query),
+            None,
+            None,
+            None,
+            ResponseCode::NXDomain,
+            false,
+            None,
+        );
+        let answer = CachedAnswer::from_error(&error).expect("negative response");
+        let value = CacheEntry::new(answer, system_time(future));
 
         assert!(value.is_current(now));
         assert!(value.is_current(not_the_future));
@@ -612,7 +1111,7 @@ mod tests {
             trusted: false,
             authorities: None,
         };
-        let nx_error = lru.negative(name.clone(), err.into(), now);
+        let nx_error = lru.negative(name.clone(), None, err.into(), now);
         match nx_error.kind() {
             &ProtoErrorKind::NoRecordsFound { negative_ttl, .. } => {
                 let valid_until = negative_ttl.expect("resolve error should have a deadline");
@@ -632,7 +1131,7 @@ mod tests {
             trusted: false,
             authorities: None,
         };
-        let nx_error = lru.negative(name, err.into(), now);
+        let nx_error = lru.negative(name, None, err.into(), now);
         match nx_error.kind() {
             &ProtoErrorKind::NoRecordsFound { negative_ttl, .. } => {
                 let negative_ttl = negative_ttl.expect("ProtoError should have a deadline");
@@ -712,7 +1211,7 @@ mod tests {
             trusted: false,
             authorities: None,
         };
-        let nx_error = lru.negative(name.clone(), err.into(), now);
+        let nx_error = lru.negative(name.clone(), None, err.into(), now);
         match nx_error.kind() {
             &ProtoErrorKind::NoRecordsFound { negative_ttl, .. } => {
                 let negative_ttl = negative_ttl.expect("resolve error should have a deadline");
@@ -732,7 +1231,7 @@ mod tests {
             trusted: false,
             authorities: None,
         };
-        let nx_error = lru.negative(name, err.into(), now);
+        let nx_error = lru.negative(name, None, err.into(), now);
         match nx_error.kind() {
             &ProtoErrorKind::NoRecordsFound { negative_ttl, .. } => {
                 let negative_ttl = negative_ttl.expect("resolve error should have a deadline");
@@ -945,6 +1444,39 @@ mod tests {
         assert!(rc_ips.is_none());
     }
 
+    #[test]
+    fn test_insert_returns_clamped_ttl() {
+        let now = Instant::now();
+        let name = Name::from_str("www.example.com.").unwrap();
+        let query = Query::query(name.clone(), RecordType::A);
+        let ttls = TtlConfig::new(
+            Some(Duration::from_secs(60)),
+            None,
+            Some(Duration::from_secs(3600)),
+            None,
+        );
+        let lru = DnsLru::new(1, ttls);
+
+        // TTLs below the minimum and above the maximum are clamped, for the caller and the cache
+        for (ttl, clamped) in [(1, 60), (86400, 3600)] {
+            let record = Record::from_rdata(name.clone(), ttl, RData::A(A::new(127, 0, 0, 1)));
+            let inserted = lru.insert(query.clone(), vec![(record, ttl)], now);
+            assert_eq!(inserted.valid_until(), now + Duration::from_secs(clamped));
+            assert_eq!(inserted.records()[0].ttl(), clamped as u32);
+
+            let cached = lru.get(&query, now).unwrap().unwrap();
+            assert_eq!(cached.valid_until(), inserted.valid_until());
+            assert_eq!(cached.records()[0].ttl(), inserted.records()[0].ttl());
+
+            assert!(lru
+                .get(&query, now + Duration::from_secs(clamped))
+                .is_some());
+            assert!(lru
+                .get(&query, now + Duration::from_secs(clamped + 1))
+                .is_none());
+        }
+    }
+
     #[test]
     fn test_lookup_positive_min_ttl_different_query_types() {
         let now = Instant::now();
@@ -997,4 +1529,285 @@ mod tests {
         // greater than the min TTL for TXT records.
         assert_eq!(rc_txt.valid_until(), now + Duration::from_secs(7));
     }
+
+    #[test]
+    fn test_lru_store() {
+        let now = Instant::now();
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let key = CacheKey {
+            query: query.clone(),
+            subnet: None,
+        };
+        let store = LruStore::new(1);
+
+        assert!(store.get(&key).is_none());
+
+        let lookup = Lookup::from_rdata(query, RData::A(A::new(127, 0, 0, 1)));
+        let entry = CacheEntry::new(
+            CachedAnswer::from_lookup(&lookup),
+            system_time(now + Duration::from_secs(10)),
+        );
+        store.insert(key.clone(), entry);
+        let entry = store.get(&key).expect("entry should exist");
+        assert_eq!(
+            entry.valid_until(),
+            system_time(now + Duration::from_secs(10))
+        );
+        assert_eq!(
+            *entry.lookup().unwrap().iter().next().unwrap(),
+            RData::A(A::new(127, 0, 0, 1))
+        );
+
+        store.remove(&key);
+        assert!(store.get(&key).is_none());
+
+        store.insert(key.clone(), entry);
+        store.clear();
+        assert!(store.get(&key).is_none());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_entry_serde() {
+        let now = Instant::now();
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let lookup = Lookup::from_rdata(query.clone(), RData::A(A::new(127, 0, 0, 1)));
+        let entry = CacheEntry::new(
+            CachedAnswer::from_lookup(&lookup),
+            system_time(now + Duration::from_secs(10)),
+        );
+
+        let entry = toml::from_str::<CacheEntry>(&toml::to_string(&entry).unwrap()).unwrap();
+        assert_eq!(
+            entry.valid_until(),
+            system_time(now + Duration::from_secs(10))
+        );
+        assert_eq!(entry.lookup().unwrap().records(), lookup.records());
+
+        let error = ProtoError::nx_error(
+            Box::new(query),
+            None,
+            None,
+            Some(30),
+            ResponseCode::NXDomain,
+            true,
+            None,
+        );
+        let entry = CacheEntry::new(
+            CachedAnswer::from_error(&error).expect("negative response"),
+            system_time(now + Duration::from_secs(30)),
+        );
+
+        let entry = toml::from_str::<CacheEntry>(&toml::to_string(&entry).unwrap()).unwrap();
+        let error = entry.lookup().expect_err("negative response");
+        let ProtoErrorKind::NoRecordsFound {
+            response_code,
+            negative_ttl,
+            trusted,
+            ..
+        } = error.kind()
+        else {
+            panic!("unexpected error: {error}");
+        };
+        assert_eq!(*response_code, ResponseCode::NXDomain);
+        assert_eq!(*negative_ttl, Some(30));
+        assert!(*trusted);
+    }
+
+    /// An entry for `name` with a TXT record of about `len` bytes
+    fn txt_entry(name: &str, len: usize, now: Instant) -> (CacheKey, CacheEntry) {
+        let name = Name::from_str(name).unwrap();
+        let query = Query::query(name.clone(), RecordType::TXT);
+        let txt = TXT::new(vec!["x".repeat(250); len / 250]);
+        let lookup = Lookup::new_with_max_ttl(
+            query.clone(),
+            Arc::from([Record::from_rdata(name, 86400, RData::TXT(txt))]),
+        );
+        let key = CacheKey {
+            query,
+            subnet: None,
+        };
+        (
+            key,
+            CacheEntry::new(
+                CachedAnswer::from_lookup(&lookup),
+                system_time(now + Duration::from_secs(86400)),
+            ),
+        )
+    }
+
+    #[test]
+    fn test_entry_size() {
+        let now = Instant::now();
+        let (_, small) = txt_entry("small.example.com.", 250, now);
+        let (_, large) = txt_entry("large.example.com.", 2500, now);
+        assert!(small.size() > 250);
+        assert!(large.size() > 2500);
+        assert!(large.size() - small.size() >= 2250);
+    }
+
+    #[test]
+    fn test_lru_store_max_bytes() {
+        let now = Instant::now();
+
+        // the large entries are limited by the bytes
+        let store = LruStore::with_max_bytes(100, 8192);
+        for i in 0..50 {
+            let (key, entry) = txt_entry(&format!("large{i}.example.com."), 2000, now);
+            store.insert(key, entry);
+        }
+        // the small entries are limited by the number of entries
+        for i in 0..50 {
+            let (key, entry) = txt_entry(&format!("small{i}.example.com."), 250, now);
+            store.insert(key, entry);
+        }
+        // an entry larger than all the bytes is never kept
+        let (huge_key, huge) = txt_entry("huge.example.com.", 10000, now);
+        store.insert(huge_key.clone(), huge);
+        store.cache.run_pending_tasks();
+
+        assert!(store.cache.weighted_size() <= 8192);
+        let kept = store
+            .cache
+            .iter()
+            .map(|(_, entry)| entry.size())
+            .collect::<Vec<_>>();
+        assert!(!kept.is_empty());
+        assert!(kept.iter().sum::<usize>() <= 8192);
+        assert!(store.get(&huge_key).is_none());
+
+        let store = LruStore::with_max_bytes(4, 1 << 20);
+        for i in 0..20 {
+            let (key, entry) = txt_entry(&format!("small{i}.example.com."), 250, now);
+            store.insert(key, entry);
+        }
+        store.cache.run_pending_tasks();
+        assert!(store.cache.entry_count() <= 4);
+    }
+
+    /// A store which never evicts entries by itself
+    #[derive(Debug, Default)]
+    struct MapStore {
+        entries: std::sync::Mutex<HashMap<CacheKey, CacheEntry>>,
+        gets: std::sync::atomic::AtomicUsize,
+    }
+
+    impl CacheStore for MapStore {
+        fn get(&self, key: &CacheKey) -> Option<CacheEntry> {
+            self.gets.fetch_add(1, Ordering::SeqCst);
+            self.entries.lock().unwrap().get(key).cloned()
+        }
+
+        fn insert(&self, key: CacheKey, entry: CacheEntry) {
+            self.entries.lock().unwrap().insert(key, entry);
+        }
+
+        fn remove(&self, key: &CacheKey) {
+            self.entries.lock().unwrap().remove(key);
+        }
+
+        fn clear(&self) {
+            self.entries.lock().unwrap().clear();
+        }
+    }
+
+    #[test]
+    fn test_custom_store() {
+        let now = Instant::now();
+        let store = Arc::new(MapStore::default());
+        let lru = DnsLru::with_store(store.clone(), TtlConfig::default());
+
+        // positive responses have their TTL decremented
+        let name = Name::from_str("www.example.com.").unwrap();
+        let query = Query::query(name.clone(), RecordType::A);
+        let records = vec![(
+            Record::from_rdata(name.clone(), 10, RData::A(A::new(127, 0, 0, 1))),
+            10,
+        )];
+        lru.insert(query.clone(), records, now);
+        assert_eq!(store.entries.lock().unwrap().len(), 1);
+
+        let lookup = lru
+            .get(&query, now + Duration::from_secs(4))
+            .unwrap()
+            .expect("records should exist");
+        assert_eq!(lookup.record_iter().next().unwrap().ttl(), 6);
+
+        // negative responses are cached, with the remaining TTL
+        let nx_query = Query::query(Name::from_str("nx.example.com.").unwrap(), RecordType::A);
+        let err = ProtoErrorKind::NoRecordsFound {
+            query: Box::new(nx_query.clone()),
+            soa: None,
+            ns: None,
+            negative_ttl: Some(10),
+            response_code: ResponseCode::NXDomain,
+            trusted: false,
+            authorities: None,
+        };
+        lru.negative(nx_query.clone(), None, err.into(), now);
+        assert_eq!(store.entries.lock().unwrap().len(), 2);
+
+        let err = lru
+            .get(&nx_query, now + Duration::from_secs(3))
+            .unwrap()
+            .expect_err("negative response should be cached");
+        match err.kind() {
+            ProtoErrorKind::NoRecordsFound { negative_ttl, .. } => {
+                assert_eq!(*negative_ttl, Some(7))
+            }
+            other => panic!("expected ProtoErrorKind::NoRecordsFound, got {other:?}"),
+        }
+
+        // expired entries are removed from the store
+        assert!(lru.get(&query, now + Duration::from_secs(11)).is_none());
+        assert!(lru.get(&nx_query, now + Duration::from_secs(11)).is_none());
+        assert!(store.entries.lock().unwrap().is_empty());
+
+        // caches built on the same store share their entries, unlike with the default store
+        let store: Arc<dyn CacheStore> = store;
+        let shared = DnsLru::with_store(Arc::clone(&store), TtlConfig::default());
+        let default = DnsLru::new(1, TtlConfig::default());
+        lru.insert(
+            query.clone(),
+            vec![(
+                Record::from_rdata(name, 10, RData::A(A::new(127, 0, 0, 1))),
+                10,
+            )],
+            now,
+        );
+        assert!(shared.get(&query, now).is_some());
+        assert!(default.get(&query, now).is_none());
+    }
+
+    #[test]
+    fn test_scoped_lookup_probes() {
+        let now = Instant::now();
+        let store = Arc::new(MapStore::default());
+        let lru = DnsLru::with_store(store.clone(), TtlConfig::default());
+
+        let name = Name::from_str("www.example.com.").unwrap();
+        let query = Query::query(name.clone(), RecordType::A);
+        let records = vec![(
+            Record::from_rdata(name, 10, RData::A(A::new(127, 0, 0, 1))),
+            10,
+        )];
+        lru.insert_scoped(
+            query.clone(),
+            Some("192.0.0.0/16".parse().unwrap()),
+            records,
+            now,
+        );
+
+        // only the scope of the inserted prefix length is probed
+        let gets = |subnet: &str| {
+            store.gets.store(0, Ordering::SeqCst);
+            let found = lru
+                .get_scoped(&query, Some(subnet.parse().unwrap()), now)
+                .is_some();
+            (found, store.gets.load(Ordering::SeqCst))
+        };
+        assert_eq!(gets("192.0.2.1/24"), (true, 1));
+        assert_eq!(gets("198.51.100.1/24"), (false, 2));
+        assert_eq!(gets("2001:db8::1/56"), (false, 1));
+    }
 }
@@ -11,7 +11,11 @@ use std::{fmt, io, sync};
 
 use thiserror::Error;
 
+use crate::lookup::LookupMetadata;
+#[cfg(feature = "dnssec-ring")]
+use crate::proto::dnssec::Proof;
 use crate::proto::{
+    op::ExtendedDnsError,
     rr::{rdata::SOA, Record},
     xfer::retry_dns_handle::RetryableError,
     ProtoError, ProtoErrorKind,
@@ -53,6 +57,8 @@ impl Clone for ResolveErrorKind {
 #[derive(Debug, Clone, Error)]
 pub struct ResolveError {
     pub(crate) kind: ResolveErrorKind,
+    extended_dns_error: Option<ExtendedDnsError>,
+    metadata: Option<Box<LookupMetadata>>,
     #[cfg(feature = "backtrace")]
     backtrack: Option<ExtBacktrace>,
 }
@@ -90,6 +96,33 @@ impl ResolveError {
             .unwrap_or(false)
     }
 
+    /// The Extended DNS Error, [RFC 8914](https://tools.ietf.org/html/rfc8914), describing why the
+    /// resolver failed, if one applies
+    ///
+    /// This is set for failures synthesized by the resolver itself, such as a response that failed
+    /// DNSSEC validation, so that a server forwarding to this resolver can include it in its own
+    /// response.
+    pub fn extended_dns_error(&self) -> Option<&ExtendedDnsError> {
+        self.extended_dns_error.as_ref()
+    }
+
+    /// Attach an Extended DNS Error to this error, replacing any existing one
+    pub fn with_extended_dns_error(mut self, extended_dns_error: ExtendedDnsError) -> Self {
+        self.extended_dns_error = Some(extended_dns_error);
+        self
+    }
+
+    /// Returns where the error was obtained from, e.g. a negative answer from the cache
+    pub fn metadata(&self) -> LookupMetadata {
+        self.metadata.as_deref().copied().unwrap_or_default()
+    }
+
+    /// Sets where the error was obtained from, see [`Self::metadata`]
+    pub(crate) fn with_metadata(mut self, metadata: LookupMetadata) -> Self {
+        self.metadata = Some(Box::new(metadata));
+        self
+    }
+
     /// Returns the SOA record, if the error contains one
     pub fn into_soa(self) -> Option<Box<Record<SOA>>> {
         match self.kind {
@@ -134,14 +167,41 @@ impl fmt::Display for ResolveError {
 
 impl From<ResolveErrorKind> for ResolveError {
     fn from(kind: ResolveErrorKind) -> Self {
+        let extended_dns_error = match &kind {
+            ResolveErrorKind::Proto(proto) => extended_dns_error(proto.kind()),
+            _ => None,
+        };
+
         Self {
             kind,
+            extended_dns_error,
+            metadata: None,
             #[cfg(feature = "backtrace")]
             backtrack: trace!(),
         }
     }
 }
 
+/// The Extended DNS Error for failures detected by the resolver
+///
+/// See the [IANA registry](https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#extended-dns-error-codes)
+/// for the info codes.
+fn extended_dns_error(kind: &ProtoErrorKind) -> Option<ExtendedDnsError> {
+    let (info_code, extra_text) = match kind {
+        #[cfg(feature = "dnssec-ring")]
+        ProtoErrorKind::Nsec { proof, .. } => match proof {
+            Proof::Bogus => (6, "DNSSEC Bogus"),
+            Proof::Indeterminate => (5, "DNSSEC Indeterminate"),
+            _ => return None,
+        },
+        ProtoErrorKind::Timeout => (22, "No Reachable Authority"),
+        ProtoErrorKind::Io(_) => (23, "Network Error"),
+        _ => return None,
+    };
+
+    ExtendedDnsError::new(info_code, extra_text).ok()
+}
+
 impl From<&'static str> for ResolveError {
     fn from(msg: &'static str) -> Self {
         ResolveErrorKind::Message(msg).into()
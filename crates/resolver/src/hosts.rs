@@ -1,17 +1,19 @@
 //! Hosts result from a configuration of the system hosts file
 
 use std::collections::HashMap;
+use std::fs;
 use std::io;
 use std::net::IpAddr;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
 use crate::proto::op::Query;
 use crate::proto::rr::rdata::PTR;
 use crate::proto::rr::{Name, RecordType};
 use crate::proto::rr::{RData, Record};
-use tracing::warn;
+use tracing::{debug, warn};
 
 use crate::dns_lru;
 use crate::lookup::Lookup;
@@ -53,7 +55,16 @@ impl Hosts {
         }
         match query.query_type() {
             RecordType::A | RecordType::AAAA => {
-                let val = self.by_name.get(query.name())?;
+                // the entries of the hosts file are not fully qualified, the names of the resolver are
+                let val = match self.by_name.get(query.name()) {
+                    Some(val) => val,
+                    None if query.name().is_fqdn() => {
+                        let mut name = query.name().clone();
+                        name.set_fqdn(false);
+                        self.by_name.get(&name)?
+                    }
+                    None => return None,
+                };
 
                 match query.query_type() {
                     RecordType::A => val.a.clone(),
@@ -193,6 +204,130 @@ impl Hosts {
     }
 }
 
+/// A hosts file which is read again when it changes
+///
+/// The file is checked for changes of its modification time or size when the hosts are requested,
+/// at most once per `interval`. The new entries replace the previous ones at once, lookups which
+/// are in progress keep the entries they started with. While the file is absent or can not be
+/// read, e.g. while an editor replaces it, the previous entries are kept.
+#[derive(Debug)]
+pub struct HostsWatcher {
+    path: PathBuf,
+    interval: Duration,
+    state: Mutex<WatchState>,
+}
+
+#[derive(Debug)]
+struct WatchState {
+    hosts: Arc<Hosts>,
+    stamp: Option<(SystemTime, u64)>,
+    checked_at: Instant,
+}
+
+impl HostsWatcher {
+    /// Reads the hosts file at `path`, which is checked for changes every `interval`
+    ///
+    /// A `Duration::ZERO` interval checks the file on every lookup. If the file can not be read,
+    /// there are no entries until it can be read.
+    pub fn new(path: impl Into<PathBuf>, interval: Duration) -> Self {
+        let path = path.into();
+        let stamp = file_stamp(&path).ok();
+        let hosts = match read_hosts_conf(&path) {
+            Ok(hosts) => hosts,
+            Err(e) => {
+                debug!("failed to read hosts file {}: {e}", path.display());
+                Hosts::default()
+            }
+        };
+
+        Self {
+            path,
+            interval,
+            state: Mutex::new(WatchState {
+                hosts: Arc::new(hosts),
+                stamp,
+                checked_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Watches the system hosts file, only works for Windows and Unix-like OSes
+    #[cfg(any(unix, windows))]
+    pub fn system(interval: Duration) -> Self {
+        Self::new(hosts_path(), interval)
+    }
+
+    /// The current entries of the hosts file, read again if the file changed
+    pub fn hosts(&self) -> Arc<Hosts> {
+        let mut state = self.state.lock().expect("HostsWatcher lock poisoned");
+        if state.checked_at.elapsed() >= self.interval {
+            state.checked_at = Instant::now();
+            self.refresh(&mut state);
+        }
+
+        Arc::clone(&state.hosts)
+    }
+
+    fn refresh(&self, state: &mut WatchState) {
+        let stamp = match file_stamp(&self.path) {
+            Ok(stamp) => stamp,
+            Err(e) => {
+                debug!(
+                    "hosts file {} is unavailable, keeping the previous entries: {e}",
+                    self.path.display()
+                );
+                return;
+            }
+        };
+
+        if state.stamp == Some(stamp) {
+            return;
+        }
+
+        match read_hosts_conf(&self.path) {
+            Ok(hosts) => {
+                debug!("reloaded hosts file {}", self.path.display());
+                state.hosts = Arc::new(hosts);
+                state.stamp = Some(stamp);
+            }
+            Err(e) => warn!(
+                "failed to read hosts file {}, keeping the previous entries: {e}",
+                self.path.display()
+            ),
+        }
+    }
+}
+
+fn file_stamp(path: &Path) -> io::Result<(SystemTime, u64)> {
+    let metadata = fs::metadata(path)?;
+    Ok((metadata.modified()?, metadata.len()))
+}
+
+/// The static hosts of a resolver, either read once or watched for changes
+#[derive(Clone, Debug)]
+pub(crate) enum HostsSource {
+    Static(Arc<Hosts>),
+    Watched(Arc<HostsWatcher>),
+}
+
+impl HostsSource {
+    /// The system hosts file, watched if there is a `reload_interval`
+    pub(crate) fn system(reload_interval: Option<Duration>) -> Self {
+        match reload_interval {
+            #[cfg(any(unix, windows))]
+            Some(interval) => Self::Watched(Arc::new(HostsWatcher::system(interval))),
+            _ => Self::Static(Arc::new(Hosts::new())),
+        }
+    }
+
+    pub(crate) fn hosts(&self) -> Arc<Hosts> {
+        match self {
+            Self::Static(hosts) => Arc::clone(hosts),
+            Self::Watched(watcher) => watcher.hosts(),
+        }
+    }
+}
+
 #[cfg(unix)]
 fn hosts_path() -> &'static str {
     "/etc/hosts"
@@ -207,11 +342,8 @@ fn hosts_path() -> std::path::PathBuf {
 }
 
 /// parse configuration from `path`
-#[cfg(any(unix, windows))]
 pub(crate) fn read_hosts_conf<P: AsRef<Path>>(path: P) -> io::Result<Hosts> {
-    use std::fs::File;
-
-    let file = File::open(path)?;
+    let file = fs::File::open(path)?;
     let mut hosts = Hosts::default();
     hosts.read_hosts_conf(file)?;
     Ok(hosts)
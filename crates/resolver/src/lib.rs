@@ -127,6 +127,12 @@
 //! Inside the `Future::poll` method would be the place to implement a loop over the different IP
 //! addresses.
 //!
+//! ## Using the blocking Resolver
+//!
+//! Without an async runtime, e.g. in CLI tools, the `blocking` feature provides
+//! [`blocking::Resolver`], which owns a current-thread Tokio runtime and blocks until each lookup
+//! completes.
+//!
 //! ## DNS-over-TLS and DNS-over-HTTPS
 //!
 //! DNS-over-TLS and DNS-over-HTTPS are supported in the Hickory DNS Resolver library. The underlying
@@ -203,8 +209,11 @@ pub use hickory_proto as proto;
 // reexports from proto
 pub use proto::rr::{IntoName, Name};
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
 pub mod caching_client;
 pub mod config;
+mod dns64;
 pub mod dns_lru;
 mod error;
 pub use error::{ResolveError, ResolveErrorKind};
@@ -213,11 +222,13 @@ mod h2;
 #[cfg(feature = "dns-over-h3")]
 mod h3;
 mod hosts;
-pub use hosts::Hosts;
+pub use hosts::{Hosts, HostsWatcher};
 pub mod lookup;
 pub mod lookup_ip;
 // TODO: consider #[doc(hidden)]
 pub mod name_server;
+#[cfg(feature = "dnssec-ring")]
+mod nsec_cache;
 #[cfg(feature = "tokio-runtime")]
 use name_server::TokioConnectionProvider;
 #[cfg(feature = "dns-over-quic")]
@@ -225,6 +236,8 @@ mod quic;
 mod resolver;
 #[cfg(feature = "testing")]
 pub use resolver::testing;
+#[cfg(all(any(test, feature = "testing"), feature = "tokio-runtime"))]
+pub mod test_transport;
 pub use resolver::LookupFuture;
 pub use resolver::Resolver;
 #[cfg(feature = "tokio-runtime")]
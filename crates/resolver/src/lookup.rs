@@ -9,6 +9,7 @@
 
 use std::{
     cmp::min,
+    net::SocketAddr,
     pin::Pin,
     slice::Iter,
     sync::Arc,
@@ -27,13 +28,13 @@ use crate::{
             rdata::{self, A, AAAA, NS, PTR},
             RData, Record,
         },
-        xfer::{DnsRequest, DnsResponse},
+        xfer::{DnsRequest, DnsResponse, Protocol},
         DnsHandle, ProtoError, RetryDnsHandle,
     },
 };
 
 #[cfg(feature = "dnssec-ring")]
-use crate::proto::dnssec::{DnssecDnsHandle, Proven};
+use crate::proto::dnssec::{DnssecDnsHandle, Proof, Proven};
 
 /// Result of a DNS query when querying for any record type supported by the Hickory DNS Proto library.
 ///
@@ -43,6 +44,65 @@ pub struct Lookup {
     query: Query,
     records: Arc<[Record]>,
     valid_until: Instant,
+    metadata: LookupMetadata,
+}
+
+/// Where the records of a [`Lookup`] were obtained from
+///
+/// See [`Resolver::lookup_with_metadata`](crate::Resolver::lookup_with_metadata).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct LookupMetadata {
+    /// The address of the name server which sent the records, `None` if they were answered from
+    /// the cache, or without querying a name server, e.g. from the hosts file
+    pub server: Option<SocketAddr>,
+    /// The protocol used to query the name server
+    pub protocol: Option<Protocol>,
+    /// Whether the records were answered from the cache
+    pub from_cache: bool,
+    /// How long the lookup took when it was not answered from the cache, zero otherwise
+    pub upstream_duration: Duration,
+}
+
+impl LookupMetadata {
+    /// The metadata of the records of a response sent by the name server
+    pub(crate) fn from_response(response: &DnsResponse) -> Self {
+        let (server, protocol) = response.server().unzip();
+        Self {
+            server,
+            protocol,
+            from_cache: false,
+            upstream_duration: Duration::ZERO,
+        }
+    }
+
+    /// The metadata of records answered from the cache
+    pub fn cached() -> Self {
+        Self {
+            server: None,
+            protocol: None,
+            from_cache: true,
+            upstream_duration: Duration::ZERO,
+        }
+    }
+
+    /// Sets how long the lookup took, see [`Self::upstream_duration`]
+    pub fn with_upstream_duration(mut self, upstream_duration: Duration) -> Self {
+        self.upstream_duration = upstream_duration;
+        self
+    }
+}
+
+/// The outcome of the DNSSEC validation of a [`Lookup`]
+#[cfg(feature = "dnssec-ring")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DnssecStatus {
+    /// All the records were validated, the AD flag of a response with these records would be set
+    AuthenticData,
+    /// At least one of the records failed the validation
+    Bogus,
+    /// Some of the records are not signed, or their proofs could not be determined
+    Insecure,
 }
 
 impl Lookup {
@@ -55,11 +115,7 @@ impl Lookup {
     /// Return new instance with given records and the maximum TTL.
     pub fn new_with_max_ttl(query: Query, records: Arc<[Record]>) -> Self {
         let valid_until = Instant::now() + Duration::from_secs(u64::from(MAX_TTL));
-        Self {
-            query,
-            records,
-            valid_until,
-        }
+        Self::new_with_deadline(query, records, valid_until)
     }
 
     /// Return a new instance with the given records and deadline.
@@ -68,9 +124,21 @@ impl Lookup {
             query,
             records,
             valid_until,
+            metadata: LookupMetadata::default(),
         }
     }
 
+    /// Returns where the records were obtained from
+    pub fn metadata(&self) -> LookupMetadata {
+        self.metadata
+    }
+
+    /// Sets where the records were obtained from, see [`Self::metadata`]
+    pub fn with_metadata(mut self, metadata: LookupMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
     /// Returns a reference to the `Query` that was used to produce this result.
     pub fn query(&self) -> &Query {
         &self.query
@@ -102,6 +170,24 @@ impl Lookup {
         DnssecLookupRecordIter(self.records.iter())
     }
 
+    /// Returns the outcome of the DNSSEC validation of the records
+    ///
+    /// This is only meaningful when the resolver validates the responses, see
+    /// [`DnssecMode`](crate::config::DnssecMode), otherwise it is always
+    /// [`DnssecStatus::Insecure`].
+    #[cfg(feature = "dnssec-ring")]
+    pub fn dnssec_status(&self) -> DnssecStatus {
+        if self.records.iter().any(|r| r.proof() == Proof::Bogus) {
+            DnssecStatus::Bogus
+        } else if !self.records.is_empty()
+            && self.records.iter().all(|r| r.proof() == Proof::Secure)
+        {
+            DnssecStatus::AuthenticData
+        } else {
+            DnssecStatus::Insecure
+        }
+    }
+
     /// Returns the `Instant` at which this `Lookup` is no longer valid.
     pub fn valid_until(&self) -> Instant {
         self.valid_until
@@ -131,6 +217,7 @@ impl Lookup {
         // Choose the sooner deadline of the two lookups.
         let valid_until = min(self.valid_until(), other.valid_until());
         Self::new_with_deadline(self.query.clone(), Arc::from(records), valid_until)
+            .with_metadata(self.metadata)
     }
 
     /// Add new records to this lookup, without creating a new Lookup
@@ -522,6 +609,7 @@ mod tests {
             query: Query::default(),
             records: Arc::from([a1.clone(), a2.clone()]),
             valid_until: Instant::now(),
+            metadata: LookupMetadata::default(),
         };
 
         let mut lookup = lookup.dnssec_iter();
@@ -9,14 +9,16 @@
 //!
 //! At it's heart LookupIp uses Lookup for performing all lookups. It is unlike other standard lookups in that there are customizations around A and AAAA resolutions.
 
+use std::collections::{HashMap, VecDeque};
 use std::future::Future;
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 use std::time::Instant;
 
 use futures_util::{future, future::Either, FutureExt};
+use rand::seq::SliceRandom;
 use tracing::debug;
 
 use crate::proto::op::Query;
@@ -24,7 +26,7 @@ use crate::proto::rr::{Name, RData, Record, RecordType};
 use crate::proto::xfer::{DnsHandle, DnsRequestOptions};
 
 use crate::caching_client::CachingClient;
-use crate::config::LookupIpStrategy;
+use crate::config::{LookupIpStrategy, RotationStrategy};
 use crate::dns_lru::MAX_TTL;
 use crate::error::*;
 use crate::hosts::Hosts;
@@ -34,46 +36,77 @@ use crate::lookup::{Lookup, LookupIntoIter, LookupIter};
 ///
 /// When resolving IP records, there can be many IPs that match a given name. A consumer of this should expect that there are more than a single address potentially returned. Generally there are multiple IPs stored for a given service in DNS so that there is a form of high availability offered for a given name. The service implementation is responsible for the semantics around which IP should be used and when, but in general if a connection fails to one, the next in the list should be attempted.
 #[derive(Debug, Clone)]
-pub struct LookupIp(Lookup);
+pub struct LookupIp {
+    lookup: Lookup,
+    /// The scope id of the link-local IPv6 addresses, `0` if none
+    scope_id: u32,
+}
 
 impl LookupIp {
     /// Returns an iterator over the response records.
     ///
     /// Only IP records will be returned, either A or AAAA record types.
     pub fn iter(&self) -> LookupIpIter<'_> {
-        LookupIpIter(self.0.iter())
+        LookupIpIter(self.lookup.iter())
+    }
+
+    /// Returns an iterator over the socket addresses of the IPs with the `port`
+    ///
+    /// Link-local IPv6 addresses are only usable within their scope, they carry the scope id of
+    /// the `ipv6_scope_id` option, see [`crate::config::ResolverOpts`].
+    pub fn socket_addrs(&self, port: u16) -> impl Iterator<Item = SocketAddr> + '_ {
+        self.iter().map(move |ip| match ip {
+            IpAddr::V6(ip) if is_unicast_link_local(&ip) => {
+                SocketAddr::V6(SocketAddrV6::new(ip, port, 0, self.scope_id))
+            }
+            ip => SocketAddr::new(ip, port),
+        })
     }
 
     /// Returns a reference to the `Query` that was used to produce this result.
     pub fn query(&self) -> &Query {
-        self.0.query()
+        self.lookup.query()
     }
 
     /// Returns the `Instant` at which this lookup is no longer valid.
     pub fn valid_until(&self) -> Instant {
-        self.0.valid_until()
+        self.lookup.valid_until()
     }
 
     /// Return a reference to the inner lookup
     ///
     /// This can be useful for getting all records from the request
     pub fn as_lookup(&self) -> &Lookup {
-        &self.0
+        &self.lookup
+    }
+
+    /// Sets the scope id of the link-local IPv6 addresses
+    pub(crate) fn with_scope_id(mut self, scope_id: Option<u32>) -> Self {
+        self.scope_id = scope_id.unwrap_or_default();
+        self
     }
 }
 
 impl From<Lookup> for LookupIp {
     fn from(lookup: Lookup) -> Self {
-        Self(lookup)
+        Self {
+            lookup,
+            scope_id: 0,
+        }
     }
 }
 
 impl From<LookupIp> for Lookup {
     fn from(lookup: LookupIp) -> Self {
-        lookup.0
+        lookup.lookup
     }
 }
 
+/// Whether the address is in `fe80::/10`, the link-local unicast addresses
+fn is_unicast_link_local(ip: &Ipv6Addr) -> bool {
+    ip.segments()[0] & 0xffc0 == 0xfe80
+}
+
 /// Borrowed view of set of IPs returned from a LookupIp
 pub struct LookupIpIter<'i>(pub(crate) LookupIter<'i>);
 
@@ -96,7 +129,7 @@ impl IntoIterator for LookupIp {
 
     /// This is not a free conversion, because the `RData`s are cloned.
     fn into_iter(self) -> Self::IntoIter {
-        LookupIpIntoIter(self.0.into_iter())
+        LookupIpIntoIter(self.lookup.into_iter())
     }
 }
 
@@ -116,6 +149,105 @@ impl Iterator for LookupIpIntoIter {
     }
 }
 
+/// Varies the order of the addresses of repeated lookups of the same name
+///
+/// The records of a single lookup keep their order, only the positions of the A and AAAA
+/// records among them are changed.
+pub(crate) struct AddressRotation {
+    strategy: RotationStrategy,
+    /// The number of lookups of each name, used as the offset of the round-robin rotation
+    counters: Mutex<HashMap<Name, usize>>,
+    max_names: usize,
+}
+
+impl AddressRotation {
+    pub(crate) fn new(strategy: RotationStrategy, max_names: usize) -> Self {
+        Self {
+            strategy,
+            counters: Mutex::new(HashMap::new()),
+            max_names: max_names.max(1),
+        }
+    }
+
+    /// Returns the lookup with its addresses in the order for this lookup of the name
+    pub(crate) fn rotate(&self, lookup: LookupIp) -> LookupIp {
+        let name = lookup.query().name().clone();
+        reorder_addresses(lookup, |addresses| match self.strategy {
+            RotationStrategy::RoundRobin => {
+                let offset = self.next_offset(&name) % addresses.len();
+                addresses.rotate_left(offset);
+            }
+            RotationStrategy::Shuffle => addresses.shuffle(&mut rand::thread_rng()),
+        })
+    }
+
+    fn next_offset(&self, name: &Name) -> usize {
+        let mut counters = self.counters.lock().expect("AddressRotation lock poisoned");
+        if counters.len() >= self.max_names && !counters.contains_key(name) {
+            counters.clear();
+        }
+
+        let counter = counters.entry(name.clone()).or_default();
+        let offset = *counter;
+        *counter = counter.wrapping_add(1);
+        offset
+    }
+}
+
+/// Orders the addresses for connection attempts, per [RFC 8305, section 4](https://www.rfc-editor.org/rfc/rfc8305#section-4)
+///
+/// The address families are interleaved, starting with IPv6, the addresses of each family keep
+/// their order. Once the addresses of one family are exhausted, the remaining ones follow.
+pub(crate) fn happy_eyeballs_order(lookup: LookupIp) -> LookupIp {
+    reorder_addresses(lookup, |addresses| {
+        let (mut ipv6, mut ipv4): (VecDeque<_>, VecDeque<_>) = addresses
+            .drain(..)
+            .partition(|record| matches!(record.data(), RData::AAAA(_)));
+
+        while !ipv6.is_empty() || !ipv4.is_empty() {
+            addresses.extend(ipv6.pop_front());
+            addresses.extend(ipv4.pop_front());
+        }
+    })
+}
+
+/// Reorders the A and AAAA records of the lookup with `reorder`
+///
+/// The other records keep their positions, `reorder` is only called with two or more addresses.
+fn reorder_addresses(lookup: LookupIp, reorder: impl FnOnce(&mut Vec<Record>)) -> LookupIp {
+    let records = lookup.as_lookup().records();
+    let positions = records
+        .iter()
+        .enumerate()
+        .filter(|(_, record)| matches!(record.data(), RData::A(_) | RData::AAAA(_)))
+        .map(|(idx, _)| idx)
+        .collect::<Vec<_>>();
+    if positions.len() < 2 {
+        return lookup;
+    }
+
+    let mut addresses = positions
+        .iter()
+        .map(|&idx| records[idx].clone())
+        .collect::<Vec<_>>();
+    reorder(&mut addresses);
+
+    let mut reordered = records.to_vec();
+    for (idx, address) in positions.into_iter().zip(addresses) {
+        reordered[idx] = address;
+    }
+
+    LookupIp {
+        lookup: Lookup::new_with_deadline(
+            lookup.query().clone(),
+            Arc::from(reordered),
+            lookup.valid_until(),
+        )
+        .with_metadata(lookup.as_lookup().metadata()),
+        scope_id: lookup.scope_id,
+    }
+}
+
 /// The Future returned from [crate::Resolver] when performing an A or AAAA lookup.
 ///
 /// This type isn't necessarily something that should be used by users, see the default TypeParameters are generally correct
@@ -126,6 +258,7 @@ where
     client_cache: CachingClient<C>,
     names: Vec<Name>,
     strategy: LookupIpStrategy,
+    single_request: bool,
     options: DnsRequestOptions,
     query: Pin<Box<dyn Future<Output = Result<Lookup, ResolveError>> + Send>>,
     hosts: Option<Arc<Hosts>>,
@@ -162,6 +295,7 @@ where
                     self.query = strategic_lookup(
                         name,
                         self.strategy,
+                        self.single_request,
                         self.client_cache.clone(),
                         self.options,
                         self.hosts.clone(),
@@ -215,6 +349,7 @@ where
         Self {
             names,
             strategy,
+            single_request: false,
             client_cache,
             // If there are no names remaining, this will be returned immediately,
             // otherwise, it will be retried.
@@ -224,12 +359,19 @@ where
             finally_ip_addr,
         }
     }
+
+    /// Sends the A and AAAA queries of [`LookupIpStrategy::Ipv4AndIpv6`] one after the other
+    pub(crate) fn with_single_request(mut self, single_request: bool) -> Self {
+        self.single_request = single_request;
+        self
+    }
 }
 
 /// returns a new future for lookup
 async fn strategic_lookup<C>(
     name: Name,
     strategy: LookupIpStrategy,
+    single_request: bool,
     client: CachingClient<C>,
     options: DnsRequestOptions,
     hosts: Option<Arc<Hosts>>,
@@ -240,6 +382,9 @@ where
     match strategy {
         LookupIpStrategy::Ipv4Only => ipv4_only(name, client, options, hosts).await,
         LookupIpStrategy::Ipv6Only => ipv6_only(name, client, options, hosts).await,
+        LookupIpStrategy::Ipv4AndIpv6 if single_request => {
+            ipv4_and_ipv6_serial(name, client, options, hosts).await
+        }
         LookupIpStrategy::Ipv4AndIpv6 => ipv4_and_ipv6(name, client, options, hosts).await,
         LookupIpStrategy::Ipv6thenIpv4 => ipv6_then_ipv4(name, client, options, hosts).await,
         LookupIpStrategy::Ipv4thenIpv6 => ipv4_then_ipv6(name, client, options, hosts).await,
@@ -321,6 +466,36 @@ where
 
     let next_ips = remaining_query.await;
 
+    merge_ipv4_and_ipv6(ips, next_ips)
+}
+
+/// queries for A and then for AAAA records, the second query is only sent once the first completed
+async fn ipv4_and_ipv6_serial<C>(
+    name: Name,
+    client: CachingClient<C>,
+    options: DnsRequestOptions,
+    hosts: Option<Arc<Hosts>>,
+) -> Result<Lookup, ResolveError>
+where
+    C: DnsHandle + 'static,
+{
+    let ipv4 = hosts_lookup(
+        Query::query(name.clone(), RecordType::A),
+        client.clone(),
+        options,
+        hosts.clone(),
+    )
+    .await;
+    let ipv6 = hosts_lookup(Query::query(name, RecordType::AAAA), client, options, hosts).await;
+
+    merge_ipv4_and_ipv6(ipv4, ipv6)
+}
+
+/// merges the results of the A and AAAA queries, failing only if both failed
+fn merge_ipv4_and_ipv6(
+    ips: Result<Lookup, ResolveError>,
+    next_ips: Result<Lookup, ResolveError>,
+) -> Result<Lookup, ResolveError> {
     match (ips, next_ips) {
         (Ok(ips), Ok(next_ips)) => {
             // TODO: create a LookupIp enum with the ability to chain these together
@@ -436,6 +611,7 @@ where
 
 #[cfg(test)]
 pub(crate) mod tests {
+    use std::collections::HashSet;
     use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
     use std::sync::{Arc, Mutex};
 
@@ -444,6 +620,7 @@ pub(crate) mod tests {
     use futures_util::stream::{once, Stream};
 
     use crate::proto::op::Message;
+    use crate::proto::rr::rdata::CNAME;
     use crate::proto::rr::{Name, RData, Record};
     use crate::proto::xfer::{DnsHandle, DnsRequest, DnsResponse};
     use crate::proto::ProtoError;
@@ -623,6 +800,42 @@ pub(crate) mod tests {
         );
     }
 
+    #[test]
+    fn test_ipv4_and_ipv6_serial_strategy() {
+        // the A query is sent first, then the AAAA query
+        assert_eq!(
+            block_on(ipv4_and_ipv6_serial(
+                Name::root(),
+                CachingClient::new(0, mock(vec![v6_message(), v4_message()]), false),
+                DnsRequestOptions::default(),
+                None,
+            ))
+            .unwrap()
+            .iter()
+            .map(|r| r.ip_addr().unwrap())
+            .collect::<Vec<IpAddr>>(),
+            vec![
+                IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)),
+            ]
+        );
+
+        // error, then only ipv6 available
+        assert_eq!(
+            block_on(ipv4_and_ipv6_serial(
+                Name::root(),
+                CachingClient::new(0, mock(vec![v6_message(), error()]), false),
+                DnsRequestOptions::default(),
+                None,
+            ))
+            .unwrap()
+            .iter()
+            .map(|r| r.ip_addr().unwrap())
+            .collect::<Vec<IpAddr>>(),
+            vec![IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1))]
+        );
+    }
+
     #[test]
     fn test_ipv6_then_ipv4_strategy() {
         // ipv6 first
@@ -718,4 +931,152 @@ pub(crate) mod tests {
             vec![Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)]
         );
     }
+
+    fn lookup_ip(addresses: &[Ipv4Addr]) -> LookupIp {
+        let name = Name::from_ascii("www.example.com.").unwrap();
+        let cname = Record::from_rdata(name.clone(), 86400, RData::CNAME(CNAME(name.clone())));
+        let records = std::iter::once(cname)
+            .chain(
+                addresses
+                    .iter()
+                    .map(|ip| Record::from_rdata(name.clone(), 86400, RData::A((*ip).into()))),
+            )
+            .collect::<Vec<_>>();
+
+        Lookup::new_with_max_ttl(Query::query(name, RecordType::A), Arc::from(records)).into()
+    }
+
+    #[test]
+    fn test_round_robin_rotation() {
+        let addresses = [
+            Ipv4Addr::new(192, 0, 2, 1),
+            Ipv4Addr::new(192, 0, 2, 2),
+            Ipv4Addr::new(192, 0, 2, 3),
+        ];
+        let rotation = AddressRotation::new(RotationStrategy::RoundRobin, 32);
+
+        let mut firsts = Vec::new();
+        for _ in 0..addresses.len() {
+            let rotated = rotation.rotate(lookup_ip(&addresses));
+            let ips = rotated.iter().collect::<Vec<_>>();
+
+            // a rotation of the server order, the other records stay in place
+            let start = addresses.iter().position(|ip| ips[0] == *ip).unwrap();
+            let expected = addresses
+                .iter()
+                .cycle()
+                .skip(start)
+                .take(addresses.len())
+                .map(|ip| IpAddr::V4(*ip))
+                .collect::<Vec<_>>();
+            assert_eq!(ips, expected);
+            assert_eq!(
+                rotated.as_lookup().records()[0].record_type(),
+                RecordType::CNAME
+            );
+
+            // stable within a single response
+            assert_eq!(rotated.iter().collect::<Vec<_>>(), ips);
+            firsts.push(ips[0]);
+        }
+
+        // cycles through all the addresses
+        assert_eq!(
+            firsts,
+            addresses
+                .iter()
+                .map(|ip| IpAddr::V4(*ip))
+                .collect::<Vec<_>>()
+        );
+        let again = rotation.rotate(lookup_ip(&addresses));
+        assert_eq!(again.iter().next(), Some(IpAddr::V4(addresses[0])));
+    }
+
+    #[test]
+    fn test_shuffle_rotation() {
+        let addresses = (1..=8)
+            .map(|idx| Ipv4Addr::new(192, 0, 2, idx))
+            .collect::<Vec<_>>();
+        let rotation = AddressRotation::new(RotationStrategy::Shuffle, 32);
+
+        let mut orders = HashSet::new();
+        for _ in 0..20 {
+            let mut ips = rotation
+                .rotate(lookup_ip(&addresses))
+                .iter()
+                .collect::<Vec<_>>();
+            orders.insert(ips.clone());
+
+            ips.sort();
+            let expected = addresses
+                .iter()
+                .map(|ip| IpAddr::V4(*ip))
+                .collect::<Vec<_>>();
+            assert_eq!(ips, expected);
+        }
+        assert!(orders.len() > 1);
+    }
+
+    #[test]
+    fn test_happy_eyeballs_order() {
+        let name = Name::from_ascii("www.example.com.").unwrap();
+        let v4 = |idx| IpAddr::V4(Ipv4Addr::new(192, 0, 2, idx));
+        let v6 = |idx| IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, idx));
+        let answers = [v4(1), v4(2), v4(3), v6(1), v6(2)];
+
+        let records = answers
+            .iter()
+            .map(|ip| Record::from_rdata(name.clone(), 86400, RData::from(*ip)))
+            .collect::<Vec<_>>();
+        let lookup =
+            Lookup::new_with_max_ttl(Query::query(name, RecordType::A), Arc::from(records));
+
+        let ordered = happy_eyeballs_order(lookup.into());
+        assert_eq!(
+            ordered.iter().collect::<Vec<_>>(),
+            vec![v6(1), v4(1), v6(2), v4(2), v4(3)]
+        );
+
+        // the families alternate, starting with IPv6
+        let families = ordered
+            .iter()
+            .take(4)
+            .map(|ip| ip.is_ipv6())
+            .collect::<Vec<_>>();
+        assert_eq!(families, vec![true, false, true, false]);
+    }
+
+    #[test]
+    fn test_link_local_scope_id() {
+        let name = Name::from_ascii("www.example.com.").unwrap();
+        let link_local = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+        let global = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+        let v4 = Ipv4Addr::new(192, 0, 2, 1);
+
+        let records = [IpAddr::from(link_local), global.into(), v4.into()]
+            .iter()
+            .map(|ip| Record::from_rdata(name.clone(), 86400, RData::from(*ip)))
+            .collect::<Vec<_>>();
+        let lookup =
+            Lookup::new_with_max_ttl(Query::query(name, RecordType::AAAA), Arc::from(records));
+
+        // only the link-local address is paired with the scope id
+        let lookup = LookupIp::from(lookup).with_scope_id(Some(3));
+        assert_eq!(
+            lookup.socket_addrs(853).collect::<Vec<_>>(),
+            vec![
+                SocketAddr::V6(SocketAddrV6::new(link_local, 853, 0, 3)),
+                SocketAddr::V6(SocketAddrV6::new(global, 853, 0, 0)),
+                SocketAddr::new(v4.into(), 853),
+            ]
+        );
+
+        // the scope id is kept when the addresses are reordered
+        let ordered = happy_eyeballs_order(lookup.with_scope_id(Some(4)));
+        let scoped = ordered.socket_addrs(53).find_map(|addr| match addr {
+            SocketAddr::V6(addr) if *addr.ip() == link_local => Some(addr.scope_id()),
+            _ => None,
+        });
+        assert_eq!(scoped, Some(4));
+    }
 }
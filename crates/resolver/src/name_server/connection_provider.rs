@@ -23,6 +23,8 @@ use futures_util::ready;
 use futures_util::stream::{Stream, StreamExt};
 #[cfg(feature = "dns-over-rustls")]
 use tokio_rustls::client::TlsStream as TokioTlsStream;
+#[cfg(all(feature = "dns-over-h3", feature = "dns-over-https-rustls"))]
+use tracing::debug;
 
 use crate::config::{NameServerConfig, ResolverOpts};
 #[cfg(any(feature = "dns-over-h3", feature = "dns-over-https-rustls"))]
@@ -35,6 +37,8 @@ use crate::proto::h3::{H3ClientConnect, H3ClientStream};
 use crate::proto::quic::{QuicClientConnect, QuicClientStream};
 #[cfg(feature = "dns-over-rustls")]
 use crate::proto::runtime::iocompat::AsyncIoTokioAsStd;
+#[cfg(all(feature = "dns-over-h3", feature = "dns-over-https-rustls"))]
+use crate::proto::runtime::Time;
 #[cfg(feature = "tokio-runtime")]
 #[allow(unused_imports)] // Complicated cfg for which protocols are enabled
 use crate::proto::runtime::TokioTime;
@@ -65,6 +69,9 @@ pub trait ConnectionProvider: 'static + Clone + Send + Sync + Unpin {
         config: &NameServerConfig,
         options: &ResolverOpts,
     ) -> Result<Self::FutureConn, io::Error>;
+
+    /// Create a handle to the runtime, for spawning background tasks.
+    fn create_handle(&self) -> <Self::RuntimeProvider as RuntimeProvider>::Handle;
 }
 
 #[cfg(feature = "dns-over-rustls")]
@@ -112,6 +119,12 @@ pub(crate) enum ConnectionConnect<R: RuntimeProvider> {
     Quic(DnsExchangeConnect<QuicClientConnect, QuicClientStream, TokioTime>),
     #[cfg(all(feature = "dns-over-h3", feature = "tokio-runtime"))]
     H3(DnsExchangeConnect<H3ClientConnect, H3ClientStream, TokioTime>),
+    #[cfg(all(
+        feature = "dns-over-h3",
+        feature = "dns-over-https-rustls",
+        feature = "tokio-runtime"
+    ))]
+    H3FallbackH2(Pin<Box<dyn Future<Output = Result<GenericConnection, ProtoError>> + Send>>),
 }
 
 /// Resolves to a new Connection
@@ -160,6 +173,12 @@ impl<R: RuntimeProvider> Future for ConnectionFuture<R> {
                 self.spawner.spawn_bg(bg);
                 GenericConnection(conn)
             }
+            #[cfg(all(
+                feature = "dns-over-h3",
+                feature = "dns-over-https-rustls",
+                feature = "tokio-runtime"
+            ))]
+            ConnectionConnect::H3FallbackH2(conn) => ready!(conn.poll_unpin(cx))?,
         }))
     }
 }
@@ -241,7 +260,9 @@ impl<P: RuntimeProvider> ConnectionProvider for GenericConnector<P> {
                 let socket_addr = config.socket_addr;
                 let timeout = options.timeout;
                 let tls_dns_name = config.tls_dns_name.clone().unwrap_or_default();
-                let tcp_future = self.runtime_provider.connect_tcp(socket_addr, None, None);
+                let tcp_future =
+                    self.runtime_provider
+                        .connect_tcp(socket_addr, config.bind_addr, None);
 
                 #[cfg(feature = "dns-over-rustls")]
                 let client_config = config.tls_config.clone();
@@ -270,26 +291,7 @@ impl<P: RuntimeProvider> ConnectionProvider for GenericConnector<P> {
                 ConnectionConnect::Tls(exchange)
             }
             #[cfg(feature = "dns-over-https-rustls")]
-            (Protocol::Https, _) => {
-                let socket_addr = config.socket_addr;
-                let tls_dns_name = config.tls_dns_name.clone().unwrap_or_default();
-                let http_endpoint = config
-                    .http_endpoint
-                    .clone()
-                    .unwrap_or_else(|| proto::http::DEFAULT_DNS_QUERY_PATH.to_owned());
-                #[cfg(feature = "dns-over-rustls")]
-                let client_config = config.tls_config.clone();
-                let tcp_future = self.runtime_provider.connect_tcp(socket_addr, None, None);
-
-                let exchange = crate::h2::new_https_stream_with_future(
-                    tcp_future,
-                    socket_addr,
-                    tls_dns_name,
-                    http_endpoint,
-                    client_config,
-                );
-                ConnectionConnect::Https(exchange)
-            }
+            (Protocol::Https, _) => self.https_connect(config),
             #[cfg(feature = "dns-over-quic")]
             (Protocol::Quic, Some(binder)) => {
                 let socket_addr = config.socket_addr;
@@ -332,6 +334,12 @@ impl<P: RuntimeProvider> ConnectionProvider for GenericConnector<P> {
                     http_endpoint,
                     client_config,
                 );
+
+                #[cfg(all(feature = "dns-over-https-rustls", feature = "tokio-runtime"))]
+                if options.prefer_h3_fallback_h2 {
+                    return Ok(self.h3_fallback_h2_connect(exchange, config, options));
+                }
+
                 ConnectionConnect::H3(exchange)
             }
             (protocol, _) => {
@@ -347,6 +355,77 @@ impl<P: RuntimeProvider> ConnectionProvider for GenericConnector<P> {
             spawner: self.runtime_provider.create_handle(),
         })
     }
+
+    fn create_handle(&self) -> P::Handle {
+        self.runtime_provider.create_handle()
+    }
+}
+
+impl<P: RuntimeProvider> GenericConnector<P> {
+    #[cfg(feature = "dns-over-https-rustls")]
+    fn https_connect(&self, config: &NameServerConfig) -> ConnectionConnect<P> {
+        let socket_addr = config.socket_addr;
+        let tls_dns_name = config.tls_dns_name.clone().unwrap_or_default();
+        let http_endpoint = config
+            .http_endpoint
+            .clone()
+            .unwrap_or_else(|| proto::http::DEFAULT_DNS_QUERY_PATH.to_owned());
+        let client_config = config.tls_config.clone();
+        let tcp_future = self
+            .runtime_provider
+            .connect_tcp(socket_addr, config.bind_addr, None);
+
+        let exchange = crate::h2::new_https_stream_with_future(
+            tcp_future,
+            socket_addr,
+            tls_dns_name,
+            http_endpoint,
+            client_config,
+        );
+        ConnectionConnect::Https(exchange)
+    }
+
+    /// Connects over HTTP/3, or over HTTP/2 if the QUIC handshake does not succeed within the timeout
+    #[cfg(all(
+        feature = "dns-over-h3",
+        feature = "dns-over-https-rustls",
+        feature = "tokio-runtime"
+    ))]
+    fn h3_fallback_h2_connect(
+        &self,
+        h3: DnsExchangeConnect<H3ClientConnect, H3ClientStream, TokioTime>,
+        config: &NameServerConfig,
+        options: &ResolverOpts,
+    ) -> ConnectionFuture<P> {
+        let spawner = self.runtime_provider.create_handle();
+        let h3 = ConnectionFuture::<P> {
+            connect: ConnectionConnect::H3(h3),
+            spawner: spawner.clone(),
+        };
+        let h2 = ConnectionFuture::<P> {
+            connect: self.https_connect(config),
+            spawner: spawner.clone(),
+        };
+
+        let name_server = config.socket_addr;
+        let timeout = options.timeout;
+        let connect = async move {
+            // the background task is only spawned for an established connection, dropping the
+            // pending h3 connection releases its socket
+            match P::Timer::timeout(timeout, h3).await {
+                Ok(Ok(conn)) => return Ok(conn),
+                Ok(Err(e)) => debug!("h3 connection to {name_server} failed, using h2: {e}"),
+                Err(e) => debug!("h3 connection to {name_server} failed, using h2: {e}"),
+            }
+
+            h2.await
+        };
+
+        ConnectionFuture {
+            connect: ConnectionConnect::H3FallbackH2(Box::pin(connect)),
+            spawner,
+        }
+    }
 }
 
 /// A stream of response to a DNS request.
@@ -360,3 +439,83 @@ impl Stream for ConnectionResponse {
         Poll::Ready(ready!(self.0.poll_next_unpin(cx)))
     }
 }
+
+#[cfg(test)]
+#[cfg(all(feature = "tokio-runtime", target_os = "linux"))]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, UdpSocket};
+
+    use super::*;
+    use crate::proto::op::{Message, MessageType, Query};
+    use crate::proto::rr::{Name, RecordType};
+    use crate::proto::xfer::{DnsRequestOptions, FirstAnswer};
+
+    /// A loopback alias, all of 127.0.0.0/8 is routed to the loopback interface on Linux
+    const SOURCE: Ipv4Addr = Ipv4Addr::new(127, 0, 0, 2);
+
+    fn config(server: SocketAddr, protocol: Protocol) -> NameServerConfig {
+        let mut config = NameServerConfig::new(server, protocol);
+        config.bind_addr = Some(SocketAddr::from((SOURCE, 0)));
+        config
+    }
+
+    fn response(query: &[u8]) -> Vec<u8> {
+        let mut message = Message::from_vec(query).unwrap();
+        message.set_message_type(MessageType::Response);
+        message.to_vec().unwrap()
+    }
+
+    async fn query(config: NameServerConfig) -> Result<DnsResponse, ProtoError> {
+        let connector = GenericConnector::new(TokioRuntimeProvider::default());
+        let conn = connector
+            .new_connection(&config, &ResolverOpts::default())
+            .unwrap()
+            .await?;
+
+        let name = Name::from_ascii("www.example.com.").unwrap();
+        conn.lookup(
+            Query::query(name, RecordType::A),
+            DnsRequestOptions::default(),
+        )
+        .first_answer()
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_udp_bind_addr() {
+        let server = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let server_addr = server.local_addr().unwrap();
+        let source = tokio::spawn(async move {
+            let mut buf = [0; 512];
+            let (len, src) = server.recv_from(&mut buf).await.unwrap();
+            server.send_to(&response(&buf[..len]), src).await.unwrap();
+            src
+        });
+
+        query(config(server_addr, Protocol::Udp)).await.unwrap();
+        assert_eq!(source.await.unwrap().ip(), SOURCE);
+    }
+
+    #[tokio::test]
+    async fn test_tcp_bind_addr() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let server_addr = listener.local_addr().unwrap();
+        let source = tokio::spawn(async move {
+            let (mut stream, src) = listener.accept().await.unwrap();
+            let len = stream.read_u16().await.unwrap();
+            let mut buf = vec![0; usize::from(len)];
+            stream.read_exact(&mut buf).await.unwrap();
+
+            let response = response(&buf);
+            stream.write_u16(response.len() as u16).await.unwrap();
+            stream.write_all(&response).await.unwrap();
+            src
+        });
+
+        query(config(server_addr, Protocol::Tcp)).await.unwrap();
+        assert_eq!(source.await.unwrap().ip(), SOURCE);
+    }
+}
@@ -0,0 +1,337 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Discovery of Designated Resolvers, [RFC 9462](https://tools.ietf.org/html/rfc9462)
+
+use std::net::{IpAddr, SocketAddr};
+
+use tracing::debug;
+
+use crate::config::NameServerConfig;
+use crate::proto::{
+    op::Query,
+    rr::{
+        rdata::svcb::{SvcParamKey, SvcParamValue, SVCB},
+        Name, RData, RecordType,
+    },
+    xfer::{DnsResponse, Protocol},
+};
+
+/// The name queried for the resolvers designated by a name server, RFC 9462 section 4
+const RESOLVER_ARPA: &str = "_dns.resolver.arpa.";
+
+/// The SvcParamKey of the URI template of DNS over HTTPS, RFC 9461 section 5
+const DOHPATH: u16 = 7;
+
+/// The ALPN identifiers of the encrypted protocols, with their default ports, RFC 9461 section 4
+const ALPNS: &[(&str, Protocol, u16)] = &[
+    #[cfg(feature = "dns-over-rustls")]
+    ("dot", Protocol::Tls, 853),
+    #[cfg(feature = "dns-over-https-rustls")]
+    ("h2", Protocol::Https, 443),
+    #[cfg(feature = "dns-over-h3")]
+    ("h3", Protocol::H3, 443),
+    #[cfg(feature = "dns-over-quic")]
+    ("doq", Protocol::Quic, 853),
+];
+
+/// The outcome of the discovery of the resolver designated by a name server
+#[derive(Clone, Debug)]
+pub(crate) enum Designation {
+    /// The discovery was not attempted yet
+    Unknown,
+    /// The name server designates this encrypted resolver
+    Encrypted(NameServerConfig),
+    /// The name server does not designate a usable encrypted resolver
+    Unavailable,
+}
+
+/// The SVCB query for the resolvers designated by a name server
+pub(crate) fn query() -> Query {
+    let name = Name::from_ascii(RESOLVER_ARPA).expect("valid name");
+    Query::query(name, RecordType::SVCB)
+}
+
+/// Returns the configurations of the encrypted resolvers designated in the response, by priority
+///
+/// Each configuration is verified when connecting, as its TLS name is the IP address of the name
+/// server `config`: the certificate of the designated resolver must be valid for this address,
+/// RFC 9462 section 4.2. Designations of protocols which are not enabled, with unknown mandatory
+/// keys, or without address hints, are ignored.
+pub(crate) fn designated_resolvers(
+    config: &NameServerConfig,
+    response: &DnsResponse,
+) -> Vec<NameServerConfig> {
+    let mut services = response
+        .answers()
+        .iter()
+        .filter_map(|record| match record.data() {
+            RData::SVCB(svcb) => Some(svcb),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    // the ServiceMode records are ignored when there is an AliasMode record, RFC 9460 section 2.4.1
+    if services.iter().any(|svcb| svcb.svc_priority() == 0) {
+        debug!("ignoring AliasMode designation of {}", config.socket_addr);
+        return Vec::new();
+    }
+    services.sort_by_key(|svcb| svcb.svc_priority());
+
+    services
+        .into_iter()
+        .flat_map(|svcb| designations(config, svcb))
+        .collect()
+}
+
+/// The configurations of the endpoints of a ServiceMode record
+fn designations(config: &NameServerConfig, svcb: &SVCB) -> Vec<NameServerConfig> {
+    let mut alpns = Vec::new();
+    let mut port = None;
+    let mut addrs = Vec::<IpAddr>::new();
+    let mut dohpath = None;
+    for (key, value) in svcb.svc_params() {
+        match value {
+            SvcParamValue::Mandatory(mandatory) => {
+                if let Some(key) = mandatory.0.iter().find(|key| !is_supported(**key)) {
+                    debug!("ignoring designation with unsupported mandatory key {key}");
+                    return Vec::new();
+                }
+            }
+            SvcParamValue::Alpn(alpn) => alpns.extend(alpn.0.iter().map(String::as_str)),
+            SvcParamValue::Port(value) => port = Some(*value),
+            SvcParamValue::Ipv4Hint(hint) => addrs.extend(hint.0.iter().map(|&a| IpAddr::from(a))),
+            SvcParamValue::Ipv6Hint(hint) => addrs.extend(hint.0.iter().map(|&a| IpAddr::from(a))),
+            SvcParamValue::Unknown(value) if *key == SvcParamKey::from(DOHPATH) => {
+                dohpath = std::str::from_utf8(&value.0).ok();
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(bind_addr) = config.bind_addr {
+        addrs.retain(|addr| addr.is_ipv4() == bind_addr.is_ipv4());
+    }
+    if addrs.is_empty() {
+        debug!(
+            "ignoring designation of {} without address hints",
+            svcb.target_name()
+        );
+        return Vec::new();
+    }
+
+    let mut configs = Vec::new();
+    for alpn in alpns {
+        let Some(&(_, protocol, default_port)) = ALPNS.iter().find(|(id, ..)| *id == alpn) else {
+            continue;
+        };
+
+        // the path of DNS over HTTPS is the URI template without its `dns` variable
+        let http_endpoint = if matches!(alpn, "h2" | "h3") {
+            match dohpath.and_then(|template| template.split('{').next()) {
+                Some(path) if path.starts_with('/') => Some(path.to_owned()),
+                _ => {
+                    debug!("ignoring {alpn} designation without dohpath");
+                    continue;
+                }
+            }
+        } else {
+            None
+        };
+
+        for addr in &addrs {
+            let mut designated = config.clone();
+            designated.socket_addr = SocketAddr::new(*addr, port.unwrap_or(default_port));
+            designated.protocol = protocol;
+            designated.tls_dns_name = Some(config.socket_addr.ip().to_string());
+            designated.http_endpoint = http_endpoint.clone();
+            configs.push(designated);
+        }
+    }
+
+    configs
+}
+
+/// Returns true for the keys which are understood by the discovery
+fn is_supported(key: SvcParamKey) -> bool {
+    matches!(
+        key,
+        SvcParamKey::Mandatory
+            | SvcParamKey::Alpn
+            | SvcParamKey::NoDefaultAlpn
+            | SvcParamKey::Port
+            | SvcParamKey::Ipv4Hint
+            | SvcParamKey::Ipv6Hint
+    ) || key == SvcParamKey::from(DOHPATH)
+}
+
+#[cfg(test)]
+#[cfg(feature = "dns-over-rustls")]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+    use crate::proto::op::Message;
+    use crate::proto::rr::rdata::svcb::{SvcbBuilder, Unknown};
+    use crate::proto::rr::Record;
+
+    fn config() -> NameServerConfig {
+        NameServerConfig::new(SocketAddr::from(([192, 0, 2, 1], 53)), Protocol::Udp)
+    }
+
+    fn response(services: Vec<SVCB>) -> DnsResponse {
+        let mut message = Message::new();
+        for svcb in services {
+            message.add_answer(Record::from_rdata(
+                query().name().clone(),
+                300,
+                RData::SVCB(svcb),
+            ));
+        }
+        DnsResponse::from_message(message).unwrap()
+    }
+
+    fn service(priority: u16) -> SvcbBuilder {
+        SVCB::builder()
+            .priority(priority)
+            .target_name(Name::from_ascii("dns.example.net.").unwrap())
+    }
+
+    #[test]
+    fn test_designated_resolvers() {
+        let services = vec![
+            // without address hints
+            service(1).alpn(&[b"dot"]).build(),
+            service(3)
+                .alpn(&[b"dot"])
+                .ipv4_hint(&[Ipv4Addr::new(192, 0, 2, 54)])
+                .build(),
+            service(2)
+                .alpn(&[b"unknown", b"dot"])
+                .port(8853)
+                .ipv4_hint(&[Ipv4Addr::new(192, 0, 2, 53)])
+                .ipv6_hint(&[Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x53)])
+                .build(),
+        ];
+        let services = services.into_iter().map(|svcb| svcb.unwrap()).collect();
+
+        let configs = designated_resolvers(&config(), &response(services));
+        let endpoints = configs
+            .iter()
+            .map(|config| (config.socket_addr, config.protocol))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            endpoints,
+            [
+                ("192.0.2.53:8853".parse().unwrap(), Protocol::Tls),
+                ("[2001:db8::53]:8853".parse().unwrap(), Protocol::Tls),
+                ("192.0.2.54:853".parse().unwrap(), Protocol::Tls),
+            ]
+        );
+
+        // the certificates must be valid for the address of the unencrypted name server
+        for config in configs {
+            assert_eq!(config.tls_dns_name.as_deref(), Some("192.0.2.1"));
+        }
+    }
+
+    #[test]
+    fn test_unusable_designations() {
+        let hint = [Ipv4Addr::new(192, 0, 2, 53)];
+
+        // an AliasMode record takes precedence over the ServiceMode records
+        let alias = SVCB::new(0, Name::from_ascii("dns.example.net.").unwrap(), vec![]);
+        let dot = service(1).alpn(&[b"dot"]).ipv4_hint(&hint).build().unwrap();
+        assert!(designated_resolvers(&config(), &response(vec![alias, dot])).is_empty());
+
+        // the record has a mandatory key which is not understood
+        let svcb = service(1)
+            .mandatory(&[SvcParamKey::EchConfigList])
+            .alpn(&[b"dot"])
+            .ipv4_hint(&hint)
+            .ech_config_list(vec![0; 4])
+            .build()
+            .unwrap();
+        assert!(designated_resolvers(&config(), &response(vec![svcb])).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "dns-over-https-rustls")]
+    fn test_dohpath() {
+        let hint = [Ipv4Addr::new(192, 0, 2, 53)];
+        let without_path = service(1).alpn(&[b"h2"]).ipv4_hint(&hint).build().unwrap();
+        assert!(designated_resolvers(&config(), &response(vec![without_path])).is_empty());
+
+        let mut params = service(1)
+            .alpn(&[b"h2"])
+            .ipv4_hint(&hint)
+            .build()
+            .unwrap()
+            .svc_params()
+            .to_vec();
+        params.push((
+            SvcParamKey::from(DOHPATH),
+            SvcParamValue::Unknown(Unknown(b"/dns-query{?dns}".to_vec())),
+        ));
+        let svcb = SVCB::new(1, Name::from_ascii("dns.example.net.").unwrap(), params);
+
+        let configs = designated_resolvers(&config(), &response(vec![svcb]));
+        assert_eq!(configs.len(), 1);
+        assert_eq!(configs[0].protocol, Protocol::Https);
+        assert_eq!(configs[0].socket_addr, "192.0.2.53:443".parse().unwrap());
+        assert_eq!(configs[0].http_endpoint.as_deref(), Some("/dns-query"));
+    }
+
+    #[tokio::test]
+    async fn test_upgrade() {
+        use crate::config::{LookupIpStrategy, ResolverOpts};
+        use crate::proto::rr::rdata::A;
+        use crate::test_transport::TestTransport;
+        use crate::Resolver;
+
+        let name = Name::from_ascii("www.example.com.").unwrap();
+        let transport = TestTransport::new();
+        let dot = service(1)
+            .alpn(&[b"dot"])
+            .ipv4_hint(&[Ipv4Addr::new(192, 0, 2, 53)])
+            .build()
+            .unwrap();
+        transport.answer_records(
+            query(),
+            vec![Record::from_rdata(
+                query().name().clone(),
+                300,
+                RData::SVCB(dot),
+            )],
+        );
+        let a = Query::query(name.clone(), RecordType::A);
+        transport.answer_records(
+            a.clone(),
+            vec![Record::from_rdata(
+                name.clone(),
+                300,
+                RData::A(A::new(192, 0, 2, 1)),
+            )],
+        );
+
+        let options = ResolverOpts {
+            ip_strategy: LookupIpStrategy::Ipv4Only,
+            discover_designated_resolvers: true,
+            ..ResolverOpts::default()
+        };
+        let resolver = Resolver::new(TestTransport::config(), options, transport.clone());
+        let (_, metadata) = resolver.lookup_ip_with_metadata(name).await.unwrap();
+
+        // the discovery is sent to the name server, and the lookup to its designated resolver
+        assert_eq!(
+            transport.queries(),
+            [(Protocol::Udp, query()), (Protocol::Tls, a)]
+        );
+        assert_eq!(metadata.server, Some("192.0.2.53:853".parse().unwrap()));
+        assert_eq!(metadata.protocol, Some(Protocol::Tls));
+    }
+}
@@ -8,17 +8,23 @@
 //! A module with associated items for working with nameservers
 
 mod connection_provider;
+mod designated_resolver;
 #[allow(clippy::module_inception)]
 mod name_server;
+mod name_server_cookie;
 mod name_server_pool;
 mod name_server_state;
 mod name_server_stats;
+mod query_case;
 
 pub use self::connection_provider::{ConnectionProvider, GenericConnection, GenericConnector};
 pub use self::name_server::{GenericNameServer, NameServer};
+use self::name_server_cookie::NameServerCookie;
 pub use self::name_server_pool::{GenericNameServerPool, NameServerPool};
 use self::name_server_state::NameServerState;
 use self::name_server_stats::NameServerStats;
+pub use self::name_server_stats::NameServerStatsSnapshot;
+use self::query_case::QueryCase;
 
 #[cfg(feature = "tokio-runtime")]
 pub use self::connection_provider::TokioConnectionProvider;
@@ -6,19 +6,27 @@
 // copied, modified, or distributed except according to those terms.
 
 use std::fmt::{self, Debug, Formatter};
+use std::net::SocketAddr;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::time::Instant;
 
 use futures_util::lock::Mutex;
 use futures_util::stream::{once, Stream};
+use ipnet::IpNet;
+use parking_lot::Mutex as SyncMutex;
 use tracing::debug;
 
 use crate::config::{NameServerConfig, ResolverOpts};
 use crate::name_server::connection_provider::{ConnectionProvider, GenericConnector};
-use crate::name_server::{NameServerState, NameServerStats};
+use crate::name_server::designated_resolver::{self, Designation};
+use crate::name_server::{
+    NameServerCookie, NameServerState, NameServerStats, NameServerStatsSnapshot, QueryCase,
+};
 use crate::proto::{
-    xfer::{DnsHandle, DnsRequest, DnsResponse, FirstAnswer},
+    op::{update_message::MAX_PAYLOAD_LEN, Edns, Message},
+    rr::rdata::opt::{ClientSubnet, EdnsCode, EdnsOption},
+    xfer::{DnsHandle, DnsRequest, DnsRequestOptions, DnsResponse, FirstAnswer, Protocol},
     ProtoError,
 };
 
@@ -30,6 +38,8 @@ pub struct NameServer<P: ConnectionProvider> {
     client: Arc<Mutex<Option<P::Conn>>>,
     state: Arc<NameServerState>,
     pub(crate) stats: Arc<NameServerStats>,
+    cookie: Option<Arc<NameServerCookie>>,
+    designation: Option<Arc<SyncMutex<Designation>>>,
     connection_provider: P,
 }
 
@@ -51,12 +61,20 @@ where
 {
     /// Construct a new Nameserver with the configuration and options. The connection provider will create UDP and TCP sockets
     pub fn new(config: NameServerConfig, options: ResolverOpts, connection_provider: P) -> Self {
+        let cookie = options
+            .edns_cookies
+            .then(|| Arc::new(NameServerCookie::new()));
+        let designation = (options.discover_designated_resolvers
+            && !config.protocol.is_encrypted())
+        .then(|| Arc::new(SyncMutex::new(Designation::Unknown)));
         Self {
             config,
             options,
             client: Arc::new(Mutex::new(None)),
             state: Arc::new(NameServerState::init(None)),
             stats: Arc::new(NameServerStats::default()),
+            cookie,
+            designation,
             connection_provider,
         }
     }
@@ -68,12 +86,17 @@ where
         client: P::Conn,
         connection_provider: P,
     ) -> Self {
+        let cookie = options
+            .edns_cookies
+            .then(|| Arc::new(NameServerCookie::new()));
         Self {
             config,
             options,
             client: Arc::new(Mutex::new(Some(client))),
             state: Arc::new(NameServerState::init(None)),
             stats: Arc::new(NameServerStats::default()),
+            cookie,
+            designation: None,
             connection_provider,
         }
     }
@@ -103,11 +126,7 @@ where
             // TODO: we need the local EDNS options
             self.state.reinit(None);
 
-            let new_client = Box::pin(
-                self.connection_provider
-                    .new_connection(&self.config, &self.options)?,
-            )
-            .await?;
+            let new_client = self.connect().await?;
 
             // establish a new connection
             *client = Some(new_client);
@@ -120,19 +139,132 @@ where
             .expect("bad state, client should be connected"))
     }
 
+    /// Connects to the name server, or to the encrypted resolver it designates, RFC 9462
+    ///
+    /// The designated resolver is discovered with the first connection. If it cannot be reached
+    /// later on, the name server is used again, without a new discovery.
+    async fn connect(&self) -> Result<P::Conn, ProtoError> {
+        let Some(designation) = &self.designation else {
+            return self.connect_to(&self.config).await;
+        };
+
+        let current = designation.lock().clone();
+        match current {
+            Designation::Unknown => {
+                let conn = self.connect_to(&self.config).await?;
+                let (designated, conn) = self.discover(conn).await;
+                *designation.lock() = designated;
+                Ok(conn)
+            }
+            Designation::Encrypted(config) => match self.connect_to(&config).await {
+                Ok(conn) => Ok(conn),
+                Err(error) => {
+                    debug!(config = ?config, "designated resolver unreachable: {error}");
+                    *designation.lock() = Designation::Unavailable;
+                    self.connect_to(&self.config).await
+                }
+            },
+            Designation::Unavailable => self.connect_to(&self.config).await,
+        }
+    }
+
+    /// Queries the name server `conn` for its designated resolvers, and connects to the first one
+    /// which can be verified
+    ///
+    /// Returns the unencrypted connection if no designated resolver can be used.
+    async fn discover(&self, conn: P::Conn) -> (Designation, P::Conn) {
+        let mut message = Message::new();
+        message.add_query(designated_resolver::query());
+        let request = DnsRequest::new(message, DnsRequestOptions::default());
+        let configs = match conn.send(request).first_answer().await {
+            Ok(response) => designated_resolver::designated_resolvers(&self.config, &response),
+            Err(error) => {
+                debug!(config = ?self.config, "designated resolver discovery failed: {error}");
+                Vec::new()
+            }
+        };
+
+        for config in configs {
+            match self.connect_to(&config).await {
+                Ok(encrypted) => {
+                    debug!(
+                        "upgrading {} to the designated resolver {} over {}",
+                        self.config.socket_addr, config.socket_addr, config.protocol
+                    );
+                    return (Designation::Encrypted(config), encrypted);
+                }
+                Err(error) => {
+                    debug!(config = ?config, "designated resolver not verified: {error}");
+                }
+            }
+        }
+
+        (Designation::Unavailable, conn)
+    }
+
+    async fn connect_to(&self, config: &NameServerConfig) -> Result<P::Conn, ProtoError> {
+        let conn = self
+            .connection_provider
+            .new_connection(config, &self.options)?;
+        Box::pin(conn).await
+    }
+
+    /// The address and the protocol of the name server, or of its designated resolver
+    fn endpoint(&self) -> (SocketAddr, Protocol) {
+        if let Some(designation) = &self.designation {
+            if let Designation::Encrypted(config) = &*designation.lock() {
+                return (config.socket_addr, config.protocol);
+            }
+        }
+
+        (self.config.socket_addr, self.config.protocol)
+    }
+
     async fn inner_send<R: Into<DnsRequest> + Unpin + Send + 'static>(
         mut self,
         request: R,
     ) -> Result<DnsResponse, ProtoError> {
-        let client = self.connected_mut_client().await?;
+        let mut request = request.into();
+        if let Some(subnet) = self.options.edns_client_subnet {
+            add_client_subnet(&mut request, subnet);
+        }
+        let case = self
+            .options
+            .case_randomization
+            .then(|| QueryCase::randomize(&mut request))
+            .flatten();
+
+        let client = match self.connected_mut_client().await {
+            Ok(client) => client,
+            Err(error) => {
+                self.stats.record_connection_failure();
+                self.stats.record_error(&error);
+                return Err(error);
+            }
+        };
+        let (socket_addr, protocol) = self.endpoint();
+        if let Some(block_size) = self.options.edns_padding {
+            add_padding(&mut request, protocol, block_size);
+        }
         let now = Instant::now();
-        let response = client.send(request).first_answer().await;
+        let response = match &self.cookie {
+            Some(cookie) => cookie.send(&client, request).await,
+            None => client.send(request).first_answer().await,
+        };
         let rtt = now.elapsed();
 
+        let response = match (response, case) {
+            (Ok(mut response), Some(case)) => case
+                .verify(&mut response, self.options.case_randomization_tolerant)
+                .map(|()| response),
+            (response, _) => response,
+        };
+
         match response {
-            Ok(response) => {
+            Ok(mut response) => {
                 // Record the measured latency.
                 self.stats.record_rtt(rtt);
+                response.set_server(socket_addr, protocol);
 
                 // First evaluate if the message succeeded.
                 let response =
@@ -154,6 +286,7 @@ where
 
                 // record the failure
                 self.stats.record_connection_failure();
+                self.stats.record_error(&error);
 
                 // These are connection failures, not lookup failures, that is handled in the resolver layer
                 Err(error)
@@ -161,12 +294,49 @@ where
         }
     }
 
+    /// Returns the round-trip time and query counts recorded for this NameServer
+    pub fn stats(&self) -> NameServerStatsSnapshot {
+        self.stats.snapshot(&self.config)
+    }
+
     /// Specifies that this NameServer will treat negative responses as permanent failures and will not retry
     pub fn trust_nx_responses(&self) -> bool {
         self.config.trust_negative_responses
     }
 }
 
+/// Adds an EDNS Client Subnet option for `subnet` to the request, unless it already has one
+fn add_client_subnet(request: &mut DnsRequest, subnet: IpNet) {
+    let edns = request.extensions_mut().get_or_insert_with(|| {
+        let mut edns = Edns::new();
+        edns.set_max_payload(MAX_PAYLOAD_LEN);
+        edns
+    });
+
+    if edns.option(EdnsCode::Subnet).is_none() {
+        // the address must be truncated to the source prefix, RFC 7871 section 6
+        edns.options_mut()
+            .insert(EdnsOption::Subnet(ClientSubnet::from(subnet.trunc())));
+    }
+}
+
+/// Requests the padding of the request to a multiple of `block_size` by encrypted transports
+///
+/// Padding is never applied to unencrypted protocols, where it would only add overhead.
+fn add_padding(request: &mut DnsRequest, protocol: Protocol, block_size: usize) {
+    if !protocol.is_encrypted() {
+        return;
+    }
+
+    // the padding is carried in the EDNS section
+    request.extensions_mut().get_or_insert_with(|| {
+        let mut edns = Edns::new();
+        edns.set_max_payload(MAX_PAYLOAD_LEN);
+        edns
+    });
+    request.options_mut().edns_padding = Some(u16::try_from(block_size).unwrap_or(u16::MAX));
+}
+
 impl<P> DnsHandle for NameServer<P>
 where
     P: ConnectionProvider + Clone,
@@ -195,13 +365,91 @@ mod tests {
     use test_support::subscribe;
     use tokio::runtime::Runtime;
 
-    use crate::proto::op::{Query, ResponseCode};
+    use crate::proto::op::{Message, Query, ResponseCode};
     use crate::proto::rr::{Name, RecordType};
-    use crate::proto::xfer::{DnsHandle, DnsRequestOptions, FirstAnswer, Protocol};
+    use crate::proto::xfer::{DnsHandle, DnsRequestOptions, FirstAnswer};
 
     use super::*;
     use crate::name_server::connection_provider::TokioConnectionProvider;
 
+    #[test]
+    fn test_add_client_subnet() {
+        let query = Query::query(
+            Name::parse("www.example.com.", None).unwrap(),
+            RecordType::A,
+        );
+        let mut request = DnsRequest::new(Message::new(), DnsRequestOptions::default());
+        request.add_query(query);
+
+        add_client_subnet(&mut request, "192.0.2.123/24".parse().unwrap());
+
+        let bytes = request.to_vec().unwrap();
+        let message = Message::from_vec(&bytes).unwrap();
+        let edns = message.extensions().as_ref().expect("missing OPT RR");
+        assert_eq!(edns.max_payload(), MAX_PAYLOAD_LEN);
+        assert_eq!(
+            edns.option(EdnsCode::Subnet),
+            Some(&EdnsOption::Subnet(ClientSubnet::new(
+                Ipv4Addr::new(192, 0, 2, 0).into(),
+                24,
+                0
+            )))
+        );
+    }
+
+    #[test]
+    fn test_add_client_subnet_existing() {
+        let mut request = DnsRequest::new(Message::new(), DnsRequestOptions::default());
+        let subnet = "198.51.100.0/24".parse::<ClientSubnet>().unwrap();
+        request
+            .extensions_mut()
+            .get_or_insert_with(Edns::new)
+            .options_mut()
+            .insert(EdnsOption::Subnet(subnet));
+
+        add_client_subnet(&mut request, "192.0.2.0/24".parse().unwrap());
+
+        let edns = request.extensions().as_ref().unwrap();
+        assert_eq!(
+            edns.option(EdnsCode::Subnet),
+            Some(&EdnsOption::Subnet(subnet))
+        );
+    }
+
+    #[test]
+    fn test_add_padding() {
+        let query = Query::query(
+            Name::parse("www.example.com.", None).unwrap(),
+            RecordType::A,
+        );
+        let mut request = DnsRequest::new(Message::new(), DnsRequestOptions::default());
+        request.add_query(query);
+
+        // plain UDP is never padded
+        let mut udp_request = request.clone();
+        add_padding(&mut udp_request, Protocol::Udp, 128);
+        assert_eq!(udp_request.options().edns_padding, None);
+        assert!(udp_request.extensions().is_none());
+
+        #[cfg(feature = "dns-over-rustls")]
+        {
+            let mut tls_request = request;
+            add_padding(&mut tls_request, Protocol::Tls, 128);
+            assert_eq!(tls_request.options().edns_padding, Some(128));
+
+            // as applied by the transport before sending
+            tls_request.pad_to_block_size(128).unwrap();
+            let bytes = tls_request.to_vec().unwrap();
+            assert_eq!(bytes.len() % 128, 0);
+            let message = Message::from_vec(&bytes).unwrap();
+            let edns = message.extensions().as_ref().expect("missing OPT RR");
+            assert!(matches!(
+                edns.option(EdnsCode::Padding),
+                Some(EdnsOption::Padding(_))
+            ));
+        }
+    }
+
     #[test]
     fn test_name_server() {
         subscribe();
@@ -0,0 +1,220 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use parking_lot::Mutex;
+use tracing::debug;
+
+use crate::proto::{
+    op::{update_message::MAX_PAYLOAD_LEN, Edns, ResponseCode},
+    rr::rdata::opt::{Cookie, EdnsCode, EdnsOption},
+    xfer::{DnsHandle, DnsRequest, DnsResponse, FirstAnswer},
+    ProtoError,
+};
+
+/// The DNS Cookies exchanged with a name server, [RFC 7873](https://tools.ietf.org/html/rfc7873)
+///
+/// The client cookie is random, and the server cookie is the last one learned from the responses
+/// of the server.
+pub(crate) struct NameServerCookie {
+    client: [u8; 8],
+    server: Mutex<Option<Vec<u8>>>,
+}
+
+impl NameServerCookie {
+    pub(crate) fn new() -> Self {
+        Self {
+            client: rand::random(),
+            server: Mutex::new(None),
+        }
+    }
+
+    /// Sends the request with the COOKIE option
+    ///
+    /// A server which requires a valid server cookie answers with BADCOOKIE and a new server
+    /// cookie, the request is then resent once with it, RFC 7873 section 5.3.
+    pub(crate) async fn send<H: DnsHandle>(
+        &self,
+        handle: &H,
+        mut request: DnsRequest,
+    ) -> Result<DnsResponse, ProtoError> {
+        self.add_to(&mut request);
+        let response = handle.send(request.clone()).first_answer().await?;
+        self.receive(&response)?;
+        if response.response_code() != ResponseCode::BADCOOKIE {
+            return Ok(response);
+        }
+
+        debug!("BADCOOKIE response, resending with the new server cookie");
+        self.add_to(&mut request);
+        let response = handle.send(request).first_answer().await?;
+        self.receive(&response)?;
+        Ok(response)
+    }
+
+    /// Adds the COOKIE option, replacing any existing one
+    fn add_to(&self, request: &mut DnsRequest) {
+        let edns = request.extensions_mut().get_or_insert_with(|| {
+            let mut edns = Edns::new();
+            edns.set_max_payload(MAX_PAYLOAD_LEN);
+            edns
+        });
+
+        let cookie = Cookie::new(self.client, self.server.lock().clone());
+        edns.options_mut().remove(EdnsCode::Cookie);
+        edns.options_mut().insert(EdnsOption::Cookie(cookie));
+    }
+
+    /// Learns the server cookie of a response
+    ///
+    /// Responses with a cookie that does not echo the client cookie are rejected, as they are
+    /// likely forged, RFC 7873 section 5.3.
+    fn receive(&self, response: &DnsResponse) -> Result<(), ProtoError> {
+        let cookie = response
+            .extensions()
+            .as_ref()
+            .and_then(|edns| edns.option(EdnsCode::Cookie));
+        let Some(EdnsOption::Cookie(cookie)) = cookie else {
+            return Ok(());
+        };
+
+        if cookie.client() != &self.client {
+            return Err(ProtoError::from("client cookie mismatch in response"));
+        }
+        if let Some(server) = cookie.server() {
+            *self.server.lock() = Some(server.to_vec());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::pin::Pin;
+    use std::sync::Arc;
+
+    use futures_executor::block_on;
+    use futures_util::stream::{once, Stream};
+
+    use super::*;
+    use crate::proto::op::{Message, MessageType, Query};
+    use crate::proto::rr::{Name, RecordType};
+    use crate::proto::xfer::DnsRequestOptions;
+
+    /// A server which issues `server_cookie`, and answers BADCOOKIE to queries without it
+    #[derive(Clone)]
+    struct CookieServer {
+        server_cookie: Vec<u8>,
+        received: Arc<Mutex<Vec<Cookie>>>,
+    }
+
+    impl DnsHandle for CookieServer {
+        type Response = Pin<Box<dyn Stream<Item = Result<DnsResponse, ProtoError>> + Send>>;
+
+        fn send<R: Into<DnsRequest> + Unpin + Send + 'static>(&self, request: R) -> Self::Response {
+            let request = request.into();
+            let Some(EdnsOption::Cookie(cookie)) = request
+                .extensions()
+                .as_ref()
+                .and_then(|edns| edns.option(EdnsCode::Cookie))
+            else {
+                panic!("request without a cookie");
+            };
+            self.received.lock().push(cookie.clone());
+
+            let mut response = Message::new();
+            response
+                .set_id(request.id())
+                .set_message_type(MessageType::Response)
+                .add_queries(request.queries().to_vec());
+            if cookie.server() != Some(&self.server_cookie[..]) {
+                response.set_response_code(ResponseCode::BADCOOKIE);
+            }
+            let mut edns = Edns::new();
+            edns.options_mut().insert(EdnsOption::Cookie(Cookie::new(
+                *cookie.client(),
+                Some(self.server_cookie.clone()),
+            )));
+            response.set_edns(edns);
+
+            Box::pin(once(async move { DnsResponse::from_message(response) }))
+        }
+    }
+
+    fn request() -> DnsRequest {
+        let mut message = Message::new();
+        message.add_query(Query::query(
+            Name::from_ascii("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        DnsRequest::new(message, DnsRequestOptions::default())
+    }
+
+    #[test]
+    fn test_cookie_exchange() {
+        let cookie = NameServerCookie::new();
+        let server = CookieServer {
+            server_cookie: vec![0x5a; 16],
+            received: Arc::default(),
+        };
+
+        // the initial query only carries the client cookie, it is answered with BADCOOKIE
+        // and resent with the server cookie
+        let response = block_on(cookie.send(&server, request())).unwrap();
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        {
+            let received = server.received.lock();
+            assert_eq!(received.len(), 2);
+            assert_eq!(received[0], Cookie::new(cookie.client, None));
+            assert_eq!(
+                received[1],
+                Cookie::new(cookie.client, Some(vec![0x5a; 16]))
+            );
+        }
+
+        // the server cookie is reused by the following queries
+        let response = block_on(cookie.send(&server, request())).unwrap();
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        let received = server.received.lock();
+        assert_eq!(received.len(), 3);
+        assert_eq!(received[2].server(), Some(&[0x5a; 16][..]));
+    }
+
+    #[test]
+    fn test_badcookie_retry() {
+        let cookie = NameServerCookie::new();
+        *cookie.server.lock() = Some(vec![0x11; 16]);
+        let server = CookieServer {
+            server_cookie: vec![0x22; 16],
+            received: Arc::default(),
+        };
+
+        // the outdated server cookie is replaced by the one of the BADCOOKIE response
+        let response = block_on(cookie.send(&server, request())).unwrap();
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        let received = server.received.lock();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].server(), Some(&[0x11; 16][..]));
+        assert_eq!(received[1].server(), Some(&[0x22; 16][..]));
+        assert_eq!(cookie.server.lock().as_deref(), Some(&[0x22; 16][..]));
+    }
+
+    #[test]
+    fn test_client_cookie_mismatch() {
+        let cookie = NameServerCookie::new();
+        let mut response = Message::new();
+        let mut edns = Edns::new();
+        let mut client = cookie.client;
+        client[0] ^= 0xff;
+        edns.options_mut()
+            .insert(EdnsOption::Cookie(Cookie::new(client, Some(vec![0x33; 8]))));
+        response.set_edns(edns);
+
+        let response = DnsResponse::from_message(response).unwrap();
+        assert!(cookie.receive(&response).is_err());
+        assert!(cookie.server.lock().is_none());
+    }
+}
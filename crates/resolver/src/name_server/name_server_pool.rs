@@ -24,6 +24,7 @@ use tracing::debug;
 use crate::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts, ServerOrderingStrategy};
 use crate::name_server::connection_provider::{ConnectionProvider, GenericConnector};
 use crate::name_server::name_server::NameServer;
+use crate::name_server::NameServerStatsSnapshot;
 use crate::proto::runtime::{RuntimeProvider, Time};
 use crate::proto::xfer::{DnsHandle, DnsRequest, DnsResponse, FirstAnswer};
 use crate::proto::{ProtoError, ProtoErrorKind};
@@ -155,6 +156,15 @@ where
         }
     }
 
+    /// Returns the statistics of the datagram, followed by the stream based NameServers
+    pub fn stats(&self) -> Vec<NameServerStatsSnapshot> {
+        self.datagram_conns
+            .iter()
+            .chain(self.stream_conns.iter())
+            .map(NameServer::stats)
+            .collect()
+    }
+
     async fn try_send(
         opts: ResolverOpts,
         conns: Arc<[NameServer<P>]>,
@@ -188,6 +198,101 @@ where
 
         parallel_conn_loop(conns, request_loop, opts).await
     }
+
+    /// Sends the request over UDP, then over TCP if the response is truncated or UDP failed
+    async fn send_once(self, request: DnsRequest) -> Result<DnsResponse, ProtoError> {
+        // TODO: remove this clone, return the Message in the error?
+        let tcp_message = request.clone();
+
+        // First try the UDP connections
+        let future = Self::try_send(
+            self.options.clone(),
+            self.datagram_conns,
+            request,
+            &self.datagram_index,
+        );
+        let udp_res = match future.await {
+            Ok(response) if response.truncated() => {
+                debug!("truncated response received, retrying over TCP");
+                Err(ProtoError::from("received truncated response"))
+            }
+            Err(e) if (self.options.try_tcp_on_error && e.is_io()) || e.is_no_connections() => {
+                debug!("error from UDP, retrying over TCP: {}", e);
+                Err(e)
+            }
+            result => return result,
+        };
+
+        if self.stream_conns.is_empty() {
+            debug!("no TCP connections available");
+            return udp_res;
+        }
+
+        // Try query over TCP, as response to query over UDP was either truncated or was an
+        // error.
+        Self::try_send(
+            self.options,
+            self.stream_conns,
+            tcp_message,
+            &self.stream_index,
+        )
+        .await
+    }
+
+    /// Sends the request, retrying after connection errors and timeouts as configured by
+    /// `connection_retries`, within the overall `timeout`
+    async fn send_with_retries(self, request: DnsRequest) -> Result<DnsResponse, ProtoError> {
+        if self.options.connection_retries == 0 {
+            return self.send_once(request).await;
+        }
+
+        let timeout = self.options.timeout;
+        let options = self.options.clone();
+
+        let retries = async move {
+            let mut backoff = options.connection_retry_backoff;
+            let mut retries = 0;
+            loop {
+                match self.clone().send_once(request.clone()).await {
+                    Err(e) if retries < options.connection_retries && is_retryable(&e) => {
+                        let delay = jitter(backoff);
+                        debug!("retrying in {delay:?} after error: {e}");
+                        <<P as ConnectionProvider>::RuntimeProvider as RuntimeProvider>::Timer::delay_for(
+                            delay,
+                        )
+                        .await;
+                        backoff = backoff.saturating_mul(2).min(MAX_CONNECTION_RETRY_BACKOFF);
+                        retries += 1;
+                    }
+                    result => return result,
+                }
+            }
+        };
+
+        <<P as ConnectionProvider>::RuntimeProvider as RuntimeProvider>::Timer::timeout(
+            timeout, retries,
+        )
+        .await
+        .unwrap_or_else(|_| {
+            debug!("no answer within {timeout:?}");
+            Err(ProtoErrorKind::Timeout.into())
+        })
+    }
+}
+
+/// Upper bound of the delay between two connection retries
+const MAX_CONNECTION_RETRY_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Returns `true` if the request failed for a transient reason, and is worth retrying
+fn is_retryable(error: &ProtoError) -> bool {
+    error.is_io() || matches!(error.kind(), ProtoErrorKind::Timeout)
+}
+
+/// Randomizes the `backoff` between its half and its full duration, so that the clients failing
+/// at the same time do not all retry at the same time
+fn jitter(backoff: Duration) -> Duration {
+    let half = backoff / 2;
+    half + half.mul_f64(rng().gen::<f64>())
 }
 
 impl<P> DnsHandle for NameServerPool<P>
@@ -197,15 +302,7 @@ where
     type Response = Pin<Box<dyn Stream<Item = Result<DnsResponse, ProtoError>> + Send>>;
 
     fn send<R: Into<DnsRequest>>(&self, request: R) -> Self::Response {
-        let opts = self.options.clone();
         let request = request.into();
-        let datagram_conns = Arc::clone(&self.datagram_conns);
-        let stream_conns = Arc::clone(&self.stream_conns);
-        let datagram_index = Arc::clone(&self.datagram_index);
-        let stream_index = Arc::clone(&self.stream_index);
-        // TODO: remove this clone, return the Message in the error?
-        // TODO: remove this clone, return the Message in the error?
-        let tcp_message = request.clone();
 
         // TODO: limited to only when mDNS is enabled, but this should probably always be enforced?
         let mdns = Local::NotMdns(request);
@@ -219,31 +316,10 @@ where
 
         // it wasn't a local query, continue with standard lookup path
         let request = mdns.take_request();
+        let pool = self.clone();
         Box::pin(once(async move {
             debug!("sending request: {:?}", request.queries());
-
-            // First try the UDP connections
-            let future = Self::try_send(opts.clone(), datagram_conns, request, &datagram_index);
-            let udp_res = match future.await {
-                Ok(response) if response.truncated() => {
-                    debug!("truncated response received, retrying over TCP");
-                    Err(ProtoError::from("received truncated response"))
-                }
-                Err(e) if (opts.try_tcp_on_error && e.is_io()) || e.is_no_connections() => {
-                    debug!("error from UDP, retrying over TCP: {}", e);
-                    Err(e)
-                }
-                result => return result.map_err(ProtoError::from),
-            };
-
-            if stream_conns.is_empty() {
-                debug!("no TCP connections available");
-                return udp_res.map_err(ProtoError::from);
-            }
-
-            // Try query over TCP, as response to query over UDP was either truncated or was an
-            // error.
-            Self::try_send(opts, stream_conns, tcp_message, &stream_index).await
+            pool.send_with_retries(request).await
         }))
     }
 }
@@ -400,8 +476,11 @@ impl Stream for Local {
 #[cfg(test)]
 #[cfg(feature = "tokio-runtime")]
 mod tests {
+    use std::future::{self, Future};
+    use std::io;
     use std::net::{IpAddr, Ipv4Addr, SocketAddr};
     use std::str::FromStr;
+    use std::time::Instant;
 
     use tokio::runtime::Runtime;
 
@@ -409,9 +488,10 @@ mod tests {
     use crate::config::NameServerConfig;
     use crate::name_server::connection_provider::TokioConnectionProvider;
     use crate::name_server::GenericNameServer;
-    use crate::proto::op::Query;
-    use crate::proto::rr::{Name, RecordType};
-    use crate::proto::runtime::TokioRuntimeProvider;
+    use crate::proto::op::{Message, MessageType, Query, ResponseCode};
+    use crate::proto::rr::rdata::A;
+    use crate::proto::rr::{Name, RData, Record, RecordType};
+    use crate::proto::runtime::{TokioHandle, TokioRuntimeProvider};
     use crate::proto::xfer::{DnsHandle, DnsRequestOptions, Protocol};
 
     #[ignore]
@@ -566,4 +646,194 @@ mod tests {
             Self::from_config_with_provider(config, options, GenericConnector::new(runtime))
         }
     }
+
+    /// A connection failing the first `failures` requests, with a timeout after `delay` or with
+    /// SERVFAIL, and answering the following ones
+    #[derive(Clone)]
+    struct FlakyConnection {
+        failures: usize,
+        servfail: bool,
+        delay: Duration,
+        sent: Arc<AtomicUsize>,
+    }
+
+    impl FlakyConnection {
+        fn new(failures: usize) -> Self {
+            Self {
+                failures,
+                servfail: false,
+                delay: Duration::ZERO,
+                sent: Arc::default(),
+            }
+        }
+
+        fn pool(&self, options: ResolverOpts) -> NameServerPool<Self> {
+            let config = NameServerConfig {
+                socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 53),
+                protocol: Protocol::Udp,
+                tls_dns_name: None,
+                http_endpoint: None,
+                trust_negative_responses: false,
+                #[cfg(feature = "dns-over-rustls")]
+                tls_config: None,
+                bind_addr: None,
+            };
+            let name_server = NameServer::new(config, options.clone(), self.clone());
+            NameServerPool::from_nameservers(options, vec![name_server], vec![])
+        }
+
+        fn sent(&self) -> usize {
+            self.sent.load(AtomicOrdering::SeqCst)
+        }
+    }
+
+    impl DnsHandle for FlakyConnection {
+        type Response = Pin<Box<dyn Stream<Item = Result<DnsResponse, ProtoError>> + Send>>;
+
+        fn send<R: Into<DnsRequest>>(&self, request: R) -> Self::Response {
+            let request = request.into();
+            let failed = self.sent.fetch_add(1, AtomicOrdering::SeqCst) < self.failures;
+            if failed && !self.servfail {
+                let delay = self.delay;
+                return Box::pin(once(async move {
+                    tokio::time::sleep(delay).await;
+                    Err(ProtoErrorKind::Timeout.into())
+                }));
+            }
+
+            let mut response = Message::new();
+            response
+                .set_id(request.id())
+                .set_message_type(MessageType::Response)
+                .add_queries(request.queries().to_vec());
+            if failed {
+                response.set_response_code(ResponseCode::ServFail);
+            } else {
+                let name = request.queries()[0].name().clone();
+                response.add_answer(Record::from_rdata(
+                    name,
+                    300,
+                    RData::A(A::new(192, 0, 2, 1)),
+                ));
+            }
+            Box::pin(once(future::ready(DnsResponse::from_message(response))))
+        }
+    }
+
+    impl ConnectionProvider for FlakyConnection {
+        type Conn = Self;
+        type FutureConn = Pin<Box<dyn Future<Output = Result<Self, ProtoError>> + Send>>;
+        type RuntimeProvider = TokioRuntimeProvider;
+
+        fn new_connection(
+            &self,
+            _config: &NameServerConfig,
+            _options: &ResolverOpts,
+        ) -> Result<Self::FutureConn, io::Error> {
+            Ok(Box::pin(future::ready(Ok(self.clone()))))
+        }
+
+        fn create_handle(&self) -> TokioHandle {
+            TokioHandle::default()
+        }
+    }
+
+    async fn lookup(pool: &NameServerPool<FlakyConnection>) -> Result<DnsResponse, ProtoError> {
+        let name = Name::from_str("www.example.com.").unwrap();
+        pool.lookup(
+            Query::query(name, RecordType::A),
+            DnsRequestOptions::default(),
+        )
+        .first_answer()
+        .await
+    }
+
+    fn retry_options(connection_retries: usize) -> ResolverOpts {
+        ResolverOpts {
+            connection_retries,
+            connection_retry_backoff: Duration::from_millis(1),
+            ..ResolverOpts::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connection_retries() {
+        let conn = FlakyConnection::new(2);
+        let response = lookup(&conn.pool(retry_options(2))).await.unwrap();
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(conn.sent(), 3);
+
+        // the failures are returned once the retries are exhausted
+        let conn = FlakyConnection::new(2);
+        let error = lookup(&conn.pool(retry_options(1))).await.unwrap_err();
+        assert!(matches!(error.kind(), ProtoErrorKind::Timeout));
+        assert_eq!(conn.sent(), 2);
+
+        // without retries by default
+        let conn = FlakyConnection::new(1);
+        assert!(lookup(&conn.pool(ResolverOpts::default())).await.is_err());
+        assert_eq!(conn.sent(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_connection_retries_not_on_servfail() {
+        let conn = FlakyConnection {
+            servfail: true,
+            ..FlakyConnection::new(1)
+        };
+        let error = lookup(&conn.pool(retry_options(2))).await.unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            ProtoErrorKind::NoRecordsFound {
+                response_code: ResponseCode::ServFail,
+                ..
+            }
+        ));
+        assert_eq!(conn.sent(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_connection_retries_deadline() {
+        // the backoff would extend the lookup past the timeout
+        let conn = FlakyConnection::new(2);
+        let options = ResolverOpts {
+            timeout: Duration::from_millis(50),
+            connection_retry_backoff: Duration::from_secs(10),
+            ..retry_options(2)
+        };
+
+        let start = Instant::now();
+        assert!(lookup(&conn.pool(options)).await.is_err());
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(conn.sent(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_connection_retries_after_timeouts() {
+        let options = ResolverOpts {
+            timeout: Duration::from_secs(5),
+            ..retry_options(2)
+        };
+        let conn = FlakyConnection {
+            delay: Duration::from_secs(1),
+            ..FlakyConnection::new(2)
+        };
+
+        let start = tokio::time::Instant::now();
+        let response = lookup(&conn.pool(options.clone())).await.unwrap();
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(conn.sent(), 3);
+        assert!(start.elapsed() >= Duration::from_secs(2));
+
+        // the lookup, including its retries, is cut off after the timeout
+        let conn = FlakyConnection {
+            delay: Duration::from_secs(60),
+            ..FlakyConnection::new(1)
+        };
+        let start = tokio::time::Instant::now();
+        let error = lookup(&conn.pool(options.clone())).await.unwrap_err();
+        assert!(matches!(error.kind(), ProtoErrorKind::Timeout));
+        assert_eq!(start.elapsed(), options.timeout);
+        assert_eq!(conn.sent(), 1);
+    }
 }
@@ -5,14 +5,19 @@
 // https://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::net::SocketAddr;
 use std::sync::{
-    atomic::{self, AtomicU32},
+    atomic::{self, AtomicU32, AtomicU64},
     Arc,
 };
 
 use parking_lot::Mutex;
 use rand::Rng as _;
 
+use crate::config::NameServerConfig;
+use crate::proto::xfer::Protocol;
+use crate::proto::ProtoError;
+
 #[cfg(not(test))]
 use std::time::{Duration, Instant};
 #[cfg(test)]
@@ -50,6 +55,15 @@ pub(crate) struct NameServerStats {
 
     /// The last time the `srtt_microseconds` value was updated.
     last_update: Arc<Mutex<Option<Instant>>>,
+
+    /// Number of queries which received a response.
+    successes: AtomicU64,
+
+    /// Number of queries which did not receive a response.
+    failures: AtomicU64,
+
+    /// The error of the last query which did not receive a response.
+    last_error: Mutex<Option<ProtoError>>,
 }
 
 impl Default for NameServerStats {
@@ -82,11 +96,16 @@ impl NameServerStats {
         Self {
             srtt_microseconds: AtomicU32::new(initial_srtt.as_micros() as u32),
             last_update: Arc::new(Mutex::new(None)),
+            successes: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            last_error: Mutex::new(None),
         }
     }
 
     /// Records the measured `rtt` for a particular query.
     pub(crate) fn record_rtt(&self, rtt: Duration) {
+        self.successes.fetch_add(1, atomic::Ordering::Relaxed);
+
         // If the cast on the result does overflow (it shouldn't), then the
         // value is saturated to u32::MAX, which is above the `MAX_SRTT_MICROS`
         // limit (meaning that any potential overflow is inconsequential).
@@ -107,6 +126,8 @@ impl NameServerStats {
 
     /// Records a connection failure for a particular query.
     pub(crate) fn record_connection_failure(&self) {
+        self.failures.fetch_add(1, atomic::Ordering::Relaxed);
+
         self.update_srtt(
             Self::CONNECTION_FAILURE_PENALTY,
             |cur_srtt_microseconds, _last_update| {
@@ -115,10 +136,26 @@ impl NameServerStats {
         );
     }
 
+    /// Records the error of a query which did not receive a response.
+    pub(crate) fn record_error(&self, error: &ProtoError) {
+        *self.last_error.lock() = Some(error.clone());
+    }
+
+    /// Returns a copy of the current statistics for the name server with the `config`.
+    pub(crate) fn snapshot(&self, config: &NameServerConfig) -> NameServerStatsSnapshot {
+        NameServerStatsSnapshot {
+            socket_addr: config.socket_addr,
+            protocol: config.protocol,
+            srtt: self.srtt(),
+            successes: self.successes.load(atomic::Ordering::Relaxed),
+            failures: self.failures.load(atomic::Ordering::Relaxed),
+            last_error: self.last_error.lock().clone(),
+        }
+    }
+
     /// Returns the raw SRTT value.
     ///
     /// Prefer to use `decayed_srtt` when ordering name servers.
-    #[cfg(test)]
     fn srtt(&self) -> Duration {
         Duration::from_micros(u64::from(
             self.srtt_microseconds.load(atomic::Ordering::Acquire),
@@ -173,11 +210,32 @@ impl NameServerStats {
     }
 }
 
+/// The statistics of a name server at a point in time
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct NameServerStatsSnapshot {
+    /// The address of the name server
+    pub socket_addr: SocketAddr,
+    /// The protocol used to connect to the name server
+    pub protocol: Protocol,
+    /// The smoothed round-trip time, an exponentially weighted moving average of the measured
+    /// latencies, which is increased by a penalty for each failed query
+    pub srtt: Duration,
+    /// Number of queries which received a response, including negative responses
+    pub successes: u64,
+    /// Number of queries which did not receive a response, due to a connection failure or timeout
+    pub failures: u64,
+    /// The error of the last query which did not receive a response
+    pub last_error: Option<ProtoError>,
+}
+
 #[cfg(test)]
 #[allow(clippy::extra_unused_type_parameters)]
 mod tests {
     use std::cmp::Ordering;
 
+    use crate::proto::ProtoErrorKind;
+
     use super::*;
 
     fn is_send_sync<S: Sync + Send>() -> bool {
@@ -247,6 +305,27 @@ mod tests {
         assert_eq!(server.srtt(), Duration::from_micros(81606));
     }
 
+    #[test]
+    fn test_snapshot() {
+        let server = NameServerStats::new(Duration::from_micros(10));
+        server.record_rtt(Duration::from_millis(50));
+        server.record_rtt(Duration::from_millis(50));
+        server.record_connection_failure();
+        server.record_error(&ProtoError::from(ProtoErrorKind::Timeout));
+
+        let config = NameServerConfig::new(([192, 0, 2, 1], 53).into(), Protocol::Udp);
+        let snapshot = server.snapshot(&config);
+        assert_eq!(snapshot.socket_addr, config.socket_addr);
+        assert_eq!(snapshot.protocol, Protocol::Udp);
+        assert_eq!(snapshot.srtt, server.srtt());
+        assert_eq!(snapshot.successes, 2);
+        assert_eq!(snapshot.failures, 1);
+        assert!(matches!(
+            snapshot.last_error.as_ref().map(ProtoError::kind),
+            Some(ProtoErrorKind::Timeout)
+        ));
+    }
+
     #[test]
     fn test_record_rtt_maximum_value() {
         let server = NameServerStats::new(Duration::from_micros(10));
@@ -0,0 +1,224 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use rand::Rng;
+use tracing::debug;
+
+use crate::proto::{
+    rr::{Name, Record},
+    xfer::{DnsRequest, DnsResponse},
+    ProtoError,
+};
+
+/// The case of the query name of a request, randomized with 0x20 encoding
+///
+/// See [draft-vixie-dnsext-dns0x20](https://tools.ietf.org/html/draft-vixie-dnsext-dns0x20-00)
+pub(crate) struct QueryCase {
+    original: Name,
+    sent: Name,
+}
+
+impl QueryCase {
+    /// Randomizes the case of the letters of the query name of the request
+    ///
+    /// Returns `None` if the request does not have a single query, or if its name has no letters.
+    pub(crate) fn randomize(request: &mut DnsRequest) -> Option<Self> {
+        let [query] = request.queries_mut().as_mut_slice() else {
+            return None;
+        };
+
+        let original = query.name().clone();
+        let sent = randomize_case(&original, &mut rand::thread_rng())?;
+        query.set_name(sent.clone());
+        Some(Self { original, sent })
+    }
+
+    /// Verifies that the response echoes the case of the query name, and restores its original case
+    ///
+    /// When `tolerant`, responses from name servers which normalize the case of the names are
+    /// accepted as long as the name only differs in case.
+    pub(crate) fn verify(
+        &self,
+        response: &mut DnsResponse,
+        tolerant: bool,
+    ) -> Result<(), ProtoError> {
+        let echoed = match response.queries() {
+            [query] => Some(query.name()),
+            _ => None,
+        };
+
+        match echoed {
+            Some(name) if name.eq_case(&self.sent) => {}
+            Some(name) if tolerant && *name == self.sent => {
+                debug!(sent = %self.sent, %name, "query name case not echoed in response");
+            }
+            None if tolerant => {
+                debug!(sent = %self.sent, "query name not echoed in response");
+            }
+            _ => {
+                return Err(ProtoError::from(format!(
+                    "query name case mismatch in response, expected {}",
+                    self.sent
+                )))
+            }
+        }
+
+        self.restore(response);
+        Ok(())
+    }
+
+    /// Restores the original case of the names of the response matching the query name
+    fn restore(&self, response: &mut DnsResponse) {
+        for query in response.queries_mut() {
+            if *query.name() == self.original {
+                query.set_name(self.original.clone());
+            }
+        }
+
+        let restore = |records: &mut Vec<Record>| {
+            for record in records.iter_mut().filter(|r| *r.name() == self.original) {
+                record.set_name(self.original.clone());
+            }
+        };
+        restore(response.answers_mut());
+        restore(response.name_servers_mut());
+        restore(response.additionals_mut());
+    }
+}
+
+/// Flips the case of each ASCII letter of the name at random
+///
+/// Returns `None` if the name has no letters to randomize.
+fn randomize_case(name: &Name, rng: &mut impl Rng) -> Option<Name> {
+    if !name.iter().flatten().any(u8::is_ascii_alphabetic) {
+        return None;
+    }
+
+    let labels = name.iter().map(|label| {
+        label
+            .iter()
+            .map(|&byte| match rng.gen::<bool>() {
+                true if byte.is_ascii_lowercase() => byte.to_ascii_uppercase(),
+                true if byte.is_ascii_uppercase() => byte.to_ascii_lowercase(),
+                _ => byte,
+            })
+            .collect::<Vec<u8>>()
+    });
+
+    let mut randomized = Name::from_labels(labels).ok()?;
+    randomized.set_fqdn(name.is_fqdn());
+    Some(randomized)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::proto::op::{Message, MessageType, Query};
+    use crate::proto::rr::{rdata::A, RData, RecordType};
+    use crate::proto::xfer::DnsRequestOptions;
+
+    fn request(name: &str) -> DnsRequest {
+        let mut message = Message::new();
+        message.add_query(Query::query(Name::from_ascii(name).unwrap(), RecordType::A));
+        DnsRequest::new(message, DnsRequestOptions::default())
+    }
+
+    /// Answers the request with an A record, echoing its query with the name of `echo`
+    fn answer(request: &DnsRequest, echo: impl Fn(&Name) -> Name) -> DnsResponse {
+        let query = request.queries()[0].clone();
+        let name = echo(query.name());
+
+        let mut response = Message::new();
+        response
+            .set_id(request.id())
+            .set_message_type(MessageType::Response)
+            .add_query(Query::query(name.clone(), query.query_type()))
+            .add_answer(Record::from_rdata(
+                name,
+                300,
+                RData::A(A::new(192, 0, 2, 1)),
+            ));
+        DnsResponse::from_message(response).unwrap()
+    }
+
+    #[test]
+    fn test_randomize_case() {
+        let mut rng = StdRng::seed_from_u64(0x20);
+        let name = Name::from_ascii("www.example-1.com.").unwrap();
+
+        let randomized = (0..16)
+            .map(|_| randomize_case(&name, &mut rng).unwrap())
+            .collect::<Vec<_>>();
+        for candidate in &randomized {
+            // only the case of the letters changes
+            assert_eq!(*candidate, name);
+            assert!(candidate.is_fqdn());
+            assert_eq!(candidate.to_lowercase().to_ascii(), "www.example-1.com.");
+        }
+        assert!(randomized.iter().any(|candidate| !candidate.eq_case(&name)));
+
+        assert!(randomize_case(&Name::from_ascii("1.2.3.").unwrap(), &mut rng).is_none());
+        assert!(randomize_case(&Name::root(), &mut rng).is_none());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut request = request("www.example.com.");
+        let case = QueryCase::randomize(&mut request).unwrap();
+        assert!(request.queries()[0].name().eq_case(&case.sent));
+
+        // the original case of the names is restored in the verified response
+        let mut response = answer(&request, Name::clone);
+        case.verify(&mut response, false).unwrap();
+        let original = Name::from_ascii("www.example.com.").unwrap();
+        assert!(response.queries()[0].name().eq_case(&original));
+        assert!(response.answers()[0].name().eq_case(&original));
+    }
+
+    #[test]
+    fn test_case_mismatch() {
+        let mut request = request("www.example.com.");
+        let case = QueryCase::randomize(&mut request).unwrap();
+        let flip = |name: &Name| {
+            let mut flipped = name.to_ascii().into_bytes();
+            let letter = flipped
+                .iter_mut()
+                .find(|b| b.is_ascii_alphabetic())
+                .unwrap();
+            *letter ^= 0x20;
+            Name::from_ascii(String::from_utf8(flipped).unwrap()).unwrap()
+        };
+
+        let mut response = answer(&request, flip);
+        assert!(case.verify(&mut response, false).is_err());
+
+        // a response for another name is rejected even when tolerant
+        let mut response = answer(&request, |_| Name::from_ascii("www.example.net.").unwrap());
+        assert!(case.verify(&mut response, true).is_err());
+    }
+
+    #[test]
+    fn test_case_normalized() {
+        let mut request = request("www.example.com.");
+        let case = QueryCase {
+            original: request.queries()[0].name().clone(),
+            sent: Name::from_ascii("wWw.ExaMple.cOm.").unwrap(),
+        };
+        request.queries_mut()[0].set_name(case.sent.clone());
+
+        let mut response = answer(&request, Name::to_lowercase);
+        assert!(case.verify(&mut response, false).is_err());
+
+        let mut response = answer(&request, Name::to_lowercase);
+        case.verify(&mut response, true).unwrap();
+        let original = Name::from_ascii("www.example.com.").unwrap();
+        assert!(response.queries()[0].name().eq_case(&original));
+        assert!(response.answers()[0].name().eq_case(&original));
+    }
+}
@@ -0,0 +1,547 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Aggressive use of the DNSSEC validated cache, [RFC 8198](https://tools.ietf.org/html/rfc8198)
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use tracing::debug;
+
+use crate::proto::dnssec::rdata::{DNSSECRData, NSEC, NSEC3};
+use crate::proto::dnssec::{verify_nsec3, Proof};
+use crate::proto::op::{Query, ResponseCode};
+use crate::proto::rr::rdata::SOA;
+use crate::proto::rr::{DNSClass, Name, Record, RecordType};
+use crate::proto::{ProtoError, ProtoErrorKind};
+
+/// The DNAME record type, RFC 6672, which has no variant in [`RecordType`]
+const DNAME: RecordType = RecordType::Unknown(39);
+
+/// The validated NSEC and NSEC3 records of the negative responses, by zone
+///
+/// The negative answers to the queries proven by the cached records are synthesized, without
+/// querying the name servers, see [RFC 8198, section 5](https://tools.ietf.org/html/rfc8198#section-5):
+/// NXDOMAIN when the records prove that neither the name nor the wildcard of its closest encloser
+/// exist, NODATA when the record of the name has neither the type nor CNAME in its type bitmap.
+/// The NSEC3 records with the opt-out flag do not prove the non-existence of names, and the
+/// answers to DS queries are never synthesized.
+#[derive(Debug)]
+pub(crate) struct NsecCache {
+    zones: Mutex<HashMap<Name, Zone>>,
+    max_records: usize,
+}
+
+impl NsecCache {
+    /// Creates an empty cache, holding up to `max_records` NSEC and NSEC3 records
+    pub(crate) fn new(max_records: usize) -> Self {
+        Self {
+            zones: Mutex::default(),
+            max_records,
+        }
+    }
+
+    /// Caches the NSEC and NSEC3 records of the authority section of a negative response
+    ///
+    /// Only the records proven secure are cached, along with the secure SOA record of their zone.
+    /// They are valid for the minimum of their TTL and of the TTL and minimum field of the SOA
+    /// record, see [RFC 9077](https://tools.ietf.org/html/rfc9077).
+    pub(crate) fn insert(&self, authorities: &[Record], now: Instant) {
+        let is_secure = |record: &&Record| {
+            record.proof() == Proof::Secure && record.dns_class() == DNSClass::IN
+        };
+        let Some(soa) = authorities
+            .iter()
+            .filter(is_secure)
+            .find_map(|record| record.try_borrow::<SOA>())
+            .map(|soa| soa.to_owned())
+        else {
+            return;
+        };
+
+        let records = authorities
+            .iter()
+            .filter(is_secure)
+            .filter(|record| matches!(record.record_type(), RecordType::NSEC | RecordType::NSEC3))
+            .filter(|record| soa.name().zone_of(record.name()))
+            .collect::<Vec<_>>();
+        if records.is_empty() {
+            return;
+        }
+
+        let mut zones = self.zones.lock();
+        if zones.values().map(Zone::len).sum::<usize>() + records.len() > self.max_records {
+            zones.retain(|_, zone| {
+                zone.remove_expired(now);
+                zone.len() > 0
+            });
+
+            if zones.values().map(Zone::len).sum::<usize>() + records.len() > self.max_records {
+                debug!(
+                    "NSEC cache is full, not caching the NSEC records of {}",
+                    soa.name()
+                );
+                return;
+            }
+        }
+
+        let max_ttl = soa.ttl().min(soa.data().minimum());
+        let valid_until = |ttl: u32| now + Duration::from_secs(u64::from(ttl.min(max_ttl)));
+        let zone = zones.entry(soa.name().clone()).or_insert_with(|| Zone {
+            soa: soa.clone(),
+            soa_valid_until: now,
+            nsecs: BTreeMap::new(),
+            nsec3s: HashMap::new(),
+        });
+        zone.soa_valid_until = valid_until(soa.ttl());
+        zone.soa = soa;
+
+        for record in records {
+            let entry = Entry {
+                record: record.clone(),
+                valid_until: valid_until(record.ttl()),
+            };
+
+            if record.record_type() == RecordType::NSEC {
+                zone.nsecs.insert(record.name().clone(), entry);
+                continue;
+            }
+
+            // all the NSEC3 records of a zone have the same parameters, RFC 5155 section 8.2
+            let params = |entry: &Entry| {
+                entry.nsec3().map(|nsec3| {
+                    (
+                        nsec3.hash_algorithm(),
+                        nsec3.salt().to_vec(),
+                        nsec3.iterations(),
+                    )
+                })
+            };
+            if zone
+                .nsec3s
+                .values()
+                .next()
+                .is_some_and(|cached| params(cached) != params(&entry))
+            {
+                zone.nsec3s.clear();
+            }
+            zone.nsec3s.insert(record.name().clone(), entry);
+        }
+    }
+
+    /// Synthesizes the negative answer to the query from the cached records
+    ///
+    /// Returns `None` if the records do not prove that the name or the type do not exist.
+    pub(crate) fn synthesize(&self, query: &Query, now: Instant) -> Option<ProtoError> {
+        if query.query_class() != DNSClass::IN || query.query_type() == RecordType::DS {
+            return None;
+        }
+
+        let zones = self.zones.lock();
+        let mut name = query.name().clone();
+        let zone = loop {
+            if let Some(zone) = zones.get(&name) {
+                break zone;
+            }
+            if name.is_root() {
+                return None;
+            }
+            name = name.base_name();
+        };
+        if zone.soa_valid_until <= now {
+            return None;
+        }
+
+        let (response_code, valid_until) = zone
+            .nsec_proof(query, now)
+            .or_else(|| zone.nsec3_proof(query, now))?;
+        let negative_ttl = valid_until
+            .min(zone.soa_valid_until)
+            .saturating_duration_since(now)
+            .as_secs();
+        debug!("synthesized {response_code} answer for {query} from the cached NSEC records");
+
+        Some(
+            ProtoErrorKind::NoRecordsFound {
+                query: Box::new(query.clone()),
+                soa: Some(Box::new(zone.soa.clone())),
+                ns: None,
+                negative_ttl: Some(negative_ttl as u32),
+                response_code,
+                trusted: true,
+                authorities: None,
+            }
+            .into(),
+        )
+    }
+
+    /// Removes all the cached records
+    pub(crate) fn clear(&self) {
+        self.zones.lock().clear();
+    }
+}
+
+/// The cached records of a zone
+#[derive(Debug)]
+struct Zone {
+    soa: Record<SOA>,
+    soa_valid_until: Instant,
+    /// The NSEC records, in the canonical order of their owner names
+    nsecs: BTreeMap<Name, Entry>,
+    nsec3s: HashMap<Name, Entry>,
+}
+
+impl Zone {
+    fn len(&self) -> usize {
+        self.nsecs.len() + self.nsec3s.len()
+    }
+
+    fn remove_expired(&mut self, now: Instant) {
+        self.nsecs.retain(|_, entry| entry.valid_until > now);
+        self.nsec3s.retain(|_, entry| entry.valid_until > now);
+    }
+
+    /// The proof with the NSEC records, see [RFC 4035, section 5.4](https://tools.ietf.org/html/rfc4035#section-5.4)
+    ///
+    /// Returns the response code of the answer and the expiration of the records of the proof.
+    fn nsec_proof(&self, query: &Query, now: Instant) -> Option<(ResponseCode, Instant)> {
+        let name = query.name();
+        if let Some(entry) = self.nsecs.get(name) {
+            let types = entry.nsec()?.type_bit_maps();
+            // the NSEC record of the parent side of a delegation does not prove anything about
+            // the types of the child zone
+            let is_delegation =
+                types.contains(&RecordType::NS) && !types.contains(&RecordType::SOA);
+            let exists = types.contains(&query.query_type()) || types.contains(&RecordType::CNAME);
+            if entry.valid_until <= now || is_delegation || exists {
+                return None;
+            }
+            return Some((ResponseCode::NoError, entry.valid_until));
+        }
+
+        let covering = self.nsec_covering(name, now)?;
+        let next = covering.nsec()?.next_domain_name();
+
+        // the closest encloser is the longest common ancestor of the name and of the owner or the
+        // next name of the covering record, the root is an ancestor of all of them
+        let mut encloser = name.base_name();
+        while !encloser.zone_of(covering.record.name()) && !encloser.zone_of(next) {
+            encloser = encloser.base_name();
+        }
+
+        // an existing wildcard would answer the query
+        let mut wildcard = encloser.prepend_label("*").ok()?;
+        wildcard.set_fqdn(encloser.is_fqdn());
+        if self.nsecs.contains_key(&wildcard) {
+            return None;
+        }
+        let wildcard_covering = self.nsec_covering(&wildcard, now)?;
+
+        Some((
+            ResponseCode::NXDomain,
+            covering.valid_until.min(wildcard_covering.valid_until),
+        ))
+    }
+
+    /// The NSEC record proving that the name does not exist
+    fn nsec_covering(&self, name: &Name, now: Instant) -> Option<&Entry> {
+        let (owner, entry) = self
+            .nsecs
+            .range::<Name, _>((Bound::Unbounded, Bound::Excluded(name)))
+            .next_back()?;
+        let nsec = entry.nsec()?;
+        let next = nsec.next_domain_name();
+
+        // the last record of the chain wraps around to the apex of the zone
+        let covers = name < next || next <= owner;
+        // the names below a delegation or a DNAME are not part of the zone
+        let types = nsec.type_bit_maps();
+        let is_cut = owner.zone_of(name)
+            && (types.contains(&DNAME)
+                || (types.contains(&RecordType::NS) && !types.contains(&RecordType::SOA)));
+
+        (entry.valid_until > now && covers && !is_cut).then_some(entry)
+    }
+
+    /// The proof with the NSEC3 records, see [RFC 5155, section 8](https://tools.ietf.org/html/rfc5155#section-8)
+    fn nsec3_proof(&self, query: &Query, now: Instant) -> Option<(ResponseCode, Instant)> {
+        let entries = self
+            .nsec3s
+            .values()
+            .filter(|entry| entry.valid_until > now)
+            .collect::<Vec<_>>();
+        let valid_until = entries.iter().map(|entry| entry.valid_until).min()?;
+        let nsec3s = entries
+            .iter()
+            .filter_map(|entry| Some((entry.record.name(), entry.nsec3()?)))
+            .collect::<Vec<_>>();
+
+        // the iterations of the records were already limited by their validation
+        [ResponseCode::NXDomain, ResponseCode::NoError]
+            .into_iter()
+            .find(|response_code| {
+                let proof = verify_nsec3(
+                    query,
+                    self.soa.name(),
+                    *response_code,
+                    &[],
+                    &nsec3s,
+                    u16::MAX,
+                );
+                proof == Proof::Secure
+            })
+            .map(|response_code| (response_code, valid_until))
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    record: Record,
+    valid_until: Instant,
+}
+
+impl Entry {
+    fn nsec(&self) -> Option<&NSEC> {
+        self.record
+            .data()
+            .as_dnssec()
+            .and_then(DNSSECRData::as_nsec)
+    }
+
+    fn nsec3(&self) -> Option<&NSEC3> {
+        self.record
+            .data()
+            .as_dnssec()
+            .and_then(DNSSECRData::as_nsec3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::dnssec::Nsec3HashAlgorithm;
+    use crate::proto::rr::RData;
+
+    fn name(name: &str) -> Name {
+        Name::from_ascii(name).unwrap()
+    }
+
+    fn secure(name: Name, ttl: u32, rdata: RData) -> Record {
+        let mut record = Record::from_rdata(name, ttl, rdata);
+        record.set_proof(Proof::Secure);
+        record
+    }
+
+    fn soa() -> Record {
+        let soa = SOA::new(
+            name("ns.example.com."),
+            name("hostmaster.example.com."),
+            1,
+            3600,
+            600,
+            86400,
+            300,
+        );
+        secure(name("example.com."), 3600, RData::SOA(soa))
+    }
+
+    fn nsec(owner: &str, next: &str, types: &[RecordType]) -> Record {
+        let nsec = NSEC::new(name(next), types.to_vec());
+        secure(name(owner), 3600, RData::DNSSEC(DNSSECRData::NSEC(nsec)))
+    }
+
+    fn response_code(
+        cache: &NsecCache,
+        query: &str,
+        query_type: RecordType,
+    ) -> Option<ResponseCode> {
+        let error = cache.synthesize(&Query::query(name(query), query_type), Instant::now())?;
+        match error.kind() {
+            ProtoErrorKind::NoRecordsFound { response_code, .. } => Some(*response_code),
+            _ => panic!("unexpected error: {error}"),
+        }
+    }
+
+    /// The zone `example.com.`, with the names `a` and `c` and a delegation to `d`
+    fn nsec_cache() -> NsecCache {
+        let cache = NsecCache::new(16);
+        cache.insert(
+            &[
+                soa(),
+                nsec(
+                    "example.com.",
+                    "a.example.com.",
+                    &[RecordType::SOA, RecordType::NS],
+                ),
+                nsec("a.example.com.", "c.example.com.", &[RecordType::A]),
+                nsec(
+                    "c.example.com.",
+                    "d.example.com.",
+                    &[RecordType::A, RecordType::MX],
+                ),
+                nsec("d.example.com.", "example.com.", &[RecordType::NS]),
+            ],
+            Instant::now(),
+        );
+        cache
+    }
+
+    #[test]
+    fn test_nsec_nxdomain() {
+        let cache = nsec_cache();
+        assert_eq!(
+            response_code(&cache, "b.example.com.", RecordType::A),
+            Some(ResponseCode::NXDomain)
+        );
+        assert_eq!(
+            response_code(&cache, "x.b.example.com.", RecordType::AAAA),
+            Some(ResponseCode::NXDomain)
+        );
+
+        // the names which exist, and the names below a delegation, are not proven to not exist
+        assert_eq!(response_code(&cache, "c.example.com.", RecordType::A), None);
+        assert_eq!(
+            response_code(&cache, "x.d.example.com.", RecordType::A),
+            None
+        );
+        assert_eq!(response_code(&cache, "b.example.net.", RecordType::A), None);
+    }
+
+    #[test]
+    fn test_nsec_nodata() {
+        let cache = nsec_cache();
+        assert_eq!(
+            response_code(&cache, "a.example.com.", RecordType::AAAA),
+            Some(ResponseCode::NoError)
+        );
+        assert_eq!(response_code(&cache, "a.example.com.", RecordType::A), None);
+
+        // the parent side of a delegation and DS queries are not proven
+        assert_eq!(response_code(&cache, "d.example.com.", RecordType::A), None);
+        assert_eq!(
+            response_code(&cache, "a.example.com.", RecordType::DS),
+            None
+        );
+    }
+
+    #[test]
+    fn test_nsec_wildcard() {
+        let cache = NsecCache::new(16);
+        cache.insert(
+            &[
+                soa(),
+                nsec("example.com.", "*.example.com.", &[RecordType::SOA]),
+                nsec("*.example.com.", "a.example.com.", &[RecordType::A]),
+                nsec("a.example.com.", "c.example.com.", &[RecordType::A]),
+            ],
+            Instant::now(),
+        );
+
+        // the wildcard answers the query
+        assert_eq!(response_code(&cache, "b.example.com.", RecordType::A), None);
+    }
+
+    #[test]
+    fn test_insecure_and_expired() {
+        let cache = NsecCache::new(16);
+        let mut insecure = nsec("a.example.com.", "c.example.com.", &[RecordType::A]);
+        insecure.set_proof(Proof::Insecure);
+        cache.insert(&[soa(), insecure], Instant::now());
+        assert_eq!(cache.zones.lock().len(), 0);
+
+        // the records are valid for the minimum field of the SOA record
+        let cache = nsec_cache();
+        let later = Instant::now() + Duration::from_secs(301);
+        let query = Query::query(name("b.example.com."), RecordType::A);
+        assert!(cache.synthesize(&query, later).is_none());
+
+        cache.clear();
+        assert_eq!(response_code(&cache, "b.example.com.", RecordType::A), None);
+    }
+
+    #[test]
+    fn test_max_records() {
+        let cache = NsecCache::new(2);
+        cache.insert(
+            &[
+                soa(),
+                nsec("example.com.", "a.example.com.", &[RecordType::SOA]),
+                nsec("a.example.com.", "c.example.com.", &[RecordType::A]),
+                nsec("c.example.com.", "example.com.", &[RecordType::A]),
+            ],
+            Instant::now(),
+        );
+        assert_eq!(cache.zones.lock().len(), 0);
+    }
+
+    /// The zone `example.com.`, of the names `a` and `c`, with NSEC3 records
+    fn nsec3_cache(opt_out: bool) -> NsecCache {
+        let hash = |name: &str| {
+            Nsec3HashAlgorithm::SHA1
+                .hash(&[], &Name::from_ascii(name).unwrap(), 0)
+                .unwrap()
+                .as_ref()
+                .to_vec()
+        };
+
+        let mut names = [
+            ("example.com.", vec![RecordType::SOA, RecordType::NS]),
+            ("a.example.com.", vec![RecordType::A]),
+            ("c.example.com.", vec![RecordType::A]),
+        ]
+        .into_iter()
+        .map(|(name, types)| (hash(name), types))
+        .collect::<Vec<_>>();
+        names.sort();
+
+        let mut records = vec![soa()];
+        for (i, (hashed, types)) in names.iter().enumerate() {
+            let next = names[(i + 1) % names.len()].0.clone();
+            let nsec3 = NSEC3::new(
+                Nsec3HashAlgorithm::SHA1,
+                opt_out,
+                0,
+                vec![],
+                next,
+                types.clone(),
+            );
+            let owner = Name::from_ascii(data_encoding::BASE32_DNSSEC.encode(hashed))
+                .unwrap()
+                .append_domain(&name("example.com."))
+                .unwrap();
+            records.push(secure(
+                owner,
+                3600,
+                RData::DNSSEC(DNSSECRData::NSEC3(nsec3)),
+            ));
+        }
+
+        let cache = NsecCache::new(16);
+        cache.insert(&records, Instant::now());
+        cache
+    }
+
+    #[test]
+    fn test_nsec3() {
+        let cache = nsec3_cache(false);
+        assert_eq!(
+            response_code(&cache, "b.example.com.", RecordType::A),
+            Some(ResponseCode::NXDomain)
+        );
+        assert_eq!(
+            response_code(&cache, "a.example.com.", RecordType::AAAA),
+            Some(ResponseCode::NoError)
+        );
+        assert_eq!(response_code(&cache, "a.example.com.", RecordType::A), None);
+
+        // an opt-out record may hide an insecure delegation
+        let cache = nsec3_cache(true);
+        assert_eq!(response_code(&cache, "b.example.com.", RecordType::A), None);
+    }
+}
@@ -6,30 +6,40 @@
 // copied, modified, or distributed except according to those terms.
 
 //! Structs for creating and using a Resolver
+use std::collections::BTreeMap;
 use std::fmt;
 use std::future::Future;
-use std::net::IpAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
 
+use futures_util::stream::{self, StreamExt};
 use futures_util::{future, FutureExt};
+use ipnet::{IpAddrRange, IpNet, Ipv4AddrRange, Ipv6AddrRange};
+use rand::Rng;
 use tracing::{debug, trace};
 
 use crate::caching_client::CachingClient;
-use crate::config::{ResolveHosts, ResolverConfig, ResolverOpts};
-use crate::dns_lru::{self, DnsLru};
+use crate::config::{DnssecMode, LookupIpStrategy, ResolveHosts, ResolverConfig, ResolverOpts};
+use crate::dns_lru::{self, DnsLru, LruStore};
 use crate::error::{ResolveError, ResolveErrorKind};
-use crate::hosts::Hosts;
-use crate::lookup::{self, Lookup, LookupEither};
-use crate::lookup_ip::{LookupIp, LookupIpFuture};
+use crate::hosts::{Hosts, HostsSource, HostsWatcher};
+use crate::lookup::{self, Lookup, LookupEither, LookupMetadata};
+use crate::lookup_ip::{happy_eyeballs_order, AddressRotation, LookupIp, LookupIpFuture};
 #[cfg(feature = "tokio-runtime")]
 use crate::name_server::TokioConnectionProvider;
 use crate::name_server::{ConnectionProvider, NameServerPool};
-use crate::proto::op::Query;
+use crate::proto::op::{Query, ResponseCode};
 use crate::proto::rr::domain::usage::ONION;
+use crate::proto::rr::rdata::SRV;
 use crate::proto::rr::{IntoName, Name, RData, Record, RecordType};
+use crate::proto::runtime::Spawn;
 use crate::proto::xfer::{DnsHandle, DnsRequestOptions, RetryDnsHandle};
+use crate::proto::{ProtoError, ProtoErrorKind};
+
+/// The number of PTR queries of [`Resolver::reverse_lookup_range`] in flight at once
+const REVERSE_LOOKUP_RANGE_CONCURRENCY: usize = 16;
 
 /// An asynchronous resolver for DNS generic over async Runtimes.
 ///
@@ -49,7 +59,8 @@ pub struct Resolver<P: ConnectionProvider> {
     config: ResolverConfig,
     options: ResolverOpts,
     client_cache: CachingClient<LookupEither<P>>,
-    hosts: Option<Arc<Hosts>>,
+    hosts: Option<HostsSource>,
+    rotation: Option<Arc<AddressRotation>>,
 }
 
 /// A Resolver used with Tokio
@@ -122,10 +133,11 @@ impl<R: ConnectionProvider> Resolver<R> {
     /// * `options` - basic lookup options for the resolver
     /// * `provider` - connection provider, for DNS connections, I/O, and timers
     pub fn new(config: ResolverConfig, options: ResolverOpts, provider: R) -> Self {
+        let handle = provider.create_handle();
         let pool = NameServerPool::from_config_with_provider(&config, options.clone(), provider);
         let either;
         let client = RetryDnsHandle::new(pool, options.attempts);
-        if options.validate {
+        if options.validate || options.dnssec_mode != DnssecMode::Off {
             #[cfg(feature = "dnssec-ring")]
             {
                 use crate::proto::dnssec::DnssecDnsHandle;
@@ -143,17 +155,67 @@ impl<R: ConnectionProvider> Resolver<R> {
         }
 
         let hosts = match options.use_hosts_file {
-            ResolveHosts::Always | ResolveHosts::Auto => Some(Arc::new(Hosts::new())),
+            ResolveHosts::Always | ResolveHosts::Auto => {
+                Some(HostsSource::system(options.hosts_file_reload_interval))
+            }
             ResolveHosts::Never => None,
         };
 
         trace!("handle passed back");
-        let lru = DnsLru::new(options.cache_size, dns_lru::TtlConfig::from_opts(&options));
+        let ttl_config = dns_lru::TtlConfig::from_opts(&options);
+        let lru = match &options.cache_store {
+            Some(store) => DnsLru::with_store(Arc::clone(store), ttl_config),
+            None => match options.cache_max_bytes {
+                Some(max_bytes) => DnsLru::with_store(
+                    Arc::new(LruStore::with_max_bytes(options.cache_size, max_bytes)),
+                    ttl_config,
+                ),
+                None => DnsLru::new(options.cache_size, ttl_config),
+            },
+        }
+        .with_serve_stale(options.serve_stale_ttl);
+
+        let mut client_cache = CachingClient::with_cache(
+            lru,
+            either,
+            options.preserve_intermediates,
+            options.edns_client_subnet,
+        )
+        .with_dns64(options.dns64_prefix)
+        .with_query_coalescing(options.coalesce_queries);
+        #[cfg(feature = "dnssec-ring")]
+        {
+            client_cache = client_cache.with_dnssec_mode(options.dnssec_mode);
+            if options.aggressive_nsec
+                && (options.validate || options.dnssec_mode != DnssecMode::Off)
+            {
+                client_cache = client_cache.with_aggressive_nsec(options.cache_size);
+            }
+        }
+        if options.serve_stale_ttl.is_some() || options.prefetch_threshold.is_some() {
+            client_cache = client_cache
+                .with_background_refresh(Arc::new(move |refresh| {
+                    handle.clone().spawn_bg(async move {
+                        refresh.await;
+                        Ok(())
+                    })
+                }))
+                .with_prefetch_threshold(options.prefetch_threshold);
+        }
+
+        let rotation = options.rotate.then(|| {
+            Arc::new(AddressRotation::new(
+                options.rotation_strategy,
+                options.cache_size,
+            ))
+        });
+
         Self {
             config,
-            client_cache: CachingClient::with_cache(lru, either, options.preserve_intermediates),
+            client_cache,
             options,
             hosts,
+            rotation,
         }
     }
 
@@ -221,6 +283,20 @@ impl<P: ConnectionProvider> Resolver<P> {
             .await
     }
 
+    /// Like [`Self::lookup`], but also returns where the records were obtained from
+    ///
+    /// The metadata reports the name server which sent the records and the protocol used, or
+    /// whether they were answered from the cache, e.g. to debug setups with several name servers.
+    pub async fn lookup_with_metadata<N: IntoName>(
+        &self,
+        name: N,
+        record_type: RecordType,
+    ) -> Result<(Lookup, LookupMetadata), ResolveError> {
+        let lookup = self.lookup(name, record_type).await?;
+        let metadata = lookup.metadata();
+        Ok((lookup, metadata))
+    }
+
     fn push_name(name: Name, names: &mut Vec<Name>) {
         if !names.contains(&name) {
             names.push(name);
@@ -251,8 +327,12 @@ impl<P: ConnectionProvider> Resolver<P> {
             let raw_name_first: bool =
                 name.num_labels() as usize > self.options.ndots || name.is_localhost();
 
+            // a single label is never looked up as a top-level domain with `no_tld_query`
+            let raw_name =
+                !self.options.no_tld_query || name.num_labels() > 1 || name.is_localhost();
+
             // if not meeting ndots, we always do the raw name in the final lookup
-            if !raw_name_first {
+            if raw_name && !raw_name_first {
                 let mut fqdn = name.clone();
                 fqdn.set_fqdn(true);
                 names.push(fqdn);
@@ -283,7 +363,7 @@ impl<P: ConnectionProvider> Resolver<P> {
             }
 
             // this is the direct name lookup
-            if raw_name_first {
+            if raw_name && raw_name_first {
                 // adding the name as though it's an FQDN for lookup
                 let mut fqdn = name.clone();
                 fqdn.set_fqdn(true);
@@ -309,7 +389,7 @@ impl<P: ConnectionProvider> Resolver<P> {
             record_type,
             options,
             self.client_cache.clone(),
-            self.hosts.clone(),
+            self.hosts.as_ref().map(HostsSource::hosts),
         )
         .await
         .map(L::from)
@@ -322,6 +402,46 @@ impl<P: ConnectionProvider> Resolver<P> {
     /// # Arguments
     /// * `host` - string hostname, if this is an invalid hostname, an error will be returned.
     pub async fn lookup_ip(&self, host: impl IntoName) -> Result<LookupIp, ResolveError> {
+        self.lookup_ip_with_strategy(host, self.options.ip_strategy)
+            .await
+    }
+
+    /// Like [`Self::lookup_ip`], but also returns where the records were obtained from
+    ///
+    /// See [`Self::lookup_with_metadata`]. When both A and AAAA records are queried, this is the
+    /// metadata of the lookup which completed first.
+    pub async fn lookup_ip_with_metadata(
+        &self,
+        host: impl IntoName,
+    ) -> Result<(LookupIp, LookupMetadata), ResolveError> {
+        let lookup = self.lookup_ip(host).await?;
+        let metadata = lookup.as_lookup().metadata();
+        Ok((lookup, metadata))
+    }
+
+    /// Performs a dual-stack DNS lookup for the IP for the given hostname, and returns the
+    /// addresses in the order for connection attempts, per [RFC 8305, section 4](https://www.rfc-editor.org/rfc/rfc8305#section-4)
+    ///
+    /// Both A and AAAA records are queried, independently of the `ip_strategy` option. The
+    /// address families are interleaved, starting with IPv6, e.g. for a dual-stack connect racing
+    /// the attempts in this order ("Happy Eyeballs").
+    ///
+    /// # Arguments
+    /// * `host` - string hostname, if this is an invalid hostname, an error will be returned.
+    pub async fn lookup_ip_happy_eyeballs(
+        &self,
+        host: impl IntoName,
+    ) -> Result<LookupIp, ResolveError> {
+        self.lookup_ip_with_strategy(host, LookupIpStrategy::Ipv4AndIpv6)
+            .await
+            .map(happy_eyeballs_order)
+    }
+
+    async fn lookup_ip_with_strategy(
+        &self,
+        host: impl IntoName,
+        strategy: LookupIpStrategy,
+    ) -> Result<LookupIp, ResolveError> {
         let mut finally_ip_addr = None;
         let maybe_ip = host.to_ip().map(RData::from);
         let maybe_name = host.into_name();
@@ -340,7 +460,7 @@ impl<P: ConnectionProvider> Resolver<P> {
             } else {
                 let query = Query::query(name, ip_addr.record_type());
                 let lookup = Lookup::new_with_max_ttl(query, Arc::from([record]));
-                return Ok(lookup.into());
+                return Ok(LookupIp::from(lookup).with_scope_id(self.options.ipv6_scope_id));
             }
         }
 
@@ -350,7 +470,7 @@ impl<P: ConnectionProvider> Resolver<P> {
                 // it was a valid IP, return that...
                 let query = Query::query(ip_addr.name().clone(), ip_addr.record_type());
                 let lookup = Lookup::new_with_max_ttl(query, Arc::from([ip_addr.clone()]));
-                return Ok(lookup.into());
+                return Ok(LookupIp::from(lookup).with_scope_id(self.options.ipv6_scope_id));
             }
             (Err(err), None) => {
                 return Err(err.into());
@@ -358,22 +478,34 @@ impl<P: ConnectionProvider> Resolver<P> {
         };
 
         let names = self.build_names(name);
-        let hosts = self.hosts.as_ref().cloned();
+        let hosts = self.hosts.as_ref().map(HostsSource::hosts);
 
-        LookupIpFuture::lookup(
+        let lookup = LookupIpFuture::lookup(
             names,
-            self.options.ip_strategy,
+            strategy,
             self.client_cache.clone(),
             self.request_options(),
             hosts,
             finally_ip_addr.map(Record::into_data),
         )
-        .await
+        .with_single_request(self.options.single_request)
+        .await?;
+
+        let lookup = match &self.rotation {
+            Some(rotation) => rotation.rotate(lookup),
+            None => lookup,
+        };
+        Ok(lookup.with_scope_id(self.options.ipv6_scope_id))
     }
 
     /// Customizes the static hosts used in this resolver.
     pub fn set_hosts(&mut self, hosts: Option<Hosts>) {
-        self.hosts = hosts.map(Arc::new);
+        self.hosts = hosts.map(|hosts| HostsSource::Static(Arc::new(hosts)));
+    }
+
+    /// Uses the watched hosts file as the static hosts in this resolver, see [`HostsWatcher`]
+    pub fn set_hosts_watcher(&mut self, watcher: HostsWatcher) {
+        self.hosts = Some(HostsSource::Watched(Arc::new(watcher)));
     }
 
     lookup_fn!(
@@ -382,6 +514,125 @@ impl<P: ConnectionProvider> Resolver<P> {
         RecordType::PTR,
         IpAddr
     );
+
+    /// Performs a reverse lookup of each address of the range, e.g. to audit the names of a subnet
+    ///
+    /// The PTR queries are sent concurrently, with a bounded number in flight. The addresses
+    /// without names are left out of the result, any other failure fails the whole lookup.
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - the addresses to look up, including the network and broadcast addresses
+    /// * `max_addresses` - the maximum size of the range, larger ranges are rejected before any
+    ///   query is sent, to guard against accidental scans of huge ranges
+    pub async fn reverse_lookup_range(
+        &self,
+        range: IpNet,
+        max_addresses: usize,
+    ) -> Result<BTreeMap<IpAddr, Vec<Name>>, ResolveError> {
+        let size = 1u128.checked_shl(u32::from(range.max_prefix_len() - range.prefix_len()));
+        if size.map_or(true, |size| size > max_addresses as u128) {
+            return Err(ResolveErrorKind::Msg(format!(
+                "{range} has more than {max_addresses} addresses"
+            ))
+            .into());
+        }
+
+        let addresses = match range {
+            IpNet::V4(net) => IpAddrRange::from(Ipv4AddrRange::new(net.network(), net.broadcast())),
+            IpNet::V6(net) => IpAddrRange::from(Ipv6AddrRange::new(net.network(), net.broadcast())),
+        };
+        let mut lookups = stream::iter(addresses)
+            .map(|ip| self.reverse_lookup(ip).map(move |lookup| (ip, lookup)))
+            .buffer_unordered(REVERSE_LOOKUP_RANGE_CONCURRENCY);
+
+        let mut names = BTreeMap::new();
+        while let Some((ip, lookup)) = lookups.next().await {
+            match lookup {
+                Ok(lookup) => {
+                    names.insert(ip, lookup.iter().map(|ptr| ptr.0.clone()).collect());
+                }
+                Err(err) if is_no_name(&err) => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(names)
+    }
+
+    /// Looks up the SRV records of the service, and returns the addresses of the targets in the
+    /// order of the connection attempts, with the priority and the weight of their SRV record
+    ///
+    /// The records are ordered by priority, and by a weighted random selection among those of
+    /// the same priority, per [RFC 2782](https://tools.ietf.org/html/rfc2782). The addresses of
+    /// the targets are taken from the glue in the additional section of the response, the other
+    /// targets are looked up like with [`Self::lookup_ip`], the targets which fail to resolve are
+    /// skipped.
+    ///
+    /// The list is empty if the service is decidedly not available, i.e. its target is `.`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the service, e.g. `_sip._udp.example.com.`
+    pub async fn lookup_service(
+        &self,
+        name: impl IntoName,
+    ) -> Result<Vec<(SocketAddr, u16, u16)>, ResolveError> {
+        let lookup = self.srv_lookup(name).await?;
+        let records = lookup
+            .iter()
+            .filter(|srv| !srv.target().is_root())
+            .cloned()
+            .collect::<Vec<_>>();
+        let records = srv_order(records, &mut rand::thread_rng());
+
+        let family = |ip: &IpAddr| match self.options.ip_strategy {
+            LookupIpStrategy::Ipv4Only => ip.is_ipv4(),
+            LookupIpStrategy::Ipv6Only => ip.is_ipv6(),
+            _ => true,
+        };
+        let lookup = &lookup;
+        let addresses = records.iter().map(|srv| async move {
+            let glue = lookup
+                .as_lookup()
+                .records()
+                .iter()
+                .filter(|record| record.name() == srv.target())
+                .filter_map(|record| record.data().ip_addr())
+                .filter(family)
+                .collect::<Vec<_>>();
+            if !glue.is_empty() {
+                return Ok(glue);
+            }
+
+            let ips = self.lookup_ip(srv.target().clone()).await?;
+            Ok::<_, ResolveError>(ips.iter().collect())
+        });
+
+        let mut candidates = Vec::new();
+        let mut last_error = None;
+        for (srv, ips) in records.iter().zip(future::join_all(addresses).await) {
+            match ips {
+                Ok(ips) => candidates.extend(ips.into_iter().map(|ip| {
+                    (
+                        SocketAddr::new(ip, srv.port()),
+                        srv.priority(),
+                        srv.weight(),
+                    )
+                })),
+                Err(err) => {
+                    debug!(target = %srv.target(), %err, "failed to resolve the SRV target");
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        match last_error {
+            Some(err) if candidates.is_empty() => Err(err),
+            _ => Ok(candidates),
+        }
+    }
+
     lookup_fn!(ipv4_lookup, lookup::Ipv4Lookup, RecordType::A);
     lookup_fn!(ipv6_lookup, lookup::Ipv6Lookup, RecordType::AAAA);
     lookup_fn!(mx_lookup, lookup::MxLookup, RecordType::MX);
@@ -393,6 +644,56 @@ impl<P: ConnectionProvider> Resolver<P> {
     lookup_fn!(cert_lookup, lookup::CertLookup, RecordType::CERT);
 }
 
+/// Orders the SRV records for the connection attempts, see
+/// [RFC 2782, "Usage rules"](https://tools.ietf.org/html/rfc2782)
+///
+/// The records are sorted by priority, and those of the same priority are ordered by repeated
+/// weighted random selections, in which the records of weight 0 have a small chance to be selected.
+fn srv_order(mut records: Vec<SRV>, rng: &mut impl Rng) -> Vec<SRV> {
+    // the records of weight 0 are placed at the beginning
+    records.sort_by_key(|srv| (srv.priority(), srv.weight() != 0));
+
+    let mut ordered = Vec::with_capacity(records.len());
+    let mut remaining = records.as_slice();
+    while let Some(first) = remaining.first() {
+        let count = remaining
+            .iter()
+            .take_while(|srv| srv.priority() == first.priority())
+            .count();
+        let (group, rest) = remaining.split_at(count);
+        remaining = rest;
+
+        let mut group = group.to_vec();
+        while !group.is_empty() {
+            let total = group.iter().map(|srv| u32::from(srv.weight())).sum::<u32>();
+            let selected = rng.gen_range(0..=total);
+
+            let mut running = 0;
+            let idx = group
+                .iter()
+                .position(|srv| {
+                    running += u32::from(srv.weight());
+                    running >= selected
+                })
+                .unwrap_or_default();
+            ordered.push(group.remove(idx));
+        }
+    }
+
+    ordered
+}
+
+/// Whether the error reports that the name or its records do not exist
+fn is_no_name(err: &ResolveError) -> bool {
+    matches!(
+        err.proto().map(ProtoError::kind),
+        Some(ProtoErrorKind::NoRecordsFound {
+            response_code: ResponseCode::NoError | ResponseCode::NXDomain,
+            ..
+        })
+    )
+}
+
 impl<P: ConnectionProvider> fmt::Debug for Resolver<P> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Resolver").finish()
@@ -1052,12 +1353,14 @@ pub mod testing {
 #[cfg(feature = "tokio-runtime")]
 #[allow(clippy::extra_unused_type_parameters)]
 mod tests {
+    use std::collections::{HashMap, HashSet};
     use std::net::{IpAddr, Ipv4Addr};
     use std::sync::Mutex;
 
     use futures_executor::block_on;
     use futures_util::stream::once;
     use futures_util::{future, Stream};
+    use rand::SeedableRng;
     use test_support::subscribe;
     use tokio::runtime::Runtime;
 
@@ -1065,9 +1368,10 @@ mod tests {
     use crate::config::{ResolverConfig, ResolverOpts};
     use crate::name_server::GenericConnection;
     use crate::proto::op::Message;
-    use crate::proto::rr::rdata::A;
+    use crate::proto::rr::rdata::{A, PTR};
     use crate::proto::xfer::{DnsRequest, DnsResponse};
     use crate::proto::{ProtoError, ProtoErrorKind};
+    use crate::test_transport::TestTransport;
 
     fn is_send_t<T: Send>() -> bool {
         true
@@ -1173,6 +1477,52 @@ mod tests {
         hosts_lookup_test::<Runtime, TokioConnectionProvider>(io_loop, handle);
     }
 
+    #[test]
+    fn test_hosts_watcher_reload() {
+        use std::{env, fs, process, time::Duration};
+
+        subscribe();
+        let path = env::temp_dir().join(format!("hickory-hosts-watcher-{}", process::id()));
+        fs::write(&path, "10.0.0.1 watched.example.com\n").unwrap();
+
+        let options = ResolverOpts {
+            use_hosts_file: ResolveHosts::Never,
+            ip_strategy: LookupIpStrategy::Ipv4Only,
+            ..ResolverOpts::default()
+        };
+        let mut resolver = Resolver::new(
+            ResolverConfig::from_parts(None, vec![], vec![]),
+            options,
+            TokioConnectionProvider::default(),
+        );
+        resolver.set_hosts_watcher(HostsWatcher::new(&path, Duration::ZERO));
+
+        let io_loop = Runtime::new().expect("failed to create tokio runtime io_loop");
+        let lookup = |resolver: &TokioResolver| {
+            let response = io_loop
+                .block_on(resolver.lookup_ip("watched.example.com"))
+                .expect("failed to run lookup");
+            response.iter().collect::<Vec<_>>()
+        };
+
+        assert_eq!(lookup(&resolver), [IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))]);
+
+        fs::write(&path, "10.0.0.22 watched.example.com\n").unwrap();
+        assert_eq!(lookup(&resolver), [IpAddr::V4(Ipv4Addr::new(10, 0, 0, 22))]);
+
+        // the previous entries are kept while the file is absent
+        fs::remove_file(&path).unwrap();
+        assert_eq!(lookup(&resolver), [IpAddr::V4(Ipv4Addr::new(10, 0, 0, 22))]);
+
+        fs::write(&path, "10.0.0.133 watched.example.com\n").unwrap();
+        assert_eq!(
+            lookup(&resolver),
+            [IpAddr::V4(Ipv4Addr::new(10, 0, 0, 133))]
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
     #[test]
     fn test_fqdn() {
         use super::testing::fqdn_test;
@@ -1284,6 +1634,48 @@ mod tests {
         assert_eq!(name_list.first(), Some(&fqdn));
     }
 
+    #[test]
+    fn test_build_names_ndots() {
+        let handle = TokioConnectionProvider::default();
+        let mut config = ResolverConfig::default();
+        config.add_search(Name::from_ascii("search.domain.").unwrap());
+        config.add_search(Name::from_ascii("other.domain.").unwrap());
+        let options = ResolverOpts {
+            ndots: 1,
+            ..ResolverOpts::default()
+        };
+        let resolver = Resolver::<TokioConnectionProvider>::new(config, options, handle);
+
+        // the names are attempted in the order they are popped from the list
+        let attempts = |name: &str| {
+            let mut names = resolver.build_names(Name::from_ascii(name).unwrap());
+            names.reverse();
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // fewer than ndots dots, the search domains come before the absolute name
+        assert_eq!(
+            attempts("www"),
+            vec!["www.search.domain.", "www.other.domain.", "www."]
+        );
+
+        // at least ndots dots, the absolute name comes first
+        assert_eq!(
+            attempts("www.example"),
+            vec![
+                "www.example.",
+                "www.example.search.domain.",
+                "www.example.other.domain."
+            ]
+        );
+
+        // fully qualified names skip the search entirely
+        assert_eq!(attempts("www.example.com."), vec!["www.example.com."]);
+    }
+
     #[test]
     fn test_build_names_onion() {
         let handle = TokioConnectionProvider::default();
@@ -1401,6 +1793,168 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_reverse_lookup_range() {
+        let transport = TestTransport::new();
+        let ptr = |ip: Ipv4Addr, name: &str| {
+            let ptr_name = Name::from(IpAddr::V4(ip));
+            let name = Name::from_ascii(name).unwrap();
+            transport.answer_records(
+                Query::query(ptr_name.clone(), RecordType::PTR),
+                vec![Record::from_rdata(ptr_name, 300, RData::PTR(PTR(name)))],
+            );
+        };
+        ptr(Ipv4Addr::new(192, 0, 2, 1), "gateway.example.com.");
+        ptr(Ipv4Addr::new(192, 0, 2, 2), "host.example.com.");
+
+        let resolver = Resolver::new(
+            TestTransport::config(),
+            ResolverOpts::default(),
+            transport.clone(),
+        );
+        let range = "192.0.2.0/30".parse().unwrap();
+        let names = resolver.reverse_lookup_range(range, 4).await.unwrap();
+
+        // the addresses without PTR records are left out
+        let name = |name| vec![Name::from_ascii(name).unwrap()];
+        assert_eq!(
+            names.into_iter().collect::<Vec<_>>(),
+            vec![
+                (IpAddr::from([192, 0, 2, 1]), name("gateway.example.com.")),
+                (IpAddr::from([192, 0, 2, 2]), name("host.example.com.")),
+            ]
+        );
+        assert_eq!(transport.queries().len(), 4);
+
+        // ranges larger than the guard are rejected before any query
+        assert!(resolver.reverse_lookup_range(range, 3).await.is_err());
+        let range = "2001:db8::/32".parse().unwrap();
+        assert!(resolver
+            .reverse_lookup_range(range, usize::MAX)
+            .await
+            .is_err());
+        assert_eq!(transport.queries().len(), 4);
+
+        // any other failure fails the lookup
+        transport.servfail(Query::query(
+            Name::from(IpAddr::from([192, 0, 2, 3])),
+            RecordType::PTR,
+        ));
+        resolver.clear_cache();
+        let range = "192.0.2.0/30".parse().unwrap();
+        assert!(resolver.reverse_lookup_range(range, 4).await.is_err());
+    }
+
+    fn srv(priority: u16, weight: u16, target: &str) -> SRV {
+        SRV::new(priority, weight, 5060, Name::from_ascii(target).unwrap())
+    }
+
+    #[test]
+    fn test_srv_order_priority() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(2782);
+        let records = vec![
+            srv(20, 10, "c.example.com."),
+            srv(10, 0, "a.example.com."),
+            srv(30, 50, "d.example.com."),
+            srv(10, 60, "b.example.com."),
+        ];
+
+        for _ in 0..100 {
+            let priorities = srv_order(records.clone(), &mut rng)
+                .iter()
+                .map(SRV::priority)
+                .collect::<Vec<_>>();
+            assert_eq!(priorities, [10, 10, 20, 30]);
+        }
+    }
+
+    #[test]
+    fn test_srv_order_weights() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(2782);
+        let records = vec![
+            srv(10, 90, "a.example.com."),
+            srv(10, 10, "b.example.com."),
+            srv(10, 0, "c.example.com."),
+        ];
+
+        // the first record is selected in proportion to the weights, out of a total weight of 100
+        // and a chance for the records of weight 0
+        let mut first = HashMap::<u16, usize>::new();
+        for _ in 0..10_000 {
+            *first
+                .entry(srv_order(records.clone(), &mut rng)[0].weight())
+                .or_default() += 1;
+        }
+        assert!((8_600..9_200).contains(&first[&90]), "{first:?}");
+        assert!((800..1_200).contains(&first[&10]), "{first:?}");
+        assert!((50..200).contains(&first[&0]), "{first:?}");
+    }
+
+    #[tokio::test]
+    async fn test_lookup_service() {
+        let transport = TestTransport::new();
+        let service = Name::from_ascii("_sip._udp.example.com.").unwrap();
+        let a = |name: &str, ip: [u8; 4]| {
+            Record::from_rdata(
+                Name::from_ascii(name).unwrap(),
+                300,
+                RData::A(A::from(Ipv4Addr::from(ip))),
+            )
+        };
+
+        // the glue of a.example.com. is in the response, b.example.com. is looked up
+        let mut response = Message::new();
+        response.insert_answers(
+            [
+                srv(20, 0, "b.example.com."),
+                srv(10, 0, "a.example.com."),
+                srv(30, 0, "missing.example.com."),
+            ]
+            .into_iter()
+            .map(|srv| Record::from_rdata(service.clone(), 300, RData::SRV(srv)))
+            .collect(),
+        );
+        response.insert_additionals(vec![a("a.example.com.", [192, 0, 2, 1])]);
+        transport.answer(Query::query(service.clone(), RecordType::SRV), response);
+        transport.answer_records(
+            Query::query(Name::from_ascii("b.example.com.").unwrap(), RecordType::A),
+            vec![a("b.example.com.", [192, 0, 2, 2])],
+        );
+
+        let options = ResolverOpts {
+            ip_strategy: LookupIpStrategy::Ipv4Only,
+            ..ResolverOpts::default()
+        };
+        let resolver = Resolver::new(TestTransport::config(), options, transport.clone());
+        let candidates = resolver.lookup_service(service.clone()).await.unwrap();
+        assert_eq!(
+            candidates,
+            [
+                (SocketAddr::from(([192, 0, 2, 1], 5060)), 10, 0),
+                (SocketAddr::from(([192, 0, 2, 2], 5060)), 20, 0),
+            ]
+        );
+        let looked_up = transport
+            .queries()
+            .into_iter()
+            .filter(|(_, query)| query.query_type() == RecordType::A)
+            .map(|(_, query)| query.name().to_ascii())
+            .collect::<HashSet<_>>();
+        assert!(!looked_up.contains("a.example.com."));
+
+        // the target `.` means that the service is not available
+        let service = Name::from_ascii("_xmpp._tcp.example.com.").unwrap();
+        transport.answer_records(
+            Query::query(service.clone(), RecordType::SRV),
+            vec![Record::from_rdata(
+                service.clone(),
+                300,
+                RData::SRV(srv(0, 0, ".")),
+            )],
+        );
+        assert!(resolver.lookup_service(service).await.unwrap().is_empty());
+    }
+
     #[derive(Clone)]
     struct MockDnsHandle {
         messages: Arc<Mutex<Vec<Result<DnsResponse, ProtoError>>>>,
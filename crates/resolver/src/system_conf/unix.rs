@@ -20,14 +20,38 @@ use std::str::FromStr;
 use std::time::Duration;
 
 use resolv_conf;
+use tracing::warn;
 
-use crate::config::{NameServerConfig, ResolverConfig, ResolverOpts};
+use crate::config::{
+    LookupIpStrategy, NameServerConfig, ResolverConfig, ResolverOpts, ServerOrderingStrategy,
+};
 use crate::proto::rr::Name;
 use crate::proto::xfer::Protocol;
 use crate::ResolveError;
 
 const DEFAULT_PORT: u16 = 53;
 
+/// The options of resolv.conf known to its parser, any other option is ignored
+const KNOWN_OPTIONS: &[&str] = &[
+    "attempts",
+    "debug",
+    "edns0",
+    "inet6",
+    "ip6-bytestring",
+    "ip6-dotint",
+    "ndots",
+    "no-check-names",
+    "no-ip6-dotint",
+    "no-reload",
+    "no-tld-query",
+    "rotate",
+    "single-request",
+    "single-request-reopen",
+    "timeout",
+    "trust-ad",
+    "use-vc",
+];
+
 pub fn read_system_conf() -> Result<(ResolverConfig, ResolverOpts), ResolveError> {
     read_resolv_conf("/etc/resolv.conf")
 }
@@ -44,7 +68,8 @@ fn read_resolv_conf<P: AsRef<Path>>(
 pub fn parse_resolv_conf<T: AsRef<[u8]>>(
     data: T,
 ) -> Result<(ResolverConfig, ResolverOpts), ResolveError> {
-    let parsed_conf = resolv_conf::Config::parse(&data).map_err(|e| {
+    let data = without_unknown_options(data.as_ref());
+    let parsed_conf = resolv_conf::Config::parse(data).map_err(|e| {
         io::Error::new(
             io::ErrorKind::Other,
             format!("Error parsing resolv.conf: {e}"),
@@ -53,6 +78,39 @@ pub fn parse_resolv_conf<T: AsRef<[u8]>>(
     into_resolver_config(parsed_conf)
 }
 
+/// Removes the unknown options from the `options` lines, they are ignored with a warning
+///
+/// The comments of the `options` lines are removed as well.
+fn without_unknown_options(data: &[u8]) -> Vec<u8> {
+    let lines = data.split(|b| *b == b'\n').map(|line| {
+        let Ok(text) = std::str::from_utf8(line) else {
+            return line.to_vec();
+        };
+
+        let mut words = text
+            .split([';', '#'])
+            .next()
+            .unwrap_or_default()
+            .split_whitespace();
+        if words.next() != Some("options") {
+            return line.to_vec();
+        }
+
+        let known = words.filter(|option| {
+            let name = option.split(':').next().unwrap_or_default();
+            let known = KNOWN_OPTIONS.contains(&name);
+            if !known {
+                warn!("ignoring unknown option in resolv.conf: {option}");
+            }
+            known
+        });
+        let options = Some("options").into_iter().chain(known);
+        options.collect::<Vec<_>>().join(" ").into_bytes()
+    });
+
+    lines.collect::<Vec<_>>().join(&b'\n')
+}
+
 // TODO: use a custom parsing error type maybe?
 fn into_resolver_config(
     parsed_config: resolv_conf::Config,
@@ -67,19 +125,21 @@ fn into_resolver_config(
         None
     };
 
-    // nameservers
+    // nameservers, only over TCP with `use-vc`
     let mut nameservers = Vec::<NameServerConfig>::with_capacity(parsed_config.nameservers.len());
     for ip in &parsed_config.nameservers {
-        nameservers.push(NameServerConfig {
-            socket_addr: SocketAddr::new(ip.into(), DEFAULT_PORT),
-            protocol: Protocol::Udp,
-            tls_dns_name: None,
-            http_endpoint: None,
-            trust_negative_responses: false,
-            #[cfg(feature = "dns-over-rustls")]
-            tls_config: None,
-            bind_addr: None,
-        });
+        if !parsed_config.use_vc {
+            nameservers.push(NameServerConfig {
+                socket_addr: SocketAddr::new(ip.into(), DEFAULT_PORT),
+                protocol: Protocol::Udp,
+                tls_dns_name: None,
+                http_endpoint: None,
+                trust_negative_responses: false,
+                #[cfg(feature = "dns-over-rustls")]
+                tls_config: None,
+                bind_addr: None,
+            });
+        }
         nameservers.push(NameServerConfig {
             socket_addr: SocketAddr::new(ip.into(), DEFAULT_PORT),
             protocol: Protocol::Tcp,
@@ -116,12 +176,23 @@ fn into_resolver_config(
 
     let config = ResolverConfig::from_parts(domain, search, nameservers);
 
-    let options = ResolverOpts {
+    let mut options = ResolverOpts {
         ndots: parsed_config.ndots as usize,
+        no_tld_query: parsed_config.no_tld_query,
         timeout: Duration::from_secs(u64::from(parsed_config.timeout)),
         attempts: parsed_config.attempts as usize,
+        check_names: !parsed_config.no_check_names,
+        edns0: parsed_config.edns0,
+        single_request: parsed_config.single_request || parsed_config.single_request_reopen,
+        authentic_data: parsed_config.trust_ad,
         ..ResolverOpts::default()
     };
+    if parsed_config.rotate {
+        options.server_ordering_strategy = ServerOrderingStrategy::RoundRobin;
+    }
+    if parsed_config.inet6 {
+        options.ip_strategy = LookupIpStrategy::Ipv6thenIpv4;
+    }
 
     Ok((config, options))
 }
@@ -242,6 +313,42 @@ mod tests {
         read_resolv_conf(format!("{}/resolv.conf-linux", tests_dir())).expect("linux failed");
     }
 
+    #[test]
+    fn test_options() {
+        let parsed = parse_resolv_conf(
+            "nameserver 127.0.0.1\n\
+             options attempts:4 timeout:1 rotate no-tld-query unknown:1 # comment\n\
+             options single-request use-vc\n",
+        )
+        .expect("failed");
+
+        let opts = parsed.1;
+        assert_eq!(opts.attempts, 4);
+        assert_eq!(opts.timeout, Duration::from_secs(1));
+        assert_eq!(
+            opts.server_ordering_strategy,
+            ServerOrderingStrategy::RoundRobin
+        );
+        assert!(opts.no_tld_query);
+        assert!(opts.single_request);
+        assert_eq!(opts.ndots, 1);
+
+        // use-vc only connects over TCP
+        let protocols = parsed.0.name_servers().iter().map(|ns| ns.protocol);
+        assert_eq!(protocols.collect::<Vec<_>>(), [Protocol::Tcp]);
+    }
+
+    #[test]
+    fn test_unknown_options() {
+        let parsed =
+            parse_resolv_conf("options foo bar:1 ndots:3\nnameserver 127.0.0.1").expect("failed");
+        assert_eq!(parsed.1.ndots, 3);
+        assert_eq!(parsed.0.name_servers().len(), 2);
+
+        // invalid values of known options are still rejected
+        assert!(parse_resolv_conf("options ndots:x\nnameserver 127.0.0.1").is_err());
+    }
+
     /// Validate that all options set in `into_resolver_config()` are at default values
     fn is_default_opts(opts: ResolverOpts) {
         assert_eq!(opts.ndots, 1);
@@ -0,0 +1,327 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! An in-memory transport for testing code that uses the resolver, without network access
+//!
+//! The transport is available with the `testing` feature, which downstream crates can enable for
+//! their dev-dependencies.
+//!
+//! ```rust
+//! use std::net::{IpAddr, Ipv4Addr};
+//!
+//! use hickory_resolver::config::{LookupIpStrategy, ResolverConfig, ResolverOpts};
+//! use hickory_resolver::proto::op::Query;
+//! use hickory_resolver::proto::rr::{rdata::A, Name, RData, Record, RecordType};
+//! use hickory_resolver::test_transport::TestTransport;
+//! use hickory_resolver::Resolver;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let name = Name::from_ascii("www.example.com.").unwrap();
+//! let transport = TestTransport::new();
+//! transport.answer_records(
+//!     Query::query(name.clone(), RecordType::A),
+//!     vec![Record::from_rdata(name, 300, RData::A(A::new(192, 0, 2, 1)))],
+//! );
+//!
+//! let mut options = ResolverOpts::default();
+//! options.ip_strategy = LookupIpStrategy::Ipv4Only;
+//! let resolver = Resolver::new(TestTransport::config(), options, transport);
+//!
+//! let lookup = resolver.lookup_ip("www.example.com.").await.unwrap();
+//! assert_eq!(
+//!     lookup.iter().collect::<Vec<_>>(),
+//!     [IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))]
+//! );
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::future::{self, Future};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr};
+use std::pin::Pin;
+use std::sync::Arc;
+
+use futures_util::stream::{once, Stream};
+use parking_lot::Mutex;
+
+use crate::config::{NameServerConfig, NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use crate::name_server::ConnectionProvider;
+use crate::proto::op::{Message, MessageType, Query, ResponseCode};
+use crate::proto::rr::Record;
+use crate::proto::runtime::{TokioHandle, TokioRuntimeProvider};
+use crate::proto::xfer::{DnsHandle, DnsRequest, DnsResponse, Protocol};
+use crate::proto::{ProtoError, ProtoErrorKind};
+
+/// The programmed behaviour of a [`TestTransport`] for a query
+#[derive(Clone, Debug)]
+enum TestResponse {
+    Message(Message),
+    Truncated(Message),
+    ServFail,
+    Timeout,
+}
+
+/// A [`DnsHandle`] answering queries from a table of canned responses
+///
+/// Each query is looked up in the table, and answered with the response programmed for it. The
+/// id, the queries and the flags of the request are copied into the response. Queries without an
+/// entry are answered with NXDOMAIN. Names of the queries are compared like those sent by the
+/// resolver, so they must be fully qualified.
+///
+/// `TestTransport` is also a [`ConnectionProvider`], all connections made by a resolver created
+/// with it share the same table. Clones of the transport share the table as well, so the
+/// responses may be changed while the resolver is in use.
+#[derive(Clone)]
+pub struct TestTransport {
+    responses: Arc<Mutex<HashMap<Query, TestResponse>>>,
+    queries: Arc<Mutex<Vec<(Protocol, Query)>>>,
+    protocol: Protocol,
+}
+
+impl TestTransport {
+    /// Creates a transport with an empty table of responses
+    pub fn new() -> Self {
+        Self {
+            responses: Arc::default(),
+            queries: Arc::default(),
+            protocol: Protocol::Udp,
+        }
+    }
+
+    /// A configuration with a single name server, reachable over UDP and TCP, to use with a
+    /// resolver driven by the transport
+    pub fn config() -> ResolverConfig {
+        ResolverConfig::from_parts(
+            None,
+            vec![],
+            NameServerConfigGroup::from_ips_clear(&[IpAddr::V4(Ipv4Addr::LOCALHOST)], 53, true),
+        )
+    }
+
+    /// Answers `query` with `response`
+    pub fn answer(&self, query: Query, response: Message) {
+        self.responses
+            .lock()
+            .insert(query, TestResponse::Message(response));
+    }
+
+    /// Answers `query` with a NOERROR response carrying `answers`
+    pub fn answer_records(&self, query: Query, answers: Vec<Record>) {
+        let mut response = Message::new();
+        response.insert_answers(answers);
+        self.answer(query, response);
+    }
+
+    /// Answers `query` over UDP with a truncated response without records, and with `response`
+    /// over the other protocols, e.g. the retry over TCP
+    pub fn truncate(&self, query: Query, response: Message) {
+        self.responses
+            .lock()
+            .insert(query, TestResponse::Truncated(response));
+    }
+
+    /// Answers `query` with SERVFAIL
+    pub fn servfail(&self, query: Query) {
+        self.responses.lock().insert(query, TestResponse::ServFail);
+    }
+
+    /// Fails `query` with a timeout error, as a transport does when no response is received in
+    /// time
+    pub fn timeout(&self, query: Query) {
+        self.responses.lock().insert(query, TestResponse::Timeout);
+    }
+
+    /// Returns the queries received so far, with the protocol they were sent over
+    pub fn queries(&self) -> Vec<(Protocol, Query)> {
+        self.queries.lock().clone()
+    }
+
+    fn respond(&self, request: &DnsRequest) -> Result<DnsResponse, ProtoError> {
+        let query = request.queries().first().cloned().unwrap_or_default();
+        self.queries.lock().push((self.protocol, query.clone()));
+
+        let mut response = match self.responses.lock().get(&query).cloned() {
+            Some(TestResponse::Message(response)) => response,
+            Some(TestResponse::Truncated(response)) if self.protocol != Protocol::Udp => response,
+            Some(TestResponse::Truncated(_)) => {
+                let mut response = Message::new();
+                response.set_truncated(true);
+                response
+            }
+            Some(TestResponse::ServFail) => {
+                Message::error_msg(request.id(), request.op_code(), ResponseCode::ServFail)
+            }
+            Some(TestResponse::Timeout) => return Err(ProtoErrorKind::Timeout.into()),
+            None => Message::error_msg(request.id(), request.op_code(), ResponseCode::NXDomain),
+        };
+
+        response
+            .set_id(request.id())
+            .set_message_type(MessageType::Response)
+            .set_op_code(request.op_code())
+            .set_recursion_desired(request.recursion_desired())
+            .set_recursion_available(true);
+        if response.queries().is_empty() {
+            response.add_queries(request.queries().to_vec());
+        }
+        DnsResponse::from_message(response)
+    }
+}
+
+impl Default for TestTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DnsHandle for TestTransport {
+    type Response = Pin<Box<dyn Stream<Item = Result<DnsResponse, ProtoError>> + Send>>;
+
+    fn send<R: Into<DnsRequest> + Unpin + Send + 'static>(&self, request: R) -> Self::Response {
+        let response = self.respond(&request.into());
+        Box::pin(once(future::ready(response)))
+    }
+}
+
+impl ConnectionProvider for TestTransport {
+    type Conn = Self;
+    type FutureConn = Pin<Box<dyn Future<Output = Result<Self, ProtoError>> + Send>>;
+    type RuntimeProvider = TokioRuntimeProvider;
+
+    fn new_connection(
+        &self,
+        config: &NameServerConfig,
+        _options: &ResolverOpts,
+    ) -> Result<Self::FutureConn, io::Error> {
+        let mut conn = self.clone();
+        conn.protocol = config.protocol;
+        Ok(Box::pin(future::ready(Ok(conn))))
+    }
+
+    fn create_handle(&self) -> TokioHandle {
+        TokioHandle::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv6Addr;
+
+    use super::*;
+    use crate::config::LookupIpStrategy;
+    use crate::proto::rr::rdata::{A, AAAA};
+    use crate::proto::rr::{Name, RData, RecordType};
+    use crate::Resolver;
+
+    fn name() -> Name {
+        Name::from_ascii("www.example.com.").unwrap()
+    }
+
+    fn resolver(transport: &TestTransport) -> Resolver<TestTransport> {
+        let options = ResolverOpts {
+            ip_strategy: LookupIpStrategy::Ipv4AndIpv6,
+            ..ResolverOpts::default()
+        };
+        Resolver::new(TestTransport::config(), options, transport.clone())
+    }
+
+    #[tokio::test]
+    async fn test_canned_answers() {
+        let transport = TestTransport::new();
+        transport.answer_records(
+            Query::query(name(), RecordType::A),
+            vec![Record::from_rdata(
+                name(),
+                300,
+                RData::A(A::new(192, 0, 2, 1)),
+            )],
+        );
+        transport.answer_records(
+            Query::query(name(), RecordType::AAAA),
+            vec![Record::from_rdata(
+                name(),
+                300,
+                RData::AAAA(AAAA::from(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1))),
+            )],
+        );
+
+        let lookup = resolver(&transport).lookup_ip(name()).await.unwrap();
+        let mut addresses = lookup.iter().collect::<Vec<_>>();
+        addresses.sort();
+        assert_eq!(
+            addresses,
+            [
+                IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+                IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+            ]
+        );
+
+        // names without an entry do not exist
+        let error = resolver(&transport)
+            .lookup(
+                Name::from_ascii("missing.example.com.").unwrap(),
+                RecordType::A,
+            )
+            .await
+            .unwrap_err();
+        assert!(error.is_nx_domain());
+    }
+
+    #[tokio::test]
+    async fn test_servfail_and_timeout() {
+        let transport = TestTransport::new();
+        transport.servfail(Query::query(name(), RecordType::A));
+        transport.timeout(Query::query(name(), RecordType::MX));
+
+        let resolver = resolver(&transport);
+        let error = resolver.lookup(name(), RecordType::A).await.unwrap_err();
+        assert!(matches!(
+            error.proto().map(ProtoError::kind),
+            Some(ProtoErrorKind::NoRecordsFound {
+                response_code: ResponseCode::ServFail,
+                ..
+            })
+        ));
+
+        let error = resolver.lookup(name(), RecordType::MX).await.unwrap_err();
+        assert!(matches!(
+            error.proto().map(ProtoError::kind),
+            Some(ProtoErrorKind::Timeout)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_truncation() {
+        let transport = TestTransport::new();
+        let mut response = Message::new();
+        response.add_answer(Record::from_rdata(
+            name(),
+            300,
+            RData::A(A::new(192, 0, 2, 1)),
+        ));
+        transport.truncate(Query::query(name(), RecordType::A), response);
+
+        let lookup = resolver(&transport)
+            .lookup(name(), RecordType::A)
+            .await
+            .unwrap();
+        assert_eq!(
+            lookup.record_iter().next().map(Record::data),
+            Some(&RData::A(A::new(192, 0, 2, 1)))
+        );
+
+        // the truncated response over UDP is retried over TCP
+        let protocols = transport
+            .queries()
+            .into_iter()
+            .map(|(protocol, _)| protocol)
+            .collect::<Vec<_>>();
+        assert_eq!(protocols, [Protocol::Udp, Protocol::Tcp]);
+    }
+}
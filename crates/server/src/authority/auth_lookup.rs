@@ -48,6 +48,9 @@ pub enum AuthLookup {
         /// The last SOA record of an AXFR (matches the first)
         end_soa: LookupRecords,
     },
+    /// An ixfr starts with the current soa, followed by the differences between versions, then
+    /// another soa, or contains only the soa if the zone is up to date
+    IXFR(Vec<Record>),
 }
 
 impl AuthLookup {
@@ -93,6 +96,34 @@ impl AuthLookup {
             _ => None,
         }
     }
+
+    /// Replaces the RecordSets of the lookup, e.g. with signed ones
+    ///
+    /// The records of an IXFR are not RecordSets, and are kept as is.
+    #[cfg(feature = "dnssec-ring")]
+    pub(crate) fn map_rrsets(self, f: &mut impl FnMut(Arc<RecordSet>) -> Arc<RecordSet>) -> Self {
+        match self {
+            Self::Empty => Self::Empty,
+            Self::Records {
+                answers,
+                additionals,
+            } => Self::Records {
+                answers: answers.map_rrsets(f),
+                additionals: additionals.map(|additionals| additionals.map_rrsets(f)),
+            },
+            Self::SOA(soa) => Self::SOA(soa.map_rrsets(f)),
+            Self::AXFR {
+                start_soa,
+                records,
+                end_soa,
+            } => Self::AXFR {
+                start_soa: start_soa.map_rrsets(f),
+                records: records.map_rrsets(f),
+                end_soa: end_soa.map_rrsets(f),
+            },
+            Self::IXFR(records) => Self::IXFR(records),
+        }
+    }
 }
 
 impl LookupObject for AuthLookup {
@@ -152,6 +183,7 @@ impl<'a> IntoIterator for &'a AuthLookup {
                 records,
                 end_soa,
             } => AuthLookupIter::AXFR(start_soa.into_iter().chain(records).chain(end_soa)),
+            AuthLookup::IXFR(records) => AuthLookupIter::IXFR(records.iter()),
         }
     }
 }
@@ -167,6 +199,8 @@ pub enum AuthLookupIter<'r> {
     Records(LookupRecordsIter<'r>),
     /// An iteration over an AXFR
     AXFR(Chain<Chain<LookupRecordsIter<'r>, LookupRecordsIter<'r>>, LookupRecordsIter<'r>>),
+    /// An iteration over an IXFR
+    IXFR(Iter<'r, Record>),
 }
 
 impl<'r> Iterator for AuthLookupIter<'r> {
@@ -177,6 +211,7 @@ impl<'r> Iterator for AuthLookupIter<'r> {
             AuthLookupIter::Empty => None,
             AuthLookupIter::Records(i) => i.next(),
             AuthLookupIter::AXFR(i) => i.next(),
+            AuthLookupIter::IXFR(i) => i.next(),
         }
     }
 }
@@ -226,6 +261,24 @@ impl AnyRecords {
     fn iter(&self) -> AnyRecordsIter<'_> {
         self.into_iter()
     }
+
+    /// Replaces the RecordSets which are part of the result, see `LookupRecords::map_rrsets`
+    #[cfg(feature = "dnssec-ring")]
+    fn map_rrsets(mut self, f: &mut impl FnMut(Arc<RecordSet>) -> Arc<RecordSet>) -> Self {
+        let (query_type, query_name) = (self.query_type, &self.query_name);
+        self.rrsets = self
+            .rrsets
+            .into_iter()
+            .map(|rrset| {
+                if query_type == RecordType::AXFR || LowerName::from(rrset.name()) == *query_name {
+                    f(rrset)
+                } else {
+                    rrset
+                }
+            })
+            .collect();
+        self
+    }
 }
 
 impl<'r> IntoIterator for &'r AnyRecords {
@@ -349,6 +402,25 @@ impl LookupRecords {
     pub fn iter(&self) -> LookupRecordsIter<'_> {
         self.into_iter()
     }
+
+    /// Replaces the RecordSets of the lookup, e.g. with signed ones
+    #[cfg(feature = "dnssec-ring")]
+    pub(crate) fn map_rrsets(self, f: &mut impl FnMut(Arc<RecordSet>) -> Arc<RecordSet>) -> Self {
+        match self {
+            Self::Empty => Self::Empty,
+            Self::Records {
+                lookup_options,
+                records,
+            } => Self::Records {
+                lookup_options,
+                records: f(records),
+            },
+            Self::ManyRecords(lookup_options, records) => {
+                Self::ManyRecords(lookup_options, records.into_iter().map(f).collect())
+            }
+            Self::AnyRecords(records) => Self::AnyRecords(records.map_rrsets(f)),
+        }
+    }
 }
 
 impl Default for LookupRecords {
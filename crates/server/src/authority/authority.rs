@@ -12,7 +12,10 @@ use std::fmt;
 
 use crate::{
     authority::{LookupError, LookupObject, MessageRequest, UpdateResult, ZoneType},
-    proto::rr::{LowerName, RecordSet, RecordType, RrsetRecords},
+    proto::{
+        op::LowerQuery,
+        rr::{LowerName, RecordSet, RecordType, RrsetRecords},
+    },
     server::RequestInfo,
 };
 #[cfg(feature = "dnssec-ring")]
@@ -20,8 +23,8 @@ use crate::{
     dnssec::NxProofKind,
     proto::{
         dnssec::{
-            rdata::key::KEY, ring::Digest, DnsSecResult, Nsec3HashAlgorithm, SigSigner,
-            SupportedAlgorithms,
+            rdata::key::KEY, ring::Digest, tsig::TSigner, DnsSecResult, Nsec3HashAlgorithm,
+            SigSigner, SupportedAlgorithms,
         },
         rr::Name,
         ProtoError,
@@ -117,6 +120,22 @@ pub trait Authority: Send + Sync {
     /// Perform a dynamic update of a zone
     async fn update(&self, update: &MessageRequest) -> UpdateResult<bool>;
 
+    /// Returns the TSIG keys allowed to authenticate updates and zone transfers
+    ///
+    /// Zone transfers of an authority with TSIG keys must be signed with one of them.
+    #[cfg(feature = "dnssec-ring")]
+    fn tsig_signers(&self) -> &[TSigner] {
+        &[]
+    }
+
+    /// Returns the TSIG key with the given name, if it is allowed to authenticate requests
+    #[cfg(feature = "dnssec-ring")]
+    fn tsig_signer(&self, key_name: &Name) -> Option<&TSigner> {
+        self.tsig_signers()
+            .iter()
+            .find(|signer| signer.signer_name() == key_name)
+    }
+
     /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
     fn origin(&self) -> &LowerName;
 
@@ -194,6 +213,37 @@ pub trait Authority: Send + Sync {
         lookup_options: LookupOptions,
     ) -> LookupControlFlow<Self::Lookup>;
 
+    /// Using the specified IXFR query, return the changes made to the zone since the version with
+    /// the given serial, see [RFC 1995](https://www.rfc-editor.org/rfc/rfc1995).
+    ///
+    /// # Arguments
+    ///
+    /// * `request_info` - the IXFR query.
+    /// * `serial` - the serial of the SOA in the authority section of the request.
+    /// * `lookup_options` - Query-related lookup options (e.g., DNSSEC DO bit, supported hash
+    ///                      algorithms, etc.)
+    ///
+    /// Authorities which do not keep track of the changes to their zone respond with the full
+    /// zone, which is what the default implementation does by performing an AXFR search.
+    ///
+    /// # Return value
+    ///
+    /// A LookupControlFlow containing the lookup that should be returned to the client.
+    async fn ixfr(
+        &self,
+        request_info: RequestInfo<'_>,
+        _serial: u32,
+        lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Self::Lookup> {
+        let query = axfr_query(request_info.query);
+        let request_info = RequestInfo {
+            query: &query,
+            ..request_info
+        };
+
+        self.search(request_info, lookup_options).await
+    }
+
     /// Get the NS, NameServer, record for the zone
     async fn ns(&self, lookup_options: LookupOptions) -> LookupControlFlow<Self::Lookup> {
         self.lookup(self.origin(), RecordType::NS, lookup_options)
@@ -243,6 +293,13 @@ pub trait Authority: Send + Sync {
     fn nx_proof_kind(&self) -> Option<&NxProofKind>;
 }
 
+/// Returns an AXFR query for the zone of an IXFR query
+pub(crate) fn axfr_query(ixfr_query: &LowerQuery) -> LowerQuery {
+    let mut query = ixfr_query.original().clone();
+    query.set_query_type(RecordType::AXFR);
+    LowerQuery::query(query)
+}
+
 /// Extension to Authority to allow for DNSSEC features
 #[cfg(feature = "dnssec-ring")]
 #[async_trait::async_trait]
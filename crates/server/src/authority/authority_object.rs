@@ -10,13 +10,17 @@
 use tracing::debug;
 
 #[cfg(feature = "dnssec-ring")]
-use crate::{authority::Nsec3QueryInfo, dnssec::NxProofKind};
+use crate::{
+    authority::Nsec3QueryInfo,
+    dnssec::NxProofKind,
+    proto::{dnssec::tsig::TSigner, rr::Name},
+};
 use crate::{
     authority::{
         Authority, LookupControlFlow, LookupOptions, MessageRequest, UpdateResult, ZoneType,
     },
     proto::rr::{LowerName, Record, RecordType},
-    server::RequestInfo,
+    server::{CacheStatus, RequestInfo},
 };
 
 /// An Object safe Authority
@@ -34,6 +38,14 @@ pub trait AuthorityObject: Send + Sync {
     /// Perform a dynamic update of a zone
     async fn update(&self, update: &MessageRequest) -> UpdateResult<bool>;
 
+    /// Returns the TSIG keys allowed to authenticate updates and zone transfers
+    #[cfg(feature = "dnssec-ring")]
+    fn tsig_signers(&self) -> &[TSigner];
+
+    /// Returns the TSIG key with the given name, if it is allowed to authenticate requests
+    #[cfg(feature = "dnssec-ring")]
+    fn tsig_signer(&self, key_name: &Name) -> Option<&TSigner>;
+
     /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
     fn origin(&self) -> &LowerName;
 
@@ -109,6 +121,26 @@ pub trait AuthorityObject: Send + Sync {
         lookup_options: LookupOptions,
     ) -> LookupControlFlow<Box<dyn LookupObject>>;
 
+    /// Using the specified IXFR query, return the changes made to the zone since the version with
+    /// the given serial, see [RFC 1995](https://www.rfc-editor.org/rfc/rfc1995).
+    ///
+    /// # Arguments
+    ///
+    /// * `request_info` - the IXFR query.
+    /// * `serial` - the serial of the SOA in the authority section of the request.
+    /// * `lookup_options` - Query-related lookup options (e.g., DNSSEC DO bit, supported hash
+    ///                      algorithms, etc.)
+    ///
+    /// # Return value
+    ///
+    /// A LookupControlFlow containing the lookup that should be returned to the client.
+    async fn ixfr(
+        &self,
+        request_info: RequestInfo<'_>,
+        serial: u32,
+        lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Box<dyn LookupObject>>;
+
     /// Get the NS, NameServer, record for the zone
     async fn ns(&self, lookup_options: LookupOptions) -> LookupControlFlow<Box<dyn LookupObject>> {
         self.lookup(self.origin(), RecordType::NS, lookup_options)
@@ -187,6 +219,18 @@ where
         Authority::update(self, update).await
     }
 
+    /// Returns the TSIG keys allowed to authenticate updates and zone transfers
+    #[cfg(feature = "dnssec-ring")]
+    fn tsig_signers(&self) -> &[TSigner] {
+        Authority::tsig_signers(self)
+    }
+
+    /// Returns the TSIG key with the given name, if it is allowed to authenticate requests
+    #[cfg(feature = "dnssec-ring")]
+    fn tsig_signer(&self, key_name: &Name) -> Option<&TSigner> {
+        Authority::tsig_signer(self, key_name)
+    }
+
     /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
     fn origin(&self) -> &LowerName {
         Authority::origin(self)
@@ -275,6 +319,31 @@ where
             .map_dyn()
     }
 
+    /// Using the specified IXFR query, return the changes made to the zone since the version with
+    /// the given serial, see [RFC 1995](https://www.rfc-editor.org/rfc/rfc1995).
+    ///
+    /// # Arguments
+    ///
+    /// * `request_info` - the IXFR query.
+    /// * `serial` - the serial of the SOA in the authority section of the request.
+    /// * `lookup_options` - Query-related lookup options (e.g., DNSSEC DO bit, supported hash
+    ///                      algorithms, etc.)
+    ///
+    /// # Return value
+    ///
+    /// A LookupControlFlow containing the lookup that should be returned to the client.
+    async fn ixfr(
+        &self,
+        request_info: RequestInfo<'_>,
+        serial: u32,
+        lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Box<dyn LookupObject>> {
+        debug!("performing {} on {}", request_info.query, self.origin());
+        Authority::ixfr(self, request_info, serial, lookup_options)
+            .await
+            .map_dyn()
+    }
+
     /// Return the NSEC records based on the given name
     ///
     /// # Arguments
@@ -340,6 +409,12 @@ pub trait LookupObject: Send {
     fn dnssec_summary(&self) -> DnssecSummary {
         DnssecSummary::Insecure
     }
+
+    /// Whether the records were answered from a cache, `None` if they were not looked up through
+    /// a cache, e.g. from a zone
+    fn cache_status(&self) -> Option<CacheStatus> {
+        None
+    }
 }
 
 /// A lookup that returns no records
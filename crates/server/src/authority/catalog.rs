@@ -8,7 +8,13 @@
 // TODO, I've implemented this as a separate entity from the cache, but I wonder if the cache
 //  should be the only "front-end" for lookups, where if that misses, then we go to the catalog
 //  then, if requested, do a recursive lookup... i.e. the catalog would only point to files.
-use std::{borrow::Borrow, collections::HashMap, io, sync::Arc};
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    io,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use cfg_if::cfg_if;
 use tracing::{debug, error, info, trace, warn};
@@ -18,27 +24,55 @@ use crate::{
     authority::Nsec3QueryInfo,
     dnssec::NxProofKind,
     proto::{
-        dnssec::SupportedAlgorithms,
-        rr::rdata::opt::{EdnsCode, EdnsOption},
+        dnssec::{
+            rdata::{
+                tsig::{make_tsig_record, TSIG},
+                DNSSECRData,
+            },
+            SupportedAlgorithms,
+        },
+        serialize::binary::BinEncoder,
+        ProtoError,
     },
 };
 use crate::{
     authority::{
         authority_object::DnssecSummary, AuthLookup, AuthorityObject, EmptyLookup,
         LookupControlFlow, LookupError, LookupObject, LookupOptions, LookupRecords,
-        MessageResponse, MessageResponseBuilder, ZoneType,
+        MessageResponse, MessageResponseBuilder, Notifier, ZoneRefresh, ZoneType,
     },
     proto::{
         op::{Edns, Header, LowerQuery, MessageType, OpCode, ResponseCode},
-        rr::{LowerName, Record, RecordSet, RecordType},
+        rr::{
+            rdata::{
+                opt::{EdnsCode, EdnsOption},
+                HINFO, SOA,
+            },
+            LowerName, Name, RData, Record, RecordSet, RecordType,
+        },
+        xfer::Protocol,
+    },
+    server::{
+        AccessLists, DnsCookies, Request, RequestHandler, RequestInfo, ResponseHandler,
+        ResponseInfo, UnverifiedResponseHandler,
     },
-    server::{Request, RequestHandler, RequestInfo, ResponseHandler, ResponseInfo},
 };
 
 /// Set of authorities, zones, available to this server.
-#[derive(Default)]
+#[derive(Clone)]
 pub struct Catalog {
     authorities: HashMap<LowerName, Vec<Arc<dyn AuthorityObject>>>,
+    cookies: Option<DnsCookies>,
+    access_lists: AccessLists,
+    notifiers: HashMap<LowerName, Notifier>,
+    zone_refreshes: HashMap<LowerName, ZoneRefresh>,
+    refuse_any: bool,
+}
+
+impl Default for Catalog {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[allow(unused_mut, unused_variables)]
@@ -61,6 +95,102 @@ async fn send_response<'a, R: ResponseHandler>(
     response_handle.send_response(response).await
 }
 
+/// Creates the TSIG record for the response to a TSIG signed update or zone transfer
+///
+/// [RFC 8945](https://tools.ietf.org/html/rfc8945#section-5.3), the response is signed with the
+/// key of the request, using the MAC of the request. If the key is unknown or the MAC of the
+/// request did not verify, the `NOTAUTH` response carries an unsigned TSIG with the error.
+/// `emit` encodes the response as it is sent, without the TSIG record.
+#[cfg(feature = "dnssec-ring")]
+fn response_tsig(
+    authority: &dyn AuthorityObject,
+    request: &Request,
+    response_header: &mut Header,
+    response_edns: Option<&Edns>,
+    emit: impl FnOnce(
+        MessageResponseBuilder<'_>,
+        Header,
+        &mut BinEncoder<'_>,
+    ) -> Result<ResponseInfo, ProtoError>,
+) -> Option<Record> {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let request_tsig = request.tsig()?;
+    let Some(DNSSECRData::TSIG(tsig)) = request_tsig.data().as_dnssec() else {
+        return None;
+    };
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs());
+    let signer = authority.tsig_signer(request_tsig.name());
+    let error = match (response_header.response_code(), signer) {
+        (code @ (ResponseCode::BADKEY | ResponseCode::BADSIG | ResponseCode::BADTIME), _) => code,
+        // the authority rejected the request before checking the key
+        (_, None) => ResponseCode::BADKEY,
+        (_, Some(_)) => ResponseCode::NoError,
+    };
+    if error != ResponseCode::NoError {
+        response_header.set_response_code(ResponseCode::NotAuth);
+    }
+
+    let signer = match signer {
+        Some(signer) if error == ResponseCode::NoError || error == ResponseCode::BADTIME => signer,
+        _ => {
+            let error_tsig = TSIG::new(
+                tsig.algorithm().clone(),
+                now,
+                tsig.fudge(),
+                Vec::new(),
+                request.id(),
+                error.into(),
+                Vec::new(),
+            );
+            return Some(make_tsig_record(request_tsig.name().clone(), error_tsig));
+        }
+    };
+
+    // the MAC covers the response as sent, without the TSIG record
+    let mut response = MessageResponseBuilder::new(Some(request.raw_query()));
+    if let Some(edns) = response_edns {
+        response.edns(edns.clone());
+    }
+    let mut response_bytes = Vec::with_capacity(512);
+    if let Err(e) = emit(
+        response,
+        *response_header,
+        &mut BinEncoder::new(&mut response_bytes),
+    ) {
+        warn!("failed to encode response for signing: {e}");
+        return None;
+    }
+
+    signer
+        .sign_response(tsig.mac(), &response_bytes, now, error)
+        .map_err(|e| warn!("failed to sign response: {e}"))
+        .ok()
+}
+
+/// Checks the TSIG of a zone transfer request, see [RFC 8945](https://tools.ietf.org/html/rfc8945#section-5.2)
+///
+/// Zone transfers of an authority with TSIG keys must be signed with one of them, the unsigned
+/// requests are refused. Returns the response code of a rejected request.
+#[cfg(feature = "dnssec-ring")]
+fn transfer_tsig_error(authority: &dyn AuthorityObject, request: &Request) -> Option<ResponseCode> {
+    let signers = authority.tsig_signers();
+    match request.tsig() {
+        Some(tsig) => request.verify_tsig(tsig, signers).err(),
+        None if signers.is_empty() => None,
+        None => {
+            warn!(
+                "unsigned zone transfer of {} refused, it requires TSIG",
+                authority.origin()
+            );
+            Some(ResponseCode::Refused)
+        }
+    }
+}
+
 #[async_trait::async_trait]
 impl RequestHandler for Catalog {
     /// Determines what needs to happen given the type of request, i.e. Query or Update.
@@ -77,6 +207,8 @@ impl RequestHandler for Catalog {
         trace!("request: {:?}", request);
 
         let response_edns: Option<Edns>;
+        // whether the query carries a valid server cookie
+        let mut verified = false;
 
         // check if it's edns
         if let Some(req_edns) = request.edns() {
@@ -92,14 +224,38 @@ impl RequestHandler for Catalog {
             resp_edns.set_max_payload(req_edns.max_payload().max(512));
             resp_edns.set_version(our_version);
 
+            let mut error = None;
             if req_edns.version() > our_version {
                 warn!(
                     "request edns version greater than {}: {}",
                     our_version,
                     req_edns.version()
                 );
-                response_header.set_response_code(ResponseCode::BADVERS);
-                resp_edns.set_rcode_high(ResponseCode::BADVERS.high());
+                error = Some(ResponseCode::BADVERS);
+            } else if let (Some(cookies), Some(EdnsOption::Cookie(cookie))) =
+                (&self.cookies, req_edns.option(EdnsCode::Cookie))
+            {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |now| now.as_secs() as u32);
+                let client_ip = request.src().ip();
+                let valid = cookies.is_valid(cookie, client_ip, now);
+                verified = valid;
+                resp_edns.options_mut().insert(EdnsOption::Cookie(
+                    cookies.response_cookie(cookie, client_ip, now),
+                ));
+
+                // queries over TCP can not be spoofed, RFC 7873 section 5.2.3
+                if !valid && cookies.require_server_cookie() && request.protocol() == Protocol::Udp
+                {
+                    debug!("request without a valid server cookie: {}", request.id());
+                    error = Some(ResponseCode::BADCOOKIE);
+                }
+            }
+
+            if let Some(error) = error {
+                response_header.set_response_code(error);
+                resp_edns.set_rcode_high(error.high());
                 response.edns(resp_edns);
 
                 // TODO: should ResponseHandle consume self?
@@ -122,6 +278,14 @@ impl RequestHandler for Catalog {
             response_edns = None;
         }
 
+        let max_udp_size = match &self.cookies {
+            Some(cookies) if !verified && request.protocol() == Protocol::Udp => {
+                cookies.max_unverified_udp_size()
+            }
+            _ => None,
+        };
+        let mut response_handle = UnverifiedResponseHandler::new(response_handle, max_udp_size);
+
         let result = match request.message_type() {
             // TODO think about threading query lookups for multiple lookups, this could be a huge improvement
             //  especially for recursive lookups
@@ -136,6 +300,10 @@ impl RequestHandler for Catalog {
                     debug!("update received: {}", request.id());
                     self.update(request, response_edns, response_handle).await
                 }
+                OpCode::Notify => {
+                    debug!("notify received: {}", request.id());
+                    self.notify(request, response_edns, response_handle).await
+                }
                 c => {
                     warn!("unimplemented op_code: {:?}", c);
                     let response = MessageResponseBuilder::new(Some(request.raw_query()));
@@ -170,9 +338,57 @@ impl Catalog {
     pub fn new() -> Self {
         Self {
             authorities: HashMap::new(),
+            cookies: None,
+            access_lists: AccessLists::default(),
+            notifiers: HashMap::new(),
+            zone_refreshes: HashMap::new(),
+            refuse_any: true,
         }
     }
 
+    /// Enables DNS Cookies, [RFC 7873](https://tools.ietf.org/html/rfc7873)
+    ///
+    /// The responses to queries with a COOKIE option carry a server cookie, and with
+    /// [`DnsCookies::require_server_cookie`] the queries over UDP without a valid server cookie
+    /// are answered with BADCOOKIE.
+    pub fn set_dns_cookies(&mut self, cookies: DnsCookies) {
+        self.cookies = Some(cookies);
+    }
+
+    /// Answers the queries of type ANY with a single HINFO record, RFC 8482, enabled by default
+    ///
+    /// The HINFO record has the CPU `RFC8482` and an empty OS, it replaces the records of the
+    /// queried name to limit the size of the responses, which makes ANY queries useful for
+    /// amplification attacks. As the HINFO record is not signed, the queries with the DO bit are
+    /// answered with one of the RRsets of the name and its RRSIGs instead. The names without
+    /// records are still answered with NXDOMAIN or NODATA. When disabled, ANY queries are answered
+    /// with all the records of the name.
+    pub fn set_refuse_any(&mut self, refuse_any: bool) {
+        self.refuse_any = refuse_any;
+    }
+
+    /// Sets the access control lists of the queries, recursion and zone transfers
+    ///
+    /// The queries of the clients not permitted are answered with REFUSED. The zones only served
+    /// by external authorities, i.e. forwarders and recursors, require the recursion to be
+    /// permitted.
+    pub fn set_access_lists(&mut self, access_lists: AccessLists) {
+        self.access_lists = access_lists;
+    }
+
+    /// Notifies the secondaries of `zone` of each update bumping its serial, RFC 1996
+    pub fn set_notifier(&mut self, zone: LowerName, notifier: Notifier) {
+        self.notifiers.insert(zone, notifier);
+    }
+
+    /// Accepts the NOTIFY of the primaries of the secondary `zone`, scheduling its refresh
+    ///
+    /// The NOTIFY of the zones without a refresh handle are answered with NOTAUTH, or REFUSED if
+    /// the zone is not in the catalog.
+    pub fn set_zone_refresh(&mut self, zone: LowerName, refresh: ZoneRefresh) {
+        self.zone_refreshes.insert(zone, refresh);
+    }
+
     /// Insert or update a zone authority
     ///
     /// # Arguments
@@ -183,8 +399,10 @@ impl Catalog {
         self.authorities.insert(name, authorities);
     }
 
-    /// Remove a zone from the catalog
+    /// Remove a zone from the catalog, with its notifier and refresh handle
     pub fn remove(&mut self, name: &LowerName) -> Option<Vec<Arc<dyn AuthorityObject>>> {
+        self.notifiers.remove(name);
+        self.zone_refreshes.remove(name);
         self.authorities.remove(name)
     }
 
@@ -284,20 +502,39 @@ impl Catalog {
                         let update_result = authority.update(update).await;
                         match update_result {
                             // successful update
-                            Ok(..) => ResponseCode::NoError,
+                            Ok(updated) => {
+                                if updated {
+                                    self.notify_secondaries(authority.origin()).await;
+                                }
+                                ResponseCode::NoError
+                            }
                             Err(response_code) => response_code,
                         }
                     }
                     _ => ResponseCode::NotAuth,
                 };
 
-                let response = MessageResponseBuilder::new(Some(update.raw_query()));
+                #[cfg_attr(not(feature = "dnssec-ring"), allow(unused_mut))]
+                let mut response = MessageResponseBuilder::new(Some(update.raw_query()));
                 let mut response_header = Header::default();
                 response_header.set_id(update.id());
                 response_header.set_op_code(OpCode::Update);
                 response_header.set_message_type(MessageType::Response);
                 response_header.set_response_code(response_code);
 
+                #[cfg(feature = "dnssec-ring")]
+                if let Some(tsig) = response_tsig(
+                    &**authority,
+                    update,
+                    &mut response_header,
+                    response_edns.as_ref(),
+                    |response, header, encoder| {
+                        response.build_no_records(header).destructive_emit(encoder)
+                    },
+                ) {
+                    response.sig0(vec![tsig]);
+                }
+
                 return send_response(
                     response_edns,
                     response.build_no_records(response_header),
@@ -310,6 +547,103 @@ impl Catalog {
         Ok(ResponseInfo::serve_failed())
     }
 
+    /// Notifies the secondaries of the new version of `zone`, in the background
+    ///
+    /// The updates of the zone notify them already, call it after the other changes, e.g. a
+    /// reload of the zone. Does nothing without a notifier for the zone, see
+    /// [`Self::set_notifier`].
+    pub async fn notify_secondaries(&self, zone: &LowerName) {
+        let Some(notifier) = self.notifiers.get(zone).cloned() else {
+            return;
+        };
+        let Some(authority) = self
+            .authorities
+            .get(zone)
+            .and_then(|authorities| authorities.first())
+        else {
+            return;
+        };
+
+        let soa = match authority.soa().await.map_result() {
+            Some(Ok(lookup)) => lookup
+                .iter()
+                .find(|record| record.record_type() == RecordType::SOA)
+                .cloned(),
+            _ => None,
+        };
+
+        let zone = Name::from(authority.origin());
+        tokio::spawn(async move {
+            let results = notifier.notify(&zone, soa.as_ref()).await;
+            for (secondary, result) in notifier.secondaries().iter().zip(results) {
+                if let Err(e) = result {
+                    warn!("failed to notify {secondary} of the new version of {zone}: {e}");
+                }
+            }
+        });
+    }
+
+    /// Handles the NOTIFY of a primary, scheduling the refresh of the secondary zone
+    ///
+    /// [RFC 1996](https://tools.ietf.org/html/rfc1996), DNS NOTIFY, August 1996
+    ///
+    /// ```text
+    /// 3.10. If a slave receives a NOTIFY request from a host that is not a
+    ///    known master for the zone containing the QNAME, it should ignore the
+    ///    request and produce an error message in its operations log.
+    ///
+    /// 3.11. The only defined NOTIFY event at this time is that the SOA RR has
+    ///    changed.  Upon completion of a NOTIFY transaction, the slave should
+    ///    behave as though the zone given in the QNAME had reached its REFRESH
+    ///    interval.
+    /// ```
+    ///
+    /// The NOTIFY of other hosts than the primaries are answered with REFUSED, and the NOTIFY
+    /// of other record types than SOA with NOTIMP.
+    pub async fn notify<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        response_edns: Option<Edns>,
+        response_handle: R,
+    ) -> io::Result<ResponseInfo> {
+        let query = request.query();
+        let response_code = if !request.has_query() {
+            ResponseCode::FormErr
+        } else if query.query_type() != RecordType::SOA {
+            ResponseCode::NotImp
+        } else {
+            match self.zone_refreshes.get(query.name()) {
+                Some(refresh) if !refresh.is_primary(request.src().ip()) => {
+                    warn!(
+                        "NOTIFY of {} from {}, not a primary of the zone",
+                        query.name(),
+                        request.src()
+                    );
+                    ResponseCode::Refused
+                }
+                Some(refresh) if refresh.schedule() => ResponseCode::NoError,
+                Some(_) => {
+                    error!("no refresher for {}", query.name());
+                    ResponseCode::ServFail
+                }
+                None if self.contains(query.name()) => ResponseCode::NotAuth,
+                None => ResponseCode::Refused,
+            }
+        };
+
+        let response = MessageResponseBuilder::new(Some(request.raw_query()));
+        let mut response_header = Header::response_from_request(request.header());
+        response_header.set_authoritative(response_code == ResponseCode::NoError);
+        response_header.set_response_code(response_code);
+
+        send_response(
+            response_edns,
+            response.build_no_records(response_header),
+            response_handle,
+        )
+        .await
+    }
+
     /// Checks whether the `Catalog` contains DNS records for `name`
     ///
     /// Use this when you know the exact `LowerName` that was used when
@@ -336,10 +670,13 @@ impl Catalog {
         response_handle: R,
     ) -> ResponseInfo {
         let request_info = request.request_info();
-        let authorities = self.find(request_info.query.name());
+        let authorities = self
+            .find(request_info.query.name())
+            .filter(|authorities| self.permits(request, authorities));
 
         let Some(authorities) = authorities else {
-            // There are no authorities registered that can handle the request
+            // There are no authorities registered that can handle the request, or the client is
+            //  not permitted to use them
             let response = MessageResponseBuilder::new(Some(request.raw_query()));
 
             let result = send_response(
@@ -361,6 +698,7 @@ impl Catalog {
         let result = lookup(
             request_info.clone(),
             authorities,
+            self.refuse_any,
             request,
             response_edns
                 .as_ref()
@@ -375,6 +713,31 @@ impl Catalog {
         }
     }
 
+    /// Checks the access control lists for the request, to be answered by `authorities`
+    fn permits(&self, request: &Request, authorities: &[Arc<dyn AuthorityObject>]) -> bool {
+        let client_ip = request.src().ip();
+        let denied = if !self.access_lists.allow_query.permits(client_ip) {
+            "query"
+        } else if matches!(
+            request.request_info().query.query_type(),
+            RecordType::AXFR | RecordType::IXFR
+        ) && !self.access_lists.allow_transfer.permits(client_ip)
+        {
+            "transfer"
+        } else if authorities
+            .iter()
+            .all(|authority| authority.zone_type() == ZoneType::External)
+            && !self.access_lists.allow_recursion.permits(client_ip)
+        {
+            "recursion"
+        } else {
+            return true;
+        };
+
+        debug!("{denied} refused for {client_ip}: {}", request.id());
+        false
+    }
+
     /// Recursively searches the catalog for a matching authority
     pub fn find(&self, name: &LowerName) -> Option<&Vec<Arc<(dyn AuthorityObject + 'static)>>> {
         debug!("searching authorities for: {name}");
@@ -392,8 +755,9 @@ impl Catalog {
 async fn lookup<R: ResponseHandler + Unpin>(
     request_info: RequestInfo<'_>,
     authorities: &[Arc<dyn AuthorityObject>],
+    refuse_any: bool,
     request: &Request,
-    response_edns: Option<Edns>,
+    mut response_edns: Option<Edns>,
     response_handle: R,
 ) -> Result<ResponseInfo, LookupError> {
     let edns = request.edns();
@@ -407,15 +771,75 @@ async fn lookup<R: ResponseHandler + Unpin>(
 
     let query = request_info.query;
 
+    // RFC 1995: the authority section of an IXFR request contains the SOA of the client's version
+    let ixfr_serial = if query.query_type() == RecordType::IXFR {
+        let serial = request
+            .name_servers()
+            .iter()
+            .find_map(|record| record.data().as_soa())
+            .map(SOA::serial);
+
+        if serial.is_none() {
+            debug!("IXFR request {request_id} without SOA in the authority section");
+            let response = MessageResponseBuilder::new(Some(request.raw_query()));
+            return send_response(
+                response_edns,
+                response.error_msg(request.header(), ResponseCode::FormErr),
+                response_handle,
+            )
+            .await
+            .map_err(LookupError::Io);
+        }
+
+        serial
+    } else {
+        None
+    };
+
     for (authority_index, authority) in authorities.iter().enumerate() {
         debug!(
             "performing {query} on authority {origin} with request id {request_id}",
             origin = authority.origin(),
         );
 
+        #[cfg(feature = "dnssec-ring")]
+        if matches!(query.query_type(), RecordType::AXFR | RecordType::IXFR) {
+            if let Some(response_code) = transfer_tsig_error(&**authority, request) {
+                let mut response = MessageResponseBuilder::new(Some(request.raw_query()));
+                let mut response_header = Header::response_from_request(request.header());
+                response_header.set_response_code(response_code);
+                if let Some(tsig) = response_tsig(
+                    &**authority,
+                    request,
+                    &mut response_header,
+                    response_edns.as_ref(),
+                    |response, header, encoder| {
+                        response.build_no_records(header).destructive_emit(encoder)
+                    },
+                ) {
+                    response.sig0(vec![tsig]);
+                }
+
+                return send_response(
+                    response_edns,
+                    response.build_no_records(response_header),
+                    response_handle,
+                )
+                .await
+                .map_err(LookupError::Io);
+            }
+        }
+
         // Wait so we can determine if we need to fire a request to the next authority in a chained
         // configuration if the current authority declines to answer.
-        let mut result = authority.search(request_info.clone(), lookup_options).await;
+        let mut result = match ixfr_serial {
+            Some(serial) => {
+                authority
+                    .ixfr(request_info.clone(), serial, lookup_options)
+                    .await
+            }
+            None => authority.search(request_info.clone(), lookup_options).await,
+        };
 
         if let LookupControlFlow::Skip = result {
             trace!("catalog::lookup::authority did not handle request");
@@ -453,17 +877,65 @@ async fn lookup<R: ResponseHandler + Unpin>(
             return Err(LookupError::ResponseCode(ResponseCode::ServFail));
         };
 
-        let (response_header, sections) = build_response(
+        if let Err(LookupError::Dropped) = result {
+            debug!("request {request_id} dropped without response");
+            return Err(LookupError::Dropped);
+        }
+
+        let cache_status = match &result {
+            Ok(lookup) => lookup.cache_status(),
+            Err(e) => e.cache_status(),
+        };
+        #[cfg_attr(not(feature = "dnssec-ring"), allow(unused_mut))]
+        let (mut response_header, mut sections) = build_response(
             result,
             &**authority,
             request_id,
             request.header(),
             query,
             edns,
+            response_edns.as_mut(),
         )
         .await;
 
-        let message_response = MessageResponseBuilder::new(Some(request.raw_query())).build(
+        if refuse_any
+            && query.query_type() == RecordType::ANY
+            && response_header.response_code() == ResponseCode::NoError
+            && !sections.answers.is_empty()
+        {
+            debug!("answering ANY query {request_id} with the RFC 8482 HINFO");
+            sections.answers = rfc8482_answer(query, &*sections.answers, lookup_options);
+            sections.additionals = Box::new(EmptyLookup);
+        }
+
+        #[cfg_attr(not(feature = "dnssec-ring"), allow(unused_mut))]
+        let mut response = MessageResponseBuilder::new(Some(request.raw_query()));
+
+        // the verified TSIG of a zone transfer, the response is signed with the same key
+        #[cfg(feature = "dnssec-ring")]
+        if matches!(query.query_type(), RecordType::AXFR | RecordType::IXFR) {
+            if let Some(tsig) = response_tsig(
+                &**authority,
+                request,
+                &mut response_header,
+                response_edns.as_ref(),
+                |response, header, encoder| {
+                    response
+                        .build(
+                            header,
+                            sections.answers.iter(),
+                            sections.ns.iter(),
+                            sections.soa.iter(),
+                            sections.additionals.iter(),
+                        )
+                        .destructive_emit(encoder)
+                },
+            ) {
+                response.sig0(vec![tsig]);
+            }
+        }
+
+        let message_response = response.build(
             response_header,
             sections.answers.iter(),
             sections.ns.iter(),
@@ -478,7 +950,7 @@ async fn lookup<R: ResponseHandler + Unpin>(
                 error!("error sending response: {e}");
                 return Err(LookupError::Io(e));
             }
-            Ok(l) => return Ok(l),
+            Ok(l) => return Ok(l.with_cache_status(cache_status)),
         }
     }
 
@@ -486,6 +958,51 @@ async fn lookup<R: ResponseHandler + Unpin>(
     Err(LookupError::ResponseCode(ResponseCode::ServFail))
 }
 
+/// The answer of an ANY query in place of the `answers`, RFC 8482 section 4
+///
+/// This is the synthesized HINFO record of section 4.2, which is not signed. The requests with the
+/// DO bit are answered with a single RRset of the `answers` and its signatures instead, section 4.1.
+#[cfg_attr(not(feature = "dnssec-ring"), allow(unused_variables))]
+fn rfc8482_answer(
+    query: &LowerQuery,
+    answers: &dyn LookupObject,
+    lookup_options: LookupOptions,
+) -> Box<dyn LookupObject> {
+    #[cfg(feature = "dnssec-ring")]
+    if lookup_options.dnssec_ok() {
+        let record_type = answers
+            .iter()
+            .map(Record::record_type)
+            .find(|record_type| *record_type != RecordType::RRSIG);
+
+        if let Some(record_type) = record_type {
+            let mut rrset = RecordSet::new(query.name().into(), record_type, 0);
+            for record in answers.iter() {
+                match record.data() {
+                    RData::DNSSEC(DNSSECRData::RRSIG(rrsig))
+                        if rrsig.type_covered() == record_type =>
+                    {
+                        rrset.insert_rrsig(record.clone())
+                    }
+                    _ if record.record_type() == record_type => {
+                        rrset.insert(record.clone(), 0);
+                    }
+                    _ => {}
+                }
+            }
+
+            let records = LookupRecords::new(lookup_options, Arc::new(rrset));
+            return Box::new(AuthLookup::answers(records, None));
+        }
+    }
+
+    let ttl = answers.iter().map(Record::ttl).min().unwrap_or_default();
+    let hinfo = HINFO::new("RFC8482".to_string(), String::new());
+    let record = Record::from_rdata(query.name().into(), ttl, RData::HINFO(hinfo));
+    let records = LookupRecords::new(LookupOptions::default(), Arc::new(record.into()));
+    Box::new(AuthLookup::answers(records, None))
+}
+
 #[allow(unused_variables)]
 fn lookup_options_for_edns(edns: Option<&Edns>) -> LookupOptions {
     let edns = match edns {
@@ -518,6 +1035,7 @@ async fn build_response(
     request_header: &Header,
     query: &LowerQuery,
     edns: Option<&Edns>,
+    response_edns: Option<&mut Edns>,
 ) -> (Header, LookupSections) {
     let lookup_options = lookup_options_for_edns(edns);
 
@@ -545,6 +1063,7 @@ async fn build_response(
                 authority.can_validate_dnssec(),
                 query,
                 lookup_options,
+                response_edns,
             )
             .await
         }
@@ -573,10 +1092,10 @@ async fn build_authoritative_response(
             response_header.set_authoritative(true);
             Some(records)
         }
-        // This request was refused
-        // TODO: there are probably other error cases that should just drop through (FormErr, ServFail)
-        Err(LookupError::ResponseCode(ResponseCode::Refused)) => {
-            response_header.set_response_code(ResponseCode::Refused);
+        // This request was refused, or the zone is not served, e.g. an expired secondary zone
+        // TODO: there are probably other error cases that should just drop through (FormErr)
+        Err(LookupError::ResponseCode(code @ (ResponseCode::Refused | ResponseCode::ServFail))) => {
+            response_header.set_response_code(code);
             return LookupSections {
                 answers: Box::<AuthLookup>::default(),
                 ns: Box::<AuthLookup>::default(),
@@ -614,33 +1133,34 @@ async fn build_authoritative_response(
         } else {
             #[cfg(feature = "dnssec-ring")]
             {
-                if let Some(NxProofKind::Nsec3 {
-                    algorithm,
-                    salt,
-                    iterations,
-                }) = authority.nx_proof_kind()
-                {
-                    // This unwrap will not panic as we know that `answers` is `Some`.
-                    let has_wildcard_match =
-                        answers.as_ref().unwrap().iter().any(|rr| {
-                            rr.record_type() == RecordType::RRSIG && rr.name().is_wildcard()
-                        });
-
-                    match authority
-                        .get_nsec3_records(
-                            Nsec3QueryInfo {
-                                qname: query.name(),
-                                qtype: query.query_type(),
-                                has_wildcard_match,
-                                algorithm: *algorithm,
-                                salt,
-                                iterations: *iterations,
-                            },
-                            lookup_options,
-                        )
-                        .await
-                        .map_result()
-                    {
+                // This unwrap will not panic as we know that `answers` is `Some`.
+                let has_wildcard_match = answers.as_ref().unwrap().iter().any(is_wildcard_rrsig);
+
+                let future = match authority.nx_proof_kind() {
+                    Some(NxProofKind::Nsec3 {
+                        algorithm,
+                        salt,
+                        iterations,
+                    }) => Some(authority.get_nsec3_records(
+                        Nsec3QueryInfo {
+                            qname: query.name(),
+                            qtype: query.query_type(),
+                            has_wildcard_match,
+                            algorithm: *algorithm,
+                            salt,
+                            iterations: *iterations,
+                        },
+                        lookup_options,
+                    )),
+                    // a wildcard answer proves that there is no closer match, RFC 4035 section 3.1.3.3
+                    Some(NxProofKind::Nsec) if has_wildcard_match => {
+                        Some(authority.get_nsec_records(query.name(), lookup_options))
+                    }
+                    _ => None,
+                };
+
+                match future {
+                    Some(future) => match future.await.map_result() {
                         // run the soa lookup
                         Some(Ok(nsecs)) => (Some(nsecs), None),
                         Some(Err(e)) => {
@@ -651,9 +1171,8 @@ async fn build_authoritative_response(
                             warn!("unexpected lookup skip for request {_request_id}");
                             (None, None)
                         }
-                    }
-                } else {
-                    (None, None)
+                    },
+                    None => (None, None),
                 }
             }
             #[cfg(not(feature = "dnssec-ring"))]
@@ -747,6 +1266,16 @@ async fn build_authoritative_response(
     }
 }
 
+/// Whether the record is the RRSIG of a wildcard expansion, i.e. signed over fewer labels than
+/// its owner name
+#[cfg(feature = "dnssec-ring")]
+fn is_wildcard_rrsig(record: &Record) -> bool {
+    match record.data() {
+        RData::DNSSEC(DNSSECRData::RRSIG(rrsig)) => rrsig.num_labels() < record.name().num_labels(),
+        _ => false,
+    }
+}
+
 /// Prepare a response for a forwarded zone.
 async fn build_forwarded_response(
     response: Result<Box<dyn LookupObject>, LookupError>,
@@ -755,6 +1284,7 @@ async fn build_forwarded_response(
     can_validate_dnssec: bool,
     query: &LowerQuery,
     lookup_options: LookupOptions,
+    response_edns: Option<&mut Edns>,
 ) -> LookupSections {
     response_header.set_recursion_available(true);
     response_header.set_authoritative(false);
@@ -831,6 +1361,14 @@ async fn build_forwarded_response(
         Err(e) => {
             response_header.set_response_code(ResponseCode::ServFail);
             debug!("error resolving {e:?}");
+
+            // RFC 8914, the Extended DNS Error explains the SERVFAIL to clients supporting EDNS
+            if let (Some(ede), Some(response_edns)) = (e.extended_dns_error(), response_edns) {
+                response_edns
+                    .options_mut()
+                    .insert(EdnsOption::ExtendedDnsError(ede.clone()));
+            }
+
             (
                 Answer::Normal(Box::new(EmptyLookup)),
                 Box::<AuthLookup>::default(),
@@ -0,0 +1,678 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Catalog zones, for the provisioning of member zones, see [RFC 9432](https://www.rfc-editor.org/rfc/rfc9432)
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::SocketAddr,
+    sync::Arc,
+};
+
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+use crate::{
+    authority::{AuthorityObject, Catalog, LookupOptions},
+    proto::rr::{LowerName, Name, RData, Record, RecordType},
+    ConfigError, ConfigErrorKind,
+};
+
+/// The version of the catalog zone schema supported, RFC 9432, section 4.2.1
+const SCHEMA_VERSION: &[u8] = b"2";
+
+/// A member zone of a catalog zone, RFC 9432, section 4.3
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CatalogMember {
+    unique_id: Name,
+    zone: Name,
+    groups: Vec<String>,
+    change_of_ownership: Option<Name>,
+}
+
+impl CatalogMember {
+    /// The owner of the PTR record of the member, `<unique-id>.zones.<catalog>`
+    ///
+    /// A change of the unique id of a member zone resets it, its state is discarded and it is
+    /// provisioned again.
+    pub fn unique_id(&self) -> &Name {
+        &self.unique_id
+    }
+
+    /// The name of the member zone
+    pub fn zone(&self) -> &Name {
+        &self.zone
+    }
+
+    /// The groups of the member zone, from the `group` property, RFC 9432, section 4.4.2
+    ///
+    /// Groups are names agreed on by the producer and the consumers of the catalog, to select
+    /// the configuration of the member zone, e.g. the primary servers to transfer it from.
+    pub fn groups(&self) -> &[String] {
+        &self.groups
+    }
+
+    /// The catalog zone the member zone is being migrated to, from the `coo` property, RFC
+    /// 9432, section 4.4.1
+    ///
+    /// The member zone is handed over once it is listed in the new catalog zone, until then it
+    /// stays provisioned from this catalog, see [`CatalogZones::update`].
+    pub fn change_of_ownership(&self) -> Option<&Name> {
+        self.change_of_ownership.as_ref()
+    }
+}
+
+/// Configuration of the consumer of a catalog zone
+///
+/// The member zones are provisioned as secondary zones, transferred from the primaries of the
+/// first of their groups configured here, or from the default `primaries` otherwise.
+#[derive(Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CatalogZoneConfig {
+    /// addresses of the primaries of the member zones, tried in order
+    #[serde(default)]
+    pub primaries: Vec<SocketAddr>,
+    /// addresses of the primaries of the member zones of each group, RFC 9432, section 4.4.2
+    #[serde(default)]
+    pub groups: HashMap<String, Vec<SocketAddr>>,
+}
+
+impl CatalogZoneConfig {
+    /// The primaries of the member zone, `None` if none is configured
+    pub fn primaries(&self, member: &CatalogMember) -> Option<&[SocketAddr]> {
+        let primaries = member
+            .groups
+            .iter()
+            .find_map(|group| self.groups.get(group))
+            .unwrap_or(&self.primaries);
+
+        match primaries.is_empty() {
+            true => None,
+            false => Some(primaries),
+        }
+    }
+}
+
+/// The member zones listed in a catalog zone
+///
+/// A catalog zone is a regular zone, for which every member zone is listed with a PTR record at
+/// `<unique-id>.zones.<catalog>`, and optional property records below it. The catalog is parsed
+/// from the records of the zone, and applied to a [`Catalog`] with [`CatalogZones::update`],
+/// after a change of the catalog zone, the member zones are registered and deregistered
+/// accordingly.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CatalogZone {
+    origin: Name,
+    members: Vec<CatalogMember>,
+}
+
+impl CatalogZone {
+    /// Parses the catalog zone from its records
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - the name of the catalog zone
+    /// * `records` - the records of the catalog zone, records of unknown properties are ignored
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the zone is not a catalog zone of the supported schema version, the
+    /// members which are not valid are skipped.
+    pub fn from_records<'r>(
+        origin: Name,
+        records: impl IntoIterator<Item = &'r Record>,
+    ) -> Result<Self, ConfigError> {
+        let version = Name::from_labels([b"version" as &[u8]])
+            .and_then(|name| name.append_domain(&origin))
+            .map_err(|e| ConfigErrorKind::CatalogZone(e.to_string()))?;
+        let zones = Name::from_labels([b"zones" as &[u8]])
+            .and_then(|name| name.append_domain(&origin))
+            .map_err(|e| ConfigErrorKind::CatalogZone(e.to_string()))?;
+        let member_labels = usize::from(zones.num_labels()) + 1;
+
+        let mut versions = Vec::new();
+        let mut entries = BTreeMap::<Name, MemberEntry>::new();
+        for record in records {
+            let name = record.name();
+            if name == &version {
+                if let RData::TXT(txt) = record.data() {
+                    versions.push(txt.txt_data().concat());
+                }
+                continue;
+            }
+
+            if !zones.zone_of(name) || usize::from(name.num_labels()) < member_labels {
+                continue;
+            }
+
+            let entry = entries.entry(name.trim_to(member_labels)).or_default();
+            if usize::from(name.num_labels()) == member_labels {
+                if let RData::PTR(ptr) = record.data() {
+                    entry.zones.push(ptr.0.clone());
+                }
+                continue;
+            }
+
+            if usize::from(name.num_labels()) != member_labels + 1 {
+                continue;
+            }
+            let property = name.iter().next().unwrap_or_default();
+            match record.data() {
+                RData::PTR(ptr) if property.eq_ignore_ascii_case(b"coo") => {
+                    entry.coos.push(ptr.0.clone())
+                }
+                RData::TXT(txt) if property.eq_ignore_ascii_case(b"group") => entry
+                    .groups
+                    .push(String::from_utf8_lossy(&txt.txt_data().concat()).into_owned()),
+                _ => (),
+            }
+        }
+
+        if versions.len() != 1 || versions[0] != SCHEMA_VERSION {
+            return Err(ConfigErrorKind::CatalogZone(format!(
+                "the catalog zone {origin} must have a single version TXT record of value 2"
+            ))
+            .into());
+        }
+
+        let mut members = Vec::<CatalogMember>::with_capacity(entries.len());
+        for (unique_id, entry) in entries {
+            let MemberEntry {
+                mut zones,
+                groups,
+                mut coos,
+            } = entry;
+
+            // RFC 9432, section 4.3: the member is ignored unless there is a single PTR record
+            let zone = match (zones.pop(), zones.is_empty()) {
+                (Some(zone), true) => zone,
+                _ => {
+                    warn!("ignoring the member {unique_id} of the catalog zone {origin}, it does not have a single PTR record");
+                    continue;
+                }
+            };
+
+            // RFC 9432, section 5.1: only one of the duplicate members is processed
+            if members.iter().any(|member| member.zone == zone) {
+                warn!("ignoring the member {unique_id} of the catalog zone {origin}, {zone} is a member already");
+                continue;
+            }
+
+            members.push(CatalogMember {
+                unique_id,
+                zone,
+                groups,
+                change_of_ownership: match coos.len() {
+                    1 => coos.pop(),
+                    _ => None,
+                },
+            });
+        }
+
+        Ok(Self { origin, members })
+    }
+
+    /// Parses the catalog zone from the records of its authority
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the records of the zone can't be looked up, e.g. for a secondary zone
+    /// not transferred yet, or if it is not a catalog zone, see [`Self::from_records`].
+    pub async fn from_authority(authority: &dyn AuthorityObject) -> Result<Self, ConfigError> {
+        let origin = authority.origin();
+        let records = match authority
+            .lookup(origin, RecordType::AXFR, LookupOptions::default())
+            .await
+            .map_result()
+        {
+            Some(Ok(records)) => records,
+            Some(Err(e)) => {
+                return Err(ConfigErrorKind::CatalogZone(format!(
+                    "failed to read the catalog zone {origin}: {e}"
+                ))
+                .into())
+            }
+            None => {
+                return Err(ConfigErrorKind::CatalogZone(format!(
+                    "the catalog zone {origin} is not served by its authority"
+                ))
+                .into())
+            }
+        };
+
+        Self::from_records(Name::from(origin), records.iter())
+    }
+
+    /// The name of the catalog zone
+    pub fn origin(&self) -> &Name {
+        &self.origin
+    }
+
+    /// The member zones of the catalog
+    pub fn members(&self) -> &[CatalogMember] {
+        &self.members
+    }
+
+    /// Returns the member with the zone name, if any
+    pub fn member(&self, zone: &Name) -> Option<&CatalogMember> {
+        self.members.iter().find(|member| member.zone == *zone)
+    }
+}
+
+/// The catalog zones applied to a [`Catalog`], and the owners of their member zones
+///
+/// A member zone is owned by the first catalog zone it is provisioned from, the other catalog
+/// zones listing it are ignored, unless the owner migrates it to them with its `coo` property,
+/// RFC 9432, section 5.6.
+#[derive(Default)]
+pub struct CatalogZones {
+    zones: HashMap<Name, CatalogZone>,
+    owners: HashMap<Name, Name>,
+}
+
+impl CatalogZones {
+    /// Creates an empty set of catalog zones
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The last version applied of the catalog zone, if any
+    pub fn get(&self, origin: &Name) -> Option<&CatalogZone> {
+        self.zones.get(origin)
+    }
+
+    /// The catalog zone owning the member zone, if any
+    pub fn owner(&self, zone: &Name) -> Option<&Name> {
+        self.owners.get(zone)
+    }
+
+    /// Registers the member zones of the catalog zone in the `catalog`, and deregisters the ones
+    /// removed since its previous version
+    ///
+    /// The member zones which are new, were reset or whose groups changed are provisioned with
+    /// `provision`, which returns the authorities of the member zone, or `None` if it can't be
+    /// provisioned, e.g. for an unknown group. The zones of the `catalog` which are not members
+    /// of a catalog zone, i.e. configured statically, are not replaced.
+    ///
+    /// A member zone of another catalog zone is taken over only if its `coo` property points to
+    /// this catalog zone, it is kept as is if its unique id is unchanged, and provisioned again
+    /// otherwise.
+    pub fn update<F>(&mut self, zone: CatalogZone, catalog: &mut Catalog, mut provision: F)
+    where
+        F: FnMut(&CatalogMember) -> Option<Vec<Arc<dyn AuthorityObject>>>,
+    {
+        let origin = zone.origin.clone();
+        let previous = self.zones.remove(&origin);
+
+        for member in previous
+            .as_ref()
+            .map(CatalogZone::members)
+            .unwrap_or_default()
+        {
+            if zone.member(&member.zone).is_some() || self.owners.get(&member.zone) != Some(&origin)
+            {
+                continue;
+            }
+
+            debug!("deregistering {} of the catalog zone {origin}", member.zone);
+            self.owners.remove(&member.zone);
+            catalog.remove(&LowerName::from(&member.zone));
+        }
+
+        for member in &zone.members {
+            let name = LowerName::from(&member.zone);
+            let current = match self.owners.get(&member.zone) {
+                Some(owner) if *owner == origin => previous
+                    .as_ref()
+                    .and_then(|previous| previous.member(&member.zone)),
+                Some(owner) => {
+                    let owned = self
+                        .zones
+                        .get(owner)
+                        .and_then(|owner| owner.member(&member.zone))
+                        .filter(|owned| owned.change_of_ownership.as_ref() == Some(&origin));
+                    let Some(owned) = owned else {
+                        warn!(
+                            "ignoring {} of the catalog zone {origin}, it is a member of {owner}",
+                            member.zone
+                        );
+                        continue;
+                    };
+
+                    debug!(
+                        "migrating {} from the catalog zone {owner} to {origin}",
+                        member.zone
+                    );
+                    Some(owned)
+                }
+                None if catalog.contains(&name) => {
+                    warn!(
+                        "ignoring {} of the catalog zone {origin}, it is configured already",
+                        member.zone
+                    );
+                    continue;
+                }
+                None => None,
+            };
+
+            // RFC 9432, section 5.5 and 5.6: the state is kept unless the unique id changed
+            let unchanged = current.is_some_and(|current| {
+                current.unique_id.iter().next() == member.unique_id.iter().next()
+                    && current.groups == member.groups
+            });
+            self.owners.insert(member.zone.clone(), origin.clone());
+            if unchanged && catalog.contains(&name) {
+                continue;
+            }
+
+            match provision(member) {
+                Some(authorities) => {
+                    debug!("registering {} of the catalog zone {origin}", member.zone);
+                    catalog.upsert(name, authorities);
+                }
+                None => {
+                    warn!(
+                        "failed to provision {} of the catalog zone {origin}",
+                        member.zone
+                    );
+                    self.owners.remove(&member.zone);
+                    catalog.remove(&name);
+                }
+            }
+        }
+
+        self.zones.insert(origin, zone);
+    }
+}
+
+/// The records of a member, before validation
+#[derive(Default)]
+struct MemberEntry {
+    zones: Vec<Name>,
+    groups: Vec<String>,
+    coos: Vec<Name>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+
+    use crate::authority::ZoneType;
+    use crate::proto::op::{Message, Query, ResponseCode};
+    use crate::proto::rr::rdata::{A, PTR, SOA, TXT};
+    use crate::server::tests::{send_request, udp_request};
+    use crate::store::in_memory::InMemoryAuthority;
+
+    use super::*;
+
+    fn name(name: &str) -> Name {
+        Name::from_str(name).unwrap()
+    }
+
+    fn record(owner: &str, rdata: RData) -> Record {
+        Record::from_rdata(name(owner), 3600, rdata)
+    }
+
+    fn ptr(owner: &str, target: &str) -> Record {
+        record(owner, RData::PTR(PTR(name(target))))
+    }
+
+    fn txt(owner: &str, value: &str) -> Record {
+        record(owner, RData::TXT(TXT::new(vec![value.to_string()])))
+    }
+
+    fn soa(zone: &str) -> Record {
+        let soa = SOA::new(name("invalid."), name("invalid."), 1, 3600, 600, 86400, 0);
+        record(zone, RData::SOA(soa))
+    }
+
+    fn catalog_zone(members: &[Record]) -> Result<CatalogZone, ConfigError> {
+        let mut records = vec![
+            soa("catalog.example."),
+            txt("version.catalog.example.", "2"),
+        ];
+        records.extend_from_slice(members);
+        CatalogZone::from_records(name("catalog.example."), &records)
+    }
+
+    /// Provisions the member zones with a SOA and an A record
+    fn provision(member: &CatalogMember) -> Option<Vec<Arc<dyn AuthorityObject>>> {
+        let zone = member.zone().to_string();
+        let mut authority = InMemoryAuthority::empty(
+            member.zone().clone(),
+            ZoneType::Secondary,
+            false,
+            #[cfg(feature = "dnssec-ring")]
+            None,
+        );
+        authority.upsert_mut(soa(&zone), 1);
+        authority.upsert_mut(record(&zone, RData::A(A::new(192, 0, 2, 1))), 1);
+        Some(vec![Arc::new(authority)])
+    }
+
+    async fn query(catalog: &Catalog, zone: &str) -> Message {
+        let mut message = Message::new();
+        message.add_query(Query::query(name(zone), RecordType::A));
+        let request = udp_request(&message, SocketAddr::from((Ipv4Addr::LOCALHOST, 53)));
+        send_request(catalog, &request).await.unwrap()
+    }
+
+    #[test]
+    fn test_parse_catalog_zone() {
+        let catalog = catalog_zone(&[
+            ptr("m1.zones.catalog.example.", "example.com."),
+            txt("group.m1.zones.catalog.example.", "primary-a"),
+            ptr("m2.zones.catalog.example.", "example.net."),
+            ptr("coo.m2.zones.catalog.example.", "other.catalog.example."),
+            // not a single PTR record
+            ptr("m3.zones.catalog.example.", "example.org."),
+            ptr("m3.zones.catalog.example.", "example.edu."),
+            // a duplicate of m1
+            ptr("m4.zones.catalog.example.", "example.com."),
+            // a custom property
+            txt("ext.m1.zones.catalog.example.", "ignored"),
+        ])
+        .unwrap();
+
+        assert_eq!(catalog.origin(), &name("catalog.example."));
+        assert_eq!(catalog.members().len(), 2);
+
+        let m1 = catalog.member(&name("example.com.")).unwrap();
+        assert_eq!(m1.unique_id(), &name("m1.zones.catalog.example."));
+        assert_eq!(m1.groups(), ["primary-a"]);
+        assert_eq!(m1.change_of_ownership(), None);
+
+        let m2 = catalog.member(&name("example.net.")).unwrap();
+        assert!(m2.groups().is_empty());
+        assert_eq!(
+            m2.change_of_ownership(),
+            Some(&name("other.catalog.example."))
+        );
+
+        // the version is required
+        let records = [ptr("m1.zones.catalog.example.", "example.com.")];
+        assert!(CatalogZone::from_records(name("catalog.example."), &records).is_err());
+        let records = [txt("version.catalog.example.", "1")];
+        assert!(CatalogZone::from_records(name("catalog.example."), &records).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_update_catalog() {
+        let mut catalog = Catalog::new();
+        let mut zones = CatalogZones::new();
+        let members = catalog_zone(&[
+            ptr("m1.zones.catalog.example.", "example.com."),
+            ptr("m2.zones.catalog.example.", "example.net."),
+        ])
+        .unwrap();
+        zones.update(members, &mut catalog, provision);
+
+        for zone in ["example.com.", "example.net."] {
+            let response = query(&catalog, zone).await;
+            assert_eq!(response.response_code(), ResponseCode::NoError, "{zone}");
+            assert_eq!(response.answer_count(), 1, "{zone}");
+        }
+
+        // removing a member deregisters it, the other one is not provisioned again
+        let updated = catalog_zone(&[ptr("m2.zones.catalog.example.", "example.net.")]).unwrap();
+        zones.update(updated, &mut catalog, |member| {
+            panic!("unexpected provisioning of {}", member.zone())
+        });
+
+        assert!(!catalog.contains(&LowerName::from(name("example.com."))));
+        assert_eq!(zones.owner(&name("example.com.")), None);
+        let response = query(&catalog, "example.com.").await;
+        assert_eq!(response.response_code(), ResponseCode::Refused);
+        assert!(catalog.contains(&LowerName::from(name("example.net."))));
+
+        // a new unique id resets the member
+        let reset = catalog_zone(&[ptr("m3.zones.catalog.example.", "example.net.")]).unwrap();
+        let mut provisioned = Vec::new();
+        zones.update(reset, &mut catalog, |member| {
+            provisioned.push(member.zone().clone());
+            provision(member)
+        });
+        assert_eq!(provisioned, vec![name("example.net.")]);
+    }
+
+    #[test]
+    fn test_change_of_ownership() {
+        let mut catalog = Catalog::new();
+        let mut zones = CatalogZones::new();
+        let other = |members: &[Record]| {
+            let mut records = vec![txt("version.other.example.", "2")];
+            records.extend_from_slice(members);
+            CatalogZone::from_records(name("other.example."), &records).unwrap()
+        };
+
+        // a static zone is not taken over
+        catalog.upsert(
+            LowerName::from(name("example.org.")),
+            provision(
+                &catalog_zone(&[ptr("m0.zones.catalog.example.", "example.org.")])
+                    .unwrap()
+                    .members()[0],
+            )
+            .unwrap(),
+        );
+
+        let owner = catalog_zone(&[
+            ptr("m1.zones.catalog.example.", "example.com."),
+            ptr("m2.zones.catalog.example.", "example.net."),
+            ptr("m3.zones.catalog.example.", "example.org."),
+        ])
+        .unwrap();
+        zones.update(owner, &mut catalog, provision);
+        assert_eq!(zones.owner(&name("example.org.")), None);
+
+        // the member of another catalog zone is ignored without coo
+        let mut provisioned = Vec::new();
+        zones.update(
+            other(&[
+                ptr("m1.zones.other.example.", "example.com."),
+                ptr("n2.zones.other.example.", "example.net."),
+            ]),
+            &mut catalog,
+            |member| {
+                provisioned.push(member.zone().clone());
+                provision(member)
+            },
+        );
+        assert!(provisioned.is_empty());
+        assert_eq!(
+            zones.owner(&name("example.com.")),
+            Some(&name("catalog.example."))
+        );
+
+        // with coo, the state is kept for the same unique id, and reset otherwise
+        let migrating = catalog_zone(&[
+            ptr("m1.zones.catalog.example.", "example.com."),
+            ptr("coo.m1.zones.catalog.example.", "other.example."),
+            ptr("m2.zones.catalog.example.", "example.net."),
+            ptr("coo.m2.zones.catalog.example.", "other.example."),
+        ])
+        .unwrap();
+        zones.update(migrating, &mut catalog, |member| {
+            panic!("unexpected provisioning of {}", member.zone())
+        });
+        zones.update(
+            other(&[
+                ptr("m1.zones.other.example.", "example.com."),
+                ptr("n2.zones.other.example.", "example.net."),
+            ]),
+            &mut catalog,
+            |member| {
+                provisioned.push(member.zone().clone());
+                provision(member)
+            },
+        );
+        assert_eq!(provisioned, vec![name("example.net.")]);
+        for zone in ["example.com.", "example.net."] {
+            assert_eq!(zones.owner(&name(zone)), Some(&name("other.example.")));
+        }
+
+        // the removal from the previous owner doesn't deregister the members
+        zones.update(catalog_zone(&[]).unwrap(), &mut catalog, provision);
+        for zone in ["example.com.", "example.net.", "example.org."] {
+            assert!(catalog.contains(&LowerName::from(name(zone))), "{zone}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_from_authority() {
+        let mut authority = InMemoryAuthority::empty(
+            name("catalog.example."),
+            ZoneType::Primary,
+            false,
+            #[cfg(feature = "dnssec-ring")]
+            None,
+        );
+        authority.upsert_mut(soa("catalog.example."), 1);
+        authority.upsert_mut(txt("version.catalog.example.", "2"), 1);
+        authority.upsert_mut(ptr("m1.zones.catalog.example.", "example.com."), 1);
+
+        let zone = CatalogZone::from_authority(&authority).await.unwrap();
+        assert_eq!(
+            zone,
+            catalog_zone(&[ptr("m1.zones.catalog.example.", "example.com.")]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_config_primaries() {
+        let config = CatalogZoneConfig {
+            primaries: vec![SocketAddr::from((Ipv4Addr::new(192, 0, 2, 1), 53))],
+            groups: HashMap::from([(
+                "primary-b".to_string(),
+                vec![SocketAddr::from((Ipv4Addr::new(192, 0, 2, 2), 53))],
+            )]),
+        };
+        let zone = catalog_zone(&[
+            ptr("m1.zones.catalog.example.", "example.com."),
+            ptr("m2.zones.catalog.example.", "example.net."),
+            txt("group.m2.zones.catalog.example.", "primary-a"),
+            txt("group.m2.zones.catalog.example.", "primary-b"),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            config.primaries(&zone.members()[0]),
+            Some(&config.primaries[..])
+        );
+        assert_eq!(
+            config.primaries(&zone.members()[1]),
+            Some(&config.groups["primary-b"][..])
+        );
+
+        let config = CatalogZoneConfig {
+            primaries: vec![],
+            groups: HashMap::new(),
+        };
+        assert_eq!(config.primaries(&zone.members()[0]), None);
+    }
+}
@@ -10,13 +10,14 @@ use std::{io, sync::Arc};
 use enum_as_inner::EnumAsInner;
 use thiserror::Error;
 
-use crate::proto::op::ResponseCode;
+use crate::proto::op::{ExtendedDnsError, ResponseCode};
 use crate::proto::rr::{rdata::SOA, Record};
 use crate::proto::{ProtoError, ProtoErrorKind};
 #[cfg(feature = "recursor")]
 use crate::recursor::ErrorKind;
 #[cfg(feature = "resolver")]
 use crate::resolver::ResolveError;
+use crate::server::CacheStatus;
 
 // TODO: should this implement Failure?
 #[allow(clippy::large_enum_variant)]
@@ -44,6 +45,9 @@ pub enum LookupError {
     /// An underlying IO error occurred
     #[error("io error: {0}")]
     Io(io::Error),
+    /// The request is dropped, without any response to the client
+    #[error("request dropped")]
+    Dropped,
 }
 
 impl LookupError {
@@ -86,6 +90,27 @@ impl LookupError {
         }
     }
 
+    /// Returns the Extended DNS Error explaining the failure, if the error contains one
+    pub fn extended_dns_error(&self) -> Option<&ExtendedDnsError> {
+        match self {
+            #[cfg(feature = "resolver")]
+            Self::ResolveError(e) => e.extended_dns_error(),
+            _ => None,
+        }
+    }
+
+    /// Whether the error was answered from the cache of a forwarder or recursor, `None` for the
+    /// other errors
+    pub fn cache_status(&self) -> Option<CacheStatus> {
+        match self {
+            #[cfg(feature = "resolver")]
+            Self::ResolveError(e) => Some(e.metadata().into()),
+            #[cfg(feature = "recursor")]
+            Self::RecursiveError(e) => Some(e.metadata().into()),
+            _ => None,
+        }
+    }
+
     /// Return authority records
     pub fn authorities(&self) -> Option<Arc<[Record]>> {
         match self {
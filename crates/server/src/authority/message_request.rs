@@ -7,10 +7,18 @@
 
 use std::iter::once;
 
+#[cfg(feature = "dnssec-ring")]
+use tracing::{debug, warn};
+
+#[cfg(feature = "dnssec-ring")]
+use crate::proto::{
+    dnssec::{tsig::TSigner, DnsSecErrorKind},
+    rr::RecordType,
+};
 use crate::proto::{
     op::{
         message::{self, EmitAndCount},
-        Edns, Header, LowerQuery, Message, MessageType, OpCode, ResponseCode,
+        Edns, Header, LowerQuery, Message, MessageType, OpCode, Query, ResponseCode,
     },
     rr::Record,
     serialize::binary::{BinDecodable, BinDecoder, BinEncodable, BinEncoder},
@@ -91,10 +99,18 @@ impl MessageRequest {
     /// ```text
     /// Question        Carries the query name and other query parameters.
     /// ```
+    ///
+    /// The requests of the opcodes without a question, e.g. a NOTIFY with QDCOUNT 0, have the
+    /// default query of the root; see [`Self::has_query`].
     pub fn query(&self) -> &LowerQuery {
         &self.query.query
     }
 
+    /// Returns false if the question section of the request is empty
+    pub fn has_query(&self) -> bool {
+        !self.query.is_empty()
+    }
+
     /// ```text
     /// Answer          Carries RRs which directly answer the query.
     /// ```
@@ -180,6 +196,70 @@ impl MessageRequest {
     pub(crate) fn raw_query(&self) -> &WireQuery {
         &self.query
     }
+
+    /// The TSIG record of the request, it is always the last record of the message
+    #[cfg(feature = "dnssec-ring")]
+    pub(crate) fn tsig(&self) -> Option<&Record> {
+        self.sig0
+            .last()
+            .filter(|record| record.record_type() == RecordType::TSIG)
+    }
+
+    /// Verifies the TSIG of the request, see [RFC 8945](https://tools.ietf.org/html/rfc8945#section-5.2)
+    ///
+    /// Returns the key of `signers` which signed the request. The errors are the TSIG errors
+    /// `BADKEY`, `BADSIG` and `BADTIME`, the response to a request rejected with one of these has
+    /// the `NOTAUTH` response code.
+    #[cfg(feature = "dnssec-ring")]
+    pub(crate) fn verify_tsig<'s>(
+        &self,
+        tsig: &Record,
+        signers: &'s [TSigner],
+    ) -> Result<&'s TSigner, ResponseCode> {
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        // 5.2.1.  Key Check and Error Handling
+        let Some(signer) = signers
+            .iter()
+            .find(|signer| signer.signer_name() == tsig.name())
+        else {
+            warn!("request signed with unknown TSIG key: {}", tsig.name());
+            return Err(ResponseCode::BADKEY);
+        };
+
+        let message = self.to_bytes().map_err(|e| {
+            warn!("failed to encode request for TSIG verification: {e}");
+            ResponseCode::FormErr
+        })?;
+
+        // 5.2.2.  MAC Check and Error Handling
+        let (_, valid_time, _) = match signer.verify_message_byte(None, &message, true) {
+            Ok(verified) => verified,
+            Err(e) if matches!(e.kind(), DnsSecErrorKind::TsigWrongKey) => {
+                warn!(
+                    "request signed with unknown TSIG algorithm: {}",
+                    tsig.name()
+                );
+                return Err(ResponseCode::BADKEY);
+            }
+            Err(e) => {
+                warn!("TSIG of request did not verify: {e}");
+                return Err(ResponseCode::BADSIG);
+            }
+        };
+
+        // 5.2.3.  Time Check and Error Handling
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_secs());
+        if !valid_time.contains(&now) {
+            warn!("TSIG of request is outside of the time window: {now} {valid_time:?}");
+            return Err(ResponseCode::BADTIME);
+        }
+
+        debug!("verified TSIG of request with key: {}", tsig.name());
+        Ok(signer)
+    }
 }
 
 impl<'q> BinDecodable<'q> for MessageRequest {
@@ -196,7 +276,7 @@ impl<'q> BinDecodable<'q> for MessageRequest {
             let additional_count = header.additional_count() as usize;
 
             let queries = Queries::read(decoder, query_count)?;
-            let query = queries.try_into_query()?;
+            let query = queries.try_into_query(header.op_code())?;
             let (answers, _, _) = Message::read_records(decoder, answer_count, false)?;
             let (name_servers, _, _) = Message::read_records(decoder, name_server_count, false)?;
             let (additionals, edns, sig0) = Message::read_records(decoder, additional_count, true)?;
@@ -286,17 +366,27 @@ impl Queries {
     }
 
     /// Performs a validation that this set of Queries is one and only one Query
-    pub(crate) fn try_into_query(mut self) -> Result<WireQuery, ProtoError> {
+    ///
+    /// A QUERY or an UPDATE must have exactly one question, the zone of an UPDATE, the other
+    /// opcodes may also have none, e.g. a NOTIFY or a DSO message, RFC 8490.
+    pub(crate) fn try_into_query(mut self, op_code: OpCode) -> Result<WireQuery, ProtoError> {
         let count = self.queries.len();
-        if count == 1 {
-            let query = self.queries.pop().expect("should have been at least one");
-
-            Ok(WireQuery {
-                query,
+        match count {
+            1 => {
+                let query = self.queries.pop().expect("should have been at least one");
+
+                Ok(WireQuery {
+                    query,
+                    original: self.original,
+                    empty: false,
+                })
+            }
+            0 if !matches!(op_code, OpCode::Query | OpCode::Update) => Ok(WireQuery {
+                query: LowerQuery::query(Query::default()),
                 original: self.original,
-            })
-        } else {
-            Err(ProtoErrorKind::BadQueryCount(count).into())
+                empty: true,
+            }),
+            _ => Err(ProtoErrorKind::BadQueryCount(count).into()),
         }
     }
 }
@@ -306,13 +396,28 @@ impl Queries {
 pub(crate) struct WireQuery {
     query: LowerQuery,
     original: Box<[u8]>,
+    /// The question section is empty, `query` is only a placeholder
+    empty: bool,
 }
 
 impl WireQuery {
+    pub(crate) fn query(&self) -> &LowerQuery {
+        &self.query
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.empty
+    }
+
+    /// The queries to emit, none if the question section is empty
+    fn queries(&self) -> impl Iterator<Item = &LowerQuery> {
+        once(&self.query).filter(|_| !self.empty)
+    }
+
     pub(crate) fn as_emit_and_count(&self) -> QueriesEmitAndCount<'_> {
         QueriesEmitAndCount {
-            length: 1,
-            first_query: Some(&self.query),
+            length: usize::from(!self.empty),
+            first_query: self.queries().next(),
             cached_serialized: self.original.as_ref(),
         }
     }
@@ -332,22 +437,43 @@ impl EmitAndCount for QueriesEmitAndCount<'_> {
         let original_offset = encoder.offset();
         encoder.emit_vec(self.cached_serialized)?;
         if !encoder.is_canonical_names() && self.first_query.is_some() {
-            encoder.store_label_pointer(
-                original_offset,
-                original_offset + self.cached_serialized.len(),
-            )
+            store_label_pointers(encoder, original_offset, self.cached_serialized);
         }
         Ok(self.length)
     }
 }
 
+/// Stores a label pointer to each suffix of the wire-format name emitted at `offset`
+///
+/// The pointers span the labels up to the root label, as the ones stored by the names themselves
+/// when emitted, nothing is stored for a name which is compressed.
+fn store_label_pointers(encoder: &mut BinEncoder<'_>, offset: usize, name: &[u8]) {
+    let mut label_starts = Vec::new();
+    let mut idx = 0;
+    let end = loop {
+        match name.get(idx) {
+            Some(0) => break idx,
+            Some(&len) if len <= 63 => {
+                label_starts.push(idx);
+                idx += 1 + usize::from(len);
+            }
+            // a pointer or a malformed label
+            _ => return,
+        }
+    };
+
+    for start in label_starts {
+        encoder.store_label_pointer(offset + start, offset + end);
+    }
+}
+
 impl BinEncodable for MessageRequest {
     fn emit(&self, encoder: &mut BinEncoder<'_>) -> Result<(), ProtoError> {
         message::emit_message_parts(
             &self.header,
             // we emit the queries, not the raw bytes, in order to guarantee canonical form
             //   in cases where that's necessary, like SIG0 validation
-            &mut once(&self.query.query),
+            &mut self.query.queries(),
             &mut self.answers.iter(),
             &mut self.name_servers.iter(),
             &mut self.additionals.iter(),
@@ -406,3 +532,61 @@ impl UpdateRequest for MessageRequest {
         self.sig0()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::proto::rr::{Name, RecordType};
+
+    use super::*;
+
+    fn read(op_code: OpCode, queries: &[&str]) -> Result<MessageRequest, ProtoError> {
+        let mut message = Message::new();
+        message.set_id(1).set_op_code(op_code);
+        for name in queries {
+            message.add_query(Query::query(Name::from_ascii(name).unwrap(), RecordType::A));
+        }
+
+        let bytes = message.to_vec().unwrap();
+        MessageRequest::read(&mut BinDecoder::new(&bytes))
+    }
+
+    fn assert_bad_query_count(result: Result<MessageRequest, ProtoError>, count: usize) {
+        let error = result.unwrap_err();
+        let (header, error) = error.kind().as_form_error().expect("not a FormError");
+        assert_eq!(header.id(), 1);
+        assert!(matches!(error.kind(), ProtoErrorKind::BadQueryCount(c) if *c == count));
+    }
+
+    #[test]
+    fn test_one_query() {
+        let request = read(OpCode::Query, &["www.example.com."]).unwrap();
+        assert!(request.has_query());
+        assert_eq!(
+            request.query().name(),
+            &Name::from_ascii("www.example.com.").unwrap().into()
+        );
+
+        let message = Message::from_vec(&request.to_bytes().unwrap()).unwrap();
+        assert_eq!(message.queries().len(), 1);
+    }
+
+    #[test]
+    fn test_two_queries() {
+        let queries = ["www.example.com.", "example.com."];
+        assert_bad_query_count(read(OpCode::Query, &queries), 2);
+        assert_bad_query_count(read(OpCode::Notify, &queries), 2);
+    }
+
+    #[test]
+    fn test_no_query() {
+        assert_bad_query_count(read(OpCode::Query, &[]), 0);
+        assert_bad_query_count(read(OpCode::Update, &[]), 0);
+
+        let request = read(OpCode::Notify, &[]).unwrap();
+        assert!(!request.has_query());
+
+        let message = Message::from_vec(&request.to_bytes().unwrap()).unwrap();
+        assert_eq!(message.op_code(), OpCode::Notify);
+        assert!(message.queries().is_empty());
+    }
+}
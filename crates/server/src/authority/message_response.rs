@@ -5,6 +5,8 @@
 // https://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::iter;
+
 use crate::{
     authority::{
         message_request::{MessageRequest, QueriesEmitAndCount},
@@ -13,7 +15,7 @@ use crate::{
     proto::{
         op::{
             message::{self, EmitAndCount},
-            Edns, Header, ResponseCode,
+            Edns, Header, LowerQuery, ResponseCode,
         },
         rr::Record,
         serialize::binary::BinEncoder,
@@ -41,6 +43,8 @@ where
     additionals: Additionals,
     sig0: Vec<Record>,
     edns: Option<Edns>,
+    /// Limit of the size of the response over UDP, below the payload size of the client
+    max_udp_size: Option<u16>,
 }
 
 enum EmptyOrQueries<'q> {
@@ -73,7 +77,7 @@ impl EmitAndCount for EmptyOrQueries<'_> {
     }
 }
 
-impl<'a, A, N, S, D> MessageResponse<'_, 'a, A, N, S, D>
+impl<'q, 'a, A, N, S, D> MessageResponse<'q, 'a, A, N, S, D>
 where
     A: Iterator<Item = &'a Record> + Send + 'a,
     N: Iterator<Item = &'a Record> + Send + 'a,
@@ -101,6 +105,97 @@ where
         &self.edns
     }
 
+    /// The query this is a response to, if any
+    pub(crate) fn query(&self) -> Option<&LowerQuery> {
+        self.query
+            .filter(|query| !query.is_empty())
+            .map(WireQuery::query)
+    }
+
+    /// Consumes self, and returns an empty response with the truncated flag set
+    ///
+    /// The query and EDNS options are kept, the client is expected to retry over TCP.
+    pub(crate) fn into_truncated(
+        self,
+    ) -> MessageResponse<
+        'q,
+        'a,
+        impl Iterator<Item = &'a Record> + Send + 'a,
+        impl Iterator<Item = &'a Record> + Send + 'a,
+        impl Iterator<Item = &'a Record> + Send + 'a,
+        impl Iterator<Item = &'a Record> + Send + 'a,
+    > {
+        let mut header = self.header;
+        header.set_truncated(true);
+
+        MessageResponse {
+            header,
+            query: self.query,
+            answers: iter::empty(),
+            name_servers: iter::empty(),
+            soa: iter::empty(),
+            additionals: iter::empty(),
+            sig0: self.sig0,
+            edns: self.edns,
+            max_udp_size: self.max_udp_size,
+        }
+    }
+
+    /// Consumes self, and returns the freshness lifetime of the response along with it
+    ///
+    /// This is the lowest TTL of the answers or, for the negative responses, of the SOA record of
+    /// the authority section, RFC 8484 section 5.1. The records of these sections are collected
+    /// to be inspected before the response is emitted.
+    #[cfg(feature = "dns-over-https-rustls")]
+    pub(crate) fn into_max_age(
+        self,
+    ) -> (
+        Option<u32>,
+        MessageResponse<
+            'q,
+            'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+            D,
+        >,
+    ) {
+        let answers = self.answers.collect::<Vec<_>>();
+        let name_servers = self.name_servers.collect::<Vec<_>>();
+        let soa = self.soa.collect::<Vec<_>>();
+
+        let max_age = match answers.is_empty() {
+            false => answers.iter().map(|record| record.ttl()).min(),
+            true => name_servers.iter().chain(&soa).find_map(|record| {
+                let soa = record.data().as_soa()?;
+                Some(record.ttl().min(soa.minimum()))
+            }),
+        };
+
+        let response = MessageResponse {
+            header: self.header,
+            query: self.query,
+            answers: answers.into_iter(),
+            name_servers: name_servers.into_iter(),
+            soa: soa.into_iter(),
+            additionals: self.additionals,
+            sig0: self.sig0,
+            edns: self.edns,
+            max_udp_size: self.max_udp_size,
+        };
+        (max_age, response)
+    }
+
+    /// Limits the size of the response over UDP to `size`, larger responses are truncated
+    pub(crate) fn limit_udp_size(&mut self, size: u16) {
+        self.max_udp_size = Some(self.max_udp_size.map_or(size, |max| max.min(size)));
+    }
+
+    /// The limit of the size of the response over UDP, see [`Self::limit_udp_size`]
+    pub(crate) fn max_udp_size(&self) -> Option<u16> {
+        self.max_udp_size
+    }
+
     /// Consumes self, and emits to the encoder.
     pub fn destructive_emit(
         mut self,
@@ -159,6 +254,12 @@ impl<'q> MessageResponseBuilder<'q> {
         self
     }
 
+    /// Associate the SIG(0) or TSIG records signing the Response, these are emitted last
+    pub fn sig0(&mut self, sig0: Vec<Record>) -> &mut Self {
+        self.sig0 = Some(sig0);
+        self
+    }
+
     /// Constructs the new MessageResponse with associated Header
     ///
     /// # Arguments
@@ -191,6 +292,7 @@ impl<'q> MessageResponseBuilder<'q> {
             additionals: additionals.into_iter(),
             sig0: self.sig0.unwrap_or_default(),
             edns: self.edns,
+            max_udp_size: None,
         }
     }
 
@@ -215,6 +317,7 @@ impl<'q> MessageResponseBuilder<'q> {
             additionals: Box::new(None.into_iter()),
             sig0: self.sig0.unwrap_or_default(),
             edns: self.edns,
+            max_udp_size: None,
         }
     }
 
@@ -249,6 +352,7 @@ impl<'q> MessageResponseBuilder<'q> {
             additionals: Box::new(None.into_iter()),
             sig0: self.sig0.unwrap_or_default(),
             edns: self.edns,
+            max_udp_size: None,
         }
     }
 }
@@ -259,9 +363,9 @@ mod tests {
     use std::net::Ipv4Addr;
     use std::str::FromStr;
 
-    use crate::proto::op::{Header, Message};
-    use crate::proto::rr::{DNSClass, Name, RData, Record};
-    use crate::proto::serialize::binary::BinEncoder;
+    use crate::proto::op::{Header, Message, Query};
+    use crate::proto::rr::{DNSClass, Name, RData, Record, RecordType};
+    use crate::proto::serialize::binary::{BinDecodable, BinEncoder};
 
     use super::*;
 
@@ -289,6 +393,7 @@ mod tests {
                 additionals: iter::once(&answer),
                 sig0: vec![],
                 edns: None,
+                max_udp_size: None,
             };
 
             message
@@ -327,6 +432,7 @@ mod tests {
                 additionals: iter::repeat(&answer),
                 sig0: vec![],
                 edns: None,
+                max_udp_size: None,
             };
 
             message
@@ -340,6 +446,45 @@ mod tests {
         assert!(response.name_server_count() > 1);
     }
 
+    #[test]
+    fn test_names_compressed_to_query() {
+        let mut query = Message::new();
+        query.add_query(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        let request = MessageRequest::from_bytes(&query.to_vec().unwrap()).unwrap();
+
+        let answers = ["www.example.com.", "mail.example.com."].map(|name| {
+            Record::from_rdata(
+                Name::from_str(name).unwrap(),
+                0,
+                RData::A(Ipv4Addr::new(192, 0, 2, 1).into()),
+            )
+        });
+
+        let mut buf = Vec::new();
+        MessageResponseBuilder::new(Some(request.raw_query()))
+            .build(
+                Header::response_from_request(request.header()),
+                answers.iter(),
+                iter::empty(),
+                iter::empty(),
+                iter::empty(),
+            )
+            .destructive_emit(&mut BinEncoder::new(&mut buf))
+            .unwrap();
+
+        // the owner names point to the query name after the header, and to its example.com. suffix
+        let first = 12 + 17 + 4;
+        assert_eq!(buf[first..first + 2], [0xC0, 12]);
+        let second = first + 2 + 10 + 4;
+        assert_eq!(buf[second..second + 7], *b"\x04mail\xC0\x10");
+
+        let response = Message::from_vec(&buf).unwrap();
+        assert_eq!(response.answers(), answers);
+    }
+
     // https://github.com/hickory-dns/hickory-dns/issues/2210
     // If a client sends this DNS request to the hickory 0.24.0 DNS server:
     //
@@ -373,8 +518,6 @@ mod tests {
     //     encoder.emit_vec(self.cached_serialized)?;
     #[test]
     fn bad_length_of_named_pointers() {
-        use hickory_proto::serialize::binary::BinDecodable;
-
         let mut buf = Vec::with_capacity(512);
         let mut encoder = BinEncoder::new(&mut buf);
 
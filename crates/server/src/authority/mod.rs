@@ -17,20 +17,25 @@ mod auth_lookup;
 mod authority;
 pub(crate) mod authority_object;
 mod catalog;
+mod catalog_zone;
 mod error;
 pub(crate) mod message_request;
 mod message_response;
+mod notify;
 mod zone_type;
 
 pub use self::auth_lookup::{
     AnyRecords, AuthLookup, AuthLookupIter, LookupRecords, LookupRecordsIter,
 };
+pub(crate) use self::authority::axfr_query;
 pub use self::authority::{Authority, LookupControlFlow, LookupOptions};
 pub use self::authority_object::{AuthorityObject, DnssecSummary, EmptyLookup, LookupObject};
 pub use self::catalog::Catalog;
+pub use self::catalog_zone::{CatalogMember, CatalogZone, CatalogZoneConfig, CatalogZones};
 pub use self::error::LookupError;
 pub use self::message_request::{MessageRequest, Queries, UpdateRequest};
 pub use self::message_response::{MessageResponse, MessageResponseBuilder};
+pub use self::notify::{Notifier, ZoneRefresh};
 pub use self::zone_type::ZoneType;
 
 #[cfg(feature = "dnssec-ring")]
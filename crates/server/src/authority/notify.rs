@@ -0,0 +1,436 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Notification of the zone changes between primaries and secondaries, see
+//! [RFC 1996](https://tools.ietf.org/html/rfc1996)
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use futures_util::future;
+use tokio::{
+    net::UdpSocket,
+    sync::mpsc::{self, error::TrySendError},
+};
+use tracing::{debug, warn};
+
+use crate::proto::{
+    op::{Message, MessageType, OpCode, Query, ResponseCode},
+    rr::{Name, Record, RecordType},
+    ProtoError, ProtoErrorKind,
+};
+
+/// The interval between the retransmissions of a NOTIFY, RFC 1996 section 3.6
+const DEFAULT_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+/// The retransmissions of a NOTIFY not acknowledged, before giving up
+const DEFAULT_RETRIES: usize = 5;
+
+/// Notifies the secondaries of a zone of its changes
+///
+/// The NOTIFY is sent over UDP, with the new SOA in the answer section, and retransmitted until
+/// the secondary acknowledges it with a NOERROR response. Register it in the
+/// [`Catalog`](crate::authority::Catalog) to notify the secondaries of each update bumping the
+/// serial of the zone.
+#[derive(Clone, Debug)]
+pub struct Notifier {
+    secondaries: Vec<SocketAddr>,
+    retries: usize,
+    retry_interval: Duration,
+}
+
+impl Notifier {
+    /// Notifies `secondaries`, retransmitting 5 times every 5 seconds
+    pub fn new(secondaries: Vec<SocketAddr>) -> Self {
+        Self {
+            secondaries,
+            retries: DEFAULT_RETRIES,
+            retry_interval: DEFAULT_RETRY_INTERVAL,
+        }
+    }
+
+    /// Sets the retransmissions of a NOTIFY not acknowledged, and the interval between them
+    pub fn set_retries(&mut self, retries: usize, retry_interval: Duration) {
+        self.retries = retries;
+        self.retry_interval = retry_interval;
+    }
+
+    /// The addresses of the secondaries notified
+    pub fn secondaries(&self) -> &[SocketAddr] {
+        &self.secondaries
+    }
+
+    /// Notifies all the secondaries of the new version of `zone`
+    ///
+    /// Returns the outcome for each secondary, in order, once all of them acknowledged the
+    /// NOTIFY or the retransmissions are exhausted.
+    pub async fn notify(&self, zone: &Name, soa: Option<&Record>) -> Vec<Result<(), ProtoError>> {
+        let mut message = Message::new();
+        message
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Notify)
+            .set_authoritative(true)
+            .add_query(Query::query(zone.clone(), RecordType::SOA));
+        if let Some(soa) = soa {
+            message.add_answer(soa.clone());
+        }
+
+        future::join_all(
+            self.secondaries
+                .iter()
+                .map(|secondary| self.notify_one(*secondary, message.clone())),
+        )
+        .await
+    }
+
+    async fn notify_one(
+        &self,
+        secondary: SocketAddr,
+        mut message: Message,
+    ) -> Result<(), ProtoError> {
+        let id = rand::random();
+        let bytes = message.set_id(id).to_vec()?;
+
+        let bind_addr = match secondary {
+            SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+        };
+        let socket = UdpSocket::bind(bind_addr).await?;
+        socket.connect(secondary).await?;
+
+        for attempt in 0..=self.retries {
+            socket.send(&bytes).await?;
+            match tokio::time::timeout(self.retry_interval, acknowledgment(&socket, id)).await {
+                Ok(result) => return result,
+                Err(_) => debug!(
+                    "NOTIFY:{id} not acknowledged by {secondary}, attempt {}",
+                    attempt + 1
+                ),
+            }
+        }
+
+        warn!("NOTIFY:{id} not acknowledged by {secondary}, giving up");
+        Err(ProtoErrorKind::Timeout.into())
+    }
+}
+
+/// Waits for the response to the NOTIFY `id`, the other messages are ignored
+async fn acknowledgment(socket: &UdpSocket, id: u16) -> Result<(), ProtoError> {
+    let mut buf = [0; 4096];
+    loop {
+        let len = socket.recv(&mut buf).await?;
+        let response = match Message::from_vec(&buf[..len]) {
+            Ok(response) => response,
+            Err(e) => {
+                debug!("ignoring invalid response to NOTIFY:{id}: {e}");
+                continue;
+            }
+        };
+
+        if response.id() != id
+            || response.message_type() != MessageType::Response
+            || response.op_code() != OpCode::Notify
+        {
+            continue;
+        }
+
+        return match response.response_code() {
+            ResponseCode::NoError => Ok(()),
+            code => Err(ProtoError::from(format!("NOTIFY:{id} rejected: {code}"))),
+        };
+    }
+}
+
+/// The handle scheduling the refresh of a secondary zone, on the NOTIFY of its primaries
+///
+/// Created by a [`SecondaryAuthority`](crate::store::secondary::SecondaryAuthority), and
+/// registered in the [`Catalog`](crate::authority::Catalog) to accept the NOTIFY of the zone.
+#[derive(Clone, Debug)]
+pub struct ZoneRefresh {
+    primaries: Arc<[SocketAddr]>,
+    sender: mpsc::Sender<()>,
+}
+
+impl ZoneRefresh {
+    pub(crate) fn new(primaries: Arc<[SocketAddr]>, sender: mpsc::Sender<()>) -> Self {
+        Self { primaries, sender }
+    }
+
+    /// Returns true if `ip` is the address of a primary of the zone
+    ///
+    /// The NOTIFY from the other hosts are refused, RFC 1996 section 3.10.
+    pub fn is_primary(&self, ip: IpAddr) -> bool {
+        self.primaries.iter().any(|primary| primary.ip() == ip)
+    }
+
+    /// Schedules a refresh of the zone
+    ///
+    /// A refresh already scheduled and not started yet covers this one. Returns false if the
+    /// refresher is gone.
+    pub fn schedule(&self) -> bool {
+        match self.sender.try_send(()) {
+            Ok(()) | Err(TrySendError::Full(())) => true,
+            Err(TrySendError::Closed(())) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::authority::{
+        AuthLookup, Authority, Catalog, LookupControlFlow, LookupOptions, MessageRequest,
+        UpdateResult, ZoneType,
+    };
+    use crate::proto::rr::rdata::SOA;
+    use crate::proto::rr::{LowerName, RData};
+    use crate::server::tests::{send_request, udp_request};
+    use crate::server::RequestInfo;
+    use crate::store::in_memory::InMemoryAuthority;
+    #[cfg(feature = "dnssec-ring")]
+    use crate::{authority::Nsec3QueryInfo, dnssec::NxProofKind};
+
+    fn name(name: &str) -> Name {
+        Name::from_str(name).unwrap()
+    }
+
+    fn soa(serial: u32) -> Record {
+        let soa = SOA::new(
+            name("ns.example.com."),
+            name("invalid."),
+            serial,
+            3600,
+            600,
+            86400,
+            0,
+        );
+        Record::from_rdata(name("example.com."), 3600, RData::SOA(soa))
+    }
+
+    /// The example.com. zone
+    fn example(zone_type: ZoneType, serial: u32) -> InMemoryAuthority {
+        let mut authority = InMemoryAuthority::empty(
+            name("example.com."),
+            zone_type,
+            true,
+            #[cfg(feature = "dnssec-ring")]
+            None,
+        );
+        authority.upsert_mut(soa(serial), serial);
+        authority
+    }
+
+    /// A primary zone, where all the updates bump the serial
+    struct Primary(InMemoryAuthority);
+
+    #[async_trait::async_trait]
+    impl Authority for Primary {
+        type Lookup = AuthLookup;
+
+        fn zone_type(&self) -> ZoneType {
+            ZoneType::Primary
+        }
+
+        fn is_axfr_allowed(&self) -> bool {
+            false
+        }
+
+        async fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
+            let serial = self.0.serial().await + 1;
+            self.0.upsert(soa(serial), serial).await;
+            Ok(true)
+        }
+
+        fn origin(&self) -> &LowerName {
+            self.0.origin()
+        }
+
+        async fn lookup(
+            &self,
+            name: &LowerName,
+            rtype: RecordType,
+            lookup_options: LookupOptions,
+        ) -> LookupControlFlow<Self::Lookup> {
+            self.0.lookup(name, rtype, lookup_options).await
+        }
+
+        async fn search(
+            &self,
+            request_info: RequestInfo<'_>,
+            lookup_options: LookupOptions,
+        ) -> LookupControlFlow<Self::Lookup> {
+            self.0.search(request_info, lookup_options).await
+        }
+
+        async fn get_nsec_records(
+            &self,
+            name: &LowerName,
+            lookup_options: LookupOptions,
+        ) -> LookupControlFlow<Self::Lookup> {
+            self.0.get_nsec_records(name, lookup_options).await
+        }
+
+        #[cfg(feature = "dnssec-ring")]
+        async fn get_nsec3_records(
+            &self,
+            info: Nsec3QueryInfo<'_>,
+            lookup_options: LookupOptions,
+        ) -> LookupControlFlow<Self::Lookup> {
+            self.0.get_nsec3_records(info, lookup_options).await
+        }
+
+        #[cfg(feature = "dnssec-ring")]
+        fn nx_proof_kind(&self) -> Option<&NxProofKind> {
+            None
+        }
+    }
+
+    /// Answers a request of the catalog, sent from `src`
+    async fn handle(catalog: &Catalog, message: &Message, src: SocketAddr) -> Message {
+        send_request(catalog, &udp_request(message, src))
+            .await
+            .unwrap()
+    }
+
+    fn notify_message(zone: &str) -> Message {
+        let mut message = Message::new();
+        message
+            .set_id(1)
+            .set_op_code(OpCode::Notify)
+            .set_authoritative(true)
+            .add_query(Query::query(name(zone), RecordType::SOA));
+        message
+    }
+
+    /// Acknowledges the NOTIFY received on `socket`, except the first `ignored` ones
+    async fn secondary_stub(socket: UdpSocket, ignored: usize) -> Vec<Message> {
+        let mut buf = [0; 4096];
+        let mut received = Vec::new();
+        loop {
+            let (len, src) = socket.recv_from(&mut buf).await.unwrap();
+            let message = Message::from_vec(&buf[..len]).unwrap();
+            received.push(message.clone());
+            if received.len() <= ignored {
+                continue;
+            }
+
+            let mut response = message;
+            response.set_message_type(MessageType::Response);
+            socket
+                .send_to(&response.to_vec().unwrap(), src)
+                .await
+                .unwrap();
+            return received;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_notify_retries() {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let secondary = socket.local_addr().unwrap();
+        let stub = tokio::spawn(secondary_stub(socket, 1));
+
+        let mut notifier = Notifier::new(vec![secondary]);
+        notifier.set_retries(2, Duration::from_millis(100));
+        let results = notifier.notify(&name("example.com."), Some(&soa(2))).await;
+        assert!(matches!(results[..], [Ok(())]));
+
+        // the NOTIFY is retransmitted with the same id
+        let received = stub.await.unwrap();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].id(), received[1].id());
+        let notify = &received[1];
+        assert_eq!(notify.op_code(), OpCode::Notify);
+        assert!(notify.authoritative());
+        assert_eq!(notify.queries()[0].name(), &name("example.com."));
+        assert_eq!(notify.queries()[0].query_type(), RecordType::SOA);
+        assert_eq!(notify.answers(), [soa(2)]);
+
+        // no secondary acknowledges
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let mut notifier = Notifier::new(vec![socket.local_addr().unwrap()]);
+        notifier.set_retries(1, Duration::from_millis(50));
+        let results = notifier.notify(&name("example.com."), None).await;
+        assert!(matches!(results[..], [Err(_)]));
+    }
+
+    #[tokio::test]
+    async fn test_update_notifies_secondaries() {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let secondary = socket.local_addr().unwrap();
+        let stub = tokio::spawn(secondary_stub(socket, 0));
+
+        let origin = LowerName::from(name("example.com."));
+        let mut catalog = Catalog::new();
+        catalog.upsert(
+            origin.clone(),
+            vec![Arc::new(Primary(example(ZoneType::Primary, 1)))],
+        );
+        catalog.set_notifier(origin, Notifier::new(vec![secondary]));
+
+        let mut update = Message::new();
+        update
+            .set_id(1)
+            .set_op_code(OpCode::Update)
+            .add_query(Query::query(name("example.com."), RecordType::SOA));
+        let response = handle(&catalog, &update, secondary).await;
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+
+        // the NOTIFY carries the serial bumped by the update
+        let received = tokio::time::timeout(Duration::from_secs(5), stub)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(received[0].op_code(), OpCode::Notify);
+        assert_eq!(received[0].answers(), [soa(2)]);
+    }
+
+    #[tokio::test]
+    async fn test_notify_schedules_refresh() {
+        let primary = SocketAddr::from((Ipv4Addr::new(192, 0, 2, 1), 53));
+        let other = SocketAddr::from((Ipv4Addr::new(192, 0, 2, 2), 53));
+
+        let secondary = Arc::new(example(ZoneType::Secondary, 1));
+        let (sender, mut requests) = mpsc::channel(1);
+
+        let mut catalog = Catalog::new();
+        catalog.upsert(secondary.origin().clone(), vec![secondary.clone()]);
+        catalog.set_zone_refresh(
+            secondary.origin().clone(),
+            ZoneRefresh::new(Arc::from([primary]), sender),
+        );
+
+        // only the primaries are allowed to notify
+        let response = handle(&catalog, &notify_message("example.com."), other).await;
+        assert_eq!(response.response_code(), ResponseCode::Refused);
+        assert!(requests.try_recv().is_err());
+
+        let response = handle(&catalog, &notify_message("example.com."), primary).await;
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(response.op_code(), OpCode::Notify);
+        assert!(response.authoritative());
+        assert_eq!(response.queries()[0].name(), &name("example.com."));
+        assert!(requests.try_recv().is_ok());
+
+        // a zone not in the catalog
+        let response = handle(&catalog, &notify_message("example.net."), primary).await;
+        assert_eq!(response.response_code(), ResponseCode::Refused);
+
+        // a zone of the catalog without refresh
+        catalog.upsert(
+            LowerName::from(name("example.org.")),
+            vec![Arc::new(example(ZoneType::Primary, 1))],
+        );
+        let response = handle(&catalog, &notify_message("example.org."), primary).await;
+        assert_eq!(response.response_code(), ResponseCode::NotAuth);
+    }
+}
@@ -29,6 +29,10 @@ pub enum ErrorKind {
     /// An error occurred while parsing a zone file
     #[error("failed to parse the zone file: {0}")]
     ZoneParse(#[from] crate::proto::serialize::txt::ParseError),
+
+    /// A catalog zone is not valid
+    #[error("invalid catalog zone: {0}")]
+    CatalogZone(String),
 }
 
 /// The error type for errors that get returned in the crate
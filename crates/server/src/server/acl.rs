@@ -0,0 +1,89 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Access control lists of the queries, recursion and zone transfers
+
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+use serde::Deserialize;
+
+/// A list of the networks of the clients permitted an operation
+///
+/// The denied networks take precedence over the allowed ones. Without allowed networks, all the
+/// clients not denied are permitted, so the default list permits all the clients.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Acl {
+    /// Networks of the permitted clients, all the clients if empty
+    pub allow: Vec<IpNet>,
+    /// Networks of the clients never permitted
+    pub deny: Vec<IpNet>,
+}
+
+impl Acl {
+    /// Returns `true` if the client at `ip` is permitted
+    pub fn permits(&self, ip: IpAddr) -> bool {
+        if self.deny.iter().any(|net| net.contains(&ip)) {
+            return false;
+        }
+
+        self.allow.is_empty() || self.allow.iter().any(|net| net.contains(&ip))
+    }
+}
+
+/// The access control lists of a catalog, the requests of the clients not permitted are answered
+/// with REFUSED
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AccessLists {
+    /// Clients permitted to query the zones
+    pub allow_query: Acl,
+    /// Clients permitted to query the zones served by forwarders and recursors
+    pub allow_recursion: Acl,
+    /// Clients permitted to transfer the zones with AXFR and IXFR
+    pub allow_transfer: Acl,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permits() {
+        let ip = |ip: &str| ip.parse::<IpAddr>().unwrap();
+
+        // everything is permitted by default
+        let acl = Acl::default();
+        assert!(acl.permits(ip("192.0.2.1")));
+        assert!(acl.permits(ip("2001:db8::1")));
+
+        let acl = Acl {
+            allow: vec!["192.0.2.0/24".parse().unwrap()],
+            deny: vec![],
+        };
+        assert!(acl.permits(ip("192.0.2.1")));
+        assert!(!acl.permits(ip("198.51.100.1")));
+        assert!(!acl.permits(ip("2001:db8::1")));
+
+        let acl = Acl {
+            allow: vec![],
+            deny: vec!["2001:db8::/32".parse().unwrap()],
+        };
+        assert!(acl.permits(ip("192.0.2.1")));
+        assert!(!acl.permits(ip("2001:db8::1")));
+
+        // the denied networks take precedence, even when more specific networks are allowed
+        let acl = Acl {
+            allow: vec!["192.0.2.1/32".parse().unwrap()],
+            deny: vec!["192.0.2.0/24".parse().unwrap()],
+        };
+        assert!(!acl.permits(ip("192.0.2.1")));
+        assert!(!acl.permits(ip("192.0.2.2")));
+        assert!(!acl.permits(ip("198.51.100.1")));
+    }
+}
@@ -0,0 +1,391 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DNS Cookies, [RFC 7873](https://tools.ietf.org/html/rfc7873)
+//!
+//! The server cookies are generated as specified by [RFC 9018](https://tools.ietf.org/html/rfc9018),
+//! so that the servers of an anycast set sharing the secret accept the cookies of each other.
+
+use std::{fmt, io, net::IpAddr};
+
+use crate::{
+    authority::MessageResponse,
+    proto::rr::{rdata::opt::Cookie, Record},
+    server::{ResponseHandler, ResponseInfo},
+};
+
+/// Version of the server cookies of RFC 9018
+const VERSION: u8 = 1;
+
+/// Server cookies are valid for one hour after being issued, RFC 9018 section 4.3
+const MAX_AGE: u32 = 3600;
+
+/// Server cookies from up to five minutes in the future are accepted, as the clocks of the
+/// servers of an anycast set may differ, RFC 9018 section 4.3
+const MAX_SKEW: u32 = 300;
+
+/// Configuration of the DNS Cookies of the server
+///
+/// Each response to a query with a COOKIE option carries a new server cookie, which is a keyed
+/// hash of the client cookie, the client address and a timestamp. The key is a secret of the
+/// server.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct DnsCookies {
+    secret: [u8; 16],
+    require_server_cookie: bool,
+    max_unverified_udp_size: Option<u16>,
+}
+
+impl DnsCookies {
+    /// Creates the configuration with a random secret
+    ///
+    /// With `require_server_cookie`, queries over UDP with a COOKIE option, but without a valid
+    /// server cookie, are answered with BADCOOKIE and a new server cookie instead of being
+    /// processed.
+    pub fn new(require_server_cookie: bool) -> Self {
+        Self::with_secret(rand::random(), require_server_cookie)
+    }
+
+    /// Creates the configuration with the secret shared by the servers of an anycast set
+    pub fn with_secret(secret: [u8; 16], require_server_cookie: bool) -> Self {
+        Self {
+            secret,
+            require_server_cookie,
+            max_unverified_udp_size: None,
+        }
+    }
+
+    /// Limits the size of the UDP responses to the queries without a valid server cookie
+    ///
+    /// The larger responses are truncated, the clients retry over TCP, or with the server cookie
+    /// of the truncated response. This makes the server a poor amplifier for the spoofed queries,
+    /// which can't carry a valid server cookie, while the clients supporting cookies still get
+    /// the full responses over UDP. The size is at least 512 bytes.
+    pub fn with_max_unverified_udp_size(mut self, size: u16) -> Self {
+        self.max_unverified_udp_size = Some(size.max(512));
+        self
+    }
+
+    /// Queries over UDP without a valid server cookie are answered with BADCOOKIE
+    pub fn require_server_cookie(&self) -> bool {
+        self.require_server_cookie
+    }
+
+    /// The maximum size of the UDP responses to the queries without a valid server cookie
+    pub fn max_unverified_udp_size(&self) -> Option<u16> {
+        self.max_unverified_udp_size
+    }
+
+    /// Returns the COOKIE option for the response to a query with `cookie` from `client_ip`
+    pub(crate) fn response_cookie(&self, cookie: &Cookie, client_ip: IpAddr, now: u32) -> Cookie {
+        Cookie::new(
+            *cookie.client(),
+            Some(self.server_cookie(cookie.client(), client_ip, now).to_vec()),
+        )
+    }
+
+    /// Returns `true` if the server cookie of `cookie` was issued by this server for the client
+    pub(crate) fn is_valid(&self, cookie: &Cookie, client_ip: IpAddr, now: u32) -> bool {
+        let Some(server) = cookie.server() else {
+            return false;
+        };
+        if server.len() != 16 || server[0] != VERSION {
+            return false;
+        }
+
+        let timestamp = u32::from_be_bytes([server[4], server[5], server[6], server[7]]);
+        let age = now.wrapping_sub(timestamp);
+        if age > MAX_AGE && timestamp.wrapping_sub(now) > MAX_SKEW {
+            return false;
+        }
+
+        self.server_cookie(cookie.client(), client_ip, timestamp)[..] == *server
+    }
+
+    /// The server cookie of RFC 9018 section 4
+    ///
+    /// ```text
+    /// Hash = SipHash-2-4(
+    ///     Client Cookie | Version | Reserved | Timestamp | Client-IP,
+    ///     Server Secret )
+    /// ```
+    fn server_cookie(&self, client: &[u8; 8], client_ip: IpAddr, timestamp: u32) -> [u8; 16] {
+        let mut cookie = [0; 16];
+        cookie[0] = VERSION;
+        cookie[4..8].copy_from_slice(&timestamp.to_be_bytes());
+
+        let mut input = Vec::with_capacity(32);
+        input.extend_from_slice(client);
+        input.extend_from_slice(&cookie[..8]);
+        match client_ip {
+            IpAddr::V4(ip) => input.extend_from_slice(&ip.octets()),
+            IpAddr::V6(ip) => input.extend_from_slice(&ip.octets()),
+        }
+
+        let hash = siphash24(&self.secret, &input);
+        cookie[8..].copy_from_slice(&hash.to_le_bytes());
+        cookie
+    }
+}
+
+impl fmt::Debug for DnsCookies {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DnsCookies")
+            .field("require_server_cookie", &self.require_server_cookie)
+            .field("max_unverified_udp_size", &self.max_unverified_udp_size)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Limits the size of the UDP responses to a client without a valid server cookie
+#[derive(Clone)]
+pub(crate) struct UnverifiedResponseHandler<R: ResponseHandler> {
+    handler: R,
+    max_udp_size: Option<u16>,
+}
+
+impl<R: ResponseHandler> UnverifiedResponseHandler<R> {
+    /// Wraps `handler`, limiting the size of the UDP responses to `max_udp_size`, if any
+    pub(crate) fn new(handler: R, max_udp_size: Option<u16>) -> Self {
+        Self {
+            handler,
+            max_udp_size,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<R: ResponseHandler> ResponseHandler for UnverifiedResponseHandler<R> {
+    async fn send_response<'a>(
+        &mut self,
+        mut response: MessageResponse<
+            '_,
+            'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+        >,
+    ) -> io::Result<ResponseInfo> {
+        if let Some(max_udp_size) = self.max_udp_size {
+            response.limit_udp_size(max_udp_size);
+        }
+
+        self.handler.send_response(response).await
+    }
+}
+
+/// SipHash-2-4, the keyed hash function of <https://131002.net/siphash/siphash.pdf>
+fn siphash24(key: &[u8; 16], data: &[u8]) -> u64 {
+    fn round(v: &mut [u64; 4]) {
+        v[0] = v[0].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(13) ^ v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(16) ^ v[2];
+        v[0] = v[0].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(21) ^ v[0];
+        v[2] = v[2].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(17) ^ v[2];
+        v[2] = v[2].rotate_left(32);
+    }
+
+    fn compress(v: &mut [u64; 4], m: u64) {
+        v[3] ^= m;
+        round(v);
+        round(v);
+        v[0] ^= m;
+    }
+
+    let mut k = [0; 8];
+    k.copy_from_slice(&key[..8]);
+    let k0 = u64::from_le_bytes(k);
+    k.copy_from_slice(&key[8..]);
+    let k1 = u64::from_le_bytes(k);
+
+    let mut v = [
+        k0 ^ 0x736f_6d65_7073_6575,
+        k1 ^ 0x646f_7261_6e64_6f6d,
+        k0 ^ 0x6c79_6765_6e65_7261,
+        k1 ^ 0x7465_6462_7974_6573,
+    ];
+
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        k.copy_from_slice(chunk);
+        compress(&mut v, u64::from_le_bytes(k));
+    }
+
+    // the last block holds the remaining bytes and the length of the data
+    let mut last = [0; 8];
+    last[..chunks.remainder().len()].copy_from_slice(chunks.remainder());
+    last[7] = data.len() as u8;
+    compress(&mut v, u64::from_le_bytes(last));
+
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        round(&mut v);
+    }
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+
+    use crate::authority::Catalog;
+    use crate::proto::op::{Edns, Message, Query};
+    use crate::proto::rr::rdata::{
+        opt::{EdnsCode, EdnsOption},
+        TXT,
+    };
+    use crate::proto::rr::{Name, RData, RecordType};
+    use crate::server::tests::{example_catalog, send_request, udp_request};
+
+    use super::*;
+
+    fn hex(value: &str) -> Vec<u8> {
+        data_encoding::HEXLOWER.decode(value.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_siphash24() {
+        // test vector of the SipHash paper, appendix A
+        let key: [u8; 16] = core::array::from_fn(|i| i as u8);
+        let data: Vec<u8> = (0..15).collect();
+        assert_eq!(siphash24(&key, &data), 0xa129_ca61_49be_45e5);
+    }
+
+    #[test]
+    fn test_rfc9018_examples() {
+        let mut secret = [0; 16];
+        secret.copy_from_slice(&hex("e5e973e5a6b2a43f48e7dc849e37bfcf"));
+        let cookies = DnsCookies::with_secret(secret, false);
+        let mut client = [0; 8];
+        client.copy_from_slice(&hex("2464c4abcf10c957"));
+        let client_ip = IpAddr::V4(Ipv4Addr::new(198, 51, 100, 100));
+
+        // appendix A.1, learning a new server cookie
+        let query = Cookie::new(client, None);
+        let response = cookies.response_cookie(&query, client_ip, 1559731985);
+        assert_eq!(
+            response.server(),
+            Some(&hex("010000005cf79f111f8130c3eee29480")[..])
+        );
+
+        // appendix A.2, the same client learning a renewed server cookie
+        let query = Cookie::new(client, Some(hex("010000005cf79f111f8130c3eee29480")));
+        assert!(cookies.is_valid(&query, client_ip, 1559734385));
+        let response = cookies.response_cookie(&query, client_ip, 1559734385);
+        assert_eq!(
+            response.server(),
+            Some(&hex("010000005cf7a871d4a564a1442aca77")[..])
+        );
+    }
+
+    #[test]
+    fn test_is_valid() {
+        let cookies = DnsCookies::new(true);
+        let client_ip = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let query = Cookie::new([7; 8], None);
+        let now = 1_700_000_000;
+
+        assert!(!cookies.is_valid(&query, client_ip, now));
+
+        let cookie = cookies.response_cookie(&query, client_ip, now);
+        assert!(cookies.is_valid(&cookie, client_ip, now));
+        assert!(cookies.is_valid(&cookie, client_ip, now + MAX_AGE));
+        assert!(cookies.is_valid(&cookie, client_ip, now - MAX_SKEW));
+
+        // expired, or from too far in the future
+        assert!(!cookies.is_valid(&cookie, client_ip, now + MAX_AGE + 1));
+        assert!(!cookies.is_valid(&cookie, client_ip, now - MAX_SKEW - 1));
+
+        // issued for another client, or by another server
+        let other_ip = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+        assert!(!cookies.is_valid(&cookie, other_ip, now));
+        assert!(!DnsCookies::new(true).is_valid(&cookie, client_ip, now));
+        let other_client = Cookie::new([8; 8], cookie.server().map(<[u8]>::to_vec));
+        assert!(!cookies.is_valid(&other_client, client_ip, now));
+    }
+
+    /// A catalog where www.example.com. has about 2000 bytes of TXT records
+    fn catalog(cookies: DnsCookies) -> Catalog {
+        let www = Name::from_str("www.example.com.").unwrap();
+        let mut catalog = example_catalog((0..20).map(|i| {
+            let txt = RData::TXT(TXT::new(vec![format!("{i:02}").repeat(50)]));
+            Record::from_rdata(www.clone(), 3600, txt)
+        }));
+        catalog.set_dns_cookies(cookies);
+        catalog
+    }
+
+    async fn query(catalog: &Catalog, cookie: Option<Cookie>) -> Message {
+        let mut message = Message::new();
+        message.add_query(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::TXT,
+        ));
+        let mut edns = Edns::new();
+        edns.set_max_payload(4096);
+        if let Some(cookie) = cookie {
+            edns.options_mut().insert(EdnsOption::Cookie(cookie));
+        }
+        message.set_edns(edns);
+
+        let src = SocketAddr::from((Ipv4Addr::new(192, 0, 2, 1), 5353));
+        send_request(catalog, &udp_request(&message, src))
+            .await
+            .unwrap()
+    }
+
+    fn response_cookie(response: &Message) -> Cookie {
+        match response
+            .extensions()
+            .as_ref()
+            .unwrap()
+            .option(EdnsCode::Cookie)
+        {
+            Some(EdnsOption::Cookie(cookie)) => cookie.clone(),
+            option => panic!("unexpected cookie option: {option:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_max_unverified_udp_size() {
+        let catalog = catalog(DnsCookies::new(false).with_max_unverified_udp_size(512));
+
+        // without a cookie, or with only a client cookie, the response is truncated
+        let response = query(&catalog, None).await;
+        assert!(response.truncated());
+        assert!(response.to_vec().unwrap().len() <= 512);
+
+        let response = query(&catalog, Some(Cookie::new([7; 8], None))).await;
+        assert!(response.truncated());
+        assert!(response.answers().len() < 20);
+
+        // with the server cookie of the truncated response, it is sent in full
+        let cookie = response_cookie(&response);
+        let response = query(&catalog, Some(cookie)).await;
+        assert!(!response.truncated());
+        assert_eq!(response.answers().len(), 20);
+
+        // an invalid server cookie is not trusted
+        let invalid = Cookie::new([7; 8], Some(vec![0; 16]));
+        let response = query(&catalog, Some(invalid)).await;
+        assert!(response.truncated());
+    }
+
+    #[tokio::test]
+    async fn test_unlimited_unverified_udp_size() {
+        let catalog = catalog(DnsCookies::new(false));
+        let response = query(&catalog, None).await;
+        assert!(!response.truncated());
+        assert_eq!(response.answers().len(), 20);
+    }
+}
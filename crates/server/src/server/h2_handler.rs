@@ -11,6 +11,7 @@ use bytes::{Bytes, BytesMut};
 use futures_util::lock::Mutex;
 use h2::server;
 use hickory_proto::{http::Version, rr::Record};
+use http::{header::CACHE_CONTROL, HeaderValue, StatusCode};
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, warn};
@@ -52,6 +53,7 @@ pub(crate) async fn h2_handler<T, I>(
 
     // Accept all inbound HTTP/2.0 streams sent over the
     // connection.
+    let mut draining = false;
     loop {
         let (request, respond) = tokio::select! {
             result = h2.accept() => match result {
@@ -64,9 +66,12 @@ pub(crate) async fn h2_handler<T, I>(
                     return;
                 }
             },
-            _ = shutdown.cancelled() => {
-                // A graceful shutdown was initiated.
-                return
+            _ = shutdown.cancelled(), if !draining => {
+                // A graceful shutdown was initiated, GOAWAY is sent and the connection is
+                // polled until the responses in flight are sent
+                h2.graceful_shutdown();
+                draining = true;
+                continue;
             },
         };
 
@@ -80,7 +85,10 @@ pub(crate) async fn h2_handler<T, I>(
         tokio::spawn(async move {
             match h2_server::message_from(dns_hostname, http_endpoint, request).await {
                 Ok(bytes) => handle_request(bytes, src_addr, access, handler, responder).await,
-                Err(err) => warn!("error while handling request from {}: {}", src_addr, err),
+                Err(err) => {
+                    warn!("error while handling request from {}: {}", src_addr, err);
+                    responder.send_error(err.status_code()).await;
+                }
             };
         });
 
@@ -111,6 +119,26 @@ async fn handle_request<T>(
 #[derive(Clone)]
 struct HttpsResponseHandle(Arc<Mutex<server::SendResponse<Bytes>>>);
 
+impl HttpsResponseHandle {
+    /// Rejects the request with the HTTP `status`, without DNS response
+    async fn send_error(&self, status: StatusCode) {
+        use crate::proto::http::response;
+
+        let response = match response::error(Version::Http2, status) {
+            Ok(response) => response,
+            Err(err) => {
+                warn!("failed to create the {status} response: {err}");
+                return;
+            }
+        };
+
+        debug!("sending response: {:#?}", response);
+        if let Err(err) = self.0.lock().await.send_response(response, true) {
+            warn!("failed to send the {status} response: {err}");
+        }
+    }
+}
+
 #[async_trait::async_trait]
 impl ResponseHandler for HttpsResponseHandle {
     async fn send_response<'a>(
@@ -128,6 +156,7 @@ impl ResponseHandler for HttpsResponseHandle {
         use crate::proto::http::response;
         use crate::proto::serialize::binary::BinEncoder;
 
+        let (max_age, response) = response.into_max_age();
         let mut bytes = Vec::with_capacity(512);
         // mut block
         let info = {
@@ -135,7 +164,12 @@ impl ResponseHandler for HttpsResponseHandle {
             response.destructive_emit(&mut encoder)?
         };
         let bytes = Bytes::from(bytes);
-        let response = response::new(Version::Http2, bytes.len())?;
+        let mut response = response::new(Version::Http2, bytes.len())?;
+        if let Some(max_age) = max_age {
+            let cache_control = HeaderValue::from_str(&format!("max-age={max_age}"))
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            response.headers_mut().insert(CACHE_CONTROL, cache_control);
+        }
 
         debug!("sending response: {:#?}", response);
         let mut stream = self
@@ -149,3 +183,153 @@ impl ResponseHandler for HttpsResponseHandle {
         Ok(info)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use http::header::CONTENT_TYPE;
+    use http::{Request, Response};
+
+    use super::*;
+    use crate::authority::Catalog;
+    use crate::proto::http::request;
+    use crate::proto::op::{Message, Query, ResponseCode};
+    use crate::proto::rr::rdata::{A, SOA};
+    use crate::proto::rr::{Name, RData, RecordType};
+    use crate::server::tests::example_catalog;
+
+    fn name(name: &str) -> Name {
+        Name::from_str(name).unwrap()
+    }
+
+    /// A catalog with the example.com. zone, where www has an address, and the negative answers a
+    /// TTL of 60 seconds
+    fn catalog() -> Catalog {
+        let origin = name("example.com.");
+        let soa = SOA::new(origin.clone(), origin.clone(), 2, 3600, 600, 86400, 60);
+        example_catalog([
+            Record::from_rdata(origin, 3600, RData::SOA(soa)),
+            Record::from_rdata(
+                name("www.example.com."),
+                300,
+                RData::A(A::new(192, 0, 2, 1)),
+            ),
+        ])
+    }
+
+    fn query(query_name: &str) -> Vec<u8> {
+        let mut message = Message::new();
+        message.set_id(1);
+        message.add_query(Query::query(name(query_name), RecordType::A));
+        message.to_vec().unwrap()
+    }
+
+    /// Sends the request, with `body` if any, to the handler of a connection, returns the
+    /// response and its content
+    async fn send(request: Request<()>, body: Option<Vec<u8>>) -> (Response<()>, Vec<u8>) {
+        let (client_io, server_io) = tokio::io::duplex(4_096);
+        let shutdown = CancellationToken::new();
+        tokio::spawn(h2_handler(
+            Arc::new(AccessControl::default()),
+            Arc::new(catalog()),
+            server_io,
+            SocketAddr::from((Ipv4Addr::LOCALHOST, 443)),
+            Some(Arc::from("ns.example.com")),
+            Arc::from("/dns-query"),
+            shutdown.clone(),
+        ));
+
+        let (client, connection) = h2::client::handshake(client_io).await.unwrap();
+        tokio::spawn(connection);
+        let mut client = client.ready().await.unwrap();
+        let (response, mut stream) = client.send_request(request, body.is_none()).unwrap();
+        if let Some(body) = body {
+            stream.send_data(Bytes::from(body), true).unwrap();
+        }
+
+        let (response, mut body) = response.await.unwrap().into_parts();
+        let mut content = Vec::new();
+        while let Some(data) = body.data().await {
+            let data = data.unwrap();
+            let _ = body.flow_control().release_capacity(data.len());
+            content.extend_from_slice(&data);
+        }
+
+        shutdown.cancel();
+        (Response::from_parts(response, ()), content)
+    }
+
+    fn answer(response: &Response<()>, content: &[u8]) -> Message {
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE).unwrap(),
+            "application/dns-message"
+        );
+        Message::from_vec(content).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_get() {
+        let message = query("www.example.com.");
+        let request =
+            request::new_get(Version::Http2, "ns.example.com", "/dns-query", &message).unwrap();
+        let (response, content) = send(request, None).await;
+
+        let answer = answer(&response, &content);
+        assert_eq!(answer.response_code(), ResponseCode::NoError);
+        assert_eq!(answer.answers()[0].data(), &RData::A(A::new(192, 0, 2, 1)));
+        // the TTL of the answer
+        assert_eq!(
+            response.headers().get(CACHE_CONTROL).unwrap(),
+            "max-age=300"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_post() {
+        let message = query("nx.example.com.");
+        let request = request::new(
+            Version::Http2,
+            "ns.example.com",
+            "/dns-query",
+            message.len(),
+        )
+        .unwrap();
+        let (response, content) = send(request, Some(message)).await;
+
+        let answer = answer(&response, &content);
+        assert_eq!(answer.response_code(), ResponseCode::NXDomain);
+        // the minimum of the SOA record for the negative responses
+        assert_eq!(response.headers().get(CACHE_CONTROL).unwrap(), "max-age=60");
+    }
+
+    #[tokio::test]
+    async fn test_malformed_request() {
+        let request =
+            request::new_get(Version::Http2, "ns.example.com", "/dns-query", &[]).unwrap();
+        let (mut parts, ()) = request.into_parts();
+        parts.uri = "https://ns.example.com/dns-query?dns=not-base64url!"
+            .parse()
+            .unwrap();
+        let (response, content) = send(Request::from_parts(parts, ()), None).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(content.is_empty());
+
+        // the POST requests must have the DNS message content type
+        let message = query("www.example.com.");
+        let mut request = request::new(
+            Version::Http2,
+            "ns.example.com",
+            "/dns-query",
+            message.len(),
+        )
+        .unwrap();
+        request
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        let (response, _) = send(request, Some(message)).await;
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
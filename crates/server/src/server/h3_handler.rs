@@ -58,7 +58,9 @@ where
                 }
             },
             _ = shutdown.cancelled() => {
-                // A graceful shutdown was initiated.
+                // A graceful shutdown was initiated, GOAWAY is sent, the requests in flight are
+                // still answered
+                connection.shutdown().await?;
                 break;
             },
         };
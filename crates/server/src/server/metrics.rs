@@ -0,0 +1,553 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Metrics of the server, in the [Prometheus text format](https://prometheus.io/docs/instrumenting/exposition_formats/)
+//!
+//! All the metrics are labeled with the `transport` of the requests, one of `udp`, `tcp`, `tls`,
+//! `https`, `quic` or `h3`:
+//!
+//! | metric                                | type      | labels                 |
+//! |---------------------------------------|-----------|------------------------|
+//! | `hickory_queries_total`               | counter   | `transport`, `qtype`   |
+//! | `hickory_responses_total`             | counter   | `transport`, `rcode`   |
+//! | `hickory_cache_hits_total`            | counter   | `transport`            |
+//! | `hickory_cache_misses_total`          | counter   | `transport`            |
+//! | `hickory_response_duration_seconds`   | histogram | `transport`            |
+//! | `hickory_upstream_duration_seconds`   | histogram | `transport`            |
+//! | `hickory_active_connections`          | gauge     | `transport`            |
+//!
+//! The query types and response codes without a label of their own are counted as `OTHER`. The
+//! cache hits and misses count the responses of the forwarded and recursive zones, including the
+//! negative answers, the upstream duration is the time the resolver took to look up the misses.
+
+use std::{
+    fmt::{self, Write as _},
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::timeout,
+};
+use tracing::debug;
+
+use crate::{
+    proto::{
+        op::ResponseCode,
+        rr::{RecordType, RecordType::*},
+        xfer::Protocol,
+    },
+    server::{query_log::Rcode, CacheStatus, Middleware, MiddlewareAction, Request, ResponseInfo},
+};
+
+/// The label values of the transports
+const TRANSPORTS: [&str; 6] = ["udp", "tcp", "tls", "https", "quic", "h3"];
+
+/// The query types counted under their own label
+const QUERY_TYPES: [RecordType; 18] = [
+    A, AAAA, ANY, AXFR, CAA, CNAME, DNSKEY, DS, HTTPS, IXFR, MX, NAPTR, NS, PTR, SOA, SRV, SVCB,
+    TXT,
+];
+
+/// The response codes counted under their own label
+const RESPONSE_CODES: [ResponseCode; 11] = [
+    ResponseCode::NoError,
+    ResponseCode::FormErr,
+    ResponseCode::ServFail,
+    ResponseCode::NXDomain,
+    ResponseCode::NotImp,
+    ResponseCode::Refused,
+    ResponseCode::YXDomain,
+    ResponseCode::YXRRSet,
+    ResponseCode::NXRRSet,
+    ResponseCode::NotAuth,
+    ResponseCode::NotZone,
+];
+
+/// The upper bounds of the buckets of the duration histograms, in microseconds
+const DURATION_BUCKETS: [u64; 12] = [
+    500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 500_000, 1_000_000,
+    2_500_000,
+];
+
+/// How long a client of the metrics endpoint has to send its request
+const SCRAPE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The maximum size of the requests to the metrics endpoint
+const MAX_SCRAPE_REQUEST: usize = 8192;
+
+/// The metrics of a server
+///
+/// The metrics of the queries are collected as a [`Middleware`], the active connections by the
+/// [`ServerFuture`](crate::server::ServerFuture) they are given to. All the clones share the
+/// same counters, which are updated without locks.
+#[derive(Clone, Default)]
+pub struct ServerMetrics(Arc<Counters>);
+
+impl ServerMetrics {
+    /// Creates the metrics, with all the counters at zero
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a connection over `protocol` as active, until the returned guard is dropped
+    pub(crate) fn connection(&self, protocol: Protocol) -> Option<ConnectionGuard> {
+        let transport = transport(protocol)?;
+        self.0.connections[transport].fetch_add(1, Ordering::Relaxed);
+        Some(ConnectionGuard {
+            metrics: self.clone(),
+            transport,
+        })
+    }
+
+    /// Renders the current values of the metrics in the Prometheus text format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        self.0
+            .write(&mut out)
+            .expect("writing to a String can't fail");
+        out
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for ServerMetrics {
+    async fn on_request(&self, _request: &Request) -> MiddlewareAction {
+        MiddlewareAction::Continue
+    }
+
+    async fn on_response(&self, request: &Request, response: &ResponseInfo) {
+        let Some(transport) = transport(request.protocol()) else {
+            return;
+        };
+
+        let counters = &self.0;
+        let query_type = QUERY_TYPES
+            .iter()
+            .position(|t| *t == request.query().query_type())
+            .unwrap_or(QUERY_TYPES.len());
+        counters.queries[transport][query_type].fetch_add(1, Ordering::Relaxed);
+
+        let response_code = RESPONSE_CODES
+            .iter()
+            .position(|code| *code == response.response_code())
+            .unwrap_or(RESPONSE_CODES.len());
+        counters.responses[transport][response_code].fetch_add(1, Ordering::Relaxed);
+
+        let duration = request.received().elapsed();
+        counters.response_duration[transport].observe(duration);
+
+        match response.cache_status() {
+            Some(CacheStatus::Hit) => {
+                counters.cache_hits[transport].fetch_add(1, Ordering::Relaxed);
+            }
+            Some(CacheStatus::Miss(upstream_duration)) => {
+                counters.cache_misses[transport].fetch_add(1, Ordering::Relaxed);
+                counters.upstream_duration[transport].observe(upstream_duration);
+            }
+            None => {}
+        }
+    }
+}
+
+/// Decrements the active connections of a transport when dropped
+pub(crate) struct ConnectionGuard {
+    metrics: ServerMetrics,
+    transport: usize,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.metrics.0.connections[self.transport].fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// The index of the label of the transport of `protocol`
+fn transport(protocol: Protocol) -> Option<usize> {
+    match protocol {
+        Protocol::Udp => Some(0),
+        Protocol::Tcp => Some(1),
+        #[cfg(feature = "dns-over-rustls")]
+        Protocol::Tls => Some(2),
+        #[cfg(feature = "dns-over-https-rustls")]
+        Protocol::Https => Some(3),
+        #[cfg(feature = "dns-over-quic")]
+        Protocol::Quic => Some(4),
+        #[cfg(feature = "dns-over-h3")]
+        Protocol::H3 => Some(5),
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    queries: [[AtomicU64; QUERY_TYPES.len() + 1]; TRANSPORTS.len()],
+    responses: [[AtomicU64; RESPONSE_CODES.len() + 1]; TRANSPORTS.len()],
+    cache_hits: [AtomicU64; TRANSPORTS.len()],
+    cache_misses: [AtomicU64; TRANSPORTS.len()],
+    response_duration: [Histogram; TRANSPORTS.len()],
+    upstream_duration: [Histogram; TRANSPORTS.len()],
+    connections: [AtomicI64; TRANSPORTS.len()],
+}
+
+impl Counters {
+    fn write(&self, out: &mut String) -> fmt::Result {
+        header(
+            out,
+            "hickory_queries_total",
+            "counter",
+            "Queries received, by query type",
+        )?;
+        for (transport, counts) in TRANSPORTS.iter().zip(&self.queries) {
+            for (index, count) in counts.iter().enumerate() {
+                let count = count.load(Ordering::Relaxed);
+                if count == 0 {
+                    continue;
+                }
+
+                let qtype = match QUERY_TYPES.get(index) {
+                    Some(query_type) => query_type.to_string(),
+                    None => "OTHER".to_string(),
+                };
+                writeln!(
+                    out,
+                    "hickory_queries_total{{transport=\"{transport}\",qtype=\"{qtype}\"}} {count}"
+                )?;
+            }
+        }
+
+        header(
+            out,
+            "hickory_responses_total",
+            "counter",
+            "Responses sent, by response code",
+        )?;
+        for (transport, counts) in TRANSPORTS.iter().zip(&self.responses) {
+            for (index, count) in counts.iter().enumerate() {
+                let count = count.load(Ordering::Relaxed);
+                if count == 0 {
+                    continue;
+                }
+
+                let rcode = match RESPONSE_CODES.get(index) {
+                    Some(code) => Rcode(*code).to_string(),
+                    None => "OTHER".to_string(),
+                };
+                writeln!(
+                    out,
+                    "hickory_responses_total{{transport=\"{transport}\",rcode=\"{rcode}\"}} {count}"
+                )?;
+            }
+        }
+
+        for (name, help, counts) in [
+            (
+                "hickory_cache_hits_total",
+                "Responses of the forwarded zones answered from the cache",
+                &self.cache_hits,
+            ),
+            (
+                "hickory_cache_misses_total",
+                "Responses of the forwarded zones answered by the upstream name servers",
+                &self.cache_misses,
+            ),
+        ] {
+            header(out, name, "counter", help)?;
+            for (transport, count) in TRANSPORTS.iter().zip(counts) {
+                let count = count.load(Ordering::Relaxed);
+                writeln!(out, "{name}{{transport=\"{transport}\"}} {count}")?;
+            }
+        }
+
+        for (name, help, histograms) in [
+            (
+                "hickory_response_duration_seconds",
+                "Time from the reception of the requests to their responses",
+                &self.response_duration,
+            ),
+            (
+                "hickory_upstream_duration_seconds",
+                "Time taken to answer the cache misses of the forwarded zones",
+                &self.upstream_duration,
+            ),
+        ] {
+            header(out, name, "histogram", help)?;
+            for (transport, histogram) in TRANSPORTS.iter().zip(histograms) {
+                histogram.write(out, name, transport)?;
+            }
+        }
+
+        header(
+            out,
+            "hickory_active_connections",
+            "gauge",
+            "Connections currently open",
+        )?;
+        for (transport, count) in TRANSPORTS.iter().zip(&self.connections) {
+            let count = count.load(Ordering::Relaxed);
+            writeln!(
+                out,
+                "hickory_active_connections{{transport=\"{transport}\"}} {count}"
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) -> fmt::Result {
+    writeln!(out, "# HELP {name} {help}")?;
+    writeln!(out, "# TYPE {name} {kind}")
+}
+
+/// A histogram of durations, with the buckets of `DURATION_BUCKETS`
+#[derive(Default)]
+struct Histogram {
+    /// The observations of each bucket, not cumulative, the last one is `+Inf`
+    buckets: [AtomicU64; DURATION_BUCKETS.len() + 1],
+    /// The sum of the observations, in microseconds
+    sum: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    fn observe(&self, duration: Duration) {
+        let micros = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        let bucket = DURATION_BUCKETS
+            .iter()
+            .position(|bound| micros <= *bound)
+            .unwrap_or(DURATION_BUCKETS.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(micros, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn write(&self, out: &mut String, name: &str, transport: &str) -> fmt::Result {
+        let mut cumulative = 0;
+        for (index, bucket) in self.buckets.iter().enumerate() {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let le = match DURATION_BUCKETS.get(index) {
+                Some(bound) => Seconds(*bound).to_string(),
+                None => "+Inf".to_string(),
+            };
+            writeln!(
+                out,
+                "{name}_bucket{{transport=\"{transport}\",le=\"{le}\"}} {cumulative}"
+            )?;
+        }
+
+        let sum = Seconds(self.sum.load(Ordering::Relaxed));
+        let count = self.count.load(Ordering::Relaxed);
+        writeln!(out, "{name}_sum{{transport=\"{transport}\"}} {sum}")?;
+        writeln!(out, "{name}_count{{transport=\"{transport}\"}} {count}")
+    }
+}
+
+/// A number of microseconds, formatted in seconds
+struct Seconds(u64);
+
+impl fmt::Display for Seconds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (seconds, micros) = (self.0 / 1_000_000, self.0 % 1_000_000);
+        if micros == 0 {
+            return write!(f, "{seconds}");
+        }
+
+        let fraction = format!("{micros:06}");
+        write!(f, "{seconds}.{}", fraction.trim_end_matches('0'))
+    }
+}
+
+/// Answers a request of the metrics endpoint, over HTTP/1.1, and closes the connection
+///
+/// `GET /metrics` is answered with the rendered metrics, anything else with an error.
+pub(crate) async fn serve_scrape(mut stream: TcpStream, metrics: ServerMetrics) {
+    let mut request = Vec::new();
+    let read = timeout(SCRAPE_TIMEOUT, async {
+        let mut buf = [0; 1024];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            let len = stream.read(&mut buf).await?;
+            if len == 0 || request.len() + len > MAX_SCRAPE_REQUEST {
+                return Err(std::io::ErrorKind::InvalidData.into());
+            }
+            request.extend_from_slice(&buf[..len]);
+        }
+
+        Ok::<_, std::io::Error>(())
+    })
+    .await;
+
+    if let Err(e) = read.unwrap_or_else(|_| Err(std::io::ErrorKind::TimedOut.into())) {
+        debug!("failed to read the metrics request: {e}");
+        return;
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+    let (status, body) = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", metrics.render()),
+        (Some("GET"), _) => ("404 Not Found", String::new()),
+        _ => ("405 Method Not Allowed", String::new()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    if let Err(e) = stream.write_all(response.as_bytes()).await {
+        debug!("failed to send the metrics: {e}");
+    }
+    let _ = stream.shutdown().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+
+    use tokio::net::{TcpListener, UdpSocket};
+
+    use crate::proto::op::{Header, Message, Query};
+    use crate::proto::rr::rdata::A;
+    use crate::proto::rr::{Name, RData, Record};
+    use crate::server::tests::{example_catalog, udp_request};
+    use crate::server::{MiddlewareChain, ServerFuture};
+
+    use super::*;
+
+    async fn scrape(addr: std::net::SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_scrape_metrics() {
+        let metrics = ServerMetrics::new();
+        let mut chain = MiddlewareChain::new(example_catalog([Record::from_rdata(
+            Name::from_str("www.example.com.").unwrap(),
+            3600,
+            RData::A(A::new(192, 0, 2, 1)),
+        )]));
+        chain.push(metrics.clone());
+        let mut server_future = ServerFuture::new(chain);
+        server_future.set_metrics(metrics.clone());
+
+        let udp = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let udp_addr = udp.local_addr().unwrap();
+        server_future.register_socket(udp);
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let metrics_addr = listener.local_addr().unwrap();
+        server_future.register_metrics_listener(listener, metrics);
+
+        let client = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        for name in ["www.example.com.", "missing.example.com."] {
+            let mut message = Message::new();
+            message.add_query(Query::query(Name::from_str(name).unwrap(), RecordType::A));
+            client
+                .send_to(&message.to_vec().unwrap(), udp_addr)
+                .await
+                .unwrap();
+            let mut buf = [0; 512];
+            client.recv_from(&mut buf).await.unwrap();
+        }
+
+        let response = scrape(metrics_addr, "/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        for line in [
+            "# TYPE hickory_queries_total counter",
+            "hickory_queries_total{transport=\"udp\",qtype=\"A\"} 2",
+            "hickory_responses_total{transport=\"udp\",rcode=\"NOERROR\"} 1",
+            "hickory_responses_total{transport=\"udp\",rcode=\"NXDOMAIN\"} 1",
+            "hickory_cache_hits_total{transport=\"udp\"} 0",
+            "hickory_cache_misses_total{transport=\"udp\"} 0",
+            "hickory_response_duration_seconds_count{transport=\"udp\"} 2",
+            "hickory_upstream_duration_seconds_count{transport=\"udp\"} 0",
+            "hickory_active_connections{transport=\"tcp\"} 0",
+        ] {
+            assert!(response.lines().any(|l| l == line), "missing {line}");
+        }
+
+        let response = scrape(metrics_addr, "/").await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+        server_future.shutdown_gracefully().await.unwrap();
+    }
+
+    #[test]
+    fn test_histogram() {
+        let histogram = Histogram::default();
+        histogram.observe(Duration::from_micros(300));
+        histogram.observe(Duration::from_millis(3));
+        histogram.observe(Duration::from_secs(10));
+
+        let mut out = String::new();
+        histogram.write(&mut out, "test", "udp").unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "test_bucket{transport=\"udp\",le=\"0.0005\"} 1");
+        assert_eq!(lines[3], "test_bucket{transport=\"udp\",le=\"0.005\"} 2");
+        assert_eq!(lines[11], "test_bucket{transport=\"udp\",le=\"2.5\"} 2");
+        assert_eq!(lines[12], "test_bucket{transport=\"udp\",le=\"+Inf\"} 3");
+        assert_eq!(lines[13], "test_sum{transport=\"udp\"} 10.0033");
+        assert_eq!(lines[14], "test_count{transport=\"udp\"} 3");
+    }
+
+    #[test]
+    fn test_active_connections() {
+        let metrics = ServerMetrics::new();
+        let first = metrics.connection(Protocol::Tcp);
+        let second = metrics.connection(Protocol::Tcp);
+        drop(first);
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("hickory_active_connections{transport=\"tcp\"} 1\n"));
+        drop(second);
+        let rendered = metrics.render();
+        assert!(rendered.contains("hickory_active_connections{transport=\"tcp\"} 0\n"));
+    }
+
+    #[tokio::test]
+    async fn test_cache_status() {
+        let mut message = Message::new();
+        message.add_query(Query::query(Name::root(), RecordType::A));
+        let request = udp_request(&message, SocketAddr::from((Ipv4Addr::LOCALHOST, 5353)));
+
+        // the negative answers from the cache are hits as well, the zones are neither
+        let metrics = ServerMetrics::new();
+        let mut header = Header::response_from_request(request.header());
+        header.set_response_code(ResponseCode::NXDomain);
+        for cache_status in [
+            Some(CacheStatus::Hit),
+            Some(CacheStatus::Miss(Duration::from_millis(3))),
+            None,
+        ] {
+            let response = ResponseInfo::from(header).with_cache_status(cache_status);
+            metrics.on_response(&request, &response).await;
+        }
+
+        let rendered = metrics.render();
+        for line in [
+            "hickory_cache_hits_total{transport=\"udp\"} 1",
+            "hickory_cache_misses_total{transport=\"udp\"} 1",
+            "hickory_upstream_duration_seconds_bucket{transport=\"udp\",le=\"0.0025\"} 0",
+            "hickory_upstream_duration_seconds_bucket{transport=\"udp\",le=\"0.005\"} 1",
+            "hickory_upstream_duration_seconds_count{transport=\"udp\"} 1",
+            "hickory_response_duration_seconds_count{transport=\"udp\"} 3",
+        ] {
+            assert!(rendered.lines().any(|l| l == line), "missing {line}");
+        }
+    }
+}
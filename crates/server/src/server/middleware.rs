@@ -0,0 +1,300 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Hooks on the requests and responses of a handler, for logging, rewriting or blocking queries
+
+use std::sync::Arc;
+
+use tracing::{debug, error};
+
+use crate::{
+    authority::MessageResponseBuilder,
+    proto::op::ResponseCode,
+    server::{Request, RequestHandler, ResponseHandler, ResponseInfo},
+};
+
+/// What to do with a request after a [`Middleware`] inspected it
+pub enum MiddlewareAction {
+    /// Passes the request to the next middleware, or the handler
+    Continue,
+    /// Passes the replacement request to the next middleware, or the handler, e.g. a request
+    /// with a rewritten query
+    Replace(Box<Request>),
+    /// Answers the request with the response code, without the rest of the chain and the handler
+    Respond(ResponseCode),
+}
+
+/// A hook on the requests passed to a handler, and the responses sent by the handler
+#[async_trait::async_trait]
+pub trait Middleware: Send + Sync + 'static {
+    /// Inspects the request before it is passed down the chain
+    async fn on_request(&self, request: &Request) -> MiddlewareAction;
+
+    /// Inspects the response to the request, once sent
+    ///
+    /// Called in the reverse order of the chain, not called for the middlewares after the one
+    /// which short-circuited the request. `request` is the request as seen by this middleware,
+    /// before any replacement by the next ones.
+    async fn on_response(&self, request: &Request, response: &ResponseInfo) {
+        let _ = (request, response);
+    }
+}
+
+/// A chain of middlewares in front of a handler
+///
+/// The middlewares see the requests in the order they are pushed. Each one can let a request
+/// through, replace it, or answer it directly, e.g. with NXDOMAIN for a blocked name.
+pub struct MiddlewareChain<T: RequestHandler> {
+    middlewares: Vec<Arc<dyn Middleware>>,
+    handler: T,
+}
+
+impl<T: RequestHandler> MiddlewareChain<T> {
+    /// Creates the chain without middlewares, passing all the requests to `handler`
+    pub fn new(handler: T) -> Self {
+        Self {
+            middlewares: Vec::new(),
+            handler,
+        }
+    }
+
+    /// Appends a middleware, it sees the requests after the middlewares already in the chain
+    pub fn push(&mut self, middleware: impl Middleware) {
+        self.middlewares.push(Arc::new(middleware));
+    }
+
+    /// The handler of the requests let through by the middlewares
+    pub fn handler(&self) -> &T {
+        &self.handler
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: RequestHandler> RequestHandler for MiddlewareChain<T> {
+    async fn handle_request<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        response_handle: R,
+    ) -> ResponseInfo {
+        let mut replacements = Vec::<Box<Request>>::new();
+        // the replacement seen by each middleware, `None` for the original request
+        let mut seen = Vec::with_capacity(self.middlewares.len());
+        let mut response_code = None;
+
+        for middleware in &self.middlewares {
+            seen.push(replacements.len().checked_sub(1));
+            let action = middleware
+                .on_request(replacements.last().map_or(request, |r| &**r))
+                .await;
+
+            match action {
+                MiddlewareAction::Continue => (),
+                MiddlewareAction::Replace(request) => replacements.push(request),
+                MiddlewareAction::Respond(code) => {
+                    response_code = Some(code);
+                    break;
+                }
+            }
+        }
+
+        let current = replacements.last().map_or(request, |r| &**r);
+        let response = match response_code {
+            Some(response_code) => respond(current, response_code, response_handle).await,
+            None => self.handler.handle_request(current, response_handle).await,
+        };
+
+        for (middleware, index) in self.middlewares.iter().zip(seen).rev() {
+            let request = index.map_or(request, |index| &replacements[index]);
+            middleware.on_response(request, &response).await;
+        }
+
+        response
+    }
+}
+
+/// Answers `request` with an empty response of `response_code`
+async fn respond<R: ResponseHandler>(
+    request: &Request,
+    response_code: ResponseCode,
+    mut response_handle: R,
+) -> ResponseInfo {
+    debug!(
+        "request:{id} answered by a middleware: {response_code}",
+        id = request.id()
+    );
+
+    let response = MessageResponseBuilder::new(Some(request.raw_query()));
+    let result = response_handle
+        .send_response(response.error_msg(request.header(), response_code))
+        .await;
+
+    match result {
+        Ok(response) => response,
+        Err(e) => {
+            error!("failed to send response: {e}");
+            ResponseInfo::serve_failed()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+    use std::sync::Mutex;
+
+    use crate::authority::Catalog;
+    use crate::proto::op::{Message, Query};
+    use crate::proto::rr::rdata::A;
+    use crate::proto::rr::{Name, RData, Record, RecordType};
+    use crate::server::tests::{example_catalog, send_request, udp_request};
+
+    use super::*;
+
+    fn name(name: &str) -> Name {
+        Name::from_str(name).unwrap()
+    }
+
+    /// A catalog with the example.com. zone, where www and blocked have addresses
+    fn catalog() -> Catalog {
+        example_catalog(
+            [
+                ("www.example.com.", A::new(192, 0, 2, 1)),
+                ("blocked.example.com.", A::new(192, 0, 2, 2)),
+            ]
+            .map(|(owner, a)| Record::from_rdata(name(owner), 3600, RData::A(a))),
+        )
+    }
+
+    fn new_request(query_name: &str) -> Request {
+        let mut message = Message::new();
+        message.set_id(1);
+        message.add_query(Query::query(name(query_name), RecordType::A));
+        udp_request(&message, SocketAddr::from((Ipv4Addr::LOCALHOST, 53)))
+    }
+
+    async fn query<T: RequestHandler>(handler: &T, query_name: &str) -> Message {
+        send_request(handler, &new_request(query_name))
+            .await
+            .unwrap()
+    }
+
+    /// Refuses the queries of the blocked names
+    struct Blocklist(Vec<Name>);
+
+    #[async_trait::async_trait]
+    impl Middleware for Blocklist {
+        async fn on_request(&self, request: &Request) -> MiddlewareAction {
+            let query_name = Name::from(request.query().name());
+            if self.0.contains(&query_name) {
+                return MiddlewareAction::Respond(ResponseCode::Refused);
+            }
+
+            MiddlewareAction::Continue
+        }
+    }
+
+    /// Logs the queried names and the response codes
+    struct Recorder(&'static str, Arc<Mutex<Vec<String>>>);
+
+    #[async_trait::async_trait]
+    impl Middleware for Recorder {
+        async fn on_request(&self, request: &Request) -> MiddlewareAction {
+            let entry = format!("{} request {}", self.0, request.query().name());
+            self.1.lock().unwrap().push(entry);
+            MiddlewareAction::Continue
+        }
+
+        async fn on_response(&self, request: &Request, response: &ResponseInfo) {
+            let entry = format!(
+                "{} response {} {}",
+                self.0,
+                request.query().name(),
+                response.response_code()
+            );
+            self.1.lock().unwrap().push(entry);
+        }
+    }
+
+    /// Rewrites the queries of a name into the queries of another name
+    struct Rewrite(Name, &'static str);
+
+    #[async_trait::async_trait]
+    impl Middleware for Rewrite {
+        async fn on_request(&self, request: &Request) -> MiddlewareAction {
+            if Name::from(request.query().name()) != self.0 {
+                return MiddlewareAction::Continue;
+            }
+
+            MiddlewareAction::Replace(Box::new(new_request(self.1)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocklist() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut chain = MiddlewareChain::new(catalog());
+        chain.push(Recorder("first", log.clone()));
+        chain.push(Blocklist(vec![name("blocked.example.com.")]));
+        chain.push(Recorder("last", log.clone()));
+
+        let response = query(&chain, "blocked.example.com.").await;
+        assert_eq!(response.response_code(), ResponseCode::Refused);
+        assert!(response.answers().is_empty());
+        assert_eq!(response.queries()[0].name(), &name("blocked.example.com."));
+
+        let response = query(&chain, "www.example.com.").await;
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(
+            response.answers()[0].data(),
+            &RData::A(A::new(192, 0, 2, 1))
+        );
+
+        // the middlewares after the blocklist don't see the blocked request
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "first request blocked.example.com.",
+                "first response blocked.example.com. Query Refused",
+                "first request www.example.com.",
+                "last request www.example.com.",
+                "last response www.example.com. No Error",
+                "first response www.example.com. No Error",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rewrite() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut chain = MiddlewareChain::new(catalog());
+        chain.push(Recorder("first", log.clone()));
+        chain.push(Rewrite(name("alias.example.com."), "www.example.com."));
+        chain.push(Blocklist(vec![name("www.example.com.")]));
+
+        // the rewritten request is seen by the next middlewares
+        let response = query(&chain, "alias.example.com.").await;
+        assert_eq!(response.response_code(), ResponseCode::Refused);
+
+        chain.middlewares.pop();
+        let response = query(&chain, "alias.example.com.").await;
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(
+            response.answers()[0].data(),
+            &RData::A(A::new(192, 0, 2, 1))
+        );
+
+        // the first middleware sees the original request
+        assert_eq!(
+            log.lock().unwrap()[..2],
+            [
+                "first request alias.example.com.",
+                "first response alias.example.com. Query Refused",
+            ]
+        );
+    }
+}
@@ -7,18 +7,108 @@
 
 //! `Server` component for hosting a domain name servers operations.
 
+mod acl;
+mod cookies;
 #[cfg(feature = "dns-over-https-rustls")]
 mod h2_handler;
 #[cfg(feature = "dns-over-h3")]
 mod h3_handler;
+mod metrics;
+mod middleware;
+mod query_log;
 #[cfg(feature = "dns-over-quic")]
 mod quic_handler;
+mod rate_limit;
 mod request_handler;
 mod response_handler;
 mod server_future;
 mod timeout_stream;
+mod views;
 
-pub use self::request_handler::{Request, RequestHandler, RequestInfo, ResponseInfo};
+pub use self::acl::{AccessLists, Acl};
+pub use self::cookies::DnsCookies;
+pub(crate) use self::cookies::UnverifiedResponseHandler;
+pub use self::metrics::ServerMetrics;
+pub use self::middleware::{Middleware, MiddlewareAction, MiddlewareChain};
+pub use self::query_log::QueryLog;
+pub use self::rate_limit::ResponseRateLimit;
+pub use self::request_handler::{CacheStatus, Request, RequestHandler, RequestInfo, ResponseInfo};
 pub use self::response_handler::{ResponseHandle, ResponseHandler};
 pub use self::server_future::ServerFuture;
 pub use self::timeout_stream::TimeoutStream;
+pub use self::views::{View, Views};
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::net::SocketAddr;
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    use futures_util::StreamExt;
+
+    use crate::authority::{AuthorityObject, Catalog, MessageRequest, ZoneType};
+    use crate::proto::op::Message;
+    use crate::proto::rr::rdata::SOA;
+    use crate::proto::rr::{Name, RData, Record};
+    use crate::proto::serialize::binary::{BinDecodable, BinDecoder};
+    use crate::proto::xfer::Protocol;
+    use crate::proto::BufDnsStreamHandle;
+    use crate::server::{Request, RequestHandler, ResponseHandle};
+    use crate::store::in_memory::InMemoryAuthority;
+
+    /// The catalog serving the zone `origin` from the `authorities`
+    pub(crate) fn zone_catalog(
+        origin: Name,
+        authorities: Vec<Arc<dyn AuthorityObject>>,
+    ) -> Catalog {
+        let mut catalog = Catalog::new();
+        catalog.upsert(origin.into(), authorities);
+        catalog
+    }
+
+    /// The catalog of the example.com. zone, with its SOA and the `records`
+    ///
+    /// The SOA has a serial of 1 and a MINIMUM of 0, a SOA with a greater serial in `records`
+    /// replaces it.
+    pub(crate) fn example_catalog(records: impl IntoIterator<Item = Record>) -> Catalog {
+        let origin = Name::from_str("example.com.").unwrap();
+        let mut authority = InMemoryAuthority::empty(
+            origin.clone(),
+            ZoneType::Primary,
+            false,
+            #[cfg(feature = "dnssec-ring")]
+            None,
+        );
+        let soa = SOA::new(origin.clone(), origin.clone(), 1, 3600, 600, 86400, 0);
+        authority.upsert_mut(Record::from_rdata(origin.clone(), 3600, RData::SOA(soa)), 1);
+        for record in records {
+            authority.upsert_mut(record, 1);
+        }
+
+        zone_catalog(origin, vec![Arc::new(authority)])
+    }
+
+    /// The request of the `message`, received over UDP from `src`
+    pub(crate) fn udp_request(message: &Message, src: SocketAddr) -> Request {
+        let bytes = message.to_vec().unwrap();
+        let message = MessageRequest::read(&mut BinDecoder::new(&bytes)).unwrap();
+        Request::new(message, src, Protocol::Udp)
+    }
+
+    /// The response of the `handler` to the `request`, `None` if it was dropped
+    pub(crate) async fn send_request(
+        handler: &impl RequestHandler,
+        request: &Request,
+    ) -> Option<Message> {
+        let (stream_handle, mut receiver) = BufDnsStreamHandle::new(request.src());
+        handler
+            .handle_request(
+                request,
+                ResponseHandle::new(request.src(), stream_handle, request.protocol()),
+            )
+            .await;
+
+        let response = receiver.next().await?;
+        Some(Message::from_vec(response.bytes()).unwrap())
+    }
+}
@@ -0,0 +1,350 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Query log in the [JSON lines](https://jsonlines.org) format
+//!
+//! Each answered query is written as one JSON object per line, with the fields:
+//!
+//! | field        | type    | description                                                      |
+//! |--------------|---------|------------------------------------------------------------------|
+//! | `timestamp`  | string  | time at which the response was sent, RFC 3339 in UTC            |
+//! | `client`     | string  | IP address of the client                                         |
+//! | `qname`      | string  | queried name, fully qualified                                    |
+//! | `qtype`      | string  | queried record type, e.g. `AAAA`                                 |
+//! | `rcode`      | string  | response code, e.g. `NXDOMAIN`, or `RCODE<n>` for unknown codes  |
+//! | `latency_us` | integer | microseconds from the reception of the request to the response  |
+//! | `protocol`   | string  | protocol of the request, e.g. `udp` or `https`                   |
+//! | `cache_hit`  | boolean | whether the answers came from the forwarder or recursor cache    |
+//!
+//! The fields are always written in this order. New fields may be appended, existing ones are
+//! not renamed or removed.
+
+use std::{
+    fmt,
+    io::{self, BufWriter, Write},
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use time::OffsetDateTime;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::warn;
+
+use crate::{
+    proto::{
+        op::ResponseCode,
+        rr::{Name, RecordType},
+        xfer::Protocol,
+    },
+    server::{Middleware, MiddlewareAction, Request, ResponseInfo},
+};
+
+/// The number of records buffered by default, see [`QueryLog::with_capacity`]
+const DEFAULT_CAPACITY: usize = 4096;
+
+/// A [`Middleware`] writing the answered queries to a query log, in JSON lines
+///
+/// The records are passed through a bounded channel to a thread which writes them, so that a
+/// slow writer never delays the responses. Records which don't fit in the channel are dropped
+/// and counted, see [`QueryLog::dropped`].
+///
+/// The log should be the first middleware of the chain, to see the requests as sent by the
+/// clients, and to measure the latency of the whole chain.
+#[derive(Clone)]
+pub struct QueryLog {
+    sender: mpsc::Sender<QueryLogRecord>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl QueryLog {
+    /// Writes the query log to `writer`, buffering up to 4096 records
+    pub fn new(writer: impl Write + Send + 'static) -> io::Result<Self> {
+        Self::with_capacity(writer, DEFAULT_CAPACITY)
+    }
+
+    /// Writes the query log to `writer`, buffering up to `capacity` records
+    ///
+    /// The writer is owned by a dedicated thread, which stops once all the clones of the log are
+    /// dropped.
+    pub fn with_capacity(writer: impl Write + Send + 'static, capacity: usize) -> io::Result<Self> {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        thread::Builder::new()
+            .name("hickory-query-log".to_string())
+            .spawn(move || write_records(receiver, writer))?;
+
+        Ok(Self {
+            sender,
+            dropped: Arc::default(),
+        })
+    }
+
+    /// The number of records dropped because the writer could not keep up
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn log(&self, record: QueryLogRecord) {
+        match self.sender.try_send(record) {
+            Ok(()) => (),
+            Err(TrySendError::Full(_)) | Err(TrySendError::Closed(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for QueryLog {
+    async fn on_request(&self, _request: &Request) -> MiddlewareAction {
+        MiddlewareAction::Continue
+    }
+
+    async fn on_response(&self, request: &Request, response: &ResponseInfo) {
+        let query = request.query();
+        self.log(QueryLogRecord {
+            timestamp: SystemTime::now(),
+            client: request.src().ip(),
+            query_name: query.name().into(),
+            query_type: query.query_type(),
+            response_code: response.response_code(),
+            latency: request.received().elapsed(),
+            protocol: request.protocol(),
+            cache_hit: response.cache_hit(),
+        });
+    }
+}
+
+/// Writes the records until all the senders are dropped, flushing whenever the channel is empty
+fn write_records(mut receiver: mpsc::Receiver<QueryLogRecord>, writer: impl Write) {
+    let mut writer = BufWriter::new(writer);
+    while let Some(record) = receiver.blocking_recv() {
+        let mut result = writeln!(writer, "{record}");
+        while let Ok(record) = receiver.try_recv() {
+            result = result.and_then(|()| writeln!(writer, "{record}"));
+        }
+
+        if let Err(e) = result.and_then(|()| writer.flush()) {
+            warn!("failed to write the query log: {e}");
+        }
+    }
+}
+
+/// A query answered by the server
+#[derive(Clone, Debug)]
+struct QueryLogRecord {
+    timestamp: SystemTime,
+    client: IpAddr,
+    query_name: Name,
+    query_type: RecordType,
+    response_code: ResponseCode,
+    latency: Duration,
+    protocol: Protocol,
+    cache_hit: bool,
+}
+
+impl fmt::Display for QueryLogRecord {
+    /// Formats the record as a JSON object, without the line feed
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let timestamp = OffsetDateTime::from(self.timestamp);
+        write!(
+            f,
+            "{{\"timestamp\":\"{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z\"",
+            timestamp.year(),
+            u8::from(timestamp.month()),
+            timestamp.day(),
+            timestamp.hour(),
+            timestamp.minute(),
+            timestamp.second(),
+            timestamp.microsecond(),
+        )?;
+        write!(f, ",\"client\":\"{}\"", self.client)?;
+        write!(
+            f,
+            ",\"qname\":\"{}\"",
+            JsonEscaped(&self.query_name.to_string())
+        )?;
+        write!(f, ",\"qtype\":\"{}\"", self.query_type)?;
+        write!(f, ",\"rcode\":\"{}\"", Rcode(self.response_code))?;
+        write!(f, ",\"latency_us\":{}", self.latency.as_micros())?;
+        write!(f, ",\"protocol\":\"{}\"", self.protocol)?;
+        write!(f, ",\"cache_hit\":{}}}", self.cache_hit)
+    }
+}
+
+/// The content of a JSON string, with the quotes, backslashes and control characters escaped
+struct JsonEscaped<'a>(&'a str);
+
+impl fmt::Display for JsonEscaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                c if c.is_control() => write!(f, "\\u{:04x}", u32::from(c))?,
+                c => write!(f, "{c}")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The mnemonic of a response code, as in the presentation format of the zone files
+pub(crate) struct Rcode(pub(crate) ResponseCode);
+
+impl fmt::Display for Rcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mnemonic = match self.0 {
+            ResponseCode::NoError => "NOERROR",
+            ResponseCode::FormErr => "FORMERR",
+            ResponseCode::ServFail => "SERVFAIL",
+            ResponseCode::NXDomain => "NXDOMAIN",
+            ResponseCode::NotImp => "NOTIMP",
+            ResponseCode::Refused => "REFUSED",
+            ResponseCode::YXDomain => "YXDOMAIN",
+            ResponseCode::YXRRSet => "YXRRSET",
+            ResponseCode::NXRRSet => "NXRRSET",
+            ResponseCode::NotAuth => "NOTAUTH",
+            ResponseCode::NotZone => "NOTZONE",
+            ResponseCode::BADVERS | ResponseCode::BADSIG => "BADVERS",
+            ResponseCode::BADKEY => "BADKEY",
+            ResponseCode::BADTIME => "BADTIME",
+            ResponseCode::BADMODE => "BADMODE",
+            ResponseCode::BADNAME => "BADNAME",
+            ResponseCode::BADALG => "BADALG",
+            ResponseCode::BADTRUNC => "BADTRUNC",
+            ResponseCode::BADCOOKIE => "BADCOOKIE",
+            code @ ResponseCode::Unknown(_) => return write!(f, "RCODE{}", u16::from(code)),
+        };
+
+        f.write_str(mnemonic)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    use crate::proto::op::{Message, Query};
+    use crate::proto::rr::rdata::A;
+    use crate::proto::rr::{RData, Record};
+    use crate::server::tests::{example_catalog, send_request, udp_request};
+    use crate::server::MiddlewareChain;
+
+    use super::*;
+
+    /// A writer to a buffer shared with the test
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_log_record() {
+        let buffer = SharedBuffer::default();
+        let log = QueryLog::new(buffer.clone()).unwrap();
+        let mut chain = MiddlewareChain::new(example_catalog([Record::from_rdata(
+            Name::from_str("www.example.com.").unwrap(),
+            3600,
+            RData::A(A::new(192, 0, 2, 1)),
+        )]));
+        chain.push(log.clone());
+
+        let mut message = Message::new();
+        message.add_query(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        let src = SocketAddr::from((Ipv4Addr::new(192, 0, 2, 53), 5353));
+        send_request(&chain, &udp_request(&message, src))
+            .await
+            .unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let line = loop {
+            let output = buffer.0.lock().unwrap().clone();
+            if output.ends_with(b"\n") {
+                break String::from_utf8(output).unwrap();
+            }
+
+            assert!(Instant::now() < deadline, "query log not written");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+
+        let (timestamp, rest) = line
+            .strip_prefix("{\"timestamp\":\"")
+            .unwrap()
+            .split_once('"')
+            .unwrap();
+        assert_eq!(timestamp.len(), "2024-01-01T00:00:00.000000Z".len());
+        assert!(timestamp.ends_with('Z'));
+
+        let (head, rest) = rest.split_once(",\"latency_us\":").unwrap();
+        assert_eq!(
+            head,
+            ",\"client\":\"192.0.2.53\",\"qname\":\"www.example.com.\",\"qtype\":\"A\",\"rcode\":\"NOERROR\""
+        );
+        let (latency, tail) = rest.split_once(',').unwrap();
+        assert!(latency.parse::<u64>().is_ok());
+        assert_eq!(tail, "\"protocol\":\"udp\",\"cache_hit\":false}\n");
+        assert_eq!(log.dropped(), 0);
+    }
+
+    #[test]
+    fn test_query_log_dropped() {
+        // the receiver is not drained, as if the writer was blocked
+        let (sender, _receiver) = mpsc::channel(1);
+        let log = QueryLog {
+            sender,
+            dropped: Arc::default(),
+        };
+
+        let record = QueryLogRecord {
+            timestamp: SystemTime::UNIX_EPOCH,
+            client: IpAddr::from(Ipv4Addr::LOCALHOST),
+            query_name: Name::root(),
+            query_type: RecordType::NS,
+            response_code: ResponseCode::NoError,
+            latency: Duration::ZERO,
+            protocol: Protocol::Tcp,
+            cache_hit: true,
+        };
+        log.log(record.clone());
+        log.log(record.clone());
+        log.log(record.clone());
+        assert_eq!(log.dropped(), 2);
+
+        assert_eq!(
+            record.to_string(),
+            "{\"timestamp\":\"1970-01-01T00:00:00.000000Z\",\"client\":\"127.0.0.1\",\"qname\":\".\",\"qtype\":\"NS\",\"rcode\":\"NOERROR\",\"latency_us\":0,\"protocol\":\"tcp\",\"cache_hit\":true}"
+        );
+    }
+
+    #[test]
+    fn test_json_escaped() {
+        assert_eq!(
+            JsonEscaped("a\"b\\c\u{1}.").to_string(),
+            "a\\\"b\\\\c\\u0001."
+        );
+    }
+}
@@ -20,7 +20,7 @@ use crate::{
         quic::{DoqErrorCode, QuicStream},
         rr::Record,
         xfer::Protocol,
-        ProtoError,
+        ProtoError, ProtoErrorKind,
     },
     server::{
         request_handler::RequestHandler, response_handler::ResponseHandler, server_future,
@@ -41,6 +41,8 @@ where
 {
     // TODO: we should make this configurable
     let mut max_requests = 100u32;
+    // the stream of the last response, which must be received before a graceful close
+    let mut last_stream: Option<Arc<Mutex<QuicStream>>> = None;
 
     // Accept all inbound quic streams sent over the connection.
     loop {
@@ -57,11 +59,39 @@ where
             },
             _ = shutdown.cancelled() => {
                 // A graceful shutdown was initiated.
+                if let Some(stream) = last_stream.take() {
+                    if let Err(e) = stream.lock().await.stopped().await {
+                        debug!("last response to {src_addr} not received: {e}");
+                    }
+                }
+
+                // DOQ_NO_ERROR: the connection is closed without any error to signal
+                quic_streams.close(DoqErrorCode::NoError);
                 break;
             },
         };
 
-        let request = request_stream.receive_bytes().await?;
+        let request = match request_stream.receive_query().await {
+            Ok(request) => request,
+            // the client cancelled the query, or the connection is lost
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    ProtoErrorKind::QuicAborted(_)
+                        | ProtoErrorKind::QuinnConnection(_)
+                        | ProtoErrorKind::QuinnReadError(_)
+                ) =>
+            {
+                debug!("failed to receive query from {src_addr}: {err}");
+                continue;
+            }
+            // RFC: a malformed query, e.g. with a Message ID other than 0, is a connection error of type DOQ_PROTOCOL_ERROR
+            Err(err) => {
+                warn!("malformed query from {src_addr}: {err}");
+                quic_streams.close(DoqErrorCode::ProtocolError);
+                return Err(err);
+            }
+        };
 
         debug!(
             "Received bytes {} from {src_addr} {request:?}",
@@ -73,6 +103,7 @@ where
         let responder = QuicResponseHandle(stream.clone());
 
         handle_request(request, src_addr, access, handler, responder).await;
+        last_stream = Some(stream.clone());
 
         max_requests -= 1;
         if max_requests == 0 {
@@ -0,0 +1,492 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Response rate limiting (RRL) for UDP responses
+//!
+//! Limits the rate of identical responses sent to a network, to make the server less useful as
+//! an amplifier in reflection attacks. See the [Response Rate Limiting in the Domain Name
+//! System](https://kb.isc.org/docs/aa-01000) technical note.
+
+use std::{
+    collections::HashMap,
+    io,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use hickory_proto::rr::Record;
+use ipnet::IpNet;
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::{
+    authority::MessageResponse,
+    proto::{
+        op::{LowerQuery, OpCode, ResponseCode},
+        rr::{LowerName, RecordType},
+    },
+    server::{ResponseHandler, ResponseInfo},
+};
+
+/// Configuration of the response rate limiting
+///
+/// Responses are counted per client network, and for positive answers per query name and type.
+/// Each of the counters is a token bucket, holding up to one second of responses. Once a bucket
+/// is empty, the responses are dropped, except every `slip`-th one, which is sent truncated and
+/// without records. Legitimate clients then retry over TCP, which is not rate limited.
+///
+/// A rate of zero disables the limiting for that kind of responses.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResponseRateLimit {
+    /// Maximum rate of identical positive answers per second
+    pub responses_per_second: u32,
+    /// Maximum rate of NXDOMAIN responses per second
+    pub nxdomains_per_second: u32,
+    /// Maximum rate of error responses per second, e.g. REFUSED or SERVFAIL
+    pub errors_per_second: u32,
+    /// Every `slip`-th limited response is sent truncated instead of being dropped, zero to
+    /// always drop them
+    pub slip: u32,
+    /// Length of the prefix of the IPv4 networks of the clients
+    pub ipv4_prefix_len: u8,
+    /// Length of the prefix of the IPv6 networks of the clients
+    pub ipv6_prefix_len: u8,
+    /// Maximum number of tracked buckets
+    pub max_entries: usize,
+}
+
+impl Default for ResponseRateLimit {
+    fn default() -> Self {
+        Self {
+            responses_per_second: 5,
+            nxdomains_per_second: 5,
+            errors_per_second: 5,
+            slip: 2,
+            ipv4_prefix_len: 24,
+            ipv6_prefix_len: 56,
+            max_entries: 20_000,
+        }
+    }
+}
+
+/// What to do with a response
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RateLimitAction {
+    /// The response is within its rate
+    Send,
+    /// The response is limited, but sent truncated
+    Slip,
+    /// The response is limited
+    Drop,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum ResponseKind {
+    Answer,
+    NxDomain,
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct BucketKey {
+    network: IpNet,
+    kind: ResponseKind,
+    /// The query, only for positive answers
+    query: Option<(LowerName, RecordType)>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    /// Number of responses limited since the last one sent
+    limited: u32,
+}
+
+impl Bucket {
+    fn new(rate: u32, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(rate),
+            updated: now,
+            limited: 0,
+        }
+    }
+
+    /// Takes a token if one is available, the bucket refills at `rate` tokens per second
+    fn take(&mut self, rate: u32, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * f64::from(rate)).min(f64::from(rate));
+        self.updated = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            self.limited = 0;
+            true
+        } else {
+            self.limited = self.limited.wrapping_add(1);
+            false
+        }
+    }
+}
+
+/// The token buckets of the response rate limiting
+pub(crate) struct ResponseRateLimiter {
+    config: ResponseRateLimit,
+    buckets: Mutex<HashMap<BucketKey, Bucket>>,
+}
+
+impl ResponseRateLimiter {
+    pub(crate) fn new(config: ResponseRateLimit) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Accounts for a response with `response_code` to the `query` of the `client`
+    pub(crate) fn check(
+        &self,
+        client: IpAddr,
+        response_code: ResponseCode,
+        query: Option<&LowerQuery>,
+        now: Instant,
+    ) -> RateLimitAction {
+        let (kind, rate) = match response_code {
+            ResponseCode::NoError => (ResponseKind::Answer, self.config.responses_per_second),
+            ResponseCode::NXDomain => (ResponseKind::NxDomain, self.config.nxdomains_per_second),
+            _ => (ResponseKind::Error, self.config.errors_per_second),
+        };
+        if rate == 0 {
+            return RateLimitAction::Send;
+        }
+
+        let prefix_len = match client {
+            IpAddr::V4(_) => self.config.ipv4_prefix_len.min(32),
+            IpAddr::V6(_) => self.config.ipv6_prefix_len.min(128),
+        };
+        let network = IpNet::new(client, prefix_len)
+            .expect("prefix length is within bounds")
+            .trunc();
+
+        let key = BucketKey {
+            network,
+            kind,
+            query: match kind {
+                ResponseKind::Answer => query.map(|q| (q.name().clone(), q.query_type())),
+                _ => None,
+            },
+        };
+
+        let mut buckets = self.buckets.lock().expect("rate limiter lock poisoned");
+        if buckets.len() >= self.config.max_entries && !buckets.contains_key(&key) {
+            // buckets idle for a second are full again, they are the same as new ones
+            buckets.retain(|_, bucket| now.saturating_duration_since(bucket.updated).as_secs() < 1);
+            if buckets.len() >= self.config.max_entries {
+                buckets.clear();
+            }
+        }
+
+        let bucket = buckets.entry(key).or_insert_with(|| Bucket::new(rate, now));
+        if bucket.take(rate, now) {
+            RateLimitAction::Send
+        } else if self.config.slip != 0 && bucket.limited % self.config.slip == 0 {
+            RateLimitAction::Slip
+        } else {
+            RateLimitAction::Drop
+        }
+    }
+}
+
+/// Applies the response rate limiting to the responses sent with the wrapped handler
+#[derive(Clone)]
+pub(crate) struct RateLimitedResponseHandler<R: ResponseHandler> {
+    handler: R,
+    client: IpAddr,
+    rate_limiter: Arc<ResponseRateLimiter>,
+}
+
+impl<R: ResponseHandler> RateLimitedResponseHandler<R> {
+    pub(crate) fn new(handler: R, client: IpAddr, rate_limiter: Arc<ResponseRateLimiter>) -> Self {
+        Self {
+            handler,
+            client,
+            rate_limiter,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<R: ResponseHandler> ResponseHandler for RateLimitedResponseHandler<R> {
+    async fn send_response<'a>(
+        &mut self,
+        response: MessageResponse<
+            '_,
+            'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+        >,
+    ) -> io::Result<ResponseInfo> {
+        // only the responses to queries can be used for amplification
+        if response.header().op_code() != OpCode::Query {
+            return self.handler.send_response(response).await;
+        }
+
+        let action = self.rate_limiter.check(
+            self.client,
+            response.header().response_code(),
+            response.query(),
+            Instant::now(),
+        );
+
+        match action {
+            RateLimitAction::Send => self.handler.send_response(response).await,
+            RateLimitAction::Slip => {
+                debug!("rate limited response to {}, truncated", self.client);
+                self.handler.send_response(response.into_truncated()).await
+            }
+            RateLimitAction::Drop => {
+                debug!("rate limited response to {}, dropped", self.client);
+                Ok(ResponseInfo::from(*response.header()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use futures_util::StreamExt;
+
+    use crate::authority::{MessageRequest, MessageResponseBuilder};
+    use crate::proto::op::{Header, Message, MessageType, Query};
+    use crate::proto::rr::{rdata::A, Name, RData};
+    use crate::proto::serialize::binary::{BinDecodable, BinDecoder};
+    use crate::proto::xfer::Protocol;
+    use crate::proto::BufDnsStreamHandle;
+    use crate::server::ResponseHandle;
+
+    use super::*;
+
+    fn query(name: &str) -> LowerQuery {
+        LowerQuery::query(Query::query(Name::from_str(name).unwrap(), RecordType::A))
+    }
+
+    fn flood(
+        limiter: &ResponseRateLimiter,
+        client: &str,
+        response_code: ResponseCode,
+        name: &str,
+        now: Instant,
+        count: usize,
+    ) -> Vec<RateLimitAction> {
+        let query = query(name);
+        (0..count)
+            .map(|_| limiter.check(client.parse().unwrap(), response_code, Some(&query), now))
+            .collect()
+    }
+
+    #[test]
+    fn test_flood_identical_queries() {
+        use RateLimitAction::*;
+
+        let limiter = ResponseRateLimiter::new(ResponseRateLimit::default());
+        let now = Instant::now();
+
+        let actions = flood(
+            &limiter,
+            "192.0.2.1",
+            ResponseCode::NoError,
+            "www.example.com.",
+            now,
+            9,
+        );
+        assert_eq!(
+            actions,
+            vec![Send, Send, Send, Send, Send, Drop, Slip, Drop, Slip]
+        );
+
+        // the bucket is shared by the network of the client, but not by other queries
+        let actions = flood(
+            &limiter,
+            "192.0.2.200",
+            ResponseCode::NoError,
+            "www.example.com.",
+            now,
+            1,
+        );
+        assert_eq!(actions, vec![Drop]);
+        let actions = flood(
+            &limiter,
+            "192.0.2.1",
+            ResponseCode::NoError,
+            "ftp.example.com.",
+            now,
+            1,
+        );
+        assert_eq!(actions, vec![Send]);
+        let actions = flood(
+            &limiter,
+            "198.51.100.1",
+            ResponseCode::NoError,
+            "www.example.com.",
+            now,
+            1,
+        );
+        assert_eq!(actions, vec![Send]);
+
+        // the bucket refills over time
+        let later = now + Duration::from_millis(400);
+        let actions = flood(
+            &limiter,
+            "192.0.2.1",
+            ResponseCode::NoError,
+            "www.example.com.",
+            later,
+            3,
+        );
+        assert_eq!(actions, vec![Send, Send, Drop]);
+    }
+
+    #[test]
+    fn test_nxdomain_bucket() {
+        use RateLimitAction::*;
+
+        let config = ResponseRateLimit {
+            nxdomains_per_second: 2,
+            slip: 1,
+            ..ResponseRateLimit::default()
+        };
+        let limiter = ResponseRateLimiter::new(config);
+        let now = Instant::now();
+
+        // all the NXDOMAIN responses to the network share a bucket
+        let mut actions = flood(
+            &limiter,
+            "2001:db8::1",
+            ResponseCode::NXDomain,
+            "a.example.com.",
+            now,
+            2,
+        );
+        actions.extend(flood(
+            &limiter,
+            "2001:db8::2",
+            ResponseCode::NXDomain,
+            "b.example.com.",
+            now,
+            2,
+        ));
+        assert_eq!(actions, vec![Send, Send, Slip, Slip]);
+
+        // which is separate from the answers
+        let actions = flood(
+            &limiter,
+            "2001:db8::1",
+            ResponseCode::NoError,
+            "a.example.com.",
+            now,
+            1,
+        );
+        assert_eq!(actions, vec![Send]);
+
+        // a zero rate disables the limiting
+        let config = ResponseRateLimit {
+            errors_per_second: 0,
+            ..ResponseRateLimit::default()
+        };
+        let limiter = ResponseRateLimiter::new(config);
+        let actions = flood(
+            &limiter,
+            "2001:db8::1",
+            ResponseCode::Refused,
+            "a.example.com.",
+            now,
+            100,
+        );
+        assert!(actions.iter().all(|action| *action == Send));
+    }
+
+    #[test]
+    fn test_max_entries() {
+        let config = ResponseRateLimit {
+            responses_per_second: 1,
+            max_entries: 2,
+            ..ResponseRateLimit::default()
+        };
+        let limiter = ResponseRateLimiter::new(config);
+        let now = Instant::now();
+
+        for name in ["a.example.com.", "b.example.com.", "c.example.com."] {
+            flood(&limiter, "192.0.2.1", ResponseCode::NoError, name, now, 1);
+        }
+        assert!(limiter.buckets.lock().unwrap().len() <= 2);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_response_handler() {
+        let client = SocketAddr::from((Ipv4Addr::new(192, 0, 2, 1), 53));
+        let (stream_handle, mut receiver) = BufDnsStreamHandle::new(client);
+        let config = ResponseRateLimit {
+            responses_per_second: 2,
+            slip: 2,
+            ..ResponseRateLimit::default()
+        };
+        let rate_limiter = Arc::new(ResponseRateLimiter::new(config));
+
+        let mut message = Message::new();
+        message.add_query(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        let bytes = message.to_vec().unwrap();
+        let request = MessageRequest::read(&mut BinDecoder::new(&bytes)).unwrap();
+
+        let name = Name::from_str("www.example.com.").unwrap();
+        let answer = Record::from_rdata(name, 300, RData::A(A::new(192, 0, 2, 10)));
+
+        for _ in 0..4 {
+            let mut handler = RateLimitedResponseHandler::new(
+                ResponseHandle::new(client, stream_handle.clone(), Protocol::Udp),
+                client.ip(),
+                rate_limiter.clone(),
+            );
+
+            let mut header = Header::response_from_request(request.header());
+            header.set_message_type(MessageType::Response);
+            let response = MessageResponseBuilder::from_message_request(&request).build(
+                header,
+                [&answer],
+                [],
+                [],
+                [],
+            );
+            handler.send_response(response).await.unwrap();
+        }
+
+        // two answers, then the third is dropped and the fourth truncated
+        let mut responses = Vec::new();
+        for _ in 0..3 {
+            let message = receiver.next().await.unwrap();
+            responses.push(Message::from_vec(message.bytes()).unwrap());
+        }
+        assert!(responses[..2]
+            .iter()
+            .all(|response| !response.truncated() && response.answer_count() == 1));
+        assert!(responses[2].truncated());
+        assert_eq!(responses[2].answer_count(), 0);
+        assert_eq!(responses[2].queries(), message.queries());
+
+        drop(stream_handle);
+        assert!(receiver.next().await.is_none());
+    }
+}
@@ -7,8 +7,13 @@
 
 //! Request Handler for incoming requests
 
-use std::net::SocketAddr;
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
 
+#[cfg(feature = "resolver")]
+use crate::resolver::lookup::LookupMetadata;
 use crate::{
     authority::MessageRequest,
     proto::{
@@ -27,6 +32,8 @@ pub struct Request {
     src: SocketAddr,
     /// Protocol of the request
     protocol: Protocol,
+    /// When the request was received
+    received: Instant,
 }
 
 impl Request {
@@ -38,6 +45,7 @@ impl Request {
             message,
             src,
             protocol,
+            received: Instant::now(),
         }
     }
 
@@ -60,6 +68,11 @@ impl Request {
     pub fn protocol(&self) -> Protocol {
         self.protocol
     }
+
+    /// When the request was received, to measure the time taken to answer it
+    pub fn received(&self) -> Instant {
+        self.received
+    }
 }
 
 impl std::ops::Deref for Request {
@@ -109,10 +122,31 @@ impl<'a> RequestInfo<'a> {
     }
 }
 
+/// Whether the answer of a response was served from the cache of a forwarder or recursor
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CacheStatus {
+    /// The answer was served from the cache
+    Hit,
+    /// The answer was not cached, and it took the duration to obtain it from the name servers
+    Miss(Duration),
+}
+
+#[cfg(feature = "resolver")]
+impl From<LookupMetadata> for CacheStatus {
+    fn from(metadata: LookupMetadata) -> Self {
+        match metadata.from_cache {
+            true => Self::Hit,
+            false => Self::Miss(metadata.upstream_duration),
+        }
+    }
+}
+
 /// Information about the response sent for a request
 #[derive(Clone, Copy, Debug)]
-#[repr(transparent)]
-pub struct ResponseInfo(Header);
+pub struct ResponseInfo {
+    header: Header,
+    cache_status: Option<CacheStatus>,
+}
 
 impl ResponseInfo {
     pub(crate) fn serve_failed() -> Self {
@@ -120,11 +154,30 @@ impl ResponseInfo {
         header.set_response_code(ResponseCode::ServFail);
         header.into()
     }
+
+    /// Whether the answers were served from the cache of a forwarder or recursor, rather than from
+    /// a zone or a name server
+    pub fn cache_hit(&self) -> bool {
+        self.cache_status == Some(CacheStatus::Hit)
+    }
+
+    /// Whether the answers were served from the cache, `None` for the answers of the zones
+    pub fn cache_status(&self) -> Option<CacheStatus> {
+        self.cache_status
+    }
+
+    pub(crate) fn with_cache_status(mut self, cache_status: Option<CacheStatus>) -> Self {
+        self.cache_status = cache_status;
+        self
+    }
 }
 
 impl From<Header> for ResponseInfo {
     fn from(header: Header) -> Self {
-        Self(header)
+        Self {
+            header,
+            cache_status: None,
+        }
     }
 }
 
@@ -132,7 +185,7 @@ impl std::ops::Deref for ResponseInfo {
     type Target = Header;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.header
     }
 }
 
@@ -5,7 +5,7 @@
 // https://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::{io, net::SocketAddr};
+use std::{io, net::SocketAddr, time::Duration};
 
 use hickory_proto::rr::Record;
 use tracing::{debug, trace};
@@ -13,6 +13,7 @@ use tracing::{debug, trace};
 use crate::{
     authority::MessageResponse,
     proto::{
+        rr::rdata::opt::{EdnsCode, EdnsOption},
         serialize::binary::BinEncoder,
         xfer::{Protocol, SerialMessage},
         BufDnsStreamHandle, DnsStreamHandle,
@@ -20,6 +21,12 @@ use crate::{
     server::ResponseInfo,
 };
 
+/// The maximum size of UDP responses to clients which do not advertise one with EDNS
+///
+/// This is the size recommended by the [DNS flag day 2020](https://www.dnsflagday.net/2020/),
+/// which avoids IP fragmentation on most networks.
+const DEFAULT_UDP_PAYLOAD_SIZE: u16 = 1232;
+
 /// A handler for send a response to a client
 #[async_trait::async_trait]
 pub trait ResponseHandler: Clone + Send + Sync + Unpin + 'static {
@@ -49,6 +56,7 @@ pub struct ResponseHandle {
     dst: SocketAddr,
     stream_handle: BufDnsStreamHandle,
     protocol: Protocol,
+    tcp_keepalive: Option<u16>,
 }
 
 impl ResponseHandle {
@@ -58,9 +66,21 @@ impl ResponseHandle {
             dst,
             stream_handle,
             protocol,
+            tcp_keepalive: None,
         }
     }
 
+    /// Signals the idle timeout of the connection to the client, [RFC 7828](https://tools.ietf.org/html/rfc7828)
+    ///
+    /// The edns-tcp-keepalive option is added to the responses with EDNS over TCP and TLS, it is
+    /// never sent over UDP.
+    pub fn with_tcp_keepalive(mut self, idle_timeout: Duration) -> Self {
+        // the timeout is in units of 100 milliseconds
+        let timeout = (idle_timeout.as_millis() / 100).min(u128::from(u16::MAX)) as u16;
+        self.tcp_keepalive = Some(timeout);
+        self
+    }
+
     /// Selects an appropriate maximum serialized size for the given response.
     fn max_size_for_response<'a>(
         &self,
@@ -75,12 +95,16 @@ impl ResponseHandle {
     ) -> u16 {
         match self.protocol {
             Protocol::Udp => {
-                // Use EDNS, if available.
-                if let Some(edns) = response.get_edns() {
-                    edns.max_payload()
+                // Use the payload size advertised by the client in EDNS, if available.
+                let max_size = if let Some(edns) = response.get_edns() {
+                    edns.max_payload().max(512)
                 } else {
-                    // No EDNS, use the recommended max from RFC6891.
-                    hickory_proto::udp::MAX_RECEIVE_BUFFER_SIZE as u16
+                    DEFAULT_UDP_PAYLOAD_SIZE
+                };
+
+                match response.max_udp_size() {
+                    Some(limit) => max_size.min(limit.max(512)),
+                    None => max_size,
                 }
             }
             _ => u16::MAX,
@@ -109,6 +133,22 @@ impl ResponseHandler for ResponseHandle {
             response.header().id(),
             response.header().response_code(),
         );
+
+        let mut response = response;
+        let is_tcp = match self.protocol {
+            Protocol::Tcp => true,
+            #[cfg(feature = "dns-over-rustls")]
+            Protocol::Tls => true,
+            _ => false,
+        };
+        if let (Some(timeout), true) = (self.tcp_keepalive, is_tcp) {
+            if let Some(mut edns) = response.get_edns().clone() {
+                edns.options_mut().remove(EdnsCode::Keepalive);
+                edns.options_mut()
+                    .insert(EdnsOption::TcpKeepalive(Some(timeout)));
+                response.set_edns(edns);
+            }
+        }
         let mut buffer = Vec::with_capacity(512);
         let encode_result = {
             let mut encoder = BinEncoder::new(&mut buffer);
@@ -135,3 +175,134 @@ impl ResponseHandler for ResponseHandle {
         Ok(info)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+
+    use futures_util::StreamExt;
+
+    use crate::authority::{MessageRequest, MessageResponseBuilder};
+    use crate::proto::op::{Edns, Header, Message, MessageType, Query};
+    use crate::proto::rr::{rdata::TXT, Name, RData, RecordType};
+    use crate::proto::serialize::binary::{BinDecodable, BinDecoder};
+
+    use super::*;
+
+    /// Sends a response with `count` TXT records of 100 bytes over UDP, and returns the bytes sent
+    async fn send_txt_records(edns: Option<Edns>, count: usize) -> Vec<u8> {
+        send_txt_records_over(Protocol::Udp, None, edns, count).await
+    }
+
+    async fn send_txt_records_over(
+        protocol: Protocol,
+        tcp_keepalive: Option<Duration>,
+        edns: Option<Edns>,
+        count: usize,
+    ) -> Vec<u8> {
+        let client = SocketAddr::from((Ipv4Addr::new(192, 0, 2, 1), 53));
+        let (stream_handle, mut receiver) = BufDnsStreamHandle::new(client);
+
+        let name = Name::from_str("www.example.com.").unwrap();
+        let mut message = Message::new();
+        message.add_query(Query::query(name.clone(), RecordType::TXT));
+        let bytes = message.to_vec().unwrap();
+        let request = MessageRequest::read(&mut BinDecoder::new(&bytes)).unwrap();
+
+        let txt = RData::TXT(TXT::new(vec!["x".repeat(100)]));
+        let records = (0..count)
+            .map(|_| Record::from_rdata(name.clone(), 300, txt.clone()))
+            .collect::<Vec<_>>();
+
+        let mut builder = MessageResponseBuilder::from_message_request(&request);
+        if let Some(edns) = edns {
+            builder.edns(edns);
+        }
+        let mut header = Header::response_from_request(request.header());
+        header.set_message_type(MessageType::Response);
+
+        let mut handle = ResponseHandle::new(client, stream_handle, protocol);
+        if let Some(idle_timeout) = tcp_keepalive {
+            handle = handle.with_tcp_keepalive(idle_timeout);
+        }
+        handle
+            .send_response(builder.build(header, records.iter(), [], [], []))
+            .await
+            .unwrap();
+
+        receiver.next().await.unwrap().bytes().to_vec()
+    }
+
+    fn edns(max_payload: u16) -> Option<Edns> {
+        let mut edns = Edns::new();
+        edns.set_max_payload(max_payload);
+        Some(edns)
+    }
+
+    #[tokio::test]
+    async fn test_truncation_to_client_payload_size() {
+        // about 600 bytes of records
+        for (max_payload, truncated) in [(512, true), (4096, false)] {
+            let bytes = send_txt_records(edns(max_payload), 5).await;
+            assert!(bytes.len() <= usize::from(max_payload));
+
+            let response = Message::from_vec(&bytes).unwrap();
+            assert_eq!(response.truncated(), truncated, "bufsize {max_payload}");
+            assert_eq!(response.answer_count() < 5, truncated);
+            // the OPT record is always included
+            assert_eq!(
+                response.extensions().as_ref().unwrap().max_payload(),
+                max_payload
+            );
+        }
+
+        // a response which exactly fits without the OPT record
+        let bytes = send_txt_records(None, 5).await;
+        let max_payload = bytes.len() as u16;
+        let response = Message::from_vec(&send_txt_records(edns(max_payload), 5).await).unwrap();
+        assert!(response.truncated());
+        assert!(response.extensions().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_truncation_without_edns() {
+        let response = Message::from_vec(&send_txt_records(None, 10).await).unwrap();
+        assert!(!response.truncated());
+        assert_eq!(response.answer_count(), 10);
+
+        let bytes = send_txt_records(None, 20).await;
+        assert!(bytes.len() <= usize::from(DEFAULT_UDP_PAYLOAD_SIZE));
+        let response = Message::from_vec(&bytes).unwrap();
+        assert!(response.truncated());
+        assert!(response.extensions().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tcp_keepalive() {
+        let idle_timeout = Some(Duration::from_secs(5));
+        for protocol in [
+            Protocol::Tcp,
+            #[cfg(feature = "dns-over-rustls")]
+            Protocol::Tls,
+        ] {
+            let bytes = send_txt_records_over(protocol, idle_timeout, edns(4096), 1).await;
+            let response = Message::from_vec(&bytes).unwrap();
+            let edns = response.extensions().as_ref().unwrap();
+            assert_eq!(
+                edns.option(EdnsCode::Keepalive),
+                Some(&EdnsOption::TcpKeepalive(Some(50)))
+            );
+        }
+
+        // the option is never sent over UDP, and only with EDNS
+        let bytes = send_txt_records_over(Protocol::Udp, idle_timeout, edns(4096), 1).await;
+        let response = Message::from_vec(&bytes).unwrap();
+        let edns = response.extensions().as_ref().unwrap();
+        assert!(edns.option(EdnsCode::Keepalive).is_none());
+
+        let bytes = send_txt_records_over(Protocol::Tcp, idle_timeout, None, 1).await;
+        let response = Message::from_vec(&bytes).unwrap();
+        assert!(response.extensions().is_none());
+    }
+}
@@ -37,9 +37,17 @@ use crate::{
         xfer::{Protocol, SerialMessage},
         BufDnsStreamHandle, ProtoError,
     },
-    server::{Request, RequestHandler, ResponseHandle, ResponseHandler, TimeoutStream},
+    server::{
+        metrics::serve_scrape,
+        rate_limit::{RateLimitedResponseHandler, ResponseRateLimiter},
+        Request, RequestHandler, ResponseHandle, ResponseHandler, ResponseRateLimit, ServerMetrics,
+        TimeoutStream,
+    },
 };
 
+/// The time given to the requests in flight by a graceful shutdown
+const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
 // TODO, would be nice to have a Slab for buffers here...
 /// A Futures based implementation of a DNS server
 pub struct ServerFuture<T: RequestHandler> {
@@ -47,6 +55,9 @@ pub struct ServerFuture<T: RequestHandler> {
     join_set: JoinSet<Result<(), ProtoError>>,
     shutdown_token: CancellationToken,
     access: Arc<AccessControl>,
+    rate_limiter: Option<Arc<ResponseRateLimiter>>,
+    metrics: Option<ServerMetrics>,
+    drain_timeout: Duration,
 }
 
 impl<T: RequestHandler> ServerFuture<T> {
@@ -66,9 +77,69 @@ impl<T: RequestHandler> ServerFuture<T> {
             join_set: JoinSet::new(),
             shutdown_token: CancellationToken::new(),
             access: Arc::new(access),
+            rate_limiter: None,
+            metrics: None,
+            drain_timeout: DEFAULT_DRAIN_TIMEOUT,
         }
     }
 
+    /// Sets how long [`Self::shutdown_gracefully`] waits for the requests in flight, 5 seconds by
+    /// default
+    pub fn set_drain_timeout(&mut self, drain_timeout: Duration) {
+        self.drain_timeout = drain_timeout;
+    }
+
+    /// Enables the response rate limiting of the responses sent over UDP
+    ///
+    /// Only applies to the UDP sockets registered after this call.
+    pub fn set_response_rate_limit(&mut self, config: ResponseRateLimit) {
+        self.rate_limiter = Some(Arc::new(ResponseRateLimiter::new(config)));
+    }
+
+    /// Counts the active connections in `metrics`
+    ///
+    /// Only applies to the listeners registered after this call. The metrics of the queries are
+    /// collected by pushing the same metrics in the [`MiddlewareChain`](crate::server::MiddlewareChain)
+    /// of the handler.
+    pub fn set_metrics(&mut self, metrics: ServerMetrics) {
+        self.metrics = Some(metrics);
+    }
+
+    /// Serves `metrics` in the Prometheus text format, at `/metrics` over HTTP on the listener
+    pub fn register_metrics_listener(
+        &mut self,
+        listener: net::TcpListener,
+        metrics: ServerMetrics,
+    ) {
+        debug!("register metrics: {:?}", listener);
+
+        let shutdown = self.shutdown_token.clone();
+        self.join_set.spawn(async move {
+            let mut inner_join_set = JoinSet::new();
+            loop {
+                let stream = tokio::select! {
+                    stream = listener.accept() => match stream {
+                        Ok((stream, _)) => stream,
+                        Err(e) => {
+                            debug!("error receiving metrics tcp_stream error: {}", e);
+                            if is_unrecoverable_socket_error(&e) {
+                                break;
+                            }
+                            continue;
+                        },
+                    },
+                    _ = shutdown.cancelled() => break,
+                };
+
+                inner_join_set.spawn(serve_scrape(stream, metrics.clone()));
+                reap_tasks(&mut inner_join_set);
+            }
+
+            drain(&mut inner_join_set).await;
+            Ok(())
+        });
+    }
+
     /// Register a UDP socket. Should be bound before calling this function.
     pub fn register_socket(&mut self, socket: net::UdpSocket) {
         debug!("registering udp: {:?}", socket);
@@ -80,6 +151,7 @@ impl<T: RequestHandler> ServerFuture<T> {
         let shutdown = self.shutdown_token.clone();
         let handler = self.handler.clone();
         let access = self.access.clone();
+        let rate_limiter = self.rate_limiter.clone();
 
         // this spawns a ForEach future which handles all the requests into a Handler.
         self.join_set.spawn({
@@ -121,15 +193,55 @@ impl<T: RequestHandler> ServerFuture<T> {
                     let handler = handler.clone();
                     let access = access.clone();
                     let stream_handle = stream_handle.with_remote_addr(src_addr);
+                    let rate_limiter = rate_limiter.clone();
 
                     inner_join_set.spawn(async move {
-                        handle_raw_request(message, Protocol::Udp, access, handler, stream_handle)
+                        let Some(rate_limiter) = rate_limiter else {
+                            handle_raw_request(
+                                message,
+                                Protocol::Udp,
+                                access,
+                                handler,
+                                stream_handle,
+                            )
                             .await;
+                            return;
+                        };
+
+                        let response_handler = RateLimitedResponseHandler::new(
+                            ResponseHandle::new(src_addr, stream_handle, Protocol::Udp),
+                            src_addr.ip(),
+                            rate_limiter,
+                        );
+                        handle_request(
+                            message.bytes(),
+                            src_addr,
+                            Protocol::Udp,
+                            access,
+                            handler,
+                            response_handler,
+                        )
+                        .await;
                     });
 
                     reap_tasks(&mut inner_join_set);
                 }
 
+                // the requests in flight are answered, or aborted by the drain timeout. The
+                // responses are sent by polling the stream, the new requests are dropped
+                loop {
+                    tokio::select! {
+                        biased;
+                        task = inner_join_set.join_next() => if task.is_none() {
+                            break;
+                        },
+                        message = stream.next() => if message.is_none() {
+                            break;
+                        },
+                    }
+                }
+                let _ = stream.next().now_or_never();
+
                 if shutdown.is_cancelled() {
                     Ok(())
                 } else {
@@ -146,6 +258,29 @@ impl<T: RequestHandler> ServerFuture<T> {
         Ok(())
     }
 
+    /// Binds `count` UDP sockets to `addr` with `SO_REUSEPORT`, and registers each of them
+    ///
+    /// The kernel spreads the queries across the sockets, each one served by its own task, so that
+    /// a high rate of queries isn't bottlenecked on a single core. If the port of `addr` is `0`,
+    /// all the sockets are bound to the port assigned to the first one. IPv6 sockets will not
+    /// accept IPv4 queries.
+    ///
+    /// Returns the address the sockets are bound to.
+    #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+    pub fn register_reuse_port_sockets(
+        &mut self,
+        addr: SocketAddr,
+        count: usize,
+    ) -> io::Result<SocketAddr> {
+        let sockets = bind_reuse_port(addr, count)?;
+        let local_addr = sockets[0].local_addr()?;
+        for socket in sockets {
+            self.register_socket(socket);
+        }
+
+        Ok(local_addr)
+    }
+
     /// Register a TcpListener to the Server. This should already be bound to either an IPv6 or an
     ///  IPv4 address.
     ///
@@ -163,6 +298,7 @@ impl<T: RequestHandler> ServerFuture<T> {
 
         let handler = self.handler.clone();
         let access = self.access.clone();
+        let metrics = self.metrics.clone();
 
         // for each incoming request...
         let shutdown = self.shutdown_token.clone();
@@ -198,16 +334,30 @@ impl<T: RequestHandler> ServerFuture<T> {
 
                 let handler = handler.clone();
                 let access = access.clone();
+                let shutdown = shutdown.clone();
+                let connection = metrics.as_ref().and_then(|m| m.connection(Protocol::Tcp));
 
                 // and spawn to the io_loop
                 inner_join_set.spawn(async move {
+                    let _connection = connection;
                     debug!("accepted request from: {}", src_addr);
                     // take the created stream...
                     let (buf_stream, stream_handle) =
                         TcpStream::from_stream(AsyncIoTokioAsStd(tcp_stream), src_addr);
                     let mut timeout_stream = TimeoutStream::new(buf_stream, timeout);
 
-                    while let Some(message) = timeout_stream.next().await {
+                    loop {
+                        let message = tokio::select! {
+                            // polled first, to flush the response to the previous request
+                            biased;
+                            message = timeout_stream.next() => match message {
+                                Some(message) => message,
+                                None => break,
+                            },
+                            // no new request is read once a graceful shutdown was initiated
+                            _ = shutdown.cancelled() => break,
+                        };
+
                         let message = match message {
                             Ok(message) => message,
                             Err(e) => {
@@ -220,13 +370,21 @@ impl<T: RequestHandler> ServerFuture<T> {
                             }
                         };
 
+                        let response_handle = ResponseHandle::new(
+                            message.addr(),
+                            stream_handle.clone(),
+                            Protocol::Tcp,
+                        )
+                        .with_tcp_keepalive(timeout);
+
                         // we don't spawn here to limit clients from getting too many resources
-                        handle_raw_request(
-                            message,
+                        handle_request(
+                            message.bytes(),
+                            message.addr(),
                             Protocol::Tcp,
                             access.clone(),
                             handler.clone(),
-                            stream_handle.clone(),
+                            response_handle,
                         )
                         .await;
                     }
@@ -235,6 +393,9 @@ impl<T: RequestHandler> ServerFuture<T> {
                 reap_tasks(&mut inner_join_set);
             }
 
+            // the requests in flight are answered, or aborted by the drain timeout
+            drain(&mut inner_join_set).await;
+
             if shutdown.is_cancelled() {
                 Ok(())
             } else {
@@ -289,6 +450,7 @@ impl<T: RequestHandler> ServerFuture<T> {
 
         let handler = self.handler.clone();
         let access = self.access.clone();
+        let metrics = self.metrics.clone();
 
         debug!("registered tcp: {:?}", listener);
 
@@ -329,9 +491,12 @@ impl<T: RequestHandler> ServerFuture<T> {
                 let handler = handler.clone();
                 let access = access.clone();
                 let tls_acceptor = tls_acceptor.clone();
+                let shutdown = shutdown.clone();
+                let connection = metrics.as_ref().and_then(|m| m.connection(Protocol::Tls));
 
                 // kick out to a different task immediately, let them do the TLS handshake
                 inner_join_set.spawn(async move {
+                    let _connection = connection;
                     debug!("starting TLS request from: {}", src_addr);
 
                     // perform the TLS
@@ -352,7 +517,18 @@ impl<T: RequestHandler> ServerFuture<T> {
                     debug!("accepted TLS request from: {}", src_addr);
                     let (buf_stream, stream_handle) = tls_from_stream(tls_stream, src_addr);
                     let mut timeout_stream = TimeoutStream::new(buf_stream, handshake_timeout);
-                    while let Some(message) = timeout_stream.next().await {
+                    loop {
+                        let message = tokio::select! {
+                            // polled first, to flush the response to the previous request
+                            biased;
+                            message = timeout_stream.next() => match message {
+                                Some(message) => message,
+                                None => break,
+                            },
+                            // no new request is read once a graceful shutdown was initiated
+                            _ = shutdown.cancelled() => break,
+                        };
+
                         let message = match message {
                             Ok(message) => message,
                             Err(e) => {
@@ -366,12 +542,20 @@ impl<T: RequestHandler> ServerFuture<T> {
                             }
                         };
 
-                        handle_raw_request(
-                            message,
+                        let response_handle = ResponseHandle::new(
+                            message.addr(),
+                            stream_handle.clone(),
+                            Protocol::Tls,
+                        )
+                        .with_tcp_keepalive(handshake_timeout);
+
+                        handle_request(
+                            message.bytes(),
+                            message.addr(),
                             Protocol::Tls,
                             access.clone(),
                             handler.clone(),
-                            stream_handle.clone(),
+                            response_handle,
                         )
                         .await;
                     }
@@ -380,6 +564,9 @@ impl<T: RequestHandler> ServerFuture<T> {
                 reap_tasks(&mut inner_join_set);
             }
 
+            // the requests in flight are answered, or aborted by the drain timeout
+            drain(&mut inner_join_set).await;
+
             if shutdown.is_cancelled() {
                 Ok(())
             } else {
@@ -445,6 +632,7 @@ impl<T: RequestHandler> ServerFuture<T> {
 
         let handler = self.handler.clone();
         let access = self.access.clone();
+        let metrics = self.metrics.clone();
         debug!("registered https: {listener:?}");
 
         let tls_acceptor = TlsAcceptor::from(Arc::new(tls_server_config(
@@ -487,8 +675,10 @@ impl<T: RequestHandler> ServerFuture<T> {
                 let tls_acceptor = tls_acceptor.clone();
                 let dns_hostname = dns_hostname.clone();
                 let http_endpoint = http_endpoint.clone();
+                let connection = metrics.as_ref().and_then(|m| m.connection(Protocol::Https));
 
                 inner_join_set.spawn(async move {
+                    let _connection = connection;
                     debug!("starting HTTPS request from: {src_addr}");
 
                     // TODO: need to consider timeout of total connect...
@@ -524,6 +714,9 @@ impl<T: RequestHandler> ServerFuture<T> {
                 reap_tasks(&mut inner_join_set);
             }
 
+            // the requests in flight are answered, or aborted by the drain timeout
+            drain(&mut inner_join_set).await;
+
             if shutdown.is_cancelled() {
                 Ok(())
             } else {
@@ -563,6 +756,7 @@ impl<T: RequestHandler> ServerFuture<T> {
 
         let handler = self.handler.clone();
         let access = self.access.clone();
+        let metrics = self.metrics.clone();
 
         debug!("registered quic: {:?}", socket);
         let mut server =
@@ -603,8 +797,10 @@ impl<T: RequestHandler> ServerFuture<T> {
                 let handler = handler.clone();
                 let access = access.clone();
                 let dns_hostname = dns_hostname.clone();
+                let connection = metrics.as_ref().and_then(|m| m.connection(Protocol::Quic));
 
                 inner_join_set.spawn(async move {
+                    let _connection = connection;
                     debug!("starting quic stream request from: {src_addr}");
 
                     // TODO: need to consider timeout of total connect...
@@ -626,6 +822,9 @@ impl<T: RequestHandler> ServerFuture<T> {
                 reap_tasks(&mut inner_join_set);
             }
 
+            // the requests in flight are answered, or aborted by the drain timeout
+            drain(&mut inner_join_set).await;
+
             Ok(())
         });
 
@@ -661,6 +860,7 @@ impl<T: RequestHandler> ServerFuture<T> {
 
         let handler = self.handler.clone();
         let access = self.access.clone();
+        let metrics = self.metrics.clone();
 
         debug!("registered h3: {:?}", socket);
         let mut server =
@@ -701,8 +901,10 @@ impl<T: RequestHandler> ServerFuture<T> {
                 let handler = handler.clone();
                 let access = access.clone();
                 let dns_hostname = dns_hostname.clone();
+                let connection = metrics.as_ref().and_then(|m| m.connection(Protocol::H3));
 
                 inner_join_set.spawn(async move {
+                    let _connection = connection;
                     debug!("starting h3 stream request from: {src_addr}");
 
                     // TODO: need to consider timeout of total connect...
@@ -724,6 +926,9 @@ impl<T: RequestHandler> ServerFuture<T> {
                 reap_tasks(&mut inner_join_set);
             }
 
+            // the requests in flight are answered, or aborted by the drain timeout
+            drain(&mut inner_join_set).await;
+
             Ok(())
         });
 
@@ -732,11 +937,29 @@ impl<T: RequestHandler> ServerFuture<T> {
 
     /// Triggers a graceful shutdown the server. All background tasks will stop accepting
     /// new connections and the returned future will complete once all tasks have terminated.
+    ///
+    /// The requests in flight are still answered, the connections are closed once idle, i.e. TCP
+    /// and TLS after the pending responses, and QUIC with `DOQ_NO_ERROR`. The connections which are
+    /// not done within the drain timeout, see [`Self::set_drain_timeout`], are aborted, and an
+    /// error returned.
     pub async fn shutdown_gracefully(&mut self) -> Result<(), ProtoError> {
         self.shutdown_token.cancel();
 
         // Wait for the server to complete.
-        block_until_done(&mut self.join_set).await
+        match tokio::time::timeout(self.drain_timeout, block_until_done(&mut self.join_set)).await {
+            Ok(result) => result,
+            Err(_) => {
+                warn!(
+                    "requests still in flight after {:?}, aborting",
+                    self.drain_timeout
+                );
+                self.join_set.abort_all();
+                while self.join_set.join_next().await.is_some() {}
+                Err(ProtoError::from(
+                    "graceful shutdown timed out, connections aborted",
+                ))
+            }
+        }
     }
 
     /// This will run until all background tasks complete. If one or more tasks return an error,
@@ -773,6 +996,11 @@ async fn block_until_done(
     out
 }
 
+/// Waits for the tasks of a `JoinSet`, i.e. the connections and requests of a listener
+async fn drain(join_set: &mut JoinSet<()>) {
+    while join_set.join_next().await.is_some() {}
+}
+
 /// Reap finished tasks from a `JoinSet`, without awaiting or blocking.
 fn reap_tasks(join_set: &mut JoinSet<()>) {
     while FutureExt::now_or_never(join_set.join_next())
@@ -1072,6 +1300,36 @@ fn sanitize_src_address(src: SocketAddr) -> Result<(), String> {
     }
 }
 
+/// Binds `count` UDP sockets to `addr` sharing the port with `SO_REUSEPORT`
+#[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+fn bind_reuse_port(mut addr: SocketAddr, count: usize) -> io::Result<Vec<net::UdpSocket>> {
+    use socket2::{Domain, Socket, Type};
+
+    if count == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "at least one UDP socket must be bound",
+        ));
+    }
+
+    let mut sockets = Vec::with_capacity(count);
+    for _ in 0..count {
+        let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, None)?;
+        if addr.is_ipv6() {
+            socket.set_only_v6(true)?;
+        }
+        socket.set_reuse_port(true)?;
+        socket.set_nonblocking(true)?;
+        socket.bind(&addr.into())?;
+
+        let socket = net::UdpSocket::from_std(socket.into())?;
+        addr = socket.local_addr()?;
+        sockets.push(socket);
+    }
+
+    Ok(sockets)
+}
+
 fn is_unrecoverable_socket_error(err: &io::Error) -> bool {
     matches!(
         err.kind(),
@@ -1083,10 +1341,14 @@ fn is_unrecoverable_socket_error(err: &io::Error) -> bool {
 mod tests {
     use super::*;
     use crate::authority::Catalog;
+    use crate::proto::op::Message;
+    use crate::proto::rr::{Name, RecordType};
+    use crate::server::ResponseInfo;
     use futures_util::future;
     #[cfg(feature = "dns-over-rustls")]
     use rustls::pki_types::{CertificateDer, PrivateKeyDer};
     use std::net::SocketAddr;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
     use tokio::net::{TcpListener, UdpSocket};
     use tokio::time::timeout;
 
@@ -1121,6 +1383,169 @@ mod tests {
         endpoints.rebind_all().await;
     }
 
+    /// Answers all the queries with REFUSED, after a delay
+    struct SlowHandler(Duration);
+
+    #[async_trait::async_trait]
+    impl RequestHandler for SlowHandler {
+        async fn handle_request<R: ResponseHandler>(
+            &self,
+            request: &Request,
+            mut response_handle: R,
+        ) -> ResponseInfo {
+            tokio::time::sleep(self.0).await;
+            let response = MessageResponseBuilder::new(Some(request.raw_query()));
+            response_handle
+                .send_response(response.error_msg(request.header(), ResponseCode::Refused))
+                .await
+                .unwrap()
+        }
+    }
+
+    /// A server answering over UDP and TCP after `delay`, and its addresses
+    async fn slow_server(delay: Duration) -> (ServerFuture<SlowHandler>, SocketAddr, SocketAddr) {
+        let mut server_future = ServerFuture::new(SlowHandler(delay));
+        let udp = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let udp_addr = udp.local_addr().unwrap();
+        server_future.register_socket(udp);
+        let tcp = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let tcp_addr = tcp.local_addr().unwrap();
+        server_future.register_listener(tcp, Duration::from_secs(5));
+
+        (server_future, udp_addr, tcp_addr)
+    }
+
+    fn query_bytes() -> Vec<u8> {
+        let mut message = Message::new();
+        message.set_id(7);
+        message.add_query(Query::query(
+            Name::from_ascii("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        message.to_vec().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_graceful_shutdown_in_flight() {
+        let (mut server_future, udp_addr, tcp_addr) = slow_server(Duration::from_millis(500)).await;
+
+        let query = query_bytes();
+        let udp_client = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        udp_client.send_to(&query, udp_addr).await.unwrap();
+        let mut tcp_client = net::TcpStream::connect(tcp_addr).await.unwrap();
+        tcp_client
+            .write_all(&(query.len() as u16).to_be_bytes())
+            .await
+            .unwrap();
+        tcp_client.write_all(&query).await.unwrap();
+
+        // the shutdown starts while the queries are handled
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let (shutdown, udp_response, tcp_response) = tokio::join!(
+            timeout(Duration::from_secs(2), server_future.shutdown_gracefully()),
+            async {
+                let mut buf = [0; 512];
+                let (len, _) = udp_client.recv_from(&mut buf).await.unwrap();
+                Message::from_vec(&buf[..len]).unwrap()
+            },
+            async {
+                let len = tcp_client.read_u16().await.unwrap();
+                let mut buf = vec![0; usize::from(len)];
+                tcp_client.read_exact(&mut buf).await.unwrap();
+                let response = Message::from_vec(&buf).unwrap();
+
+                // the connection is closed after the response
+                assert_eq!(tcp_client.read(&mut buf).await.unwrap(), 0);
+                response
+            },
+        );
+
+        shutdown
+            .expect("timed out waiting for the server to complete")
+            .expect("error while awaiting tasks");
+        for response in [udp_response, tcp_response] {
+            assert_eq!(response.id(), 7);
+            assert_eq!(response.response_code(), ResponseCode::Refused);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_graceful_shutdown_drain_timeout() {
+        let (mut server_future, udp_addr, _) = slow_server(Duration::from_secs(30)).await;
+        server_future.set_drain_timeout(Duration::from_millis(100));
+
+        let client = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        client.send_to(&query_bytes(), udp_addr).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // the request in flight is aborted
+        timeout(Duration::from_secs(2), server_future.shutdown_gracefully())
+            .await
+            .expect("timed out waiting for the server to complete")
+            .expect_err("expected the drain timeout");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_reuse_port_sockets() {
+        let sockets = bind_reuse_port((Ipv4Addr::LOCALHOST, 0).into(), 2).unwrap();
+        let addr = sockets[0].local_addr().unwrap();
+        assert_eq!(sockets[1].local_addr().unwrap(), addr);
+
+        // the kernel spreads the datagrams by the address of the client
+        let mut received = [0; 2];
+        for _ in 0..64 {
+            let client = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+            client.send_to(b"query", addr).await.unwrap();
+
+            let (mut first, mut second) = ([0; 16], [0; 16]);
+            let index = tokio::select! {
+                _ = sockets[0].recv_from(&mut first) => 0,
+                _ = sockets[1].recv_from(&mut second) => 1,
+            };
+            received[index] += 1;
+        }
+
+        assert!(received.iter().all(|count| *count > 0), "{received:?}");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_reuse_port_graceful_shutdown() {
+        let mut server_future = ServerFuture::new(Catalog::new());
+        let addr = server_future
+            .register_reuse_port_sockets((Ipv4Addr::LOCALHOST, 0).into(), 4)
+            .unwrap();
+        assert_ne!(addr.port(), 0);
+
+        // the queries are answered, REFUSED by the empty catalog
+        let client = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let mut message = Message::new();
+        message.add_query(Query::query(
+            Name::from_ascii("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        client
+            .send_to(&message.to_vec().unwrap(), addr)
+            .await
+            .unwrap();
+        let mut buf = [0; 512];
+        let (len, _) = timeout(Duration::from_secs(2), client.recv_from(&mut buf))
+            .await
+            .expect("timed out waiting for the response")
+            .unwrap();
+        let response = Message::from_vec(&buf[..len]).unwrap();
+        assert_eq!(response.response_code(), ResponseCode::Refused);
+
+        timeout(Duration::from_secs(2), server_future.shutdown_gracefully())
+            .await
+            .expect("timed out waiting for the server to complete")
+            .expect("error while awaiting tasks");
+
+        // all the sockets are closed, the port can be bound without SO_REUSEPORT
+        UdpSocket::bind(addr).await.unwrap();
+    }
+
     #[test]
     fn test_sanitize_src_addr() {
         // ipv4 tests
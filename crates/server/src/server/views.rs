@@ -0,0 +1,216 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Views, serving different zones to clients depending on their address (split-horizon DNS)
+
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+
+use crate::{
+    authority::Catalog,
+    server::{Request, RequestHandler, ResponseHandler, ResponseInfo},
+};
+
+/// A set of zones served to the clients from a set of networks
+#[derive(Clone)]
+pub struct View {
+    name: String,
+    match_clients: Vec<IpNet>,
+    catalog: Catalog,
+}
+
+impl View {
+    /// Creates a view serving the zones of `catalog` to the clients in `match_clients`
+    pub fn new(name: impl Into<String>, match_clients: Vec<IpNet>, catalog: Catalog) -> Self {
+        Self {
+            name: name.into(),
+            match_clients,
+            catalog,
+        }
+    }
+
+    /// The name of the view, for logging
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The networks of the clients the view is served to
+    pub fn match_clients(&self) -> &[IpNet] {
+        &self.match_clients
+    }
+
+    /// Returns `true` if the view is served to the client at `ip`
+    pub fn matches(&self, ip: IpAddr) -> bool {
+        self.match_clients.iter().any(|net| net.contains(&ip))
+    }
+
+    /// The zones of the view
+    pub fn catalog(&self) -> &Catalog {
+        &self.catalog
+    }
+
+    /// The zones of the view, for adding or replacing zones
+    pub fn catalog_mut(&mut self) -> &mut Catalog {
+        &mut self.catalog
+    }
+}
+
+/// An ordered list of views, answering each request from the first view matching the client
+///
+/// Requests from clients matching none of the views are answered from the default catalog. An
+/// empty default catalog answers these requests with REFUSED.
+#[derive(Clone, Default)]
+pub struct Views {
+    views: Vec<View>,
+    default: Catalog,
+}
+
+impl Views {
+    /// Creates the list without views, all requests are answered from `default`
+    pub fn new(default: Catalog) -> Self {
+        Self {
+            views: Vec::new(),
+            default,
+        }
+    }
+
+    /// Appends a view, it is matched after the views already in the list
+    pub fn push(&mut self, view: View) {
+        self.views.push(view);
+    }
+
+    /// The views, in the order they are matched
+    pub fn views(&self) -> &[View] {
+        &self.views
+    }
+
+    /// The views, for adding or replacing zones
+    pub fn views_mut(&mut self) -> &mut [View] {
+        &mut self.views
+    }
+
+    /// The catalog of the clients matching none of the views
+    pub fn default_catalog(&self) -> &Catalog {
+        &self.default
+    }
+
+    /// The catalog of the clients matching none of the views, for adding or replacing zones
+    pub fn default_catalog_mut(&mut self) -> &mut Catalog {
+        &mut self.default
+    }
+
+    /// Returns the catalog serving the client at `ip`
+    pub fn catalog_for(&self, ip: IpAddr) -> &Catalog {
+        self.views
+            .iter()
+            .find(|view| view.matches(ip))
+            .map_or(&self.default, View::catalog)
+    }
+}
+
+#[async_trait::async_trait]
+impl RequestHandler for Views {
+    async fn handle_request<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        response_handle: R,
+    ) -> ResponseInfo {
+        self.catalog_for(request.src().ip())
+            .handle_request(request, response_handle)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+
+    use crate::proto::op::{Message, Query, ResponseCode};
+    use crate::proto::rr::rdata::A;
+    use crate::proto::rr::{Name, RData, Record, RecordType};
+    use crate::server::tests::{example_catalog, send_request, udp_request};
+
+    use super::*;
+
+    fn name(name: &str) -> Name {
+        Name::from_str(name).unwrap()
+    }
+
+    /// A catalog with the example.com. zone, where www.example.com. has the address `a`
+    fn catalog(a: A) -> Catalog {
+        example_catalog([Record::from_rdata(
+            name("www.example.com."),
+            3600,
+            RData::A(a),
+        )])
+    }
+
+    async fn query(views: &Views, client: Ipv4Addr) -> Message {
+        let mut message = Message::new();
+        message.add_query(Query::query(name("www.example.com."), RecordType::A));
+        let request = udp_request(&message, SocketAddr::from((client, 53)));
+        send_request(views, &request).await.unwrap()
+    }
+
+    fn answer(message: &Message) -> Option<&RData> {
+        message.answers().first().map(Record::data)
+    }
+
+    #[tokio::test]
+    async fn test_split_horizon() {
+        let mut views = Views::default();
+        views.push(View::new(
+            "internal",
+            vec!["10.0.0.0/8".parse().unwrap()],
+            catalog(A::new(10, 0, 0, 1)),
+        ));
+        views.push(View::new(
+            "external",
+            vec![
+                "192.0.2.0/24".parse().unwrap(),
+                "198.51.100.0/24".parse().unwrap(),
+            ],
+            catalog(A::new(192, 0, 2, 1)),
+        ));
+
+        let internal = query(&views, Ipv4Addr::new(10, 1, 2, 3)).await;
+        assert_eq!(answer(&internal), Some(&RData::A(A::new(10, 0, 0, 1))));
+        let external = query(&views, Ipv4Addr::new(198, 51, 100, 7)).await;
+        assert_eq!(answer(&external), Some(&RData::A(A::new(192, 0, 2, 1))));
+
+        // the clients of no view are refused by the empty default catalog
+        let other = query(&views, Ipv4Addr::new(203, 0, 113, 1)).await;
+        assert_eq!(other.response_code(), ResponseCode::Refused);
+        assert!(other.answers().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_first_match_and_default() {
+        let mut views = Views::new(catalog(A::new(203, 0, 113, 1)));
+        views.push(View::new(
+            "host",
+            vec!["10.0.0.1/32".parse().unwrap()],
+            catalog(A::new(10, 0, 0, 1)),
+        ));
+        views.push(View::new(
+            "network",
+            vec!["10.0.0.0/8".parse().unwrap()],
+            catalog(A::new(10, 0, 0, 2)),
+        ));
+
+        // the views are matched in order, not by the longest prefix
+        let host = query(&views, Ipv4Addr::new(10, 0, 0, 1)).await;
+        assert_eq!(answer(&host), Some(&RData::A(A::new(10, 0, 0, 1))));
+        let network = query(&views, Ipv4Addr::new(10, 0, 0, 3)).await;
+        assert_eq!(answer(&network), Some(&RData::A(A::new(10, 0, 0, 2))));
+
+        let other = query(&views, Ipv4Addr::new(192, 0, 2, 1)).await;
+        assert_eq!(answer(&other), Some(&RData::A(A::new(203, 0, 113, 1))));
+    }
+}
@@ -210,6 +210,17 @@ impl Authority for FileAuthority {
         self.0.search(request_info, lookup_options).await
     }
 
+    /// Using the specified IXFR query, return the changes made to the zone since the version with
+    /// the given serial, see `InMemoryAuthority::ixfr()`.
+    async fn ixfr(
+        &self,
+        request_info: RequestInfo<'_>,
+        serial: u32,
+        lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Self::Lookup> {
+        self.0.ixfr(request_info, serial, lookup_options).await
+    }
+
     /// Get the NS, NameServer, record for the zone
     async fn ns(&self, lookup_options: LookupOptions) -> LookupControlFlow<Self::Lookup> {
         self.0.ns(lookup_options).await
@@ -347,4 +358,42 @@ mod tests {
             _ => panic!("wrong rdata type returned"),
         }
     }
+
+    #[cfg(feature = "dnssec-ring")]
+    fn load_zonemd_zone(path: &str) -> FileAuthority {
+        let config = FileConfig {
+            zone_file_path: PathBuf::from(path),
+        };
+        FileAuthority::try_from_config(
+            Name::from_str("example.").unwrap(),
+            ZoneType::Primary,
+            false,
+            None,
+            &config,
+            None,
+        )
+        .expect("failed to load file")
+    }
+
+    #[test]
+    #[cfg(feature = "dnssec-ring")]
+    fn test_verify_zonemd() {
+        let authority = load_zonemd_zone("../../tests/test-data/test_configs/zonemd/example.zone");
+        block_on(authority.verify_zonemd()).expect("ZONEMD verification failed");
+    }
+
+    #[test]
+    #[cfg(feature = "dnssec-ring")]
+    fn test_verify_corrupted_zonemd() {
+        let authority =
+            load_zonemd_zone("../../tests/test-data/test_configs/zonemd/example_corrupted.zone");
+        assert!(block_on(authority.verify_zonemd()).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "dnssec-ring")]
+    fn test_verify_missing_zonemd() {
+        let authority = load_zonemd_zone("../../tests/test-data/test_configs/example.com.zone");
+        assert!(block_on(authority.verify_zonemd()).is_err());
+    }
 }
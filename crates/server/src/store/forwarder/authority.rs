@@ -11,7 +11,7 @@ use hickory_resolver::{
     config::ResolveHosts,
     name_server::{ConnectionProvider, TokioConnectionProvider},
 };
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 #[cfg(feature = "dnssec-ring")]
 use crate::{authority::Nsec3QueryInfo, dnssec::NxProofKind};
@@ -25,16 +25,20 @@ use crate::{
         rr::{LowerName, Name, Record, RecordType},
     },
     resolver::{config::ResolverConfig, lookup::Lookup as ResolverLookup, Resolver},
-    server::RequestInfo,
+    server::{CacheStatus, RequestInfo},
     store::forwarder::ForwardConfig,
 };
 
 /// An authority that will forward resolutions to upstream resolvers.
 ///
-/// This uses the hickory-resolver crate for resolving requests.
+/// This uses the hickory-resolver crate for resolving requests. The queries of the configured
+/// domains are forwarded to the resolvers of the longest matching domain, the other queries to the
+/// default resolver.
 pub struct ForwardAuthority<P: ConnectionProvider = TokioConnectionProvider> {
     origin: LowerName,
     resolver: Resolver<P>,
+    /// the resolvers of the domains, the longest domains first
+    domains: Vec<(LowerName, Resolver<P>)>,
 }
 
 impl<P: ConnectionProvider> ForwardAuthority<P> {
@@ -46,6 +50,7 @@ impl<P: ConnectionProvider> ForwardAuthority<P> {
         Ok(Self {
             origin: Name::root().into(),
             resolver,
+            domains: Vec::new(),
         })
     }
 
@@ -86,6 +91,22 @@ impl<P: ConnectionProvider> ForwardAuthority<P> {
             options.use_hosts_file = ResolveHosts::Never;
         }
 
+        let origin = LowerName::from(origin);
+        let mut domains = Vec::with_capacity(config.domains.len());
+        for domain_config in &config.domains {
+            let domain = LowerName::from(&domain_config.domain);
+            if !origin.zone_of(&domain) {
+                warn!("forwarded domain {domain} is not in the zone {origin}, it is never queried");
+            }
+
+            let config =
+                ResolverConfig::from_parts(None, vec![], domain_config.name_servers.clone());
+            let resolver = Resolver::new(config, options.clone(), runtime.clone());
+            info!("forward resolver configured for domain: {domain}");
+            domains.push((domain, resolver));
+        }
+        domains.sort_by_key(|(domain, _)| std::cmp::Reverse(domain.num_labels()));
+
         let config = ResolverConfig::from_parts(None, vec![], name_servers);
 
         let resolver = Resolver::new(config, options, runtime);
@@ -94,10 +115,19 @@ impl<P: ConnectionProvider> ForwardAuthority<P> {
 
         // TODO: this might be infallible?
         Ok(Self {
-            origin: origin.into(),
+            origin,
             resolver,
+            domains,
         })
     }
+
+    /// The resolver of the longest configured domain of `name`, the default resolver otherwise
+    fn resolver(&self, name: &LowerName) -> &Resolver<P> {
+        self.domains
+            .iter()
+            .find(|(domain, _)| domain.zone_of(name))
+            .map_or(&self.resolver, |(_, resolver)| resolver)
+    }
 }
 
 impl ForwardAuthority<TokioConnectionProvider> {
@@ -154,6 +184,7 @@ impl<P: ConnectionProvider> Authority for ForwardAuthority<P> {
         debug_assert!(self.origin.zone_of(name));
 
         debug!("forwarding lookup: {} {}", name, rtype);
+        let resolver = self.resolver(name);
 
         // Ignore FQDN when we forward DNS queries. Without this we can't look
         // up addresses from system hosts file.
@@ -161,7 +192,7 @@ impl<P: ConnectionProvider> Authority for ForwardAuthority<P> {
         name.set_fqdn(false);
 
         use LookupControlFlow::*;
-        match self.resolver.lookup(name, rtype).await {
+        match resolver.lookup(name, rtype).await {
             Ok(lookup) => Continue(Ok(ForwardLookup(lookup))),
             Err(e) => Continue(Err(LookupError::from(e))),
         }
@@ -226,4 +257,61 @@ impl LookupObject for ForwardLookup {
     fn take_additionals(&mut self) -> Option<Box<dyn LookupObject>> {
         None
     }
+
+    fn cache_status(&self) -> Option<CacheStatus> {
+        Some(self.0.metadata().into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::net::UdpSocket;
+
+    use super::*;
+    use crate::proto::op::{Edns, Message, Query};
+    use crate::proto::rr::rdata::opt::{EdnsCode, EdnsOption};
+    use crate::proto::xfer::Protocol;
+    use crate::resolver::config::{NameServerConfig, ResolverOpts};
+    use crate::server::tests::{send_request, udp_request, zone_catalog};
+
+    #[tokio::test]
+    async fn test_timeout_extended_dns_error() {
+        // an upstream which never answers
+        let upstream = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let name_server = NameServerConfig::new(upstream.local_addr().unwrap(), Protocol::Udp);
+        let mut options = ResolverOpts::default();
+        options.timeout = Duration::from_millis(100);
+        options.attempts = 1;
+        let config = ForwardConfig {
+            name_servers: vec![name_server].into(),
+            options: Some(options),
+            domains: Vec::new(),
+        };
+        let authority =
+            ForwardAuthority::try_from_config(Name::root(), ZoneType::External, &config).unwrap();
+        let catalog = zone_catalog(Name::root(), vec![Arc::new(authority)]);
+
+        let mut message = Message::new();
+        message.add_query(Query::query(
+            Name::from_ascii("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        message.set_recursion_desired(true);
+        message.set_edns(Edns::new());
+        let src = SocketAddr::from((Ipv4Addr::new(192, 0, 2, 1), 5353));
+        let response = send_request(&catalog, &udp_request(&message, src))
+            .await
+            .unwrap();
+
+        assert_eq!(response.response_code(), ResponseCode::ServFail);
+        let edns = response.extensions().as_ref().unwrap();
+        match edns.option(EdnsCode::ExtendedDnsError) {
+            Some(EdnsOption::ExtendedDnsError(ede)) => assert_eq!(ede.info_code(), 22),
+            option => panic!("unexpected extended dns error: {option:?}"),
+        }
+    }
 }
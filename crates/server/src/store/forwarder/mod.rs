@@ -11,6 +11,7 @@
 
 use serde::Deserialize;
 
+use crate::proto::rr::Name;
 use crate::resolver::config::{NameServerConfigGroup, ResolverOpts};
 
 mod authority;
@@ -24,4 +25,19 @@ pub struct ForwardConfig {
     pub name_servers: NameServerConfigGroup,
     /// Resolver options
     pub options: Option<ResolverOpts>,
+    /// upstream name_server configurations of specific domains, e.g. for split DNS, the queries
+    ///   of the other names are forwarded to `name_servers`
+    #[serde(default)]
+    pub domains: Vec<ForwardDomainConfig>,
+}
+
+/// Configuration of the upstream name servers of a domain
+#[derive(Clone, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ForwardDomainConfig {
+    /// the domain, the queries of the names at or under it are forwarded to `name_servers`, the
+    ///   longest matching domain wins
+    pub domain: Name,
+    /// upstream name_server configurations of the domain
+    pub name_servers: NameServerConfigGroup,
 }
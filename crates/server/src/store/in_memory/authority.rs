@@ -30,20 +30,27 @@ use crate::{
     proto::{
         dnssec::{
             rdata::{key::KEY, DNSSECRData, DNSKEY, NSEC, NSEC3, NSEC3PARAM, RRSIG},
-            DnsSecResult, Nsec3HashAlgorithm, SigSigner, SupportedAlgorithms, TBS,
+            ring::Digest,
+            DigestType, DnsSecResult, Nsec3HashAlgorithm, SigSigner, SupportedAlgorithms, TBS,
         },
+        rr::rdata::zonemd::{HashAlgorithm, Scheme},
+        serialize::binary::{BinEncodable, BinEncoder},
         ProtoError,
     },
 };
 
+use super::journal::IxfrJournal;
 use crate::{
     authority::{
-        AnyRecords, AuthLookup, Authority, LookupControlFlow, LookupError, LookupOptions,
-        LookupRecords, MessageRequest, UpdateResult, ZoneType,
+        axfr_query, AnyRecords, AuthLookup, Authority, LookupControlFlow, LookupError,
+        LookupOptions, LookupRecords, MessageRequest, UpdateResult, ZoneType,
     },
     proto::{
         op::ResponseCode,
-        rr::{rdata::SOA, DNSClass, LowerName, Name, RData, Record, RecordSet, RecordType, RrKey},
+        rr::{
+            rdata::SOA, DNSClass, LowerName, Name, RData, Record, RecordSet, RecordType, RrKey,
+            SerialNumber,
+        },
     },
     server::RequestInfo,
 };
@@ -57,6 +64,7 @@ pub struct InMemoryAuthority {
     class: DNSClass,
     zone_type: ZoneType,
     allow_axfr: bool,
+    minimal_responses: bool,
     inner: RwLock<InnerInMemory>,
     #[cfg(feature = "dnssec-ring")]
     nx_proof_kind: Option<NxProofKind>,
@@ -138,6 +146,7 @@ impl InMemoryAuthority {
             class: DNSClass::IN,
             zone_type,
             allow_axfr,
+            minimal_responses: false,
             inner: RwLock::new(InnerInMemory::default()),
 
             #[cfg(feature = "dnssec-ring")]
@@ -156,6 +165,15 @@ impl InMemoryAuthority {
         self.allow_axfr = allow_axfr;
     }
 
+    /// Only add the additional records required by the responses
+    ///
+    /// The addresses of the name servers are only added as glue, i.e. when they are below the
+    /// name of the NS records, and the ones of the MX and SRV targets are omitted. The records of
+    /// CNAME and ANAME chains are always added.
+    pub fn set_minimal_responses(&mut self, minimal_responses: bool) {
+        self.minimal_responses = minimal_responses;
+    }
+
     /// Clears all records (including SOA, etc)
     pub fn clear(&mut self) {
         self.inner.get_mut().records.clear()
@@ -198,10 +216,23 @@ impl InMemoryAuthority {
     #[cfg(any(feature = "dnssec-ring", feature = "sqlite"))]
     #[allow(unused)]
     pub(crate) async fn increment_soa_serial(&self) -> u32 {
-        self.inner
-            .write()
-            .await
-            .increment_soa_serial(self.origin(), self.class)
+        let mut inner = self.inner.write().await;
+        let serial = inner.increment_soa_serial(self.origin(), self.class);
+        inner.record_journal(self.origin());
+        serial
+    }
+
+    /// Keep track of the changes made with each serial bump, used to answer IXFR requests
+    ///
+    /// The current version of the zone is the oldest one from which an incremental transfer is
+    /// possible, requests for older or unknown versions are answered with the full zone.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_entries` - the number of serial bumps to keep the changes of
+    pub fn enable_ixfr_journal(&mut self, max_entries: usize) {
+        let inner = self.inner.get_mut();
+        inner.journal = Some(IxfrJournal::new(max_entries, &inner.records, &self.origin));
     }
 
     /// Inserts or updates a `Record` depending on it's existence in the authority.
@@ -217,12 +248,69 @@ impl InMemoryAuthority {
     ///
     /// true if the value was inserted, false otherwise
     pub async fn upsert(&self, record: Record, serial: u32) -> bool {
-        self.inner.write().await.upsert(record, serial, self.class)
+        let mut inner = self.inner.write().await;
+        let is_soa = record.record_type() == RecordType::SOA;
+        let upserted = inner.upsert(record, serial, self.class);
+        if upserted && is_soa {
+            inner.record_journal(self.origin());
+        }
+        upserted
+    }
+
+    /// Replaces all the records of the zone, e.g. with the records of a zone transfer
+    ///
+    /// The serial of the new SOA is recorded against the records, and the changes are kept in
+    /// the IXFR journal if it is enabled.
+    pub async fn replace_records(&self, records: Vec<Record>) {
+        let serial = records
+            .iter()
+            .find_map(|record| match record.data() {
+                RData::SOA(soa) => Some(soa.serial()),
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        let mut inner = self.inner.write().await;
+        inner.records.clear();
+        for record in records {
+            inner.upsert(record, serial, self.class);
+        }
+        inner.record_journal(self.origin());
+    }
+
+    /// Applies the changes between two versions of the zone, e.g. a difference of an IXFR
+    ///
+    /// The `deleted` records are removed, the `added` ones inserted, and `soa` replaces the SOA
+    /// of the zone. The changes are kept in the IXFR journal if it is enabled.
+    pub async fn apply_changes(&self, deleted: Vec<Record>, added: Vec<Record>, soa: Record) {
+        let serial = match soa.data() {
+            RData::SOA(soa) => soa.serial(),
+            _ => self.serial().await,
+        };
+
+        let mut inner = self.inner.write().await;
+        // the records are added first, the last NS of a name is never removed
+        for record in added {
+            inner.upsert(record, serial, self.class);
+        }
+        for record in deleted {
+            if record.record_type() != RecordType::SOA {
+                inner.remove(&record, serial);
+            }
+        }
+        inner.upsert(soa, serial, self.class);
+        inner.record_journal(self.origin());
     }
 
     /// Non-async version of upsert when behind a mutable reference.
     pub fn upsert_mut(&mut self, record: Record, serial: u32) -> bool {
-        self.inner.get_mut().upsert(record, serial, self.class)
+        let inner = self.inner.get_mut();
+        let is_soa = record.record_type() == RecordType::SOA;
+        let upserted = inner.upsert(record, serial, self.class);
+        if upserted && is_soa {
+            inner.record_journal(&self.origin);
+        }
+        upserted
     }
 
     /// Add a (Sig0) key that is authorized to perform updates against this authority
@@ -315,6 +403,30 @@ impl InMemoryAuthority {
     pub fn secure_zone_mut(&mut self) -> Result<(), &str> {
         Err("DNSSEC was not enabled during compilation.")
     }
+
+    /// Verifies that zone transfers are allowed, and that this is either the Secondary or Primary
+    fn check_zone_transfer(&self) -> Result<(), LookupError> {
+        // TODO: support more advanced AXFR options
+        if !self.is_axfr_allowed() {
+            return Err(LookupError::from(ResponseCode::Refused));
+        }
+
+        #[allow(deprecated)]
+        match self.zone_type() {
+            ZoneType::Primary | ZoneType::Secondary | ZoneType::Master | ZoneType::Slave => Ok(()),
+            // TODO: Forward?
+            _ => Err(LookupError::from(ResponseCode::NXDomain)),
+        }
+    }
+
+    /// Verifies the contents of the zone against the digest in its apex ZONEMD record
+    ///
+    /// See [RFC 8976](https://www.rfc-editor.org/rfc/rfc8976#section-4), only the SIMPLE scheme
+    /// with SHA384 is supported.
+    #[cfg(feature = "dnssec-ring")]
+    pub async fn verify_zonemd(&self) -> DnsSecResult<()> {
+        self.inner.read().await.verify_zonemd(self.origin())
+    }
 }
 
 #[derive(Default)]
@@ -327,6 +439,7 @@ struct InnerInMemory {
     //   for this, in some form, perhaps alternate root zones...
     #[cfg(feature = "dnssec-ring")]
     secure_keys: Vec<SigSigner>,
+    journal: Option<IxfrJournal>,
 }
 
 impl InnerInMemory {
@@ -416,12 +529,7 @@ impl InnerInMemory {
         record_type: RecordType,
         lookup_options: LookupOptions,
     ) -> Option<Arc<RecordSet>> {
-        // if this is a wildcard or a root, both should break continued lookups
-        let wildcard = if name.is_wildcard() || name.is_root() {
-            return None;
-        } else {
-            name.clone().into_wildcard()
-        };
+        let wildcard = self.source_of_synthesis(name)?;
 
         #[allow(clippy::needless_late_init)]
         self.inner_lookup(&wildcard, record_type, lookup_options)
@@ -450,15 +558,58 @@ impl InnerInMemory {
                     new_answer.add_rdata(record.data().clone());
                 }
 
+                // RFC 4035 section 5.3.4: the RRSIGs are owned by the query name, but keep the
+                // label count of the wildcard, which tells the validators about the expansion
                 #[cfg(feature = "dnssec-ring")]
                 for rrsig in _rrsigs {
-                    new_answer.insert_rrsig(rrsig.clone())
+                    let mut rrsig = rrsig.clone();
+                    rrsig.set_name(Name::from(name));
+                    new_answer.insert_rrsig(rrsig)
                 }
 
                 Arc::new(new_answer)
             })
     }
 
+    /// Whether `name` owns records, or is an empty non-terminal, i.e. only its descendants do
+    fn name_exists(&self, name: &LowerName) -> bool {
+        // the descendants of a name follow it in the canonical order
+        let start_range_key = RrKey::new(name.clone(), RecordType::Unknown(u16::MIN));
+        self.records
+            .range(&start_range_key..)
+            .next()
+            .is_some_and(|(key, _)| name.zone_of(key.name()))
+    }
+
+    /// The next closer name of `name`, i.e. `name` or its ancestor one label below the closest
+    /// encloser, the longest existing ancestor of `name`, RFC 4592 section 3.3.1
+    fn next_closer_name(&self, name: &LowerName) -> Option<LowerName> {
+        let mut next_closer_name = name.clone();
+        while !next_closer_name.is_root() {
+            let closest_encloser = next_closer_name.base_name();
+            if self.name_exists(&closest_encloser) {
+                return Some(next_closer_name);
+            }
+
+            next_closer_name = closest_encloser;
+        }
+
+        None
+    }
+
+    /// The wildcard answering the queries of `name`, if `name` does not exist
+    ///
+    /// Per RFC 4592 section 3.3.1, only the wildcard at the closest encloser applies, e.g.
+    /// `*.example.com.` does not match `a.b.example.com.` if `b.example.com.` exists.
+    fn source_of_synthesis(&self, name: &LowerName) -> Option<LowerName> {
+        if name.is_wildcard() || self.name_exists(name) {
+            return None;
+        }
+
+        let wildcard = self.next_closer_name(name)?.into_wildcard();
+        self.name_exists(&wildcard).then_some(wildcard)
+    }
+
     /// Search for additional records to include in the response
     ///
     /// # Arguments
@@ -529,6 +680,13 @@ impl InnerInMemory {
         }
     }
 
+    /// Records the changes to the zone in the journal, if enabled and the serial was bumped
+    fn record_journal(&mut self, origin: &LowerName) {
+        if let Some(journal) = &mut self.journal {
+            journal.record(&self.records, origin);
+        }
+    }
+
     #[cfg(any(feature = "dnssec-ring", feature = "sqlite"))]
     fn increment_soa_serial(&mut self, origin: &LowerName, dns_class: DNSClass) -> u32 {
         // we'll remove the SOA and then replace it
@@ -648,6 +806,25 @@ impl InnerInMemory {
         }
     }
 
+    /// Removes a `Record` from the authority, the record set is dropped once empty
+    ///
+    /// Returns true if the record was removed.
+    fn remove(&mut self, record: &Record, serial: u32) -> bool {
+        let rr_key = RrKey::new(record.name().into(), record.record_type());
+        let Some(records) = self.records.get_mut(&rr_key) else {
+            return false;
+        };
+
+        let mut records_clone = RecordSet::clone(&*records);
+        let removed = records_clone.remove(record, serial);
+        if records_clone.is_empty() {
+            self.records.remove(&rr_key);
+        } else {
+            *records = Arc::new(records_clone);
+        }
+        removed
+    }
+
     /// (Re)generates the nsec records, increments the serial number and signs the zone
     #[cfg(feature = "dnssec-ring")]
     fn secure_zone_mut(
@@ -673,7 +850,9 @@ impl InnerInMemory {
         self.increment_soa_serial(origin, dns_class);
 
         // TODO: should we auto sign here? or maybe up a level...
-        self.sign_zone(origin, dns_class)
+        self.sign_zone(origin, dns_class)?;
+        self.record_journal(origin);
+        Ok(())
     }
 
     #[cfg(feature = "dnssec-ring")]
@@ -856,86 +1035,6 @@ impl InnerInMemory {
         Ok(())
     }
 
-    /// Signs an RecordSet, and stores the RRSIGs in the RecordSet
-    ///
-    /// This will sign the RecordSet with all the registered keys in the zone
-    ///
-    /// # Arguments
-    ///
-    /// * `rr_set` - RecordSet to sign
-    /// * `secure_keys` - Set of keys to use to sign the RecordSet, see `self.signers()`
-    /// * `zone_ttl` - the zone TTL, see `self.minimum_ttl()`
-    /// * `zone_class` - DNSClass of the zone, see `self.zone_class()`
-    #[cfg(feature = "dnssec-ring")]
-    fn sign_rrset(
-        rr_set: &mut RecordSet,
-        secure_keys: &[SigSigner],
-        zone_ttl: u32,
-        zone_class: DNSClass,
-    ) -> DnsSecResult<()> {
-        let inception = OffsetDateTime::now_utc();
-
-        rr_set.clear_rrsigs();
-
-        let rrsig_temp = Record::update0(rr_set.name().clone(), zone_ttl, RecordType::RRSIG);
-
-        for signer in secure_keys {
-            debug!(
-                "signing rr_set: {}, {} with: {}",
-                rr_set.name(),
-                rr_set.record_type(),
-                signer.key().algorithm(),
-            );
-
-            let expiration = inception + signer.sig_duration();
-            let tbs = TBS::from_rrset(rr_set, zone_class, inception, expiration, signer);
-
-            // TODO, maybe chain these with some ETL operations instead?
-            let tbs = match tbs {
-                Ok(tbs) => tbs,
-                Err(err) => {
-                    error!("could not serialize rrset to sign: {}", err);
-                    continue;
-                }
-            };
-
-            let signature = signer.sign(&tbs);
-            let signature = match signature {
-                Ok(signature) => signature,
-                Err(err) => {
-                    error!("could not sign rrset: {}", err);
-                    continue;
-                }
-            };
-
-            let mut rrsig = rrsig_temp.clone();
-            rrsig.set_data(RData::DNSSEC(DNSSECRData::RRSIG(RRSIG::new(
-                // type_covered: RecordType,
-                rr_set.record_type(),
-                // algorithm: Algorithm,
-                signer.key().algorithm(),
-                // num_labels: u8,
-                rr_set.name().num_labels(),
-                // original_ttl: u32,
-                rr_set.ttl(),
-                // sig_expiration: u32,
-                expiration.unix_timestamp() as u32,
-                // sig_inception: u32,
-                inception.unix_timestamp() as u32,
-                // key_tag: u16,
-                signer.calculate_key_tag()?,
-                // signer_name: Name,
-                signer.signer_name().clone(),
-                // sig: Vec<u8>
-                signature,
-            ))));
-
-            rr_set.insert_rrsig(rrsig);
-        }
-
-        Ok(())
-    }
-
     /// Signs all records in the zone.
     #[cfg(feature = "dnssec-ring")]
     fn sign_zone(&mut self, origin: &LowerName, dns_class: DNSClass) -> DnsSecResult<()> {
@@ -957,7 +1056,7 @@ impl InnerInMemory {
         for rr_set_orig in records.values_mut() {
             // because the rrset is an Arc, it must be cloned before mutated
             let rr_set = Arc::make_mut(rr_set_orig);
-            Self::sign_rrset(rr_set, secure_keys, minimum_ttl, dns_class)?;
+            sign_rrset(rr_set, secure_keys, minimum_ttl, dns_class)?;
         }
 
         Ok(())
@@ -1018,6 +1117,103 @@ impl InnerInMemory {
 
         Ok(None)
     }
+
+    /// Verifies the zone data against the ZONEMD records at the apex of the zone
+    #[cfg(feature = "dnssec-ring")]
+    fn verify_zonemd(&self, origin: &LowerName) -> DnsSecResult<()> {
+        let rr_key = RrKey::new(origin.clone(), RecordType::ZONEMD);
+        let Some(zonemds) = self.records.get(&rr_key) else {
+            return Err(format!("no ZONEMD record found for {origin}").into());
+        };
+
+        // RFC 8976 4 - only the SIMPLE scheme with SHA384 is supported, and the serial must match
+        // the SOA of the zone
+        let serial = self.serial(origin);
+        let zonemds = zonemds
+            .records_without_rrsigs()
+            .filter_map(|record| record.data().as_zonemd())
+            .filter(|zonemd| {
+                zonemd.scheme() == Scheme::Simple
+                    && zonemd.hash_algorithm() == HashAlgorithm::SHA384
+                    && zonemd.serial() == serial
+            })
+            .collect::<Vec<_>>();
+
+        let zonemd = match zonemds.as_slice() {
+            [] => return Err(format!("no supported ZONEMD record found for {origin}").into()),
+            [zonemd] => zonemd,
+            _ => {
+                return Err(
+                    format!("multiple ZONEMD records with the same parameters in {origin}").into(),
+                )
+            }
+        };
+
+        let digest = self.simple_zone_digest(origin, DigestType::SHA384)?;
+        if digest.as_ref() != zonemd.digest() {
+            return Err(format!("ZONEMD digest does not match the contents of {origin}").into());
+        }
+
+        Ok(())
+    }
+
+    /// Computes the digest of the zone with the SIMPLE scheme, RFC 8976 section 3.3
+    #[cfg(feature = "dnssec-ring")]
+    fn simple_zone_digest(
+        &self,
+        origin: &LowerName,
+        digest_type: DigestType,
+    ) -> DnsSecResult<Digest> {
+        let mut records = Vec::new();
+        for rr_set in self.records.values() {
+            // The apex ZONEMD RRset and the signatures covering it are excluded from the digest
+            if rr_set.record_type() == RecordType::ZONEMD
+                && origin == &LowerName::from(rr_set.name())
+            {
+                continue;
+            }
+
+            for record in rr_set.records_without_rrsigs().chain(rr_set.rrsigs()) {
+                let mut rdata = Vec::new();
+                let mut encoder = BinEncoder::new(&mut rdata);
+                encoder.set_canonical_names(true);
+                record.data().emit(&mut encoder)?;
+
+                records.push((
+                    record.name().to_lowercase(),
+                    record.record_type(),
+                    record.dns_class(),
+                    record.ttl(),
+                    rdata,
+                ));
+            }
+        }
+
+        // RRs are digested in canonical order, with any duplicates removed
+        records.sort_by(
+            |(name_a, type_a, _, _, rdata_a), (name_b, type_b, _, _, rdata_b)| {
+                name_a
+                    .cmp(name_b)
+                    .then(type_a.cmp(type_b))
+                    .then(rdata_a.cmp(rdata_b))
+            },
+        );
+        records.dedup_by(|a, b| a.0 == b.0 && a.1 == b.1 && a.2 == b.2 && a.4 == b.4);
+
+        let mut buf = Vec::new();
+        let mut encoder = BinEncoder::new(&mut buf);
+        encoder.set_canonical_names(true);
+        for (name, record_type, dns_class, ttl, rdata) in &records {
+            name.emit_as_canonical(&mut encoder, true)?;
+            record_type.emit(&mut encoder)?;
+            dns_class.emit(&mut encoder)?;
+            encoder.emit_u32(*ttl)?;
+            encoder.emit_u16(rdata.len() as u16)?;
+            encoder.emit_vec(rdata)?;
+        }
+
+        Ok(Digest::new(&buf, digest_type))
+    }
 }
 
 /// Gets the next search name, and returns the RecordType that it originated from
@@ -1074,6 +1270,23 @@ fn maybe_next_name(
     }
 }
 
+/// Returns `true` if the additional records of `search_name` are required in a minimal response
+/// for `name`
+///
+/// The addresses of the name servers below the name of the NS records are glue, the name servers
+/// can not be reached without them.
+fn is_additional_required(
+    name: &LowerName,
+    search_name: &LowerName,
+    search_type: RecordType,
+) -> bool {
+    match search_type {
+        RecordType::NS => name.zone_of(search_name),
+        RecordType::MX | RecordType::SRV => false,
+        _ => true,
+    }
+}
+
 #[async_trait::async_trait]
 impl Authority for InMemoryAuthority {
     type Lookup = AuthLookup;
@@ -1200,6 +1413,10 @@ impl Authority for InMemoryAuthority {
                     let additionals_root_chain_type: Option<(_, _)> = answer
                         .as_ref()
                         .and_then(|a| maybe_next_name(a, query_type))
+                        .filter(|(search_name, search_type)| {
+                            !self.minimal_responses
+                                || is_additional_required(name, search_name, *search_type)
+                        })
                         .and_then(|(search_name, search_type)| {
                             inner
                                 .additional_search(
@@ -1268,7 +1485,7 @@ impl Authority for InMemoryAuthority {
 
                                     // ANAME's are constructed on demand, so need to be signed before return
                                     if lookup_options.dnssec_ok() {
-                                        InnerInMemory::sign_rrset(
+                                        sign_rrset(
                                             &mut new_answer,
                                             inner.secure_keys(),
                                             inner.minimum_ttl(self.origin()),
@@ -1317,11 +1534,8 @@ impl Authority for InMemoryAuthority {
         use LookupControlFlow::*;
         let result = match result {
             Continue(Err(LookupError::ResponseCode(ResponseCode::NXDomain))) => {
-                if inner
-                    .records
-                    .keys()
-                    .any(|key| key.name() == name || name.zone_of(key.name()))
-                {
+                // the name exists, or a wildcard matches it without records of the type
+                if inner.name_exists(name) || inner.source_of_synthesis(name).is_some() {
                     return Continue(Err(LookupError::NameExists));
                 } else {
                     let code = if self.origin().zone_of(name) {
@@ -1352,20 +1566,8 @@ impl Authority for InMemoryAuthority {
         // if this is an AXFR zone transfer, verify that this is either the Secondary or Primary
         //  for AXFR the first and last record must be the SOA
         if RecordType::AXFR == record_type {
-            // TODO: support more advanced AXFR options
-            if !self.is_axfr_allowed() {
-                return LookupControlFlow::Continue(Err(LookupError::from(ResponseCode::Refused)));
-            }
-
-            #[allow(deprecated)]
-            match self.zone_type() {
-                ZoneType::Primary | ZoneType::Secondary | ZoneType::Master | ZoneType::Slave => (),
-                // TODO: Forward?
-                _ => {
-                    return LookupControlFlow::Continue(Err(LookupError::from(
-                        ResponseCode::NXDomain,
-                    )))
-                }
+            if let Err(e) = self.check_zone_transfer() {
+                return LookupControlFlow::Continue(Err(e));
             }
         }
 
@@ -1408,6 +1610,70 @@ impl Authority for InMemoryAuthority {
         }
     }
 
+    /// Answers an IXFR request from the journal, see `enable_ixfr_journal()`
+    ///
+    /// The changes of all versions since `serial` are condensed into a single difference. If the
+    /// journal does not contain changes since `serial`, the full zone is returned as for an AXFR.
+    async fn ixfr(
+        &self,
+        request_info: RequestInfo<'_>,
+        serial: u32,
+        lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Self::Lookup> {
+        debug!("ixfr for {} from serial {serial}", self.origin());
+        if let Err(e) = self.check_zone_transfer() {
+            return LookupControlFlow::Continue(Err(e));
+        }
+
+        let records = {
+            let inner = self.inner.read().await;
+            let current_serial = inner.serial(self.origin());
+            let soa = inner
+                .records
+                .get(&RrKey::new(self.origin().clone(), RecordType::SOA))
+                .and_then(|rrset| rrset.records_without_rrsigs().next().cloned());
+
+            match soa {
+                // the client is up to date, a single SOA is the response
+                Some(soa) if SerialNumber::from(serial) >= SerialNumber::from(current_serial) => {
+                    Some(vec![soa])
+                }
+                Some(_) => inner
+                    .journal
+                    .as_ref()
+                    .and_then(|journal| journal.changes(serial, current_serial))
+                    .map(|diff| {
+                        let new_soa = diff.new_soa;
+                        let mut records = vec![new_soa.clone(), diff.old_soa];
+                        records.extend(diff.deleted);
+                        records.push(new_soa.clone());
+                        records.extend(diff.added);
+                        records.push(new_soa);
+
+                        if !lookup_options.dnssec_ok() {
+                            records.retain(|r| r.record_type() != RecordType::RRSIG);
+                        }
+                        records
+                    }),
+                None => None,
+            }
+        };
+
+        match records {
+            Some(records) => LookupControlFlow::Continue(Ok(AuthLookup::IXFR(records))),
+            None => {
+                debug!("no journal entry for serial {serial}, falling back to AXFR");
+                let query = axfr_query(request_info.query);
+                let request_info = RequestInfo {
+                    query: &query,
+                    ..request_info
+                };
+
+                self.search(request_info, lookup_options).await
+            }
+        }
+    }
+
     /// Return the NSEC records based on the given name
     ///
     /// # Arguments
@@ -1467,21 +1733,22 @@ impl Authority for InMemoryAuthority {
 
         let closest_proof = get_closest_nsec(name);
 
-        // we need the wildcard proof, but make sure that it's still part of the zone.
-        let wildcard = name.base_name();
+        // we need the wildcard proof, at the closest encloser, but make sure that it's still
+        // part of the zone.
         let origin = self.origin();
-        let wildcard = if origin.zone_of(&wildcard) {
-            wildcard
-        } else {
-            origin.clone()
-        };
-
-        // don't duplicate the record...
-        let wildcard_proof = if wildcard != *name {
-            get_closest_nsec(&wildcard)
-        } else {
-            None
-        };
+        let wildcard = inner
+            .next_closer_name(name)
+            .map(LowerName::into_wildcard)
+            .filter(|wildcard| origin.zone_of(wildcard))
+            .unwrap_or_else(|| origin.clone());
+
+        // the NSEC of the wildcard for a wildcard answer or no data response, or the NSEC
+        // covering the wildcard for a name error
+        let wildcard_proof = inner
+            .records
+            .get(&RrKey::new(wildcard.clone(), RecordType::NSEC))
+            .cloned()
+            .or_else(|| get_closest_nsec(&wildcard));
 
         let proofs = match (closest_proof, wildcard_proof) {
             (Some(closest_proof), Some(wildcard_proof)) => {
@@ -1565,14 +1832,13 @@ impl Authority for InMemoryAuthority {
 
                         let wildcard_record = match next_closer_name {
                             Some(next_closer_name) => {
-                                let wildcard_match = {
-                                    let wildcard = qname.clone().into_wildcard();
-                                    inner.records.keys().any(|rr_key| rr_key.name == wildcard)
-                                };
+                                let wildcard_at_closest_encloser = next_closer_name.into_wildcard();
+                                let wildcard_match = inner
+                                    .records
+                                    .keys()
+                                    .any(|rr_key| rr_key.name == wildcard_at_closest_encloser);
 
                                 if wildcard_match {
-                                    let wildcard_at_closest_encloser =
-                                        next_closer_name.into_wildcard();
                                     let rr_key = RrKey::new(
                                         info.get_hashed_owner_name(
                                             &wildcard_at_closest_encloser,
@@ -1582,8 +1848,6 @@ impl Authority for InMemoryAuthority {
                                     );
                                     inner.records.get(&rr_key).cloned()
                                 } else if qtype != RecordType::DS {
-                                    let wildcard_at_closest_encloser =
-                                        next_closer_name.into_wildcard();
                                     inner.find_cover(&wildcard_at_closest_encloser, zone, &info)?
                                 } else {
                                     None
@@ -1641,3 +1905,83 @@ impl DnssecAuthority for InMemoryAuthority {
         inner.secure_zone_mut(self.origin(), self.class, self.nx_proof_kind.as_ref())
     }
 }
+
+/// Signs an RecordSet, and stores the RRSIGs in the RecordSet
+///
+/// This will sign the RecordSet with all the registered keys in the zone
+///
+/// # Arguments
+///
+/// * `rr_set` - RecordSet to sign
+/// * `secure_keys` - Set of keys to use to sign the RecordSet
+/// * `zone_ttl` - the TTL of the RRSIG records, the minimum TTL of the zone
+/// * `zone_class` - DNSClass of the zone
+#[cfg(feature = "dnssec-ring")]
+pub(crate) fn sign_rrset(
+    rr_set: &mut RecordSet,
+    secure_keys: &[SigSigner],
+    zone_ttl: u32,
+    zone_class: DNSClass,
+) -> DnsSecResult<()> {
+    let inception = OffsetDateTime::now_utc();
+
+    rr_set.clear_rrsigs();
+
+    let rrsig_temp = Record::update0(rr_set.name().clone(), zone_ttl, RecordType::RRSIG);
+
+    for signer in secure_keys {
+        debug!(
+            "signing rr_set: {}, {} with: {}",
+            rr_set.name(),
+            rr_set.record_type(),
+            signer.key().algorithm(),
+        );
+
+        let expiration = inception + signer.sig_duration();
+        let tbs = TBS::from_rrset(rr_set, zone_class, inception, expiration, signer);
+
+        // TODO, maybe chain these with some ETL operations instead?
+        let tbs = match tbs {
+            Ok(tbs) => tbs,
+            Err(err) => {
+                error!("could not serialize rrset to sign: {}", err);
+                continue;
+            }
+        };
+
+        let signature = signer.sign(&tbs);
+        let signature = match signature {
+            Ok(signature) => signature,
+            Err(err) => {
+                error!("could not sign rrset: {}", err);
+                continue;
+            }
+        };
+
+        let mut rrsig = rrsig_temp.clone();
+        rrsig.set_data(RData::DNSSEC(DNSSECRData::RRSIG(RRSIG::new(
+            // type_covered: RecordType,
+            rr_set.record_type(),
+            // algorithm: Algorithm,
+            signer.key().algorithm(),
+            // num_labels: u8,
+            rr_set.name().num_labels(),
+            // original_ttl: u32,
+            rr_set.ttl(),
+            // sig_expiration: u32,
+            expiration.unix_timestamp() as u32,
+            // sig_inception: u32,
+            inception.unix_timestamp() as u32,
+            // key_tag: u16,
+            signer.calculate_key_tag()?,
+            // signer_name: Name,
+            signer.signer_name().clone(),
+            // sig: Vec<u8>
+            signature,
+        ))));
+
+        rr_set.insert_rrsig(rrsig);
+    }
+
+    Ok(())
+}
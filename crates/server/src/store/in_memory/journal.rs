@@ -0,0 +1,298 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Journal of the recent changes to a zone, used to answer IXFR requests
+
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    sync::Arc,
+};
+
+use crate::proto::rr::{rdata::SOA, LowerName, Record, RecordSet, RecordType, RrKey};
+
+/// The records deleted and added between two versions of a zone
+#[derive(Clone, Debug)]
+pub(super) struct IxfrDiff {
+    pub(super) old_soa: Record,
+    pub(super) deleted: Vec<Record>,
+    pub(super) new_soa: Record,
+    pub(super) added: Vec<Record>,
+}
+
+/// Keeps the changes of the most recent serial bumps of a zone
+///
+/// Every time the serial of the zone changes, the records of the zone are compared against a
+/// snapshot of the previous version. Cloning the snapshot is cheap, as only the changed record
+/// sets are not shared with the zone.
+pub(super) struct IxfrJournal {
+    max_entries: usize,
+    entries: VecDeque<IxfrDiff>,
+    snapshot: BTreeMap<RrKey, Arc<RecordSet>>,
+    snapshot_soa: Option<Record>,
+}
+
+impl IxfrJournal {
+    /// Starts a journal from the current version of the zone
+    pub(super) fn new(
+        max_entries: usize,
+        records: &BTreeMap<RrKey, Arc<RecordSet>>,
+        origin: &LowerName,
+    ) -> Self {
+        Self {
+            max_entries,
+            entries: VecDeque::new(),
+            snapshot: records.clone(),
+            snapshot_soa: soa_record(records, origin),
+        }
+    }
+
+    /// Records the changes since the last version, if the serial of the zone was bumped
+    ///
+    /// Changes made without bumping the serial are included in the entry of the next bump.
+    pub(super) fn record(&mut self, records: &BTreeMap<RrKey, Arc<RecordSet>>, origin: &LowerName) {
+        let Some(new_soa) = soa_record(records, origin) else {
+            return;
+        };
+
+        let Some(old_soa) = self.snapshot_soa.take() else {
+            self.snapshot = records.clone();
+            self.snapshot_soa = Some(new_soa);
+            return;
+        };
+
+        if serial(&old_soa) == serial(&new_soa) {
+            self.snapshot_soa = Some(old_soa);
+            return;
+        }
+
+        let (deleted, added) = diff(&self.snapshot, records);
+        self.entries.push_back(IxfrDiff {
+            old_soa,
+            deleted,
+            new_soa: new_soa.clone(),
+            added,
+        });
+        if self.entries.len() > self.max_entries {
+            self.entries.pop_front();
+        }
+
+        self.snapshot = records.clone();
+        self.snapshot_soa = Some(new_soa);
+    }
+
+    /// Returns the changes from `serial` to `current_serial`, condensed into a single difference
+    ///
+    /// [RFC 1995, Incremental Zone Transfer in DNS, August 1996](https://www.rfc-editor.org/rfc/rfc1995#section-5)
+    ///
+    /// ```text
+    /// 5. Condensation
+    ///
+    ///    An IXFR server may optionally condense multiple difference sequences
+    ///    into a single difference sequence, thus, dropping information on
+    ///    intermediate versions.
+    /// ```
+    ///
+    /// `None` if the journal does not reach back to `serial`, or does not end at `current_serial`.
+    pub(super) fn changes(&self, serial: u32, current_serial: u32) -> Option<IxfrDiff> {
+        if self.snapshot_soa.as_ref().map(self::serial) != Some(current_serial) {
+            return None;
+        }
+
+        let start = self
+            .entries
+            .iter()
+            .position(|entry| self::serial(&entry.old_soa) == serial)?;
+
+        let mut deleted = BTreeSet::new();
+        let mut added = BTreeSet::new();
+        for entry in self.entries.range(start..) {
+            for record in &entry.deleted {
+                if !added.remove(record) {
+                    deleted.insert(record.clone());
+                }
+            }
+            for record in &entry.added {
+                if !deleted.remove(record) {
+                    added.insert(record.clone());
+                }
+            }
+        }
+
+        Some(IxfrDiff {
+            old_soa: self.entries[start].old_soa.clone(),
+            deleted: deleted.into_iter().collect(),
+            new_soa: self.entries.back()?.new_soa.clone(),
+            added: added.into_iter().collect(),
+        })
+    }
+}
+
+fn soa_record(records: &BTreeMap<RrKey, Arc<RecordSet>>, origin: &LowerName) -> Option<Record> {
+    records
+        .get(&RrKey::new(origin.clone(), RecordType::SOA))
+        .and_then(|rrset| rrset.records_without_rrsigs().next().cloned())
+}
+
+fn serial(soa: &Record) -> u32 {
+    soa.data().as_soa().map_or(0, SOA::serial)
+}
+
+/// Returns the records deleted from and added to `old` to get to `new`, excluding the SOA
+fn diff(
+    old: &BTreeMap<RrKey, Arc<RecordSet>>,
+    new: &BTreeMap<RrKey, Arc<RecordSet>>,
+) -> (Vec<Record>, Vec<Record>) {
+    fn records(rrset: Option<&Arc<RecordSet>>) -> BTreeSet<Record> {
+        rrset
+            .into_iter()
+            .flat_map(|rrset| rrset.records_without_rrsigs().chain(rrset.rrsigs()))
+            .filter(|record| record.record_type() != RecordType::SOA)
+            .cloned()
+            .collect()
+    }
+
+    let mut deleted = Vec::new();
+    let mut added = Vec::new();
+    let keys = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+    for key in keys {
+        let (old, new) = (old.get(key), new.get(key));
+        if let (Some(old), Some(new)) = (old, new) {
+            if Arc::ptr_eq(old, new) {
+                continue;
+            }
+        }
+
+        let (old, new) = (records(old), records(new));
+        deleted.extend(old.difference(&new).cloned());
+        added.extend(new.difference(&old).cloned());
+    }
+
+    (deleted, added)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use crate::proto::rr::{rdata::A, Name, RData};
+
+    use super::*;
+
+    fn origin() -> Name {
+        Name::from_str("example.com.").unwrap()
+    }
+
+    fn soa(serial: u32) -> Record {
+        let soa = SOA::new(
+            Name::from_str("ns.example.com.").unwrap(),
+            Name::from_str("hostmaster.example.com.").unwrap(),
+            serial,
+            3600,
+            600,
+            86400,
+            3600,
+        );
+        Record::from_rdata(origin(), 3600, RData::SOA(soa))
+    }
+
+    fn a(name: &str, last_octet: u8) -> Record {
+        Record::from_rdata(
+            Name::from_str(name).unwrap(),
+            3600,
+            RData::A(A::from(Ipv4Addr::new(192, 0, 2, last_octet))),
+        )
+    }
+
+    fn upsert(records: &mut BTreeMap<RrKey, Arc<RecordSet>>, record: Record) {
+        let key = RrKey::new(record.name().into(), record.record_type());
+        let rrset = records.entry(key).or_insert_with(|| {
+            Arc::new(RecordSet::new(
+                record.name().clone(),
+                record.record_type(),
+                0,
+            ))
+        });
+        let mut new = RecordSet::clone(rrset);
+        if record.record_type() == RecordType::SOA {
+            new = RecordSet::new(record.name().clone(), RecordType::SOA, 0);
+        }
+        new.insert(record, 0);
+        *rrset = Arc::new(new);
+    }
+
+    fn remove(records: &mut BTreeMap<RrKey, Arc<RecordSet>>, record: &Record) {
+        let key = RrKey::new(record.name().into(), record.record_type());
+        let rrset = records.get_mut(&key).unwrap();
+        let mut new = RecordSet::clone(rrset);
+        new.remove(record, 0);
+        *rrset = Arc::new(new);
+    }
+
+    #[test]
+    fn test_journal_condenses_changes() {
+        let origin = LowerName::from(origin());
+        let mut records = BTreeMap::new();
+        upsert(&mut records, soa(1));
+        upsert(&mut records, a("www.example.com.", 1));
+
+        let mut journal = IxfrJournal::new(10, &records, &origin);
+
+        // 1 -> 2: replace www, add tmp
+        remove(&mut records, &a("www.example.com.", 1));
+        upsert(&mut records, a("www.example.com.", 2));
+        upsert(&mut records, a("tmp.example.com.", 1));
+        upsert(&mut records, soa(2));
+        journal.record(&records, &origin);
+
+        // a change without a serial bump is part of the next entry
+        upsert(&mut records, a("mail.example.com.", 1));
+        journal.record(&records, &origin);
+
+        // 2 -> 3: drop tmp again
+        remove(&mut records, &a("tmp.example.com.", 1));
+        upsert(&mut records, soa(3));
+        journal.record(&records, &origin);
+
+        let diff = journal.changes(2, 3).unwrap();
+        assert_eq!(diff.old_soa, soa(2));
+        assert_eq!(diff.new_soa, soa(3));
+        assert_eq!(diff.deleted, vec![a("tmp.example.com.", 1)]);
+        assert_eq!(diff.added, vec![a("mail.example.com.", 1)]);
+
+        let diff = journal.changes(1, 3).unwrap();
+        assert_eq!(diff.old_soa, soa(1));
+        assert_eq!(diff.new_soa, soa(3));
+        assert_eq!(diff.deleted, vec![a("www.example.com.", 1)]);
+        assert_eq!(
+            diff.added,
+            vec![a("mail.example.com.", 1), a("www.example.com.", 2)]
+        );
+
+        // unknown serial, or a zone which moved on without being recorded
+        assert!(journal.changes(0, 3).is_none());
+        assert!(journal.changes(1, 4).is_none());
+    }
+
+    #[test]
+    fn test_journal_max_entries() {
+        let origin = LowerName::from(origin());
+        let mut records = BTreeMap::new();
+        upsert(&mut records, soa(1));
+
+        let mut journal = IxfrJournal::new(2, &records, &origin);
+        for serial in 2..=4 {
+            upsert(&mut records, a("www.example.com.", serial as u8));
+            upsert(&mut records, soa(serial));
+            journal.record(&records, &origin);
+        }
+
+        assert!(journal.changes(1, 4).is_none());
+        assert!(journal.changes(2, 4).is_some());
+        assert!(journal.changes(3, 4).is_some());
+    }
+}
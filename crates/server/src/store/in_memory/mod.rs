@@ -8,5 +8,8 @@
 //! Zone file based serving with Dynamic DNS and journaling support
 
 mod authority;
+mod journal;
 
+#[cfg(feature = "dnssec-ring")]
+pub(crate) use self::authority::sign_rrset;
 pub use self::authority::InMemoryAuthority;
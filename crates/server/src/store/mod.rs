@@ -11,6 +11,10 @@ pub mod blocklist;
 pub mod file;
 pub mod forwarder;
 pub mod in_memory;
+#[cfg(feature = "dnssec-ring")]
+pub mod online_signer;
 pub mod recursor;
+pub mod rpz;
+pub mod secondary;
 #[cfg(feature = "sqlite")]
 pub mod sqlite;
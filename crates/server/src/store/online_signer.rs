@@ -0,0 +1,631 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Online signing, DNSSEC signatures generated at query time
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use time::OffsetDateTime;
+use tracing::{debug, warn};
+
+use crate::{
+    authority::{
+        AuthLookup, Authority, LookupControlFlow, LookupError, LookupOptions, LookupRecords,
+        MessageRequest, Nsec3QueryInfo, UpdateResult, ZoneType,
+    },
+    dnssec::NxProofKind,
+    proto::{
+        dnssec::{rdata::NSEC, tsig::TSigner, DnsSecResult, SigSigner},
+        rr::{DNSClass, LowerName, Name, Record, RecordData, RecordSet, RecordType, RrKey},
+        ProtoError,
+    },
+    server::RequestInfo,
+    store::in_memory::sign_rrset,
+};
+
+/// The number of signed RRsets kept by default, see `OnlineSigner::with_cache_size`
+const DEFAULT_CACHE_SIZE: usize = 4096;
+
+static NSEC_PROOF: NxProofKind = NxProofKind::Nsec;
+
+/// An authority which signs the responses of another authority when they are requested
+///
+/// The wrapped authority holds the unsigned zone, e.g. records which are generated on demand. For
+/// requests with the DO bit set, the RRsets of the answer, authority and additional sections are
+/// signed with the zone signing keys, and the DNSKEY RRset of the zone is built from the keys.
+/// RRsets which already carry signatures are returned as is.
+///
+/// Signing is expensive, the signed RRsets are cached, and reused as long as their records are
+/// unchanged, until half of the validity period of the signatures has passed.
+///
+/// Non-existence is proven with minimally covering NSEC records, see
+/// [RFC 4470](https://tools.ietf.org/html/rfc4470), which are generated for each negative answer
+/// instead of maintaining an NSEC chain over the zone. This also prevents walking the zone. NSEC3
+/// is not supported.
+///
+/// The zone is expected to be of class IN.
+pub struct OnlineSigner<A> {
+    authority: A,
+    signers: Vec<SigSigner>,
+    dnskeys: Arc<RecordSet>,
+    cache_size: usize,
+    cache: Mutex<HashMap<RrKey, CachedRrset>>,
+}
+
+impl<A: Authority<Lookup = AuthLookup>> OnlineSigner<A> {
+    /// Creates a new online signer
+    ///
+    /// # Arguments
+    ///
+    /// * `authority` - the authority for the unsigned zone
+    /// * `signers` - the keys the zone is signed with
+    /// * `dnskey_ttl` - the TTL of the DNSKEY RRset of the zone
+    pub fn new(authority: A, signers: Vec<SigSigner>, dnskey_ttl: u32) -> DnsSecResult<Self> {
+        let origin = Name::from(authority.origin());
+        let mut dnskeys = RecordSet::with_ttl(origin, RecordType::DNSKEY, dnskey_ttl);
+        for signer in &signers {
+            dnskeys.add_rdata(signer.to_dnskey()?.into_rdata());
+        }
+
+        Ok(Self {
+            authority,
+            signers,
+            dnskeys: Arc::new(dnskeys),
+            cache_size: DEFAULT_CACHE_SIZE,
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// The maximum number of signed RRsets to cache, defaults to 4096
+    pub fn with_cache_size(mut self, cache_size: usize) -> Self {
+        self.cache_size = cache_size;
+        self
+    }
+
+    /// The wrapped authority
+    pub fn authority(&self) -> &A {
+        &self.authority
+    }
+
+    /// Signs the RRsets of the lookup, if the request asked for DNSSEC records
+    fn sign_lookup(&self, lookup: AuthLookup, lookup_options: LookupOptions) -> AuthLookup {
+        if !lookup_options.dnssec_ok() {
+            return lookup;
+        }
+
+        lookup.map_rrsets(&mut |rrset| self.sign(rrset))
+    }
+
+    /// Returns the signed version of `rrset`, from the cache if it was signed before
+    fn sign(&self, rrset: Arc<RecordSet>) -> Arc<RecordSet> {
+        if self.signers.is_empty() || !rrset.rrsigs().is_empty() || rrset.is_empty() {
+            return rrset;
+        }
+
+        let key = RrKey::new(LowerName::from(rrset.name()), rrset.record_type());
+        let now = OffsetDateTime::now_utc();
+        if let Some(cached) = self.lock().get(&key) {
+            if now < cached.refresh_at && cached.signs(&rrset) {
+                return cached.rrset.clone();
+            }
+        }
+
+        debug!("signing {} {}", rrset.name(), rrset.record_type());
+        let mut signed = RecordSet::clone(&rrset);
+        if let Err(e) = sign_rrset(&mut signed, &self.signers, rrset.ttl(), DNSClass::IN) {
+            // rather than failing the request, the records are returned unsigned
+            warn!(
+                "failed to sign {} {}: {e}",
+                rrset.name(),
+                rrset.record_type()
+            );
+            return rrset;
+        }
+
+        let signed = Arc::new(signed);
+        let validity = self
+            .signers
+            .iter()
+            .map(SigSigner::sig_duration)
+            .min()
+            .unwrap_or_default();
+
+        let mut cache = self.lock();
+        if cache.len() >= self.cache_size {
+            cache.retain(|_, cached| now < cached.refresh_at);
+            if cache.len() >= self.cache_size {
+                cache.clear();
+            }
+        }
+        cache.insert(
+            key,
+            CachedRrset {
+                rrset: signed.clone(),
+                refresh_at: now + validity / 2,
+            },
+        );
+
+        signed
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<RrKey, CachedRrset>> {
+        self.cache.lock().expect("OnlineSigner cache lock poisoned")
+    }
+
+    /// Returns the signed NSEC records proving the non-existence of `name`, or of its type
+    async fn nsec_proof(&self, name: &LowerName) -> Result<Vec<Arc<RecordSet>>, LookupError> {
+        let origin = self.origin();
+        if !origin.zone_of(name) {
+            return Ok(vec![]);
+        }
+
+        let ttl = self.negative_ttl().await;
+        let nsec = |owner: Name, next: Name, types: Vec<RecordType>| {
+            let mut types = types;
+            types.extend([RecordType::RRSIG, RecordType::NSEC]);
+            types.sort();
+            types.dedup();
+
+            let mut rrset = RecordSet::with_ttl(owner, RecordType::NSEC, ttl);
+            rrset.add_rdata(NSEC::new(next, types).into_rdata());
+            self.sign(Arc::new(rrset))
+        };
+
+        // no data, the NSEC record matches the name, and only covers the name itself
+        let name = Name::from(name);
+        if let Some(types) = self.record_types(&name).await? {
+            let next = successor(&name, origin);
+            return Ok(vec![nsec(name, next, types)]);
+        }
+
+        // name error, find the closest encloser, the origin always exists
+        let mut next_closer = name.clone();
+        let (encloser, encloser_types) = loop {
+            let encloser = next_closer.base_name();
+            if let Some(types) = self.record_types(&encloser).await? {
+                break (encloser, types);
+            }
+            if LowerName::from(&encloser) == *origin {
+                break (encloser, vec![]);
+            }
+            next_closer = encloser;
+        };
+
+        // the next closer name, and with it the name, do not exist
+        let next_closer_proof = nsec(predecessor(&next_closer)?, successor(&name, origin), vec![]);
+        // the closest encloser exists
+        let next = successor(&encloser, origin);
+        let encloser_proof = nsec(encloser.clone(), next, encloser_types);
+        // no wildcard at the closest encloser could have matched
+        let wildcard = encloser.into_wildcard();
+        let wildcard_proof = nsec(
+            predecessor(&wildcard)?,
+            successor(&wildcard, origin),
+            vec![],
+        );
+
+        Ok(vec![next_closer_proof, encloser_proof, wildcard_proof])
+    }
+
+    /// The types of the records at `name`, `None` if the name does not exist
+    async fn record_types(&self, name: &Name) -> Result<Option<Vec<RecordType>>, LookupError> {
+        let lower_name = LowerName::from(name);
+        let options = LookupOptions::default();
+
+        let types = match self
+            .authority
+            .lookup(&lower_name, RecordType::ANY, options)
+            .await
+            .map_result()
+        {
+            Some(Ok(lookup)) => lookup
+                .iter()
+                .filter(|record| record.name() == name)
+                .map(Record::record_type)
+                .collect::<Vec<_>>(),
+            Some(Err(e)) if e.is_nx_domain() => return Ok(None),
+            Some(Err(e)) if e.is_name_exists() => return Ok(Some(vec![])),
+            Some(Err(e)) => return Err(e),
+            None => vec![],
+        };
+
+        if !types.is_empty() {
+            return Ok(Some(types));
+        }
+
+        // empty non-terminals exist, without any records
+        match self
+            .authority
+            .lookup(&lower_name, RecordType::NSEC, options)
+            .await
+            .map_result()
+        {
+            Some(Ok(_)) => Ok(Some(vec![])),
+            Some(Err(e)) if e.is_name_exists() => Ok(Some(vec![])),
+            _ => Ok(None),
+        }
+    }
+
+    /// The TTL of negative answers, see [RFC 9077](https://tools.ietf.org/html/rfc9077)
+    async fn negative_ttl(&self) -> u32 {
+        let Some(Ok(lookup)) = self.authority.soa().await.map_result() else {
+            return 0;
+        };
+
+        lookup
+            .iter()
+            .find_map(|record| {
+                let soa = record.data().as_soa()?;
+                Some(soa.minimum().min(record.ttl()))
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[async_trait::async_trait]
+impl<A: Authority<Lookup = AuthLookup>> Authority for OnlineSigner<A> {
+    type Lookup = AuthLookup;
+
+    fn zone_type(&self) -> ZoneType {
+        self.authority.zone_type()
+    }
+
+    fn is_axfr_allowed(&self) -> bool {
+        self.authority.is_axfr_allowed()
+    }
+
+    fn can_validate_dnssec(&self) -> bool {
+        self.authority.can_validate_dnssec()
+    }
+
+    async fn update(&self, update: &MessageRequest) -> UpdateResult<bool> {
+        self.authority.update(update).await
+    }
+
+    fn tsig_signers(&self) -> &[TSigner] {
+        self.authority.tsig_signers()
+    }
+
+    fn origin(&self) -> &LowerName {
+        self.authority.origin()
+    }
+
+    async fn lookup(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Self::Lookup> {
+        if rtype == RecordType::DNSKEY && name == self.origin() {
+            let dnskeys = if lookup_options.dnssec_ok() {
+                self.sign(self.dnskeys.clone())
+            } else {
+                self.dnskeys.clone()
+            };
+            return LookupControlFlow::Continue(Ok(
+                LookupRecords::new(lookup_options, dnskeys).into()
+            ));
+        }
+
+        self.authority
+            .lookup(name, rtype, lookup_options)
+            .await
+            .map(|lookup| self.sign_lookup(lookup, lookup_options))
+    }
+
+    async fn search(
+        &self,
+        request_info: RequestInfo<'_>,
+        lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Self::Lookup> {
+        let query = request_info.query;
+        if query.query_type() == RecordType::DNSKEY && query.name() == self.origin() {
+            return self
+                .lookup(query.name(), query.query_type(), lookup_options)
+                .await;
+        }
+
+        self.authority
+            .search(request_info, lookup_options)
+            .await
+            .map(|lookup| self.sign_lookup(lookup, lookup_options))
+    }
+
+    async fn ixfr(
+        &self,
+        request_info: RequestInfo<'_>,
+        serial: u32,
+        lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Self::Lookup> {
+        self.authority
+            .ixfr(request_info, serial, lookup_options)
+            .await
+            .map(|lookup| self.sign_lookup(lookup, lookup_options))
+    }
+
+    async fn get_nsec_records(
+        &self,
+        name: &LowerName,
+        lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Self::Lookup> {
+        LookupControlFlow::Continue(
+            self.nsec_proof(name)
+                .await
+                .map(|proof| LookupRecords::many(lookup_options, proof).into()),
+        )
+    }
+
+    async fn get_nsec3_records(
+        &self,
+        _info: Nsec3QueryInfo<'_>,
+        _lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Self::Lookup> {
+        LookupControlFlow::Continue(Ok(AuthLookup::default()))
+    }
+
+    fn nx_proof_kind(&self) -> Option<&NxProofKind> {
+        Some(&NSEC_PROOF)
+    }
+}
+
+/// A signed RRset in the cache of an `OnlineSigner`
+struct CachedRrset {
+    rrset: Arc<RecordSet>,
+    refresh_at: OffsetDateTime,
+}
+
+impl CachedRrset {
+    /// Returns true if the signatures are for the records of `rrset`
+    fn signs(&self, rrset: &RecordSet) -> bool {
+        self.rrset.ttl() == rrset.ttl()
+            && self
+                .rrset
+                .records_without_rrsigs()
+                .eq(rrset.records_without_rrsigs())
+    }
+}
+
+/// A name which follows `name` closely in the canonical order, `\000.name`
+///
+/// Names too long to prepend a label to are followed by the origin, i.e. the NSEC record is the
+/// last one of the zone.
+fn successor(name: &Name, origin: &LowerName) -> Name {
+    prepend_label(name, vec![0]).unwrap_or_else(|_| Name::from(origin))
+}
+
+/// A name which precedes `name` closely in the canonical order, [RFC 4470, section 4.1](https://tools.ietf.org/html/rfc4470#section-4.1)
+///
+/// The last octet of the first label is decremented, and followed by `\255`, e.g. `b.example.`
+/// is preceded by `a\255.example.`. Only names starting with that label lie in between.
+fn predecessor(name: &Name) -> Result<Name, ProtoError> {
+    let base = name.base_name();
+    let mut label = match name.iter().next() {
+        Some(label) => label.to_ascii_lowercase(),
+        None => return Ok(base),
+    };
+
+    match label.pop() {
+        // `\000.base` is the first name following `base`
+        Some(0) if label.is_empty() => Ok(base),
+        Some(0) => prepend_label(&prepend_label(&base, label)?, vec![u8::MAX]),
+        Some(last) => {
+            // labels are compared in lower case, an upper case letter would sort after `last`
+            let mut previous = last - 1;
+            if previous.is_ascii_uppercase() {
+                previous = b'A' - 1;
+            }
+
+            label.push(previous);
+            if label.len() < 63 {
+                label.push(u8::MAX);
+            }
+            prepend_label(&base, label)
+        }
+        None => Ok(base),
+    }
+}
+
+/// `Name::prepend_label`, keeping the name fully qualified
+fn prepend_label(name: &Name, label: Vec<u8>) -> Result<Name, ProtoError> {
+    let mut prepended = name.prepend_label(label)?;
+    prepended.set_fqdn(name.is_fqdn());
+    Ok(prepended)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::Ipv4Addr, str::FromStr, time::Duration};
+
+    use crate::proto::{
+        dnssec::{
+            rdata::{DNSSECRData, DNSKEY},
+            ring::Ed25519SigningKey,
+            verify_nsec, Proof, SigningKey, Verifier,
+        },
+        op::Query,
+        rr::{
+            rdata::{A, SOA},
+            RData,
+        },
+    };
+    use crate::store::in_memory::InMemoryAuthority;
+
+    use super::*;
+
+    fn signer() -> OnlineSigner<InMemoryAuthority> {
+        let origin = Name::from_str("example.com.").unwrap();
+        let mut authority =
+            InMemoryAuthority::empty(origin.clone(), ZoneType::Primary, false, None);
+
+        let soa = SOA::new(
+            Name::from_str("ns.example.com.").unwrap(),
+            Name::from_str("admin.example.com.").unwrap(),
+            1,
+            3600,
+            600,
+            86400,
+            300,
+        );
+        authority.upsert_mut(Record::from_rdata(origin.clone(), 3600, RData::SOA(soa)), 1);
+        for (name, ip) in [("www", [192, 0, 2, 1]), ("host.sub", [192, 0, 2, 2])] {
+            let name = Name::from_str(name)
+                .unwrap()
+                .append_domain(&origin)
+                .unwrap();
+            let record = Record::from_rdata(name, 300, RData::A(A::from(Ipv4Addr::from(ip))));
+            authority.upsert_mut(record, 1);
+        }
+
+        let pkcs8 = Ed25519SigningKey::generate_pkcs8().unwrap();
+        let key = Ed25519SigningKey::from_pkcs8(&pkcs8).unwrap();
+        let dnskey = DNSKEY::from_key(&key.to_public_key().unwrap());
+        let signer = SigSigner::dnssec(dnskey, Box::new(key), origin, Duration::from_secs(86400));
+
+        OnlineSigner::new(authority, vec![signer], 3600).unwrap()
+    }
+
+    fn dnssec_ok() -> LookupOptions {
+        LookupOptions::default().set_dnssec_ok(true)
+    }
+
+    /// Returns the DNSKEY of the zone, the signature of the DNSKEY RRset is verified with it
+    async fn dnskey(signer: &OnlineSigner<InMemoryAuthority>) -> DNSKEY {
+        let lookup = signer
+            .lookup(signer.origin(), RecordType::DNSKEY, dnssec_ok())
+            .await
+            .unwrap();
+        let records = lookup.iter().cloned().collect::<Vec<_>>();
+        assert_eq!(records.len(), 2);
+
+        let dnskey = records[0]
+            .data()
+            .as_dnssec()
+            .and_then(DNSSECRData::as_dnskey)
+            .unwrap()
+            .clone();
+        verify(&dnskey, &records);
+        dnskey
+    }
+
+    /// Verifies the RRSIGs of all the RRsets in `records` with `dnskey`
+    fn verify(dnskey: &DNSKEY, records: &[Record]) {
+        let rrsigs = records
+            .iter()
+            .filter_map(|record| {
+                let rrsig = record.data().as_dnssec()?.as_rrsig()?;
+                Some((record.name(), rrsig))
+            })
+            .collect::<Vec<_>>();
+
+        let rrsets = records
+            .iter()
+            .filter(|record| record.record_type() != RecordType::RRSIG)
+            .map(|record| (record.name(), record.record_type()))
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(rrsigs.len(), rrsets.len(), "every RRset should be signed");
+
+        for (name, rrsig) in rrsigs {
+            let covered = records.iter().filter(|record| {
+                record.name() == name && record.record_type() == rrsig.type_covered()
+            });
+            dnskey
+                .verify_rrsig(name, DNSClass::IN, rrsig, covered)
+                .unwrap_or_else(|e| panic!("{name} {}: {e}", rrsig.type_covered()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_signed_answer() {
+        let signer = signer();
+        let dnskey = dnskey(&signer).await;
+
+        let name = LowerName::from_str("www.example.com.").unwrap();
+        let lookup = signer
+            .lookup(&name, RecordType::A, dnssec_ok())
+            .await
+            .unwrap();
+        let records = lookup.iter().cloned().collect::<Vec<_>>();
+        assert_eq!(records.len(), 2);
+        verify(&dnskey, &records);
+
+        // the signatures are reused
+        let LookupControlFlow::Continue(Ok(cached)) =
+            signer.lookup(&name, RecordType::A, dnssec_ok()).await
+        else {
+            panic!("lookup failed");
+        };
+        assert_eq!(cached.iter().cloned().collect::<Vec<_>>(), records);
+
+        // no signatures without DO
+        let lookup = signer
+            .lookup(&name, RecordType::A, LookupOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(lookup.iter().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_signed_negative_answers() {
+        let signer = signer();
+        let dnskey = dnskey(&signer).await;
+        let origin = Name::from(signer.origin());
+
+        for (name, query_type) in [
+            // no data
+            ("www.example.com.", RecordType::AAAA),
+            // empty non-terminal
+            ("sub.example.com.", RecordType::A),
+            // name errors
+            ("none.example.com.", RecordType::A),
+            ("a.b.none.example.com.", RecordType::A),
+            ("none.sub.example.com.", RecordType::A),
+        ] {
+            let name = LowerName::from_str(name).unwrap();
+            let lookup = signer.get_nsec_records(&name, dnssec_ok()).await.unwrap();
+            let records = lookup.iter().cloned().collect::<Vec<_>>();
+            assert!(!records.is_empty(), "{name}");
+            verify(&dnskey, &records);
+
+            let nsecs = records
+                .iter()
+                .filter(|record| record.record_type() == RecordType::NSEC)
+                .collect::<Vec<_>>();
+            let query = Query::query(name.clone().into(), query_type);
+            assert_eq!(
+                verify_nsec(&query, &origin, &nsecs),
+                Proof::Secure,
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_predecessor() {
+        let example = |labels: &[&[u8]]| {
+            let labels = labels.iter().copied().chain([&b"example"[..], b"com"]);
+            Name::from_labels(labels).unwrap()
+        };
+
+        let cases: [(&[u8], &[&[u8]]); 6] = [
+            (b"b", &[b"a\xff"]),
+            (b"B", &[b"a\xff"]),
+            (b"[", &[b"@\xff"]),
+            (b"*", &[b")\xff"]),
+            (b"a\x00", &[b"\xff", b"a"]),
+            (b"\x00", &[]),
+        ];
+        for (label, expected) in cases {
+            let name = example(&[label]);
+            let predecessor = predecessor(&name).unwrap();
+            assert_eq!(predecessor, example(expected), "{name}");
+            assert!(predecessor < name, "{name}");
+        }
+    }
+}
@@ -24,7 +24,7 @@ use crate::{
         config::{NameServerConfig, NameServerConfigGroup},
         lookup::Lookup,
     },
-    server::RequestInfo,
+    server::{CacheStatus, RequestInfo},
     store::recursor::RecursiveConfig,
 };
 #[cfg(feature = "dnssec-ring")]
@@ -104,6 +104,7 @@ impl RecursiveAuthority {
             })
             .avoid_local_udp_ports(config.avoid_local_udp_ports.clone())
             .ttl_config(config.cache_policy.clone())
+            .qname_minimization(config.qname_minimization)
             .build(roots)
             .map_err(|e| format!("failed to initialize recursor: {e}"))?;
 
@@ -226,6 +227,10 @@ impl LookupObject for RecursiveLookup {
         None
     }
 
+    fn cache_status(&self) -> Option<CacheStatus> {
+        Some(self.0.metadata().into())
+    }
+
     #[cfg(feature = "dnssec-ring")]
     fn dnssec_summary(&self) -> DnssecSummary {
         let mut all_secure = None;
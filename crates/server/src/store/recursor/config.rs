@@ -72,6 +72,10 @@ pub struct RecursiveConfig {
     /// Caching policy, setting minimum and maximum TTLs
     #[serde(default)]
     pub cache_policy: TtlConfig,
+
+    /// Send only the minimal part of the queried name to each zone's name servers, RFC 9156
+    #[serde(default)]
+    pub qname_minimization: bool,
 }
 
 impl RecursiveConfig {
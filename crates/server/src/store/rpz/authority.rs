@@ -0,0 +1,423 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::{fs, io, path::Path, sync::Arc};
+
+use tracing::{info, trace};
+
+#[cfg(feature = "dnssec-ring")]
+use crate::{authority::Nsec3QueryInfo, dnssec::NxProofKind};
+use crate::{
+    authority::{
+        AuthLookup, Authority, LookupControlFlow, LookupError, LookupObject, LookupOptions,
+        LookupRecords, MessageRequest, UpdateResult, ZoneType,
+    },
+    proto::{
+        op::ResponseCode,
+        rr::{rdata::CNAME, LowerName, Name, RData, Record, RecordSet, RecordType},
+    },
+    server::RequestInfo,
+    store::rpz::{Rpz, RpzAction, RpzConfig, RpzPolicy},
+};
+
+/// A conditional authority applying the policies of a response policy zone to the queries, and
+/// to the answers of the other authorities of the chain, like a forwarder or a recursor
+///
+/// The policy zone must be configured before the other authorities of the chain. The client IP
+/// and QNAME triggers are checked before the query is passed to the next authorities. The IP and
+/// NSDNAME triggers are checked against their answers, when consulting the policy zone, unless
+/// the queried name matched a PASSTHRU policy. The client IP PASSTHRU policies only exempt the
+/// clients from the QNAME policies, as the client is not known when consulting.
+///
+/// The CNAME policies answer with the CNAME record alone, the clients resolve its target.
+pub struct RpzAuthority {
+    origin: LowerName,
+    rpz: Rpz,
+}
+
+impl RpzAuthority {
+    /// Applies the policies of `rpz` to the queries of the names under `origin`
+    pub fn new(origin: Name, rpz: Rpz) -> Self {
+        Self {
+            origin: origin.into(),
+            rpz,
+        }
+    }
+
+    /// Read the Authority for the origin from the specified configuration
+    pub fn try_from_config(
+        origin: Name,
+        _zone_type: ZoneType,
+        config: &RpzConfig,
+        base_dir: Option<&Path>,
+    ) -> Result<Self, String> {
+        let zone_path = base_dir
+            .map(|dir| dir.join(&config.zone_file_path))
+            .unwrap_or_else(|| config.zone_file_path.clone());
+
+        info!("loading response policy zone: {}", zone_path.display());
+        let zone = fs::read_to_string(&zone_path)
+            .map_err(|e| format!("failed to read {}: {e:?}", zone_path.display()))?;
+        let rpz = Rpz::parse(&zone, Some(zone_path.clone()), config.policy_zone.clone())
+            .map_err(|e| format!("failed to parse {}: {e:?}", zone_path.display()))?;
+
+        info!(
+            "response policy zone loaded: {} with {} policies",
+            config.policy_zone,
+            rpz.len()
+        );
+        Ok(Self::new(origin, rpz))
+    }
+
+    /// The policies applied
+    pub fn rpz(&self) -> &Rpz {
+        &self.rpz
+    }
+
+    /// The answer to the query of `name` forged by the action of `policy`, `Skip` for PASSTHRU
+    fn apply(&self, policy: &RpzPolicy, name: &LowerName) -> LookupControlFlow<AuthLookup> {
+        use LookupControlFlow::*;
+
+        info!(
+            "RPZ {trigger:?} policy matched query {name}: {action:?}",
+            trigger = policy.trigger,
+            action = policy.action,
+        );
+
+        match &policy.action {
+            RpzAction::NxDomain => Break(Err(LookupError::ResponseCode(ResponseCode::NXDomain))),
+            RpzAction::NoData => Break(Ok(AuthLookup::default())),
+            RpzAction::Passthru => Skip,
+            RpzAction::Drop => Break(Err(LookupError::Dropped)),
+            RpzAction::Cname(target) => {
+                let record = Record::from_rdata(
+                    Name::from(name),
+                    policy.ttl,
+                    RData::CNAME(CNAME(target.clone())),
+                );
+                let records =
+                    LookupRecords::new(LookupOptions::default(), Arc::new(RecordSet::from(record)));
+                Break(Ok(AuthLookup::answers(records, None)))
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Authority for RpzAuthority {
+    type Lookup = AuthLookup;
+
+    fn zone_type(&self) -> ZoneType {
+        ZoneType::External
+    }
+
+    fn is_axfr_allowed(&self) -> bool {
+        false
+    }
+
+    async fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
+        Err(ResponseCode::NotImp)
+    }
+
+    fn origin(&self) -> &LowerName {
+        &self.origin
+    }
+
+    /// Applies the QNAME policies, `Skip` if none matches
+    async fn lookup(
+        &self,
+        name: &LowerName,
+        _rtype: RecordType,
+        _lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Self::Lookup> {
+        match self.rpz.check_qname(name) {
+            Some(policy) => self.apply(policy, name),
+            None => LookupControlFlow::Skip,
+        }
+    }
+
+    /// Applies the IP and NSDNAME policies to the answers of another authority, unless the name
+    /// matches a QNAME PASSTHRU policy
+    async fn consult(
+        &self,
+        name: &LowerName,
+        _rtype: RecordType,
+        _lookup_options: LookupOptions,
+        last_result: LookupControlFlow<Box<dyn LookupObject>>,
+    ) -> LookupControlFlow<Box<dyn LookupObject>> {
+        if let Some(RpzPolicy {
+            action: RpzAction::Passthru,
+            ..
+        }) = self.rpz.check_qname(name)
+        {
+            return last_result;
+        }
+
+        let policy = match &last_result {
+            LookupControlFlow::Continue(Ok(lookup)) | LookupControlFlow::Break(Ok(lookup)) => {
+                let records = lookup.iter().collect::<Vec<_>>();
+                self.rpz.check_response(records.iter().copied())
+            }
+            _ => None,
+        };
+
+        match policy {
+            Some(policy) if policy.action != RpzAction::Passthru => {
+                self.apply(policy, name).map_dyn()
+            }
+            _ => {
+                trace!("no RPZ policy matched the answers of {name}");
+                last_result
+            }
+        }
+    }
+
+    /// Applies the client IP policies, then the QNAME policies, `Skip` if none matches
+    async fn search(
+        &self,
+        request_info: RequestInfo<'_>,
+        _lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Self::Lookup> {
+        let name = request_info.query.name();
+        match self.rpz.check_query(request_info.src.ip(), name) {
+            Some(policy) => self.apply(policy, name),
+            None => LookupControlFlow::Skip,
+        }
+    }
+
+    async fn get_nsec_records(
+        &self,
+        _name: &LowerName,
+        _lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Self::Lookup> {
+        LookupControlFlow::Continue(Err(LookupError::from(io::Error::new(
+            io::ErrorKind::Other,
+            "getting NSEC records is unimplemented for the response policy zone",
+        ))))
+    }
+
+    #[cfg(feature = "dnssec-ring")]
+    async fn get_nsec3_records(
+        &self,
+        _info: Nsec3QueryInfo<'_>,
+        _lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Self::Lookup> {
+        LookupControlFlow::Continue(Err(LookupError::from(io::Error::new(
+            io::ErrorKind::Other,
+            "getting NSEC3 records is unimplemented for the response policy zone",
+        ))))
+    }
+
+    #[cfg(feature = "dnssec-ring")]
+    fn nx_proof_kind(&self) -> Option<&NxProofKind> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::authority::Catalog;
+    use crate::proto::op::{Message, Query};
+    use crate::proto::rr::rdata::{A, NS};
+    use crate::server::tests::{send_request, udp_request, zone_catalog};
+
+    fn name(name: &str) -> Name {
+        Name::from_str(name).unwrap()
+    }
+
+    /// Answers all the queries like a forwarder, the addresses of sinkholed.example.org. are in
+    /// 10.0.0.0/16, the domain hosted.example.info. is served by the name servers of bad-ns
+    struct Upstream(LowerName);
+
+    #[async_trait::async_trait]
+    impl Authority for Upstream {
+        type Lookup = AuthLookup;
+
+        fn zone_type(&self) -> ZoneType {
+            ZoneType::External
+        }
+
+        fn is_axfr_allowed(&self) -> bool {
+            false
+        }
+
+        async fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
+            Err(ResponseCode::NotImp)
+        }
+
+        fn origin(&self) -> &LowerName {
+            &self.0
+        }
+
+        async fn lookup(
+            &self,
+            name: &LowerName,
+            rtype: RecordType,
+            _lookup_options: LookupOptions,
+        ) -> LookupControlFlow<Self::Lookup> {
+            let owner = Name::from(name);
+            let rdata = match (rtype, owner.to_ascii().as_str()) {
+                (RecordType::NS, "hosted.example.info.") => {
+                    RData::NS(NS(self::name("ns1.bad-ns.example.org.")))
+                }
+                (RecordType::NS, _) => RData::NS(NS(self::name("ns.example.org."))),
+                (_, "sinkholed.example.org.") => RData::A(A::new(10, 0, 1, 1)),
+                _ => RData::A(A::new(192, 0, 2, 100)),
+            };
+
+            let record = Record::from_rdata(owner, 60, rdata);
+            let records =
+                LookupRecords::new(LookupOptions::default(), Arc::new(RecordSet::from(record)));
+            LookupControlFlow::Continue(Ok(AuthLookup::answers(records, None)))
+        }
+
+        async fn search(
+            &self,
+            request_info: RequestInfo<'_>,
+            lookup_options: LookupOptions,
+        ) -> LookupControlFlow<Self::Lookup> {
+            let query = request_info.query;
+            self.lookup(query.name(), query.query_type(), lookup_options)
+                .await
+        }
+
+        async fn get_nsec_records(
+            &self,
+            _name: &LowerName,
+            _lookup_options: LookupOptions,
+        ) -> LookupControlFlow<Self::Lookup> {
+            LookupControlFlow::Skip
+        }
+
+        #[cfg(feature = "dnssec-ring")]
+        async fn get_nsec3_records(
+            &self,
+            _info: Nsec3QueryInfo<'_>,
+            _lookup_options: LookupOptions,
+        ) -> LookupControlFlow<Self::Lookup> {
+            LookupControlFlow::Skip
+        }
+
+        #[cfg(feature = "dnssec-ring")]
+        fn nx_proof_kind(&self) -> Option<&NxProofKind> {
+            None
+        }
+    }
+
+    /// The response policy zone of the test data, in front of the upstream authority
+    fn catalog() -> Catalog {
+        let config = RpzConfig {
+            policy_zone: name("rpz.local."),
+            zone_file_path: "default/rpz.zone".into(),
+        };
+        let rpz = RpzAuthority::try_from_config(
+            Name::root(),
+            ZoneType::External,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+        )
+        .unwrap();
+        assert_eq!(rpz.rpz().len(), 8);
+
+        zone_catalog(
+            Name::root(),
+            vec![Arc::new(rpz), Arc::new(Upstream(Name::root().into()))],
+        )
+    }
+
+    /// The response to the query of the client, `None` if dropped
+    async fn query(
+        catalog: &Catalog,
+        client: Ipv4Addr,
+        query_name: &str,
+        query_type: RecordType,
+    ) -> Option<Message> {
+        let mut message = Message::new();
+        message.set_recursion_desired(true);
+        message.add_query(Query::query(name(query_name), query_type));
+        let request = udp_request(&message, SocketAddr::from((client, 53)));
+        send_request(catalog, &request).await
+    }
+
+    const CLIENT: Ipv4Addr = Ipv4Addr::new(198, 51, 100, 1);
+
+    fn cname(owner: &str) -> Record {
+        Record::from_rdata(
+            name(owner),
+            300,
+            RData::CNAME(CNAME(name("walled.garden.example."))),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_nxdomain_and_walled_garden() {
+        let catalog = catalog();
+
+        for bad in ["bad.example.com.", "www.bad.example.com."] {
+            let response = query(&catalog, CLIENT, bad, RecordType::A).await.unwrap();
+            assert_eq!(response.response_code(), ResponseCode::NXDomain);
+            assert!(response.answers().is_empty());
+        }
+
+        let response = query(&catalog, CLIENT, "suspicious.example.com.", RecordType::A)
+            .await
+            .unwrap();
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(response.answers(), [cname("suspicious.example.com.")]);
+
+        // the other names are answered upstream
+        let response = query(&catalog, CLIENT, "www.example.com.", RecordType::A)
+            .await
+            .unwrap();
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(
+            response.answers()[0].data(),
+            &RData::A(A::new(192, 0, 2, 100))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_actions_and_triggers() {
+        let catalog = catalog();
+
+        let response = query(&catalog, CLIENT, "tracker.example.com.", RecordType::A)
+            .await
+            .unwrap();
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert!(response.answers().is_empty());
+
+        assert!(query(&catalog, CLIENT, "c2.example.net.", RecordType::A)
+            .await
+            .is_none());
+
+        // the answers of the upstream authority
+        let response = query(&catalog, CLIENT, "sinkholed.example.org.", RecordType::A)
+            .await
+            .unwrap();
+        assert_eq!(response.answers(), [cname("sinkholed.example.org.")]);
+
+        let response = query(&catalog, CLIENT, "hosted.example.info.", RecordType::NS)
+            .await
+            .unwrap();
+        assert_eq!(response.response_code(), ResponseCode::NXDomain);
+
+        // the client IP policies take precedence over the QNAME policies
+        let monitoring = Ipv4Addr::new(192, 0, 2, 10);
+        let response = query(&catalog, monitoring, "bad.example.com.", RecordType::A)
+            .await
+            .unwrap();
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(
+            response.answers()[0].data(),
+            &RData::A(A::new(192, 0, 2, 100))
+        );
+    }
+}
@@ -0,0 +1,32 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#![cfg(feature = "rpz")]
+
+//! Response policy zones, the filtering of the answers of forwarders and recursors
+mod authority;
+mod policy;
+
+pub use self::authority::RpzAuthority;
+pub use self::policy::{Rpz, RpzAction, RpzPolicy, RpzTrigger};
+
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::proto::rr::Name;
+
+/// Configuration of a response policy zone
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RpzConfig {
+    /// The name of the policy zone, the origin of the triggers of its records, e.g. `rpz.local.`
+    pub policy_zone: Name,
+
+    /// The path of the zone file of the policies, relative to the server zone directory
+    pub zone_file_path: PathBuf,
+}
@@ -0,0 +1,514 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::PathBuf,
+};
+
+use ipnet::IpNet;
+use tracing::{trace, warn};
+
+use crate::proto::{
+    rr::{LowerName, Name, RData, Record, RecordType},
+    serialize::txt::{ParseResult, Parser},
+};
+
+/// The label of the client IP triggers, after the address of the client
+const CLIENT_IP: &str = "rpz-client-ip";
+/// The label of the response IP triggers, after an address of the answers
+const IP: &str = "rpz-ip";
+/// The label of the NSDNAME triggers, after the name of a name server
+const NSDNAME: &str = "rpz-nsdname";
+/// The label of the NSIP triggers, not supported
+const NSIP: &str = "rpz-nsip";
+
+/// The kind of trigger matched by a policy, in the order of precedence
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RpzTrigger {
+    /// The address of the client, `<prefix>.<reversed address>.rpz-client-ip`
+    ClientIp,
+    /// The queried name, `<name>` or `*.<name>` for the names under it
+    Qname,
+    /// An address of the A or AAAA records of the answers, `<prefix>.<reversed address>.rpz-ip`
+    Ip,
+    /// The name of a name server of the NS records of the answers, `<name>.rpz-nsdname`
+    NsDname,
+}
+
+/// The action of a policy, encoded in the target of its CNAME record
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RpzAction {
+    /// Answers with NXDOMAIN, `CNAME .`
+    NxDomain,
+    /// Answers NOERROR without records, `CNAME *.`
+    NoData,
+    /// Answers as without the policy zone, the other triggers are ignored, `CNAME rpz-passthru.`
+    Passthru,
+    /// Drops the query without answer, `CNAME rpz-drop.`
+    Drop,
+    /// Answers with a CNAME record to the name, e.g. of a walled garden
+    Cname(Name),
+}
+
+impl RpzAction {
+    fn from_target(target: &Name) -> Option<Self> {
+        // the labels, including the wildcard not in `num_labels`
+        let mut labels = target.iter();
+        match (labels.next(), labels.next()) {
+            (None, _) => return Some(Self::NxDomain),
+            (Some(b"*"), None) => return Some(Self::NoData),
+            (Some(label), None) => {
+                if label.eq_ignore_ascii_case(b"rpz-passthru") {
+                    return Some(Self::Passthru);
+                } else if label.eq_ignore_ascii_case(b"rpz-drop") {
+                    return Some(Self::Drop);
+                } else if label.starts_with(b"rpz-") {
+                    // e.g. rpz-tcp-only
+                    return None;
+                }
+            }
+            _ => (),
+        }
+
+        // the local data of the other records, and the wildcard CNAMEs, are not supported
+        if target.is_wildcard() {
+            return None;
+        }
+
+        Some(Self::Cname(target.clone()))
+    }
+}
+
+/// A policy of the zone, the action applied when its trigger matches
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RpzPolicy {
+    /// The kind of trigger which matched
+    pub trigger: RpzTrigger,
+    /// The action to apply to the query
+    pub action: RpzAction,
+    /// The TTL of the synthesized records
+    pub ttl: u32,
+}
+
+/// The policies triggered by names, exactly or by a wildcard for the names under it
+#[derive(Default)]
+struct NameTriggers {
+    exact: HashMap<LowerName, RpzPolicy>,
+    wildcards: HashMap<LowerName, RpzPolicy>,
+}
+
+impl NameTriggers {
+    fn insert(&mut self, name: Name, policy: RpzPolicy) {
+        if name.is_wildcard() {
+            self.wildcards.insert(name.base_name().into(), policy);
+        } else {
+            self.exact.insert(name.into(), policy);
+        }
+    }
+
+    /// The exact match, otherwise the wildcard of the closest enclosing name
+    fn find(&self, name: &LowerName) -> Option<&RpzPolicy> {
+        if let Some(policy) = self.exact.get(name) {
+            return Some(policy);
+        }
+
+        let mut base = name.clone();
+        while !base.is_root() {
+            base = base.base_name();
+            if let Some(policy) = self.wildcards.get(&base) {
+                return Some(policy);
+            }
+        }
+
+        None
+    }
+
+    fn len(&self) -> usize {
+        self.exact.len() + self.wildcards.len()
+    }
+}
+
+/// The policies triggered by addresses, the longest matching prefix wins
+#[derive(Default)]
+struct IpTriggers(Vec<(IpNet, RpzPolicy)>);
+
+impl IpTriggers {
+    fn find(&self, ip: IpAddr) -> Option<(u8, &RpzPolicy)> {
+        self.0
+            .iter()
+            .filter(|(net, _)| net.contains(&ip))
+            .max_by_key(|(net, _)| net.prefix_len())
+            .map(|(net, policy)| (net.prefix_len(), policy))
+    }
+}
+
+/// A response policy zone, [draft-vixie-dnsop-dns-rpz](https://datatracker.ietf.org/doc/draft-vixie-dnsop-dns-rpz/)
+///
+/// The policies are the CNAME records of the zone, the owner names, relative to the origin of
+/// the zone, are the triggers and the targets are the actions. The triggers are checked in the
+/// order of precedence of [`RpzTrigger`], the client IP before the queried name, before the
+/// addresses and then the name servers of the answers. Within the triggers of a kind, an exact
+/// name wins over wildcards, and the longest prefix wins for the addresses.
+///
+/// The NSIP triggers, the local data records and the wildcard CNAME targets are not supported,
+/// such policies are ignored when the zone is loaded.
+#[derive(Default)]
+pub struct Rpz {
+    client_ips: IpTriggers,
+    qnames: NameTriggers,
+    ips: IpTriggers,
+    nsdnames: NameTriggers,
+}
+
+impl Rpz {
+    /// Parses the policies of the zone file `zone`, with `origin` as the name of the zone
+    pub fn parse(zone: &str, path: Option<PathBuf>, origin: Name) -> ParseResult<Self> {
+        let (origin, records) = Parser::new(zone, path, Some(origin)).parse()?;
+
+        let mut rpz = Self::default();
+        for record in records
+            .values()
+            .flat_map(|rrset| rrset.records_without_rrsigs())
+        {
+            rpz.insert(&origin, record);
+        }
+
+        Ok(rpz)
+    }
+
+    fn insert(&mut self, origin: &Name, record: &Record) {
+        let owner = record.name();
+        let target = match record.data() {
+            RData::CNAME(cname) => &cname.0,
+            _ if matches!(record.record_type(), RecordType::SOA | RecordType::NS) => return,
+            _ => {
+                warn!(
+                    "ignoring RPZ local data, not supported: {owner} {}",
+                    record.record_type()
+                );
+                return;
+            }
+        };
+
+        let Some(action) = RpzAction::from_target(target) else {
+            warn!("ignoring RPZ policy with an unsupported action: {owner} CNAME {target}");
+            return;
+        };
+
+        // the trigger, relative to the origin of the zone
+        let labels = owner.iter().len().saturating_sub(origin.iter().len());
+        let mut trigger = owner.iter().take(labels).collect::<Vec<_>>();
+        if !origin.zone_of(owner) || trigger.is_empty() {
+            warn!("ignoring RPZ policy outside of the zone {origin}: {owner}");
+            return;
+        }
+
+        let policy = |trigger| RpzPolicy {
+            trigger,
+            action: action.clone(),
+            ttl: record.ttl(),
+        };
+
+        let kind = trigger[trigger.len() - 1].to_ascii_lowercase();
+        match &kind[..] {
+            k if k == CLIENT_IP.as_bytes() || k == IP.as_bytes() => {
+                trigger.pop();
+                let Some(net) = parse_ip_trigger(&trigger) else {
+                    warn!("ignoring RPZ policy with an invalid address: {owner}");
+                    return;
+                };
+
+                match k == CLIENT_IP.as_bytes() {
+                    true => self.client_ips.0.push((net, policy(RpzTrigger::ClientIp))),
+                    false => self.ips.0.push((net, policy(RpzTrigger::Ip))),
+                }
+            }
+            k if k == NSDNAME.as_bytes() => {
+                trigger.pop();
+                match Name::from_labels(trigger) {
+                    Ok(name) => self.nsdnames.insert(name, policy(RpzTrigger::NsDname)),
+                    Err(e) => warn!("ignoring RPZ policy with an invalid name: {owner}: {e}"),
+                }
+            }
+            k if k == NSIP.as_bytes() => {
+                warn!("ignoring RPZ NSIP policy, not supported: {owner}");
+            }
+            _ => match Name::from_labels(trigger) {
+                Ok(name) => self.qnames.insert(name, policy(RpzTrigger::Qname)),
+                Err(e) => warn!("ignoring RPZ policy with an invalid name: {owner}: {e}"),
+            },
+        }
+    }
+
+    /// The number of policies of the zone
+    pub fn len(&self) -> usize {
+        self.client_ips.0.len() + self.qnames.len() + self.ips.0.len() + self.nsdnames.len()
+    }
+
+    /// Returns `true` if the zone has no policies
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The policy of the query of `qname` by the client at `client`, the client IP triggers take
+    /// precedence over the QNAME triggers
+    pub fn check_query(&self, client: IpAddr, qname: &LowerName) -> Option<&RpzPolicy> {
+        if let Some((_, policy)) = self.client_ips.find(client) {
+            return Some(policy);
+        }
+
+        self.check_qname(qname)
+    }
+
+    /// The policy of the queries of `qname`, by the QNAME triggers
+    pub fn check_qname(&self, qname: &LowerName) -> Option<&RpzPolicy> {
+        let policy = self.qnames.find(qname);
+        trace!("RPZ QNAME {qname}: {policy:?}");
+        policy
+    }
+
+    /// The policy of the answers, by the addresses of the A and AAAA records, then by the names
+    /// of the name servers of the NS records
+    pub fn check_response<'a>(
+        &self,
+        records: impl Iterator<Item = &'a Record> + Clone,
+    ) -> Option<&RpzPolicy> {
+        let ip = records
+            .clone()
+            .filter_map(|record| match record.data() {
+                RData::A(a) => Some(IpAddr::V4(a.0)),
+                RData::AAAA(aaaa) => Some(IpAddr::V6(aaaa.0)),
+                _ => None,
+            })
+            .filter_map(|ip| self.ips.find(ip))
+            .max_by_key(|(prefix_len, _)| *prefix_len);
+        if let Some((_, policy)) = ip {
+            return Some(policy);
+        }
+
+        records
+            .filter_map(|record| record.data().as_ns())
+            .find_map(|ns| self.nsdnames.find(&LowerName::from(&ns.0)))
+    }
+}
+
+/// Parses the labels of an address trigger, the prefix length then the reversed address, with
+/// `zz` for the longest run of zeros of IPv6 addresses
+fn parse_ip_trigger(labels: &[&[u8]]) -> Option<IpNet> {
+    let (prefix_len, address) = labels.split_first()?;
+    let prefix_len = std::str::from_utf8(prefix_len).ok()?.parse::<u8>().ok()?;
+    let address = address
+        .iter()
+        .rev()
+        .map(|label| std::str::from_utf8(label).ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    let ip = if address.len() == 4 && !address.contains(&"zz") {
+        let mut octets = [0; 4];
+        for (octet, label) in octets.iter_mut().zip(&address) {
+            *octet = label.parse().ok()?;
+        }
+        IpAddr::V4(Ipv4Addr::from(octets))
+    } else {
+        let mut words = Vec::with_capacity(8);
+        for label in &address {
+            if label.eq_ignore_ascii_case("zz") {
+                let zeros = 9_usize.checked_sub(address.len())?;
+                words.extend(std::iter::repeat(0).take(zeros));
+            } else {
+                words.push(u16::from_str_radix(label, 16).ok()?);
+            }
+        }
+
+        let words = <[u16; 8]>::try_from(words).ok()?;
+        IpAddr::V6(Ipv6Addr::from(words))
+    };
+
+    // the address must be the network of the prefix
+    let net = IpNet::new(ip, prefix_len).ok()?;
+    (net.network() == ip).then_some(net)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::proto::rr::rdata::{A, AAAA, NS};
+
+    const ZONE: &str = "
+$TTL 300
+@                                 SOA  localhost. root.localhost. 1 3600 600 86400 60
+                                  NS   localhost.
+bad.example.com                   CNAME .
+*.bad.example.com                 CNAME .
+empty.example.com                 CNAME *.
+garden.example.com                CNAME walled.garden.example.
+ok.garden.example.com             CNAME rpz-passthru.
+drop.example.com                  CNAME rpz-drop.
+tcp.example.com                   CNAME rpz-tcp-only.
+local.example.com                 A    192.0.2.1
+24.0.2.0.192.rpz-client-ip        CNAME rpz-passthru.
+32.1.2.0.192.rpz-client-ip        CNAME rpz-drop.
+16.0.0.0.10.rpz-ip                CNAME .
+32.1.0.0.10.rpz-ip                CNAME walled.garden.example.
+48.zz.db8.2001.rpz-ip             CNAME *.
+ns.bad.example.net.rpz-nsdname    CNAME .
+*.evil.example.net.rpz-nsdname    CNAME walled.garden.example.
+32.1.2.0.192.rpz-nsip             CNAME .
+";
+
+    fn name(name: &str) -> LowerName {
+        LowerName::from_str(name).unwrap()
+    }
+
+    fn rpz() -> Rpz {
+        Rpz::parse(ZONE, None, Name::from_str("rpz.example.").unwrap()).unwrap()
+    }
+
+    fn action(policy: Option<&RpzPolicy>) -> Option<(RpzTrigger, RpzAction)> {
+        policy.map(|policy| (policy.trigger, policy.action.clone()))
+    }
+
+    #[test]
+    fn test_parse() {
+        let rpz = rpz();
+        // the local data, the unsupported actions and the NSIP trigger are ignored
+        assert_eq!(rpz.len(), 13);
+        assert_eq!(rpz.check_qname(&name("local.example.com.")), None);
+        assert_eq!(rpz.check_qname(&name("tcp.example.com.")), None);
+
+        let policy = rpz.check_qname(&name("bad.example.com.")).unwrap();
+        assert_eq!(policy.ttl, 300);
+        assert_eq!(policy.action, RpzAction::NxDomain);
+    }
+
+    #[test]
+    fn test_qname() {
+        use RpzAction::*;
+        let rpz = rpz();
+        let check = |qname| action(rpz.check_qname(&name(qname)));
+        let qname = |action| Some((RpzTrigger::Qname, action));
+
+        assert_eq!(check("bad.example.com."), qname(NxDomain));
+        assert_eq!(check("www.bad.example.com."), qname(NxDomain));
+        assert_eq!(check("a.b.BAD.example.com."), qname(NxDomain));
+        assert_eq!(check("empty.example.com."), qname(NoData));
+        assert_eq!(check("drop.example.com."), qname(Drop));
+        assert_eq!(
+            check("garden.example.com."),
+            qname(Cname(Name::from_str("walled.garden.example.").unwrap()))
+        );
+
+        // the exact names win over the wildcards, and without wildcard the names under aren't
+        // matched
+        assert_eq!(check("ok.garden.example.com."), qname(Passthru));
+        assert_eq!(check("www.garden.example.com."), None);
+        assert_eq!(check("example.com."), None);
+    }
+
+    #[test]
+    fn test_client_ip_precedence() {
+        let rpz = rpz();
+        let check =
+            |client: [u8; 4], qname| action(rpz.check_query(IpAddr::from(client), &name(qname)));
+
+        assert_eq!(
+            check([198, 51, 100, 1], "bad.example.com."),
+            Some((RpzTrigger::Qname, RpzAction::NxDomain))
+        );
+        // the client IP triggers take precedence, the longest prefix first
+        assert_eq!(
+            check([192, 0, 2, 2], "bad.example.com."),
+            Some((RpzTrigger::ClientIp, RpzAction::Passthru))
+        );
+        assert_eq!(
+            check([192, 0, 2, 1], "www.example.com."),
+            Some((RpzTrigger::ClientIp, RpzAction::Drop))
+        );
+    }
+
+    #[test]
+    fn test_response() {
+        let rpz = rpz();
+        let a = |ip: [u8; 4]| {
+            Record::from_rdata(
+                Name::from_str("www.example.org.").unwrap(),
+                300,
+                RData::A(A::from(Ipv4Addr::from(ip))),
+            )
+        };
+        let check = |records: &[Record]| action(rpz.check_response(records.iter()));
+
+        assert_eq!(check(&[a([192, 0, 2, 1])]), None);
+        assert_eq!(
+            check(&[a([192, 0, 2, 1]), a([10, 0, 200, 1])]),
+            Some((RpzTrigger::Ip, RpzAction::NxDomain))
+        );
+        // the longest prefix of all the addresses wins
+        assert_eq!(
+            check(&[a([10, 0, 200, 1]), a([10, 0, 0, 1])]),
+            Some((
+                RpzTrigger::Ip,
+                RpzAction::Cname(Name::from_str("walled.garden.example.").unwrap())
+            ))
+        );
+
+        let aaaa = Record::from_rdata(
+            Name::from_str("www.example.org.").unwrap(),
+            300,
+            RData::AAAA(AAAA::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, 1)),
+        );
+        assert_eq!(check(&[aaaa]), Some((RpzTrigger::Ip, RpzAction::NoData)));
+
+        let ns = |ns: &str| {
+            Record::from_rdata(
+                Name::from_str("example.org.").unwrap(),
+                300,
+                RData::NS(NS(Name::from_str(ns).unwrap())),
+            )
+        };
+        assert_eq!(check(&[ns("ns.example.org.")]), None);
+        assert_eq!(
+            check(&[ns("ns.example.org."), ns("ns.bad.example.net.")]),
+            Some((RpzTrigger::NsDname, RpzAction::NxDomain))
+        );
+        assert_eq!(
+            check(&[ns("ns1.evil.example.net.")]).map(|(trigger, _)| trigger),
+            Some(RpzTrigger::NsDname)
+        );
+    }
+
+    #[test]
+    fn test_parse_ip_trigger() {
+        let parse = |trigger: &str| {
+            let labels = trigger.split('.').map(str::as_bytes).collect::<Vec<_>>();
+            parse_ip_trigger(&labels)
+        };
+
+        assert_eq!(parse("32.1.2.0.192"), "192.0.2.1/32".parse().ok());
+        assert_eq!(parse("8.0.0.0.10"), "10.0.0.0/8".parse().ok());
+        assert_eq!(parse("128.1.zz.db8.2001"), "2001:db8::1/128".parse().ok());
+        assert_eq!(
+            parse("128.1.0.0.0.0.0.db8.2001"),
+            "2001:db8::1/128".parse().ok()
+        );
+        assert_eq!(
+            parse("64.zz.1.0.db8.2001"),
+            "2001:db8:0:1::/64".parse().ok()
+        );
+
+        // not the network of the prefix, or not an address
+        assert_eq!(parse("8.1.0.0.10"), None);
+        assert_eq!(parse("33.1.2.0.192"), None);
+        assert_eq!(parse("32.1.2.0"), None);
+        assert_eq!(parse("32.1.2.0.256"), None);
+        assert_eq!(parse("128.zz.zz.2001"), None);
+    }
+}
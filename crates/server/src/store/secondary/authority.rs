@@ -0,0 +1,512 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Secondary authority, refreshed from its primaries
+
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+#[cfg(feature = "dnssec-ring")]
+use crate::{authority::Nsec3QueryInfo, dnssec::NxProofKind};
+use crate::{
+    authority::{
+        Authority, LookupControlFlow, LookupError, LookupOptions, MessageRequest, UpdateResult,
+        ZoneRefresh, ZoneType,
+    },
+    proto::{
+        op::ResponseCode,
+        rr::{LowerName, RData, RecordType},
+        ProtoError,
+    },
+    server::RequestInfo,
+    store::{in_memory::InMemoryAuthority, secondary::transfer},
+};
+
+/// The interval between the attempts to transfer the zone, before the first transfer
+const INITIAL_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+/// The shortest interval between the refreshes, whatever the timers of the SOA
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A secondary zone, transferred from its primaries
+///
+/// The zone is transferred once created and then refreshed on the timers of its SOA, RFC 1034
+/// section 4.3.5: the serial of the primaries is polled every refresh interval, or every retry
+/// interval after a failure, and the zone is transferred with IXFR if the primary has a newer
+/// version, falling back to AXFR. A NOTIFY of the primaries also triggers a refresh, see
+/// [`Self::zone_refresh`].
+///
+/// Until the first transfer, and once no primary could be reached for the expire interval of the
+/// SOA, the zone is not served and the queries are answered with SERVFAIL.
+pub struct SecondaryAuthority {
+    in_memory: Arc<InMemoryAuthority>,
+    primaries: Arc<[SocketAddr]>,
+    status: Arc<Status>,
+    refresh: ZoneRefresh,
+}
+
+impl SecondaryAuthority {
+    /// Serves `authority`, an empty zone, with the transfers from `primaries`, tried in order
+    ///
+    /// The refreshes run in a task spawned on the current Tokio runtime, until the authority and
+    /// all its [`ZoneRefresh`] handles are dropped.
+    pub fn new(authority: InMemoryAuthority, primaries: Vec<SocketAddr>) -> Self {
+        let in_memory = Arc::new(authority);
+        let primaries = Arc::<[SocketAddr]>::from(primaries);
+        let status = Arc::new(Status::default());
+        let (sender, requests) = mpsc::channel(1);
+
+        tokio::spawn(run(
+            in_memory.clone(),
+            primaries.clone(),
+            status.clone(),
+            requests,
+        ));
+
+        Self {
+            in_memory,
+            primaries: primaries.clone(),
+            status,
+            refresh: ZoneRefresh::new(primaries, sender),
+        }
+    }
+
+    /// The handle scheduling a refresh, register it in the
+    /// [`Catalog`](crate::authority::Catalog) to accept the NOTIFY of the primaries
+    pub fn zone_refresh(&self) -> ZoneRefresh {
+        self.refresh.clone()
+    }
+
+    /// The addresses of the primaries of the zone
+    pub fn primaries(&self) -> &[SocketAddr] {
+        &self.primaries
+    }
+
+    /// Returns true if the zone is not served, not transferred yet or expired
+    pub fn is_expired(&self) -> bool {
+        self.status.expired.load(Ordering::Relaxed)
+    }
+
+    /// Transfers the zone from the first primary answering, if its version is newer
+    ///
+    /// Returns true if the zone was updated.
+    pub async fn refresh(&self) -> Result<bool, ProtoError> {
+        refresh(&self.in_memory, &self.primaries, &self.status).await
+    }
+
+    /// The zone, as last transferred
+    pub fn in_memory(&self) -> &InMemoryAuthority {
+        &self.in_memory
+    }
+
+    fn check_expired<T>(&self) -> Option<LookupControlFlow<T>> {
+        if !self.is_expired() {
+            return None;
+        }
+
+        debug!("{} is expired", self.origin());
+        Some(LookupControlFlow::Break(Err(LookupError::from(
+            ResponseCode::ServFail,
+        ))))
+    }
+}
+
+/// The state of the zone, shared with the refresh task
+struct Status {
+    expired: AtomicBool,
+    /// The last time a primary answered
+    last_contact: Mutex<Option<Instant>>,
+}
+
+impl Default for Status {
+    fn default() -> Self {
+        Self {
+            expired: AtomicBool::new(true),
+            last_contact: Mutex::new(None),
+        }
+    }
+}
+
+async fn refresh(
+    authority: &InMemoryAuthority,
+    primaries: &[SocketAddr],
+    status: &Status,
+) -> Result<bool, ProtoError> {
+    let refreshed = transfer::refresh(authority, primaries).await?;
+
+    *status.last_contact.lock().expect("poisoned") = Some(Instant::now());
+    if status.expired.swap(false, Ordering::Relaxed) {
+        info!(
+            "serving {}, serial {}",
+            authority.origin(),
+            authority.serial().await
+        );
+    }
+
+    Ok(refreshed)
+}
+
+/// Refreshes the zone on the timers of its SOA, and on each refresh scheduled
+async fn run(
+    authority: Arc<InMemoryAuthority>,
+    primaries: Arc<[SocketAddr]>,
+    status: Arc<Status>,
+    mut requests: mpsc::Receiver<()>,
+) {
+    loop {
+        let result = refresh(&authority, &primaries, &status).await;
+        if let Err(e) = &result {
+            warn!("failed to refresh {}: {e}", authority.origin());
+        }
+
+        let wait = match timers(&authority).await {
+            Some(timers) if result.is_ok() => timers.refresh,
+            Some(timers) => {
+                let last_contact = *status.last_contact.lock().expect("poisoned");
+                let since_contact = last_contact.map_or(timers.expire, |last| last.elapsed());
+                match timers.expire.checked_sub(since_contact) {
+                    Some(remaining) if !remaining.is_zero() => timers.retry.min(remaining),
+                    _ => {
+                        if !status.expired.swap(true, Ordering::Relaxed) {
+                            warn!("{} expired, no primary answered", authority.origin());
+                        }
+                        timers.retry
+                    }
+                }
+            }
+            None => INITIAL_RETRY_INTERVAL,
+        };
+
+        debug!("next refresh of {} in {wait:?}", authority.origin());
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => (),
+            request = requests.recv() => if request.is_none() {
+                debug!("stopping the refreshes of {}", authority.origin());
+                return;
+            },
+        }
+    }
+}
+
+/// The refresh, retry and expire intervals of the SOA of the zone
+struct Timers {
+    refresh: Duration,
+    retry: Duration,
+    expire: Duration,
+}
+
+async fn timers(authority: &InMemoryAuthority) -> Option<Timers> {
+    let lookup = authority.soa().await.map_result()?.ok()?;
+    let timers = lookup.iter().find_map(|record| match record.data() {
+        RData::SOA(soa) => Some(Timers {
+            refresh: interval(soa.refresh()),
+            retry: interval(soa.retry()),
+            expire: interval(soa.expire()),
+        }),
+        _ => None,
+    });
+    timers
+}
+
+fn interval(seconds: i32) -> Duration {
+    let seconds = u64::try_from(seconds).unwrap_or_default();
+    Duration::from_secs(seconds).max(MIN_REFRESH_INTERVAL)
+}
+
+#[async_trait::async_trait]
+impl Authority for SecondaryAuthority {
+    type Lookup = <InMemoryAuthority as Authority>::Lookup;
+
+    /// What type is this zone
+    fn zone_type(&self) -> ZoneType {
+        self.in_memory.zone_type()
+    }
+
+    /// Return true if AXFR is allowed
+    fn is_axfr_allowed(&self) -> bool {
+        self.in_memory.is_axfr_allowed()
+    }
+
+    /// The secondary zones are only updated by their primaries
+    async fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
+        Err(ResponseCode::NotImp)
+    }
+
+    /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
+    fn origin(&self) -> &LowerName {
+        self.in_memory.origin()
+    }
+
+    async fn lookup(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Self::Lookup> {
+        if let Some(expired) = self.check_expired() {
+            return expired;
+        }
+
+        self.in_memory.lookup(name, rtype, lookup_options).await
+    }
+
+    async fn search(
+        &self,
+        request_info: RequestInfo<'_>,
+        lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Self::Lookup> {
+        if let Some(expired) = self.check_expired() {
+            return expired;
+        }
+
+        self.in_memory.search(request_info, lookup_options).await
+    }
+
+    async fn ixfr(
+        &self,
+        request_info: RequestInfo<'_>,
+        serial: u32,
+        lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Self::Lookup> {
+        if let Some(expired) = self.check_expired() {
+            return expired;
+        }
+
+        self.in_memory
+            .ixfr(request_info, serial, lookup_options)
+            .await
+    }
+
+    async fn ns(&self, lookup_options: LookupOptions) -> LookupControlFlow<Self::Lookup> {
+        if let Some(expired) = self.check_expired() {
+            return expired;
+        }
+
+        self.in_memory.ns(lookup_options).await
+    }
+
+    async fn get_nsec_records(
+        &self,
+        name: &LowerName,
+        lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Self::Lookup> {
+        if let Some(expired) = self.check_expired() {
+            return expired;
+        }
+
+        self.in_memory.get_nsec_records(name, lookup_options).await
+    }
+
+    #[cfg(feature = "dnssec-ring")]
+    async fn get_nsec3_records(
+        &self,
+        info: Nsec3QueryInfo<'_>,
+        lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Self::Lookup> {
+        if let Some(expired) = self.check_expired() {
+            return expired;
+        }
+
+        self.in_memory.get_nsec3_records(info, lookup_options).await
+    }
+
+    async fn soa(&self) -> LookupControlFlow<Self::Lookup> {
+        if let Some(expired) = self.check_expired() {
+            return expired;
+        }
+
+        self.in_memory.soa().await
+    }
+
+    async fn soa_secure(&self, lookup_options: LookupOptions) -> LookupControlFlow<Self::Lookup> {
+        if let Some(expired) = self.check_expired() {
+            return expired;
+        }
+
+        self.in_memory.soa_secure(lookup_options).await
+    }
+
+    #[cfg(feature = "dnssec-ring")]
+    fn nx_proof_kind(&self) -> Option<&NxProofKind> {
+        self.in_memory.nx_proof_kind()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::authority::Catalog;
+    use crate::proto::rr::rdata::{A, SOA};
+    use crate::proto::rr::{Name, Record};
+    use crate::ServerFuture;
+
+    fn name(name: &str) -> Name {
+        Name::from_str(name).unwrap()
+    }
+
+    /// The SOA of example.com., refreshed every second and expiring after 2 seconds
+    fn soa(serial: u32) -> Record {
+        let soa = SOA::new(
+            name("ns.example.com."),
+            name("invalid."),
+            serial,
+            1,
+            1,
+            2,
+            0,
+        );
+        Record::from_rdata(name("example.com."), 3600, RData::SOA(soa))
+    }
+
+    fn www(a: A) -> Record {
+        Record::from_rdata(name("www.example.com."), 3600, RData::A(a))
+    }
+
+    fn empty(zone_type: ZoneType) -> InMemoryAuthority {
+        InMemoryAuthority::empty(
+            name("example.com."),
+            zone_type,
+            true,
+            #[cfg(feature = "dnssec-ring")]
+            None,
+        )
+    }
+
+    /// Serves the primary zone over TCP, with the changes of each version for IXFR
+    async fn primary(
+        addresses: &[A],
+    ) -> (Arc<InMemoryAuthority>, SocketAddr, ServerFuture<Catalog>) {
+        let mut authority = empty(ZoneType::Primary);
+        authority.upsert_mut(soa(1), 1);
+        for a in addresses {
+            authority.upsert_mut(www(*a), 1);
+        }
+        authority.enable_ixfr_journal(8);
+
+        let authority = Arc::new(authority);
+        let mut catalog = Catalog::new();
+        catalog.upsert(authority.origin().clone(), vec![authority.clone()]);
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut server = ServerFuture::new(catalog);
+        server.register_listener(listener, Duration::from_secs(5));
+        (authority, addr, server)
+    }
+
+    async fn addresses(secondary: &SecondaryAuthority) -> Result<Vec<A>, LookupError> {
+        let www = LowerName::from(name("www.example.com."));
+        let lookup = secondary
+            .lookup(&www, RecordType::A, LookupOptions::default())
+            .await
+            .map_result()
+            .unwrap()?;
+
+        let mut addresses = lookup
+            .iter()
+            .filter_map(|record| record.data().as_a().copied())
+            .collect::<Vec<_>>();
+        addresses.sort_by_key(|a| a.0);
+        Ok(addresses)
+    }
+
+    /// Waits for the secondary to reach the serial
+    async fn wait_for_serial(secondary: &SecondaryAuthority, serial: u32) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while secondary.is_expired() || secondary.in_memory().serial().await != serial {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .expect("zone not refreshed");
+    }
+
+    #[tokio::test]
+    async fn test_refresh() {
+        let (primary, addr, _server) = primary(&[A::new(192, 0, 2, 1)]).await;
+        let secondary = SecondaryAuthority::new(empty(ZoneType::Secondary), vec![addr]);
+
+        // the zone is transferred once created
+        wait_for_serial(&secondary, 1).await;
+        assert_eq!(addresses(&secondary).await.unwrap(), [A::new(192, 0, 2, 1)]);
+
+        // and refreshed with IXFR on the refresh timer
+        primary.upsert(www(A::new(192, 0, 2, 2)), 2).await;
+        primary.upsert(soa(2), 2).await;
+        wait_for_serial(&secondary, 2).await;
+        assert_eq!(
+            addresses(&secondary).await.unwrap(),
+            [A::new(192, 0, 2, 1), A::new(192, 0, 2, 2)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refresh_up_to_date() {
+        let (_primary, addr, _server) = primary(&[A::new(192, 0, 2, 1)]).await;
+        let secondary = SecondaryAuthority::new(empty(ZoneType::Secondary), vec![addr]);
+        wait_for_serial(&secondary, 1).await;
+
+        // the serial did not change, the zone is not transferred again
+        assert!(!secondary.refresh().await.unwrap());
+        assert_eq!(secondary.in_memory().serial().await, 1);
+        assert!(!secondary.is_expired());
+    }
+
+    #[tokio::test]
+    async fn test_expire() {
+        let (_primary, addr, mut server) = primary(&[A::new(192, 0, 2, 1)]).await;
+        let secondary = SecondaryAuthority::new(empty(ZoneType::Secondary), vec![addr]);
+        wait_for_serial(&secondary, 1).await;
+
+        // the zone is served until the expire timer of the SOA, once the primary is gone
+        server.shutdown_gracefully().await.unwrap();
+        assert!(addresses(&secondary).await.is_ok());
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !secondary.is_expired() {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .expect("zone not expired");
+
+        let error = addresses(&secondary).await.unwrap_err();
+        assert!(matches!(
+            error,
+            LookupError::ResponseCode(ResponseCode::ServFail)
+        ));
+        assert!(secondary.refresh().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_not_transferred() {
+        // nothing listens on the address of the primary
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let secondary = SecondaryAuthority::new(empty(ZoneType::Secondary), vec![addr]);
+        assert!(secondary.refresh().await.is_err());
+        assert!(secondary.is_expired());
+        assert!(matches!(
+            addresses(&secondary).await,
+            Err(LookupError::ResponseCode(ResponseCode::ServFail))
+        ));
+    }
+}
@@ -0,0 +1,24 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Secondary zones, transferred from their primaries and kept up to date on the SOA timers
+
+use std::net::SocketAddr;
+
+use serde::Deserialize;
+
+mod authority;
+pub(crate) mod transfer;
+pub use authority::SecondaryAuthority;
+
+/// Configuration for secondary zones
+#[derive(Deserialize, PartialEq, Eq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SecondaryConfig {
+    /// addresses of the primaries the zone is transferred from, tried in order
+    pub primaries: Vec<SocketAddr>,
+}
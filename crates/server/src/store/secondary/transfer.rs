@@ -0,0 +1,411 @@
+// Copyright 2015-2024 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Transfers of a secondary zone from its primaries, with IXFR or AXFR over TCP
+
+use std::{net::SocketAddr, time::Duration};
+
+use futures_util::StreamExt;
+use tracing::{debug, info};
+
+use crate::{
+    authority::Authority,
+    proto::{
+        op::{Edns, Message, MessageType, OpCode, Query, ResponseCode},
+        rr::{Name, RData, Record, RecordType},
+        runtime::{TokioRuntimeProvider, TokioTime},
+        tcp::TcpClientStream,
+        xfer::{DnsExchange, DnsHandle, DnsMultiplexer, DnsRequest, DnsRequestOptions},
+        ProtoError, ProtoErrorKind,
+    },
+    store::in_memory::InMemoryAuthority,
+};
+
+/// The timeout of the queries to the primaries, SOA and zone transfers
+const TRANSFER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Transfers the zone from the first primary answering, if its version is newer
+///
+/// Returns true if the zone was updated.
+pub(crate) async fn refresh(
+    authority: &InMemoryAuthority,
+    primaries: &[SocketAddr],
+) -> Result<bool, ProtoError> {
+    let mut error = ProtoError::from("no primary configured");
+    for primary in primaries {
+        match refresh_from(authority, *primary).await {
+            Ok(refreshed) => return Ok(refreshed),
+            Err(e) => {
+                debug!(
+                    "failed to refresh {} from {primary}: {e}",
+                    authority.origin()
+                );
+                error = e;
+            }
+        }
+    }
+
+    Err(error)
+}
+
+async fn refresh_from(
+    authority: &InMemoryAuthority,
+    primary: SocketAddr,
+) -> Result<bool, ProtoError> {
+    let zone = Name::from(authority.origin());
+    let (stream, handle) = TcpClientStream::new(
+        primary,
+        None,
+        Some(TRANSFER_TIMEOUT),
+        TokioRuntimeProvider::new(),
+    );
+    let multiplexer = DnsMultiplexer::with_timeout(stream, handle, TRANSFER_TIMEOUT, None);
+    let (exchange, background) = DnsExchange::connect::<_, _, TokioTime>(multiplexer).await?;
+    tokio::spawn(background);
+
+    let mut options = DnsRequestOptions::default();
+    options.recursion_desired = false;
+    options.edns_set_dnssec_ok = true;
+
+    let response = exchange
+        .lookup(Query::query(zone.clone(), RecordType::SOA), options)
+        .next()
+        .await
+        .ok_or(ProtoErrorKind::Timeout)??;
+    let Some(serial) = response.answers().iter().find_map(soa_serial) else {
+        return Err(ProtoError::from(format!("no SOA for {zone} on {primary}")));
+    };
+
+    let current = current_soa(authority).await;
+    let current_serial = current.as_ref().and_then(soa_serial);
+    if let Some(current_serial) = current_serial {
+        if !is_newer(serial, current_serial) {
+            debug!("{zone} is up to date with {primary}, serial {current_serial}");
+            return Ok(false);
+        }
+    }
+
+    // the incremental transfer is only possible from a version of the zone
+    let request = transfer_request(&zone, current.clone(), options);
+    let transfer = match transfer(&exchange, request, current_serial).await {
+        Ok(transfer) => transfer,
+        Err(e) if current.is_some() => {
+            debug!("IXFR of {zone} from {primary} failed, falling back to AXFR: {e}");
+            let request = transfer_request(&zone, None, options);
+            transfer(&exchange, request, None).await?
+        }
+        Err(e) => return Err(e),
+    };
+
+    match transfer {
+        Transfer::UpToDate => {
+            debug!("{zone} is up to date with {primary}");
+            return Ok(false);
+        }
+        Transfer::Full(records) => {
+            info!("transferred {zone} from {primary}, serial {serial}");
+            authority.replace_records(records).await;
+        }
+        Transfer::Incremental(changes) => {
+            info!(
+                "transferred {} changes of {zone} from {primary}, serial {serial}",
+                changes.len()
+            );
+            for change in changes {
+                authority
+                    .apply_changes(change.deleted, change.added, change.soa)
+                    .await;
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+/// The AXFR request, or the IXFR request with the SOA of the current version in the authority
+/// section, RFC 1995 section 3
+fn transfer_request(
+    zone: &Name,
+    current: Option<Record>,
+    options: DnsRequestOptions,
+) -> DnsRequest {
+    let query_type = match current {
+        Some(_) => RecordType::IXFR,
+        None => RecordType::AXFR,
+    };
+
+    let mut message = Message::new();
+    message
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .add_query(Query::query(zone.clone(), query_type));
+    if let Some(current) = current {
+        message.add_name_server(current);
+    }
+
+    // the RRSIGs of the signed zones are transferred
+    message
+        .extensions_mut()
+        .get_or_insert_with(Edns::new)
+        .set_max_payload(u16::MAX)
+        .set_dnssec_ok(true);
+
+    DnsRequest::new(message, options)
+}
+
+/// Receives the responses of a zone transfer until the last SOA
+async fn transfer(
+    exchange: &DnsExchange,
+    request: DnsRequest,
+    current: Option<u32>,
+) -> Result<Transfer, ProtoError> {
+    let mut responses = exchange.send(request);
+    let mut records = Vec::new();
+    while let Some(response) = responses.next().await {
+        let response = response?;
+        if response.response_code() != ResponseCode::NoError {
+            return Err(ProtoError::from(format!(
+                "zone transfer refused: {}",
+                response.response_code()
+            )));
+        }
+
+        records.extend(response.answers().iter().cloned());
+        if let Some(transfer) = parse_transfer(&records, current)? {
+            return Ok(transfer);
+        }
+    }
+
+    Err(ProtoError::from("incomplete zone transfer"))
+}
+
+/// The outcome of a zone transfer
+#[derive(Debug, PartialEq)]
+enum Transfer {
+    /// The zone is already at the version of the primary
+    UpToDate,
+    /// The records of the zone, starting with its SOA
+    Full(Vec<Record>),
+    /// The changes since the version of the zone, in order
+    Incremental(Vec<Changes>),
+}
+
+/// The changes between two versions of a zone
+#[derive(Debug, PartialEq)]
+struct Changes {
+    deleted: Vec<Record>,
+    added: Vec<Record>,
+    /// The SOA of the new version
+    soa: Record,
+}
+
+/// Parses the answers of an AXFR or IXFR response, `None` until the last SOA is received
+///
+/// The zone is between the first and the last SOA, RFC 5936 section 2.2. The changes of an
+/// incremental transfer are the sequences of the old SOA, the deleted records, the new SOA and
+/// the added records, between the SOA of the new version of the zone, RFC 1995 section 4.
+fn parse_transfer(
+    records: &[Record],
+    current: Option<u32>,
+) -> Result<Option<Transfer>, ProtoError> {
+    let Some(first) = records.first() else {
+        return Ok(None);
+    };
+    let serial =
+        soa_serial(first).ok_or_else(|| ProtoError::from("zone transfer without first SOA"))?;
+
+    let Some(second) = records.get(1) else {
+        // a single SOA answers the IXFR of a zone up to date
+        return Ok(current
+            .filter(|current| !is_newer(serial, *current))
+            .map(|_| Transfer::UpToDate));
+    };
+
+    let soas = records
+        .iter()
+        .filter(|record| record.record_type() == RecordType::SOA)
+        .count();
+    let complete = records.last().and_then(soa_serial) == Some(serial);
+
+    // the full zone, the second SOA of an IXFR is the one of the current version
+    if soa_serial(second).map_or(true, |second| second == serial) {
+        return match soas {
+            1 => Ok(None),
+            2 if complete => Ok(Some(Transfer::Full(records[..records.len() - 1].to_vec()))),
+            _ => Err(ProtoError::from("zone transfer with trailing records")),
+        };
+    }
+
+    if soas % 2 == 1 || !complete {
+        return Ok(None);
+    }
+
+    // the SOAs followed by the deleted or the added records
+    let mut sequences = Vec::<(&Record, Vec<Record>)>::new();
+    for record in &records[1..records.len() - 1] {
+        match sequences.last_mut() {
+            Some((_, sequence)) if record.record_type() != RecordType::SOA => {
+                sequence.push(record.clone())
+            }
+            _ => sequences.push((record, Vec::new())),
+        }
+    }
+
+    let mut expected = current
+        .ok_or_else(|| ProtoError::from("incremental transfer of a zone without version"))?;
+    let mut changes = Vec::with_capacity(sequences.len() / 2);
+    for pair in sequences.chunks(2) {
+        let [(old_soa, deleted), (new_soa, added)] = pair else {
+            return Err(ProtoError::from("IXFR changes without new SOA"));
+        };
+
+        let old_serial = soa_serial(old_soa);
+        if old_serial != Some(expected) {
+            return Err(ProtoError::from(format!(
+                "IXFR changes from serial {old_serial:?}, expected {expected}"
+            )));
+        }
+
+        expected = soa_serial(new_soa).unwrap_or_default();
+        changes.push(Changes {
+            deleted: deleted.clone(),
+            added: added.clone(),
+            soa: (*new_soa).clone(),
+        });
+    }
+
+    if expected != serial {
+        return Err(ProtoError::from(format!(
+            "IXFR changes up to serial {expected}, expected {serial}"
+        )));
+    }
+
+    Ok(Some(Transfer::Incremental(changes)))
+}
+
+/// The SOA of the zone, if it has one
+async fn current_soa(authority: &InMemoryAuthority) -> Option<Record> {
+    let lookup = authority.soa().await.map_result()?.ok()?;
+    let soa = lookup
+        .iter()
+        .find(|record| soa_serial(record).is_some())
+        .cloned();
+    soa
+}
+
+fn soa_serial(record: &Record) -> Option<u32> {
+    match record.data() {
+        RData::SOA(soa) => Some(soa.serial()),
+        _ => None,
+    }
+}
+
+/// Compares the serials in sequence space arithmetic, RFC 1982
+fn is_newer(serial: u32, current: u32) -> bool {
+    serial != current && serial.wrapping_sub(current) < 1 << 31
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::proto::rr::rdata::{A, SOA};
+
+    fn name(name: &str) -> Name {
+        Name::from_str(name).unwrap()
+    }
+
+    fn soa(serial: u32) -> Record {
+        let soa = SOA::new(
+            name("ns.example.com."),
+            name("invalid."),
+            serial,
+            3600,
+            600,
+            86400,
+            0,
+        );
+        Record::from_rdata(name("example.com."), 3600, RData::SOA(soa))
+    }
+
+    fn a(last: u8) -> Record {
+        let a = A::new(192, 0, 2, last);
+        Record::from_rdata(name("www.example.com."), 3600, RData::A(a))
+    }
+
+    #[test]
+    fn test_parse_axfr() {
+        let records = [soa(2), a(1), a(2), soa(2)];
+        assert_eq!(parse_transfer(&records[..3], None).unwrap(), None);
+        assert_eq!(
+            parse_transfer(&records, None).unwrap(),
+            Some(Transfer::Full(records[..3].to_vec()))
+        );
+
+        // the full zone answering an IXFR
+        assert_eq!(
+            parse_transfer(&records, Some(1)).unwrap(),
+            Some(Transfer::Full(records[..3].to_vec()))
+        );
+
+        assert!(parse_transfer(&[a(1), soa(2)], None).is_err());
+        assert!(parse_transfer(&[soa(2), a(1), soa(2), a(2)], None).is_err());
+    }
+
+    #[test]
+    fn test_parse_ixfr() {
+        // a single SOA, the zone is up to date
+        assert_eq!(
+            parse_transfer(&[soa(2)], Some(2)).unwrap(),
+            Some(Transfer::UpToDate)
+        );
+        assert_eq!(parse_transfer(&[soa(2)], Some(1)).unwrap(), None);
+
+        // from 1 to 2, a(1) replaced by a(2), then a(3) added in 3
+        let records = [
+            soa(3),
+            soa(1),
+            a(1),
+            soa(2),
+            a(2),
+            soa(2),
+            soa(3),
+            a(3),
+            soa(3),
+        ];
+        for len in 2..records.len() {
+            assert_eq!(parse_transfer(&records[..len], Some(1)).unwrap(), None);
+        }
+        assert_eq!(
+            parse_transfer(&records, Some(1)).unwrap(),
+            Some(Transfer::Incremental(vec![
+                Changes {
+                    deleted: vec![a(1)],
+                    added: vec![a(2)],
+                    soa: soa(2),
+                },
+                Changes {
+                    deleted: vec![],
+                    added: vec![a(3)],
+                    soa: soa(3),
+                },
+            ]))
+        );
+
+        // the changes don't start at the current version
+        assert!(parse_transfer(&records, Some(0)).is_err());
+    }
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer(2, 1));
+        assert!(!is_newer(1, 1));
+        assert!(!is_newer(1, 2));
+        assert!(is_newer(0, u32::MAX));
+    }
+}
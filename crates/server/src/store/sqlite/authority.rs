@@ -40,10 +40,14 @@ use crate::{
     dnssec::NxProofKind,
     proto::dnssec::{
         rdata::{key::KEY, DNSSECRData},
+        tsig::TSigner,
         DnsSecResult, SigSigner, Verifier,
     },
 };
 
+/// The number of serial bumps kept in memory for answering IXFR requests
+const IXFR_JOURNAL_ENTRIES: usize = 64;
+
 /// SqliteAuthority is responsible for storing the resource records for a particular zone.
 ///
 /// Authorities default to DNSClass IN. The ZoneType specifies if this should be treated as the
@@ -54,6 +58,8 @@ pub struct SqliteAuthority {
     journal: Mutex<Option<Journal>>,
     allow_update: bool,
     is_dnssec_enabled: bool,
+    #[cfg(feature = "dnssec-ring")]
+    tsig_signers: Vec<TSigner>,
 }
 
 impl SqliteAuthority {
@@ -75,6 +81,8 @@ impl SqliteAuthority {
             journal: Mutex::new(None),
             allow_update,
             is_dnssec_enabled,
+            #[cfg(feature = "dnssec-ring")]
+            tsig_signers: Vec::new(),
         }
     }
 
@@ -119,6 +127,7 @@ impl SqliteAuthority {
                 .map_err(|e| format!("error recovering from journal: {e}"))?;
 
             authority.set_journal(journal).await;
+            authority.enable_ixfr_journal(IXFR_JOURNAL_ENTRIES);
             info!("recovered zone: {}", zone_name);
 
             Ok(authority)
@@ -155,6 +164,7 @@ impl SqliteAuthority {
                 .persist_to_journal()
                 .await
                 .map_err(|e| format!("error persisting to journal {journal_path:?}: {e}"))?;
+            authority.enable_ixfr_journal(IXFR_JOURNAL_ENTRIES);
 
             info!("zone file loaded: {}", zone_name);
             Ok(authority)
@@ -237,6 +247,12 @@ impl SqliteAuthority {
         self.allow_update = allow_update;
     }
 
+    /// Add a TSIG key that is authorized to perform updates and zone transfers of this authority
+    #[cfg(feature = "dnssec-ring")]
+    pub fn add_tsig_signer(&mut self, signer: TSigner) {
+        self.tsig_signers.push(signer);
+    }
+
     /// Get serial
     #[cfg(any(test, feature = "testing"))]
     pub async fn serial(&self) -> u32 {
@@ -490,7 +506,14 @@ impl SqliteAuthority {
             return Err(ResponseCode::Refused);
         }
 
-        // verify sig0, currently the only authorization that is accepted.
+        // the errors of TSIG signed updates are answered with NOTAUTH and the TSIG error
+        if let Some(tsig) = update_message.tsig() {
+            return update_message
+                .verify_tsig(tsig, &self.tsig_signers)
+                .map(drop);
+        }
+
+        // verify sig0, currently the only other authorization that is accepted.
         let sig0s: &[Record] = update_message.sig0();
         debug!("authorizing with: {:?}", sig0s);
         if !sig0s.is_empty() {
@@ -945,6 +968,12 @@ impl Authority for SqliteAuthority {
         Err(ResponseCode::NotImp)
     }
 
+    /// Returns the TSIG keys allowed to authenticate updates and zone transfers
+    #[cfg(feature = "dnssec-ring")]
+    fn tsig_signers(&self) -> &[TSigner] {
+        &self.tsig_signers
+    }
+
     /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
     fn origin(&self) -> &LowerName {
         self.in_memory.origin()
@@ -982,6 +1011,19 @@ impl Authority for SqliteAuthority {
         self.in_memory.search(request_info, lookup_options).await
     }
 
+    /// Using the specified IXFR query, return the changes made to the zone since the version with
+    /// the given serial, see `InMemoryAuthority::ixfr()`.
+    async fn ixfr(
+        &self,
+        request_info: RequestInfo<'_>,
+        serial: u32,
+        lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Self::Lookup> {
+        self.in_memory
+            .ixfr(request_info, serial, lookup_options)
+            .await
+    }
+
     /// Return the NSEC records based on the given name
     ///
     /// # Arguments
@@ -197,13 +197,11 @@ fn hermetic_dsa() -> Result<()> {
 }
 
 #[test]
-#[ignore = "hickory doesn't support ED448"]
 fn ed448() -> Result<()> {
     compare("ed448").map(drop)
 }
 
 #[test]
-#[ignore = "hickory doesn't support ED448"]
 fn hermetic_ed448() -> Result<()> {
     hermetic_compare("ed448").map(drop)
 }
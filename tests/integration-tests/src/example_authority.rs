@@ -188,6 +188,38 @@ pub fn create_example() -> InMemoryAuthority {
 #[cfg(feature = "dnssec-ring")]
 #[allow(unused)]
 pub fn create_secure_example() -> InMemoryAuthority {
+    let mut authority = create_example();
+    secure_example(&mut authority);
+    authority
+}
+
+/// The example.com zone with a `*.example.com.` wildcard, signed as [`create_secure_example`]
+#[cfg(feature = "dnssec-ring")]
+#[allow(unused)]
+pub fn create_secure_wildcard_example() -> InMemoryAuthority {
+    use std::net::Ipv4Addr;
+
+    let mut authority = create_example();
+
+    // *.example.com. 86400 IN A 192.0.2.1
+    authority.upsert_mut(
+        Record::from_rdata(
+            Name::from_str("*.example.com.").unwrap(),
+            86400,
+            RData::A(Ipv4Addr::new(192, 0, 2, 1).into()),
+        )
+        .set_dns_class(DNSClass::IN)
+        .clone(),
+        0,
+    );
+
+    secure_example(&mut authority);
+    authority
+}
+
+#[cfg(feature = "dnssec-ring")]
+#[allow(unused)]
+fn secure_example(authority: &mut InMemoryAuthority) {
     use hickory_proto::dnssec::{
         rdata::DNSKEY, ring::RsaSigningKey, Algorithm, PublicKey, SigSigner, SigningKey,
     };
@@ -195,8 +227,6 @@ pub fn create_secure_example() -> InMemoryAuthority {
     use rustls_pki_types::PrivatePkcs8KeyDer;
     use time::Duration;
 
-    let mut authority = create_example();
-
     const KEY: &[u8] = include_bytes!("../tests/rsa-2048.pk8");
     let key =
         RsaSigningKey::from_pkcs8(&PrivatePkcs8KeyDer::from(KEY), Algorithm::RSASHA256).unwrap();
@@ -210,6 +240,4 @@ pub fn create_secure_example() -> InMemoryAuthority {
 
     authority.add_zone_signing_key_mut(signer);
     authority.secure_zone_mut();
-
-    authority
 }
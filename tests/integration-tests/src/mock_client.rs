@@ -140,6 +140,10 @@ impl<O: OnSend + Unpin> ConnectionProvider for MockConnProvider<O> {
             self.on_send.clone(),
         ))))
     }
+
+    fn create_handle(&self) -> TokioHandle {
+        TokioHandle::default()
+    }
 }
 
 #[derive(Clone)]
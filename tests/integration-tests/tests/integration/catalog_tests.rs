@@ -10,7 +10,7 @@ use hickory_proto::{
 use hickory_server::dnssec::NxProofKind;
 use hickory_server::{
     authority::{Authority, Catalog, MessageRequest, ZoneType},
-    server::Request,
+    server::{AccessLists, Acl, DnsCookies, Request, RequestHandler},
     store::in_memory::InMemoryAuthority,
 };
 
@@ -506,6 +506,221 @@ async fn test_axfr_refused() {
     assert!(result.additionals().is_empty());
 }
 
+#[tokio::test]
+async fn test_axfr_access_lists() {
+    let mut test = create_test();
+    test.set_allow_axfr(true);
+    let origin = test.origin().clone();
+
+    let mut catalog = Catalog::new();
+    catalog.upsert(origin.clone(), vec![Arc::new(test)]);
+    catalog.set_access_lists(AccessLists {
+        allow_transfer: Acl {
+            allow: vec!["127.0.0.0/8".parse().unwrap()],
+            deny: vec!["127.0.0.2/32".parse().unwrap()],
+        },
+        ..AccessLists::default()
+    });
+
+    let query = |client: [u8; 4], record_type: RecordType| {
+        let mut question: Message = Message::new();
+        question.add_query(Query::query(origin.clone().into(), record_type));
+        let question_bytes = question.to_bytes().unwrap();
+        let question_req = MessageRequest::from_bytes(&question_bytes).unwrap();
+        Request::new(question_req, (client, 5553).into(), Protocol::Tcp)
+    };
+
+    // an allowed client
+    let response_handler = TestResponseHandler::new();
+    catalog
+        .lookup(
+            &query([127, 0, 0, 1], RecordType::AXFR),
+            None,
+            response_handler.clone(),
+        )
+        .await;
+    let result = response_handler.into_message().await;
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert!(result.answers().len() > 2);
+
+    // a client outside of the allowed networks, and a denied one
+    for client in [[192, 0, 2, 1], [127, 0, 0, 2]] {
+        let response_handler = TestResponseHandler::new();
+        catalog
+            .lookup(
+                &query(client, RecordType::AXFR),
+                None,
+                response_handler.clone(),
+            )
+            .await;
+        let result = response_handler.into_message().await;
+        assert_eq!(result.response_code(), ResponseCode::Refused);
+        assert!(result.answers().is_empty());
+
+        // these clients may still query the zone
+        let response_handler = TestResponseHandler::new();
+        catalog
+            .lookup(
+                &query(client, RecordType::SOA),
+                None,
+                response_handler.clone(),
+            )
+            .await;
+        let result = response_handler.into_message().await;
+        assert_eq!(result.response_code(), ResponseCode::NoError);
+        assert_eq!(result.answers().len(), 1);
+    }
+}
+
+#[tokio::test]
+async fn test_query_and_recursion_access_lists() {
+    let test = create_test();
+    let origin = test.origin().clone();
+    let external_origin = LowerName::from(Name::parse("external.", None).unwrap());
+    let external = InMemoryAuthority::empty(
+        external_origin.clone().into(),
+        ZoneType::External,
+        false,
+        #[cfg(feature = "dnssec")]
+        None,
+    );
+
+    let mut catalog = Catalog::new();
+    catalog.upsert(origin.clone(), vec![Arc::new(test)]);
+    catalog.upsert(external_origin.clone(), vec![Arc::new(external)]);
+    catalog.set_access_lists(AccessLists {
+        allow_query: Acl {
+            allow: vec![],
+            deny: vec!["192.0.2.0/24".parse().unwrap()],
+        },
+        allow_recursion: Acl {
+            allow: vec!["127.0.0.0/8".parse().unwrap()],
+            deny: vec![],
+        },
+        ..AccessLists::default()
+    });
+
+    let response_code = |client: [u8; 4], name: &LowerName| {
+        let mut question: Message = Message::new();
+        question.add_query(Query::query(name.clone().into(), RecordType::SOA));
+        let question_bytes = question.to_bytes().unwrap();
+        let question_req = MessageRequest::from_bytes(&question_bytes).unwrap();
+        let request = Request::new(question_req, (client, 5553).into(), Protocol::Udp);
+
+        let catalog = &catalog;
+        async move {
+            let response_handler = TestResponseHandler::new();
+            catalog
+                .lookup(&request, None, response_handler.clone())
+                .await;
+            response_handler.into_message().await.response_code()
+        }
+    };
+
+    assert_eq!(
+        response_code([198, 51, 100, 1], &origin).await,
+        ResponseCode::NoError
+    );
+    assert_eq!(
+        response_code([192, 0, 2, 1], &origin).await,
+        ResponseCode::Refused
+    );
+
+    // only the clients permitted recursion reach the external authorities
+    assert_ne!(
+        response_code([127, 0, 0, 1], &external_origin).await,
+        ResponseCode::Refused
+    );
+    assert_eq!(
+        response_code([198, 51, 100, 1], &external_origin).await,
+        ResponseCode::Refused
+    );
+}
+
+#[tokio::test]
+async fn test_minimal_responses() {
+    // the name servers of sub.test.com. are below the delegation, the ones of other.test.com.
+    // and the mail exchanger of test.com. are not
+    let records = [
+        (
+            "sub.test.com.",
+            RData::NS(NS(Name::from_str("ns.sub.test.com.").unwrap())),
+        ),
+        ("ns.sub.test.com.", RData::A(A::new(192, 0, 2, 1))),
+        (
+            "other.test.com.",
+            RData::NS(NS(Name::from_str("ns.test.com.").unwrap())),
+        ),
+        ("ns.test.com.", RData::A(A::new(192, 0, 2, 2))),
+        (
+            "test.com.",
+            RData::MX(MX::new(10, Name::from_str("mail.test.com.").unwrap())),
+        ),
+        ("mail.test.com.", RData::A(A::new(192, 0, 2, 3))),
+        (
+            "alias.test.com.",
+            RData::CNAME(CNAME(Name::from_str("www.test.com.").unwrap())),
+        ),
+    ];
+
+    let response = |minimal_responses: bool, name: &str, record_type: RecordType| {
+        let mut test = create_test();
+        for (owner, rdata) in &records {
+            test.upsert_mut(
+                Record::from_rdata(Name::from_str(owner).unwrap(), 86400, rdata.clone()),
+                0,
+            );
+        }
+        test.set_minimal_responses(minimal_responses);
+
+        let mut catalog = Catalog::new();
+        catalog.upsert(test.origin().clone(), vec![Arc::new(test)]);
+
+        let mut question: Message = Message::new();
+        question.add_query(Query::query(Name::from_str(name).unwrap(), record_type));
+        let question_bytes = question.to_bytes().unwrap();
+        let question_req = MessageRequest::from_bytes(&question_bytes).unwrap();
+        let question_req = Request::new(question_req, ([127, 0, 0, 1], 5553).into(), Protocol::Udp);
+
+        async move {
+            let response_handler = TestResponseHandler::new();
+            catalog
+                .lookup(&question_req, None, response_handler.clone())
+                .await;
+            response_handler.into_message().await
+        }
+    };
+
+    // the glue is required
+    let full = response(false, "sub.test.com.", RecordType::NS).await;
+    let minimal = response(true, "sub.test.com.", RecordType::NS).await;
+    assert_eq!(minimal.answers(), full.answers());
+    assert_eq!(minimal.additionals().len(), 1);
+    assert_eq!(minimal.additionals(), full.additionals());
+    assert_eq!(
+        minimal.to_vec().unwrap().len(),
+        full.to_vec().unwrap().len()
+    );
+
+    for (name, record_type) in [
+        ("other.test.com.", RecordType::NS),
+        ("test.com.", RecordType::MX),
+    ] {
+        let full = response(false, name, record_type).await;
+        let minimal = response(true, name, record_type).await;
+        assert_eq!(minimal.answers(), full.answers());
+        assert_eq!(full.additionals().len(), 1);
+        assert!(minimal.additionals().is_empty());
+        assert!(minimal.to_vec().unwrap().len() < full.to_vec().unwrap().len());
+    }
+
+    // the records of a CNAME chain are required
+    let full = response(false, "alias.test.com.", RecordType::A).await;
+    let minimal = response(true, "alias.test.com.", RecordType::A).await;
+    assert_eq!(minimal.additionals().len(), 1);
+    assert_eq!(minimal.additionals(), full.additionals());
+}
+
 // TODO: add this test
 // #[test]
 // fn test_truncated_returns_records() {
@@ -619,3 +834,223 @@ async fn test_multiple_cname_additionals() {
         &RData::A(A::new(93, 184, 215, 14))
     );
 }
+
+/// Sends a query for the example zone with `cookie`, and returns the response
+async fn query_with_cookie(
+    catalog: &Catalog,
+    protocol: Protocol,
+    cookie: rdata::opt::Cookie,
+) -> Message {
+    let mut question = Message::new();
+    let mut query = Query::new();
+    query.set_name(Name::from_str("example.com.").unwrap());
+    question.add_query(query);
+
+    let mut edns = Edns::new();
+    edns.options_mut()
+        .insert(rdata::opt::EdnsOption::Cookie(cookie));
+    question.set_edns(edns);
+
+    let question_bytes = question.to_bytes().unwrap();
+    let question_req = MessageRequest::from_bytes(&question_bytes).unwrap();
+    let question_req = Request::new(question_req, ([127, 0, 0, 1], 5553).into(), protocol);
+
+    let response_handler = TestResponseHandler::new();
+    catalog
+        .handle_request(&question_req, response_handler.clone())
+        .await;
+    response_handler.into_message().await
+}
+
+fn response_cookie(response: &Message) -> rdata::opt::Cookie {
+    match response
+        .extensions()
+        .as_ref()
+        .and_then(|edns| edns.option(rdata::opt::EdnsCode::Cookie))
+    {
+        Some(rdata::opt::EdnsOption::Cookie(cookie)) => cookie.clone(),
+        option => panic!("unexpected cookie option: {option:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_dns_cookies() {
+    let example = create_example();
+    let origin = example.origin().clone();
+    let client = [0x2a; 8];
+
+    let mut catalog = Catalog::new();
+    catalog.upsert(origin, vec![Arc::new(example)]);
+    catalog.set_dns_cookies(DnsCookies::new(false));
+
+    // initial exchange, the server cookie is issued for the client cookie
+    let client_only = rdata::opt::Cookie::new(client, None);
+    let response = query_with_cookie(&catalog, Protocol::Udp, client_only).await;
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(!response.answers().is_empty());
+    let cookie = response_cookie(&response);
+    assert_eq!(cookie.client(), &client);
+    assert_eq!(cookie.server().map(<[u8]>::len), Some(16));
+
+    // the server cookie is accepted in the following queries
+    let response = query_with_cookie(&catalog, Protocol::Udp, cookie.clone()).await;
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert_eq!(response_cookie(&response).client(), &client);
+
+    // an invalid server cookie is replaced, the query is still answered
+    let invalid = rdata::opt::Cookie::new(client, Some(vec![0; 16]));
+    let response = query_with_cookie(&catalog, Protocol::Udp, invalid).await;
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert_ne!(response_cookie(&response).server(), Some(&[0; 16][..]));
+}
+
+#[tokio::test]
+async fn test_dns_cookies_required() {
+    let example = create_example();
+    let origin = example.origin().clone();
+    let client = [0x2a; 8];
+
+    let mut catalog = Catalog::new();
+    catalog.upsert(origin, vec![Arc::new(example)]);
+    catalog.set_dns_cookies(DnsCookies::new(true));
+
+    // queries over UDP without a server cookie are answered with BADCOOKIE
+    let client_only = rdata::opt::Cookie::new(client, None);
+    let response = query_with_cookie(&catalog, Protocol::Udp, client_only.clone()).await;
+    assert_eq!(response.response_code(), ResponseCode::BADCOOKIE);
+    assert!(response.answers().is_empty());
+    let cookie = response_cookie(&response);
+
+    // the retry with the server cookie of the BADCOOKIE response is answered
+    let response = query_with_cookie(&catalog, Protocol::Udp, cookie).await;
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(!response.answers().is_empty());
+
+    // queries over TCP do not need a server cookie
+    let response = query_with_cookie(&catalog, Protocol::Tcp, client_only).await;
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(response_cookie(&response).server().is_some());
+}
+
+/// Sends an ANY query for the example zone apex, with the DO bit if `dnssec_ok` is set
+async fn query_any(catalog: &Catalog, dnssec_ok: bool) -> Message {
+    let mut question = Message::new();
+    question.add_query(Query::query(
+        Name::from_str("example.com.").unwrap(),
+        RecordType::ANY,
+    ));
+    if dnssec_ok {
+        let mut edns = Edns::new();
+        edns.set_dnssec_ok(true);
+        question.set_edns(edns);
+    }
+
+    let question_bytes = question.to_bytes().unwrap();
+    let question_req = MessageRequest::from_bytes(&question_bytes).unwrap();
+    let question_req = Request::new(question_req, ([127, 0, 0, 1], 5553).into(), Protocol::Udp);
+
+    let response_handler = TestResponseHandler::new();
+    catalog
+        .lookup(&question_req, None, response_handler.clone())
+        .await;
+    response_handler.into_message().await
+}
+
+#[tokio::test]
+async fn test_refuse_any() {
+    let example = create_example();
+    let origin = example.origin().clone();
+
+    let mut catalog = Catalog::new();
+    catalog.upsert(origin, vec![Arc::new(example)]);
+
+    // the ANY queries are answered with the HINFO of RFC 8482 by default
+    let response = query_any(&catalog, false).await;
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert_eq!(response.answers().len(), 1);
+    let answer = &response.answers()[0];
+    assert_eq!(answer.name(), &Name::from_str("example.com.").unwrap());
+    assert_eq!(
+        answer.data(),
+        &RData::HINFO(HINFO::new("RFC8482".to_string(), String::new()))
+    );
+
+    // the explicit types are still answered
+    let mut question = Message::new();
+    question.add_query(Query::query(
+        Name::from_str("example.com.").unwrap(),
+        RecordType::NS,
+    ));
+    let question_bytes = question.to_bytes().unwrap();
+    let question_req = MessageRequest::from_bytes(&question_bytes).unwrap();
+    let question_req = Request::new(question_req, ([127, 0, 0, 1], 5553).into(), Protocol::Udp);
+    let response_handler = TestResponseHandler::new();
+    catalog
+        .lookup(&question_req, None, response_handler.clone())
+        .await;
+    let response = response_handler.into_message().await;
+    assert_eq!(response.answers().len(), 2);
+    assert!(response
+        .answers()
+        .iter()
+        .all(|record| record.record_type() == RecordType::NS));
+
+    // all the records are answered when disabled
+    catalog.set_refuse_any(false);
+    let response = query_any(&catalog, false).await;
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    let types = response
+        .answers()
+        .iter()
+        .map(Record::record_type)
+        .collect::<Vec<_>>();
+    for record_type in [
+        RecordType::SOA,
+        RecordType::NS,
+        RecordType::A,
+        RecordType::AAAA,
+    ] {
+        assert!(types.contains(&record_type), "missing {record_type}");
+    }
+    assert!(!types.contains(&RecordType::HINFO));
+}
+
+#[cfg(feature = "dnssec-ring")]
+#[tokio::test]
+async fn test_refuse_any_dnssec_ok() {
+    use hickory_integration::example_authority::create_secure_example;
+    use hickory_proto::dnssec::rdata::DNSSECRData;
+
+    let example = create_secure_example();
+    let origin = example.origin().clone();
+
+    let mut catalog = Catalog::new();
+    catalog.upsert(origin, vec![Arc::new(example)]);
+
+    // the unsigned HINFO is only answered without the DO bit
+    let response = query_any(&catalog, false).await;
+    assert_eq!(response.answers().len(), 1);
+    assert_eq!(response.answers()[0].record_type(), RecordType::HINFO);
+
+    // with the DO bit, a single RRset is answered with its signatures
+    let response = query_any(&catalog, true).await;
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    let (rrsigs, records): (Vec<_>, Vec<_>) = response
+        .answers()
+        .iter()
+        .partition(|record| record.record_type() == RecordType::RRSIG);
+    assert!(!records.is_empty());
+    assert!(!rrsigs.is_empty());
+
+    let record_type = records[0].record_type();
+    assert_ne!(record_type, RecordType::HINFO);
+    assert!(records
+        .iter()
+        .all(|record| record.record_type() == record_type));
+    for rrsig in rrsigs {
+        let RData::DNSSEC(DNSSECRData::RRSIG(rrsig)) = rrsig.data() else {
+            panic!("expected an RRSIG: {rrsig}");
+        };
+        assert_eq!(rrsig.type_covered(), record_type);
+    }
+}
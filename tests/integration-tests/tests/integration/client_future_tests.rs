@@ -237,15 +237,18 @@ fn test_notify() {
 
     let name = Name::from_str("ping.example.com.").unwrap();
 
+    // only the changes of the SOA are notified, RFC 1996 section 3.11
     let message =
         io_loop.block_on(client.notify(name, DNSClass::IN, RecordType::A, None::<RecordSet>));
     assert!(message.is_ok());
     let message = message.unwrap();
-    assert_eq!(
-        message.response_code(),
-        ResponseCode::NotImp,
-        "the catalog must support Notify now, update this"
-    );
+    assert_eq!(message.response_code(), ResponseCode::NotImp);
+
+    // the catalog is not a secondary of the zone
+    let name = Name::from_str("example.com.").unwrap();
+    let message =
+        io_loop.block_on(client.notify(name, DNSClass::IN, RecordType::SOA, None::<RecordSet>));
+    assert_eq!(message.unwrap().response_code(), ResponseCode::NotAuth);
 }
 
 // update tests
@@ -917,6 +920,54 @@ fn test_delete_all() {
     assert_eq!(result.answers().len(), 0);
 }
 
+#[cfg(all(feature = "dnssec-ring", feature = "sqlite"))]
+#[test]
+fn test_update_builder() {
+    use hickory_proto::op::update_message::UpdateBuilder;
+
+    let io_loop = Runtime::new().unwrap();
+    let ((mut client, bg), origin) = io_loop.block_on(create_sig0_ready_client());
+    hickory_proto::runtime::spawn_bg(&io_loop, bg);
+
+    let name = Name::from_str("builder.example.com.").unwrap();
+    let old = Record::from_rdata(name.clone(), 300, RData::A(A::new(100, 10, 100, 10)));
+    let new = Record::from_rdata(name.clone(), 300, RData::A(A::new(101, 11, 101, 11)));
+
+    // create the record only if the name is free
+    let update = UpdateBuilder::new(origin.clone(), DNSClass::IN)
+        .require_not_exists(name.clone(), RecordType::A)
+        .add_record(old.clone());
+    let result = io_loop
+        .block_on(client.update(update))
+        .expect("update failed");
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+
+    // the prerequisite isn't met anymore, nothing is applied
+    let update = UpdateBuilder::new(origin.clone(), DNSClass::IN)
+        .require_not_exists(name.clone(), RecordType::A)
+        .add_record(new.clone());
+    let result = io_loop
+        .block_on(client.update(update))
+        .expect("update failed");
+    assert_eq!(result.response_code(), ResponseCode::YXRRSet);
+
+    // replace the record in a single update
+    let update = UpdateBuilder::new(origin, DNSClass::IN)
+        .require_exists(name.clone(), RecordType::A)
+        .delete_record(old)
+        .add_record(new.clone());
+    let result = io_loop
+        .block_on(client.update(update))
+        .expect("update failed");
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+
+    let result = io_loop
+        .block_on(client.query(name, DNSClass::IN, RecordType::A))
+        .expect("query failed");
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(result.answers(), [new]);
+}
+
 fn test_timeout_query(mut client: Client, io_loop: Runtime) {
     let name = Name::from_str("www.example.com").unwrap();
 
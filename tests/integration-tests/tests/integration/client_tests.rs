@@ -24,6 +24,8 @@ use hickory_integration::{GOOGLE_V4, TEST3_V4};
 #[cfg(all(feature = "dnssec-ring", feature = "sqlite"))]
 use hickory_proto::dnssec::rdata::{DNSSECRData, KEY};
 #[cfg(all(feature = "dnssec-ring", feature = "sqlite"))]
+use hickory_proto::dnssec::{rdata::tsig::TsigAlgorithm, tsig::TSigner};
+#[cfg(all(feature = "dnssec-ring", feature = "sqlite"))]
 use hickory_proto::dnssec::{ring::RsaSigningKey, Algorithm, PublicKey, SigSigner, SigningKey};
 #[cfg(all(feature = "dnssec-ring", feature = "sqlite"))]
 use hickory_proto::op::MessageFinalizer;
@@ -44,7 +46,9 @@ use hickory_proto::xfer::{DnsHandle, DnsMultiplexer};
 use hickory_proto::ProtoError;
 use hickory_proto::ProtoErrorKind;
 #[cfg(all(feature = "dnssec-ring", feature = "sqlite"))]
-use hickory_server::authority::{Authority, Catalog};
+use hickory_server::authority::{Authority, Catalog, ZoneType};
+#[cfg(all(feature = "dnssec-ring", feature = "sqlite"))]
+use hickory_server::dnssec::NxProofKind;
 #[cfg(feature = "dnssec-ring")]
 use test_support::subscribe;
 
@@ -555,6 +559,217 @@ async fn test_create() {
     assert_eq!(result.response_code(), ResponseCode::YXRRSet);
 }
 
+#[cfg(all(feature = "dnssec-ring", feature = "sqlite"))]
+async fn create_tsig_ready_client(
+    mut catalog: Catalog,
+    client_signer: Option<TSigner>,
+) -> (Client, Name) {
+    use hickory_server::store::{in_memory::InMemoryAuthority, sqlite::SqliteAuthority};
+
+    // the example zone, allowing zone transfers
+    let example = create_example();
+    let records = example
+        .records()
+        .await
+        .into_iter()
+        .map(|(key, rrset)| {
+            (
+                key,
+                Arc::try_unwrap(rrset).unwrap_or_else(|rrset| (*rrset).clone()),
+            )
+        })
+        .collect();
+    let authority = InMemoryAuthority::new(
+        example.origin().into(),
+        records,
+        ZoneType::Primary,
+        true,
+        Some(NxProofKind::Nsec),
+    )
+    .unwrap();
+    let mut authority = SqliteAuthority::new(authority, true, false);
+    authority.set_allow_update(true);
+    let origin = authority.origin().clone();
+
+    let server_signer = TSigner::new(
+        TSIG_KEY.to_vec(),
+        TsigAlgorithm::HmacSha256,
+        Name::from_str("tsig-key.").unwrap(),
+        300,
+    )
+    .unwrap();
+    authority.add_tsig_signer(server_signer);
+
+    catalog.upsert(authority.origin().clone(), vec![Arc::new(authority)]);
+    let client_signer = client_signer.map(|signer| Arc::new(signer) as Arc<dyn MessageFinalizer>);
+    let multiplexer = TestClientConnection::new(catalog).to_multiplexer(client_signer);
+    let (client, driver) = Client::connect(multiplexer)
+        .await
+        .expect("failed to connect");
+    tokio::spawn(driver);
+
+    (client, origin.into())
+}
+
+#[cfg(all(feature = "dnssec-ring", feature = "sqlite"))]
+const TSIG_KEY: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+#[cfg(all(feature = "dnssec-ring", feature = "sqlite"))]
+#[tokio::test]
+async fn test_create_tsig() {
+    let signer = TSigner::new(
+        TSIG_KEY.to_vec(),
+        TsigAlgorithm::HmacSha256,
+        Name::from_str("tsig-key.").unwrap(),
+        300,
+    )
+    .unwrap();
+    let (mut client, origin) = create_tsig_ready_client(Catalog::new(), Some(signer)).await;
+
+    let record = Record::from_rdata(
+        Name::from_str("new.example.com.").unwrap(),
+        Duration::minutes(5).whole_seconds() as u32,
+        RData::A(A::new(100, 10, 100, 10)),
+    );
+
+    // the response is verified against the request MAC by the client signer
+    let result = client
+        .create(record.clone(), origin)
+        .await
+        .expect("create failed");
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    let result = client
+        .query(
+            record.name().clone(),
+            record.dns_class(),
+            record.record_type(),
+        )
+        .await
+        .expect("query failed");
+    assert_eq!(result.answers(), &[record]);
+}
+
+#[cfg(all(feature = "dnssec-ring", feature = "sqlite"))]
+#[tokio::test]
+async fn test_create_tsig_rejected() {
+    let signers = [
+        // wrong secret
+        TSigner::new(
+            b"fedcba9876543210fedcba9876543210".to_vec(),
+            TsigAlgorithm::HmacSha256,
+            Name::from_str("tsig-key.").unwrap(),
+            300,
+        )
+        .unwrap(),
+        // unknown key
+        TSigner::new(
+            TSIG_KEY.to_vec(),
+            TsigAlgorithm::HmacSha256,
+            Name::from_str("other-key.").unwrap(),
+            300,
+        )
+        .unwrap(),
+    ];
+
+    for signer in signers {
+        let (mut client, origin) = create_tsig_ready_client(Catalog::new(), Some(signer)).await;
+
+        let record = Record::from_rdata(
+            Name::from_str("new.example.com.").unwrap(),
+            Duration::minutes(5).whole_seconds() as u32,
+            RData::A(A::new(100, 10, 100, 10)),
+        );
+
+        // the server rejects the update with an unsigned BADSIG or BADKEY error
+        assert!(client.create(record.clone(), origin).await.is_err());
+        let result = client
+            .query(
+                record.name().clone(),
+                record.dns_class(),
+                record.record_type(),
+            )
+            .await
+            .expect("query failed");
+        assert_eq!(result.response_code(), ResponseCode::NXDomain);
+    }
+}
+
+#[cfg(all(feature = "dnssec-ring", feature = "sqlite"))]
+#[tokio::test]
+async fn test_zone_transfer_tsig() {
+    let signer = TSigner::new(
+        TSIG_KEY.to_vec(),
+        TsigAlgorithm::HmacSha256,
+        Name::from_str("tsig-key.").unwrap(),
+        300,
+    )
+    .unwrap();
+    let (mut client, origin) = create_tsig_ready_client(Catalog::new(), Some(signer)).await;
+
+    // the response is verified against the request MAC by the client signer
+    let responses = client
+        .zone_transfer(origin, None)
+        .try_collect::<Vec<_>>()
+        .await
+        .expect("zone transfer failed");
+    let [response] = &responses[..] else {
+        panic!("expected a single response: {responses:?}");
+    };
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert_eq!(response.answers()[0].record_type(), RecordType::SOA);
+    assert!(response
+        .answers()
+        .iter()
+        .any(|record| record.record_type() == RecordType::A));
+}
+
+#[cfg(all(feature = "dnssec-ring", feature = "sqlite"))]
+#[tokio::test]
+async fn test_zone_transfer_tsig_rejected() {
+    // unsigned
+    let (mut client, origin) = create_tsig_ready_client(Catalog::new(), None).await;
+    let responses = client
+        .zone_transfer(origin, None)
+        .try_collect::<Vec<_>>()
+        .await
+        .expect("zone transfer failed");
+    assert_eq!(responses[0].response_code(), ResponseCode::Refused);
+    assert!(responses[0].answers().is_empty());
+
+    let signers = [
+        // wrong secret
+        TSigner::new(
+            b"fedcba9876543210fedcba9876543210".to_vec(),
+            TsigAlgorithm::HmacSha256,
+            Name::from_str("tsig-key.").unwrap(),
+            300,
+        )
+        .unwrap(),
+        // unknown key
+        TSigner::new(
+            TSIG_KEY.to_vec(),
+            TsigAlgorithm::HmacSha256,
+            Name::from_str("other-key.").unwrap(),
+            300,
+        )
+        .unwrap(),
+    ];
+
+    for signer in signers {
+        let (mut client, origin) = create_tsig_ready_client(Catalog::new(), Some(signer)).await;
+
+        // the server rejects the transfer with an unsigned BADSIG or BADKEY error
+        let responses = client
+            .zone_transfer(origin, None)
+            .try_collect::<Vec<_>>()
+            .await;
+        assert!(
+            responses.is_err(),
+            "badly signed zone transfer not rejected"
+        );
+    }
+}
+
 #[cfg(all(feature = "dnssec-ring", feature = "sqlite"))]
 #[tokio::test]
 async fn test_append() {
@@ -7,16 +7,21 @@ use futures::executor::block_on;
 use tokio::runtime::Runtime;
 
 use hickory_client::client::{Client, ClientHandle, MemoizeClientHandle};
+use hickory_proto::dnssec::{rdata::DNSSECRData, Proof};
 use hickory_proto::dnssec::{DnssecDnsHandle, TrustAnchor};
 use hickory_proto::op::ResponseCode;
+use hickory_proto::rr::rdata::A;
 use hickory_proto::rr::Name;
-use hickory_proto::rr::{DNSClass, RecordType};
+use hickory_proto::rr::{DNSClass, RData, RecordType};
 use hickory_proto::runtime::TokioRuntimeProvider;
 use hickory_proto::tcp::TcpClientStream;
 use hickory_proto::udp::UdpClientStream;
 use hickory_server::authority::{Authority, Catalog};
+use hickory_server::store::in_memory::InMemoryAuthority;
 
-use hickory_integration::example_authority::create_secure_example;
+use hickory_integration::example_authority::{
+    create_secure_example, create_secure_wildcard_example,
+};
 use hickory_integration::{TestClientStream, GOOGLE_V4};
 
 #[test]
@@ -86,6 +91,67 @@ where
     assert_eq!(response.response_code(), ResponseCode::NXDomain);
 }
 
+#[test]
+fn test_secure_query_wildcard_nonet() {
+    with_authority(create_secure_wildcard_example(), test_secure_query_wildcard);
+}
+
+fn test_secure_query_wildcard<H>(mut client: DnssecDnsHandle<H>, io_loop: Runtime)
+where
+    H: ClientHandle + Sync + 'static,
+{
+    // b.example.com. does not exist either, the closest encloser is example.com.
+    let name = Name::from_str("a.b.example.com.").unwrap();
+    let response = io_loop
+        .block_on(client.query(name.clone(), DNSClass::IN, RecordType::A))
+        .expect("query failed");
+
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    let answers = response
+        .answers()
+        .iter()
+        .filter(|r| r.record_type() == RecordType::A)
+        .collect::<Vec<_>>();
+    assert_eq!(answers.len(), 1);
+    assert_eq!(answers[0].name(), &name);
+    assert_eq!(answers[0].data(), &RData::A(A::new(192, 0, 2, 1)));
+    assert_eq!(answers[0].proof(), Proof::Secure);
+
+    // the RRSIG is owned by the query name, with the label count of the wildcard
+    let rrsig = response
+        .answers()
+        .iter()
+        .find_map(|r| r.data().as_dnssec().and_then(DNSSECRData::as_rrsig))
+        .expect("RRSIG missing");
+    assert_eq!(rrsig.num_labels(), 2);
+    assert!(response
+        .answers()
+        .iter()
+        .filter(|r| r.record_type() == RecordType::RRSIG)
+        .all(|r| r.name() == &name));
+
+    // the NSEC proving that there is no closer match
+    assert!(response.name_servers().iter().any(|r| {
+        r.data()
+            .as_dnssec()
+            .and_then(DNSSECRData::as_nsec)
+            .is_some_and(|nsec| {
+                Name::from_str("b.example.com.").unwrap() > *r.name()
+                    && Name::from_str("b.example.com.").unwrap() < *nsec.next_domain_name()
+            })
+    }));
+
+    // www.example.com. exists, the wildcard doesn't match its descendants
+    let response = io_loop
+        .block_on(client.query(
+            Name::from_str("a.www.example.com.").unwrap(),
+            DNSClass::IN,
+            RecordType::A,
+        ))
+        .expect("query failed");
+    assert_eq!(response.response_code(), ResponseCode::NXDomain);
+}
+
 // TODO: NSEC response code wrong in Hickory DNS? Issue #53
 // #[test]
 // fn test_nsec_query_type_nonet() {
@@ -180,6 +246,14 @@ where
 // }
 
 fn with_nonet<F>(test: F)
+where
+    F: Fn(DnssecDnsHandle<MemoizeClientHandle<Client>>, Runtime),
+{
+    with_authority(create_secure_example(), test)
+}
+
+/// Runs the test against the signed `authority`, without network
+fn with_authority<F>(authority: InMemoryAuthority, test: F)
 where
     F: Fn(DnssecDnsHandle<MemoizeClientHandle<Client>>, Runtime),
 {
@@ -201,8 +275,6 @@ where
         })
         .unwrap();
 
-    let authority = create_secure_example();
-
     let trust_anchor = {
         let signers = block_on(authority.secure_keys());
         let public_key = signers
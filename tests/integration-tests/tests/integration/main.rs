@@ -7,5 +7,6 @@ mod lookup_tests;
 mod name_server_pool_tests;
 mod retry_dns_handle_tests;
 mod server_future_tests;
+mod socks5_tests;
 mod sqlite_authority_tests;
 mod truncation_tests;
@@ -148,6 +148,56 @@ fn test_datagram() {
     assert_eq!(response.answers()[0], udp_record);
 }
 
+#[test]
+fn test_stats() {
+    let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+
+    let udp_record = v4_record(query.name().clone(), Ipv4Addr::LOCALHOST);
+    let udp_message = message(query.clone(), vec![udp_record], vec![], vec![]);
+    let response = || Ok(DnsResponse::from_message(udp_message.clone()).unwrap());
+
+    // messages are sent in reverse order, the last query fails
+    let udp_nameserver = mock_nameserver(
+        vec![
+            Err(ProtoError::from(std::io::Error::from(
+                std::io::ErrorKind::ConnectionReset,
+            ))),
+            response(),
+            response(),
+            response(),
+        ],
+        Default::default(),
+    );
+
+    let pool = mock_nameserver_pool(vec![udp_nameserver], vec![], None, Default::default());
+    for _ in 0..3 {
+        let request = message(query.clone(), vec![], vec![], vec![]);
+        block_on(pool.send(request).first_answer()).unwrap();
+    }
+
+    let stats = pool.stats();
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].successes, 3);
+    assert_eq!(stats[0].failures, 0);
+    assert!(stats[0].last_error.is_none());
+
+    let request = message(query, vec![], vec![], vec![]);
+    block_on(pool.send(request).first_answer()).unwrap_err();
+
+    let stats = pool.stats();
+    assert_eq!(
+        stats[0].socket_addr,
+        SocketAddr::new(DEFAULT_SERVER_ADDR, 0)
+    );
+    assert_eq!(stats[0].protocol, Protocol::Udp);
+    assert_eq!(stats[0].successes, 3);
+    assert_eq!(stats[0].failures, 1);
+    assert!(matches!(
+        stats[0].last_error.as_ref().map(ProtoError::kind),
+        Some(ProtoErrorKind::Io(_))
+    ));
+}
+
 #[test]
 fn test_datagram_stream_upgrades_on_truncation() {
     // Lookup to UDP should return a truncated message, then we expect lookup on TCP.
@@ -150,15 +150,23 @@ async fn test_server_form_error_on_multiple_queries() {
         .set_op_code(OpCode::Query)
         .set_recursion_desired(true);
 
-    let mut client_result = client
-        .send(message)
+    // the client refuses to send the message, it is sent as is
+    assert!(client
+        .send(message.clone())
         .try_collect::<Vec<_>>()
         .await
-        .expect("query failed");
+        .is_err());
 
-    assert_eq!(client_result.len(), 1);
-    let client_result = client_result.pop().expect("there should be one response");
+    let socket = UdpSocket::bind(&addr).await.unwrap();
+    socket
+        .send_to(&message.set_id(1).to_vec().unwrap(), ipaddr)
+        .await
+        .unwrap();
+    let mut buf = [0; 512];
+    let (len, _) = socket.recv_from(&mut buf).await.unwrap();
+    let client_result = Message::from_vec(&buf[..len]).unwrap();
 
+    assert_eq!(client_result.id(), 1);
     assert_eq!(client_result.response_code(), ResponseCode::FormErr);
 
     server_continue.store(false, Ordering::Relaxed);
@@ -257,6 +265,184 @@ async fn test_server_www_tls() {
     server.await.unwrap();
 }
 
+#[cfg(feature = "dns-over-rustls")]
+#[tokio::test]
+async fn test_server_www_tls_dane_ee() {
+    use std::env;
+
+    use hickory_proto::rr::rdata::tlsa::{CertUsage, Matching, Selector, TLSA};
+    use hickory_proto::rustls::{dane_client_config, tls_client_connect_with_bind_addr};
+
+    subscribe();
+
+    // a self-signed certificate, trusted by no root, only authenticated by its TLSA record
+    let server_path = env::var("TDNS_WORKSPACE_ROOT").unwrap_or_else(|_| "../..".to_owned());
+    let cert_chain = read_certs(format!("{server_path}/tests/test-data/dane-ee.pem")).unwrap();
+    let key =
+        PrivateKeyDer::from_pem_file(format!("{server_path}/tests/test-data/dane-ee.key")).unwrap();
+    let tlsa = TLSA::new(
+        CertUsage::DomainIssued,
+        Selector::Full,
+        Matching::Raw,
+        cert_chain[0].to_vec(),
+    );
+
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0));
+    let tcp_listener = TcpListener::bind(&addr).await.unwrap();
+    let ipaddr = tcp_listener.local_addr().unwrap();
+    let server_continue = Arc::new(AtomicBool::new(true));
+    let server = tokio::spawn(server_thread_tls(
+        tcp_listener,
+        server_continue.clone(),
+        (cert_chain, key),
+    ));
+
+    let client = tokio::spawn(client_thread_www(async move {
+        let (tls_client_stream, handle) = tls_client_connect_with_bind_addr(
+            ipaddr,
+            None,
+            "ns.example.com".to_string(),
+            Arc::new(dane_client_config(vec![tlsa])),
+            TokioRuntimeProvider::default(),
+        );
+
+        let multiplexer = DnsMultiplexer::new(Box::pin(tls_client_stream), handle, None);
+        let (client, driver) = Client::connect(multiplexer)
+            .await
+            .expect("failed to connect");
+        tokio::spawn(driver);
+        client
+    }));
+
+    let client_result = client.await;
+    assert!(client_result.is_ok(), "client failed: {client_result:?}");
+    server_continue.store(false, Ordering::Relaxed);
+    server.await.unwrap();
+}
+
+#[cfg(all(feature = "dns-over-h3", feature = "dns-over-https-rustls"))]
+#[tokio::test]
+async fn test_resolver_h3_fallback_h2() {
+    use std::env;
+
+    use hickory_proto::xfer::Protocol;
+    use hickory_resolver::config::{NameServerConfig, ResolverConfig, ResolverOpts};
+    use hickory_resolver::Resolver;
+
+    subscribe();
+
+    let dns_name = "ns.example.com";
+
+    let server_path = env::var("TDNS_WORKSPACE_ROOT").unwrap_or_else(|_| "../..".to_owned());
+    let ca = read_certs(format!("{}/tests/test-data/ca.pem", server_path)).unwrap();
+    let cert_chain = read_certs(format!("{}/tests/test-data/cert.pem", server_path)).unwrap();
+    let key =
+        PrivateKeyDer::from_pem_file(format!("{server_path}/tests/test-data/cert.key")).unwrap();
+
+    // the server only speaks DNS-over-HTTPS over HTTP/2, nothing is listening for QUIC
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0));
+    let https_listener = TcpListener::bind(&addr).await.unwrap();
+    let ipaddr = https_listener.local_addr().unwrap();
+
+    let mut server = ServerFuture::new(new_catalog());
+    server
+        .register_https_listener(
+            https_listener,
+            Duration::from_secs(30),
+            (cert_chain, key),
+            Some(dns_name.to_string()),
+            "/dns-query".to_string(),
+        )
+        .expect("failed to register HTTPS");
+
+    let mut root_store = RootCertStore::empty();
+    let (_, ignored) = root_store.add_parsable_certificates(ca);
+    assert_eq!(ignored, 0, "bad certificate!");
+    let client_config =
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(root_store)
+            .with_no_client_auth();
+
+    let mut name_server = NameServerConfig::new(ipaddr, Protocol::H3);
+    name_server.tls_dns_name = Some(dns_name.to_string());
+    let mut config = ResolverConfig::new();
+    config.add_name_server(name_server);
+    config.set_tls_client_config(Arc::new(client_config));
+
+    let mut options = ResolverOpts::default();
+    options.prefer_h3_fallback_h2 = true;
+    options.timeout = Duration::from_secs(1);
+
+    let resolver = Resolver::tokio(config, options);
+    let lookup = resolver
+        .ipv4_lookup("www.example.com.")
+        .await
+        .expect("lookup over h2 failed");
+    assert_eq!(
+        lookup.iter().next().map(|a| a.0),
+        Some(Ipv4Addr::new(93, 184, 215, 14))
+    );
+
+    server.shutdown_gracefully().await.unwrap();
+}
+
+#[cfg(feature = "dns-over-quic")]
+#[tokio::test]
+async fn test_server_www_quic() {
+    use std::env;
+
+    use hickory_proto::quic::QuicClientStream;
+
+    subscribe();
+
+    let dns_name = "ns.example.com";
+
+    let server_path = env::var("TDNS_WORKSPACE_ROOT").unwrap_or_else(|_| "../..".to_owned());
+    let ca = read_certs(format!("{server_path}/tests/test-data/ca.pem")).unwrap();
+    let cert_chain = read_certs(format!("{server_path}/tests/test-data/cert.pem")).unwrap();
+    let key =
+        PrivateKeyDer::from_pem_file(format!("{server_path}/tests/test-data/cert.key")).unwrap();
+
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0));
+    let quic_socket = UdpSocket::bind(&addr).await.unwrap();
+    let ipaddr = quic_socket.local_addr().unwrap();
+
+    let mut server = ServerFuture::new(new_catalog());
+    server
+        .register_quic_listener(
+            quic_socket,
+            Duration::from_secs(30),
+            (cert_chain, key),
+            Some(dns_name.to_string()),
+        )
+        .expect("failed to register QUIC");
+
+    let mut root_store = RootCertStore::empty();
+    let (_, ignored) = root_store.add_parsable_certificates(ca);
+    assert_eq!(ignored, 0, "bad certificate!");
+    let client_config =
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(root_store)
+            .with_no_client_auth();
+
+    let mut builder = QuicClientStream::builder();
+    builder.crypto_config(client_config);
+    let (client, driver) = Client::connect(builder.build(ipaddr, dns_name.to_string()))
+        .await
+        .expect("failed to connect");
+    tokio::spawn(driver);
+
+    // each query is sent on its own stream of the connection
+    client_thread_www(async { client.clone() }).await;
+    client_thread_www(async { client }).await;
+
+    server.shutdown_gracefully().await.unwrap();
+}
+
 async fn lazy_udp_client(addr: SocketAddr) -> Client {
     let conn = UdpClientStream::builder(addr, TokioRuntimeProvider::default()).build();
     let (client, driver) = Client::connect(conn).await.expect("failed to connect");
@@ -0,0 +1,152 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{copy_bidirectional, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+
+use hickory_integration::example_authority::create_example;
+use hickory_proto::rr::rdata::A;
+use hickory_proto::rr::RecordType;
+use hickory_proto::runtime::TokioRuntimeProvider;
+use hickory_proto::tcp::Socks5Provider;
+use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use hickory_resolver::name_server::GenericConnector;
+use hickory_resolver::{ResolveError, Resolver};
+use hickory_server::authority::{Authority, Catalog};
+use hickory_server::ServerFuture;
+use test_support::subscribe;
+
+const USERNAME: &str = "hickory";
+const PASSWORD: &str = "secret";
+
+/// A SOCKS5 proxy requiring the username/password authentication, and recording the addresses
+/// of the CONNECT requests
+async fn socks5_stub() -> (SocketAddr, Arc<Mutex<Vec<SocketAddr>>>) {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let connected = Arc::new(Mutex::new(Vec::new()));
+
+    let connected2 = connected.clone();
+    tokio::spawn(async move {
+        loop {
+            let (client, _) = listener.accept().await.unwrap();
+            tokio::spawn(proxy(client, connected2.clone()));
+        }
+    });
+
+    (addr, connected)
+}
+
+async fn proxy(mut client: TcpStream, connected: Arc<Mutex<Vec<SocketAddr>>>) {
+    // greeting, with the username/password method
+    let mut header = [0; 2];
+    client.read_exact(&mut header).await.unwrap();
+    let mut methods = vec![0; usize::from(header[1])];
+    client.read_exact(&mut methods).await.unwrap();
+    if header[0] != 5 || !methods.contains(&2) {
+        client.write_all(&[5, 0xFF]).await.unwrap();
+        return;
+    }
+    client.write_all(&[5, 2]).await.unwrap();
+
+    // username/password authentication, RFC 1929
+    let mut header = [0; 2];
+    client.read_exact(&mut header).await.unwrap();
+    let mut username = vec![0; usize::from(header[1])];
+    client.read_exact(&mut username).await.unwrap();
+    let mut len = [0; 1];
+    client.read_exact(&mut len).await.unwrap();
+    let mut password = vec![0; usize::from(len[0])];
+    client.read_exact(&mut password).await.unwrap();
+    if username != USERNAME.as_bytes() || password != PASSWORD.as_bytes() {
+        client.write_all(&[1, 1]).await.unwrap();
+        return;
+    }
+    client.write_all(&[1, 0]).await.unwrap();
+
+    // CONNECT request to an IPv4 address
+    let mut request = [0; 10];
+    client.read_exact(&mut request).await.unwrap();
+    assert_eq!(request[..4], [5, 1, 0, 1]);
+    let target = SocketAddr::from((
+        [request[4], request[5], request[6], request[7]],
+        u16::from_be_bytes([request[8], request[9]]),
+    ));
+    connected.lock().await.push(target);
+
+    let mut server = TcpStream::connect(target).await.unwrap();
+    client
+        .write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0])
+        .await
+        .unwrap();
+    let _ = copy_bidirectional(&mut client, &mut server).await;
+}
+
+/// A server of the example.com zone, only listening over TCP
+async fn tcp_server() -> (SocketAddr, ServerFuture<Catalog>) {
+    let example = create_example();
+    let mut catalog = Catalog::new();
+    catalog.upsert(example.origin().clone(), vec![Arc::new(example)]);
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let mut server = ServerFuture::new(catalog);
+    server.register_listener(listener, Duration::from_secs(5));
+    (addr, server)
+}
+
+async fn lookup_through(
+    server: SocketAddr,
+    provider: Socks5Provider<TokioRuntimeProvider>,
+) -> Result<Vec<A>, ResolveError> {
+    // UDP fails through the proxy, the query is retried over TCP
+    let config = ResolverConfig::from_parts(
+        None,
+        vec![],
+        NameServerConfigGroup::from_ips_clear(&[server.ip()], server.port(), true),
+    );
+    let mut options = ResolverOpts::default();
+    options.try_tcp_on_error = true;
+    let resolver = Resolver::new(config, options, GenericConnector::new(provider));
+
+    let lookup = resolver.lookup("www.example.com.", RecordType::A).await?;
+    Ok(lookup
+        .iter()
+        .filter_map(|rdata| rdata.as_a().copied())
+        .collect())
+}
+
+#[tokio::test]
+async fn test_query_through_socks5_proxy() {
+    subscribe();
+
+    let (server_addr, _server) = tcp_server().await;
+    let (proxy_addr, connected) = socks5_stub().await;
+
+    let provider = Socks5Provider::new(TokioRuntimeProvider::default(), proxy_addr)
+        .with_credentials(USERNAME, PASSWORD);
+    let addresses = lookup_through(server_addr, provider).await.unwrap();
+
+    assert_eq!(addresses, vec![A::new(93, 184, 215, 14)]);
+    assert_eq!(*connected.lock().await, vec![server_addr]);
+}
+
+#[tokio::test]
+async fn test_socks5_proxy_authentication() {
+    subscribe();
+
+    let (server_addr, _server) = tcp_server().await;
+    let (proxy_addr, connected) = socks5_stub().await;
+
+    let provider = Socks5Provider::new(TokioRuntimeProvider::default(), proxy_addr)
+        .with_credentials(USERNAME, "wrong");
+    assert!(lookup_through(server_addr, provider).await.is_err());
+
+    // the proxy requires the credentials
+    let provider = Socks5Provider::new(TokioRuntimeProvider::default(), proxy_addr);
+    assert!(lookup_through(server_addr, provider).await.is_err());
+
+    assert!(connected.lock().await.is_empty());
+}
@@ -1089,3 +1089,99 @@ async fn test_refused_axfr() {
     // just update this if the count goes up in the authority
     assert!(result.unwrap_err().is_refused());
 }
+
+#[tokio::test]
+async fn test_ixfr() {
+    let mut authority = create_example();
+    authority.set_allow_axfr(true);
+    authority.enable_ixfr_journal(10);
+
+    let old_serial = authority.serial().await;
+    let old_soa = authority
+        .soa()
+        .await
+        .unwrap()
+        .iter()
+        .next()
+        .unwrap()
+        .clone();
+
+    let new_name = Name::from_str("new.example.com.").unwrap();
+    let add_record = Record::from_rdata(new_name, 86400, RData::A(A::new(93, 184, 216, 24)))
+        .set_dns_class(DNSClass::IN)
+        .clone();
+    assert!(authority
+        .update_records(std::slice::from_ref(&add_record), true)
+        .await
+        .expect("update failed"));
+    assert_eq!(old_serial + 1, authority.serial().await);
+
+    let query = LowerQuery::from(Query::query(
+        Name::from_str("example.com.").unwrap(),
+        RecordType::IXFR,
+    ));
+    let request_info = RequestInfo::new(
+        SocketAddr::from((Ipv4Addr::LOCALHOST, 53)),
+        Protocol::Udp,
+        TEST_HEADER,
+        &query,
+    );
+
+    let result = authority
+        .ixfr(request_info, old_serial, LookupOptions::default())
+        .await
+        .unwrap();
+    let records = result.iter().collect::<Vec<_>>();
+
+    let new_soa = records[0];
+    assert_eq!(new_soa.data().as_soa().unwrap().serial(), old_serial + 1);
+    assert_eq!(
+        records,
+        vec![new_soa, &old_soa, new_soa, &add_record, new_soa]
+    );
+
+    // already up to date
+    let request_info = RequestInfo::new(
+        SocketAddr::from((Ipv4Addr::LOCALHOST, 53)),
+        Protocol::Udp,
+        TEST_HEADER,
+        &query,
+    );
+    let result = authority
+        .ixfr(request_info, old_serial + 1, LookupOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(result.iter().collect::<Vec<_>>(), vec![new_soa]);
+}
+
+#[tokio::test]
+async fn test_ixfr_unknown_serial() {
+    let mut authority = create_example();
+    authority.set_allow_axfr(true);
+    authority.enable_ixfr_journal(10);
+
+    let old_serial = authority.serial().await;
+
+    let query = LowerQuery::from(Query::query(
+        Name::from_str("example.com.").unwrap(),
+        RecordType::IXFR,
+    ));
+    let request_info = RequestInfo::new(
+        SocketAddr::from((Ipv4Addr::LOCALHOST, 53)),
+        Protocol::Udp,
+        TEST_HEADER,
+        &query,
+    );
+
+    // the journal starts at the current serial, older versions get the full zone
+    let result = authority
+        .ixfr(request_info, old_serial - 1, LookupOptions::default())
+        .await
+        .unwrap();
+
+    // same as the AXFR, see test_axfr
+    let records = result.iter().collect::<Vec<_>>();
+    assert_eq!(records.len(), 12);
+    assert_eq!(records.first().unwrap().record_type(), RecordType::SOA);
+    assert_eq!(records.last().unwrap().record_type(), RecordType::SOA);
+}